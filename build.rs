@@ -0,0 +1,19 @@
+//! Records the git commit of the checkout for run manifests
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    if !Path::new(".git").is_dir() {
+        return;
+    }
+    for path in [".git/HEAD", ".git/refs/heads", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    let output = Command::new("git").args(["rev-parse", "HEAD"]).output();
+    if let Some(output) = output.ok().filter(|output| output.status.success()) {
+        let commit = String::from_utf8_lossy(&output.stdout);
+        println!("cargo:rustc-env=SWE_GIT_COMMIT={}", commit.trim());
+    }
+}
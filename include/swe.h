@@ -0,0 +1,143 @@
+#ifndef SWE_H
+#define SWE_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define SWE_OK 0
+
+// A handle or array pointer was null
+#define SWE_ERROR_NULL -1
+
+// An array length does not match the mesh
+#define SWE_ERROR_SIZE -2
+
+// A parameter is out of range
+#define SWE_ERROR_INVALID -3
+
+// The solver panicked; the handle should be destroyed
+#define SWE_ERROR_PANIC -4
+
+// Opaque solver handle
+typedef struct SweSolver SweSolver;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Create a solver on a flat nx × ny node grid of `width` × `height` metres
+// (2 (nx - 1)(ny - 1) triangles) without friction and with dry cells.
+// Returns null if a parameter is out of range.
+SweSolver *swe_create_solver(size_t nx, size_t ny, double width, double height, double cfl);
+
+// Release a solver (null is ignored)
+//
+// # Safety
+// `solver` must be null or a handle from `swe_create_solver` that was not
+// destroyed before.
+void swe_destroy_solver(SweSolver *solver);
+
+// Number of cells (length of the state arrays); 0 for a null handle
+//
+// # Safety
+// `solver` must be null or a live handle.
+size_t swe_num_cells(const SweSolver *solver);
+
+// Number of mesh nodes (length of the bed array); 0 for a null handle
+//
+// # Safety
+// `solver` must be null or a live handle.
+size_t swe_num_nodes(const SweSolver *solver);
+
+// Simulated time in seconds; NaN for a null handle
+//
+// # Safety
+// `solver` must be null or a live handle.
+double swe_get_time(const SweSolver *solver);
+
+// Last time step in seconds; NaN for a null handle
+//
+// # Safety
+// `solver` must be null or a live handle.
+double swe_get_dt(const SweSolver *solver);
+
+// Total water volume in m³; NaN for a null handle
+//
+// # Safety
+// `solver` must be null or a live handle.
+double swe_total_mass(const SweSolver *solver);
+
+// Copy the cell centroids into `x` and `y` (`len` = number of cells)
+//
+// # Safety
+// `solver` must be null or a live handle; `x` and `y` must be null or
+// point to `len` writable doubles.
+int32_t swe_get_cell_centroids(const SweSolver *solver, double *x, double *y, size_t len);
+
+// Set the bed elevation of every node (m); call before the first step
+//
+// # Safety
+// `solver` must be null or a live handle; `z` must be null or point to
+// `len` doubles.
+int32_t swe_set_bed(SweSolver *solver, const double *z, size_t len);
+
+// Use Manning friction with coefficient `n` (s/m^(1/3)); 0 disables friction
+//
+// # Safety
+// `solver` must be null or a live handle.
+int32_t swe_set_manning(SweSolver *solver, double n);
+
+// Set the state of every cell: depth `h` (m) and momenta `hu`, `hv`
+// (m²/s); null momenta are taken as zero
+//
+// # Safety
+// `solver` must be null or a live handle; `h` must be null or point to
+// `len` doubles, `hu` and `hv` likewise.
+int32_t swe_set_state(SweSolver *solver,
+                      const double *h,
+                      const double *hu,
+                      const double *hv,
+                      size_t len);
+
+// Copy the depth of every cell (m) into `out`
+//
+// # Safety
+// `solver` must be null or a live handle; `out` must be null or point to
+// `len` writable doubles.
+int32_t swe_get_h(const SweSolver *solver, double *out, size_t len);
+
+// Copy the x-momentum of every cell (m²/s) into `out`
+//
+// # Safety
+// As for `swe_get_h`.
+int32_t swe_get_hu(const SweSolver *solver, double *out, size_t len);
+
+// Copy the y-momentum of every cell (m²/s) into `out`
+//
+// # Safety
+// As for `swe_get_h`.
+int32_t swe_get_hv(const SweSolver *solver, double *out, size_t len);
+
+// Advance by one CFL-limited time step
+//
+// # Safety
+// `solver` must be null or a live handle.
+int32_t swe_step(SweSolver *solver);
+
+// Step until the simulated time reaches `time` (the last step may overshoot
+// it, as in the command-line program)
+//
+// # Safety
+// `solver` must be null or a live handle.
+int32_t swe_advance(SweSolver *solver, double time);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SWE_H */
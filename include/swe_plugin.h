@@ -0,0 +1,54 @@
+#ifndef SWE_PLUGIN_H
+#define SWE_PLUGIN_H
+
+/* ABI of step plugins loaded with --plugin (src/plugin.rs). */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// View of the solver passed to the step functions. Every array holds
+// `cells` values; h, hu and hv may be changed in place.
+typedef struct SwePluginState {
+  // Simulation time (s)
+  double time;
+  // Last time step (s)
+  double dt;
+  // Length of every array
+  size_t cells;
+  // Depth (m)
+  double *h;
+  // x-momentum (m^2/s)
+  double *hu;
+  // y-momentum (m^2/s)
+  double *hv;
+  // Bed elevation of each cell (m)
+  const double *z_bed;
+  // Cell centroids
+  const double *x;
+  const double *y;
+  // Water-holding cell area (m^2)
+  const double *area;
+  // Set by swe_plugin_init
+  void *user;
+} SwePluginState;
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Optional: called once with the --plugin-config string; nonzero fails the run
+int32_t swe_plugin_init(const char *config, void **user);
+
+// Called before and after every time step (at least one is required);
+// nonzero stops the run
+int32_t swe_plugin_pre_step(SwePluginState *state);
+int32_t swe_plugin_post_step(SwePluginState *state);
+
+// Optional: called once at the end of the run
+void swe_plugin_finish(void *user);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SWE_PLUGIN_H */
@@ -0,0 +1,113 @@
+/// ndarray interop
+/// The state fields are exposed as one-dimensional views over the cell
+/// vectors without copying. Cell fields can be sampled onto a regular
+/// `RasterGrid` as `Array2<f64>` (row 0 is the northern edge, NaN outside
+/// the mesh), the layout image and GIS tools expect. `GridSampler` keeps
+/// the pixel-to-triangle lookup so that several fields or time steps can be
+/// sampled cheaply.
+use crate::mesh::TriangularMesh;
+use crate::raster::{self, RasterGrid};
+use crate::solver::State;
+use ndarray::{Array2, ArrayView1, ArrayViewMut1};
+
+impl State {
+    /// Depth per cell
+    pub fn h_view(&self) -> ArrayView1<'_, f64> {
+        ArrayView1::from(&self.h[..])
+    }
+
+    /// x-momentum per cell
+    pub fn hu_view(&self) -> ArrayView1<'_, f64> {
+        ArrayView1::from(&self.hu[..])
+    }
+
+    /// y-momentum per cell
+    pub fn hv_view(&self) -> ArrayView1<'_, f64> {
+        ArrayView1::from(&self.hv[..])
+    }
+
+    /// Mutable views of (h, hu, hv)
+    /// After changing the state of a solver outside a step, call
+    /// `reset_active_set` on it.
+    pub fn views_mut(
+        &mut self,
+    ) -> (
+        ArrayViewMut1<'_, f64>,
+        ArrayViewMut1<'_, f64>,
+        ArrayViewMut1<'_, f64>,
+    ) {
+        (
+            ArrayViewMut1::from(&mut self.h[..]),
+            ArrayViewMut1::from(&mut self.hu[..]),
+            ArrayViewMut1::from(&mut self.hv[..]),
+        )
+    }
+}
+
+/// Samples cell fields onto a fixed grid
+pub struct GridSampler {
+    pub grid: RasterGrid,
+    triangles: Vec<Option<usize>>, // Containing triangle per pixel (row-major)
+}
+
+impl GridSampler {
+    pub fn new(mesh: &TriangularMesh, grid: RasterGrid) -> Self {
+        GridSampler {
+            triangles: raster::pixel_triangles(mesh, &grid),
+            grid,
+        }
+    }
+
+    /// Value of the containing cell at every pixel centre (nrows × ncols)
+    pub fn sample(&self, values: &[f64]) -> Array2<f64> {
+        let data = self
+            .triangles
+            .iter()
+            .map(|tri| tri.map_or(f64::NAN, |i| values[i]))
+            .collect();
+        Array2::from_shape_vec((self.grid.nrows, self.grid.ncols), data)
+            .expect("one value per pixel")
+    }
+}
+
+/// Sample a cell field onto `grid` (see `GridSampler`)
+pub fn sample_grid(mesh: &TriangularMesh, values: &[f64], grid: &RasterGrid) -> Array2<f64> {
+    GridSampler::new(mesh, *grid).sample(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+
+    #[test]
+    fn test_views_share_the_state() {
+        let mut state = State::new(4);
+        {
+            let (mut h, mut hu, _) = state.views_mut();
+            h.fill(2.0);
+            hu[1] = 3.0;
+        }
+        assert_eq!(state.h, vec![2.0; 4]);
+        assert_eq!(state.h_view().sum(), 8.0);
+        assert_eq!(state.hu_view()[1], 3.0);
+        assert_eq!(state.hv_view().as_ptr(), state.hv.as_ptr());
+    }
+
+    #[test]
+    fn test_grid_sampling_orientation() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 4.0, 4.0, TopographyType::Flat);
+        let y: Vec<f64> = mesh.triangles.iter().map(|t| t.centroid.1).collect();
+        let grid = RasterGrid {
+            x_min: -1.0,
+            ..RasterGrid::covering(&mesh, Some(0.5))
+        };
+        let sampled = sample_grid(&mesh, &y, &grid);
+
+        assert_eq!(sampled.dim(), (8, 8));
+        // First column lies west of the mesh
+        assert!(sampled.column(0).iter().all(|v| v.is_nan()));
+        // Row 0 is the northern edge
+        assert!(sampled[[0, 4]] > 3.0 && sampled[[7, 4]] < 1.0);
+    }
+}
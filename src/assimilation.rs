@@ -0,0 +1,327 @@
+/// Ensemble Kalman filter data assimilation
+/// An ensemble of solver states is advanced to each observation time and
+/// updated with the stochastic EnKF analysis (perturbed observations):
+///
+///   x_m <- x_m + A (HA)^T (HA (HA)^T + (N-1) R)^-1 (y + e_m - H x_m)
+///
+/// where A holds the state anomalies of the N members, HA their anomalies in
+/// observation space and R = s^2 I the observation error covariance. The
+/// gain is never formed; only the small system in observation space is
+/// solved. The state vector is (h, hu, hv) in every cell, so momentum is
+/// corrected through its ensemble correlation with the observed levels.
+use crate::ensemble::SplitMix64;
+use crate::simulation::Simulation;
+use crate::solver::{ShallowWaterSolver, State};
+use rayon::prelude::*;
+
+/// Observation operator: water surface elevation h + z_b in gauge cells
+#[derive(Debug, Clone)]
+pub struct GaugeOperator {
+    pub cells: Vec<usize>,
+}
+
+impl GaugeOperator {
+    pub fn apply(&self, solver: &ShallowWaterSolver) -> Vec<f64> {
+        self.cells
+            .iter()
+            .map(|&i| solver.state.h[i] + solver.mesh.triangles[i].z_bed)
+            .collect()
+    }
+}
+
+/// Ensemble statistics at the gauges before and after one analysis
+#[derive(Debug, Clone)]
+pub struct AnalysisReport {
+    pub forecast_mean: Vec<f64>,
+    pub forecast_spread: Vec<f64>, // Ensemble standard deviation
+    pub analysis_mean: Vec<f64>,
+    pub analysis_spread: Vec<f64>,
+}
+
+pub struct EnsembleKalmanFilter {
+    pub members: Vec<ShallowWaterSolver>,
+    pub observation_std: f64, // Observation error standard deviation (m)
+    rng: SplitMix64,
+}
+
+impl EnsembleKalmanFilter {
+    pub fn new(members: Vec<ShallowWaterSolver>, observation_std: f64, seed: u64) -> Self {
+        EnsembleKalmanFilter {
+            members,
+            observation_std,
+            rng: SplitMix64::new(seed),
+        }
+    }
+
+    /// Add spatially smooth random errors with standard deviation `std` (m)
+    /// to the water level of wet cells. White noise is smoothed by
+    /// `smoothing` neighbour-averaging passes, which sets the correlation
+    /// length to a few cells per pass, and rescaled to `std`.
+    pub fn perturb_levels(&mut self, std: f64, smoothing: usize) {
+        for member in &mut self.members {
+            let mesh = &member.mesh;
+            let mut noise: Vec<f64> = (0..mesh.triangles.len())
+                .map(|_| self.rng.next_normal())
+                .collect();
+            for _ in 0..smoothing {
+                noise = mesh
+                    .triangles
+                    .iter()
+                    .enumerate()
+                    .map(|(i, t)| {
+                        let (sum, count) = t
+                            .neighbors
+                            .iter()
+                            .flatten()
+                            .fold((noise[i], 1.0), |(s, c), &j| (s + noise[j], c + 1.0));
+                        sum / count
+                    })
+                    .collect();
+            }
+            let rms = (noise.iter().map(|e| e * e).sum::<f64>() / noise.len().max(1) as f64).sqrt();
+            let scale = if rms > 0.0 { std / rms } else { 0.0 };
+
+            let dry = member.constants.dry_tolerance;
+            for (h, e) in member.state.h.iter_mut().zip(&noise) {
+                if *h > dry {
+                    *h = (*h + scale * e).max(0.0);
+                }
+            }
+        }
+    }
+
+    /// Advance every member until its clock reaches `time`
+    pub fn advance_to(&mut self, time: f64) {
+        self.members.par_iter_mut().for_each(|member| {
+            Simulation::until(member, time)
+                .run()
+                .expect("members have no couplings to fail");
+        });
+    }
+
+    /// EnKF update of all members with the observed values
+    pub fn analyze(&mut self, operator: &GaugeOperator, observed: &[f64]) -> AnalysisReport {
+        let n_members = self.members.len();
+        let p = observed.len();
+        let predicted: Vec<Vec<f64>> = self.members.iter().map(|m| operator.apply(m)).collect();
+        let (forecast_mean, forecast_spread) = mean_and_spread(&predicted);
+        if n_members < 2 || p == 0 {
+            return AnalysisReport {
+                analysis_mean: forecast_mean.clone(),
+                analysis_spread: forecast_spread.clone(),
+                forecast_mean,
+                forecast_spread,
+            };
+        }
+        let scale = 1.0 / (n_members - 1) as f64;
+
+        // Anomalies in observation space and their covariance plus R
+        let hx_anomalies: Vec<Vec<f64>> = predicted
+            .iter()
+            .map(|hx| hx.iter().zip(&forecast_mean).map(|(v, m)| v - m).collect())
+            .collect();
+        let mut covariance = vec![vec![0.0; p]; p];
+        for anomaly in &hx_anomalies {
+            for a in 0..p {
+                for b in 0..p {
+                    covariance[a][b] += scale * anomaly[a] * anomaly[b];
+                }
+            }
+        }
+        for (a, row) in covariance.iter_mut().enumerate() {
+            row[a] += self.observation_std * self.observation_std;
+        }
+
+        // Innovations against perturbed observations, solved for all members
+        let mut innovations: Vec<Vec<f64>> = predicted
+            .iter()
+            .map(|hx| {
+                (0..p)
+                    .map(|a| observed[a] + self.observation_std * self.rng.next_normal() - hx[a])
+                    .collect()
+            })
+            .collect();
+        solve_dense(covariance, &mut innovations);
+
+        // Weights of the state anomalies in each member's increment
+        let weights: Vec<Vec<f64>> = innovations
+            .iter()
+            .map(|w| {
+                hx_anomalies
+                    .iter()
+                    .map(|anomaly| scale * anomaly.iter().zip(w).map(|(a, b)| a * b).sum::<f64>())
+                    .collect()
+            })
+            .collect();
+
+        let mean = self.mean_state();
+        let anomalies: Vec<State> = self
+            .members
+            .iter()
+            .map(|m| State {
+                h: m.state.h.iter().zip(&mean.h).map(|(v, a)| v - a).collect(),
+                hu: m
+                    .state
+                    .hu
+                    .iter()
+                    .zip(&mean.hu)
+                    .map(|(v, a)| v - a)
+                    .collect(),
+                hv: m
+                    .state
+                    .hv
+                    .iter()
+                    .zip(&mean.hv)
+                    .map(|(v, a)| v - a)
+                    .collect(),
+            })
+            .collect();
+
+        self.members
+            .par_iter_mut()
+            .zip(&weights)
+            .for_each(|(member, weights)| {
+                for (anomaly, &w) in anomalies.iter().zip(weights) {
+                    for (field, delta) in [
+                        (&mut member.state.h, &anomaly.h),
+                        (&mut member.state.hu, &anomaly.hu),
+                        (&mut member.state.hv, &anomaly.hv),
+                    ] {
+                        for (v, d) in field.iter_mut().zip(delta) {
+                            *v += w * d;
+                        }
+                    }
+                }
+                member.reset_active_set();
+                // Keep the updated state physical
+                let dry = member.constants.dry_tolerance;
+                let state = &mut member.state;
+                for i in 0..state.h.len() {
+                    if state.h[i] <= dry {
+                        state.h[i] = state.h[i].max(0.0);
+                        state.hu[i] = 0.0;
+                        state.hv[i] = 0.0;
+                    }
+                }
+            });
+
+        let updated: Vec<Vec<f64>> = self.members.iter().map(|m| operator.apply(m)).collect();
+        let (analysis_mean, analysis_spread) = mean_and_spread(&updated);
+        AnalysisReport {
+            forecast_mean,
+            forecast_spread,
+            analysis_mean,
+            analysis_spread,
+        }
+    }
+
+    /// Ensemble mean of (h, hu, hv)
+    pub fn mean_state(&self) -> State {
+        let n = self.members[0].state.h.len();
+        let mut mean = State::new(n);
+        let weight = 1.0 / self.members.len() as f64;
+        for member in &self.members {
+            for i in 0..n {
+                mean.h[i] += weight * member.state.h[i];
+                mean.hu[i] += weight * member.state.hu[i];
+                mean.hv[i] += weight * member.state.hv[i];
+            }
+        }
+        mean
+    }
+}
+
+/// Per-component mean and sample standard deviation over members
+fn mean_and_spread(values: &[Vec<f64>]) -> (Vec<f64>, Vec<f64>) {
+    let p = values.first().map_or(0, |v| v.len());
+    let n = values.len() as f64;
+    let mean: Vec<f64> = (0..p)
+        .map(|a| values.iter().map(|v| v[a]).sum::<f64>() / n)
+        .collect();
+    let spread = (0..p)
+        .map(|a| {
+            let ss: f64 = values.iter().map(|v| (v[a] - mean[a]).powi(2)).sum();
+            if values.len() > 1 {
+                (ss / (n - 1.0)).sqrt()
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    (mean, spread)
+}
+
+/// Solve the symmetric positive definite system `matrix x = b` in place for
+/// every right-hand side (Gaussian elimination with partial pivoting; the
+/// systems have one row per observation)
+fn solve_dense(mut matrix: Vec<Vec<f64>>, rhs: &mut [Vec<f64>]) {
+    let p = matrix.len();
+    for col in 0..p {
+        let pivot = (col..p)
+            .max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))
+            .unwrap();
+        matrix.swap(col, pivot);
+        for b in rhs.iter_mut() {
+            b.swap(col, pivot);
+        }
+        let (upper, lower) = matrix.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (offset, row) in lower.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            for (a, b) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *a -= factor * b;
+            }
+            for b in rhs.iter_mut() {
+                b[col + 1 + offset] -= factor * b[col];
+            }
+        }
+    }
+    for b in rhs.iter_mut() {
+        for row in (0..p).rev() {
+            let sum: f64 = (row + 1..p).map(|k| matrix[row][k] * b[k]).sum();
+            b[row] = (b[row] - sum) / matrix[row][row];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_solve_dense() {
+        let matrix = vec![vec![4.0, 1.0], vec![1.0, 3.0]];
+        let mut rhs = vec![vec![1.0, 2.0]];
+        solve_dense(matrix, &mut rhs);
+        assert!((rhs[0][0] - 1.0 / 11.0).abs() < 1e-12);
+        assert!((rhs[0][1] - 7.0 / 11.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_analysis_moves_ensemble_towards_observation() {
+        let mesh = TriangularMesh::new_rectangular(6, 6, 10.0, 10.0, TopographyType::Flat);
+        let members: Vec<ShallowWaterSolver> = (0..40)
+            .map(|_| {
+                let mut solver = ShallowWaterSolver::new(mesh.clone(), 0.45, FrictionLaw::None);
+                solver.state.h.fill(1.0);
+                solver
+            })
+            .collect();
+        let mut filter = EnsembleKalmanFilter::new(members, 0.01, 3);
+        filter.perturb_levels(0.2, 2);
+
+        let operator = GaugeOperator { cells: vec![0, 25] };
+        let report = filter.analyze(&operator, &[1.3, 1.3]);
+        for k in 0..2 {
+            assert!((report.analysis_mean[k] - 1.3).abs() < (report.forecast_mean[k] - 1.3).abs());
+            assert!((report.analysis_mean[k] - 1.3).abs() < 0.05);
+            assert!(report.analysis_spread[k] < 0.5 * report.forecast_spread[k]);
+        }
+        assert!(filter
+            .members
+            .iter()
+            .all(|m| m.state.h.iter().all(|&h| h >= 0.0)));
+    }
+}
@@ -0,0 +1,200 @@
+/// Standardized performance benchmarks
+/// Each problem is a dam break on a flat square basin at a fixed resolution,
+/// so throughput can be compared between commits, machines and thread
+/// counts. A measurement times a fixed number of steps after one warm-up
+/// step and reports cell updates per second (cells x steps / wall time)
+/// together with the profiler's per-phase breakdown.
+use crate::mesh::{TopographyType, TriangularMesh};
+use crate::profiler::{Phase, PhaseTimes};
+use crate::solver::ShallowWaterSolver;
+use std::time::Instant;
+
+pub struct Problem {
+    pub name: &'static str,
+    pub nx: usize, // Grid points per side
+}
+
+pub const PROBLEMS: [Problem; 4] = [
+    Problem {
+        name: "tiny",
+        nx: 33,
+    },
+    Problem {
+        name: "small",
+        nx: 101,
+    },
+    Problem {
+        name: "medium",
+        nx: 317,
+    },
+    Problem {
+        name: "large",
+        nx: 1001,
+    },
+];
+
+/// Side length of the benchmark basin (m)
+const SIZE: f64 = 100.0;
+
+/// Parse a comma-separated list of problem names
+pub fn parse_problems(spec: &str) -> Result<Vec<&'static Problem>, String> {
+    spec.split(',')
+        .map(|name| {
+            let name = name.trim();
+            PROBLEMS
+                .iter()
+                .find(|problem| problem.name == name)
+                .ok_or_else(|| {
+                    let names: Vec<&str> = PROBLEMS.iter().map(|problem| problem.name).collect();
+                    format!("unknown problem '{}' (expected {})", name, names.join(", "))
+                })
+        })
+        .collect()
+}
+
+/// Parse "1,2,4" into thread counts; None gives powers of two up to `available`
+pub fn parse_thread_counts(spec: Option<&str>, available: usize) -> Result<Vec<usize>, String> {
+    let Some(spec) = spec else {
+        let mut counts: Vec<usize> = std::iter::successors(Some(1), |n| Some(n * 2))
+            .take_while(|&n| n < available)
+            .collect();
+        counts.push(available.max(1));
+        return Ok(counts);
+    };
+    spec.split(',')
+        .map(|v| match v.trim().parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("invalid thread count '{}'", v.trim())),
+        })
+        .collect()
+}
+
+impl Problem {
+    pub fn mesh(&self) -> TriangularMesh {
+        TriangularMesh::new_rectangular(self.nx, self.nx, SIZE, SIZE, TopographyType::Flat)
+    }
+
+    /// Dam break across the middle of the basin
+    pub fn initialize(&self, solver: &mut ShallowWaterSolver) {
+        solver.set_dam_break(0.5 * SIZE);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub problem: &'static str,
+    pub backend: &'static str,
+    pub threads: usize,
+    pub cells: usize,
+    pub steps: usize,
+    pub seconds: f64,
+    pub phases: Option<PhaseTimes>, // CPU only
+}
+
+impl Measurement {
+    pub fn cell_updates_per_second(&self) -> f64 {
+        self.cells as f64 * self.steps as f64 / self.seconds
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let phases = self.phases.map(|times| {
+            Phase::ALL
+                .iter()
+                .zip(times.seconds)
+                .map(|(phase, s)| (phase.name().replace(' ', "_"), serde_json::json!(s)))
+                .collect::<serde_json::Map<_, _>>()
+        });
+        serde_json::json!({
+            "problem": self.problem,
+            "backend": self.backend,
+            "threads": self.threads,
+            "cells": self.cells,
+            "steps": self.steps,
+            "seconds": self.seconds,
+            "cell_updates_per_second": self.cell_updates_per_second(),
+            "phases": phases,
+        })
+    }
+}
+
+/// Time `steps` steps after one warm-up step; the phase breakdown is
+/// recorded when `profile` is set
+pub fn measure(
+    solver: &mut ShallowWaterSolver,
+    steps: usize,
+    profile: bool,
+) -> (f64, Option<PhaseTimes>) {
+    solver.step();
+    solver.profiler.enabled = profile;
+    let before = solver.profiler.snapshot();
+    let start = Instant::now();
+    for _ in 0..steps {
+        solver.step();
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    let phases = profile.then(|| solver.profiler.snapshot().since(&before));
+    solver.profiler.enabled = false;
+    (seconds, phases)
+}
+
+/// Speed-up of each CPU measurement over the single-thread run of the same
+/// problem, as (problem, threads, speed-up, parallel efficiency)
+pub fn scaling(measurements: &[Measurement]) -> Vec<(&'static str, usize, f64, f64)> {
+    measurements
+        .iter()
+        .filter(|m| m.backend == "cpu")
+        .filter_map(|m| {
+            let serial = measurements
+                .iter()
+                .find(|s| s.backend == "cpu" && s.problem == m.problem && s.threads == 1)?;
+            let speedup = m.cell_updates_per_second() / serial.cell_updates_per_second();
+            Some((m.problem, m.threads, speedup, speedup / m.threads as f64))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_parse_lists() {
+        assert_eq!(parse_thread_counts(None, 6).unwrap(), vec![1, 2, 4, 6]);
+        assert_eq!(parse_thread_counts(None, 4).unwrap(), vec![1, 2, 4]);
+        assert_eq!(parse_thread_counts(Some("1, 3"), 8).unwrap(), vec![1, 3]);
+        assert!(parse_thread_counts(Some("0"), 8).is_err());
+        assert_eq!(parse_problems("tiny,large").unwrap()[1].nx, 1001);
+        assert!(parse_problems("huge").is_err());
+    }
+
+    #[test]
+    fn test_measure_and_scaling() {
+        let problem = &PROBLEMS[0];
+        let mut solver = ShallowWaterSolver::new(problem.mesh(), 0.45, FrictionLaw::None);
+        problem.initialize(&mut solver);
+        let (seconds, phases) = measure(&mut solver, 3, true);
+        let phases = phases.unwrap();
+        assert!(seconds > 0.0);
+        assert!(phases.seconds[Phase::Flux as usize] > 0.0);
+        assert!(!solver.profiler.enabled);
+
+        let run = |threads, seconds| Measurement {
+            problem: "tiny",
+            backend: "cpu",
+            threads,
+            cells: solver.mesh.triangles.len(),
+            steps: 3,
+            seconds,
+            phases: Some(phases),
+        };
+        let measurements = [run(1, 2.0), run(4, 0.8)];
+        let table = scaling(&measurements);
+        assert_eq!(table[1].1, 4);
+        assert!((table[1].2 - 2.5).abs() < 1e-12);
+        assert!((table[1].3 - 0.625).abs() < 1e-12);
+        let json = measurements[1].to_json();
+        assert_eq!(json["cell_updates_per_second"], 2048.0 * 3.0 / 0.8);
+        assert!(json["phases"]["flux"].as_f64().unwrap() > 0.0);
+    }
+}
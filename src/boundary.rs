@@ -0,0 +1,385 @@
+/// Boundary conditions assigned from GeoJSON features
+/// Each feature of a FeatureCollection tags part of the boundary with the
+/// condition in its `"boundary"` property: "wall", "open" (zero-gradient
+/// outflow), "stage" with a water surface `"level"` (m), or "inflow" with a
+/// `"discharge"` into the domain (m^3/s, shared among the feature's edges in
+/// proportion to their length), and optionally a `"name"` by which the
+/// condition can be changed during the run (`TaggedBoundary::set_kind`).
+/// LineString and Polygon geometries and their Multi forms are accepted, in
+/// mesh coordinates (`transform` moves them from another reference system).
+/// A boundary edge belongs to the first feature whose lines (polygon rings included) pass within
+/// the tolerance of its midpoint or whose polygons contain the midpoint;
+/// edges no feature claims stay walls.
+use crate::mesh::{point_in_polygon, Edge, TriangularMesh};
+use crate::solver::{wall_flux, BoundaryClosure, EdgeState, ReflectiveWall, ShallowWaterSolver};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Condition carried by a feature
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoundaryKind {
+    Wall,
+    Open,
+    Stage { level: f64 },      // Water surface elevation (m)
+    Inflow { discharge: f64 }, // Total discharge into the domain (m^3/s)
+}
+
+/// Tagged part of the boundary
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundaryFeature {
+    pub name: Option<String>,
+    pub kind: BoundaryKind,
+    pub lines: Vec<Vec<(f64, f64)>>, // Polylines, including the polygon rings
+    pub polygons: Vec<Vec<(f64, f64)>>, // Polygon exteriors
+}
+
+impl BoundaryFeature {
+    /// Apply the coordinate transformation `f` to every vertex
+    pub fn transform(&mut self, f: impl Fn((f64, f64)) -> (f64, f64)) {
+        for p in self.lines.iter_mut().chain(&mut self.polygons).flatten() {
+            *p = f(*p);
+        }
+    }
+}
+
+/// Condition of one boundary edge
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EdgeCondition {
+    Wall,
+    Open,
+    Stage(f64),  // Surface elevation (m)
+    Inflow(f64), // Discharge per unit length (m^2/s)
+}
+
+/// Boundary closure with a condition per edge
+#[derive(Debug, Clone)]
+pub struct TaggedBoundary {
+    features: Arc<HashMap<[usize; 2], usize>>, // Feature of each tagged edge, by sorted end nodes
+    conditions: Vec<EdgeCondition>,            // Condition of each feature
+    lengths: Vec<f64>,                         // Length of the edges tagged by each feature
+    pub edges: Vec<usize>,                     // Boundary edges tagged by each feature
+}
+
+fn items(value: &Value) -> Result<&Vec<Value>, String> {
+    value
+        .as_array()
+        .ok_or_else(|| "expected an array of coordinates".to_string())
+}
+
+fn positions(value: &Value) -> Result<Vec<(f64, f64)>, String> {
+    items(value)?
+        .iter()
+        .map(|p| match (p[0].as_f64(), p[1].as_f64()) {
+            (Some(x), Some(y)) => Ok((x, y)),
+            _ => Err(format!("invalid position {}", p)),
+        })
+        .collect()
+}
+
+fn parse_kind(properties: &Value) -> Result<BoundaryKind, String> {
+    let number = |key: &str| {
+        properties[key]
+            .as_f64()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| format!("missing the numeric property \"{}\"", key))
+    };
+    match properties["boundary"].as_str() {
+        Some("wall") => Ok(BoundaryKind::Wall),
+        Some("open") => Ok(BoundaryKind::Open),
+        Some("stage") => Ok(BoundaryKind::Stage {
+            level: number("level")?,
+        }),
+        Some("inflow") => Ok(BoundaryKind::Inflow {
+            discharge: number("discharge")?,
+        }),
+        Some(other) => Err(format!(
+            "unknown boundary '{}' (expected wall, open, stage or inflow)",
+            other
+        )),
+        None => Err("missing the \"boundary\" property".to_string()),
+    }
+}
+
+fn parse_feature(feature: &Value) -> Result<BoundaryFeature, String> {
+    let kind = parse_kind(&feature["properties"])?;
+    let geometry = &feature["geometry"];
+    let coordinates = &geometry["coordinates"];
+    let name = match &feature["properties"]["name"] {
+        Value::Null => None,
+        Value::String(name) => Some(name.clone()),
+        other => return Err(format!("feature name {} is not a string", other)),
+    };
+    let mut feature = BoundaryFeature {
+        name,
+        kind,
+        lines: Vec::new(),
+        polygons: Vec::new(),
+    };
+    let mut add_polygon = |rings: &Value| -> Result<(), String> {
+        for (r, ring) in items(rings)?.iter().enumerate() {
+            let ring = positions(ring)?;
+            if r == 0 {
+                feature.polygons.push(ring.clone());
+            }
+            feature.lines.push(ring);
+        }
+        Ok(())
+    };
+    match geometry["type"].as_str() {
+        Some("LineString") => feature.lines.push(positions(coordinates)?),
+        Some("MultiLineString") => {
+            for line in items(coordinates)? {
+                feature.lines.push(positions(line)?);
+            }
+        }
+        Some("Polygon") => add_polygon(coordinates)?,
+        Some("MultiPolygon") => {
+            for polygon in items(coordinates)? {
+                add_polygon(polygon)?;
+            }
+        }
+        Some(other) => return Err(format!("unsupported geometry type {}", other)),
+        None => return Err("missing geometry".to_string()),
+    }
+    if feature.lines.iter().any(|line| line.is_empty()) {
+        return Err("empty geometry".to_string());
+    }
+    Ok(feature)
+}
+
+/// Parse a GeoJSON Feature or FeatureCollection of tagged boundaries
+pub fn parse_boundary_features(text: &str) -> Result<Vec<BoundaryFeature>, String> {
+    let json: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let features: Vec<&Value> = match json["type"].as_str() {
+        Some("FeatureCollection") => items(&json["features"])?.iter().collect(),
+        Some("Feature") => vec![&json],
+        _ => return Err("expected a GeoJSON Feature or FeatureCollection".to_string()),
+    };
+    features
+        .into_iter()
+        .enumerate()
+        .map(|(k, feature)| parse_feature(feature).map_err(|e| format!("feature {}: {}", k + 1, e)))
+        .collect()
+}
+
+pub fn read_boundary_features(path: &str) -> Result<Vec<BoundaryFeature>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse_boundary_features(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Distance from `p` to the polyline `line`
+fn distance_to_line(p: (f64, f64), line: &[(f64, f64)]) -> f64 {
+    let to_segment = |a: (f64, f64), b: (f64, f64)| {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let length2 = dx * dx + dy * dy;
+        let t = if length2 > 0.0 {
+            (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+    };
+    match line {
+        [a] => to_segment(*a, *a),
+        _ => line
+            .windows(2)
+            .map(|w| to_segment(w[0], w[1]))
+            .fold(f64::INFINITY, f64::min),
+    }
+}
+
+impl BoundaryFeature {
+    /// Whether the feature claims a boundary edge with midpoint `p`
+    fn claims(&self, p: (f64, f64), tolerance: f64) -> bool {
+        self.polygons
+            .iter()
+            .any(|polygon| point_in_polygon(p, polygon))
+            || self
+                .lines
+                .iter()
+                .any(|line| distance_to_line(p, line) <= tolerance)
+    }
+}
+
+fn key(nodes: [usize; 2]) -> [usize; 2] {
+    [nodes[0].min(nodes[1]), nodes[0].max(nodes[1])]
+}
+
+/// Condition of the edges of a feature of kind `kind` and total length `length`
+fn edge_condition(kind: BoundaryKind, length: f64) -> EdgeCondition {
+    match kind {
+        BoundaryKind::Wall => EdgeCondition::Wall,
+        BoundaryKind::Open => EdgeCondition::Open,
+        BoundaryKind::Stage { level } => EdgeCondition::Stage(level),
+        BoundaryKind::Inflow { discharge } if length > 0.0 => {
+            EdgeCondition::Inflow(discharge / length)
+        }
+        BoundaryKind::Inflow { .. } => EdgeCondition::Wall,
+    }
+}
+
+impl TaggedBoundary {
+    /// Assign the boundary edges of `mesh` to `features`, claiming edges
+    /// whose midpoints lie within `tolerance` (mesh coordinates) of a
+    /// feature's lines; by default a quarter of the edge's length, so that
+    /// edges that merely touch the end of a line are not claimed
+    pub fn new(
+        mesh: &TriangularMesh,
+        features: &[BoundaryFeature],
+        tolerance: Option<f64>,
+    ) -> Self {
+        let mut claimed: Vec<Vec<usize>> = vec![Vec::new(); features.len()];
+        for (e, edge) in mesh.edges.iter().enumerate() {
+            if edge.right_triangle.is_some() {
+                continue;
+            }
+            let (a, b) = (&mesh.nodes[edge.nodes[0]], &mesh.nodes[edge.nodes[1]]);
+            let midpoint = (0.5 * (a.x + b.x), 0.5 * (a.y + b.y));
+            let tolerance = tolerance.unwrap_or(0.25 * (b.x - a.x).hypot(b.y - a.y));
+            if let Some(k) = features.iter().position(|f| f.claims(midpoint, tolerance)) {
+                claimed[k].push(e);
+            }
+        }
+
+        let mut tagged = HashMap::new();
+        for (k, edges) in claimed.iter().enumerate() {
+            for &e in edges {
+                tagged.insert(key(mesh.edges[e].nodes), k);
+            }
+        }
+        let lengths: Vec<f64> = claimed
+            .iter()
+            .map(|edges| edges.iter().map(|&e| mesh.edges[e].length).sum())
+            .collect();
+        TaggedBoundary {
+            features: Arc::new(tagged),
+            conditions: features
+                .iter()
+                .zip(&lengths)
+                .map(|(feature, &length)| edge_condition(feature.kind, length))
+                .collect(),
+            lengths,
+            edges: claimed.iter().map(Vec::len).collect(),
+        }
+    }
+
+    /// Change the condition of feature `k` (a copy shares the edge tags,
+    /// so a solver can be handed a new closure every step)
+    pub fn set_kind(&mut self, k: usize, kind: BoundaryKind) {
+        self.conditions[k] = edge_condition(kind, self.lengths[k]);
+    }
+}
+
+impl TaggedBoundary {
+    fn condition(&self, edge: &Edge) -> EdgeCondition {
+        self.features
+            .get(&key(edge.nodes))
+            .map_or(EdgeCondition::Wall, |&k| self.conditions[k])
+    }
+}
+
+/// Ghost depth of an inflow of `q` per unit length
+fn inflow_depth(q: f64, inner: &EdgeState, solver: &ShallowWaterSolver) -> f64 {
+    inner.h.max((q * q / solver.constants.gravity).cbrt())
+}
+
+impl BoundaryClosure for TaggedBoundary {
+    fn name(&self) -> &str {
+        "tagged"
+    }
+
+    fn boundary_flux(
+        &self,
+        solver: &ShallowWaterSolver,
+        edge: &Edge,
+        inner: &EdgeState,
+    ) -> Option<(f64, f64, f64)> {
+        let closed = match self.condition(edge) {
+            EdgeCondition::Wall => true,
+            EdgeCondition::Inflow(q) => {
+                inflow_depth(q, inner, solver) < solver.constants.dry_tolerance
+            }
+            EdgeCondition::Open | EdgeCondition::Stage(_) => false,
+        };
+        closed.then(|| wall_flux(inner, edge.normal, &solver.constants))
+    }
+
+    fn ghost(&self, solver: &ShallowWaterSolver, edge: &Edge, inner: &EdgeState) -> EdgeState {
+        match self.condition(edge) {
+            EdgeCondition::Wall => ReflectiveWall.ghost(solver, edge, inner),
+            EdgeCondition::Open => *inner,
+            EdgeCondition::Stage(level) => {
+                let z =
+                    0.5 * (solver.mesh.nodes[edge.nodes[0]].z + solver.mesh.nodes[edge.nodes[1]].z);
+                EdgeState::from_velocity((level - z).max(0.0), inner.u, inner.v)
+            }
+            EdgeCondition::Inflow(q) => {
+                // At least the critical depth of the inflow, with the normal
+                // velocity whose mean with the interior one carries q in
+                let h = inflow_depth(q, inner, solver);
+                if h < solver.constants.dry_tolerance {
+                    return ReflectiveWall.ghost(solver, edge, inner);
+                }
+                let (nx, ny) = edge.normal;
+                let un = inner.u * nx + inner.v * ny;
+                let ghost_un = -2.0 * q / h - un;
+                EdgeState::from_velocity(
+                    h,
+                    inner.u + (ghost_un - un) * nx,
+                    inner.v + (ghost_un - un) * ny,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+    use crate::solver::FrictionLaw;
+
+    const BOUNDARIES: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {"type": "Feature", "properties": {"boundary": "inflow", "discharge": 2.0},
+             "geometry": {"type": "LineString", "coordinates": [[0, -1], [0, 11]]}},
+            {"type": "Feature", "properties": {"boundary": "open"},
+             "geometry": {"type": "Polygon",
+                          "coordinates": [[[19.8, -1], [21, -1], [21, 11], [19.8, 11], [19.8, -1]]]}}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_and_assign_features() {
+        let features = parse_boundary_features(BOUNDARIES).unwrap();
+        assert_eq!(features[0].kind, BoundaryKind::Inflow { discharge: 2.0 });
+        assert_eq!(features[1].polygons.len(), 1);
+
+        let mesh = TriangularMesh::new_rectangular(21, 11, 20.0, 10.0, TopographyType::Flat);
+        let boundary = TaggedBoundary::new(&mesh, &features, None);
+        assert_eq!(boundary.edges, vec![10, 10]);
+
+        let stage = r#"{"type": "Feature", "properties": {"boundary": "stage"},
+                        "geometry": {"type": "LineString", "coordinates": [[0, 0], [1, 0]]}}"#;
+        let error = parse_boundary_features(stage).unwrap_err();
+        assert!(error.contains("level"), "{}", error);
+    }
+
+    #[test]
+    fn test_inflow_adds_its_discharge() {
+        // A basin closed except for 2 m^3/s entering through the west side
+        let mesh = TriangularMesh::new_rectangular(21, 11, 20.0, 10.0, TopographyType::Flat);
+        let features = parse_boundary_features(BOUNDARIES).unwrap();
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.boundary_closure = Box::new(TaggedBoundary::new(&solver.mesh, &features[..1], None));
+        solver.state.h.iter_mut().for_each(|h| *h = 1.0);
+        let volume = solver.compute_total_mass();
+        while solver.time < 2.0 {
+            solver.step();
+        }
+        let added = solver.compute_total_mass() - volume;
+        assert!((added - 2.0 * solver.time).abs() < 1e-9, "{}", added);
+    }
+}
@@ -0,0 +1,505 @@
+/// When the time loop writes outputs
+/// Outputs are due at every multiple of the output interval, optionally
+/// every N steps, and whenever an event trigger fires. Triggers refer to
+/// gauges by name and are checked after every step, so the arrival of a wave
+/// is captured at the step it happens instead of at the next output time.
+/// An `OutputSchedule` lets single output streams and frame fields follow
+/// intervals of their own instead, e.g. velocity frames less often than
+/// depth frames and gauges every step.
+use crate::output::OutputField;
+use crate::solver::ShallowWaterSolver;
+
+/// Event that causes an extra output
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputTrigger {
+    /// Depth at the gauge rises above the threshold (m); fires again after
+    /// it has dropped below
+    Depth { gauge: String, threshold: f64 },
+    /// The gauge cell becomes wet for the first time
+    Wet { gauge: String },
+    /// Any cell becomes wet for the first time
+    Wetting,
+}
+
+impl OutputTrigger {
+    /// Parse "depth:GAUGE>VALUE", "wet:GAUGE" or "wetting"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        let invalid = || {
+            format!(
+                "invalid trigger '{}' (expected depth:GAUGE>VALUE, wet:GAUGE or wetting)",
+                spec
+            )
+        };
+        match spec.split_once(':') {
+            None if spec == "wetting" => Ok(OutputTrigger::Wetting),
+            Some(("depth", condition)) => {
+                let (gauge, threshold) = condition.split_once('>').ok_or_else(invalid)?;
+                Ok(OutputTrigger::Depth {
+                    gauge: gauge.trim().to_string(),
+                    threshold: threshold.trim().parse().map_err(|_| invalid())?,
+                })
+            }
+            Some(("wet", gauge)) if !gauge.trim().is_empty() => Ok(OutputTrigger::Wet {
+                gauge: gauge.trim().to_string(),
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn gauge(&self) -> Option<&str> {
+        match self {
+            OutputTrigger::Depth { gauge, .. } | OutputTrigger::Wet { gauge } => Some(gauge),
+            OutputTrigger::Wetting => None,
+        }
+    }
+}
+
+/// Parse a semicolon-separated list of triggers
+pub fn parse_triggers(spec: &str) -> Result<Vec<OutputTrigger>, String> {
+    spec.split(';')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(OutputTrigger::parse)
+        .collect()
+}
+
+struct ArmedTrigger {
+    trigger: OutputTrigger,
+    cell: usize, // Gauge cell (unused for Wetting)
+    armed: bool,
+}
+
+pub struct OutputCadence {
+    interval: f64,
+    next_time: f64,
+    every_steps: Option<usize>,
+    triggers: Vec<ArmedTrigger>,
+    ever_wet: Vec<bool>, // Only tracked with a Wetting trigger
+}
+
+impl OutputCadence {
+    /// Outputs every `interval` seconds from the solver's current time, every
+    /// `every_steps` steps and on `triggers`, whose gauges are looked up in
+    /// `gauges` (name, cell)
+    pub fn new(
+        solver: &ShallowWaterSolver,
+        interval: f64,
+        every_steps: Option<usize>,
+        triggers: Vec<OutputTrigger>,
+        gauges: &[(String, usize)],
+    ) -> Result<Self, String> {
+        let dry = solver.constants.dry_tolerance;
+        let wet = |cell: usize| solver.state.h[cell] > dry;
+        let triggers = triggers
+            .into_iter()
+            .map(|trigger| {
+                let cell = match trigger.gauge() {
+                    Some(name) => gauges
+                        .iter()
+                        .find(|(gauge, _)| gauge == name)
+                        .map(|&(_, cell)| cell)
+                        .ok_or_else(|| format!("trigger refers to unknown gauge '{}'", name))?,
+                    None => 0,
+                };
+                let armed = match &trigger {
+                    OutputTrigger::Depth { threshold, .. } => solver.state.h[cell] <= *threshold,
+                    OutputTrigger::Wet { .. } => !wet(cell),
+                    OutputTrigger::Wetting => true,
+                };
+                Ok(ArmedTrigger {
+                    trigger,
+                    cell,
+                    armed,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let ever_wet = if triggers.iter().any(|t| t.trigger == OutputTrigger::Wetting) {
+            (0..solver.state.h.len()).map(wet).collect()
+        } else {
+            Vec::new()
+        };
+        Ok(OutputCadence {
+            interval,
+            next_time: solver.time + interval,
+            every_steps: every_steps.filter(|&n| n > 0),
+            triggers,
+            ever_wet,
+        })
+    }
+
+    /// Time of the next output on the interval
+    pub fn next_time(&self) -> f64 {
+        self.next_time
+    }
+
+    /// Whether an output is due after step number `step`; triggers are
+    /// updated, so call this once per step
+    pub fn due(&mut self, solver: &ShallowWaterSolver, step: usize) -> bool {
+        let mut due = false;
+        if solver.time >= self.next_time {
+            self.next_time += self.interval;
+            due = true;
+        }
+        if self.every_steps.is_some_and(|n| step.is_multiple_of(n)) {
+            due = true;
+        }
+
+        let (h, dry) = (&solver.state.h, solver.constants.dry_tolerance);
+        let mut newly_wet = false;
+        for (cell, ever_wet) in self.ever_wet.iter_mut().enumerate() {
+            if !*ever_wet && h[cell] > dry {
+                *ever_wet = true;
+                newly_wet = true;
+            }
+        }
+        for trigger in &mut self.triggers {
+            let fired = match &trigger.trigger {
+                OutputTrigger::Depth { threshold, .. } => {
+                    let above = h[trigger.cell] > *threshold;
+                    let fired = trigger.armed && above;
+                    trigger.armed = !above;
+                    fired
+                }
+                OutputTrigger::Wet { .. } => {
+                    let fired = trigger.armed && h[trigger.cell] > dry;
+                    trigger.armed &= !fired;
+                    fired
+                }
+                OutputTrigger::Wetting => newly_wet,
+            };
+            due |= fired;
+        }
+        due
+    }
+}
+
+/// Output that can be written on a schedule of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Frames, // VTK frames
+    Gauges,
+    Statistics,
+    Profiles,
+    Runup,
+    Nesting,
+    Selafin,
+    Sww,
+    Zarr,
+}
+
+impl OutputStream {
+    pub const ALL: [OutputStream; 9] = [
+        OutputStream::Frames,
+        OutputStream::Gauges,
+        OutputStream::Statistics,
+        OutputStream::Profiles,
+        OutputStream::Runup,
+        OutputStream::Nesting,
+        OutputStream::Selafin,
+        OutputStream::Sww,
+        OutputStream::Zarr,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputStream::Frames => "vtk",
+            OutputStream::Gauges => "gauges",
+            OutputStream::Statistics => "statistics",
+            OutputStream::Profiles => "profiles",
+            OutputStream::Runup => "runup",
+            OutputStream::Nesting => "nest",
+            OutputStream::Selafin => "selafin",
+            OutputStream::Sww => "sww",
+            OutputStream::Zarr => "zarr",
+        }
+    }
+}
+
+/// What a schedule entry applies to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScheduleTarget {
+    Stream(OutputStream),
+    Field(OutputField), // A field of the VTK frames
+}
+
+/// Interval of one output stream or frame field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduleEntry {
+    pub target: ScheduleTarget,
+    pub interval: f64, // Seconds; 0 writes after every step
+}
+
+impl ScheduleEntry {
+    /// Entry for `key`, a stream name or "vtk.FIELD"
+    pub fn new(key: &str, interval: f64) -> Result<Self, String> {
+        if !(interval >= 0.0 && interval.is_finite()) {
+            return Err(format!("interval of '{}' must not be negative", key));
+        }
+        let key = key.trim();
+        let target = match key.split_once('.') {
+            Some((stream, field)) if stream.trim() == "vtk" => {
+                ScheduleTarget::Field(OutputField::parse(field)?)
+            }
+            _ => ScheduleTarget::Stream(
+                OutputStream::ALL
+                    .into_iter()
+                    .find(|stream| stream.name() == key)
+                    .ok_or_else(|| {
+                        let names: Vec<&str> = OutputStream::ALL.iter().map(|s| s.name()).collect();
+                        format!(
+                            "unknown output '{}' (expected {} or vtk.FIELD)",
+                            key,
+                            names.join(", ")
+                        )
+                    })?,
+            ),
+        };
+        Ok(ScheduleEntry { target, interval })
+    }
+
+    /// Name of the stream or "vtk.FIELD"
+    pub fn key(&self) -> String {
+        match self.target {
+            ScheduleTarget::Stream(stream) => stream.name().to_string(),
+            ScheduleTarget::Field(field) => format!("vtk.{}", field.name()),
+        }
+    }
+
+    /// Parse "STREAM=SECONDS" or "vtk.FIELD=SECONDS"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (key, interval) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("invalid schedule '{}' (expected OUTPUT=SECONDS)", spec))?;
+        let interval = interval
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid interval in '{}'", spec))?;
+        Self::new(key, interval)
+    }
+}
+
+/// Serialized as "KEY=SECONDS", as parsed
+impl serde::Serialize for ScheduleEntry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{}={}", self.key(), self.interval))
+    }
+}
+
+struct Timer {
+    interval: f64,
+    next_time: f64,
+}
+
+impl Timer {
+    fn due(&mut self, time: f64) -> bool {
+        if time < self.next_time {
+            return false;
+        }
+        self.next_time += self.interval;
+        true
+    }
+}
+
+/// Streams and frame fields written on intervals of their own; the others
+/// follow the common cadence (and fields without an interval the frames)
+pub struct OutputSchedule {
+    streams: Vec<(OutputStream, Timer)>,
+    fields: Vec<(OutputField, Timer)>,
+    due_streams: Vec<OutputStream>,
+    due_fields: Vec<OutputField>,
+}
+
+impl OutputSchedule {
+    /// Schedule of `entries` from `time`; a later entry for the same stream
+    /// or field replaces an earlier one
+    pub fn new(time: f64, entries: &[ScheduleEntry]) -> Self {
+        let mut schedule = OutputSchedule {
+            streams: Vec::new(),
+            fields: Vec::new(),
+            due_streams: Vec::new(),
+            due_fields: Vec::new(),
+        };
+        for entry in entries {
+            let timer = Timer {
+                interval: entry.interval,
+                next_time: time + entry.interval,
+            };
+            match entry.target {
+                ScheduleTarget::Stream(stream) => {
+                    schedule.streams.retain(|(s, _)| *s != stream);
+                    schedule.streams.push((stream, timer));
+                }
+                ScheduleTarget::Field(field) => {
+                    schedule.fields.retain(|(f, _)| *f != field);
+                    schedule.fields.push((field, timer));
+                }
+            }
+        }
+        schedule
+    }
+
+    /// Work out what is due after a step at `time`, given whether the common
+    /// cadence is due; `force` makes everything due (e.g. before stopping).
+    /// Returns whether anything is due; call this once per step.
+    pub fn advance(&mut self, time: f64, common: bool, force: bool) -> bool {
+        self.due_streams.clear();
+        for stream in OutputStream::ALL {
+            let due = match self.streams.iter_mut().find(|(s, _)| *s == stream) {
+                Some((_, timer)) => timer.due(time),
+                None => common,
+            };
+            if due || force {
+                self.due_streams.push(stream);
+            }
+        }
+        self.due_fields.clear();
+        for (field, timer) in &mut self.fields {
+            if timer.due(time) || force {
+                self.due_fields.push(*field);
+            }
+        }
+        !self.due_streams.is_empty() || !self.due_fields.is_empty()
+    }
+
+    /// Earliest time a stream or field is due after `time`
+    pub fn next_time(&self, time: f64) -> f64 {
+        self.streams
+            .iter()
+            .map(|(_, timer)| timer)
+            .chain(self.fields.iter().map(|(_, timer)| timer))
+            .map(|timer| timer.next_time)
+            .filter(|&next| next > time)
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn due(&self, stream: OutputStream) -> bool {
+        self.due_streams.contains(&stream)
+    }
+
+    /// The fields of `fields` due in this step's frame (none if no frame is due)
+    pub fn frame_fields(&self, fields: &[OutputField]) -> Vec<OutputField> {
+        let frames = self.due(OutputStream::Frames);
+        fields
+            .iter()
+            .copied()
+            .filter(|field| match self.fields.iter().any(|(f, _)| f == field) {
+                true => self.due_fields.contains(field),
+                false => frames,
+            })
+            .collect()
+    }
+
+    /// Scheduled fields missing from `fields`
+    pub fn unused_fields(&self, fields: &[OutputField]) -> Vec<OutputField> {
+        self.fields
+            .iter()
+            .map(|&(field, _)| field)
+            .filter(|field| !fields.contains(field))
+            .collect()
+    }
+
+    /// Whether the VTK frames depart from the common cadence
+    pub fn schedules_frames(&self) -> bool {
+        !self.fields.is_empty() || self.streams.iter().any(|(s, _)| *s == OutputStream::Frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_parse_triggers() {
+        let triggers = parse_triggers("depth:g1>0.5; wet:inlet;wetting").unwrap();
+        assert_eq!(
+            triggers,
+            vec![
+                OutputTrigger::Depth {
+                    gauge: "g1".into(),
+                    threshold: 0.5
+                },
+                OutputTrigger::Wet {
+                    gauge: "inlet".into()
+                },
+                OutputTrigger::Wetting,
+            ]
+        );
+        for bad in ["depth:g1", "depth:g1>x", "wet:", "flood", "wetting:g1"] {
+            assert!(OutputTrigger::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_triggers_fire_on_arrival() {
+        let mesh = TriangularMesh::new_rectangular(41, 3, 20.0, 1.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+            solver.state.h[i] = if tri.centroid.0 < 5.0 { 1.0 } else { 0.0 };
+        }
+        let gauge = solver.nearest_cell(10.0, 0.5).unwrap();
+        let gauges = vec![("far".to_string(), gauge)];
+        let cadence = |triggers| OutputCadence::new(&solver, 100.0, None, triggers, &gauges);
+        assert!(cadence(parse_triggers("wet:near").unwrap()).is_err());
+        let mut wet = cadence(parse_triggers("wet:far").unwrap()).unwrap();
+        let mut depth = cadence(parse_triggers("depth:far>0.1").unwrap()).unwrap();
+        let mut wetting = cadence(vec![OutputTrigger::Wetting]).unwrap();
+        let mut every = OutputCadence::new(&solver, 100.0, Some(3), Vec::new(), &[]).unwrap();
+
+        let (mut wet_steps, mut depth_steps, mut wetting_steps) = (Vec::new(), Vec::new(), 0);
+        for step in 1..=400 {
+            solver.step();
+            if wet.due(&solver, step) {
+                wet_steps.push(step);
+            }
+            if depth.due(&solver, step) {
+                depth_steps.push(step);
+            }
+            if wetting.due(&solver, step) {
+                wetting_steps += 1;
+            }
+            assert_eq!(every.due(&solver, step), step.is_multiple_of(3));
+        }
+        // The front reaches the gauge once, and gets deeper than 0.1 m later
+        assert_eq!(wet_steps.len(), 1);
+        assert_eq!(depth_steps.len(), 1);
+        assert!(depth_steps[0] > wet_steps[0]);
+        // The front crosses several cells, each wetting step is an output
+        assert!(wetting_steps > 5);
+    }
+
+    #[test]
+    fn test_schedule_streams_and_fields() {
+        let entries: Vec<ScheduleEntry> = ["vtk.vel=60", "gauges=0", "zarr=20", "zarr=30"]
+            .iter()
+            .map(|spec| ScheduleEntry::parse(spec).unwrap())
+            .collect();
+        for bad in ["vtk=-1", "netcdf=10", "vtk.speed=10", "gauges"] {
+            assert!(ScheduleEntry::parse(bad).is_err(), "{}", bad);
+        }
+        let fields = [OutputField::Height, OutputField::Velocity];
+        let mut schedule = OutputSchedule::new(0.0, &entries);
+        assert!(schedule.schedules_frames());
+        assert_eq!(
+            schedule.unused_fields(&fields[..1]),
+            vec![OutputField::Velocity]
+        );
+
+        // Common outputs every 10 s, steps of 5 s
+        let (mut gauges, mut zarr, mut velocity, mut height) = (0, 0, 0, 0);
+        for step in 1..=24 {
+            let time = 5.0 * step as f64;
+            assert!(schedule.advance(time, step % 2 == 0, false));
+            gauges += schedule.due(OutputStream::Gauges) as usize;
+            zarr += schedule.due(OutputStream::Zarr) as usize;
+            let frame = schedule.frame_fields(&fields);
+            height += frame.contains(&OutputField::Height) as usize;
+            velocity += frame.contains(&OutputField::Velocity) as usize;
+            assert_eq!(schedule.due(OutputStream::Statistics), step % 2 == 0);
+        }
+        assert_eq!((gauges, zarr, height, velocity), (24, 4, 12, 2));
+        schedule.advance(121.0, false, true);
+        assert_eq!(schedule.frame_fields(&fields), fields);
+    }
+}
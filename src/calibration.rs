@@ -0,0 +1,381 @@
+/// Friction calibration against gauge records
+/// Observed water levels are read from a CSV file with one row per reading
+/// (`gauge,x,y,time,level`). Each gauge is represented by the cell whose
+/// centroid is nearest to it, and the simulated water surface is linearly
+/// interpolated between time steps at the observation times. The Manning
+/// coefficients are fitted with the Nelder-Mead simplex method, which needs
+/// only objective values and copes with the noise of a discretised run.
+use crate::mesh::{PointLocator, TriangularMesh};
+use crate::simulation::{Observer, Simulation, StepEvent};
+use crate::solver::ShallowWaterSolver;
+use std::collections::BTreeMap;
+
+/// Observed water-level time series at one location
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gauge {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+    pub times: Vec<f64>,  // Observation times (s), ascending
+    pub levels: Vec<f64>, // Observed water surface elevation (m)
+}
+
+/// Parse gauge records "gauge,x,y,time,level" (header line and lines
+/// starting with '#' are skipped); rows may come in any order
+pub fn parse_observations(text: &str) -> Result<Vec<Gauge>, String> {
+    let mut gauges: BTreeMap<String, Gauge> = BTreeMap::new();
+    let mut readings: BTreeMap<String, Vec<(f64, f64)>> = BTreeMap::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || (number == 0 && line.starts_with("gauge")) {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "line {}: expected gauge,x,y,time,level but found '{}'",
+                number + 1,
+                line
+            ));
+        }
+        let value = |k: usize| {
+            fields[k]
+                .parse::<f64>()
+                .map_err(|_| format!("line {}: invalid number '{}'", number + 1, fields[k]))
+        };
+        let (x, y, time, level) = (value(1)?, value(2)?, value(3)?, value(4)?);
+        let gauge = gauges
+            .entry(fields[0].to_string())
+            .or_insert_with(|| Gauge {
+                name: fields[0].to_string(),
+                x,
+                y,
+                times: Vec::new(),
+                levels: Vec::new(),
+            });
+        if (gauge.x, gauge.y) != (x, y) {
+            return Err(format!(
+                "line {}: gauge '{}' moved from ({}, {}) to ({}, {})",
+                number + 1,
+                gauge.name,
+                gauge.x,
+                gauge.y,
+                x,
+                y
+            ));
+        }
+        readings
+            .entry(fields[0].to_string())
+            .or_default()
+            .push((time, level));
+    }
+
+    if gauges.is_empty() {
+        return Err("no gauge readings found".to_string());
+    }
+    Ok(gauges
+        .into_values()
+        .map(|mut gauge| {
+            let mut series = readings.remove(&gauge.name).unwrap_or_default();
+            series.sort_by(|a, b| a.0.total_cmp(&b.0));
+            (gauge.times, gauge.levels) = series.into_iter().unzip();
+            gauge
+        })
+        .collect())
+}
+
+pub fn read_observations(path: &str) -> Result<Vec<Gauge>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse_observations(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Named output location (name, (x, y))
+pub type GaugePoint = (String, (f64, f64));
+
+/// Parse output gauge locations "name=x,y;x,y" (unnamed gauges are called
+/// g1, g2, ... by position)
+pub fn parse_gauge_points(spec: &str) -> Result<Vec<GaugePoint>, String> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .enumerate()
+        .map(|(k, entry)| {
+            let (name, point) = match entry.split_once('=') {
+                Some((name, point)) => (name.trim().to_string(), point),
+                None => (format!("g{}", k + 1), entry),
+            };
+            let coordinates: Vec<f64> = point
+                .split(',')
+                .map(|v| v.trim().parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("invalid gauge '{}'", entry))?;
+            match coordinates[..] {
+                [x, y] => Ok((name, (x, y))),
+                _ => Err(format!("expected name=x,y but found '{}'", entry)),
+            }
+        })
+        .collect()
+}
+
+/// Cells whose centroids are closest to the points
+pub fn nearest_cells(mesh: &TriangularMesh, points: &[(f64, f64)]) -> Vec<usize> {
+    let locator = PointLocator::new(mesh);
+    points
+        .iter()
+        .map(|&(x, y)| locator.nearest(mesh, x, y).unwrap_or(0))
+        .collect()
+}
+
+fn level(solver: &ShallowWaterSolver, i: usize) -> f64 {
+    solver.state.h[i] + solver.mesh.triangles[i].z_bed
+}
+
+/// Simulated gauge series, interpolated in time to the observation times
+/// between the levels before and after each step
+struct GaugeSampler<'a> {
+    gauges: &'a [Gauge],
+    cells: &'a [usize],
+    simulated: Vec<Vec<f64>>,
+    previous: Vec<f64>,
+    previous_time: f64,
+}
+
+impl GaugeSampler<'_> {
+    fn record(&mut self, solver: &ShallowWaterSolver) {
+        for (k, gauge) in self.gauges.iter().enumerate() {
+            let series = &mut self.simulated[k];
+            while series.len() < gauge.times.len() && gauge.times[series.len()] <= solver.time {
+                let t = gauge.times[series.len()];
+                let current = level(solver, self.cells[k]);
+                let span = solver.time - self.previous_time;
+                let w = if span > 0.0 {
+                    ((t - self.previous_time) / span).clamp(0.0, 1.0)
+                } else {
+                    1.0
+                };
+                series.push(self.previous[k] + w * (current - self.previous[k]));
+            }
+        }
+    }
+}
+
+impl Observer for GaugeSampler<'_> {
+    fn observe(&mut self, solver: &ShallowWaterSolver, _event: &StepEvent) {
+        self.record(solver);
+        self.previous = self.cells.iter().map(|&i| level(solver, i)).collect();
+        self.previous_time = solver.time;
+    }
+}
+
+/// Advance the solver to the last observation time and return the simulated
+/// water levels of every gauge at its observation times
+pub fn simulate_gauges(
+    solver: &mut ShallowWaterSolver,
+    gauges: &[Gauge],
+    cells: &[usize],
+) -> Vec<Vec<f64>> {
+    let end_time = gauges
+        .iter()
+        .filter_map(|g| g.times.last())
+        .fold(0.0, |a: f64, &b| a.max(b));
+
+    let mut sampler = GaugeSampler {
+        gauges,
+        cells,
+        simulated: gauges
+            .iter()
+            .map(|g| Vec::with_capacity(g.times.len()))
+            .collect(),
+        previous: cells.iter().map(|&i| level(solver, i)).collect(),
+        previous_time: solver.time,
+    };
+    sampler.record(solver);
+    let mut simulation = Simulation::until(solver, end_time);
+    simulation.add_observer(Box::new(&mut sampler));
+    simulation.run().expect("no couplings to fail");
+    drop(simulation);
+    sampler.simulated
+}
+
+/// Misfit between simulated and observed levels over all readings
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorMetrics {
+    pub rmse: f64,          // Root mean square error (m)
+    pub bias: f64,          // Mean of simulated - observed (m)
+    pub max_abs_error: f64, // Largest absolute error (m)
+    pub nse: f64,           // Nash-Sutcliffe efficiency against each gauge's mean
+}
+
+pub fn error_metrics(gauges: &[Gauge], simulated: &[Vec<f64>]) -> ErrorMetrics {
+    let (mut count, mut sum_sq, mut sum, mut max_abs, mut variance) = (0, 0.0, 0.0, 0.0, 0.0);
+    for (gauge, series) in gauges.iter().zip(simulated) {
+        let mean = gauge.levels.iter().sum::<f64>() / gauge.levels.len().max(1) as f64;
+        for (observed, simulated) in gauge.levels.iter().zip(series) {
+            let error = simulated - observed;
+            count += 1;
+            sum += error;
+            sum_sq += error * error;
+            max_abs = f64::max(max_abs, error.abs());
+            variance += (observed - mean) * (observed - mean);
+        }
+    }
+    let n = count.max(1) as f64;
+    ErrorMetrics {
+        rmse: (sum_sq / n).sqrt(),
+        bias: sum / n,
+        max_abs_error: max_abs,
+        nse: if variance > 0.0 {
+            1.0 - sum_sq / variance
+        } else {
+            f64::NAN
+        },
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NelderMeadResult {
+    pub x: Vec<f64>,
+    pub value: f64,
+    pub evaluations: usize,
+    pub converged: bool,
+}
+
+/// Minimise `f` with the Nelder-Mead simplex method, starting from `x0` with
+/// initial simplex edges `step`. Stops when the objective values of the
+/// simplex differ by less than `tolerance` or after `max_evaluations`.
+pub fn nelder_mead<F: FnMut(&[f64]) -> f64>(
+    mut f: F,
+    x0: &[f64],
+    step: f64,
+    tolerance: f64,
+    max_evaluations: usize,
+) -> NelderMeadResult {
+    const REFLECT: f64 = 1.0;
+    const EXPAND: f64 = 2.0;
+    const CONTRACT: f64 = 0.5;
+    const SHRINK: f64 = 0.5;
+
+    let n = x0.len();
+    let mut evaluations = 0;
+    let mut eval = |x: &[f64], evaluations: &mut usize| {
+        *evaluations += 1;
+        f(x)
+    };
+    let mut simplex: Vec<(Vec<f64>, f64)> = Vec::with_capacity(n + 1);
+    simplex.push((x0.to_vec(), eval(x0, &mut evaluations)));
+    for k in 0..n {
+        let mut x = x0.to_vec();
+        x[k] += step;
+        let value = eval(&x, &mut evaluations);
+        simplex.push((x, value));
+    }
+
+    let converged = loop {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        if (simplex[n].1 - simplex[0].1).abs() <= tolerance {
+            break true;
+        }
+        if evaluations >= max_evaluations {
+            break false;
+        }
+
+        let centroid: Vec<f64> = (0..n)
+            .map(|k| simplex[..n].iter().map(|(x, _)| x[k]).sum::<f64>() / n as f64)
+            .collect();
+        let towards = |t: f64, worst: &[f64]| -> Vec<f64> {
+            centroid
+                .iter()
+                .zip(worst)
+                .map(|(c, w)| c + t * (c - w))
+                .collect()
+        };
+
+        let worst = simplex[n].0.clone();
+        let reflected = towards(REFLECT, &worst);
+        let reflected_value = eval(&reflected, &mut evaluations);
+
+        if reflected_value < simplex[0].1 {
+            let expanded = towards(EXPAND, &worst);
+            let expanded_value = eval(&expanded, &mut evaluations);
+            simplex[n] = if expanded_value < reflected_value {
+                (expanded, expanded_value)
+            } else {
+                (reflected, reflected_value)
+            };
+        } else if reflected_value < simplex[n - 1].1 {
+            simplex[n] = (reflected, reflected_value);
+        } else {
+            // Outside contraction if the reflection improved on the worst point
+            let outside = reflected_value < simplex[n].1;
+            let contracted = towards(if outside { CONTRACT } else { -CONTRACT }, &worst);
+            let contracted_value = eval(&contracted, &mut evaluations);
+            if contracted_value < reflected_value.min(simplex[n].1) {
+                simplex[n] = (contracted, contracted_value);
+            } else {
+                let best = simplex[0].0.clone();
+                for vertex in simplex.iter_mut().skip(1) {
+                    let x: Vec<f64> = best
+                        .iter()
+                        .zip(&vertex.0)
+                        .map(|(b, v)| b + SHRINK * (v - b))
+                        .collect();
+                    let value = eval(&x, &mut evaluations);
+                    *vertex = (x, value);
+                }
+            }
+        }
+    };
+
+    let (x, value) = simplex.swap_remove(0);
+    NelderMeadResult {
+        x,
+        value,
+        evaluations,
+        converged,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_observations_groups_and_sorts_gauges() {
+        let text = "gauge,x,y,time,level\n\
+                    b,5,5,2.0,1.2\n\
+                    a,1,2,1.0,1.5\n\
+                    # comment\n\
+                    a,1,2,0.5,1.7\n";
+        let gauges = parse_observations(text).unwrap();
+        assert_eq!(gauges.len(), 2);
+        assert_eq!(gauges[0].name, "a");
+        assert_eq!(gauges[0].times, vec![0.5, 1.0]);
+        assert_eq!(gauges[0].levels, vec![1.7, 1.5]);
+        assert!(parse_observations("a,1,2,0.5,1.7\na,3,2,1.0,1.5").is_err());
+
+        let points = parse_gauge_points("harbour=10,2.5; 4,1").unwrap();
+        assert_eq!(points[0], ("harbour".to_string(), (10.0, 2.5)));
+        assert_eq!(points[1], ("g2".to_string(), (4.0, 1.0)));
+        assert!(parse_gauge_points("a=1").is_err());
+
+        let simulated = vec![vec![1.6, 1.5], vec![1.4]];
+        let metrics = error_metrics(&gauges, &simulated);
+        assert!((metrics.rmse - (0.05f64 / 3.0).sqrt()).abs() < 1e-12);
+        assert!((metrics.max_abs_error - 0.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_nelder_mead_finds_quadratic_minimum() {
+        let result = nelder_mead(
+            |x| (x[0] - 1.0).powi(2) + 10.0 * (x[1] + 0.5).powi(2),
+            &[0.0, 0.0],
+            0.5,
+            1e-12,
+            500,
+        );
+        assert!(result.converged);
+        assert!((result.x[0] - 1.0).abs() < 1e-4);
+        assert!((result.x[1] + 0.5).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,374 @@
+/// Regression comparison of two runs
+/// Pairs the VTK frames of two runs by frame number and measures, for every
+/// cell array both frames have, the area-weighted RMS (L2) and the largest
+/// (L∞) difference. The last rows of the statistics tables give the changes
+/// of the final mass and energy. A run is named by its output prefix, or by
+/// a directory holding a single run. Comparing a refactored solver or the
+/// GPU backend with a reference run shows whether the results moved beyond
+/// a tolerance.
+use crate::hotstart::VtkFrame;
+use crate::mesh::CoordinateSystem;
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Statistics columns compared between the runs
+const METRICS: [&str; 2] = ["mass", "total_energy"];
+
+/// Output files of one run
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub prefix: String,
+    pub coordinate_system: CoordinateSystem,
+    pub frames: BTreeMap<usize, String>, // Frame number to file
+    pub statistics: Option<String>,
+}
+
+impl Run {
+    /// The run with output prefix `path`, or the only run in directory `path`
+    pub fn locate(path: &str) -> Result<Self, String> {
+        let prefix = if Path::new(path).is_dir() {
+            Self::prefix_in(path)?
+        } else {
+            path.to_string()
+        };
+        let directory = match Path::new(&prefix).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => Path::new(".").to_path_buf(),
+        };
+        let stem = Path::new(&prefix)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut frames = BTreeMap::new();
+        for entry in std::fs::read_dir(&directory)
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let number = name
+                .strip_prefix(stem.as_str())
+                .and_then(|rest| rest.strip_prefix('_'))
+                .and_then(|rest| rest.strip_suffix(".vtk"))
+                .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()));
+            if let Some(number) = number.and_then(|digits| digits.parse().ok()) {
+                frames.insert(number, entry.path().display().to_string());
+            }
+        }
+        if frames.is_empty() {
+            return Err(format!("no VTK frames {}_NNNN.vtk", prefix));
+        }
+        let statistics = format!("{}_statistics.csv", prefix);
+        Ok(Run {
+            coordinate_system: manifest_coordinates(&prefix),
+            statistics: Path::new(&statistics).is_file().then_some(statistics),
+            prefix,
+            frames,
+        })
+    }
+
+    /// Prefix of the run whose manifest, or else first frame, is in `directory`
+    fn prefix_in(directory: &str) -> Result<String, String> {
+        let names: Vec<String> = std::fs::read_dir(directory)
+            .map_err(|e| format!("{}: {}", directory, e))?
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        for suffix in ["_manifest.json", "_0000.vtk"] {
+            let mut stems: Vec<&str> = names
+                .iter()
+                .filter_map(|name| name.strip_suffix(suffix))
+                .collect();
+            stems.sort_unstable();
+            match stems[..] {
+                [] => continue,
+                [stem] => return Ok(Path::new(directory).join(stem).display().to_string()),
+                _ => {
+                    return Err(format!(
+                        "{} holds several runs ({}); give the output prefix",
+                        directory,
+                        stems.join(", ")
+                    ))
+                }
+            }
+        }
+        Err(format!("{} holds no run manifest or VTK frames", directory))
+    }
+}
+
+/// Coordinate system recorded in the run manifest (Cartesian without one)
+fn manifest_coordinates(prefix: &str) -> CoordinateSystem {
+    let manifest = std::fs::read_to_string(format!("{}_manifest.json", prefix))
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok());
+    manifest
+        .as_ref()
+        .and_then(|manifest| manifest.pointer("/mesh/coordinate_system"))
+        .and_then(|value| CoordinateSystem::deserialize(value).ok())
+        .unwrap_or(CoordinateSystem::Cartesian)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDifference {
+    pub name: String,
+    pub frames: usize,
+    pub l2: f64,            // Largest area-weighted RMS difference of a frame
+    pub linf: f64,          // Largest difference in a cell
+    pub worst_frame: usize, // Frame with the largest difference
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricDifference {
+    pub name: &'static str,
+    pub a: f64,
+    pub b: f64,
+}
+
+impl MetricDifference {
+    pub fn relative(&self) -> f64 {
+        let scale = self.a.abs().max(self.b.abs());
+        if scale > 0.0 {
+            (self.a - self.b).abs() / scale
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub frames: usize,
+    pub only_a: Vec<usize>,    // Frame numbers missing from run B
+    pub only_b: Vec<usize>,    // Frame numbers missing from run A
+    pub time_offset: f64,      // Largest time difference of paired frames
+    pub unpaired: Vec<String>, // Arrays in one run only
+    pub fields: Vec<FieldDifference>,
+    pub metrics: Vec<MetricDifference>,
+}
+
+impl Comparison {
+    /// Whether every frame is paired, every field is within `tolerance` (L∞)
+    /// and every metric within the relative `conservation_tolerance`
+    pub fn passed(&self, tolerance: f64, conservation_tolerance: f64) -> bool {
+        self.only_a.is_empty()
+            && self.only_b.is_empty()
+            && self.fields.iter().all(|field| field.linf <= tolerance)
+            && self
+                .metrics
+                .iter()
+                .all(|metric| metric.relative() <= conservation_tolerance)
+    }
+}
+
+/// Compare the paired frames and the statistics of two runs
+pub fn compare(a: &Run, b: &Run) -> Result<Comparison, String> {
+    let paired: Vec<usize> = a
+        .frames
+        .keys()
+        .filter(|number| b.frames.contains_key(number))
+        .copied()
+        .collect();
+    if paired.is_empty() {
+        return Err(format!(
+            "{} and {} have no frame numbers in common",
+            a.prefix, b.prefix
+        ));
+    }
+    let unpaired = |run: &Run, other: &Run| -> Vec<usize> {
+        run.frames
+            .keys()
+            .filter(|number| !other.frames.contains_key(number))
+            .copied()
+            .collect()
+    };
+    let differences = paired
+        .par_iter()
+        .map(|&number| {
+            let frame_a = VtkFrame::read(&a.frames[&number], a.coordinate_system)?;
+            let frame_b = VtkFrame::read(&b.frames[&number], b.coordinate_system)?;
+            frame_differences(&frame_a, &frame_b)
+                .map(|fields| (number, (frame_a.time - frame_b.time).abs(), fields))
+                .map_err(|e| format!("frame {}: {}", number, e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut fields: Vec<FieldDifference> = Vec::new();
+    let mut unpaired_arrays = Vec::new();
+    let mut time_offset: f64 = 0.0;
+    for (number, offset, frame) in differences {
+        time_offset = time_offset.max(offset);
+        for (name, difference) in frame {
+            let Some((l2, linf)) = difference else {
+                if !unpaired_arrays.contains(&name) {
+                    unpaired_arrays.push(name);
+                }
+                continue;
+            };
+            let index = match fields.iter().position(|field| field.name == name) {
+                Some(index) => index,
+                None => {
+                    fields.push(FieldDifference {
+                        name,
+                        frames: 0,
+                        l2: 0.0,
+                        linf: 0.0,
+                        worst_frame: number,
+                    });
+                    fields.len() - 1
+                }
+            };
+            let field = &mut fields[index];
+            field.frames += 1;
+            if l2 > field.l2 || l2.is_nan() {
+                field.l2 = l2;
+            }
+            if linf > field.linf || linf.is_nan() {
+                field.linf = linf;
+                field.worst_frame = number;
+            }
+        }
+    }
+    unpaired_arrays.retain(|name| !fields.iter().any(|field| &field.name == name));
+
+    let metrics = match (&a.statistics, &b.statistics) {
+        (Some(table_a), Some(table_b)) => {
+            let (last_a, last_b) = (last_row(table_a)?, last_row(table_b)?);
+            METRICS
+                .iter()
+                .filter_map(|&name| {
+                    Some(MetricDifference {
+                        name,
+                        a: *last_a.get(name)?,
+                        b: *last_b.get(name)?,
+                    })
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+    Ok(Comparison {
+        frames: paired.len(),
+        only_a: unpaired(a, b),
+        only_b: unpaired(b, a),
+        time_offset,
+        unpaired: unpaired_arrays,
+        fields,
+        metrics,
+    })
+}
+
+/// L2 and L∞ differences per array, None for arrays in one frame only
+type FrameDifferences = Vec<(String, Option<(f64, f64)>)>;
+
+fn frame_differences(a: &VtkFrame, b: &VtkFrame) -> Result<FrameDifferences, String> {
+    let same_mesh = a.mesh.triangles.len() == b.mesh.triangles.len()
+        && a.mesh
+            .triangles
+            .iter()
+            .zip(&b.mesh.triangles)
+            .all(|(ta, tb)| ta.nodes == tb.nodes);
+    if !same_mesh {
+        return Err("the runs have different meshes".to_string());
+    }
+    let total_area: f64 = a.mesh.triangles.iter().map(|t| t.area).sum();
+    let mut differences: FrameDifferences = a
+        .arrays
+        .iter()
+        .map(|(name, values)| {
+            let difference = b.array(name).map(|other| {
+                let (mut squares, mut largest) = (0.0, 0.0f64);
+                for ((x, y), tri) in values.iter().zip(other).zip(&a.mesh.triangles) {
+                    let d = (x - y).abs();
+                    squares += tri.area * d * d;
+                    if d > largest || d.is_nan() {
+                        largest = d; // Keeps NaN
+                    }
+                }
+                ((squares / total_area).sqrt(), largest)
+            });
+            (name.clone(), difference)
+        })
+        .collect();
+    for (name, _) in &b.arrays {
+        if a.array(name).is_none() {
+            differences.push((name.clone(), None));
+        }
+    }
+    Ok(differences)
+}
+
+/// Last row of a CSV table by column name
+fn last_row(path: &str) -> Result<BTreeMap<String, f64>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().ok_or(format!("{}: empty table", path))?;
+    let last = lines.next_back().ok_or(format!("{}: no rows", path))?;
+    Ok(header
+        .split(',')
+        .zip(last.split(','))
+        .filter_map(|(name, value)| Some((name.trim().to_string(), value.trim().parse().ok()?)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::output::{save_vtk, Frame, OutputField};
+    use crate::solver::{FrictionLaw, ShallowWaterSolver};
+
+    fn write_run(directory: &Path, name: &str, solver: &ShallowWaterSolver, frames: usize) {
+        let prefix = directory.join(name).display().to_string();
+        let fields = [OutputField::Height, OutputField::Velocity];
+        for k in 0..frames {
+            let frame = Frame::capture(solver, &fields);
+            save_vtk(&format!("{}_{:04}.vtk", prefix, k), &solver.mesh, &frame).unwrap();
+        }
+        let mass = solver.compute_total_mass();
+        std::fs::write(
+            format!("{}_statistics.csv", prefix),
+            format!("time,mass,total_energy\n0,{},1\n1,{},1\n", mass, mass),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_compare_runs() {
+        let directory = std::env::temp_dir().join(format!("swe_compare_{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("single")).unwrap();
+        let mesh = TriangularMesh::new_rectangular(6, 5, 5.0, 4.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.iter_mut().for_each(|h| *h = 1.0);
+        write_run(&directory, "a", &solver, 3);
+        write_run(&directory.join("single"), "b", &solver, 3);
+
+        let a = Run::locate(&directory.join("a").display().to_string()).unwrap();
+        let b = Run::locate(directory.join("single").to_str().unwrap()).unwrap();
+        assert_eq!(a.frames.len(), 3);
+        let same = compare(&a, &b).unwrap();
+        assert_eq!(same.frames, 3);
+        assert_eq!(same.fields.len(), 3); // height, velocity_x, velocity_y
+        assert!(same.fields.iter().all(|f| f.l2 == 0.0 && f.linf == 0.0));
+        assert_eq!(same.metrics.len(), 2);
+        assert!(same.passed(0.0, 0.0));
+
+        solver.state.h[4] += 0.01;
+        write_run(&directory, "c", &solver, 2);
+        let c = Run::locate(&directory.join("c").display().to_string()).unwrap();
+        let changed = compare(&a, &c).unwrap();
+        assert_eq!(changed.only_a, [2]);
+        let height = &changed.fields[0];
+        assert_eq!(height.name, "height");
+        assert!((height.linf - 0.01).abs() < 1e-9);
+        let area = |i: usize| solver.mesh.triangles[i].area;
+        let total: f64 = (0..solver.mesh.triangles.len()).map(area).sum();
+        assert!((height.l2 - 0.01 * (area(4) / total).sqrt()).abs() < 1e-9);
+        assert!(changed.metrics[0].relative() > 0.0);
+        assert!(!changed.passed(1.0, 1.0)); // Frame 2 is missing from run c
+
+        assert!(Run::locate(directory.to_str().unwrap()).is_err()); // Runs a and c
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,272 @@
+/// Lossless compression for the output writers
+/// Deflate with LZ77 matches (hash chains over a 32 KiB window) and the
+/// fixed Huffman codes of RFC 1951. Dynamic Huffman tables would compress a
+/// little better; the fixed codes keep the encoder short and need no second
+/// pass over the data. Any zlib, gzip or deflate decoder reads the streams.
+const LENGTH_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Deflate bit stream, least significant bit first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting with their most significant bit
+    fn put_code(&mut self, code: u32, bits: u32) {
+        self.put(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    /// Symbol of the fixed literal/length code
+    fn put_symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.put_code(0x30 + symbol, 8),
+            144..=255 => self.put_code(0x190 + symbol - 144, 9),
+            256..=279 => self.put_code(symbol - 256, 7),
+            _ => self.put_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// zlib stream (RFC 1950) of the compressed data
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x9c];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// gzip member (RFC 1952) of the compressed data, without file name or time
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    out.extend(deflate(data));
+    out.extend(crc32(data.iter()).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Raw deflate stream (RFC 1951) of one block with LZ77 matches and the
+/// fixed Huffman codes (hash chains over a 32 KiB window)
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    const WINDOW: usize = 32768;
+    const MAX_CHAIN: usize = 64;
+    const HASH_BITS: u32 = 15;
+    let hash = |i: usize| {
+        let key = u32::from_le_bytes([data[i], data[i + 1], data[i + 2], 0]);
+        (key.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+    };
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; data.len()];
+    let insert = |i: usize, head: &mut [usize], previous: &mut [usize]| {
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            previous[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut bits = BitWriter::default();
+    bits.put(1, 1); // BFINAL
+    bits.put(1, 2); // BTYPE = 01, fixed Huffman codes
+    let mut i = 0;
+    while i < data.len() {
+        let (mut length, mut distance) = (0, 0);
+        if i + 3 <= data.len() {
+            let longest = (data.len() - i).min(258);
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let matched = (0..longest)
+                    .take_while(|&k| data[candidate + k] == data[i + k])
+                    .count();
+                if matched > length {
+                    (length, distance) = (matched, i - candidate);
+                    if matched == longest {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+        if length >= 3 {
+            let code = LENGTH_BASE
+                .iter()
+                .rposition(|&base| base <= length)
+                .unwrap();
+            bits.put_symbol(257 + code as u32);
+            bits.put((length - LENGTH_BASE[code]) as u32, LENGTH_EXTRA[code]);
+            let code = DISTANCE_BASE
+                .iter()
+                .rposition(|&base| base <= distance)
+                .unwrap();
+            bits.put_code(code as u32, 5);
+            bits.put(
+                (distance - DISTANCE_BASE[code]) as u32,
+                DISTANCE_EXTRA[code],
+            );
+            for k in i..i + length {
+                insert(k, &mut head, &mut previous);
+            }
+            i += length;
+        } else {
+            bits.put_symbol(data[i] as u32);
+            insert(i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+    bits.put_symbol(256); // End of block
+    bits.finish()
+}
+
+/// zlib checksum
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// CRC-32 of PNG, gzip and zip
+pub fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Decode a zlib stream written by `zlib`
+#[cfg(test)]
+pub(crate) fn inflate(stream: &[u8]) -> Vec<u8> {
+    let out = inflate_raw(&stream[2..stream.len() - 4]);
+    assert_eq!(&stream[stream.len() - 4..], adler32(&out).to_be_bytes());
+    out
+}
+
+/// Decode a gzip member written by `gzip`
+#[cfg(test)]
+pub(crate) fn inflate_gzip(member: &[u8]) -> Vec<u8> {
+    let out = inflate_raw(&member[10..member.len() - 8]);
+    let crc = &member[member.len() - 8..member.len() - 4];
+    assert_eq!(crc, crc32(out.iter()).to_le_bytes());
+    out
+}
+
+/// Decode one fixed-Huffman deflate block
+#[cfg(test)]
+fn inflate_raw(data: &[u8]) -> Vec<u8> {
+    let mut position = 0;
+    let mut bit = |n: u32| {
+        let mut value = 0;
+        for k in 0..n {
+            value |= (((data[position / 8] >> (position % 8)) & 1) as usize) << k;
+            position += 1;
+        }
+        value
+    };
+    let mut out: Vec<u8> = Vec::new();
+    assert_eq!((bit(1), bit(2)), (1, 1));
+    loop {
+        // Read the fixed code MSB first, 7 to 9 bits long
+        let mut code = 0;
+        for _ in 0..7 {
+            code = (code << 1) | bit(1);
+        }
+        let symbol = if code <= 0x17 {
+            256 + code
+        } else {
+            code = (code << 1) | bit(1);
+            if (0x30..=0xbf).contains(&code) {
+                code - 0x30
+            } else if (0xc0..=0xc7).contains(&code) {
+                280 + code - 0xc0
+            } else {
+                144 + ((code << 1) | bit(1)) - 0x190
+            }
+        };
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => break,
+            _ => {
+                let k = symbol - 257;
+                let length = LENGTH_BASE[k] + bit(LENGTH_EXTRA[k]);
+                let mut d = 0;
+                for _ in 0..5 {
+                    d = (d << 1) | bit(1);
+                }
+                let distance = DISTANCE_BASE[d] + bit(DISTANCE_EXTRA[d]);
+                for _ in 0..length {
+                    out.push(out[out.len() - distance]);
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums_and_round_trip() {
+        assert_eq!(crc32(b"123456789".iter()), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+
+        let bytes: Vec<u8> = (0..20000u32)
+            .flat_map(|k| (k % 251 * k % 7).to_le_bytes())
+            .collect();
+        let compressed = zlib(&bytes);
+        assert!(compressed.len() < bytes.len() / 4);
+        assert_eq!(inflate(&compressed), bytes);
+        assert_eq!(inflate(&zlib(b"")), b"");
+        assert_eq!(inflate_gzip(&gzip(b"abcabcabc")), b"abcabcabc");
+    }
+}
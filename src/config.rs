@@ -0,0 +1,261 @@
+/// Run configuration file (JSON)
+/// Settings given on the command line take precedence over the file.
+///
+/// ```json
+/// {
+///   "mesh": { "holes": [[[4, 4], [6, 4], [5, 6]]] },
+///   "initial": { "condition": "gaussian-hump", "amplitude": 0.2, "width": 1.5 },
+///   "output": { "fields": ["h", "vel", "eta"] },
+///   "physics": { "gravity": 3.71, "density": 1200 },
+///   "timestep": { "min_dt": 1e-4, "max_dt": 0.5, "max_growth": 1.2 },
+///   "scripts": {
+///     "probes": { "basin": [120, 40] },
+///     "boundaries": { "river": "if t < 3600.0 { 20.0 + t / 360.0 } else { 30.0 }" },
+///     "gates": { "sluice": "basin.level > 2.5" },
+///     "sources": [{ "name": "pump", "at": [80, 40], "discharge": "-0.5" }]
+///   }
+/// }
+/// ```
+use crate::mesh::Polygon;
+use crate::output::OutputField;
+use crate::solver::{Perturbation, PhysicalConstants, TimestepControl, VelocityField};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunConfig {
+    pub mesh: MeshConfig,
+    pub initial: InitialConfig,
+    pub output: OutputConfig,
+    pub physics: PhysicsConfig,
+    pub timestep: TimestepConfig,
+    pub scripts: ScriptConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MeshConfig {
+    /// Interior hole polygons as lists of [x, y] vertices
+    pub holes: Option<Vec<Polygon>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InitialConfig {
+    /// Initial condition name as for --initial-condition, e.g. "solitary-wave"
+    pub condition: Option<String>,
+    pub amplitude: Option<f64>,     // Wave amplitude (m)
+    pub width: Option<f64>,         // Gaussian hump width σ (m)
+    pub center: Option<(f64, f64)>, // Hump centre or solitary wave crest
+    pub direction: Option<f64>,     // Solitary wave heading (degrees from east)
+    /// Velocity field added to the initial condition (default: at rest)
+    pub velocity: Option<VelocityField>,
+    /// Random perturbation of the initial depth and velocity
+    pub perturbation: Option<Perturbation>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Cell arrays written to every output frame (default: all)
+    pub fields: Option<Vec<OutputField>>,
+    /// Intervals (s) of single outputs by name, as for --output-schedule
+    pub schedule: BTreeMap<String, f64>,
+}
+
+/// Overrides of the physical constants; unset entries keep their defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhysicsConfig {
+    pub gravity: Option<f64>,           // m/s^2
+    pub dry_tolerance: Option<f64>,     // m
+    pub density: Option<f64>,           // kg/m^3
+    pub desingularization: Option<f64>, // m
+    pub friction_depth: Option<f64>,    // m
+}
+
+impl PhysicsConfig {
+    /// Resolve against the defaults, rejecting non-positive values
+    pub fn constants(&self) -> Result<PhysicalConstants, String> {
+        let defaults = PhysicalConstants::default();
+        let positive = |name: &str, value: Option<f64>, default: f64| {
+            let value = value.unwrap_or(default);
+            if value > 0.0 && value.is_finite() {
+                Ok(value)
+            } else {
+                Err(format!("{} must be positive, got {}", name, value))
+            }
+        };
+        Ok(PhysicalConstants {
+            gravity: positive("gravity", self.gravity, defaults.gravity)?,
+            dry_tolerance: positive("dry tolerance", self.dry_tolerance, defaults.dry_tolerance)?,
+            density: positive("density", self.density, defaults.density)?,
+            desingularization: match self.desingularization {
+                Some(depth) if !(depth >= 0.0 && depth.is_finite()) => {
+                    return Err(format!(
+                        "desingularization depth must not be negative, got {}",
+                        depth
+                    ))
+                }
+                depth => depth.unwrap_or(defaults.desingularization),
+            },
+            friction_depth: positive(
+                "friction depth",
+                self.friction_depth,
+                defaults.friction_depth,
+            )?,
+        })
+    }
+}
+
+/// Bounds of the explicit time step; setting any entry enables the control
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimestepConfig {
+    pub min_dt: Option<f64>,     // Smallest step a retry may use (s) [default: 0]
+    pub max_dt: Option<f64>,     // Largest step (s) [default: unlimited]
+    pub max_growth: Option<f64>, // Step-to-step growth factor [default: 1.5]
+}
+
+impl TimestepConfig {
+    /// The control, or None if no entry is set
+    pub fn control(&self) -> Result<Option<TimestepControl>, String> {
+        if self.min_dt.is_none() && self.max_dt.is_none() && self.max_growth.is_none() {
+            return Ok(None);
+        }
+        TimestepControl::new(
+            self.min_dt.unwrap_or(0.0),
+            self.max_dt.unwrap_or(f64::INFINITY),
+            self.max_growth.unwrap_or(1.5),
+        )
+        .map(Some)
+    }
+}
+
+/// Control scripts (Rhai) evaluated every step; see `scripting`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptConfig {
+    /// Named points whose depth, level and velocity the scripts can read
+    pub probes: BTreeMap<String, (f64, f64)>,
+    /// Level (stage) or discharge (inflow) of named boundary features
+    pub boundaries: BTreeMap<String, String>,
+    /// Whether named boundary features are open (otherwise walls)
+    pub gates: BTreeMap<String, String>,
+    /// Point sources with a scripted discharge
+    pub sources: Vec<ScriptedSource>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptedSource {
+    pub name: String,
+    pub at: (f64, f64), // Position in mesh coordinates
+    /// Discharge into the domain (m^3/s), negative for abstraction
+    pub discharge: String,
+}
+
+impl ScriptConfig {
+    pub fn is_empty(&self) -> bool {
+        self.boundaries.is_empty() && self.gates.is_empty() && self.sources.is_empty()
+    }
+}
+
+impl RunConfig {
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| format!("invalid configuration: {}", e))
+    }
+
+    /// Pretty-printed JSON that `from_json` reads back
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("configuration is serializable")
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read configuration {}: {}", path, e))?;
+        Self::from_json(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_fields_from_json() {
+        let config =
+            RunConfig::from_json(r#"{"output": {"fields": ["h", "momentum_x"]}}"#).unwrap();
+        assert_eq!(
+            config.output.fields,
+            Some(vec![OutputField::Height, OutputField::MomentumX])
+        );
+
+        assert!(RunConfig::from_json("{}").unwrap().output.fields.is_none());
+        assert!(RunConfig::from_json(r#"{"output": {"fields": ["speed"]}}"#).is_err());
+        assert!(RunConfig::from_json(r#"{"outputs": {}}"#).is_err());
+    }
+
+    #[test]
+    fn test_mesh_holes_from_json() {
+        let config =
+            RunConfig::from_json(r#"{"mesh": {"holes": [[[0, 0], [1, 0], [0.5, 1]]]}}"#).unwrap();
+        assert_eq!(
+            config.mesh.holes,
+            Some(vec![vec![(0.0, 0.0), (1.0, 0.0), (0.5, 1.0)]])
+        );
+        assert!(RunConfig::from_json(r#"{"mesh": {"holes": [[[0, 0, 1]]]}}"#).is_err());
+    }
+
+    #[test]
+    fn test_physics_overrides() {
+        let config = RunConfig::from_json(r#"{"physics": {"gravity": 1.62}}"#).unwrap();
+        let constants = config.physics.constants().unwrap();
+        assert_eq!(constants.gravity, 1.62);
+        assert_eq!(constants.density, PhysicalConstants::default().density);
+        let config = RunConfig::from_json(r#"{"physics": {"friction_depth": 1e-3}}"#).unwrap();
+        assert_eq!(config.physics.constants().unwrap().friction_depth, 1e-3);
+
+        let negative = RunConfig::from_json(r#"{"physics": {"density": -1.0}}"#).unwrap();
+        assert!(negative.physics.constants().is_err());
+        assert!(RunConfig::from_json(r#"{"physics": {"g": 9.81}}"#).is_err());
+    }
+
+    #[test]
+    fn test_timestep_bounds() {
+        assert!(RunConfig::default().timestep.control().unwrap().is_none());
+        let config = RunConfig::from_json(r#"{"timestep": {"max_dt": 0.5}}"#).unwrap();
+        let control = config.timestep.control().unwrap().unwrap();
+        assert_eq!((control.min_dt, control.max_dt), (0.0, 0.5));
+        assert_eq!(control.max_growth, 1.5);
+
+        let inverted = RunConfig::from_json(r#"{"timestep": {"min_dt": 1, "max_dt": 0.5}}"#);
+        assert!(inverted.unwrap().timestep.control().is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let text = r#"{"mesh": {"holes": [[[0, 0], [1, 0], [0.5, 1]]]},
+                       "initial": {"velocity": {"type": "vortex", "center": [5, 5], "radius": 1, "speed": 0.3}},
+                       "output": {"fields": ["h", "eta", "tau"]},
+                       "physics": {"gravity": 3.71}}"#;
+        let config = RunConfig::from_json(text).unwrap();
+        let restored = RunConfig::from_json(&config.to_json()).unwrap();
+        assert_eq!(restored.mesh.holes, config.mesh.holes);
+        assert_eq!(restored.output.fields, config.output.fields);
+        assert_eq!(restored.initial.velocity, config.initial.velocity);
+        let solitary = RunConfig::from_json(
+            r#"{"initial": {"condition": "solitary-wave", "center": [3, 0]}}"#,
+        )
+        .unwrap();
+        assert_eq!(solitary.initial.condition.as_deref(), Some("solitary-wave"));
+        assert_eq!(solitary.initial.center, Some((3.0, 0.0)));
+        assert!(
+            RunConfig::from_json(r#"{"initial": {"velocity": {"type": "uniform", "u": 1}}}"#)
+                .is_err()
+        );
+        assert_eq!(restored.physics.gravity, Some(3.71));
+        assert_eq!(restored.physics.density, None);
+    }
+}
@@ -0,0 +1,185 @@
+/// Web dashboard (feature "serve")
+/// A small HTTP server running next to the solver. `GET /` returns the bundled
+/// HTML viewer, which opens a WebSocket on the same address and receives a
+/// JSON snapshot at every output time: run statistics and the depth resampled
+/// onto a coarse grid. New viewers get the latest snapshot immediately.
+///
+/// Connections are served from one background thread; the time loop only
+/// pays for encoding the snapshot and writing it to the open sockets. Viewers
+/// that stop reading are dropped once a write times out.
+use crate::raster::{self, RasterGrid, NODATA};
+use crate::simulation::{Observer, StepEvent};
+use crate::solver::ShallowWaterSolver;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+const VIEWER_HTML: &str = include_str!("web/dashboard.html");
+/// Columns of the downsampled depth grid sent to viewers
+const SNAPSHOT_WIDTH: usize = 200;
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct Shared {
+    clients: Vec<WebSocket<TcpStream>>,
+    latest: Option<String>,
+}
+
+pub struct Dashboard {
+    address: SocketAddr,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Dashboard {
+    /// Bind `address` (e.g. "0.0.0.0:8080") and start accepting viewers
+    pub fn start(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address).map_err(|e| format!("{}: {}", address, e))?;
+        let address = listener.local_addr().map_err(|e| e.to_string())?;
+        let shared = Arc::new(Mutex::new(Shared::default()));
+
+        let accept_shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = handle_connection(stream, &accept_shared) {
+                    eprintln!("Warning: dashboard connection failed: {}", e);
+                }
+            }
+        });
+
+        Ok(Dashboard { address, shared })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Send the current state to all connected viewers
+    pub fn publish(&self, solver: &ShallowWaterSolver, steps: usize, mass_error: f64) {
+        let message = snapshot(solver, steps, mass_error);
+        let mut shared = self.shared.lock().unwrap();
+        shared
+            .clients
+            .retain_mut(|socket| socket.send(Message::text(message.clone())).is_ok());
+        shared.latest = Some(message);
+    }
+}
+
+impl Observer for Dashboard {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        if event.common {
+            self.publish(solver, event.step, event.mass_error(solver));
+        }
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, shared: &Mutex<Shared>) -> io::Result<()> {
+    stream.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+    stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+
+    // Look at the request headers without consuming them, so that the
+    // WebSocket handshake can still read the full request
+    let mut head = [0u8; 4096];
+    let n = stream.peek(&mut head)?;
+    let request = String::from_utf8_lossy(&head[..n]).to_ascii_lowercase();
+
+    if request.contains("upgrade: websocket") {
+        let mut socket =
+            tungstenite::accept(stream).map_err(|e| io::Error::other(e.to_string()))?;
+        let mut shared = shared.lock().unwrap();
+        if let Some(latest) = &shared.latest {
+            socket
+                .send(Message::text(latest.clone()))
+                .map_err(|e| io::Error::other(e.to_string()))?;
+        }
+        shared.clients.push(socket);
+        return Ok(());
+    }
+
+    let _ = stream.read(&mut head)?;
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let (status, content_type, body) = match path {
+        "/" | "/index.html" => ("200 OK", "text/html; charset=utf-8", VIEWER_HTML),
+        _ => ("404 Not Found", "text/plain", "Not found\n"),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// JSON message with run statistics and the downsampled depth (row-major,
+/// north row first, null outside the mesh)
+fn snapshot(solver: &ShallowWaterSolver, steps: usize, mass_error: f64) -> String {
+    let mesh = &solver.mesh;
+    let (x_min, x_max) = mesh
+        .nodes
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), n| {
+            (lo.min(n.x), hi.max(n.x))
+        });
+    let grid = RasterGrid::covering(mesh, Some((x_max - x_min) / SNAPSHOT_WIDTH as f64));
+    let depth: Vec<Option<f32>> = raster::rasterize(mesh, &solver.state.h, &grid)
+        .into_iter()
+        .map(|h| (h != NODATA).then(|| (h * 1000.0).round() / 1000.0))
+        .collect();
+
+    let dry = solver.constants.dry_tolerance;
+    let wet_cells = solver.state.h.iter().filter(|&&h| h > dry).count();
+    let max_depth = solver.state.h.iter().copied().fold(0.0, f64::max);
+
+    serde_json::json!({
+        "time": solver.time,
+        "dt": solver.dt,
+        "steps": steps,
+        "mass_error": mass_error,
+        "max_depth": max_depth,
+        "wet_cells": wet_cells,
+        "cells": mesh.triangles.len(),
+        "ncols": grid.ncols,
+        "nrows": grid.nrows,
+        "depth": depth,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_viewer_page_and_websocket_snapshot() {
+        let mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 5.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(1.5);
+
+        let dashboard = Dashboard::start("127.0.0.1:0").unwrap();
+        dashboard.publish(&solver, 7, 0.0);
+
+        let mut http = TcpStream::connect(dashboard.address()).unwrap();
+        http.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut page = String::new();
+        http.read_to_string(&mut page).unwrap();
+        assert!(page.starts_with("HTTP/1.1 200 OK"));
+        assert!(page.contains("WebSocket"));
+
+        // A viewer connecting after the publish still gets the latest state
+        let url = format!("ws://{}/", dashboard.address());
+        let (mut socket, _) = tungstenite::connect(url.as_str()).unwrap();
+        let message = socket.read().unwrap().into_text().unwrap();
+        let snapshot: serde_json::Value = serde_json::from_str(&message).unwrap();
+        assert_eq!(snapshot["steps"], 7);
+        assert_eq!(snapshot["ncols"], SNAPSHOT_WIDTH);
+        assert_eq!(snapshot["nrows"], SNAPSHOT_WIDTH / 2);
+        assert_eq!(snapshot["depth"][0], 1.5);
+    }
+}
@@ -0,0 +1,285 @@
+/// SQLite results database shared by the runs of a campaign
+/// Every run adds one row to `runs` and keys its rows in the other tables by
+/// that id, so hundreds of runs of a parameter study can be compared with
+/// plain SQL, e.g.
+///
+/// ```sql
+/// SELECT p.value AS manning_n, m.value AS mass_error
+/// FROM parameters p JOIN metrics m USING (run_id)
+/// WHERE p.name = 'manning_n' AND m.name = 'mass_error';
+/// ```
+///
+/// Runs can write to the same file at the same time: the database is in WAL
+/// mode and writers wait for each other's locks.
+use crate::solver::Budget;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection};
+use std::time::Duration;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    started TEXT NOT NULL DEFAULT (datetime('now')),
+    prefix TEXT NOT NULL,
+    command TEXT NOT NULL,
+    cells INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'running',
+    steps INTEGER,
+    simulated_time REAL,
+    wall_seconds REAL
+);
+CREATE TABLE IF NOT EXISTS parameters (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    name TEXT NOT NULL,
+    value,
+    PRIMARY KEY (run_id, name)
+);
+CREATE TABLE IF NOT EXISTS metrics (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    name TEXT NOT NULL,
+    value REAL,
+    PRIMARY KEY (run_id, name)
+);
+CREATE TABLE IF NOT EXISTS gauges (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    time REAL NOT NULL,
+    gauge TEXT NOT NULL,
+    depth REAL,
+    level REAL,
+    u REAL,
+    v REAL
+);
+CREATE INDEX IF NOT EXISTS gauges_run ON gauges (run_id, gauge, time);
+";
+
+/// How a run ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunStatus {
+    Completed,
+    Diverged,
+    TimeLimit, // Stopped by --max-walltime
+}
+
+impl RunStatus {
+    fn name(self) -> &'static str {
+        match self {
+            RunStatus::Completed => "completed",
+            RunStatus::Diverged => "diverged",
+            RunStatus::TimeLimit => "time-limit",
+        }
+    }
+}
+
+/// Closing record of a run
+#[derive(Debug, Clone)]
+pub struct RunSummary<'a> {
+    pub status: RunStatus,
+    pub steps: usize,
+    pub simulated_time: f64,
+    pub wall_seconds: f64,
+    /// Conservation and result metrics, e.g. ("mass_error", 1e-12)
+    pub metrics: &'a [(&'a str, f64)],
+}
+
+pub struct ResultsDatabase {
+    connection: Connection,
+    run: Option<i64>,
+}
+
+impl ResultsDatabase {
+    /// Open or create the database at `path` with its tables
+    pub fn open(path: &str) -> Result<Self, String> {
+        let connection = Connection::open(path).map_err(|e| e.to_string())?;
+        connection
+            .busy_timeout(Duration::from_secs(60))
+            .map_err(|e| e.to_string())?;
+        let statistics: Vec<String> = Budget::CSV_HEADER
+            .split(',')
+            .map(|name| format!("    {} REAL", name))
+            .collect();
+        connection
+            .query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+            .and_then(|_| connection.execute_batch(SCHEMA))
+            .and_then(|_| {
+                connection.execute_batch(&format!(
+                    "CREATE TABLE IF NOT EXISTS statistics (\n    run_id INTEGER NOT NULL REFERENCES runs(id),\n{}\n);\nCREATE INDEX IF NOT EXISTS statistics_run ON statistics (run_id, time);",
+                    statistics.join(",\n")
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            connection,
+            run: None,
+        })
+    }
+
+    /// Add a run and its parameters and make it the current run. Values that
+    /// parse as numbers are stored as REAL so they can be compared in queries.
+    pub fn begin_run(
+        &mut self,
+        prefix: &str,
+        command: &str,
+        cells: usize,
+        parameters: &[(&str, String)],
+    ) -> Result<i64, String> {
+        let transaction = self.connection.transaction().map_err(|e| e.to_string())?;
+        transaction
+            .execute(
+                "INSERT INTO runs (prefix, command, cells) VALUES (?1, ?2, ?3)",
+                params![prefix, command, cells as i64],
+            )
+            .map_err(|e| e.to_string())?;
+        let run = transaction.last_insert_rowid();
+        for (name, value) in parameters {
+            let value = match value.parse::<f64>() {
+                Ok(number) => Value::Real(number),
+                Err(_) => Value::Text(value.clone()),
+            };
+            transaction
+                .execute(
+                    "INSERT INTO parameters (run_id, name, value) VALUES (?1, ?2, ?3)",
+                    params![run, name, value],
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        transaction.commit().map_err(|e| e.to_string())?;
+        self.run = Some(run);
+        Ok(run)
+    }
+
+    fn current_run(&self) -> Result<i64, String> {
+        self.run
+            .ok_or_else(|| "no run has been started".to_string())
+    }
+
+    /// Row of the statistics table for the current run
+    pub fn push_statistics(&mut self, budget: &Budget) -> Result<(), String> {
+        let run = self.current_run()?;
+        let values = budget.values();
+        let placeholders = vec!["?"; values.len() + 1].join(",");
+        self.connection
+            .prepare_cached(&format!("INSERT INTO statistics VALUES ({})", placeholders))
+            .and_then(|mut statement| {
+                let row =
+                    std::iter::once(Value::Integer(run)).chain(values.into_iter().map(Value::Real));
+                statement.execute(params_from_iter(row))
+            })
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Depth, level and velocity (u, v) of each named gauge at `time`
+    pub fn push_gauges(&mut self, time: f64, readings: &[(&str, [f64; 4])]) -> Result<(), String> {
+        let run = self.current_run()?;
+        let transaction = self.connection.transaction().map_err(|e| e.to_string())?;
+        {
+            let mut statement = transaction
+                .prepare_cached("INSERT INTO gauges VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")
+                .map_err(|e| e.to_string())?;
+            for (name, [h, level, u, v]) in readings {
+                statement
+                    .execute(params![run, time, name, h, level, u, v])
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        transaction.commit().map_err(|e| e.to_string())
+    }
+
+    /// Record how the current run ended. A run that never finishes keeps the
+    /// status 'running'.
+    pub fn finish_run(&mut self, summary: &RunSummary) -> Result<(), String> {
+        let run = self.current_run()?;
+        let transaction = self.connection.transaction().map_err(|e| e.to_string())?;
+        transaction
+            .execute(
+                "UPDATE runs SET status = ?2, steps = ?3, simulated_time = ?4, wall_seconds = ?5 WHERE id = ?1",
+                params![
+                    run,
+                    summary.status.name(),
+                    summary.steps as i64,
+                    summary.simulated_time,
+                    summary.wall_seconds
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        for (name, value) in summary.metrics {
+            transaction
+                .execute(
+                    "INSERT OR REPLACE INTO metrics (run_id, name, value) VALUES (?1, ?2, ?3)",
+                    params![run, name, value],
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        transaction.commit().map_err(|e| e.to_string())?;
+        self.run = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::{BudgetTracker, FrictionLaw, ShallowWaterSolver};
+
+    #[test]
+    fn test_runs_share_one_database() {
+        let path = std::env::temp_dir().join(format!("swe_results_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        for (n, mass_error) in [(0.03, 1e-12), (0.05, 2e-12)] {
+            let mut database = ResultsDatabase::open(path).unwrap();
+            assert!(database.push_gauges(0.0, &[]).is_err());
+            database
+                .begin_run(
+                    "out",
+                    "shallow-water-solver --manning-n",
+                    24,
+                    &[("manning_n", n.to_string()), ("friction", "manning".into())],
+                )
+                .unwrap();
+            let mesh = TriangularMesh::new_rectangular(3, 3, 2.0, 2.0, TopographyType::Flat);
+            let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+            solver.set_dam_break(1.0);
+            database
+                .push_statistics(&BudgetTracker::new(&solver).budget(&solver))
+                .unwrap();
+            database
+                .push_gauges(1.0, &[("g1", [0.5, 1.5, 0.1, 0.0]), ("g2", [0.0; 4])])
+                .unwrap();
+            database
+                .finish_run(&RunSummary {
+                    status: RunStatus::Completed,
+                    steps: 10,
+                    simulated_time: 1.0,
+                    wall_seconds: 0.1,
+                    metrics: &[("mass_error", mass_error)],
+                })
+                .unwrap();
+        }
+
+        let connection = Connection::open(path).unwrap();
+        let count = |sql: &str| -> i64 { connection.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(
+            count("SELECT COUNT(*) FROM runs WHERE status = 'completed'"),
+            2
+        );
+        assert_eq!(count("SELECT COUNT(*) FROM gauges"), 4);
+        assert_eq!(count("SELECT COUNT(*) FROM statistics WHERE mass > 0"), 2);
+        let worst: f64 = connection
+            .query_row(
+                "SELECT p.value FROM parameters p JOIN metrics m USING (run_id)
+                 WHERE p.name = 'manning_n' AND m.name = 'mass_error'
+                 ORDER BY m.value DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(worst, 0.05);
+        drop(connection);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+}
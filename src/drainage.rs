@@ -0,0 +1,418 @@
+/// Dual drainage: coupling with a 1D sewer model such as EPA-SWMM
+/// Surface water enters the sewer network at inlets (manholes, gullies),
+/// each linked to the cell containing it and to one network node. After
+/// every solver step the exchange discharge at each inlet is computed from
+/// the surface water level and the node head. While the head is below the
+/// ground, water drains freely over a weir (the inlet perimeter) or, when the
+/// surface water is deeper, through an orifice (the inlet area). Once the
+/// head rises above the ground the inlet acts as a submerged orifice driven
+/// by the head difference. When the head is above the water surface, the
+/// network surcharges and water flows back onto the surface. The drained
+/// volume is limited to the water in the cell and, for a submerged inlet, to
+/// the volume that brings the cell level to the head, which keeps the
+/// explicit exchange from oscillating. The discharges, averaged
+/// over the step, are passed to the network, which advances by the same
+/// step and returns the new node heads (explicit, loosely coupled exchange).
+///
+/// `SewerNetwork` is the coupling interface: library users can implement it
+/// on top of the SWMM engine API (`swmm_step`, `swmm_setValue`,
+/// `swmm_getValue`). `ExternalSewer` exchanges the same data with a child
+/// process over its standard input and output, e.g. a pyswmm script (see
+/// the documentation for the line protocol).
+use crate::simulation::Coupling;
+use crate::solver::ShallowWaterSolver;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// Default discharge coefficient of the free weir
+pub const WEIR_COEFFICIENT: f64 = 0.5;
+
+/// Default discharge coefficient of the orifice
+pub const ORIFICE_COEFFICIENT: f64 = 0.6;
+
+/// Surface inlet connected to a network node of the same name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inlet {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+    pub perimeter: f64,           // Weir crest length (m)
+    pub area: f64,                // Orifice area (m^2)
+    pub weir_coefficient: f64,    // C_w in Q = C_w P h sqrt(2 g h)
+    pub orifice_coefficient: f64, // C_o in Q = C_o A sqrt(2 g Δh)
+}
+
+impl Inlet {
+    /// Discharge into the network (m^3/s; negative when it surcharges) for
+    /// surface depth `h` over ground `z` and node head `head`
+    pub fn discharge(&self, h: f64, z: f64, head: f64, gravity: f64) -> f64 {
+        let level = z + h;
+        if head < z {
+            let weir = self.weir_coefficient * self.perimeter * h * (2.0 * gravity * h).sqrt();
+            let orifice = self.orifice_coefficient * self.area * (2.0 * gravity * h).sqrt();
+            weir.min(orifice)
+        } else {
+            let difference = level - head;
+            difference.signum()
+                * self.orifice_coefficient
+                * self.area
+                * (2.0 * gravity * difference.abs()).sqrt()
+        }
+    }
+}
+
+/// Parse inlets "name,x,y,perimeter,area[,weir_coefficient,orifice_coefficient]"
+/// (header line and lines starting with '#' are skipped)
+pub fn parse_inlets(text: &str) -> Result<Vec<Inlet>, String> {
+    let mut inlets = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || (number == 0 && line.starts_with("name")) {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != 5 && fields.len() != 7 {
+            return Err(format!(
+                "line {}: expected name,x,y,perimeter,area[,weir,orifice] but found '{}'",
+                number + 1,
+                line
+            ));
+        }
+        let value = |k: usize| {
+            fields[k]
+                .parse::<f64>()
+                .map_err(|_| format!("line {}: invalid number '{}'", number + 1, fields[k]))
+        };
+        let (weir_coefficient, orifice_coefficient) = if fields.len() == 7 {
+            (value(5)?, value(6)?)
+        } else {
+            (WEIR_COEFFICIENT, ORIFICE_COEFFICIENT)
+        };
+        let inlet = Inlet {
+            name: fields[0].to_string(),
+            x: value(1)?,
+            y: value(2)?,
+            perimeter: value(3)?,
+            area: value(4)?,
+            weir_coefficient,
+            orifice_coefficient,
+        };
+        if [
+            inlet.perimeter,
+            inlet.area,
+            weir_coefficient,
+            orifice_coefficient,
+        ]
+        .iter()
+        .any(|v| v.is_nan() || *v <= 0.0)
+        {
+            return Err(format!(
+                "line {}: perimeter, area and coefficients must be positive",
+                number + 1
+            ));
+        }
+        inlets.push(inlet);
+    }
+    if inlets.is_empty() {
+        return Err("no inlets found".to_string());
+    }
+    Ok(inlets)
+}
+
+pub fn read_inlets(path: &str) -> Result<Vec<Inlet>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse_inlets(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// 1D drainage network driven by the surface model
+pub trait SewerNetwork {
+    /// Advance from `time` by `dt` with the mean discharge of every inlet
+    /// into its node (m^3/s, negative for water returned to the surface)
+    /// and return the node heads (m) at the end; `dt` is zero for the
+    /// initial heads
+    fn advance(&mut self, time: f64, dt: f64, inflows: &[f64]) -> Result<Vec<f64>, String>;
+}
+
+/// Sewer model running in a child process
+/// The solver writes one line per exchange to the child's standard input
+/// and reads one line back: first `NODES <name>...`, then
+/// `STEP <time> <dt> <inflow>...` and finally `END`. The child answers
+/// `NODES` and every `STEP` with `HEADS <head>...` in the same node order.
+pub struct ExternalSewer {
+    child: Child,
+    input: BufWriter<ChildStdin>,
+    output: BufReader<ChildStdout>,
+    nodes: usize,
+    line: String,
+}
+
+impl ExternalSewer {
+    /// Start `command` through the shell and send it the node names
+    pub fn spawn(command: &str, nodes: &[String]) -> Result<Self, String> {
+        if let Some(name) = nodes.iter().find(|n| n.contains(char::is_whitespace)) {
+            return Err(format!("node name '{}' contains whitespace", name));
+        }
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let mut child = Command::new(shell)
+            .args([flag, command])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("{}: {}", command, e))?;
+        let input = BufWriter::new(child.stdin.take().unwrap());
+        let output = BufReader::new(child.stdout.take().unwrap());
+        let mut sewer = ExternalSewer {
+            child,
+            input,
+            output,
+            nodes: nodes.len(),
+            line: String::new(),
+        };
+        sewer.send(&format!("NODES {}", nodes.join(" ")))?;
+        Ok(sewer)
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.input, "{}", line)
+            .and_then(|_| self.input.flush())
+            .map_err(|e| format!("sewer process: {}", e))
+    }
+
+    fn receive(&mut self) -> Result<Vec<f64>, String> {
+        self.line.clear();
+        let read = self
+            .output
+            .read_line(&mut self.line)
+            .map_err(|e| format!("sewer process: {}", e))?;
+        if read == 0 {
+            return Err("sewer process exited".to_string());
+        }
+        let mut words = self.line.split_whitespace();
+        if words.next() != Some("HEADS") {
+            return Err(format!(
+                "sewer process: expected HEADS but got '{}'",
+                self.line.trim()
+            ));
+        }
+        let heads: Vec<f64> = words
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("sewer process: invalid heads '{}'", self.line.trim()))?;
+        if heads.len() != self.nodes {
+            return Err(format!(
+                "sewer process: expected {} heads but got {}",
+                self.nodes,
+                heads.len()
+            ));
+        }
+        Ok(heads)
+    }
+
+    /// Tell the process to finish and wait for it
+    pub fn finish(mut self) -> Result<(), String> {
+        self.send("END")?;
+        let status = self.child.wait().map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err(format!("sewer process exited with {}", status));
+        }
+        Ok(())
+    }
+}
+
+impl SewerNetwork for ExternalSewer {
+    fn advance(&mut self, time: f64, dt: f64, inflows: &[f64]) -> Result<Vec<f64>, String> {
+        if dt > 0.0 {
+            let mut line = format!("STEP {} {}", time, dt);
+            for q in inflows {
+                line.push_str(&format!(" {}", q));
+            }
+            self.send(&line)?;
+        }
+        self.receive()
+    }
+}
+
+/// Inlets of the surface mesh coupled to a sewer network
+pub struct DualDrainage<N: SewerNetwork> {
+    pub inlets: Vec<Inlet>,
+    pub network: N,
+    cells: Vec<usize>,
+    pub heads: Vec<f64>,     // Node heads after the last exchange (m)
+    pub discharge: Vec<f64>, // Mean inlet discharge of the last exchange (m^3/s)
+    pub drained: f64,        // Total volume taken from the surface (m^3)
+    pub returned: f64,       // Total volume surcharged onto the surface (m^3)
+}
+
+impl<N: SewerNetwork> DualDrainage<N> {
+    /// Link every inlet to the cell containing it and fetch the initial
+    /// heads from the network
+    pub fn new(
+        solver: &ShallowWaterSolver,
+        inlets: Vec<Inlet>,
+        mut network: N,
+    ) -> Result<Self, String> {
+        let mut cells = Vec::with_capacity(inlets.len());
+        for inlet in &inlets {
+            match solver.locate(inlet.x, inlet.y) {
+                Some(cell) => cells.push(cell),
+                None => {
+                    return Err(format!(
+                        "inlet '{}' at ({}, {}) lies outside the mesh",
+                        inlet.name, inlet.x, inlet.y
+                    ))
+                }
+            }
+        }
+        let zeros = vec![0.0; inlets.len()];
+        let heads = network.advance(solver.time, 0.0, &zeros)?;
+        if heads.len() != inlets.len() {
+            return Err(format!(
+                "network returned {} heads for {} inlets",
+                heads.len(),
+                inlets.len()
+            ));
+        }
+        Ok(DualDrainage {
+            inlets,
+            network,
+            cells,
+            heads,
+            discharge: zeros,
+            drained: 0.0,
+            returned: 0.0,
+        })
+    }
+
+    /// Exchange water over the step just taken by `solver` and advance the
+    /// network to the solver time
+    pub fn exchange(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        let dt = solver.dt;
+        let gravity = solver.constants.gravity;
+        for (k, inlet) in self.inlets.iter().enumerate() {
+            let i = self.cells[k];
+            let h = solver.state.h[i];
+            let z = solver.mesh.triangles[i].z_bed;
+            let storage = solver.storage_area(i);
+            let q = inlet.discharge(h, z, self.heads[k], gravity);
+            // The cell cannot lose more than it holds (inlets sharing a
+            // cell take from what the previous ones left), and a submerged
+            // inlet cannot move its level past the head
+            let mut volume = (q * dt).min(h * storage);
+            if self.heads[k] >= z {
+                let gap = (z + h - self.heads[k]) * storage;
+                volume = if gap >= 0.0 {
+                    volume.min(gap)
+                } else {
+                    volume.max(gap)
+                };
+            }
+            solver.state.h[i] = h - volume / storage;
+            if solver.state.h[i] < solver.constants.dry_tolerance {
+                solver.state.hu[i] = 0.0;
+                solver.state.hv[i] = 0.0;
+            }
+            if volume >= 0.0 {
+                self.drained += volume;
+            } else {
+                self.returned -= volume;
+            }
+            solver.record_coupled_volume(-volume);
+            self.discharge[k] = if dt > 0.0 { volume / dt } else { 0.0 };
+        }
+        self.heads = self
+            .network
+            .advance(solver.time - dt, dt, &self.discharge)?;
+        Ok(())
+    }
+}
+
+impl Coupling for DualDrainage<ExternalSewer> {
+    fn name(&self) -> String {
+        "sewer exchange".to_string()
+    }
+
+    fn after_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        self.exchange(solver)
+    }
+
+    fn report(&self) -> Vec<String> {
+        vec![format!(
+            "Sewer exchange: {:.3} m^3 drained, {:.3} m^3 returned by surcharge",
+            self.drained, self.returned
+        )]
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        self.network.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    /// Storage node with a flat bottom at -2 m and a plan area of 4 m^2
+    struct Tank {
+        volume: f64,
+    }
+
+    impl SewerNetwork for Tank {
+        fn advance(&mut self, _time: f64, dt: f64, inflows: &[f64]) -> Result<Vec<f64>, String> {
+            self.volume += inflows.iter().sum::<f64>() * dt;
+            Ok(vec![-2.0 + self.volume / 4.0; inflows.len()])
+        }
+    }
+
+    #[test]
+    fn test_drainage_conserves_volume() {
+        let inlets = parse_inlets(
+            "name,x,y,perimeter,area\n\
+             # gully\n\
+             J1,5,5,2.0,0.25\n",
+        )
+        .unwrap();
+        assert_eq!(inlets[0].weir_coefficient, WEIR_COEFFICIENT);
+        assert!(parse_inlets("J1,5,5,2.0").is_err());
+        assert!(parse_inlets("J1,5,5,2.0,-1").is_err());
+
+        let mesh = TriangularMesh::new_rectangular(10, 10, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.iter_mut().for_each(|h| *h = 0.1);
+        let initial = solver.compute_total_mass();
+        let mut drainage = DualDrainage::new(&solver, inlets, Tank { volume: 0.0 }).unwrap();
+
+        // Free drainage, then a submerged inlet once the tank rises above
+        // the ground
+        for _ in 0..2000 {
+            solver.step();
+            drainage.exchange(&mut solver).unwrap();
+        }
+        let stored = drainage.network.volume;
+        assert!(drainage.drained > 8.0);
+        assert!((drainage.drained - drainage.returned - stored).abs() < 1e-9);
+        assert!((solver.compute_total_mass() + stored - initial).abs() < 1e-9 * initial);
+
+        // At rest the head equals the surface water level
+        let level = solver.state.h[drainage.cells[0]];
+        assert!(
+            (drainage.heads[0] - level).abs() < 0.01,
+            "{}",
+            drainage.heads[0]
+        );
+
+        // Surcharge returns water to the surface
+        assert!(drainage.inlets[0].discharge(0.1, 0.0, 0.5, 9.81) < 0.0);
+
+        let outside = Inlet {
+            x: 20.0,
+            ..drainage.inlets[0].clone()
+        };
+        assert!(DualDrainage::new(&solver, vec![outside], Tank { volume: 0.0 }).is_err());
+    }
+}
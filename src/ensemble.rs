@@ -0,0 +1,253 @@
+/// Ensemble runs with perturbed parameters
+/// Every member repeats the configured run with its own Manning coefficient
+/// and initial-condition amplitude, drawn by Latin hypercube sampling so that
+/// small ensembles still cover the parameter ranges evenly. Members run in
+/// parallel and only their maximum-depth envelopes are kept, folded into
+/// per-cell statistics as they finish.
+use crate::output::{FloodEnvelope, ARRIVAL_DEPTH};
+use crate::simulation::Simulation;
+use crate::solver::ShallowWaterSolver;
+use rayon::prelude::*;
+
+/// Sampled parameters of one ensemble member
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemberParameters {
+    pub member: usize,          // Member number, e.g. to vary perturbation seeds
+    pub manning_n: Option<f64>, // None: friction as configured
+    pub amplitude: f64,         // Factor on the initial-condition perturbation
+}
+
+/// Per-cell statistics of the maximum depth over all members
+#[derive(Debug, Clone)]
+pub struct EnsembleStats {
+    pub members: usize,
+    pub thresholds: Vec<f64>,
+    sum: Vec<f64>,
+    sum_sq: Vec<f64>,
+    exceedances: Vec<Vec<usize>>, // Per threshold: members exceeding it per cell
+}
+
+impl EnsembleStats {
+    pub fn new(n_cells: usize, thresholds: &[f64]) -> Self {
+        EnsembleStats {
+            members: 0,
+            thresholds: thresholds.to_vec(),
+            sum: vec![0.0; n_cells],
+            sum_sq: vec![0.0; n_cells],
+            exceedances: vec![vec![0; n_cells]; thresholds.len()],
+        }
+    }
+
+    pub fn add(&mut self, max_depth: &[f64]) {
+        self.members += 1;
+        for (i, &h) in max_depth.iter().enumerate() {
+            self.sum[i] += h;
+            self.sum_sq[i] += h * h;
+        }
+        for (threshold, counts) in self.thresholds.iter().zip(&mut self.exceedances) {
+            for (count, &h) in counts.iter_mut().zip(max_depth) {
+                if h > *threshold {
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    pub fn merge(mut self, other: EnsembleStats) -> Self {
+        self.members += other.members;
+        for (a, b) in self.sum.iter_mut().zip(&other.sum) {
+            *a += b;
+        }
+        for (a, b) in self.sum_sq.iter_mut().zip(&other.sum_sq) {
+            *a += b;
+        }
+        for (counts, other) in self.exceedances.iter_mut().zip(&other.exceedances) {
+            for (a, b) in counts.iter_mut().zip(other) {
+                *a += b;
+            }
+        }
+        self
+    }
+
+    pub fn mean(&self) -> Vec<f64> {
+        let n = self.members.max(1) as f64;
+        self.sum.iter().map(|s| s / n).collect()
+    }
+
+    /// Sample standard deviation (zero for fewer than two members)
+    pub fn std_dev(&self) -> Vec<f64> {
+        if self.members < 2 {
+            return vec![0.0; self.sum.len()];
+        }
+        let n = self.members as f64;
+        self.sum
+            .iter()
+            .zip(&self.sum_sq)
+            .map(|(s, sq)| ((sq - s * s / n) / (n - 1.0)).max(0.0).sqrt())
+            .collect()
+    }
+
+    /// Fraction of members whose maximum depth exceeds threshold `k`
+    pub fn exceedance_probability(&self, k: usize) -> Vec<f64> {
+        let n = self.members.max(1) as f64;
+        self.exceedances[k].iter().map(|&c| c as f64 / n).collect()
+    }
+}
+
+/// Parse a sampling interval "min,max" (min = max fixes the parameter)
+pub fn parse_interval(spec: &str) -> Result<(f64, f64), String> {
+    let (lo, hi) = spec
+        .split_once(',')
+        .ok_or_else(|| format!("expected min,max but found '{}'", spec))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<f64>()
+            .map_err(|_| format!("invalid number '{}'", v.trim()))
+    };
+    let (lo, hi) = (parse(lo)?, parse(hi)?);
+    if hi < lo {
+        return Err(format!("interval {},{} has max < min", lo, hi));
+    }
+    Ok((lo, hi))
+}
+
+/// Comma-separated list of depth thresholds in m
+pub fn parse_thresholds(list: &str) -> Result<Vec<f64>, String> {
+    list.split(',')
+        .map(|v| match v.trim().parse::<f64>() {
+            Ok(t) if t >= 0.0 => Ok(t),
+            _ => Err(format!("invalid depth threshold '{}'", v.trim())),
+        })
+        .collect()
+}
+
+/// Latin hypercube sample of `members` parameter sets
+pub fn sample(
+    members: usize,
+    manning_n: Option<(f64, f64)>,
+    amplitude: (f64, f64),
+    seed: u64,
+) -> Vec<MemberParameters> {
+    let mut rng = SplitMix64::new(seed);
+    let strata = |rng: &mut SplitMix64| -> Vec<f64> {
+        // One value per equal-probability stratum, strata in random order
+        let mut order: Vec<usize> = (0..members).collect();
+        for i in (1..members).rev() {
+            order.swap(i, (rng.next_f64() * (i + 1) as f64) as usize);
+        }
+        order
+            .into_iter()
+            .map(|k| (k as f64 + rng.next_f64()) / members as f64)
+            .collect()
+    };
+    let manning_u = strata(&mut rng);
+    let amplitude_u = strata(&mut rng);
+    let lerp = |(lo, hi): (f64, f64), u: f64| lo + u * (hi - lo);
+
+    (0..members)
+        .map(|m| MemberParameters {
+            member: m,
+            manning_n: manning_n.map(|range| lerp(range, manning_u[m])),
+            amplitude: lerp(amplitude, amplitude_u[m]),
+        })
+        .collect()
+}
+
+/// Run all members to `final_time` in parallel and aggregate their
+/// maximum depths. `make_solver` sets up the initial state of one member.
+pub fn run<F>(
+    members: &[MemberParameters],
+    final_time: f64,
+    thresholds: &[f64],
+    make_solver: F,
+) -> EnsembleStats
+where
+    F: Fn(&MemberParameters) -> ShallowWaterSolver + Sync,
+{
+    let n_cells = make_solver(&members[0]).mesh.triangles.len();
+    members
+        .par_iter()
+        .map(|parameters| {
+            let mut solver = make_solver(parameters);
+            let mut envelope = FloodEnvelope::new(&solver, ARRIVAL_DEPTH);
+            let mut simulation = Simulation::until(&mut solver, final_time);
+            simulation.add_observer(Box::new(&mut envelope));
+            simulation.run().expect("members have no couplings to fail");
+            drop(simulation);
+            envelope.max_depth
+        })
+        .fold(
+            || EnsembleStats::new(n_cells, thresholds),
+            |mut stats, max_depth| {
+                stats.add(&max_depth);
+                stats
+            },
+        )
+        .reduce(
+            || EnsembleStats::new(n_cells, thresholds),
+            EnsembleStats::merge,
+        )
+}
+
+/// Small deterministic generator so that a seed reproduces an ensemble
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform sample in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal sample (Box-Muller)
+    pub fn next_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64(); // (0, 1]
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latin_hypercube_covers_every_stratum() {
+        let members = sample(10, Some((0.01, 0.05)), (0.5, 1.5), 7);
+        assert_eq!(members, sample(10, Some((0.01, 0.05)), (0.5, 1.5), 7));
+
+        let mut strata: Vec<usize> = members
+            .iter()
+            .map(|m| ((m.manning_n.unwrap() - 0.01) / 0.04 * 10.0) as usize)
+            .collect();
+        strata.sort();
+        assert_eq!(strata, (0..10).collect::<Vec<_>>());
+        assert!(members.iter().all(|m| (0.5..1.5).contains(&m.amplitude)));
+    }
+
+    #[test]
+    fn test_statistics_of_maximum_depths() {
+        let mut stats = EnsembleStats::new(2, &[0.5]);
+        stats.add(&[0.0, 1.0]);
+        let mut other = EnsembleStats::new(2, &[0.5]);
+        other.add(&[0.0, 2.0]);
+        other.add(&[0.0, 0.0]);
+        let stats = stats.merge(other);
+
+        assert_eq!(stats.members, 3);
+        assert_eq!(stats.mean(), vec![0.0, 1.0]);
+        assert_eq!(stats.std_dev(), vec![0.0, 1.0]);
+        assert_eq!(stats.exceedance_probability(0), vec![0.0, 2.0 / 3.0]);
+    }
+}
@@ -0,0 +1,337 @@
+/// Flood-extent polygons (GeoJSON export)
+/// Cell depths are averaged onto the nodes by area and the depth field, linear
+/// over each triangle, is contoured at a threshold (marching triangles). The
+/// parts of the triangles deeper than the threshold are merged into polygons
+/// by tracing their outline: contour segments inside the mesh and the wet
+/// stretches of the mesh boundary. Exterior rings are counter-clockwise and
+/// holes (dry islands) clockwise, as RFC 7946 asks for.
+use crate::mesh::{point_in_polygon, Polygon, TriangularMesh};
+use crate::raster::RasterCrs;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Wet area bounded by an exterior ring, minus its holes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtentPolygon {
+    pub exterior: Polygon,
+    pub holes: Vec<Polygon>,
+}
+
+/// Outline vertex: a wet node or the threshold crossing on the edge between
+/// two nodes (smaller index first), so that neighbouring triangles agree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Vertex {
+    Node(usize),
+    Crossing(usize, usize),
+}
+
+/// Area-weighted average of the cell depths `h` around each node
+pub fn node_depths(mesh: &TriangularMesh, h: &[f64]) -> Vec<f64> {
+    let mut volume = vec![0.0; mesh.nodes.len()];
+    let mut area = vec![0.0; mesh.nodes.len()];
+    for (tri, &depth) in mesh.triangles.iter().zip(h) {
+        for &n in &tri.nodes {
+            volume[n] += tri.area * depth;
+            area[n] += tri.area;
+        }
+    }
+    volume
+        .iter()
+        .zip(&area)
+        .map(|(v, a)| if *a > 0.0 { v / a } else { 0.0 })
+        .collect()
+}
+
+/// Flood extent of the cell depths `h`: where the nodal depth exceeds `threshold`
+pub fn flood_extent(mesh: &TriangularMesh, h: &[f64], threshold: f64) -> Vec<ExtentPolygon> {
+    contour(mesh, &node_depths(mesh, h), threshold)
+}
+
+/// Polygons where the node field `values`, linear over each triangle, is
+/// above `threshold`
+pub fn contour(mesh: &TriangularMesh, values: &[f64], threshold: f64) -> Vec<ExtentPolygon> {
+    let wet = |n: usize| values[n] > threshold;
+    let boundary: HashSet<(usize, usize)> = mesh
+        .edges
+        .iter()
+        .filter(|e| e.right_triangle.is_none())
+        .map(|e| (e.nodes[0].min(e.nodes[1]), e.nodes[0].max(e.nodes[1])))
+        .collect();
+
+    // Outline segments with the wet side on the left
+    let mut segments: Vec<(Vertex, Vertex)> = Vec::new();
+    for tri in &mesh.triangles {
+        let [a, b, c] = tri.nodes;
+        let (pa, pb, pc) = (&mesh.nodes[a], &mesh.nodes[b], &mesh.nodes[c]);
+        let ccw = (pb.x - pa.x) * (pc.y - pa.y) - (pc.x - pa.x) * (pb.y - pa.y) > 0.0;
+        let nodes = if ccw { [a, b, c] } else { [a, c, b] };
+
+        // Wet part of the triangle, counter-clockwise
+        let mut clipped = Vec::with_capacity(4);
+        for k in 0..3 {
+            let (n0, n1) = (nodes[k], nodes[(k + 1) % 3]);
+            if wet(n0) {
+                clipped.push(Vertex::Node(n0));
+            }
+            if wet(n0) != wet(n1) {
+                clipped.push(Vertex::Crossing(n0.min(n1), n0.max(n1)));
+            }
+        }
+        for k in 0..clipped.len() {
+            let (from, to) = (clipped[k], clipped[(k + 1) % clipped.len()]);
+            let keep = match (from, to) {
+                (Vertex::Crossing(..), Vertex::Crossing(..)) => true,
+                _ => boundary.contains(&shared_edge(from, to)),
+            };
+            if keep {
+                segments.push((from, to));
+            }
+        }
+    }
+
+    let point = |v: Vertex| match v {
+        Vertex::Node(n) => (mesh.nodes[n].x, mesh.nodes[n].y),
+        Vertex::Crossing(n0, n1) => {
+            let (p0, p1) = (&mesh.nodes[n0], &mesh.nodes[n1]);
+            let t = (threshold - values[n0]) / (values[n1] - values[n0]);
+            (p0.x + t * (p1.x - p0.x), p0.y + t * (p1.y - p0.y))
+        }
+    };
+    let rings: Vec<Polygon> = trace_rings(&segments)
+        .into_iter()
+        .map(|ring| {
+            let mut points: Polygon = Vec::with_capacity(ring.len());
+            for v in ring {
+                let p = point(v);
+                if points.last() != Some(&p) {
+                    points.push(p);
+                }
+            }
+            if points.len() > 1 && points.first() == points.last() {
+                points.pop();
+            }
+            points
+        })
+        .filter(|ring| ring.len() >= 3 && signed_area(ring) != 0.0)
+        .collect();
+    assemble(rings)
+}
+
+/// Mesh edge holding a segment that runs along a triangle side
+fn shared_edge(from: Vertex, to: Vertex) -> (usize, usize) {
+    let ends = |v: Vertex| match v {
+        Vertex::Node(n) => vec![n],
+        Vertex::Crossing(n0, n1) => vec![n0, n1],
+    };
+    let mut nodes = ends(from);
+    nodes.extend(ends(to));
+    nodes.sort_unstable();
+    nodes.dedup();
+    (nodes[0], *nodes.last().unwrap())
+}
+
+/// Chain directed segments into closed rings
+fn trace_rings(segments: &[(Vertex, Vertex)]) -> Vec<Vec<Vertex>> {
+    let mut outgoing: HashMap<Vertex, Vec<usize>> = HashMap::new();
+    for (i, (from, _)) in segments.iter().enumerate() {
+        outgoing.entry(*from).or_default().push(i);
+    }
+    let mut used = vec![false; segments.len()];
+    let mut rings = Vec::new();
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let mut ring = vec![segments[start].0];
+        let mut at = segments[start].1;
+        while at != ring[0] {
+            ring.push(at);
+            let next = outgoing
+                .get(&at)
+                .and_then(|list| list.iter().copied().find(|&s| !used[s]));
+            match next {
+                Some(s) => {
+                    used[s] = true;
+                    at = segments[s].1;
+                }
+                None => break, // Open chain; cannot happen on a valid mesh
+            }
+        }
+        rings.push(ring);
+    }
+    rings
+}
+
+/// Twice the signed area, positive for counter-clockwise rings
+fn signed_area(ring: &[(f64, f64)]) -> f64 {
+    let mut sum = 0.0;
+    for (k, &(x0, y0)) in ring.iter().enumerate() {
+        let (x1, y1) = ring[(k + 1) % ring.len()];
+        sum += x0 * y1 - x1 * y0;
+    }
+    sum
+}
+
+/// Group the rings into polygons: each clockwise ring is a hole of the
+/// smallest counter-clockwise ring around it
+fn assemble(rings: Vec<Polygon>) -> Vec<ExtentPolygon> {
+    let (exteriors, holes): (Vec<Polygon>, Vec<Polygon>) =
+        rings.into_iter().partition(|r| signed_area(r) > 0.0);
+    let mut polygons: Vec<ExtentPolygon> = exteriors
+        .into_iter()
+        .map(|exterior| ExtentPolygon {
+            exterior,
+            holes: Vec::new(),
+        })
+        .collect();
+    for hole in holes {
+        let owner = polygons
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| point_in_polygon(hole[0], &p.exterior))
+            .min_by(|(_, p), (_, q)| signed_area(&p.exterior).total_cmp(&signed_area(&q.exterior)))
+            .map(|(i, _)| i);
+        if let Some(i) = owner {
+            polygons[i].holes.push(hole);
+        }
+    }
+    polygons
+}
+
+/// Area enclosed by the polygons, holes excluded (mesh coordinate units)
+pub fn total_area(polygons: &[ExtentPolygon]) -> f64 {
+    polygons
+        .iter()
+        .map(|p| {
+            let holes: f64 = p.holes.iter().map(|h| signed_area(h)).sum();
+            0.5 * (signed_area(&p.exterior) + holes)
+        })
+        .sum()
+}
+
+/// GeoJSON array of a closed ring
+fn ring_json(ring: &[(f64, f64)]) -> String {
+    let points: Vec<String> = ring
+        .iter()
+        .chain(&ring[..1])
+        .map(|(x, y)| format!("[{},{}]", x, y))
+        .collect();
+    format!("[{}]", points.join(","))
+}
+
+/// Write the polygons as a GeoJSON FeatureCollection with one MultiPolygon
+/// feature carrying `properties`. Coordinates are in the mesh system; a
+/// `crs` member names it unless it is WGS 84 longitude/latitude.
+pub fn write_geojson<W: Write>(
+    out: &mut W,
+    polygons: &[ExtentPolygon],
+    properties: &[(&str, f64)],
+    crs: RasterCrs,
+) -> io::Result<()> {
+    let code = match crs {
+        RasterCrs::Projected(code) => code,
+        RasterCrs::Geographic(4326) => None,
+        RasterCrs::Geographic(code) => Some(code),
+    };
+    write!(out, "{{\"type\":\"FeatureCollection\",")?;
+    if let Some(code) = code {
+        write!(
+            out,
+            "\"crs\":{{\"type\":\"name\",\"properties\":{{\"name\":\"urn:ogc:def:crs:EPSG::{}\"}}}},",
+            code
+        )?;
+    }
+    let properties: Vec<String> = properties
+        .iter()
+        .map(|(name, value)| format!("\"{}\":{}", name, value))
+        .collect();
+    writeln!(
+        out,
+        "\"features\":[{{\"type\":\"Feature\",\"properties\":{{{}}},",
+        properties.join(",")
+    )?;
+    write!(
+        out,
+        "\"geometry\":{{\"type\":\"MultiPolygon\",\"coordinates\":["
+    )?;
+    for (i, polygon) in polygons.iter().enumerate() {
+        let rings: Vec<String> = std::iter::once(&polygon.exterior)
+            .chain(&polygon.holes)
+            .map(|ring| ring_json(ring))
+            .collect();
+        let separator = if i + 1 < polygons.len() { "," } else { "" };
+        writeln!(out, "[{}]{}", rings.join(","), separator)?;
+    }
+    writeln!(out, "]}}}}]}}")
+}
+
+pub fn save_geojson(
+    filename: &str,
+    polygons: &[ExtentPolygon],
+    properties: &[(&str, f64)],
+    crs: RasterCrs,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_geojson(&mut file, polygons, properties, crs)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+
+    #[test]
+    fn test_contour_strip_and_island() {
+        let mesh = TriangularMesh::new_rectangular(20, 20, 10.0, 10.0, TopographyType::Flat);
+
+        // Linear field: the wet strip x > 3 is traced exactly
+        let values: Vec<f64> = mesh.nodes.iter().map(|n| n.x).collect();
+        let strip = contour(&mesh, &values, 3.1);
+        assert_eq!(strip.len(), 1);
+        assert!(strip[0].holes.is_empty());
+        assert!((total_area(&strip) - 69.0).abs() < 1e-9);
+        let xmin = strip[0]
+            .exterior
+            .iter()
+            .map(|p| p.0)
+            .fold(f64::MAX, f64::min);
+        assert!((xmin - 3.1).abs() < 1e-12);
+
+        // Dry island around the centre becomes a clockwise hole
+        let values: Vec<f64> = mesh
+            .nodes
+            .iter()
+            .map(|n| ((n.x - 5.0).powi(2) + (n.y - 5.0).powi(2)).sqrt())
+            .collect();
+        let lake = contour(&mesh, &values, 2.0);
+        assert_eq!(lake.len(), 1);
+        assert_eq!(lake[0].holes.len(), 1);
+        assert!(signed_area(&lake[0].holes[0]) < 0.0);
+        let island = -0.5 * signed_area(&lake[0].holes[0]);
+        assert!((island - std::f64::consts::PI * 4.0).abs() < 0.2);
+        assert!((total_area(&lake) - (100.0 - island)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_geojson_output() {
+        let polygons = vec![ExtentPolygon {
+            exterior: vec![(0.0, 0.0), (2.0, 0.0), (2.0, 1.0)],
+            holes: Vec::new(),
+        }];
+        let mut out = Vec::new();
+        write_geojson(
+            &mut out,
+            &polygons,
+            &[("time", 1.5)],
+            RasterCrs::Projected(Some(32633)),
+        )
+        .unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("EPSG::32633"));
+        assert!(text.contains("\"time\":1.5"));
+        assert!(text.contains("[[[[0,0],[2,0],[2,1],[0,0]]]"));
+    }
+}
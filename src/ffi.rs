@@ -0,0 +1,377 @@
+/// C interface (feature "ffi")
+/// A solver is created on a structured rectangular mesh and driven through an
+/// opaque `SweSolver` handle. Arrays are passed as pointer and length; the
+/// length must match the number of cells (or nodes) exactly, which catches
+/// mismatched meshes on the caller's side. Functions return `SWE_OK` or a
+/// negative error code, and panics are caught at the boundary instead of
+/// unwinding into C. Every name uses the `swe_` prefix and only `double`,
+/// `size_t` and `int32_t` appear, so Fortran can bind them with `iso_c_binding`.
+///
+/// `include/swe.h` is generated from this file with cbindgen
+/// (`cbindgen --config cbindgen.toml --output include/swe.h`).
+use crate::model::{GridConfig, ModelConfig};
+use crate::simulation::Simulation;
+use crate::solver::{FrictionLaw, ShallowWaterSolver};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+pub const SWE_OK: i32 = 0;
+/// A handle or array pointer was null
+pub const SWE_ERROR_NULL: i32 = -1;
+/// An array length does not match the mesh
+pub const SWE_ERROR_SIZE: i32 = -2;
+/// A parameter is out of range
+pub const SWE_ERROR_INVALID: i32 = -3;
+/// The solver panicked; the handle should be destroyed
+pub const SWE_ERROR_PANIC: i32 = -4;
+
+/// Opaque solver handle
+pub struct SweSolver(ShallowWaterSolver);
+
+fn guard(f: impl FnOnce() -> i32) -> i32 {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(SWE_ERROR_PANIC)
+}
+
+/// Create a solver on a flat nx × ny node grid of `width` × `height` metres
+/// (2 (nx - 1)(ny - 1) triangles) without friction and with dry cells.
+/// Returns null if a parameter is out of range.
+#[no_mangle]
+pub extern "C" fn swe_create_solver(
+    nx: usize,
+    ny: usize,
+    width: f64,
+    height: f64,
+    cfl: f64,
+) -> *mut SweSolver {
+    if cfl > 1.0 {
+        return std::ptr::null_mut();
+    }
+    let model = ModelConfig {
+        grid: GridConfig {
+            nx,
+            ny,
+            width,
+            height,
+            ..GridConfig::default()
+        },
+        cfl,
+        ..ModelConfig::default()
+    };
+    catch_unwind(
+        || match model.grid.mesh().and_then(|mesh| model.solver(mesh)) {
+            Ok(solver) => Box::into_raw(Box::new(SweSolver(solver))),
+            Err(_) => std::ptr::null_mut(),
+        },
+    )
+    .unwrap_or(std::ptr::null_mut())
+}
+
+/// Release a solver (null is ignored)
+///
+/// # Safety
+/// `solver` must be null or a handle from `swe_create_solver` that was not
+/// destroyed before.
+#[no_mangle]
+pub unsafe extern "C" fn swe_destroy_solver(solver: *mut SweSolver) {
+    if !solver.is_null() {
+        drop(Box::from_raw(solver));
+    }
+}
+
+/// Number of cells (length of the state arrays); 0 for a null handle
+///
+/// # Safety
+/// `solver` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn swe_num_cells(solver: *const SweSolver) -> usize {
+    solver.as_ref().map_or(0, |s| s.0.mesh.triangles.len())
+}
+
+/// Number of mesh nodes (length of the bed array); 0 for a null handle
+///
+/// # Safety
+/// `solver` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn swe_num_nodes(solver: *const SweSolver) -> usize {
+    solver.as_ref().map_or(0, |s| s.0.mesh.nodes.len())
+}
+
+/// Simulated time in seconds; NaN for a null handle
+///
+/// # Safety
+/// `solver` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn swe_get_time(solver: *const SweSolver) -> f64 {
+    solver.as_ref().map_or(f64::NAN, |s| s.0.time)
+}
+
+/// Last time step in seconds; NaN for a null handle
+///
+/// # Safety
+/// `solver` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn swe_get_dt(solver: *const SweSolver) -> f64 {
+    solver.as_ref().map_or(f64::NAN, |s| s.0.dt)
+}
+
+/// Total water volume in m³; NaN for a null handle
+///
+/// # Safety
+/// `solver` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn swe_total_mass(solver: *const SweSolver) -> f64 {
+    solver
+        .as_ref()
+        .map_or(f64::NAN, |s| s.0.compute_total_mass())
+}
+
+/// Copy the cell centroids into `x` and `y` (`len` = number of cells)
+///
+/// # Safety
+/// `solver` must be null or a live handle; `x` and `y` must be null or
+/// point to `len` writable doubles.
+#[no_mangle]
+pub unsafe extern "C" fn swe_get_cell_centroids(
+    solver: *const SweSolver,
+    x: *mut f64,
+    y: *mut f64,
+    len: usize,
+) -> i32 {
+    let (Some(solver), false, false) = (solver.as_ref(), x.is_null(), y.is_null()) else {
+        return SWE_ERROR_NULL;
+    };
+    let triangles = &solver.0.mesh.triangles;
+    if len != triangles.len() {
+        return SWE_ERROR_SIZE;
+    }
+    let (x, y) = (
+        std::slice::from_raw_parts_mut(x, len),
+        std::slice::from_raw_parts_mut(y, len),
+    );
+    for (i, tri) in triangles.iter().enumerate() {
+        (x[i], y[i]) = tri.centroid;
+    }
+    SWE_OK
+}
+
+/// Set the bed elevation of every node (m); call before the first step
+///
+/// # Safety
+/// `solver` must be null or a live handle; `z` must be null or point to
+/// `len` doubles.
+#[no_mangle]
+pub unsafe extern "C" fn swe_set_bed(solver: *mut SweSolver, z: *const f64, len: usize) -> i32 {
+    let (Some(solver), false) = (solver.as_mut(), z.is_null()) else {
+        return SWE_ERROR_NULL;
+    };
+    let mesh = &mut solver.0.mesh;
+    if len != mesh.nodes.len() {
+        return SWE_ERROR_SIZE;
+    }
+    let z = std::slice::from_raw_parts(z, len);
+    if z.iter().any(|v| !v.is_finite()) {
+        return SWE_ERROR_INVALID;
+    }
+    for (node, &value) in mesh.nodes.iter_mut().zip(z) {
+        node.z = value;
+    }
+    for tri in &mut mesh.triangles {
+        tri.z_bed = tri.nodes.iter().map(|&n| mesh.nodes[n].z).sum::<f64>() / 3.0;
+    }
+    SWE_OK
+}
+
+/// Use Manning friction with coefficient `n` (s/m^(1/3)); 0 disables friction
+///
+/// # Safety
+/// `solver` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn swe_set_manning(solver: *mut SweSolver, n: f64) -> i32 {
+    let Some(solver) = solver.as_mut() else {
+        return SWE_ERROR_NULL;
+    };
+    if !(n >= 0.0 && n.is_finite()) {
+        return SWE_ERROR_INVALID;
+    }
+    solver.0.friction = if n == 0.0 {
+        FrictionLaw::None
+    } else {
+        FrictionLaw::Manning { coefficient: n }
+    };
+    SWE_OK
+}
+
+/// Set the state of every cell: depth `h` (m) and momenta `hu`, `hv`
+/// (m²/s); null momenta are taken as zero
+///
+/// # Safety
+/// `solver` must be null or a live handle; `h` must be null or point to
+/// `len` doubles, `hu` and `hv` likewise.
+#[no_mangle]
+pub unsafe extern "C" fn swe_set_state(
+    solver: *mut SweSolver,
+    h: *const f64,
+    hu: *const f64,
+    hv: *const f64,
+    len: usize,
+) -> i32 {
+    let (Some(solver), false) = (solver.as_mut(), h.is_null()) else {
+        return SWE_ERROR_NULL;
+    };
+    if len != solver.0.mesh.triangles.len() {
+        return SWE_ERROR_SIZE;
+    }
+    let h = std::slice::from_raw_parts(h, len);
+    if h.iter().any(|&v| !(v >= 0.0 && v.is_finite())) {
+        return SWE_ERROR_INVALID;
+    }
+    let state = &mut solver.0.state;
+    state.h.copy_from_slice(h);
+    for (target, source) in [(&mut state.hu, hu), (&mut state.hv, hv)] {
+        if source.is_null() {
+            target.fill(0.0);
+        } else {
+            target.copy_from_slice(std::slice::from_raw_parts(source, len));
+        }
+    }
+    solver.0.reset_active_set();
+    SWE_OK
+}
+
+/// Copy one state array of the solver into `out`
+unsafe fn get_field(
+    solver: *const SweSolver,
+    out: *mut f64,
+    len: usize,
+    field: impl Fn(&ShallowWaterSolver) -> &[f64],
+) -> i32 {
+    let (Some(solver), false) = (solver.as_ref(), out.is_null()) else {
+        return SWE_ERROR_NULL;
+    };
+    let values = field(&solver.0);
+    if len != values.len() {
+        return SWE_ERROR_SIZE;
+    }
+    std::slice::from_raw_parts_mut(out, len).copy_from_slice(values);
+    SWE_OK
+}
+
+/// Copy the depth of every cell (m) into `out`
+///
+/// # Safety
+/// `solver` must be null or a live handle; `out` must be null or point to
+/// `len` writable doubles.
+#[no_mangle]
+pub unsafe extern "C" fn swe_get_h(solver: *const SweSolver, out: *mut f64, len: usize) -> i32 {
+    get_field(solver, out, len, |s| &s.state.h)
+}
+
+/// Copy the x-momentum of every cell (m²/s) into `out`
+///
+/// # Safety
+/// As for `swe_get_h`.
+#[no_mangle]
+pub unsafe extern "C" fn swe_get_hu(solver: *const SweSolver, out: *mut f64, len: usize) -> i32 {
+    get_field(solver, out, len, |s| &s.state.hu)
+}
+
+/// Copy the y-momentum of every cell (m²/s) into `out`
+///
+/// # Safety
+/// As for `swe_get_h`.
+#[no_mangle]
+pub unsafe extern "C" fn swe_get_hv(solver: *const SweSolver, out: *mut f64, len: usize) -> i32 {
+    get_field(solver, out, len, |s| &s.state.hv)
+}
+
+/// Advance by one CFL-limited time step
+///
+/// # Safety
+/// `solver` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn swe_step(solver: *mut SweSolver) -> i32 {
+    let Some(solver) = solver.as_mut() else {
+        return SWE_ERROR_NULL;
+    };
+    guard(|| {
+        solver.0.step();
+        SWE_OK
+    })
+}
+
+/// Step until the simulated time reaches `time` (the last step may overshoot
+/// it, as in the command-line program)
+///
+/// # Safety
+/// `solver` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn swe_advance(solver: *mut SweSolver, time: f64) -> i32 {
+    let Some(solver) = solver.as_mut() else {
+        return SWE_ERROR_NULL;
+    };
+    if time.is_nan() {
+        return SWE_ERROR_INVALID;
+    }
+    guard(|| match Simulation::until(&mut solver.0, time).run() {
+        Ok(_) => SWE_OK,
+        Err(_) => SWE_ERROR_INVALID,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dam_break_through_c_interface() {
+        unsafe {
+            assert!(swe_create_solver(1, 5, 1.0, 1.0, 0.5).is_null());
+            let solver = swe_create_solver(21, 6, 10.0, 2.5, 0.45);
+            assert!(!solver.is_null());
+            let cells = swe_num_cells(solver);
+            assert_eq!(cells, 2 * 20 * 5);
+
+            let (mut x, mut y) = (vec![0.0; cells], vec![0.0; cells]);
+            assert_eq!(
+                swe_get_cell_centroids(solver, x.as_mut_ptr(), y.as_mut_ptr(), cells),
+                SWE_OK
+            );
+            let h: Vec<f64> = x.iter().map(|&x| if x < 5.0 { 2.0 } else { 1.0 }).collect();
+            let null = std::ptr::null();
+            assert_eq!(swe_set_state(solver, h.as_ptr(), null, null, cells), SWE_OK);
+            assert_eq!(
+                swe_set_state(solver, h.as_ptr(), null, null, cells - 1),
+                SWE_ERROR_SIZE
+            );
+            assert_eq!(swe_set_manning(solver, -1.0), SWE_ERROR_INVALID);
+            assert_eq!(swe_set_manning(solver, 0.03), SWE_OK);
+            let mass = swe_total_mass(solver);
+
+            assert_eq!(swe_advance(solver, 0.5), SWE_OK);
+            assert!(swe_get_time(solver) >= 0.5);
+            assert!(swe_get_dt(solver) > 0.0);
+            let mut hu = vec![0.0; cells];
+            assert_eq!(swe_get_hu(solver, hu.as_mut_ptr(), cells), SWE_OK);
+            assert!(hu.iter().any(|&q| q > 0.0)); // Water flows towards +x
+            assert!((swe_total_mass(solver) - mass).abs() < 1e-9 * mass);
+
+            assert_eq!(swe_step(std::ptr::null_mut()), SWE_ERROR_NULL);
+            swe_destroy_solver(solver);
+        }
+    }
+
+    #[test]
+    fn test_header_declares_every_function() {
+        let header = include_str!("../include/swe.h");
+        let source = include_str!("ffi.rs");
+        let exported = source
+            .split("#[no_mangle]")
+            .skip(1)
+            .filter_map(|item| item.trim_start().strip_prefix("pub ")?.split("fn ").nth(1))
+            .filter_map(|item| item.split('(').next())
+            .collect::<Vec<_>>();
+        assert_eq!(exported.len(), 16);
+        for name in exported {
+            assert!(header.contains(&format!("{}(", name)), "{} missing", name);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+/// Reloadable forcing files
+/// In operational forecasting a long-running simulation takes in new
+/// forcing while it runs: a new rainfall nowcast, an updated temperature
+/// forecast, the latest outer model run. `ForcingWatcher` tracks the forcing
+/// files and reports which ones to re-read. A file is due once its
+/// modification time differs from the one it was loaded with and has not
+/// changed since the previous poll, so a file that is still being written is
+/// left alone until the writer is done. With an interval every file is also
+/// due at that wall-clock period, changed or not. A file that fails to load
+/// keeps its previous forcing and is tried again at the next poll.
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+struct WatchedFile<K> {
+    kind: K,
+    path: String,
+    loaded: Option<SystemTime>, // Modification time of the loaded version
+    seen: Option<SystemTime>,   // Modification time at the previous poll
+}
+
+/// Forcing files of kind `K` watched for updates
+pub struct ForcingWatcher<K> {
+    files: Vec<WatchedFile<K>>,
+    poll: Duration,
+    interval: Option<Duration>,
+    last_poll: Instant,
+    last_reload: Instant,
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl<K: Copy> ForcingWatcher<K> {
+    /// Watcher checking the files at most every `poll` and re-reading all of
+    /// them every `interval` (wall-clock)
+    pub fn new(poll: Duration, interval: Option<Duration>) -> Self {
+        let now = Instant::now();
+        ForcingWatcher {
+            files: Vec::new(),
+            poll,
+            interval,
+            last_poll: now,
+            last_reload: now,
+        }
+    }
+
+    /// Watch `path`, whose current version is already loaded
+    pub fn watch(&mut self, kind: K, path: &str) {
+        let time = modified(path);
+        self.files.push(WatchedFile {
+            kind,
+            path: path.to_string(),
+            loaded: time,
+            seen: time,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Files to re-read now, as (kind, path); they count as loaded unless
+    /// reported back with `failed`
+    pub fn due(&mut self) -> Vec<(K, String)> {
+        if self.last_poll.elapsed() < self.poll {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+        let all = self
+            .interval
+            .is_some_and(|interval| self.last_reload.elapsed() >= interval);
+        if all {
+            self.last_reload = Instant::now();
+        }
+        let mut due = Vec::new();
+        for file in &mut self.files {
+            let time = modified(&file.path);
+            let settled = time.is_some() && time == file.seen && time != file.loaded;
+            file.seen = time;
+            if settled || (all && time.is_some()) {
+                file.loaded = time;
+                due.push((file.kind, file.path.clone()));
+            }
+        }
+        due
+    }
+
+    /// The file of `kind` could not be loaded; retry it at the next poll
+    pub fn failed(&mut self, kind: K)
+    where
+        K: PartialEq,
+    {
+        for file in self.files.iter_mut().filter(|f| f.kind == kind) {
+            file.loaded = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload_after_settled_change() {
+        let path = std::env::temp_dir().join(format!("forcing_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "0 rain.asc").unwrap();
+        let mut watcher = ForcingWatcher::new(Duration::ZERO, None);
+        watcher.watch(1, path);
+        assert!(watcher.due().is_empty());
+
+        // A new version is picked up on the poll after it stopped changing
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert!(watcher.due().is_empty());
+        assert_eq!(watcher.due(), vec![(1, path.to_string())]);
+        assert!(watcher.due().is_empty());
+
+        // A failed load is retried
+        watcher.failed(1);
+        assert_eq!(watcher.due().len(), 1);
+
+        let mut periodic = ForcingWatcher::new(Duration::ZERO, Some(Duration::ZERO));
+        periodic.watch(2, path);
+        assert_eq!(periodic.due().len(), 1);
+        fs::remove_file(path).unwrap();
+    }
+}
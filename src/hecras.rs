@@ -0,0 +1,324 @@
+/// HEC-RAS 2D flow area import
+/// The plain-text geometry file of a HEC-RAS project (`.g01` etc.) stores
+/// each 2D flow area as a storage area with `Is2D=-1`: its perimeter
+/// ("Storage Area Surface Line") and the computational points
+/// ("Storage Area 2D Points"), both as coordinate pairs in 16-character
+/// fields. HEC-RAS builds Voronoi cells around the points; the dual of that
+/// mesh is the Delaunay triangulation of the points, which is what this
+/// importer builds. The perimeter is resampled at the mean point spacing and
+/// included, so the triangles fill the flow area, and triangles whose
+/// centroid lies outside a concave perimeter are dropped. Breakline
+/// refinement is already contained in the points. Bed elevations come from
+/// the terrain, exported from RAS Mapper as an ESRI ASCII grid.
+use crate::mesh::{delaunay, point_in_polygon, CoordinateSystem, Node, TriangularMesh};
+use crate::raster::RasterGrid;
+
+/// Width of a coordinate field
+const FIELD_WIDTH: usize = 16;
+
+/// Points closer to the perimeter than this fraction of the spacing are
+/// dropped in favour of the resampled perimeter
+const PERIMETER_CLEARANCE: f64 = 0.25;
+
+/// 2D flow area of a HEC-RAS geometry
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowArea {
+    pub name: String,
+    pub perimeter: Vec<(f64, f64)>, // Closing edge implicit
+    pub points: Vec<(f64, f64)>,    // Computational cell centres
+    pub manning: Option<f64>,       // Default Manning's n of the area
+}
+
+/// 2D flow areas of a geometry file (storage areas without 2D points are
+/// skipped)
+pub fn parse_geometry(text: &str) -> Result<Vec<FlowArea>, String> {
+    let mut areas = Vec::new();
+    let mut current: Option<(FlowArea, bool)> = None;
+    let mut lines = text.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        if key == "Storage Area" {
+            areas.extend(finish(current.take()));
+            let name = value.split(',').next().unwrap_or("").trim().to_string();
+            current = Some((
+                FlowArea {
+                    name,
+                    perimeter: Vec::new(),
+                    points: Vec::new(),
+                    manning: None,
+                },
+                false,
+            ));
+            continue;
+        }
+        let Some((area, is_2d)) = &mut current else {
+            continue;
+        };
+        match key {
+            "Storage Area Surface Line" | "Storage Area 2D Points" => {
+                let count: usize = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("line {}: invalid point count '{}'", number + 1, value))?;
+                let mut values = Vec::with_capacity(2 * count);
+                while values.len() < 2 * count {
+                    let (number, line) = lines
+                        .next()
+                        .ok_or_else(|| format!("{}: expected {} points", key, count))?;
+                    parse_fields(line, &mut values)
+                        .map_err(|e| format!("line {}: {}", number + 1, e))?;
+                }
+                let pairs = values.chunks(2).map(|c| (c[0], c[1])).collect();
+                if key == "Storage Area Surface Line" {
+                    area.perimeter = pairs;
+                } else {
+                    area.points = pairs;
+                }
+            }
+            "Storage Area Is2D" => *is_2d = value.trim() == "-1",
+            "Storage Area Mannings" => area.manning = value.trim().parse().ok(),
+            _ => {}
+        }
+    }
+    areas.extend(finish(current));
+    if areas.is_empty() {
+        return Err("no 2D flow area with computational points found".to_string());
+    }
+    Ok(areas)
+}
+
+pub fn read_geometry(path: &str) -> Result<Vec<FlowArea>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse_geometry(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn finish(area: Option<(FlowArea, bool)>) -> Option<FlowArea> {
+    area.filter(|(area, is_2d)| *is_2d && area.perimeter.len() >= 3 && !area.points.is_empty())
+        .map(|(area, _)| area)
+}
+
+/// Append the numbers of a line of fixed-width fields
+fn parse_fields(line: &str, values: &mut Vec<f64>) -> Result<(), String> {
+    let bytes = line.as_bytes();
+    for chunk in bytes.chunks(FIELD_WIDTH) {
+        let field = std::str::from_utf8(chunk)
+            .map_err(|e| e.to_string())?
+            .trim();
+        if !field.is_empty() {
+            values.push(
+                field
+                    .parse()
+                    .map_err(|_| format!("invalid coordinate '{}'", field))?,
+            );
+        }
+    }
+    Ok(())
+}
+
+impl FlowArea {
+    /// Area enclosed by the perimeter (m^2)
+    pub fn area(&self) -> f64 {
+        let p = &self.perimeter;
+        (0..p.len())
+            .map(|k| {
+                let (a, b) = (p[k], p[(k + 1) % p.len()]);
+                a.0 * b.1 - b.0 * a.1
+            })
+            .sum::<f64>()
+            .abs()
+            / 2.0
+    }
+
+    /// Mean spacing of the computational points (m)
+    pub fn spacing(&self) -> f64 {
+        (self.area() / self.points.len() as f64).sqrt()
+    }
+
+    /// Triangle mesh of the flow area with a flat bed at zero
+    pub fn mesh(&self) -> Result<TriangularMesh, String> {
+        let spacing = self.spacing();
+        let n = self.perimeter.len();
+        let mut points = Vec::new();
+        for k in 0..n {
+            let (a, b) = (self.perimeter[k], self.perimeter[(k + 1) % n]);
+            let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+            let pieces = (length / spacing).ceil().max(1.0) as usize;
+            for i in 0..pieces {
+                let s = i as f64 / pieces as f64;
+                points.push((a.0 + s * (b.0 - a.0), a.1 + s * (b.1 - a.1)));
+            }
+        }
+        points.extend(self.points.iter().copied().filter(|&p| {
+            point_in_polygon(p, &self.perimeter)
+                && distance_to_polygon(p, &self.perimeter) >= PERIMETER_CLEARANCE * spacing
+        }));
+
+        let min_area = 1e-6 * spacing * spacing;
+        let triangles: Vec<[usize; 3]> = delaunay(&points)
+            .into_iter()
+            .filter(|t| {
+                let [a, b, c] = t.map(|v| points[v]);
+                let centroid = ((a.0 + b.0 + c.0) / 3.0, (a.1 + b.1 + c.1) / 3.0);
+                let area = 0.5 * ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1));
+                area > min_area && point_in_polygon(centroid, &self.perimeter)
+            })
+            .collect();
+
+        // Keep the nodes used by the triangles
+        let mut index = vec![usize::MAX; points.len()];
+        let mut nodes = Vec::new();
+        let connectivity: Vec<[usize; 3]> = triangles
+            .iter()
+            .map(|t| {
+                t.map(|v| {
+                    if index[v] == usize::MAX {
+                        index[v] = nodes.len();
+                        nodes.push(Node {
+                            x: points[v].0,
+                            y: points[v].1,
+                            z: 0.0,
+                        });
+                    }
+                    index[v]
+                })
+            })
+            .collect();
+        TriangularMesh::from_triangles(nodes, &connectivity, CoordinateSystem::Cartesian)
+            .map_err(|e| format!("flow area {}: {}", self.name, e))
+    }
+}
+
+fn distance_to_polygon(p: (f64, f64), polygon: &[(f64, f64)]) -> f64 {
+    (0..polygon.len())
+        .map(|k| {
+            let (a, b) = (polygon[k], polygon[(k + 1) % polygon.len()]);
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let length2 = dx * dx + dy * dy;
+            let s = if length2 > 0.0 {
+                (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            ((p.0 - a.0 - s * dx).powi(2) + (p.1 - a.1 - s * dy).powi(2)).sqrt()
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Set the node elevations of `mesh` from a terrain raster (row-major
+/// values on `grid`); fails if a node lies outside the terrain or on NODATA
+pub fn apply_terrain(
+    mesh: &mut TriangularMesh,
+    grid: &RasterGrid,
+    terrain: &[f64],
+) -> Result<(), String> {
+    let mut missing = 0;
+    for node in &mut mesh.nodes {
+        match grid.pixel_at(node.x, node.y).map(|k| terrain[k]) {
+            Some(z) if !z.is_nan() => node.z = z,
+            _ => missing += 1,
+        }
+    }
+    if missing > 0 {
+        return Err(format!(
+            "{} of {} mesh nodes lie outside the terrain or on NODATA",
+            missing,
+            mesh.nodes.len()
+        ));
+    }
+    for tri in &mut mesh.triangles {
+        tri.z_bed = tri.nodes.iter().map(|&n| mesh.nodes[n].z).sum::<f64>() / 3.0;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Geometry text with an L-shaped 2D area on a 10 m point grid and a
+    /// 1D storage area
+    fn geometry() -> String {
+        let perimeter = [
+            (0.0, 0.0),
+            (200.0, 0.0),
+            (200.0, 100.0),
+            (100.0, 100.0),
+            (100.0, 200.0),
+            (0.0, 200.0),
+        ];
+        let points: Vec<(f64, f64)> = (0..400)
+            .map(|k| (5.0 + 10.0 * (k % 20) as f64, 5.0 + 10.0 * (k / 20) as f64))
+            .filter(|&(x, y)| x < 100.0 || y < 100.0)
+            .collect();
+        let block = |values: &[(f64, f64)]| {
+            values
+                .chunks(2)
+                .map(|pair| {
+                    pair.iter()
+                        .map(|(x, y)| format!("{:16.3}{:16.3}", x, y))
+                        .collect::<String>()
+                        + "\n"
+                })
+                .collect::<String>()
+        };
+        format!(
+            "Geom Title=Test\n\
+             Storage Area=Pond            ,50,50\n\
+             Storage Area Surface Line= 3\n{}\
+             Storage Area Is2D=0\n\
+             Storage Area=Floodplain      ,100,100\n\
+             Storage Area Surface Line= {}\n{}\
+             Storage Area Is2D=-1\n\
+             Storage Area Point Generation Data=,,10,10\n\
+             Storage Area 2D Points= {}\n{}\
+             Storage Area Mannings=0.045\n",
+            block(&[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]),
+            perimeter.len(),
+            block(&perimeter),
+            points.len(),
+            block(&points)
+        )
+    }
+
+    #[test]
+    fn test_parse_and_mesh_flow_area() {
+        let areas = parse_geometry(&geometry()).unwrap();
+        assert_eq!(areas.len(), 1);
+        let area = &areas[0];
+        assert_eq!(area.name, "Floodplain");
+        assert_eq!(area.perimeter.len(), 6);
+        assert_eq!(area.points.len(), 300);
+        assert_eq!(area.manning, Some(0.045));
+        assert!((area.area() - 30_000.0).abs() < 1e-9);
+
+        let mut mesh = area.mesh().unwrap();
+        let total: f64 = mesh.triangles.iter().map(|t| t.area).sum();
+        assert!((total - 30_000.0).abs() < 1e-6 * 30_000.0, "{}", total);
+        assert!(mesh
+            .triangles
+            .iter()
+            .all(|t| point_in_polygon(t.centroid, &area.perimeter)));
+
+        // Terrain sloping down to the east on a 5 m grid
+        let grid = RasterGrid {
+            x_min: -10.0,
+            y_max: 210.0,
+            cell_size: 5.0,
+            ncols: 44,
+            nrows: 44,
+        };
+        let terrain: Vec<f64> = (0..44 * 44)
+            .map(|k| 10.0 - 0.01 * grid.pixel_center(k % 44, k / 44).0)
+            .collect();
+        apply_terrain(&mut mesh, &grid, &terrain).unwrap();
+        let node = &mesh.nodes[mesh.triangles[0].nodes[0]];
+        assert!((node.z - (10.0 - 0.01 * node.x)).abs() <= 0.025 + 1e-9);
+
+        let small = RasterGrid { ncols: 10, ..grid };
+        assert!(apply_terrain(&mut mesh, &small, &terrain[..440]).is_err());
+        assert!(parse_geometry("Storage Area=Pond\nStorage Area Is2D=0\n").is_err());
+    }
+}
@@ -0,0 +1,464 @@
+/// Hot start from a previous run's output
+/// Reads the depth and momenta of one of this solver's own outputs: a legacy
+/// VTK frame (including `{prefix}_checkpoint.vtk`) or a time step of an
+/// ANUGA .sww file, together with the mesh they were written on. Cells are
+/// matched by index when the solver mesh has the same cells, otherwise by
+/// centroid: each solver cell takes the values of the saved cell containing
+/// its centroid. On a different mesh the saved water surface is kept (the
+/// depth is the surface minus the solver bed) and so is the velocity; cells
+/// outside the saved mesh keep their initial condition. Only the flow state
+/// is restored, not tracers such as sediment or a buoyant scalar.
+use crate::mesh::{CoordinateSystem, Node, PointLocator, TriangularMesh};
+use crate::netcdf::{AttributeValue, NetcdfFile};
+use crate::solver::ShallowWaterSolver;
+
+/// How the cells of the saved state are matched to the solver cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellMatching {
+    Index,    // Same mesh, cell i to cell i
+    Centroid, // Saved cell containing the centroid
+}
+
+/// Flow state saved by an earlier run
+#[derive(Clone)]
+pub struct SavedState {
+    pub time: f64,
+    pub mesh: TriangularMesh,
+    pub h: Vec<f64>,
+    pub hu: Vec<f64>,
+    pub hv: Vec<f64>,
+    pub surface: Option<Vec<f64>>, // Water surface, when the file has it or the bed
+}
+
+impl SavedState {
+    /// Read a .vtk frame or an .sww file (at the time step nearest `time`,
+    /// the last one by default); the mesh is in `coordinate_system`
+    pub fn read(
+        path: &str,
+        time: Option<f64>,
+        coordinate_system: CoordinateSystem,
+    ) -> Result<Self, String> {
+        let result = if path.ends_with(".sww") || path.ends_with(".nc") {
+            Self::read_sww(path, time, coordinate_system)
+        } else {
+            if time.is_some() {
+                return Err("a VTK file holds a single time".to_string());
+            }
+            std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| Self::parse_vtk(&text, coordinate_system))
+        };
+        result.map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn parse_vtk(text: &str, coordinate_system: CoordinateSystem) -> Result<Self, String> {
+        let VtkFrame {
+            time,
+            mut mesh,
+            arrays,
+        } = VtkFrame::parse(text, coordinate_system)?;
+        let array = |name: &str| {
+            arrays
+                .iter()
+                .find(|(array, _)| array == name)
+                .map(|(_, values)| values.clone())
+        };
+        let bed = array("bed_elevation");
+        let (h, surface) = match (array("height"), array("water_surface"), &bed) {
+            (Some(h), surface, Some(bed)) => {
+                let surface =
+                    surface.unwrap_or_else(|| h.iter().zip(bed).map(|(h, z)| h + z).collect());
+                (h, Some(surface))
+            }
+            (Some(h), surface, None) => (h, surface),
+            (None, Some(surface), Some(bed)) => {
+                let h = surface
+                    .iter()
+                    .zip(bed)
+                    .map(|(s, z)| (s - z).max(0.0))
+                    .collect();
+                (h, Some(surface))
+            }
+            _ => {
+                return Err(
+                    "the file needs the height, or the water surface and the bed".to_string(),
+                )
+            }
+        };
+        if let Some(bed) = &bed {
+            for (tri, &z) in mesh.triangles.iter_mut().zip(bed) {
+                tri.z_bed = z;
+            }
+        }
+        let momentum = |component: &str| {
+            array(&format!("momentum_{}", component)).or_else(|| {
+                let velocity = array(&format!("velocity_{}", component))?;
+                Some(velocity.iter().zip(&h).map(|(u, h)| u * h).collect())
+            })
+        };
+        let (hu, hv) = match (momentum("x"), momentum("y")) {
+            (Some(hu), Some(hv)) => (hu, hv),
+            _ => return Err("the file has neither the momenta nor the velocity".to_string()),
+        };
+        Ok(SavedState {
+            time,
+            mesh,
+            h,
+            hu,
+            hv,
+            surface,
+        })
+    }
+
+    fn read_sww(
+        path: &str,
+        time: Option<f64>,
+        coordinate_system: CoordinateSystem,
+    ) -> Result<Self, String> {
+        let mut file = NetcdfFile::open(path)?;
+        let corner = |name: &str| match file.attributes.iter().find(|(key, _)| key == name) {
+            Some((_, AttributeValue::Numbers(values))) => values.first().copied().unwrap_or(0.0),
+            _ => 0.0,
+        };
+        let (x0, y0) = (corner("xllcorner"), corner("yllcorner"));
+        let times = file.read("time")?;
+        let record = match time {
+            _ if times.is_empty() => return Err("the file has no time steps".to_string()),
+            None => times.len() - 1,
+            Some(t) => (0..times.len())
+                .min_by(|&a, &b| (times[a] - t).abs().total_cmp(&(times[b] - t).abs()))
+                .unwrap(),
+        };
+        let (x, y) = (file.read("x")?, file.read("y")?);
+        let elevation = file.read("elevation")?;
+        let volumes = file.read("volumes")?;
+        let stage = file.read_record("stage", record)?;
+        let xmomentum = file.read_record("xmomentum", record)?;
+        let ymomentum = file.read_record("ymomentum", record)?;
+        if [&y, &elevation, &stage, &xmomentum, &ymomentum]
+            .iter()
+            .any(|values| values.len() != x.len())
+            || volumes.len() % 3 != 0
+        {
+            return Err("inconsistent variable sizes".to_string());
+        }
+
+        let nodes = (0..x.len())
+            .map(|k| Node {
+                x: x[k] + x0,
+                y: y[k] + y0,
+                z: elevation[k],
+            })
+            .collect();
+        let cells: Vec<[usize; 3]> = volumes
+            .chunks(3)
+            .map(|v| [v[0] as usize, v[1] as usize, v[2] as usize])
+            .collect();
+        let mesh = TriangularMesh::from_triangles(nodes, &cells, coordinate_system)?;
+        let cell_mean = |values: &[f64]| -> Vec<f64> {
+            cells
+                .iter()
+                .map(|c| c.iter().map(|&n| values[n]).sum::<f64>() / 3.0)
+                .collect()
+        };
+        let surface = cell_mean(&stage);
+        let h = mesh
+            .triangles
+            .iter()
+            .zip(&surface)
+            .map(|(t, s)| (s - t.z_bed).max(0.0))
+            .collect();
+        Ok(SavedState {
+            time: times[record],
+            mesh,
+            h,
+            hu: cell_mean(&xmomentum),
+            hv: cell_mean(&ymomentum),
+            surface: Some(surface),
+        })
+    }
+
+    /// Whether `mesh` has the saved cells in the same order
+    pub fn same_cells(&self, mesh: &TriangularMesh) -> bool {
+        mesh.triangles.len() == self.mesh.triangles.len()
+            && mesh
+                .triangles
+                .iter()
+                .zip(&self.mesh.triangles)
+                .all(|(a, b)| {
+                    let tolerance = 1e-3 * a.area.sqrt();
+                    (a.centroid.0 - b.centroid.0).abs() <= tolerance
+                        && (a.centroid.1 - b.centroid.1).abs() <= tolerance
+                })
+    }
+
+    /// Set the depth and momenta of `solver`; returns the number of cells
+    /// set
+    pub fn initialize(
+        &self,
+        solver: &mut ShallowWaterSolver,
+        matching: CellMatching,
+    ) -> Result<usize, String> {
+        let n = solver.mesh.triangles.len();
+        if matching == CellMatching::Index {
+            if self.h.len() != n {
+                return Err(format!(
+                    "the file has {} cells for a mesh of {}",
+                    self.h.len(),
+                    n
+                ));
+            }
+            solver.state.h.copy_from_slice(&self.h);
+            solver.state.hu.copy_from_slice(&self.hu);
+            solver.state.hv.copy_from_slice(&self.hv);
+            return Ok(n);
+        }
+
+        let locator = PointLocator::new(&self.mesh);
+        let dry = solver.constants.dry_tolerance;
+        let mut count = 0;
+        for i in 0..n {
+            let (x, y) = solver.mesh.triangles[i].centroid;
+            let Some(cell) = locator.locate(&self.mesh, x, y) else {
+                continue;
+            };
+            let h = match &self.surface {
+                Some(surface) if self.h[cell] >= dry => {
+                    (surface[cell] - solver.mesh.triangles[i].z_bed).max(0.0)
+                }
+                Some(_) => 0.0,
+                None => self.h[cell],
+            };
+            let (u, v) = if self.h[cell] >= dry {
+                (self.hu[cell] / self.h[cell], self.hv[cell] / self.h[cell])
+            } else {
+                (0.0, 0.0)
+            };
+            solver.state.h[i] = h;
+            solver.state.hu[i] = h * u;
+            solver.state.hv[i] = h * v;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+/// Legacy ASCII VTK frame as written by `output::save_vtk`
+#[derive(Clone)]
+pub struct VtkFrame {
+    pub time: f64,
+    pub mesh: TriangularMesh,
+    pub arrays: Vec<(String, Vec<f64>)>, // Cell arrays in file order
+}
+
+impl VtkFrame {
+    /// Read a frame; the mesh is in `coordinate_system`
+    pub fn read(path: &str, coordinate_system: CoordinateSystem) -> Result<Self, String> {
+        std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Self::parse(&text, coordinate_system))
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn parse(text: &str, coordinate_system: CoordinateSystem) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if !lines
+            .next()
+            .unwrap_or_default()
+            .starts_with("# vtk DataFile")
+        {
+            return Err("not a legacy VTK file".to_string());
+        }
+        let title = lines.next().unwrap_or_default();
+        let time = title
+            .rsplit_once("t=")
+            .and_then(|(_, t)| t.trim().parse().ok())
+            .ok_or("the title has no time (t=...)")?;
+        let mut tokens = lines.flat_map(str::split_whitespace);
+        let mut next = || tokens.next().ok_or("unexpected end of file".to_string());
+        fn number<T: std::str::FromStr>(token: &str) -> Result<T, String> {
+            token
+                .parse()
+                .map_err(|_| format!("invalid number '{}'", token))
+        }
+
+        let (mut nodes, mut cells) = (Vec::new(), Vec::new());
+        let mut arrays: Vec<(String, Vec<f64>)> = Vec::new();
+        let mut n_cells = 0;
+        while let Ok(keyword) = next() {
+            match keyword {
+                "ASCII" => {}
+                "BINARY" => return Err("binary VTK files are not supported".to_string()),
+                "DATASET" => {
+                    if next()? != "UNSTRUCTURED_GRID" {
+                        return Err("expected an unstructured grid".to_string());
+                    }
+                }
+                "POINTS" => {
+                    let n: usize = number(next()?)?;
+                    next()?;
+                    for _ in 0..n {
+                        let (x, y) = (number(next()?)?, number(next()?)?);
+                        next()?;
+                        nodes.push(Node { x, y, z: 0.0 });
+                    }
+                }
+                "CELLS" => {
+                    n_cells = number(next()?)?;
+                    next()?;
+                    for _ in 0..n_cells {
+                        if next()? != "3" {
+                            return Err("only triangle cells are supported".to_string());
+                        }
+                        cells.push([number(next()?)?, number(next()?)?, number(next()?)?]);
+                    }
+                }
+                "CELL_TYPES" => {
+                    for _ in 0..number::<usize>(next()?)? {
+                        next()?;
+                    }
+                }
+                "CELL_DATA" => {
+                    if number::<usize>(next()?)? != n_cells {
+                        return Err("CELL_DATA does not match the cell count".to_string());
+                    }
+                }
+                "SCALARS" => {
+                    let name = next()?.to_string();
+                    next()?;
+                    let mut token = next()?;
+                    if token != "LOOKUP_TABLE" {
+                        if token != "1" {
+                            return Err(format!("'{}' has several components", name));
+                        }
+                        token = next()?;
+                    }
+                    if token != "LOOKUP_TABLE" {
+                        return Err(format!("'{}' has no lookup table", name));
+                    }
+                    next()?;
+                    let values = (0..n_cells)
+                        .map(|_| number(next()?))
+                        .collect::<Result<_, _>>()?;
+                    arrays.push((name, values));
+                }
+                "VECTORS" => {
+                    let name = next()?.to_string();
+                    next()?;
+                    let (mut xs, mut ys) = (Vec::new(), Vec::new());
+                    for _ in 0..n_cells {
+                        xs.push(number(next()?)?);
+                        ys.push(number(next()?)?);
+                        next()?;
+                    }
+                    arrays.push((format!("{}_x", name), xs));
+                    arrays.push((format!("{}_y", name), ys));
+                }
+                other => return Err(format!("unsupported VTK section '{}'", other)),
+            }
+        }
+        let mesh = TriangularMesh::from_triangles(nodes, &cells, coordinate_system)?;
+        Ok(VtkFrame { time, mesh, arrays })
+    }
+
+    /// Values of the cell array `name`; vectors are split into `NAME_x`
+    /// and `NAME_y`
+    pub fn array(&self, name: &str) -> Option<&[f64]> {
+        self.arrays
+            .iter()
+            .find(|(array, _)| array == name)
+            .map(|(_, values)| values.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+    use crate::output::{save_vtk, Frame, OutputField};
+    use crate::solver::FrictionLaw;
+    use crate::sww::SwwWriter;
+
+    const SLOPE: TopographyType = TopographyType::Slope {
+        gradient_x: 0.02,
+        gradient_y: 0.0,
+    };
+
+    fn spun_up() -> ShallowWaterSolver {
+        let mesh = TriangularMesh::new_rectangular(11, 6, 10.0, 5.0, SLOPE);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_dam_break(5.0);
+        while solver.time < 0.5 {
+            solver.step();
+        }
+        solver
+    }
+
+    #[test]
+    fn test_hot_start_from_vtk() {
+        let solver = spun_up();
+        let path = std::env::temp_dir().join(format!("hotstart_{}.vtk", std::process::id()));
+        let path = path.to_str().unwrap();
+        let fields = [
+            OutputField::Height,
+            OutputField::MomentumX,
+            OutputField::MomentumY,
+            OutputField::Bed,
+        ];
+        save_vtk(path, &solver.mesh, &Frame::capture(&solver, &fields)).unwrap();
+        let saved = SavedState::read(path, None, CoordinateSystem::Cartesian).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!((saved.time - solver.time).abs() < 1e-4);
+        assert!(saved.same_cells(&solver.mesh));
+
+        // Same mesh: the state is restored exactly
+        let mut restart = ShallowWaterSolver::new(solver.mesh.clone(), 0.45, FrictionLaw::None);
+        let count = saved.initialize(&mut restart, CellMatching::Index).unwrap();
+        assert_eq!(count, solver.mesh.triangles.len());
+        assert_eq!(restart.state.h, solver.state.h);
+        assert_eq!(restart.state.hu, solver.state.hu);
+
+        // Finer mesh: the water surface is carried over
+        let fine = TriangularMesh::new_rectangular(21, 11, 10.0, 5.0, SLOPE);
+        let mut branch = ShallowWaterSolver::new(fine, 0.45, FrictionLaw::None);
+        assert!(!saved.same_cells(&branch.mesh));
+        assert!(saved.initialize(&mut branch, CellMatching::Index).is_err());
+        let count = saved
+            .initialize(&mut branch, CellMatching::Centroid)
+            .unwrap();
+        assert_eq!(count, branch.mesh.triangles.len());
+        let coarse = solver.sample(2.6, 2.6).unwrap();
+        let fine = branch.sample(2.6, 2.6).unwrap();
+        assert!((coarse.surface - fine.surface).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_hot_start_from_sww() {
+        let mut solver = spun_up();
+        let path = std::env::temp_dir().join(format!("hotstart_{}.sww", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut writer = SwwWriter::create(path, &solver, "spin-up", None).unwrap();
+        writer.write(&solver).unwrap();
+        let first = (solver.time, solver.state.clone());
+        while solver.time < 1.0 {
+            solver.step();
+        }
+        writer.write(&solver).unwrap();
+        drop(writer);
+
+        let last = SavedState::read(path, None, CoordinateSystem::Cartesian).unwrap();
+        let early = SavedState::read(path, Some(0.4), CoordinateSystem::Cartesian).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(last.time, solver.time);
+        assert_eq!(early.time, first.0);
+        assert!(early.same_cells(&solver.mesh));
+
+        // Single precision in the file
+        let mut restart = ShallowWaterSolver::new(solver.mesh.clone(), 0.45, FrictionLaw::None);
+        early.initialize(&mut restart, CellMatching::Index).unwrap();
+        for i in 0..restart.mesh.triangles.len() {
+            assert!((restart.state.h[i] - first.1.h[i]).abs() < 1e-5);
+            assert!((restart.state.hu[i] - first.1.hu[i]).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! 2D shallow water equations on triangular meshes
+//! The command-line program in `main.rs` is built on these modules. With the
+//! `ffi` feature the library also exposes a C interface (see `ffi`).
+pub mod arrays;
+pub mod assimilation;
+pub mod bench;
+pub mod boundary;
+pub mod cadence;
+pub mod calibration;
+pub mod compare;
+pub mod compress;
+pub mod config;
+pub mod crs;
+pub mod drainage;
+pub mod ensemble;
+pub mod extent;
+pub mod forcing;
+pub mod hecras;
+pub mod hotstart;
+pub mod linear_solver;
+pub mod manifest;
+pub mod memory;
+pub mod mesh;
+pub mod metrics;
+pub mod model;
+pub mod nesting;
+pub mod netcdf;
+pub mod observers;
+pub mod okada;
+pub mod output;
+pub mod parquet;
+pub mod preview;
+pub mod profile;
+pub mod profiler;
+pub mod rainfall;
+pub mod raster;
+pub mod reduction;
+pub mod render;
+pub mod runup;
+pub mod selafin;
+pub mod sensitivity;
+pub mod simulation;
+pub mod snowmelt;
+pub mod solver;
+pub mod sweep;
+pub mod sww;
+pub mod symmetry;
+pub mod validation;
+pub mod walltime;
+pub mod zarr;
+
+#[cfg(feature = "serve")]
+pub mod dashboard;
+#[cfg(feature = "sqlite")]
+pub mod database;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "gpu")]
+pub mod gpu_solver;
+#[cfg(feature = "live")]
+pub mod live;
+#[cfg(all(feature = "plugins", unix))]
+pub mod plugin;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "serve")]
+pub mod server;
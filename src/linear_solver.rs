@@ -0,0 +1,212 @@
+/// Sparse linear algebra for implicit schemes
+/// Compressed sparse row matrices and a Jacobi-preconditioned conjugate
+/// gradient solver for symmetric positive definite systems.
+use crate::reduction;
+use rayon::prelude::*;
+
+/// Compressed sparse row (CSR) matrix
+#[derive(Debug, Clone)]
+pub struct SparseMatrix {
+    pub n: usize,
+    pub row_ptr: Vec<usize>,
+    pub col_idx: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+impl SparseMatrix {
+    /// Assemble from (row, col, value) triplets; duplicates are summed
+    pub fn from_triplets(n: usize, triplets: &[(usize, usize, f64)]) -> Self {
+        let mut rows: Vec<Vec<(usize, f64)>> = vec![Vec::new(); n];
+        for &(i, j, v) in triplets {
+            rows[i].push((j, v));
+        }
+
+        let mut row_ptr = Vec::with_capacity(n + 1);
+        let mut col_idx = Vec::with_capacity(triplets.len());
+        let mut values = Vec::with_capacity(triplets.len());
+        row_ptr.push(0);
+
+        for row in &mut rows {
+            row.sort_by_key(|&(j, _)| j);
+            let mut k = 0;
+            while k < row.len() {
+                let (j, mut v) = row[k];
+                k += 1;
+                while k < row.len() && row[k].0 == j {
+                    v += row[k].1;
+                    k += 1;
+                }
+                col_idx.push(j);
+                values.push(v);
+            }
+            row_ptr.push(col_idx.len());
+        }
+
+        SparseMatrix {
+            n,
+            row_ptr,
+            col_idx,
+            values,
+        }
+    }
+
+    /// y = A x
+    pub fn multiply(&self, x: &[f64]) -> Vec<f64> {
+        (0..self.n)
+            .into_par_iter()
+            .map(|i| {
+                (self.row_ptr[i]..self.row_ptr[i + 1])
+                    .map(|k| self.values[k] * x[self.col_idx[k]])
+                    .sum()
+            })
+            .collect()
+    }
+
+    pub fn diagonal(&self) -> Vec<f64> {
+        (0..self.n)
+            .map(|i| {
+                (self.row_ptr[i]..self.row_ptr[i + 1])
+                    .find(|&k| self.col_idx[k] == i)
+                    .map(|k| self.values[k])
+                    .unwrap_or(0.0)
+            })
+            .collect()
+    }
+}
+
+/// Convergence information from an iterative solve
+#[derive(Debug, Clone, Copy)]
+pub struct SolveStats {
+    pub iterations: usize,
+    pub relative_residual: f64,
+    pub converged: bool,
+}
+
+/// Solves of a run that stopped at the iteration limit, so that a warning
+/// is given once at the end rather than after every step
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnconvergedSolves {
+    pub count: usize,
+    pub largest_residual: f64, // Largest relative residual left
+}
+
+impl UnconvergedSolves {
+    pub fn record(&mut self, stats: &SolveStats) {
+        if !stats.converged {
+            self.count += 1;
+            self.largest_residual = self.largest_residual.max(stats.relative_residual);
+        }
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    reduction::sum(a.len(), |i| a[i] * b[i])
+}
+
+/// Jacobi-preconditioned conjugate gradient for SPD systems
+/// `x` holds the initial guess on entry and the solution on exit.
+pub fn conjugate_gradient(
+    a: &SparseMatrix,
+    b: &[f64],
+    x: &mut [f64],
+    tolerance: f64,
+    max_iterations: usize,
+) -> SolveStats {
+    let inv_diag: Vec<f64> = a
+        .diagonal()
+        .iter()
+        .map(|&d| if d.abs() > 0.0 { 1.0 / d } else { 1.0 })
+        .collect();
+
+    let ax = a.multiply(x);
+    let mut r: Vec<f64> = b.iter().zip(&ax).map(|(bi, ai)| bi - ai).collect();
+    let mut z: Vec<f64> = r.iter().zip(&inv_diag).map(|(ri, di)| ri * di).collect();
+    let mut p = z.clone();
+    let mut rz = dot(&r, &z);
+
+    let b_norm = dot(b, b).sqrt().max(1e-300);
+    let mut relative_residual = dot(&r, &r).sqrt() / b_norm;
+
+    for iteration in 0..max_iterations {
+        if relative_residual < tolerance {
+            return SolveStats {
+                iterations: iteration,
+                relative_residual,
+                converged: true,
+            };
+        }
+
+        let ap = a.multiply(&p);
+        let alpha = rz / dot(&p, &ap);
+        x.par_iter_mut()
+            .zip(p.par_iter())
+            .for_each(|(xi, pi)| *xi += alpha * pi);
+        r.par_iter_mut()
+            .zip(ap.par_iter())
+            .for_each(|(ri, api)| *ri -= alpha * api);
+
+        relative_residual = dot(&r, &r).sqrt() / b_norm;
+
+        z = r.iter().zip(&inv_diag).map(|(ri, di)| ri * di).collect();
+        let rz_new = dot(&r, &z);
+        let beta = rz_new / rz;
+        rz = rz_new;
+        p.par_iter_mut()
+            .zip(z.par_iter())
+            .for_each(|(pi, zi)| *pi = zi + beta * *pi);
+    }
+
+    SolveStats {
+        iterations: max_iterations,
+        relative_residual,
+        converged: relative_residual < tolerance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_triplet_assembly_sums_duplicates() {
+        let a = SparseMatrix::from_triplets(2, &[(0, 0, 1.0), (0, 0, 2.0), (1, 0, -1.0)]);
+        assert_eq!(a.row_ptr, vec![0, 1, 2]);
+        assert_eq!(a.values, vec![3.0, -1.0]);
+        assert_eq!(a.diagonal(), vec![3.0, 0.0]);
+    }
+
+    #[test]
+    fn test_cg_laplacian() {
+        // 1D Laplacian with identity shift: SPD and well conditioned
+        let n = 50;
+        let mut triplets = Vec::new();
+        for i in 0..n {
+            triplets.push((i, i, 3.0));
+            if i > 0 {
+                triplets.push((i, i - 1, -1.0));
+            }
+            if i + 1 < n {
+                triplets.push((i, i + 1, -1.0));
+            }
+        }
+        let a = SparseMatrix::from_triplets(n, &triplets);
+        let exact: Vec<f64> = (0..n).map(|i| (i as f64 * 0.3).sin()).collect();
+        let b = a.multiply(&exact);
+
+        let mut x = vec![0.0; n];
+        let stats = conjugate_gradient(&a, &b, &mut x, 1e-12, 200);
+
+        assert!(stats.converged);
+        for i in 0..n {
+            assert!((x[i] - exact[i]).abs() < 1e-9);
+        }
+
+        let mut unconverged = UnconvergedSolves::default();
+        unconverged.record(&stats);
+        let mut x = vec![0.0; n];
+        let stopped = conjugate_gradient(&a, &b, &mut x, 1e-12, 2);
+        unconverged.record(&stopped);
+        assert_eq!(unconverged.count, 1);
+        assert_eq!(unconverged.largest_residual, stopped.relative_residual);
+    }
+}
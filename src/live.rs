@@ -0,0 +1,520 @@
+/// Live viewer (feature "live")
+/// Opens a window showing the evolving solution while the solver advances.
+/// Frames are rendered on the CPU with the PNG snapshot renderer and drawn
+/// as a texture with wgpu.
+///
+/// Controls: Space pause/resume, Right or "." single step while paused,
+/// "+"/"-" more/fewer steps per frame, "1" depth, "2" water surface,
+/// mouse wheel zoom, left drag pan, "r" reset view, Esc or "q" quit.
+use crate::raster::RasterGrid;
+use crate::render::{self, RenderField, COLORBAR_MARGIN};
+use crate::simulation::Simulation;
+use std::sync::Arc;
+use winit::application::ApplicationHandler;
+use winit::dpi::LogicalSize;
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{Key, NamedKey};
+use winit::window::{Window, WindowId};
+
+const MAX_STEPS_PER_FRAME: usize = 1024;
+
+/// Visible region: centre in mesh coordinates and pixel size in mesh units
+#[derive(Debug, Clone, Copy)]
+struct View {
+    center: (f64, f64),
+    cell_size: f64,
+}
+
+struct Gpu {
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    texture_format: wgpu::TextureFormat,
+    frame: Option<(wgpu::Texture, wgpu::BindGroup)>,
+}
+
+struct LiveViewer<'a, 'b> {
+    simulation: &'a mut Simulation<'b>,
+    field: RenderField,
+    paused: bool,
+    steps_per_frame: usize,
+    view: Option<View>,
+    cursor: (f64, f64),
+    drag_from: Option<(f64, f64)>,
+    window: Option<Arc<Window>>,
+    gpu: Option<Gpu>,
+    error: Option<String>,
+}
+
+/// Run the simulation in a window until the final time or until the window
+/// is closed; the simulation's observers see every time step
+pub fn run(simulation: &mut Simulation) -> Result<(), String> {
+    let event_loop = EventLoop::new().map_err(|e| format!("cannot open window: {}", e))?;
+    let mut viewer = LiveViewer {
+        simulation,
+        field: RenderField::Depth,
+        paused: false,
+        steps_per_frame: 1,
+        view: None,
+        cursor: (0.0, 0.0),
+        drag_from: None,
+        window: None,
+        gpu: None,
+        error: None,
+    };
+    event_loop
+        .run_app(&mut viewer)
+        .map_err(|e| format!("event loop: {}", e))?;
+    viewer.error.map_or(Ok(()), Err)
+}
+
+impl LiveViewer<'_, '_> {
+    fn finished(&self) -> bool {
+        self.simulation.finished()
+    }
+
+    fn advance(&mut self, steps: usize) {
+        for _ in 0..steps {
+            if self.finished() {
+                self.paused = true;
+                break;
+            }
+            if let Err(e) = self.simulation.step() {
+                self.error = Some(e);
+                self.paused = true;
+                break;
+            }
+        }
+    }
+
+    /// Size of the plot area (window minus colorbar) in pixels
+    fn plot_size(&self) -> (usize, usize) {
+        let size = self
+            .window
+            .as_ref()
+            .map(|w| w.inner_size())
+            .unwrap_or_default();
+        (
+            (size.width as usize).saturating_sub(COLORBAR_MARGIN).max(1),
+            (size.height as usize).max(1),
+        )
+    }
+
+    /// View showing the whole mesh
+    fn fit_view(&self) -> View {
+        let (mut x_min, mut x_max) = (f64::INFINITY, f64::NEG_INFINITY);
+        let (mut y_min, mut y_max) = (f64::INFINITY, f64::NEG_INFINITY);
+        for node in &self.simulation.solver().mesh.nodes {
+            x_min = x_min.min(node.x);
+            x_max = x_max.max(node.x);
+            y_min = y_min.min(node.y);
+            y_max = y_max.max(node.y);
+        }
+        let (width, height) = self.plot_size();
+        View {
+            center: (0.5 * (x_min + x_max), 0.5 * (y_min + y_max)),
+            cell_size: ((x_max - x_min) / width as f64).max((y_max - y_min) / height as f64),
+        }
+    }
+
+    fn grid(&mut self) -> RasterGrid {
+        let view = match self.view {
+            Some(view) => view,
+            None => *self.view.insert(self.fit_view()),
+        };
+        let (ncols, nrows) = self.plot_size();
+        RasterGrid {
+            x_min: view.center.0 - 0.5 * ncols as f64 * view.cell_size,
+            y_max: view.center.1 + 0.5 * nrows as f64 * view.cell_size,
+            cell_size: view.cell_size,
+            ncols,
+            nrows,
+        }
+    }
+
+    /// Mesh coordinates under a window position
+    fn world_at(&mut self, position: (f64, f64)) -> (f64, f64) {
+        let grid = self.grid();
+        (
+            grid.x_min + position.0 * grid.cell_size,
+            grid.y_max - position.1 * grid.cell_size,
+        )
+    }
+
+    fn zoom(&mut self, factor: f64) {
+        let anchor = self.world_at(self.cursor);
+        if let Some(view) = &mut self.view {
+            view.cell_size *= factor;
+            view.center = (
+                anchor.0 + (view.center.0 - anchor.0) * factor,
+                anchor.1 + (view.center.1 - anchor.1) * factor,
+            );
+        }
+    }
+
+    fn update_title(&self) {
+        if let Some(window) = &self.window {
+            let state = if self.finished() {
+                "finished"
+            } else if self.paused {
+                "paused"
+            } else {
+                "running"
+            };
+            window.set_title(&format!(
+                "Shallow water: {} | t = {:.3} s | {} steps/frame | {}",
+                self.field.name(),
+                self.simulation.solver().time,
+                self.steps_per_frame,
+                state
+            ));
+        }
+    }
+
+    fn redraw(&mut self) {
+        let grid = self.grid();
+        let image = render::render_grid(self.simulation.solver(), self.field, &grid, None);
+        let Some(gpu) = &mut self.gpu else { return };
+        gpu.draw(&image);
+        self.update_title();
+    }
+
+    fn handle_key(&mut self, key: &Key) {
+        match key {
+            Key::Named(NamedKey::Space) => self.paused = !self.paused,
+            Key::Named(NamedKey::ArrowRight) if self.paused => self.advance(1),
+            Key::Character(c) => match c.as_str() {
+                "." if self.paused => self.advance(1),
+                "+" | "=" => {
+                    self.steps_per_frame = (self.steps_per_frame * 2).min(MAX_STEPS_PER_FRAME)
+                }
+                "-" => self.steps_per_frame = (self.steps_per_frame / 2).max(1),
+                "1" => self.field = RenderField::Depth,
+                "2" => self.field = RenderField::Surface,
+                "r" => self.view = None,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+impl ApplicationHandler for LiveViewer<'_, '_> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+        let attributes = Window::default_attributes()
+            .with_title("Shallow water")
+            .with_inner_size(LogicalSize::new(1000.0, 700.0));
+        let result = event_loop
+            .create_window(attributes)
+            .map_err(|e| format!("cannot open window: {}", e))
+            .and_then(|window| {
+                let window = Arc::new(window);
+                let gpu = Gpu::new(Arc::clone(&window))?;
+                Ok((window, gpu))
+            });
+        match result {
+            Ok((window, gpu)) => {
+                self.window = Some(window);
+                self.gpu = Some(gpu);
+            }
+            Err(e) => {
+                self.error = Some(e);
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => {
+                if let Some(gpu) = &mut self.gpu {
+                    gpu.resize(size.width, size.height);
+                }
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            }
+            WindowEvent::RedrawRequested => self.redraw(),
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                match &event.logical_key {
+                    Key::Named(NamedKey::Escape) => event_loop.exit(),
+                    Key::Character(c) if c.as_str() == "q" => event_loop.exit(),
+                    key => self.handle_key(key),
+                }
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = (position.x, position.y);
+                if let (Some(from), Some(view)) = (self.drag_from, &mut self.view) {
+                    view.center.0 -= (position.0 - from.0) * view.cell_size;
+                    view.center.1 += (position.1 - from.1) * view.cell_size;
+                    self.drag_from = Some(position);
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
+                }
+                self.cursor = position;
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.drag_from = (state == ElementState::Pressed).then_some(self.cursor);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y as f64,
+                    MouseScrollDelta::PixelDelta(p) => p.y / 40.0,
+                };
+                self.zoom(0.85f64.powf(lines));
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.paused || self.finished() {
+            self.paused = true;
+            event_loop.set_control_flow(ControlFlow::Wait);
+            return;
+        }
+        self.advance(self.steps_per_frame);
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+        event_loop.set_control_flow(ControlFlow::Poll);
+    }
+}
+
+impl Gpu {
+    fn new(window: Arc<Window>) -> Result<Self, String> {
+        let size = window.inner_size();
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let surface = instance
+            .create_surface(window)
+            .map_err(|e| format!("cannot create surface: {}", e))?;
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))
+        .ok_or("no graphics adapter available")?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("live viewer"),
+                required_features: wgpu::Features::empty(),
+                required_limits:
+                    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+                memory_hints: wgpu::MemoryHints::default(),
+            },
+            None,
+        ))
+        .map_err(|e| format!("cannot open graphics device: {}", e))?;
+
+        let config = surface
+            .get_default_config(&adapter, size.width.max(1), size.height.max(1))
+            .ok_or("surface not supported by the adapter")?;
+        surface.configure(&device, &config);
+        // The CPU frame is sRGB encoded; match the surface so colors pass through
+        let texture_format = if config.format.is_srgb() {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("live view shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/live_view.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("live view bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("live view pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("live view pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(config.format.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+        Ok(Gpu {
+            surface,
+            device,
+            queue,
+            config,
+            pipeline,
+            bind_group_layout,
+            sampler,
+            texture_format,
+            frame: None,
+        })
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.config.width = width;
+            self.config.height = height;
+            self.surface.configure(&self.device, &self.config);
+        }
+    }
+
+    /// Upload the image into the frame texture, recreating it on size changes
+    fn upload(&mut self, image: &render::Image) {
+        let size = wgpu::Extent3d {
+            width: image.width as u32,
+            height: image.height as u32,
+            depth_or_array_layers: 1,
+        };
+        let stale = self
+            .frame
+            .as_ref()
+            .is_none_or(|(texture, _)| texture.size() != size);
+        if stale {
+            let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("live view frame"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.texture_format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("live view bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+            self.frame = Some((texture, bind_group));
+        }
+
+        let rgba: Vec<u8> = image
+            .pixels
+            .iter()
+            .flat_map(|&[r, g, b]| [r, g, b, 255])
+            .collect();
+        let (texture, _) = self.frame.as_ref().unwrap();
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+    }
+
+    fn draw(&mut self, image: &render::Image) {
+        self.upload(image);
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.device, &self.config);
+                return;
+            }
+            Err(e) => {
+                eprintln!("Warning: live viewer frame skipped: {}", e);
+                return;
+            }
+        };
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("live view encoder"),
+            });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("live view pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            let (_, bind_group) = self.frame.as_ref().unwrap();
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        self.queue.submit(Some(encoder.finish()));
+        output.present();
+    }
+}
@@ -1,23 +1,149 @@
-mod mesh;
-mod solver;
+use shallow_water_solver::{
+    assimilation, bench, boundary, cadence, calibration, compare, config, crs, drainage, ensemble,
+    forcing, hecras, hotstart, manifest, memory, mesh, metrics, model, nesting, observers, okada,
+    output, preview, profile, profiler, rainfall, raster, reduction, render, runup, selafin,
+    sensitivity, simulation, snowmelt, solver, sweep, sww, symmetry, validation, walltime, zarr,
+};
 
+#[cfg(feature = "sqlite")]
+use shallow_water_solver::database::{ResultsDatabase, RunStatus, RunSummary};
 #[cfg(feature = "gpu")]
-mod gpu_solver;
+use shallow_water_solver::gpu_solver;
+#[cfg(feature = "live")]
+use shallow_water_solver::live;
+#[cfg(feature = "sqlite")]
+use shallow_water_solver::observers::{named_readings, record_results, ResultsRecorder};
+#[cfg(all(feature = "plugins", unix))]
+use shallow_water_solver::plugin::Plugin;
+#[cfg(feature = "scripting")]
+use shallow_water_solver::scripting::ScriptedControl;
+#[cfg(feature = "serve")]
+use shallow_water_solver::{dashboard, server};
 
-use clap::{Parser, ValueEnum};
-use mesh::{TopographyType, TriangularMesh};
-use solver::{FrictionLaw, ShallowWaterSolver};
+use cadence::{OutputSchedule, OutputStream, ScheduleEntry};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use config::{InitialConfig, PhysicsConfig, RunConfig, TimestepConfig};
+use crs::Crs;
+use drainage::{DualDrainage, ExternalSewer};
+use ensemble::MemberParameters;
+use forcing::ForcingWatcher;
+use hotstart::{CellMatching, SavedState};
+use manifest::{ManifestStatus, RunManifest};
+use mesh::{Polygon, TopographyType, TriangularMesh};
+use model::{GridConfig, ModelConfig, NumericsConfig};
+use nesting::{NestedBoundary, NestingWriter, OuterSolution};
+use observers::{
+    report_symmetry, save_flood_extent, DivergenceGuard, FrameOutputs, GaugeWriter, InvariantCheck,
+    OutputProfile, PeakDepth, ProfileWriter, Progress, RunupWriter, StatisticsWriter, StreamWriter,
+    SymmetryLog, Tables, TimestepLog,
+};
+use okada::FaultParameters;
+use output::{FloodEnvelope, Frame, FrameWriter, OutputField, ShearExposure};
+use preview::PreviewMode;
+use profile::Profile;
+use raster::{RasterCrs, RasterField, RasterGrid};
+use rayon::prelude::*;
+use render::{RenderField, RenderOptions};
+use runup::RunupTracker;
+use selafin::{Selafin, SelafinWriter};
+use serde::Serialize;
+use simulation::{mass_error_percent, Coupling, Simulation, SimulationConfig};
+use snowmelt::Snowmelt;
+use solver::{
+    BedDeformation, BedSlopeLimit, BudgetTracker, Discretization, FluxScheme, FrictionLaw,
+    FrictionZones, GradientMethod, IceCover, LandUseRoughness, MassExchange, Outfall, Perturbation,
+    PhysicalConstants, Porosity, RoughnessTable, ScalarKind, SedimentProperties,
+    ShallowWaterSolver, SourceSplitting, Subgrid, TimeIntegrator, TimestepControl, VelocityField,
+};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Write;
+use std::time::Instant;
+use sww::SwwWriter;
+use symmetry::SymmetryCheck;
+use zarr::ZarrWriter;
 
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Flux {
+    Rusanov,
+    CentralUpwind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum HotStartMatch {
+    Auto,
+    Index,
+    Centroid,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Scalar {
+    Temperature,
+    Salinity,
+}
+
+#[derive(Debug, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Gradient {
+    GreenGauss,
+    LeastSquares,
+}
+
+#[derive(Debug, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Precision {
+    Double,
+    Single,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Backend {
+    Cpu,
+    Gpu,
+    Auto,
+}
+
+#[derive(Debug, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Integrator {
+    Rk2,
+    SemiImplicit,
+    Imex,
+}
+
+#[derive(Debug, Clone, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Spatial {
+    Fv,
+    Dg1,
+}
+
+#[derive(Debug, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Splitting {
+    Subcycle,
+    Exponential,
+}
+
+#[derive(Debug, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
 enum InitialCondition {
     DamBreak,
     CircularWave,
     StandingWave,
+    Okada,
+    PartialDamBreak,
+    GaussianHump,
+    SolitaryWave,
 }
 
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
 enum Topography {
     Flat,
     Slope,
@@ -25,17 +151,265 @@ enum Topography {
     Channel,
 }
 
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Coordinates {
+    Cartesian,
+    Spherical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum TableFormat {
+    Csv,
+    Parquet,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum ExtentOutput {
+    /// At every output time
+    Outputs,
+    /// For the maximum depth over the run
+    Max,
+    Both,
+}
+
+#[derive(Debug, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
 enum Friction {
     None,
     Manning,
     Chezy,
+    Voellmy,
+    Bingham,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Repeat the run with sampled parameters and write probabilistic flood maps
+    Ensemble(EnsembleArgs),
+    /// Fit Manning's n to observed gauge water levels
+    Calibrate(CalibrateArgs),
+    /// Run every combination of the given parameter values and tabulate the results
+    Sweep(SweepArgs),
+    /// Rank parameters by the finite-difference sensitivity of an objective
+    Sensitivity(SensitivityArgs),
+    /// Correct an ensemble forecast with gauge water levels (ensemble Kalman filter)
+    Assimilate(AssimilateArgs),
+    /// Run the verification benchmarks against their analytical solutions
+    /// and report pass/fail (exit status 1 if a case fails)
+    Validate(ValidateArgs),
+    /// Compare the frames and conservation totals of two runs, e.g. before
+    /// and after a change to the numerics (exit status 1 beyond the tolerances)
+    Compare(CompareArgs),
+    /// Time standardized problems on CPU (and GPU with the 'gpu' feature)
+    /// across thread counts
+    Bench(BenchArgs),
+    /// Accept runs over HTTP and serve their status, gauges and output files
+    /// (requires 'serve' feature)
+    #[cfg(feature = "serve")]
+    Serve(ServeArgs),
+}
+
+#[cfg(feature = "serve")]
+#[derive(clap::Args, Debug, Clone)]
+struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    address: String,
+
+    /// Directory holding one subdirectory per run
+    #[arg(long, default_value = "runs")]
+    runs_dir: String,
+
+    /// Runs executed at the same time
+    #[arg(long, default_value_t = 1)]
+    jobs: usize,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct ValidateArgs {
+    /// Comma-separated benchmark names, or "all" (lake-at-rest,
+    /// mass-conservation, stoker, ritter, seiche, oblique-jump)
+    #[arg(long, default_value = "all")]
+    cases: String,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct CompareArgs {
+    /// Reference run: its output prefix, or a directory holding one run
+    run_a: String,
+
+    /// Run compared with the reference
+    run_b: String,
+
+    /// Largest difference allowed in any cell of any field (L∞)
+    #[arg(long, default_value_t = 1e-6)]
+    tolerance: f64,
+
+    /// Largest relative difference allowed in the final mass and energy
+    #[arg(long, default_value_t = 1e-6)]
+    conservation_tolerance: f64,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct BenchArgs {
+    /// Comma-separated problems: tiny (2k cells), small (20k), medium (200k),
+    /// large (2M)
+    #[arg(long, default_value = "small,medium")]
+    problems: String,
+
+    /// Timed steps per measurement (after one warm-up step)
+    #[arg(long, default_value_t = 50)]
+    steps: usize,
+
+    /// Comma-separated thread counts (default: powers of two up to all cores)
+    #[arg(long)]
+    thread_counts: Option<String>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct AssimilateArgs {
+    /// Gauge observations CSV with columns gauge,x,y,time,level
+    #[arg(long)]
+    observations: String,
+
+    /// Number of ensemble members
+    #[arg(long, default_value_t = 20)]
+    members: usize,
+
+    /// Standard deviation of the observation errors in m
+    #[arg(long, default_value_t = 0.02)]
+    observation_error: f64,
+
+    /// Standard deviation of the initial water-level perturbations in m
+    #[arg(long, default_value_t = 0.05)]
+    initial_spread: f64,
+
+    /// Smoothing passes applied to the initial perturbations (correlation length)
+    #[arg(long, default_value_t = 5)]
+    smoothing: usize,
+
+    /// Seed of the random perturbations
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum SensitivityObjective {
+    PeakDepth,
+    FinalDepth,
+    FloodedArea,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct SensitivityArgs {
+    /// Parameters to perturb: manning-n, chezy-c, voellmy-mu, voellmy-xi,
+    /// yield-stress, bingham-viscosity, gravity, density, amplitude
+    #[arg(long, default_value = "manning-n,amplitude")]
+    parameters: String,
+
+    /// Objective evaluated at the end of each run
+    #[arg(long, value_enum, default_value_t = SensitivityObjective::PeakDepth)]
+    objective: SensitivityObjective,
+
+    /// Gauge location "x,y" for the depth objectives (default: domain maximum)
+    #[arg(long)]
+    gauge: Option<String>,
+
+    /// Depth in m above which a cell counts as flooded (flooded-area)
+    #[arg(long, default_value_t = 0.1)]
+    flood_threshold: f64,
+
+    /// Perturbation as a fraction of the parameter value
+    #[arg(long, default_value_t = 0.01)]
+    relative_step: f64,
+}
+
+/// Values are lists "a,b,c" or ranges "start:stop:count"
+#[derive(clap::Args, Debug, Clone)]
+struct SweepArgs {
+    /// Grid points in x (y is scaled to keep the cell aspect ratio)
+    #[arg(long)]
+    nx_values: Option<String>,
+
+    /// CFL numbers
+    #[arg(long)]
+    cfl_values: Option<String>,
+
+    /// Manning's n values (needs --friction manning)
+    #[arg(long)]
+    manning_values: Option<String>,
+
+    /// Factors on the initial-condition amplitude
+    #[arg(long)]
+    amplitude_values: Option<String>,
+
+    /// Runs executed at the same time (runtimes are then not comparable)
+    #[arg(long, default_value_t = 1)]
+    jobs: usize,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct CalibrateArgs {
+    /// Gauge observations CSV with columns gauge,x,y,time,level
+    #[arg(long)]
+    observations: String,
+
+    /// Friction zone "x1,y1;x2,y2;x3,y3" with its own Manning's n; repeat for
+    /// several zones (cells outside all zones use the global n)
+    #[arg(long = "zone")]
+    zones: Vec<String>,
+
+    /// Admissible interval "min,max" for Manning's n
+    #[arg(long, default_value = "0.005,0.2")]
+    manning_bounds: String,
+
+    /// Maximum number of model runs
+    #[arg(long, default_value_t = 60)]
+    max_evaluations: usize,
+
+    /// Stop when the RMSE (m) within the simplex varies by less than this
+    #[arg(long, default_value_t = 1e-4)]
+    tolerance: f64,
 }
 
-#[derive(Parser, Debug)]
+#[derive(clap::Args, Debug, Clone)]
+struct EnsembleArgs {
+    /// Number of ensemble members
+    #[arg(long, default_value_t = 20)]
+    members: usize,
+
+    /// Sampling interval "min,max" for Manning's n (needs --friction manning)
+    #[arg(long)]
+    manning_range: Option<String>,
+
+    /// Sampling interval "min,max" for the factor on the initial-condition
+    /// amplitude (dam-break step, wave height or fault slip)
+    #[arg(long)]
+    amplitude_range: Option<String>,
+
+    /// Depth thresholds in m for the exceedance probability maps
+    #[arg(long, default_value = "0.1,0.5")]
+    thresholds: String,
+
+    /// Random seed; the same seed reproduces the same members
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+}
+
+#[derive(Parser, Debug, Clone, Serialize)]
 #[command(name = "Shallow Water Solver")]
 #[command(about = "Solves 2D shallow water equations on triangular mesh", long_about = None)]
+#[command(disable_help_flag = true)]
 struct Args {
+    /// Print help (`-h` is taken by --height)
+    #[arg(long, action = clap::ArgAction::Help)]
+    #[serde(skip)]
+    help: Option<bool>,
+
     /// Number of grid points in x direction
     #[arg(short = 'x', long, default_value_t = 40)]
     nx: usize,
@@ -44,6 +418,59 @@ struct Args {
     #[arg(short = 'y', long, default_value_t = 40)]
     ny: usize,
 
+    /// Renumber cells and nodes (reverse Cuthill-McKee) for cache locality
+    #[arg(long)]
+    reorder: bool,
+
+    /// Interior hole (island, building) as "x1,y1;x2,y2;x3,y3" in mesh
+    /// coordinates; repeat for several holes
+    #[arg(long = "hole")]
+    holes: Vec<String>,
+
+    /// Binary mesh cache file: loaded if it matches the mesh parameters,
+    /// otherwise the mesh is generated and saved there
+    #[arg(long)]
+    mesh_cache: Option<String>,
+
+    /// TELEMAC Selafin file whose triangle mesh (and BOTTOM/FOND bed)
+    /// replaces the generated rectangle
+    #[arg(long, value_name = "FILE")]
+    selafin_mesh: Option<String>,
+
+    /// Start from the last time step of the --selafin-mesh file
+    #[arg(long, requires = "selafin_mesh")]
+    selafin_restart: bool,
+
+    /// Start from the depth and momenta of an earlier run's output, a VTK
+    /// frame (e.g. <prefix>_checkpoint.vtk) or an .sww file, and continue
+    /// from its time
+    #[arg(long, value_name = "FILE", conflicts_with = "selafin_restart")]
+    hot_start: Option<String>,
+
+    /// Time step of the --hot-start .sww file to start from (the nearest;
+    /// default: the last)
+    #[arg(long, value_name = "T", requires = "hot_start")]
+    hot_start_time: Option<f64>,
+
+    /// Match the --hot-start cells by index (same mesh) or by centroid (any
+    /// mesh); auto uses the index when the meshes have the same cells
+    #[arg(long, value_enum, default_value = "auto", requires = "hot_start")]
+    hot_start_match: HotStartMatch,
+
+    /// HEC-RAS geometry file (.g01 ...) whose 2D flow area replaces the
+    /// generated rectangle
+    #[arg(long, value_name = "FILE", conflicts_with = "selafin_mesh")]
+    hecras_geometry: Option<String>,
+
+    /// 2D flow area of --hecras-geometry to mesh (default: the first)
+    #[arg(long, value_name = "NAME", requires = "hecras_geometry")]
+    hecras_area: Option<String>,
+
+    /// Terrain of the flow area as an ESRI ASCII grid, e.g. exported from
+    /// RAS Mapper (default: flat bed at zero)
+    #[arg(long, value_name = "FILE", requires = "hecras_geometry")]
+    hecras_terrain: Option<String>,
+
     /// Domain width (meters)
     #[arg(short = 'w', long, default_value_t = 10.0)]
     width: f64,
@@ -52,6 +479,18 @@ struct Args {
     #[arg(short = 'h', long, default_value_t = 10.0)]
     height: f64,
 
+    /// Coordinate system (spherical: width/height are degrees of longitude/latitude)
+    #[arg(long, value_enum, default_value_t = Coordinates::Cartesian)]
+    coordinates: Coordinates,
+
+    /// Western edge longitude in degrees (spherical coordinates)
+    #[arg(long, default_value_t = 0.0)]
+    lon_min: f64,
+
+    /// Southern edge latitude in degrees (spherical coordinates)
+    #[arg(long, default_value_t = 0.0)]
+    lat_min: f64,
+
     /// Final simulation time (seconds)
     #[arg(short = 't', long, default_value_t = 5.0)]
     final_time: f64,
@@ -60,18 +499,147 @@ struct Args {
     #[arg(short = 'c', long, default_value_t = 0.45)]
     cfl: f64,
 
+    /// Numerical flux and reconstruction
+    #[arg(long, value_enum, default_value_t = Flux::Rusanov)]
+    flux_scheme: Flux,
+
+    /// Cell gradient reconstruction (bed slope and MUSCL)
+    #[arg(long, value_enum, default_value_t = Gradient::GreenGauss)]
+    gradient_method: Gradient,
+
+    /// Spatial discretization: finite volumes or P1 discontinuous Galerkin
+    /// (research option; Rusanov flux, RK2, double precision)
+    #[arg(long, value_enum, default_value_t = Spatial::Fv)]
+    discretization: Spatial,
+
+    /// Time integration scheme
+    #[arg(long, value_enum, default_value_t = Integrator::Rk2)]
+    time_integrator: Integrator,
+
+    /// Floating-point precision of the explicit Rusanov/RK2 kernel (also
+    /// selects the GPU kernel; double falls back to single without SHADER_F64)
+    #[arg(long, value_enum, default_value_t = Precision::Double)]
+    precision: Precision,
+
+    /// Compute fluxes and sources only in the wet region plus one ring of
+    /// cells (explicit Rusanov/RK2 in double precision; results unchanged)
+    #[arg(long)]
+    track_wet_region: bool,
+
+    /// Smallest time step in s a rejected step is retried with; steps with
+    /// negative depths or NaNs are retried with half the step (explicit RK2
+    /// in double precision, like the two options below) [default: 0]
+    #[arg(long, value_name = "SECONDS")]
+    min_dt: Option<f64>,
+
+    /// Largest time step in s [default: unlimited]
+    #[arg(long, value_name = "SECONDS")]
+    max_dt: Option<f64>,
+
+    /// Largest ratio of a time step to the previous one [default: 1.5]
+    #[arg(long, value_name = "FACTOR")]
+    dt_growth: Option<f64>,
+
+    /// Integrate stiff source terms (friction) after the advective update in
+    /// substeps, or with exact exponential decay of the friction (explicit
+    /// RK2 in double precision)
+    #[arg(long, value_enum, value_name = "METHOD")]
+    source_splitting: Option<Splitting>,
+
+    /// Most substeps per time step of the split source terms
+    #[arg(long, default_value_t = 50, requires = "source_splitting")]
+    max_substeps: usize,
+
+    /// Implicitness weight of the free surface, 0.5..1 (semi-implicit)
+    #[arg(long, default_value_t = 0.55)]
+    theta: f64,
+
+    /// Horizontal eddy viscosity in m^2/s, treated implicitly (IMEX)
+    #[arg(long, default_value_t = 0.0)]
+    eddy_viscosity: f64,
+
+    /// Maximum gravity-wave Courant number (semi-implicit)
+    #[arg(long, default_value_t = 20.0)]
+    max_courant: f64,
+
+    /// Iterate to steady state instead of marching to the final time
+    #[arg(long, default_value_t = false)]
+    steady_state: bool,
+
+    /// Residual L2 norm at which the steady-state iteration stops
+    #[arg(long, default_value_t = 1e-6)]
+    steady_tolerance: f64,
+
+    /// Maximum number of steady-state iterations
+    #[arg(long, default_value_t = 100_000)]
+    max_iterations: usize,
+
+    /// Use per-cell pseudo time steps in steady-state mode
+    #[arg(long, default_value_t = false)]
+    local_time_stepping: bool,
+
     /// Output interval (seconds)
     #[arg(short = 'o', long, default_value_t = 0.1)]
     output_interval: f64,
 
+    /// Also write outputs every N time steps
+    #[arg(long, value_name = "N")]
+    output_every: Option<usize>,
+
+    /// Also write outputs when an event occurs: "depth:GAUGE>VALUE" (depth at
+    /// a --gauges point rises above VALUE), "wet:GAUGE" (the gauge first
+    /// gets wet) or "wetting" (any cell first gets wet), separated by ';'
+    #[arg(long, value_name = "EVENTS")]
+    output_on: Option<String>,
+
+    /// Interval of one output apart from --output-interval: "OUTPUT=SECONDS"
+    /// for vtk, gauges, statistics, profiles, runup, nest, selafin, sww or zarr, or
+    /// "vtk.FIELD=SECONDS" for one field of the VTK frames; 0 writes after
+    /// every step (repeatable)
+    #[arg(long, value_name = "OUTPUT=SECONDS", value_parser = ScheduleEntry::parse)]
+    output_schedule: Vec<ScheduleEntry>,
+
     /// Initial condition type
     #[arg(short = 'i', long, value_enum, default_value_t = InitialCondition::DamBreak)]
     initial_condition: InitialCondition,
 
+    /// Initial velocity added to the initial condition: "uniform:U,V",
+    /// "vortex:X,Y,RADIUS,SPEED" (Rankine) or "jet:Y,WIDTH,SPEED" (Bickley
+    /// jet along x)
+    #[arg(long, value_name = "FIELD", value_parser = VelocityField::parse)]
+    initial_velocity: Option<VelocityField>,
+
+    /// Standard deviation (m) of random noise added to the initial depth of
+    /// wet cells
+    #[arg(long, value_name = "M")]
+    perturb_depth: Option<f64>,
+
+    /// Standard deviation (m/s) of random noise added to each initial
+    /// velocity component of wet cells
+    #[arg(long, value_name = "M/S")]
+    perturb_velocity: Option<f64>,
+
+    /// Correlation length (m) of the initial noise; 0 for independent cells
+    #[arg(long, value_name = "M")]
+    perturb_length: Option<f64>,
+
+    /// Seed of the initial noise; ensemble and assimilation members add
+    /// their number
+    #[arg(long, value_name = "N")]
+    perturb_seed: Option<u64>,
+
     /// Topography/bathymetry type
     #[arg(long, value_enum, default_value_t = Topography::Flat)]
     topography: Topography,
 
+    /// Cap on the bed slope magnitude used in the topographic source term
+    #[arg(long)]
+    max_bed_slope: Option<f64>,
+
+    /// Depth (m) below which the bed slope source is ramped down (with --max-bed-slope)
+    #[arg(long, default_value_t = 0.05)]
+    slope_limit_depth: f64,
+
     /// Bottom friction type
     #[arg(long, value_enum, default_value_t = Friction::None)]
     friction: Friction,
@@ -80,264 +648,4438 @@ struct Args {
     #[arg(long, default_value_t = 0.03)]
     manning_n: f64,
 
+    /// Land-use classification raster (ESRI ASCII grid of integer classes)
+    /// giving a per-cell Manning's n through --land-use-table; classes
+    /// missing from the table and NODATA take --manning-n
+    #[arg(long, value_name = "FILE", requires = "land_use_table")]
+    land_use: Option<String>,
+
+    /// JSON lookup table of Manning's n per land-use class, e.g.
+    /// {"11": 0.025, "41": 0.1}
+    #[arg(long, value_name = "FILE", requires = "land_use")]
+    land_use_table: Option<String>,
+
+    /// Ice-covered region "x1,y1;x2,y2;x3,y3"; repeat for several. Covered
+    /// cells get the composite bed and ice roughness (needs --friction manning)
+    #[arg(long = "ice-cover", value_name = "POLYGON")]
+    ice_cover: Vec<String>,
+
+    /// Manning's n of the underside of the ice
+    #[arg(long, default_value_t = 0.02)]
+    ice_manning: f64,
+
+    /// Shelter ice-covered water from the wind (--wind)
+    #[arg(long)]
+    ice_blocks_wind: bool,
+
+    /// Uniform wind velocity "U,V" in m/s 10 m above the surface, driving a
+    /// surface stress with a drag coefficient of 1.3e-3
+    #[arg(long, value_name = "U,V", value_parser = parse_vector)]
+    wind: Option<(f64, f64)>,
+
     /// Chezy coefficient (used if friction=chezy)
     #[arg(long, default_value_t = 50.0)]
     chezy_c: f64,
 
-    /// Use GPU acceleration (requires 'gpu' feature)
-    #[arg(long, default_value_t = false)]
-    use_gpu: bool,
+    /// Voellmy Coulomb friction coefficient mu (used if friction=voellmy)
+    #[arg(long, default_value_t = 0.2)]
+    voellmy_mu: f64,
 
-    /// Output file prefix
-    #[arg(short = 'p', long, default_value = "output")]
-    output_prefix: String,
-}
+    /// Voellmy turbulence coefficient xi in m/s^2 (used if friction=voellmy)
+    #[arg(long, default_value_t = 500.0)]
+    voellmy_xi: f64,
 
-fn main() {
-    let args = Args::parse();
+    /// Bingham yield stress in Pa (used if friction=bingham)
+    #[arg(long, default_value_t = 100.0)]
+    yield_stress: f64,
 
-    println!("═══════════════════════════════════════════════════════════");
-    println!("  Shallow Water Equations Solver (2D Triangular Mesh)");
-    println!("═══════════════════════════════════════════════════════════");
-    println!();
+    /// Bingham plastic viscosity in Pa s (used if friction=bingham)
+    #[arg(long, default_value_t = 10.0)]
+    bingham_viscosity: f64,
 
-    // GPU availability check
-    #[cfg(feature = "gpu")]
-    if args.use_gpu {
-        println!("GPU Acceleration: ENABLED (WebGPU)");
-    } else {
-        println!("GPU Acceleration: Available but not enabled (use --use-gpu)");
-    }
+    /// Friction law in a region, "LAW=x1,y1;x2,y2;x3,y3" with LAW one of
+    /// none, manning:N, chezy:C, voellmy:MU:XI, bingham:TAU_Y:MU_B; repeat
+    /// for several (later regions take precedence)
+    #[arg(long = "friction-zone", value_name = "LAW=POLYGON")]
+    friction_zone: Vec<String>,
 
-    #[cfg(not(feature = "gpu"))]
-    if args.use_gpu {
-        println!("WARNING: GPU requested but not compiled. Build with --features gpu");
-        println!("Falling back to CPU mode.");
-    }
+    /// ESRI ASCII grid of friction zone classes, mapped to laws by
+    /// --friction-zone-table (cells of other classes keep --friction)
+    #[arg(long, value_name = "FILE", requires = "friction_zone_table")]
+    friction_zones: Option<String>,
 
-    println!();
-    println!("Mesh Configuration:");
-    println!(
-        "  Grid points: {}x{} = {} triangles",
-        args.nx,
-        args.ny,
-        2 * (args.nx - 1) * (args.ny - 1)
-    );
-    println!("  Domain size: {:.2}m × {:.2}m", args.width, args.height);
-    println!();
-    println!("Simulation Parameters:");
-    println!("  Final time: {:.2}s", args.final_time);
-    println!("  CFL number: {:.2}", args.cfl);
-    println!("  Output interval: {:.2}s", args.output_interval);
-    println!("  Initial condition: {:?}", args.initial_condition);
-    println!("  Topography: {:?}", args.topography);
-    println!("  Friction: {:?}", args.friction);
-    if matches!(args.friction, Friction::Manning) {
-        println!("  Manning's n: {:.4}", args.manning_n);
-    } else if matches!(args.friction, Friction::Chezy) {
-        println!("  Chezy C: {:.1}", args.chezy_c);
-    }
-    println!();
+    /// JSON lookup table of friction laws per zone class, e.g.
+    /// {"1": "manning:0.035", "2": "chezy:40"}
+    #[arg(long, value_name = "FILE", requires = "friction_zones")]
+    friction_zone_table: Option<String>,
 
-    // Create mesh
-    println!("Creating triangular mesh...");
-    let topography_type = match args.topography {
-        Topography::Flat => TopographyType::Flat,
-        Topography::Slope => TopographyType::Slope {
-            gradient_x: 0.01,
-            gradient_y: 0.005,
-        },
-        Topography::Gaussian => TopographyType::Gaussian {
-            center: (args.width / 2.0, args.height / 2.0),
-            amplitude: 1.0,
-            width: args.width / 4.0,
-        },
-        Topography::Channel => TopographyType::Channel {
-            depth: 2.0,
-            width: args.width / 2.0,
-        },
-    };
+    /// Bulk density of the flowing mixture in kg/m^3 [default: 1000]
+    #[arg(long, visible_alias = "bulk-density")]
+    density: Option<f64>,
 
-    let mesh =
-        TriangularMesh::new_rectangular(args.nx, args.ny, args.width, args.height, topography_type);
-    println!("  Nodes: {}", mesh.nodes.len());
-    println!("  Triangles: {}", mesh.triangles.len());
-    println!("  Edges: {}", mesh.edges.len());
+    /// Gravitational acceleration in m/s^2 [default: 9.81]
+    #[arg(long)]
+    gravity: Option<f64>,
 
-    // Report bed elevation range
-    let z_min = mesh
-        .nodes
-        .iter()
-        .map(|n| n.z)
-        .min_by(|a, b| a.partial_cmp(b).unwrap())
-        .unwrap_or(0.0);
-    let z_max = mesh
-        .nodes
-        .iter()
-        .map(|n| n.z)
-        .max_by(|a, b| a.partial_cmp(b).unwrap())
-        .unwrap_or(0.0);
-    println!("  Bed elevation range: [{:.3}, {:.3}] m", z_min, z_max);
-    println!();
+    /// Depth in m below which a cell is treated as dry [default: 1e-10]
+    #[arg(long)]
+    dry_tolerance: Option<f64>,
 
-    // Create solver
-    println!("Initializing solver...");
-    let friction_law = match args.friction {
-        Friction::None => FrictionLaw::None,
-        Friction::Manning => FrictionLaw::Manning {
-            coefficient: args.manning_n,
-        },
-        Friction::Chezy => FrictionLaw::Chezy {
-            coefficient: args.chezy_c,
-        },
-    };
+    /// Depth ε in m below which velocities are desingularized as
+    /// 2h·hu/(h² + max(h², ε²)) instead of hu/h; 0 disables it [default: 1e-4]
+    #[arg(long, value_name = "DEPTH")]
+    desingularization: Option<f64>,
 
-    let mut solver = ShallowWaterSolver::new(mesh, args.cfl, friction_law);
+    /// Depth in m below which the depth-dependent friction terms vanish
+    /// [default: 1e-6]
+    #[arg(long, value_name = "DEPTH")]
+    friction_depth: Option<f64>,
 
-    // Set initial condition
-    match args.initial_condition {
-        InitialCondition::DamBreak => {
-            println!("  Setting dam break initial condition...");
-            solver.set_dam_break(args.width / 2.0);
-        }
-        InitialCondition::CircularWave => {
-            println!("  Setting circular wave initial condition...");
-            solver.set_circular_wave((args.width / 2.0, args.height / 2.0), args.width / 4.0, 0.5);
-        }
-        InitialCondition::StandingWave => {
-            println!("  Setting standing wave initial condition...");
-            solver.set_standing_wave(0.1, args.width / 2.0);
-        }
-    }
+    /// Building-coverage raster (ESRI ASCII grid, 0 = open, 1 = built) for the
+    /// porosity formulation of urban areas
+    #[arg(long, value_name = "FILE")]
+    building_coverage: Option<String>,
 
-    let initial_mass = solver.compute_total_mass();
-    let initial_energy = solver.compute_total_energy();
-    println!("  Initial mass: {:.6}", initial_mass);
-    println!("  Initial energy: {:.6}", initial_energy);
-    println!();
+    /// GeoJSON lines and polygons tagging boundary edges with "wall", "open",
+    /// "stage" (with "level") or "inflow" (with "discharge" in m^3/s) in
+    /// their "boundary" property; untagged edges are walls
+    #[arg(long, value_name = "FILE", conflicts_with = "nest_from")]
+    boundary_geojson: Option<String>,
 
-    // Save initial state
-    save_state(&solver, 0, &args.output_prefix);
+    /// Distance from a boundary feature within which edge midpoints are
+    /// claimed, in mesh coordinates [default: a quarter of the edge length]
+    #[arg(long, value_name = "DISTANCE", requires = "boundary_geojson")]
+    boundary_tolerance: Option<f64>,
 
-    // Time stepping
-    println!("Starting time integration...");
-    let mut output_counter = 1;
-    let mut next_output_time = args.output_interval;
-    let mut step_count = 0;
+    /// High-resolution DEM (ESRI ASCII grid) sampled into hypsometric curves
+    /// of the cells for subgrid bathymetry
+    #[arg(long, value_name = "FILE")]
+    subgrid_dem: Option<String>,
 
-    while solver.time < args.final_time {
-        solver.step();
-        step_count += 1;
+    /// Rainfall frames in mm/h: a NetCDF cube (time, y, x) ending in ".nc",
+    /// or a text file of "TIME FILE" lines listing ESRI ASCII grids
+    #[arg(long, value_name = "FILE")]
+    rainfall: Option<String>,
 
-        if solver.time >= next_output_time {
-            let mass = solver.compute_total_mass();
-            let _energy = solver.compute_total_energy();
-            let mass_error = ((mass - initial_mass) / initial_mass * 100.0).abs();
+    /// Variable of the --rainfall NetCDF file (default: the first 3-D one)
+    #[arg(long, value_name = "NAME", requires = "rainfall")]
+    rainfall_variable: Option<String>,
 
-            println!(
-                "  t = {:.3}s, dt = {:.6}s, steps = {}, mass error = {:.6}%",
-                solver.time, solver.dt, step_count, mass_error
-            );
+    /// Snow zones (JSON) melted by the degree-day method as a distributed
+    /// mass source
+    #[arg(long, value_name = "FILE")]
+    snowmelt: Option<String>,
 
-            save_state(&solver, output_counter, &args.output_prefix);
-            output_counter += 1;
-            next_output_time += args.output_interval;
-        }
-    }
+    /// Grain diameter in m of suspended sediment carried by the flow
+    /// (enables sediment transport, outputs sed and dzb)
+    #[arg(long, value_name = "M")]
+    sediment_diameter: Option<f64>,
 
-    println!();
-    println!("Simulation completed!");
-    println!("  Total steps: {}", step_count);
-    println!("  Final time: {:.3}s", solver.time);
+    /// Grain density of the sediment in kg/m^3
+    #[arg(long, default_value_t = 2650.0)]
+    sediment_density: f64,
 
-    let final_mass = solver.compute_total_mass();
-    let final_energy = solver.compute_total_energy();
-    let mass_conservation = ((final_mass - initial_mass) / initial_mass * 100.0).abs();
+    /// Add the pressure of the denser sediment-laden water to the momentum
+    #[arg(long)]
+    sediment_density_coupling: bool,
 
-    println!();
-    println!("Conservation Properties:");
-    println!("  Initial mass: {:.6}", initial_mass);
-    println!("  Final mass: {:.6}", final_mass);
-    println!("  Mass conservation error: {:.8}%", mass_conservation);
-    println!("  Initial energy: {:.6}", initial_energy);
-    println!("  Final energy: {:.6}", final_energy);
-    println!();
-    println!("Output files saved with prefix: {}", args.output_prefix);
-    println!("═══════════════════════════════════════════════════════════");
-}
+    /// Carry a buoyant scalar (°C or g/kg) whose density differences drive
+    /// the flow (reduced gravity); outputs scalar
+    #[arg(long, value_enum, requires = "scalar_ambient")]
+    scalar: Option<Scalar>,
 
-fn save_state(solver: &ShallowWaterSolver, index: usize, prefix: &str) {
-    let filename = format!("{}_{:04}.vtk", prefix, index);
+    /// Ambient value of --scalar, the initial value everywhere
+    #[arg(long, value_name = "VALUE", requires = "scalar")]
+    scalar_ambient: Option<f64>,
 
-    match File::create(&filename) {
-        Ok(mut file) => {
-            // Write VTK file format for visualization in ParaView or similar
-            writeln!(file, "# vtk DataFile Version 3.0").unwrap();
-            writeln!(file, "Shallow Water Solution at t={:.4}", solver.time).unwrap();
-            writeln!(file, "ASCII").unwrap();
-            writeln!(file, "DATASET UNSTRUCTURED_GRID").unwrap();
-            writeln!(file, "POINTS {} float", solver.mesh.nodes.len()).unwrap();
+    /// Point discharge "x,y,Q,value" of water with the given --scalar value
+    /// (Q in m^3/s); repeat for several
+    #[arg(long = "outfall", value_name = "X,Y,Q,VALUE", requires = "scalar")]
+    outfalls: Vec<String>,
 
-            for node in &solver.mesh.nodes {
-                writeln!(file, "{} {} 0.0", node.x, node.y).unwrap();
-            }
+    /// Sewer inlets "name,x,y,perimeter,area[,weir,orifice]" exchanging
+    /// water with the drainage model of --swmm-command (dual drainage)
+    #[arg(long, value_name = "FILE", requires = "swmm_command")]
+    swmm_inlets: Option<String>,
 
-            writeln!(file).unwrap();
-            writeln!(
-                file,
-                "CELLS {} {}",
-                solver.mesh.triangles.len(),
-                solver.mesh.triangles.len() * 4
-            )
-            .unwrap();
+    /// Command running the sewer model (e.g. a pyswmm bridge script); it
+    /// exchanges inlet inflows and node heads over stdin/stdout every step
+    #[arg(long, value_name = "COMMAND", requires = "swmm_inlets")]
+    swmm_command: Option<String>,
 
-            for tri in &solver.mesh.triangles {
-                writeln!(file, "3 {} {} {}", tri.nodes[0], tri.nodes[1], tri.nodes[2]).unwrap();
-            }
+    /// Shared library with custom logic run before and after every time
+    /// step (C ABI of include/swe_plugin.h); repeat for several, run in
+    /// order (requires 'plugins' feature)
+    #[arg(long = "plugin", value_name = "LIB")]
+    plugins: Vec<String>,
 
-            writeln!(file).unwrap();
-            writeln!(file, "CELL_TYPES {}", solver.mesh.triangles.len()).unwrap();
-            for _ in 0..solver.mesh.triangles.len() {
-                writeln!(file, "5").unwrap(); // Triangle type
-            }
+    /// Configuration string passed to swe_plugin_init of every --plugin
+    #[arg(long, value_name = "STRING", default_value = "", requires = "plugins")]
+    plugin_config: String,
 
-            writeln!(file).unwrap();
-            writeln!(file, "CELL_DATA {}", solver.mesh.triangles.len()).unwrap();
+    /// Wave amplitude in m (gaussian-hump: 0.2, solitary-wave: 0.1 x the depth
+    /// at the crest by default)
+    #[arg(long)]
+    wave_amplitude: Option<f64>,
 
-            writeln!(file, "SCALARS height float 1").unwrap();
-            writeln!(file, "LOOKUP_TABLE default").unwrap();
-            for &h in &solver.state.h {
-                writeln!(file, "{}", h).unwrap();
-            }
+    /// Gaussian hump width σ in m (gaussian-hump; default: width / 10)
+    #[arg(long)]
+    wave_width: Option<f64>,
 
-            writeln!(file, "VECTORS velocity float").unwrap();
-            for i in 0..solver.mesh.triangles.len() {
-                let (u, v) = solver.state.get_velocity(i);
-                writeln!(file, "{} {} 0.0", u, v).unwrap();
-            }
+    /// Hump centre or a point on the solitary wave crest, x in mesh
+    /// coordinates (gaussian-hump, solitary-wave; default: domain center)
+    #[arg(long)]
+    wave_x: Option<f64>,
 
-            writeln!(file, "SCALARS momentum_x float 1").unwrap();
-            writeln!(file, "LOOKUP_TABLE default").unwrap();
-            for &hu in &solver.state.hu {
-                writeln!(file, "{}", hu).unwrap();
+    /// Hump centre or a point on the solitary wave crest, y in mesh
+    /// coordinates (gaussian-hump, solitary-wave; default: domain center)
+    #[arg(long)]
+    wave_y: Option<f64>,
+
+    /// Solitary wave heading in degrees counter-clockwise from east (solitary-wave)
+    #[arg(long)]
+    wave_direction: Option<f64>,
+
+    /// Width of the breach in the dam (partial-dam-break; default: 0.375 x height)
+    #[arg(long)]
+    breach_width: Option<f64>,
+
+    /// y of the breach centre in mesh coordinates (partial-dam-break;
+    /// default: 0.6625 x height from the south edge)
+    #[arg(long)]
+    breach_center: Option<f64>,
+
+    /// Thickness of the dam in x (partial-dam-break; default: width / 20)
+    #[arg(long)]
+    dam_thickness: Option<f64>,
+
+    /// Fault top-edge midpoint x in mesh coordinates (okada; default: domain center)
+    #[arg(long)]
+    fault_x: Option<f64>,
+
+    /// Fault top-edge midpoint y in mesh coordinates (okada; default: domain center)
+    #[arg(long)]
+    fault_y: Option<f64>,
+
+    /// Depth of the fault top edge in m (okada)
+    #[arg(long, default_value_t = 5000.0)]
+    fault_depth: f64,
+
+    /// Fault strike in degrees clockwise from north (okada)
+    #[arg(long, default_value_t = 0.0)]
+    fault_strike: f64,
+
+    /// Fault dip in degrees (okada)
+    #[arg(long, default_value_t = 15.0)]
+    fault_dip: f64,
+
+    /// Fault rake in degrees (okada)
+    #[arg(long, default_value_t = 90.0)]
+    fault_rake: f64,
+
+    /// Fault slip in m (okada)
+    #[arg(long, default_value_t = 5.0)]
+    fault_slip: f64,
+
+    /// Fault length along strike in m (okada)
+    #[arg(long, default_value_t = 100e3)]
+    fault_length: f64,
+
+    /// Fault width down dip in m (okada)
+    #[arg(long, default_value_t = 50e3)]
+    fault_width: f64,
+
+    /// Still sea level in m (okada, gaussian-hump, solitary-wave)
+    #[arg(long, default_value_t = 1.0)]
+    sea_level: f64,
+
+    /// Prescribed bed displacement CSV with columns time,x,y,dz on a regular
+    /// grid (landslides, co-seismic rupture histories)
+    #[arg(long, value_name = "FILE")]
+    bed_deformation: Option<String>,
+
+    /// Bitwise-reproducible runs: fixed-order parallel reductions
+    #[arg(long, default_value_t = false)]
+    deterministic: bool,
+
+    /// Number of worker threads (default: all cores)
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Refuse to start if the estimated memory exceeds this size, e.g. "8G"
+    /// (default: warn when it exceeds the available memory)
+    #[arg(long)]
+    memory_limit: Option<String>,
+
+    /// Stop cleanly after this wall-clock time, e.g. "3h45m": write the
+    /// outputs of the current time and <prefix>_checkpoint.vtk, then exit
+    /// with status 75
+    #[arg(long, value_name = "DURATION")]
+    max_walltime: Option<String>,
+
+    /// Re-read the --rainfall, --snowmelt and --nest-from files when they
+    /// change on disk, so a running forecast takes in updated forcing
+    #[arg(long)]
+    reload_forcing: bool,
+
+    /// Also re-read the forcing files at this wall-clock interval, e.g.
+    /// "15m", whether they changed or not
+    #[arg(long, value_name = "DURATION", requires = "reload_forcing")]
+    reload_interval: Option<String>,
+
+    /// Set up the run and report the mesh, memory estimate, initial time
+    /// step, boundary conditions and output files, then exit without time
+    /// stepping
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// Show the run in an interactive window (requires 'live' feature)
+    #[arg(long, default_value_t = false)]
+    live: bool,
+
+    /// Serve a browser dashboard on this address, e.g. "0.0.0.0:8080"
+    /// (requires 'serve' feature)
+    #[arg(long)]
+    serve: Option<String>,
+
+    /// Expose Prometheus metrics (steps/s, simulated time, dt, mass error,
+    /// memory) at http://<ADDR>/metrics, e.g. "0.0.0.0:9090"
+    #[arg(long)]
+    metrics: Option<String>,
+
+    /// Use GPU acceleration (requires 'gpu' feature; same as --backend gpu)
+    #[arg(long, default_value_t = false)]
+    use_gpu: bool,
+
+    /// Time stepping device; auto benchmarks a few steps on both and picks
+    /// the faster (requires 'gpu' feature)
+    #[arg(long, value_enum, default_value_t = Backend::Cpu)]
+    backend: Backend,
+
+    /// Run the simulation on CPU and GPU side by side and report the largest
+    /// per-cell discrepancy instead of writing output (requires 'gpu' feature)
+    #[arg(long)]
+    validate_gpu: bool,
+
+    /// Split the mesh over these GPU adapters, e.g. "0,1" (requires 'gpu' feature)
+    #[arg(long)]
+    gpus: Option<String>,
+
+    /// Output file prefix
+    #[arg(short = 'p', long, default_value = "output")]
+    output_prefix: String,
+
+    /// Cell arrays to write, e.g. "h,vel,eta" (h, vel, hu, hv, bed, eta, fr, vort, q, tau, cfl)
+    #[arg(long)]
+    fields: Option<String>,
+
+    /// Write the VTK frames only for the cells inside this region, a polygon
+    /// "x1,y1;x2,y2;..." or a box "xmin,ymin,xmax,ymax"; repeat for several
+    #[arg(long = "output-region", value_name = "REGION", value_parser = parse_region)]
+    output_regions: Vec<Polygon>,
+
+    /// Also write one cell per square of this size (m) outside the
+    /// --output-region windows
+    #[arg(long, value_name = "M", requires = "output_regions")]
+    output_outside_spacing: Option<f64>,
+
+    /// Gauges "name=x,y;x,y" whose depth, water level and velocity are written
+    /// to <prefix>_gauges.csv at every output time
+    #[arg(long)]
+    gauges: Option<String>,
+
+    /// Profile "name=x1,y1;x2,y2;..." (e.g. a thalweg or cross section) whose
+    /// depth, water level and velocity are written to <prefix>_profiles.csv
+    /// at every output time; repeat for several
+    #[arg(long = "profile-line", value_name = "POLYLINE")]
+    profiles: Vec<String>,
+
+    /// Station spacing along --profile-line lines (m, default: mean triangle size)
+    #[arg(long, value_name = "M", requires = "profiles")]
+    profile_spacing: Option<f64>,
+
+    /// Transect "name=x1,y1;x2,y2;..." drawn from the sea towards the land
+    /// along which the shoreline, runup and inundation distance are written
+    /// to <prefix>_runup.csv at every output time; repeat for several
+    #[arg(long = "runup-line", value_name = "POLYLINE")]
+    runup_lines: Vec<String>,
+
+    /// Also track the shoreline over the whole domain: the highest wet cell
+    /// and the farthest inundated cell
+    #[arg(long)]
+    runup: bool,
+
+    /// Depth above which a cell counts as wet for --runup and --runup-line (m)
+    #[arg(long, value_name = "M", default_value_t = 1e-3)]
+    runup_depth: f64,
+
+    /// Format of the gauge and statistics tables (<prefix>_gauges and
+    /// <prefix>_statistics): CSV, Apache Parquet or both
+    #[arg(long, value_enum, default_value_t = TableFormat::Csv)]
+    table_format: TableFormat,
+
+    /// Add the run's parameters, conservation metrics, statistics and gauge
+    /// series to this SQLite database, shared by all runs of a campaign
+    /// (requires 'sqlite' feature)
+    #[arg(long)]
+    results_db: Option<String>,
+
+    /// Save the mesh and the state at every output time to FILE, to drive
+    /// nested inner runs with --nest-from
+    #[arg(long, value_name = "FILE")]
+    nest_save: Option<String>,
+
+    /// Start from and drive the boundary with the outer solution saved in
+    /// FILE by --nest-save (one-way nesting)
+    #[arg(long, value_name = "FILE")]
+    nest_from: Option<String>,
+
+    /// Write the results at every output time to a TELEMAC Selafin file
+    #[arg(long, value_name = "FILE")]
+    selafin_output: Option<String>,
+
+    /// Write the results at every output time to an ANUGA .sww file
+    #[arg(long, value_name = "FILE")]
+    sww_output: Option<String>,
+
+    /// Write the output fields at every output time to a chunked,
+    /// compressed Zarr store (directory) for xarray/dask
+    #[arg(long, value_name = "DIR")]
+    zarr_output: Option<String>,
+
+    /// Output times per Zarr chunk along the time dimension
+    #[arg(long, default_value_t = 10)]
+    zarr_time_chunk: usize,
+
+    /// Log the time step and the cell limiting it after every step
+    #[arg(long, default_value_t = false)]
+    dt_diagnostics: bool,
+
+    /// Check after every step that the solution keeps these symmetries of
+    /// the setup: x, y (mirrors), point (180° rotation), diagonal or
+    /// anti-diagonal, separated by ','; the asymmetry is written to
+    /// <prefix>_symmetry.csv and the exit status is 1 beyond the tolerance
+    #[arg(long, value_name = "LIST")]
+    check_symmetry: Option<String>,
+
+    /// Check after every step that depths are non-negative, velocities
+    /// bounded, fluxes antisymmetric across interior edges and residuals
+    /// finite; stops at the first violation, naming the cell or edge
+    #[arg(long)]
+    check_invariants: bool,
+
+    /// Velocity bound of --check-invariants (m/s)
+    #[arg(
+        long,
+        value_name = "M/S",
+        default_value_t = 100.0,
+        requires = "check_invariants"
+    )]
+    invariant_max_velocity: f64,
+
+    /// Largest asymmetry of depth (m) and momentum (m^2/s) allowed by
+    /// --check-symmetry
+    #[arg(
+        long,
+        value_name = "TOL",
+        default_value_t = 1e-9,
+        requires = "check_symmetry"
+    )]
+    symmetry_tolerance: f64,
+
+    /// Time the solver phases and print a breakdown at the end of the run
+    #[arg(long, default_value_t = false)]
+    profile: bool,
+
+    /// Also print the phase breakdown of every output interval (implies --profile)
+    #[arg(long, default_value_t = false)]
+    profile_outputs: bool,
+
+    /// Frames buffered for the background writer thread (0 = write synchronously)
+    #[arg(long, default_value_t = 2)]
+    output_queue: usize,
+
+    /// GeoTIFF rasters written at the end of the run, e.g. "depth,max-depth,hazard"
+    /// or "arrival-time,duration,peak-time"
+    #[arg(long)]
+    geotiff: Option<String>,
+
+    /// Depth above which a cell counts as flooded for the arrival-time,
+    /// duration and peak-time rasters (m)
+    #[arg(long, value_name = "M", default_value_t = output::ARRIVAL_DEPTH)]
+    arrival_depth: f64,
+
+    /// Grain diameter d50 (m) whose critical shear stress the
+    /// shear-exceedance raster counts (default: --sediment-diameter)
+    #[arg(long, value_name = "M")]
+    scour_diameter: Option<f64>,
+
+    /// Critical Shields parameter for the shear-exceedance raster (default:
+    /// the Soulsby-Whitehouse curve for the grain size)
+    #[arg(long, value_name = "THETA")]
+    critical_shields: Option<f64>,
+
+    /// GeoTIFF pixel size in mesh coordinate units (default: mean triangle size)
+    #[arg(long)]
+    raster_cell_size: Option<f64>,
+
+    /// Export flood-extent polygons as GeoJSON, wet where the depth exceeds
+    /// this threshold (m)
+    #[arg(long, value_name = "DEPTH")]
+    flood_extent: Option<f64>,
+
+    /// When to export the flood extent
+    #[arg(long, value_enum, default_value_t = ExtentOutput::Max, requires = "flood_extent")]
+    flood_extent_at: ExtentOutput,
+
+    /// PNG snapshots rendered at every output time, e.g. "depth,surface"
+    #[arg(long)]
+    png: Option<String>,
+
+    /// PNG plot width in pixels (height follows the domain aspect ratio)
+    #[arg(long, default_value_t = 800)]
+    png_width: usize,
+
+    /// Fixed PNG color range "min,max" (default: per-frame range of wet cells)
+    #[arg(long)]
+    png_range: Option<String>,
+
+    /// Print a depth preview to the terminal at every output time: color, ascii
+    #[arg(long)]
+    preview: Option<String>,
+
+    /// Terminal preview width in characters
+    #[arg(long, default_value_t = 80)]
+    preview_width: usize,
+
+    /// EPSG code of the mesh coordinates for GeoTIFF, .sww and Zarr
+    /// georeferencing and the target of --input-epsg (spherical meshes
+    /// default to 4326)
+    #[arg(long)]
+    epsg: Option<u16>,
+
+    /// EPSG code of imported meshes, rasters, gauge coordinates and GeoJSON
+    /// boundaries without a "crs" member, reprojected to --epsg (built in:
+    /// 4326, 3857 and UTM 326zz/327zz)
+    #[arg(long, value_name = "CODE")]
+    input_epsg: Option<u16>,
+
+    /// JSON configuration file (command-line options take precedence)
+    #[arg(long)]
+    config: Option<String>,
+
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<Command>,
+}
+
+fn main() {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let mut walltime = args.max_walltime.as_deref().map(|spec| {
+        walltime::parse_duration(spec)
+            .map(walltime::WallClockLimit::new)
+            .unwrap_or_else(|e| exit_with_error(&format!("--max-walltime: {}", e)))
+    });
+
+    #[cfg(feature = "serve")]
+    if let Some(Command::Serve(serve)) = &args.command {
+        run_server(serve);
+        return;
+    }
+    if let Some(Command::Compare(compare)) = &args.command {
+        run_compare(compare);
+        return;
+    }
+
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap_or_else(|e| exit_with_error(&format!("thread pool: {}", e)));
+    }
+    reduction::set_deterministic(args.deterministic);
+
+    let config = match &args.config {
+        Some(path) => RunConfig::from_file(path).unwrap_or_else(|e| exit_with_error(&e)),
+        None => RunConfig::default(),
+    };
+    if matches.value_source("initial_condition") != Some(ValueSource::CommandLine) {
+        if let Some(name) = &config.initial.condition {
+            args.initial_condition = InitialCondition::from_str(name, true).unwrap_or_else(|_| {
+                exit_with_error(&format!("unknown initial condition '{}'", name))
+            });
+        }
+    }
+    args.wave_amplitude = args.wave_amplitude.or(config.initial.amplitude);
+    args.wave_width = args.wave_width.or(config.initial.width);
+    args.wave_direction = args.wave_direction.or(config.initial.direction);
+    if let Some((x, y)) = config.initial.center {
+        args.wave_x = args.wave_x.or(Some(x));
+        args.wave_y = args.wave_y.or(Some(y));
+    }
+    if args.initial_velocity.is_none() {
+        args.initial_velocity = config.initial.velocity;
+    }
+    if let Some(field) = &args.initial_velocity {
+        field.validate().unwrap_or_else(|e| exit_with_error(&e));
+    }
+    if let Some(configured) = config.initial.perturbation {
+        args.perturb_depth = args.perturb_depth.or(Some(configured.depth));
+        args.perturb_velocity = args.perturb_velocity.or(Some(configured.velocity));
+        args.perturb_length = args.perturb_length.or(Some(configured.length));
+        args.perturb_seed = args.perturb_seed.or(Some(configured.seed));
+    }
+    perturbation(&args)
+        .validate()
+        .unwrap_or_else(|e| exit_with_error(&e));
+    let raster_fields = match &args.geotiff {
+        Some(list) => raster::parse_raster_fields(list).unwrap_or_else(|e| exit_with_error(&e)),
+        None => Vec::new(),
+    };
+    let png_fields = match &args.png {
+        Some(list) => render::parse_render_fields(list).unwrap_or_else(|e| exit_with_error(&e)),
+        None => Vec::new(),
+    };
+    let png_options = RenderOptions {
+        width: args.png_width,
+        range: args.png_range.as_deref().map(|spec| {
+            render::parse_range(spec)
+                .unwrap_or_else(|e| exit_with_error(&format!("--png-range: {}", e)))
+        }),
+    };
+    let output_triggers = match &args.output_on {
+        Some(list) => cadence::parse_triggers(list)
+            .unwrap_or_else(|e| exit_with_error(&format!("--output-on: {}", e))),
+        None => Vec::new(),
+    };
+    let preview_mode = args.preview.as_deref().map(|mode| {
+        PreviewMode::parse(mode).unwrap_or_else(|e| exit_with_error(&format!("--preview: {}", e)))
+    });
+    let output_fields = match &args.fields {
+        Some(list) => output::parse_field_list(list).unwrap_or_else(|e| exit_with_error(&e)),
+        None => config
+            .output
+            .fields
+            .clone()
+            .unwrap_or_else(|| OutputField::DEFAULT.to_vec()),
+    };
+    let mut output_schedule: Vec<ScheduleEntry> = config
+        .output
+        .schedule
+        .iter()
+        .map(|(key, &interval)| ScheduleEntry::new(key, interval))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| exit_with_error(&format!("--config output.schedule: {}", e)));
+    output_schedule.append(&mut args.output_schedule);
+    args.output_schedule = output_schedule;
+    for field in OutputSchedule::new(0.0, &args.output_schedule).unused_fields(&output_fields) {
+        eprintln!(
+            "Warning: --output-schedule vtk.{} is not among the output fields",
+            field.name()
+        );
+    }
+    args.min_dt = args.min_dt.or(config.timestep.min_dt);
+    args.max_dt = args.max_dt.or(config.timestep.max_dt);
+    args.dt_growth = args.dt_growth.or(config.timestep.max_growth);
+    let constants = PhysicsConfig {
+        gravity: args.gravity.or(config.physics.gravity),
+        dry_tolerance: args.dry_tolerance.or(config.physics.dry_tolerance),
+        density: args.density.or(config.physics.density),
+        desingularization: args.desingularization.or(config.physics.desingularization),
+        friction_depth: args.friction_depth.or(config.physics.friction_depth),
+    }
+    .constants()
+    .unwrap_or_else(|e| exit_with_error(&e));
+    if !(0.5..=1.0).contains(&args.theta) {
+        exit_with_error("--theta must be in [0.5, 1]");
+    }
+    if args.eddy_viscosity < 0.0 {
+        exit_with_error("--eddy-viscosity must not be negative");
+    }
+
+    println!("═══════════════════════════════════════════════════════════");
+    println!("  Shallow Water Equations Solver (2D Triangular Mesh)");
+    println!("═══════════════════════════════════════════════════════════");
+    println!();
+
+    // GPU availability check
+    #[cfg(feature = "gpu")]
+    match requested_backend(&args) {
+        Backend::Gpu => println!("GPU Acceleration: ENABLED (WebGPU)"),
+        Backend::Auto => println!("GPU Acceleration: AUTO (fastest of CPU and GPU)"),
+        Backend::Cpu => {
+            println!("GPU Acceleration: Available but not enabled (use --backend gpu)")
+        }
+    }
+
+    #[cfg(not(feature = "gpu"))]
+    if requested_backend(&args) != Backend::Cpu || args.validate_gpu {
+        println!("WARNING: GPU requested but not compiled. Build with --features gpu");
+        println!("Falling back to CPU mode.");
+    }
+
+    #[cfg(not(feature = "live"))]
+    if args.live {
+        println!("WARNING: live viewer requested but not compiled. Build with --features live");
+        println!("Running without a window.");
+    }
+
+    #[cfg(not(feature = "serve"))]
+    if args.serve.is_some() {
+        println!("WARNING: dashboard requested but not compiled. Build with --features serve");
+    }
+
+    #[cfg(not(all(feature = "plugins", unix)))]
+    if !args.plugins.is_empty() {
+        exit_with_error("--plugin is not compiled in; build with --features plugins (Unix only)");
+    }
+    #[cfg(not(feature = "scripting"))]
+    if !config.scripts.is_empty() {
+        exit_with_error("scripts in --config are not compiled in; build with --features scripting");
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    if args.results_db.is_some() {
+        println!(
+            "WARNING: results database requested but not compiled. Build with --features sqlite"
+        );
+    }
+
+    println!();
+    println!("Mesh Configuration:");
+    println!(
+        "  Grid points: {}x{} = {} triangles",
+        args.nx,
+        args.ny,
+        2 * (args.nx - 1) * (args.ny - 1)
+    );
+    match args.coordinates {
+        Coordinates::Cartesian => {
+            println!("  Domain size: {:.2}m × {:.2}m", args.width, args.height)
+        }
+        Coordinates::Spherical => println!(
+            "  Domain: lon [{:.2}, {:.2}]°, lat [{:.2}, {:.2}]° (spherical)",
+            args.lon_min,
+            args.lon_min + args.width,
+            args.lat_min,
+            args.lat_min + args.height
+        ),
+    }
+    println!();
+    println!("Simulation Parameters:");
+    println!("  Final time: {:.2}s", args.final_time);
+    println!("  CFL number: {:.2}", args.cfl);
+    println!("  Flux scheme: {:?}", args.flux_scheme);
+    println!("  Gradient method: {:?}", args.gradient_method);
+    println!("  Time integrator: {:?}", args.time_integrator);
+    if args.discretization == Spatial::Dg1 {
+        println!("  Discretization: P1 discontinuous Galerkin");
+    }
+    if args.track_wet_region {
+        println!("  Wet-region tracking: on");
+    }
+    if matches!(args.time_integrator, Integrator::SemiImplicit) {
+        println!(
+            "  Theta: {:.2}, max Courant number: {:.1}",
+            args.theta, args.max_courant
+        );
+    }
+    if matches!(args.time_integrator, Integrator::Imex) && args.eddy_viscosity > 0.0 {
+        println!("  Eddy viscosity: {} m^2/s", args.eddy_viscosity);
+    }
+    println!("  Output interval: {:.2}s", args.output_interval);
+    if let Some(steps) = args.output_every {
+        println!("  Output every: {} steps", steps);
+    }
+    if let Some(events) = &args.output_on {
+        println!("  Output on: {}", events);
+    }
+    if !args.output_schedule.is_empty() {
+        let entries: Vec<String> = args
+            .output_schedule
+            .iter()
+            .map(|entry| match entry.interval {
+                0.0 => format!("{} every step", entry.key()),
+                interval => format!("{} every {}s", entry.key(), interval),
+            })
+            .collect();
+        println!("  Output schedule: {}", entries.join(", "));
+    }
+    if let Some(limit) = &walltime {
+        println!(
+            "  Wall-clock limit: {}",
+            walltime::format_duration(limit.limit())
+        );
+    }
+    if args.reload_forcing {
+        println!(
+            "  Forcing reload: on change{}",
+            match &args.reload_interval {
+                Some(interval) => format!(" and every {}", interval),
+                None => String::new(),
+            }
+        );
+    }
+    println!(
+        "  Threads: {}{}",
+        rayon::current_num_threads(),
+        if args.deterministic {
+            " (deterministic reductions)"
+        } else {
+            ""
+        }
+    );
+    println!(
+        "  Output fields: {}",
+        output_fields
+            .iter()
+            .map(|f| f.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    println!("  Initial condition: {:?}", args.initial_condition);
+    if let Some(field) = &args.initial_velocity {
+        println!("  Initial velocity: {:?}", field);
+    }
+    println!("  Topography: {:?}", args.topography);
+    if let Some(max_slope) = args.max_bed_slope {
+        println!(
+            "  Bed slope limit: {:.3} (ramped below {:.3} m)",
+            max_slope, args.slope_limit_depth
+        );
+    }
+    println!("  Friction: {:?}", args.friction);
+    if matches!(args.friction, Friction::Manning) {
+        println!("  Manning's n: {:.4}", args.manning_n);
+    } else if matches!(args.friction, Friction::Chezy) {
+        println!("  Chezy C: {:.1}", args.chezy_c);
+    } else if matches!(args.friction, Friction::Voellmy) {
+        println!(
+            "  Voellmy mu: {:.3}, xi: {:.1} m/s^2",
+            args.voellmy_mu, args.voellmy_xi
+        );
+    } else if matches!(args.friction, Friction::Bingham) {
+        println!(
+            "  Bingham yield stress: {:.1} Pa, viscosity: {:.2} Pa s",
+            args.yield_stress, args.bingham_viscosity
+        );
+    }
+    println!("  Bulk density: {:.1} kg/m^3", constants.density);
+    println!(
+        "  Gravity: {} m/s^2, dry tolerance: {:e} m, desingularization: {:e} m",
+        constants.gravity, constants.dry_tolerance, constants.desingularization
+    );
+    println!("  Friction cut-off depth: {:e} m", constants.friction_depth);
+    println!();
+
+    // Create mesh
+    println!("Creating triangular mesh...");
+    let origin = match args.coordinates {
+        Coordinates::Cartesian => (0.0, 0.0),
+        Coordinates::Spherical => (args.lon_min, args.lat_min),
+    };
+    let center = (origin.0 + args.width / 2.0, origin.1 + args.height / 2.0);
+    let topography_type = match args.topography {
+        Topography::Flat => TopographyType::Flat,
+        Topography::Slope => TopographyType::Slope {
+            gradient_x: 0.01,
+            gradient_y: 0.005,
+        },
+        Topography::Gaussian => TopographyType::Gaussian {
+            center,
+            amplitude: 1.0,
+            width: args.width / 4.0,
+        },
+        Topography::Channel => TopographyType::Channel {
+            depth: 2.0,
+            width: args.width / 2.0,
+        },
+    };
+
+    let mut holes = if args.holes.is_empty() {
+        config.mesh.holes.clone().unwrap_or_default()
+    } else {
+        args.holes
+            .iter()
+            .map(|spec| mesh::parse_polygon(spec))
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| exit_with_error(&format!("--hole: {}", e)))
+    };
+    if matches!(args.initial_condition, InitialCondition::PartialDamBreak) {
+        holes.extend(partial_dam(&args, origin, center));
+    }
+    if let Some(hole) = holes.iter().find(|hole| hole.len() < 3) {
+        exit_with_error(&format!("hole {:?} needs at least 3 vertices", hole));
+    }
+    if args.dry_run && args.command.is_some() {
+        exit_with_error("--dry-run cannot be combined with a subcommand");
+    }
+    if args.max_walltime.is_some() && args.command.is_some() {
+        exit_with_error("--max-walltime cannot be combined with a subcommand");
+    }
+    if args.reload_forcing {
+        if args.command.is_some() {
+            exit_with_error("--reload-forcing cannot be combined with a subcommand");
+        }
+        if args.rainfall.is_none() && args.snowmelt.is_none() && args.nest_from.is_none() {
+            exit_with_error("--reload-forcing needs --rainfall, --snowmelt or --nest-from");
+        }
+        if let Some(Err(e)) = args
+            .reload_interval
+            .as_deref()
+            .map(walltime::parse_duration)
+        {
+            exit_with_error(&format!("--reload-interval: {}", e));
+        }
+    }
+    if let Some(Command::Bench(bench)) = &args.command {
+        run_bench(&args, bench, constants);
+        return;
+    }
+    if let Some(Command::Validate(validate)) = &args.command {
+        run_validation(&args, validate, constants);
+        return;
+    }
+    if let Some(Command::Sweep(sweep)) = &args.command {
+        run_sweep(
+            &args,
+            sweep,
+            origin,
+            topography_type,
+            &holes,
+            constants,
+            center,
+        );
+        return;
+    }
+    let mesh_key =
+        format!(
+        "{:?} nx={} ny={} width={} height={} origin={:?} topography={:?} holes={:?} reorder={} \
+         selafin={:?} hecras={:?}",
+        args.coordinates,
+        args.nx,
+        args.ny,
+        args.width,
+        args.height,
+        origin,
+        topography_type,
+        holes,
+        args.reorder,
+        args.selafin_mesh,
+        (&args.hecras_geometry, &args.hecras_area, &args.hecras_terrain)
+    );
+    let (solvers, output) = match &args.command {
+        None => (1, Some((output_fields.as_slice(), args.output_queue))),
+        Some(Command::Ensemble(ensemble)) => {
+            (ensemble.members.min(rayon::current_num_threads()), None)
+        }
+        Some(Command::Assimilate(assimilate)) => (assimilate.members, None),
+        Some(_) => (1, None),
+    };
+    check_memory(
+        &args,
+        &memory::MemoryEstimate::new(
+            memory::MeshSize::structured(args.nx, args.ny),
+            solvers,
+            output,
+        ),
+    );
+    let cached = args.mesh_cache.as_deref().and_then(|path| {
+        match TriangularMesh::load_cache(path, &mesh_key) {
+            Ok(Some(mesh)) => {
+                println!("  Loaded from cache {}", path);
+                Some(mesh)
+            }
+            Ok(None) => {
+                println!(
+                    "  Cache {} has different mesh parameters; regenerating",
+                    path
+                );
+                None
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                eprintln!("Warning: Could not read mesh cache {}: {}", path, e);
+                None
+            }
+        }
+    });
+    let mesh = match cached {
+        Some(mesh) => mesh,
+        None => {
+            let mesh = build_mesh(&args, origin, topography_type, &holes);
+            if let Some(path) = &args.mesh_cache {
+                match mesh.save_cache(path, &mesh_key) {
+                    Ok(()) => println!("  Saved to cache {}", path),
+                    Err(e) => eprintln!("Warning: Could not write mesh cache {}: {}", path, e),
+                }
+            }
+            mesh
+        }
+    };
+    println!("  Nodes: {}", mesh.nodes.len());
+    println!("  Triangles: {}", mesh.triangles.len());
+    println!("  Edges: {}", mesh.edges.len());
+    if output_fields.contains(&OutputField::Partition) {
+        let partition = mesh.block_partition(rayon::current_num_threads());
+        let stats = mesh.partition_stats(&partition);
+        let halo_cells: usize = mesh
+            .subdomains(&partition, 1)
+            .iter()
+            .map(|s| s.halo[0].len())
+            .sum();
+        println!(
+            "  Partition: {} blocks of {}-{} cells, {} halo faces, {} halo cells",
+            stats.parts, stats.min_cells, stats.max_cells, stats.halo_faces, halo_cells
+        );
+    }
+    if output_fields.contains(&OutputField::Color) {
+        let colors = mesh
+            .greedy_coloring()
+            .into_iter()
+            .max()
+            .map_or(0, |c| c + 1);
+        println!("  Coloring: {} colors", colors);
+    }
+
+    // Report bed elevation range
+    let z_min = mesh
+        .nodes
+        .iter()
+        .map(|n| n.z)
+        .min_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap_or(0.0);
+    let z_max = mesh
+        .nodes
+        .iter()
+        .map(|n| n.z)
+        .max_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap_or(0.0);
+    println!("  Bed elevation range: [{:.3}, {:.3}] m", z_min, z_max);
+    println!();
+
+    let raster_crs = match args.coordinates {
+        Coordinates::Cartesian => RasterCrs::Projected(args.epsg),
+        Coordinates::Spherical => RasterCrs::Geographic(args.epsg.unwrap_or(4326)),
+    };
+    if !matches!(args.command, None | Some(Command::Sweep(_))) {
+        if args.nest_from.is_some() {
+            exit_with_error("--nest-from cannot be combined with a subcommand");
+        }
+        if args.bed_deformation.is_some() {
+            exit_with_error("--bed-deformation cannot be combined with a subcommand");
+        }
+        if args.results_db.is_some() {
+            exit_with_error("--results-db records single runs and sweeps only");
+        }
+    }
+    if args.selafin_restart && args.command.is_some() {
+        exit_with_error("--selafin-restart cannot be combined with a subcommand");
+    }
+    if args.hot_start.is_some() && args.command.is_some() {
+        exit_with_error("--hot-start cannot be combined with a subcommand");
+    }
+    if !args.output_regions.is_empty() && args.command.is_some() {
+        exit_with_error("--output-region cannot be combined with a subcommand");
+    }
+    if !args.profiles.is_empty() && args.command.is_some() {
+        exit_with_error("--profile-line cannot be combined with a subcommand");
+    }
+    if let Some(list) = &args.check_symmetry {
+        if args.command.is_some() {
+            exit_with_error("--check-symmetry cannot be combined with a subcommand");
+        }
+        if let Err(e) = symmetry::parse_symmetries(list) {
+            exit_with_error(&format!("--check-symmetry: {}", e));
+        }
+        if args.symmetry_tolerance.is_nan() || args.symmetry_tolerance < 0.0 {
+            exit_with_error("--symmetry-tolerance must not be negative");
+        }
+    }
+    if args.check_invariants {
+        if args.command.is_some() {
+            exit_with_error("--check-invariants cannot be combined with a subcommand");
+        }
+        if !(args.invariant_max_velocity > 0.0 && args.invariant_max_velocity.is_finite()) {
+            exit_with_error("--invariant-max-velocity must be positive");
+        }
+    }
+    if (args.runup || !args.runup_lines.is_empty()) && args.command.is_some() {
+        exit_with_error("--runup and --runup-line cannot be combined with a subcommand");
+    }
+    if !(args.runup_depth > 0.0 && args.runup_depth.is_finite()) {
+        exit_with_error("--runup-depth must be positive");
+    }
+    for (k, spec) in args.runup_lines.iter().enumerate() {
+        if let Err(e) = profile::parse_polyline(spec, k) {
+            exit_with_error(&format!("--runup-line: {}", e));
+        }
+    }
+    if !args.plugins.is_empty() && args.command.is_some() {
+        exit_with_error("--plugin cannot be combined with a subcommand");
+    }
+    if !config.scripts.is_empty() && args.command.is_some() {
+        exit_with_error("scripts in --config cannot be combined with a subcommand");
+    }
+    if !(args.arrival_depth > 0.0 && args.arrival_depth.is_finite()) {
+        exit_with_error("--arrival-depth must be positive");
+    }
+    if args
+        .profile_spacing
+        .is_some_and(|d| !(d > 0.0 && d.is_finite()))
+    {
+        exit_with_error("--profile-spacing must be positive");
+    }
+    for (k, spec) in args.profiles.iter().enumerate() {
+        if let Err(e) = profile::parse_polyline(spec, k) {
+            exit_with_error(&format!("--profile-line: {}", e));
+        }
+    }
+    if let Some(depth) = args.flood_extent {
+        if args.command.is_some() {
+            exit_with_error("--flood-extent cannot be combined with a subcommand");
+        }
+        if !(depth > 0.0 && depth.is_finite()) {
+            exit_with_error("--flood-extent must be a positive depth");
+        }
+    }
+    if args
+        .output_outside_spacing
+        .is_some_and(|d| !(d > 0.0 && d.is_finite()))
+    {
+        exit_with_error("--output-outside-spacing must be positive");
+    }
+    if args.swmm_inlets.is_some() && args.command.is_some() {
+        exit_with_error("--swmm-inlets cannot be combined with a subcommand");
+    }
+    if args.metrics.is_some() && args.command.is_some() {
+        exit_with_error("--metrics cannot be combined with a subcommand");
+    }
+    match &args.command {
+        Some(Command::Ensemble(ensemble)) => {
+            run_ensemble(&args, ensemble, &mesh, constants, center, raster_crs);
+            return;
+        }
+        Some(Command::Calibrate(calibrate)) => {
+            run_calibration(&args, calibrate, &mesh, constants, center);
+            return;
+        }
+        Some(Command::Sensitivity(sensitivity)) => {
+            run_sensitivity(&args, sensitivity, &mesh, constants, center);
+            return;
+        }
+        Some(Command::Assimilate(assimilate)) => {
+            run_assimilation(&args, assimilate, &mesh, constants, center, &output_fields);
+            return;
+        }
+        #[cfg(feature = "serve")]
+        Some(Command::Serve(_)) => unreachable!("dispatched before the mesh is built"),
+        Some(Command::Compare(_)) => unreachable!("dispatched before the mesh is built"),
+        Some(Command::Validate(_)) | Some(Command::Bench(_)) => {
+            unreachable!("dispatched before the mesh is built")
+        }
+        Some(Command::Sweep(_)) | None => {}
+    }
+
+    // Create solver
+    println!("Initializing solver...");
+    let (mut solver, setup) = create_solver(&args, mesh, constants, friction_law(&args));
+    setup.print();
+    println!(
+        "  Setting {} initial condition...",
+        args.initial_condition
+            .to_possible_value()
+            .unwrap()
+            .get_name()
+    );
+    if let Some(porosity) = solver.porosity() {
+        println!(
+            "  Porosity: mean storage {:.3}, {} blocked edges",
+            porosity.mean_storage(&solver.mesh),
+            porosity
+                .conveyance
+                .iter()
+                .filter(|&&psi| psi == 0.0)
+                .count()
+        );
+    }
+    if let Some(subgrid) = solver.subgrid() {
+        println!(
+            "  Subgrid bathymetry: {} of {} cells with subgrid relief",
+            subgrid.varying_cells(),
+            subgrid.cells.len()
+        );
+    }
+    if let Some(ice) = &solver.ice_cover {
+        println!(
+            "  Ice cover: {:.1} m^2 in {} cells, ice n = {}{}",
+            ice.covered_area(&solver.mesh),
+            ice.covered.iter().filter(|&&c| c).count(),
+            ice.ice_manning,
+            if ice.blocks_wind && args.wind.is_some() {
+                ", sheltered from the wind"
+            } else {
+                ""
+            }
+        );
+    }
+    if let Some(manning) = &solver.manning_field {
+        let area: f64 = solver.mesh.triangles.iter().map(|t| t.area).sum();
+        let mean = solver
+            .mesh
+            .triangles
+            .iter()
+            .zip(manning)
+            .map(|(t, n)| t.area * n)
+            .sum::<f64>()
+            / area;
+        println!(
+            "  Roughness field: n from {:.4} to {:.4}, area mean {:.4}",
+            manning.iter().copied().fold(f64::INFINITY, f64::min),
+            manning.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            mean
+        );
+    }
+    if let Some(sediment) = &solver.sediment {
+        let (g, rho) = (solver.constants.gravity, solver.constants.density);
+        println!(
+            "  Sediment: settling velocity {:.4} m/s, critical shear stress {:.3} Pa{}",
+            sediment.properties.settling_velocity(g, rho),
+            sediment.properties.critical_shear_stress(g, rho),
+            if sediment.properties.density_coupling {
+                ", density coupled"
+            } else {
+                ""
+            }
+        );
+    }
+    if let Some(scalar) = &solver.buoyant_scalar {
+        println!(
+            "  Buoyant scalar: {:?}, ambient {} ({:.2} kg/m^3), {} outfall(s)",
+            scalar.kind,
+            scalar.ambient,
+            scalar.kind.density(scalar.ambient),
+            scalar.outfalls.len()
+        );
+    }
+    set_initial_condition(&mut solver, &args, center, 1.0, 0).print();
+    if let Some(path) = &args.nest_from {
+        nest_in_outer_run(&mut solver, path);
+    }
+    if let (true, Some(path)) = (args.selafin_restart, &args.selafin_mesh) {
+        let (time, cells) = Selafin::read(path)
+            .and_then(|file| file.initialize(&mut solver))
+            .unwrap_or_else(|e| exit_with_error(&format!("--selafin-restart: {}", e)));
+        println!(
+            "  Restart: {} of {} cells from t = {:.3}s of {}",
+            cells,
+            solver.mesh.triangles.len(),
+            time,
+            path
+        );
+    }
+    if let Some(path) = &args.hot_start {
+        hot_start(&mut solver, path, &args);
+    }
+    if let Some(path) = &args.bed_deformation {
+        let deformation = BedDeformation::read(path)
+            .unwrap_or_else(|e| exit_with_error(&format!("--bed-deformation: {}", e)));
+        let (t_start, t_end) = deformation.time_range();
+        println!(
+            "  Bed deformation: {} x {} grid, {} frames, t = {:.3}-{:.3}s",
+            deformation.xs.len(),
+            deformation.ys.len(),
+            deformation.frames.len(),
+            t_start,
+            t_end
+        );
+        solver.set_bed_deformation(deformation);
+        if solver.precision == solver::Precision::Single {
+            exit_with_error("--precision single does not support moving beds");
+        }
+    }
+
+    let initial_mass = solver.compute_total_mass();
+    let initial_energy = solver.compute_total_energy();
+    println!("  Initial mass: {:.6}", initial_mass);
+    println!("  Initial energy: {:.6}", initial_energy);
+    println!();
+
+    if args.dry_run {
+        report_dry_run(
+            &args,
+            &mut solver,
+            &output_fields,
+            &png_fields,
+            &raster_fields,
+        );
+        return;
+    }
+
+    #[cfg(feature = "gpu")]
+    if args.validate_gpu {
+        validate_gpu(&args, &solver);
+        return;
+    }
+    #[cfg(feature = "gpu")]
+    select_backend(&args, &mut solver);
+
+    solver.profiler.enabled = args.profile || args.profile_outputs;
+    let run_start = Instant::now();
+    #[cfg(feature = "sqlite")]
+    let mut results = args
+        .results_db
+        .as_deref()
+        .map(|path| start_results(path, &args, &solver, constants));
+    let manifest_filename = format!("{}_manifest.json", args.output_prefix);
+    let mut manifest = RunManifest::new(
+        std::env::args().collect(),
+        serde_json::to_value(&args).expect("options are serializable"),
+        match &args.config {
+            Some(_) => serde_json::to_value(&config).expect("configuration is serializable"),
+            None => serde_json::Value::Null,
+        },
+        &solver.mesh,
+    );
+    if let Err(e) = manifest.save(&manifest_filename) {
+        eprintln!("Warning: Could not write run manifest {}", e);
+    }
+
+    // Save initial state
+    #[cfg(feature = "serve")]
+    let mut dashboard = args.serve.as_deref().map(|address| {
+        let dashboard = dashboard::Dashboard::start(address)
+            .unwrap_or_else(|e| exit_with_error(&format!("--serve: {}", e)));
+        println!("Dashboard: http://{}/", dashboard.address());
+        dashboard
+    });
+    let mut metrics = args.metrics.as_deref().map(|address| {
+        let metrics = metrics::MetricsExporter::start(address, &solver, args.final_time)
+            .unwrap_or_else(|e| exit_with_error(&format!("--metrics: {}", e)));
+        println!("Metrics: http://{}/metrics", metrics.address());
+        metrics
+    });
+    let writer = if args.output_regions.is_empty() {
+        FrameWriter::new(solver.mesh.clone(), args.output_queue)
+    } else {
+        region_writer(&solver, &args)
+    };
+    let mut envelope = FloodEnvelope::new(&solver, args.arrival_depth);
+    let track_envelope = !raster_fields.is_empty()
+        || (args.flood_extent.is_some() && args.flood_extent_at != ExtentOutput::Outputs);
+    let mut shear = raster_fields
+        .iter()
+        .any(|field| {
+            matches!(
+                field,
+                RasterField::ShearIntegral | RasterField::ShearExceedance | RasterField::MaxShear
+            )
+        })
+        .then(|| {
+            ShearExposure::new(
+                &solver,
+                critical_shear_stress(&solver, &args, &raster_fields),
+            )
+        });
+    let mut frames = FrameOutputs::new(&writer, &args.output_prefix, &output_fields);
+    frames.png_fields = png_fields;
+    frames.png_options = png_options;
+    frames.extent = args
+        .flood_extent
+        .filter(|_| args.flood_extent_at != ExtentOutput::Max)
+        .map(|depth| (depth, raster_crs));
+    frames.preview = preview_mode.map(|mode| (mode, args.preview_width));
+    let output_start = solver.profiler.start();
+    frames.write_all(&solver);
+    #[cfg(feature = "serve")]
+    if let Some(dashboard) = &dashboard {
+        dashboard.publish(&solver, 0, 0.0);
+    }
+    solver
+        .profiler
+        .record(profiler::Phase::Output, output_start);
+
+    let step_count;
+    let mut final_budget = None;
+    let mut time_limited = false;
+    let mut symmetry_broken = false;
+
+    if args.steady_state {
+        if args.nest_save.is_some() {
+            eprintln!("Warning: --nest-save is ignored with --steady-state");
+        }
+        if args.selafin_output.is_some() {
+            eprintln!("Warning: --selafin-output is ignored with --steady-state");
+        }
+        if args.sww_output.is_some() {
+            eprintln!("Warning: --sww-output is ignored with --steady-state");
+        }
+        if args.zarr_output.is_some() {
+            eprintln!("Warning: --zarr-output is ignored with --steady-state");
+        }
+        if args.swmm_inlets.is_some() {
+            eprintln!("Warning: --swmm-inlets is ignored with --steady-state");
+        }
+        if args.max_walltime.is_some() {
+            eprintln!("Warning: --max-walltime is ignored with --steady-state");
+        }
+        if args.reload_forcing {
+            eprintln!("Warning: --reload-forcing is ignored with --steady-state");
+        }
+        if solver.timestep_control.is_some() {
+            eprintln!(
+                "Warning: --min-dt, --max-dt and --dt-growth are ignored with --steady-state"
+            );
+        }
+        if args.source_splitting.is_some() {
+            eprintln!("Warning: --source-splitting is ignored with --steady-state");
+        }
+        if args.discretization == Spatial::Dg1 {
+            eprintln!("Warning: --discretization dg1 is ignored with --steady-state");
+        }
+        if !args.profiles.is_empty() {
+            eprintln!("Warning: --profile-line is ignored with --steady-state");
+        }
+        if args.runup || !args.runup_lines.is_empty() {
+            eprintln!("Warning: --runup and --runup-line are ignored with --steady-state");
+        }
+        if args.check_symmetry.is_some() {
+            eprintln!("Warning: --check-symmetry is ignored with --steady-state");
+        }
+        if args.check_invariants {
+            eprintln!("Warning: --check-invariants is ignored with --steady-state");
+        }
+        if !args.plugins.is_empty() {
+            eprintln!("Warning: --plugin is ignored with --steady-state");
+        }
+        if !config.scripts.is_empty() {
+            eprintln!("Warning: scripts in --config are ignored with --steady-state");
+        }
+        if args.output_every.is_some() || args.output_on.is_some() {
+            eprintln!("Warning: --output-every and --output-on are ignored with --steady-state");
+        }
+        if !args.output_schedule.is_empty() {
+            eprintln!("Warning: --output-schedule is ignored with --steady-state");
+        }
+        println!("Starting steady-state iterations...");
+        let report = solver.solve_steady_state(
+            args.steady_tolerance,
+            args.max_iterations,
+            args.local_time_stepping,
+        );
+        step_count = report.iterations;
+
+        let report_every = (report.residual_history.len() / 20).max(1);
+        for (iteration, residual) in report.residual_history.iter().enumerate() {
+            if iteration % report_every == 0 || iteration + 1 == report.residual_history.len() {
+                println!("  iteration {:>8}, residual = {:.6e}", iteration, residual);
+            }
+        }
+        if report.converged {
+            println!("  Converged after {} iterations", report.iterations);
+        } else {
+            println!(
+                "  WARNING: not converged after {} iterations",
+                report.iterations
+            );
+        }
+
+        write_convergence_history(&report.residual_history, &args.output_prefix);
+        if let Some(metrics) = &metrics {
+            metrics.record_step(&solver, step_count);
+            let mass_error = (solver.compute_total_mass() - initial_mass) / initial_mass * 100.0;
+            metrics.record_output(mass_error.abs());
+        }
+        frames.write_all(&solver);
+        #[cfg(feature = "serve")]
+        if let Some(dashboard) = &dashboard {
+            let mass_error = (solver.compute_total_mass() - initial_mass) / initial_mass * 100.0;
+            dashboard.publish(&solver, step_count, mass_error.abs());
+        }
+    } else {
+        // Time stepping
+        println!("Starting time integration...");
+        let initial_budget = BudgetTracker::new(&solver).budget(&solver);
+        // Not available on the other integrators; the report then falls
+        // back to the plain change of volume
+        solver.enable_mass_exchange().ok();
+        let tables = Tables {
+            prefix: args.output_prefix.clone(),
+            csv: args.table_format != TableFormat::Parquet,
+            parquet: args.table_format != TableFormat::Csv,
+        };
+        let dt_log = if args.dt_diagnostics {
+            TimestepLog::create(&args.output_prefix, &solver)
+        } else {
+            None
+        };
+        let invariants = args
+            .check_invariants
+            .then(|| InvariantCheck::new(args.invariant_max_velocity, &args.output_prefix));
+        if let Some(check) = &invariants {
+            check
+                .check(&solver, 0)
+                .unwrap_or_else(|e| exit_with_error(&format!("invariant: {}", e)));
+        }
+        let mut symmetry_checks = create_symmetry_checks(&solver, &args);
+        let symmetry_filename = format!("{}_symmetry.csv", args.output_prefix);
+        let symmetry_log = (!symmetry_checks.is_empty())
+            .then(|| SymmetryLog::create(&symmetry_filename, &solver, &mut symmetry_checks));
+        let symmetry_logged = symmetry_log.as_ref().is_some_and(SymmetryLog::is_logged);
+        let statistics = StatisticsWriter::create(&tables, &initial_budget);
+        #[cfg(feature = "sqlite")]
+        record_results(&mut results, |database| {
+            database.push_statistics(&initial_budget)
+        });
+
+        let gauge_points = match &args.gauges {
+            Some(spec) => calibration::parse_gauge_points(spec)
+                .unwrap_or_else(|e| exit_with_error(&format!("--gauges: {}", e))),
+            None => Vec::new(),
+        };
+        let gauge_cells: Vec<(String, usize)> = gauge_points
+            .into_iter()
+            .map(|(name, p)| {
+                let (x, y) = input_point(&args, p, "--gauges");
+                (name, solver.nearest_cell(x, y).unwrap_or(0))
+            })
+            .collect();
+        let gauges = (!gauge_cells.is_empty())
+            .then(|| GaugeWriter::create(&tables, &solver, gauge_cells.clone()));
+        #[cfg(feature = "sqlite")]
+        record_results(&mut results, |database| {
+            database.push_gauges(solver.time, &named_readings(&solver, &gauge_cells))
+        });
+        let profiles = create_profiles(&solver, &args);
+        let profiles =
+            (!profiles.is_empty()).then(|| ProfileWriter::create(&tables, &solver, profiles));
+        let runup = create_runup_tracker(&solver, &args)
+            .map(|tracker| RunupWriter::create(&tables, &solver, tracker));
+        let nest_writer = args.nest_save.as_deref().and_then(|path| {
+            let created = NestingWriter::create(path, &solver).and_then(|mut writer| {
+                writer.write(&solver)?;
+                Ok(writer)
+            });
+            match created {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    eprintln!("Warning: Could not write output file {}: {}", path, e);
+                    None
+                }
+            }
+        });
+
+        let selafin_writer = args.selafin_output.as_deref().and_then(|path| {
+            let created =
+                SelafinWriter::create(path, &solver, &output_fields, "shallow-water-solver")
+                    .and_then(|mut writer| {
+                        writer.write(&solver)?;
+                        Ok(writer)
+                    });
+            match created {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    eprintln!("Warning: Could not write output file {}: {}", path, e);
+                    None
+                }
+            }
+        });
+
+        let sww_writer = args.sww_output.as_deref().and_then(|path| {
+            let created = SwwWriter::create(path, &solver, &args.output_prefix, mesh_crs(&args))
+                .and_then(|mut writer| {
+                    writer.write(&solver)?;
+                    Ok(writer)
+                });
+            match created {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    eprintln!("Warning: Could not write output file {}: {}", path, e);
+                    None
+                }
+            }
+        });
+
+        let zarr_writer = args.zarr_output.as_deref().and_then(|path| {
+            let created = ZarrWriter::create(
+                path,
+                &solver,
+                &output_fields,
+                args.zarr_time_chunk,
+                &args.output_prefix,
+                mesh_crs(&args),
+            )
+            .and_then(|mut writer| {
+                writer.write(&solver)?;
+                Ok(writer)
+            });
+            match created {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    eprintln!("Warning: Could not write output file {}: {}", path, e);
+                    None
+                }
+            }
+        });
+
+        let mut couplings: Vec<Box<dyn Coupling>> = Vec::new();
+        #[cfg(feature = "scripting")]
+        if !config.scripts.is_empty() {
+            couplings.push(Box::new(start_scripts(&solver, &args, &config)));
+        }
+        #[cfg(all(feature = "plugins", unix))]
+        for path in &args.plugins {
+            let plugin = Plugin::load(path, &args.plugin_config, &solver.mesh)
+                .unwrap_or_else(|e| exit_with_error(&format!("--plugin: {}", e)));
+            println!("  Plugin: {}", plugin.name);
+            couplings.push(Box::new(plugin));
+        }
+        if let Some(path) = &args.swmm_inlets {
+            couplings.push(Box::new(start_drainage(
+                &solver,
+                path,
+                args.swmm_command.as_deref().unwrap_or_default(),
+            )));
+        }
+        if args.reload_forcing {
+            couplings.push(Box::new(ForcingReloader::new(&solver, &args)));
+        }
+
+        if cfg!(feature = "live") && args.live && walltime.take().is_some() {
+            eprintln!("Warning: --max-walltime is ignored with --live");
+        }
+        let mut simulation_config = SimulationConfig::new(args.final_time, args.output_interval);
+        simulation_config.output_every = args.output_every;
+        simulation_config.output_on = output_triggers;
+        simulation_config.gauges = gauge_cells.clone();
+        simulation_config.schedule = args.output_schedule.clone();
+        simulation_config.budget = true;
+        simulation_config.walltime = walltime;
+        simulation_config.checkpoint = Some(checkpoint_filename(&args.output_prefix));
+        let mut simulation = Simulation::with_solver(&mut solver, simulation_config)
+            .unwrap_or_else(|e| exit_with_error(&format!("--output-on: {}", e)));
+        for coupling in couplings {
+            simulation.add_coupling(coupling);
+        }
+        if let Some(check) = invariants {
+            simulation.add_coupling(Box::new(check));
+        }
+
+        if let Some(metrics) = &mut metrics {
+            simulation.add_observer(Box::new(metrics));
+        }
+        if track_envelope {
+            simulation.add_observer(Box::new(&mut envelope));
+        }
+        if let Some(shear) = &mut shear {
+            simulation.add_observer(Box::new(shear));
+        }
+        if let Some(log) = symmetry_log {
+            simulation.add_observer(Box::new(log));
+        }
+        if let Some(log) = dt_log {
+            simulation.add_observer(Box::new(log));
+        }
+        simulation.add_observer(Box::new(statistics));
+        #[cfg(feature = "sqlite")]
+        simulation.add_observer(Box::new(ResultsRecorder {
+            results: &mut results,
+            gauges: &gauge_cells,
+        }));
+        if let Some(gauges) = gauges {
+            simulation.add_observer(Box::new(gauges));
+        }
+        if let Some(profiles) = profiles {
+            simulation.add_observer(Box::new(profiles));
+        }
+        if let Some(runup) = runup {
+            simulation.add_observer(Box::new(runup));
+        }
+        if let Some(mut nest) = nest_writer {
+            simulation.add_observer(Box::new(StreamWriter {
+                stream: OutputStream::Nesting,
+                what: "nesting frame",
+                write: move |solver: &ShallowWaterSolver| nest.write(solver),
+            }));
+        }
+        if let Some(mut selafin) = selafin_writer {
+            simulation.add_observer(Box::new(StreamWriter {
+                stream: OutputStream::Selafin,
+                what: "Selafin time step",
+                write: move |solver: &ShallowWaterSolver| selafin.write(solver),
+            }));
+        }
+        if let Some(mut sww) = sww_writer {
+            simulation.add_observer(Box::new(StreamWriter {
+                stream: OutputStream::Sww,
+                what: ".sww time step",
+                write: move |solver: &ShallowWaterSolver| sww.write(solver),
+            }));
+        }
+        if let Some(mut zarr) = zarr_writer {
+            simulation.add_observer(Box::new(StreamWriter {
+                stream: OutputStream::Zarr,
+                what: "Zarr time step",
+                write: move |solver: &ShallowWaterSolver| zarr.write(solver),
+            }));
+        }
+        simulation.add_observer(Box::new(Progress));
+        simulation.add_observer(Box::new(frames));
+        #[cfg(feature = "serve")]
+        if let Some(dashboard) = &mut dashboard {
+            simulation.add_observer(Box::new(dashboard));
+        }
+        if args.profile_outputs {
+            simulation.add_observer(Box::new(OutputProfile::new(simulation.solver())));
+        }
+
+        if cfg!(feature = "live") && args.live {
+            println!("Live viewer: Space pause, +/- speed, 1/2 field, wheel zoom, drag pan");
+            #[cfg(feature = "live")]
+            live::run(&mut simulation)
+                .unwrap_or_else(|e| exit_with_error(&format!("live viewer: {}", e)));
+        } else {
+            simulation.run().unwrap_or_else(|e| exit_with_error(&e));
+        }
+        let report = simulation.report();
+        step_count = report.steps;
+        final_budget = report.budget;
+        time_limited = report.stopped;
+        for line in simulation.couplings().iter().flat_map(|c| c.report()) {
+            println!("  {}", line);
+        }
+        for e in simulation.finish() {
+            eprintln!("Warning: {}", e);
+        }
+        if !symmetry_checks.is_empty() {
+            symmetry_broken = !report_symmetry(&symmetry_checks, args.symmetry_tolerance);
+            if symmetry_logged {
+                println!("Asymmetry history saved to {}", symmetry_filename);
+            }
+        }
+    }
+
+    let output_start = solver.profiler.start();
+    writer.finish();
+
+    if track_envelope {
+        envelope.update(&solver);
+    }
+    if !raster_fields.is_empty() {
+        save_rasters(
+            &solver,
+            &envelope,
+            shear.as_ref(),
+            &raster_fields,
+            &args,
+            raster_crs,
+        );
+    }
+    if let (Some(depth), ExtentOutput::Max | ExtentOutput::Both) =
+        (args.flood_extent, args.flood_extent_at)
+    {
+        save_flood_extent(
+            &solver,
+            &envelope.max_depth,
+            &format!("{}_max_extent.geojson", args.output_prefix),
+            depth,
+            raster_crs,
+        );
+    }
+    solver
+        .profiler
+        .record(profiler::Phase::Output, output_start);
+
+    println!();
+    if time_limited {
+        println!(
+            "Simulation stopped at the wall-clock limit; state saved to {}",
+            checkpoint_filename(&args.output_prefix)
+        );
+    } else {
+        println!("Simulation completed!");
+    }
+    println!("  Total steps: {}", step_count);
+    println!("  Final time: {:.3}s", solver.time);
+    if let Some(fraction) = solver.active_fraction() {
+        println!("  Active cells at end: {:.1}%", fraction * 100.0);
+    }
+    if let Some(control) = &solver.timestep_control {
+        report_timestep_control(control);
+    }
+    let unconverged = solver.unconverged_solves;
+    if unconverged.count > 0 {
+        eprintln!(
+            "Warning: {} free-surface or viscosity solves stopped at the iteration limit \
+             (largest residual {:.2e})",
+            unconverged.count, unconverged.largest_residual
+        );
+    }
+
+    let final_mass = solver.compute_total_mass();
+    let final_energy = solver.compute_total_energy();
+    let mass_conservation = mass_error_percent(&solver, initial_mass);
+
+    println!();
+    println!("Conservation Properties:");
+    println!("  Initial mass: {:.6}", initial_mass);
+    println!("  Final mass: {:.6}", final_mass);
+    if let Some(exchange) = &solver.mass_exchange {
+        report_mass_exchange(exchange, final_mass);
+    }
+    println!("  Mass conservation error: {:.8}%", mass_conservation);
+    println!("  Initial energy: {:.6}", initial_energy);
+    println!("  Final energy: {:.6}", final_energy);
+    if let Some(budget) = final_budget {
+        println!(
+            "  Momentum: ({:.6}, {:.6}), budget residual ({:.3e}, {:.3e})",
+            budget.momentum.0,
+            budget.momentum.1,
+            budget.momentum_residual.0,
+            budget.momentum_residual.1
+        );
+        println!(
+            "  Energy budget: friction {:.6}, boundary outflow {:.6}, numerical dissipation {:.6}",
+            budget.friction_work, budget.boundary_energy_outflow, budget.numerical_dissipation
+        );
+    }
+    if let Some(metrics) = &metrics {
+        metrics.finish();
+    }
+    #[cfg(feature = "sqlite")]
+    record_results(&mut results, |database| {
+        let mut metrics = vec![
+            ("initial_mass", initial_mass),
+            ("final_mass", final_mass),
+            ("mass_error", mass_conservation),
+            ("initial_energy", initial_energy),
+            ("final_energy", final_energy),
+        ];
+        if let Some(exchange) = &solver.mass_exchange {
+            metrics.extend([
+                ("boundary_inflow", exchange.inflow.iter().sum()),
+                ("boundary_outflow", exchange.outflow.iter().sum()),
+                ("source_volume", exchange.sources),
+                ("coupled_volume", exchange.coupled),
+                ("unaccounted_volume", exchange.unaccounted(final_mass)),
+            ]);
+        }
+        if let Some(budget) = final_budget {
+            metrics.extend([
+                ("friction_work", budget.friction_work),
+                ("boundary_energy_outflow", budget.boundary_energy_outflow),
+                ("numerical_dissipation", budget.numerical_dissipation),
+                ("momentum_residual_x", budget.momentum_residual.0),
+                ("momentum_residual_y", budget.momentum_residual.1),
+            ]);
+        }
+        database.finish_run(&RunSummary {
+            status: if !final_mass.is_finite() {
+                RunStatus::Diverged
+            } else if time_limited {
+                RunStatus::TimeLimit
+            } else {
+                RunStatus::Completed
+            },
+            steps: step_count,
+            simulated_time: solver.time,
+            wall_seconds: run_start.elapsed().as_secs_f64(),
+            metrics: &metrics,
+        })
+    });
+    println!();
+    if solver.profiler.enabled {
+        println!("Run-time profile:");
+        print!(
+            "{}",
+            solver
+                .profiler
+                .snapshot()
+                .report(run_start.elapsed().as_secs_f64())
+        );
+        println!();
+    }
+    manifest.finish(
+        if !final_mass.is_finite() {
+            ManifestStatus::Diverged
+        } else if time_limited {
+            ManifestStatus::TimeLimit
+        } else {
+            ManifestStatus::Completed
+        },
+        step_count,
+        solver.time,
+        &args.output_prefix,
+        &manifest_filename,
+    );
+    println!("Output files saved with prefix: {}", args.output_prefix);
+    match manifest.save(&manifest_filename) {
+        Ok(()) => println!("Run manifest saved to {}", manifest_filename),
+        Err(e) => eprintln!("Warning: Could not write run manifest {}", e),
+    }
+    println!("═══════════════════════════════════════════════════════════");
+    if time_limited {
+        std::process::exit(TIME_LIMIT_EXIT_CODE);
+    }
+    if symmetry_broken {
+        std::process::exit(1);
+    }
+}
+
+/// Exit status of a run stopped by --max-walltime (EX_TEMPFAIL: the job
+/// can be resubmitted to continue)
+const TIME_LIMIT_EXIT_CODE: i32 = 75;
+
+fn checkpoint_filename(prefix: &str) -> String {
+    format!("{}_checkpoint.vtk", prefix)
+}
+
+/// Wall-clock period between checks of the forcing files for updates
+const FORCING_POLL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Forcing files that --reload-forcing re-reads during the run
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Forcing {
+    Rainfall,
+    Snowmelt,
+    Nesting,
+}
+
+/// Replaces the forcing of a running solver with updated files
+struct ForcingReloader {
+    watcher: ForcingWatcher<Forcing>,
+    rainfall_variable: Option<String>,
+    snowmelt: Option<Snowmelt>, // Loaded version, whose melt carries over
+}
+
+impl ForcingReloader {
+    fn new(solver: &ShallowWaterSolver, args: &Args) -> Self {
+        let interval = args.reload_interval.as_deref().map(|spec| {
+            walltime::parse_duration(spec)
+                .unwrap_or_else(|e| exit_with_error(&format!("--reload-interval: {}", e)))
+        });
+        let mut watcher = ForcingWatcher::new(FORCING_POLL, interval);
+        let files = [
+            (Forcing::Rainfall, &args.rainfall),
+            (Forcing::Snowmelt, &args.snowmelt),
+            (Forcing::Nesting, &args.nest_from),
+        ];
+        for (kind, path) in files {
+            if let Some(path) = path {
+                watcher.watch(kind, path);
+            }
+        }
+        let snowmelt = args.snowmelt.as_deref().map(|path| {
+            Snowmelt::read(path, &solver.mesh)
+                .unwrap_or_else(|e| exit_with_error(&format!("--snowmelt: {}", e)))
+        });
+        ForcingReloader {
+            watcher,
+            rainfall_variable: args.rainfall_variable.clone(),
+            snowmelt,
+        }
+    }
+
+    /// Re-read the forcing files that are due; a file that cannot be read
+    /// keeps its previous forcing
+    fn reload(&mut self, solver: &mut ShallowWaterSolver) {
+        for (kind, path) in self.watcher.due() {
+            match self.load(solver, kind, &path) {
+                Ok(summary) => {
+                    println!("  t = {:.3}s: reloaded {} ({})", solver.time, path, summary)
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Warning: could not reload {}: {}; keeping the previous forcing",
+                        path, e
+                    );
+                    self.watcher.failed(kind);
+                }
+            }
+        }
+    }
+
+    fn load(
+        &mut self,
+        solver: &mut ShallowWaterSolver,
+        kind: Forcing,
+        path: &str,
+    ) -> Result<String, String> {
+        match kind {
+            Forcing::Rainfall => {
+                let rainfall = rainfall::read_rainfall(path, self.rainfall_variable.as_deref())?;
+                let summary = format!(
+                    "{} rainfall frames up to t = {:.0} s",
+                    rainfall.times.len(),
+                    rainfall.times[rainfall.times.len() - 1]
+                );
+                solver.replace_source_term(Box::new(rainfall.source_term(&solver.mesh)));
+                Ok(summary)
+            }
+            Forcing::Snowmelt => {
+                let mut snowmelt = Snowmelt::read(path, &solver.mesh)?;
+                if let Some(previous) = &self.snowmelt {
+                    snowmelt.resume_from(previous, solver.time);
+                }
+                let summary = format!("{} snow zones", snowmelt.zones.len());
+                self.snowmelt = Some(snowmelt.clone());
+                solver.replace_source_term(Box::new(snowmelt));
+                Ok(summary)
+            }
+            Forcing::Nesting => {
+                let outer = OuterSolution::load(path)?;
+                if outer.mesh().coordinate_system != solver.mesh.coordinate_system {
+                    return Err("the outer run uses a different coordinate system".to_string());
+                }
+                let summary = format!("outer run up to t = {:.3} s", outer.time_range().1);
+                solver.boundary_closure = Box::new(NestedBoundary { outer });
+                Ok(summary)
+            }
+        }
+    }
+}
+
+impl Coupling for ForcingReloader {
+    fn name(&self) -> String {
+        "--reload-forcing".to_string()
+    }
+
+    fn after_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        self.reload(solver);
+        Ok(())
+    }
+}
+
+/// Couple the inlets in `path` to the sewer model run by `command`
+fn start_drainage(
+    solver: &ShallowWaterSolver,
+    path: &str,
+    command: &str,
+) -> DualDrainage<ExternalSewer> {
+    let inlets = drainage::read_inlets(path)
+        .unwrap_or_else(|e| exit_with_error(&format!("--swmm-inlets: {}", e)));
+    let names: Vec<String> = inlets.iter().map(|inlet| inlet.name.clone()).collect();
+    let drainage = ExternalSewer::spawn(command, &names)
+        .and_then(|network| DualDrainage::new(solver, inlets, network))
+        .unwrap_or_else(|e| exit_with_error(&format!("--swmm-command: {}", e)));
+    println!(
+        "  Dual drainage: {} inlets coupled to '{}'",
+        drainage.inlets.len(),
+        command
+    );
+    drainage
+}
+
+/// Triangle mesh of a HEC-RAS 2D flow area with its terrain
+fn hecras_mesh(args: &Args, path: &str) -> TriangularMesh {
+    if matches!(args.coordinates, Coordinates::Spherical) {
+        exit_with_error("--hecras-geometry requires Cartesian coordinates");
+    }
+    let areas = hecras::read_geometry(path)
+        .unwrap_or_else(|e| exit_with_error(&format!("--hecras-geometry: {}", e)));
+    let area = match &args.hecras_area {
+        Some(name) => areas.iter().find(|a| &a.name == name).unwrap_or_else(|| {
+            let names: Vec<&str> = areas.iter().map(|a| a.name.as_str()).collect();
+            exit_with_error(&format!(
+                "--hecras-area: no 2D flow area '{}' (available: {})",
+                name,
+                names.join(", ")
+            ))
+        }),
+        None => &areas[0],
+    };
+    let mut mesh = area
+        .mesh()
+        .and_then(|mesh| reproject_input_mesh(args, mesh))
+        .unwrap_or_else(|e| exit_with_error(&format!("--hecras-geometry: {}", e)));
+    if let Some(terrain) = &args.hecras_terrain {
+        let (grid, values) = read_raster(args, terrain, "--hecras-terrain");
+        hecras::apply_terrain(&mut mesh, &grid, &values)
+            .unwrap_or_else(|e| exit_with_error(&format!("--hecras-terrain: {}", e)));
+    }
+    println!(
+        "  HEC-RAS flow area '{}': {} cell centres, {} nodes, {} triangles",
+        area.name,
+        area.points.len(),
+        mesh.nodes.len(),
+        mesh.triangles.len()
+    );
+    if let Some(n) = area.manning {
+        println!(
+            "  HEC-RAS default Manning's n: {} (apply with --manning-n)",
+            n
+        );
+    }
+    mesh
+}
+
+/// Generate the structured mesh (or read it from --selafin-mesh or
+/// --hecras-geometry), cut holes
+/// and optionally renumber it
+fn build_mesh(
+    args: &Args,
+    origin: (f64, f64),
+    topography_type: TopographyType,
+    holes: &[Polygon],
+) -> TriangularMesh {
+    let mut mesh = if let Some(path) = &args.selafin_mesh {
+        if matches!(args.coordinates, Coordinates::Spherical) {
+            exit_with_error("--selafin-mesh requires Cartesian coordinates");
+        }
+        let file = Selafin::read(path)
+            .unwrap_or_else(|e| exit_with_error(&format!("--selafin-mesh: {}", e)));
+        println!(
+            "  Selafin mesh '{}': {} nodes, {} triangles, {} time steps",
+            file.title,
+            file.mesh.nodes.len(),
+            file.mesh.triangles.len(),
+            file.frames.len()
+        );
+        reproject_input_mesh(args, file.mesh)
+            .unwrap_or_else(|e| exit_with_error(&format!("--selafin-mesh: {}", e)))
+    } else if let Some(path) = &args.hecras_geometry {
+        hecras_mesh(args, path)
+    } else {
+        match args.coordinates {
+            Coordinates::Cartesian => TriangularMesh::new_rectangular(
+                args.nx,
+                args.ny,
+                args.width,
+                args.height,
+                topography_type,
+            ),
+            Coordinates::Spherical => TriangularMesh::new_geographic(
+                args.nx,
+                args.ny,
+                origin,
+                (args.width, args.height),
+                topography_type,
+                mesh::EARTH_RADIUS,
+            ),
+        }
+    };
+    if !holes.is_empty() {
+        let removed = mesh.cut_holes(holes);
+        println!("  Holes: {} ({} cells removed)", holes.len(), removed);
+    }
+    if args.reorder {
+        let report = mesh.reorder_rcm();
+        if report.applied {
+            println!(
+                "  Reordered (RCM): cell bandwidth {} -> {}, node bandwidth {} -> {}",
+                report.cell_bandwidth.0,
+                report.cell_bandwidth.1,
+                report.node_bandwidth.0,
+                report.node_bandwidth.1
+            );
+        } else {
+            println!(
+                "  Reordering skipped: RCM does not reduce cell bandwidth {}",
+                report.cell_bandwidth.0
+            );
+        }
+    }
+    mesh
+}
+
+/// Serve submitted runs until the process is stopped
+#[cfg(feature = "serve")]
+fn run_server(serve: &ServeArgs) {
+    let executable = std::env::current_exe()
+        .unwrap_or_else(|e| exit_with_error(&format!("serve: executable not found: {}", e)));
+    let config = server::ServerConfig {
+        command: vec![executable.display().to_string()],
+        runs_dir: serve.runs_dir.clone().into(),
+        jobs: serve.jobs,
+        validate: |argv| match Args::try_parse_from(argv) {
+            Ok(args) if args.command.is_some() => {
+                Err("subcommands cannot be submitted".to_string())
+            }
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        },
+    };
+    let server = server::Server::start(&serve.address, config)
+        .unwrap_or_else(|e| exit_with_error(&format!("serve: {}", e)));
+    println!("Simulation server: http://{}/runs", server.address());
+    println!("  Runs directory: {}", serve.runs_dir);
+    println!("  Concurrent runs: {}", serve.jobs.max(1));
+    server.wait();
+}
+
+/// Stations of the --profile-line lines on the solver's mesh
+fn create_profiles(solver: &ShallowWaterSolver, args: &Args) -> Vec<Profile> {
+    let spacing = args
+        .profile_spacing
+        .unwrap_or_else(|| profile::default_spacing(&solver.mesh));
+    let mut profiles = Vec::new();
+    for (k, spec) in args.profiles.iter().enumerate() {
+        let (name, vertices) = profile::parse_polyline(spec, k)
+            .unwrap_or_else(|e| exit_with_error(&format!("--profile-line: {}", e)));
+        let profile = Profile::new(name, &vertices, spacing, solver);
+        if profile.stations.is_empty() {
+            eprintln!(
+                "Warning: profile '{}' lies outside the mesh and is skipped",
+                profile.name
+            );
+            continue;
+        }
+        println!(
+            "  Profile '{}': {} stations, {:.1} m long",
+            profile.name,
+            profile.stations.len(),
+            profile.stations.last().unwrap().distance
+        );
+        profiles.push(profile);
+    }
+    profiles
+}
+
+/// Cell pairings of the --check-symmetry symmetries (none without it)
+fn create_symmetry_checks(solver: &ShallowWaterSolver, args: &Args) -> Vec<SymmetryCheck> {
+    let Some(list) = &args.check_symmetry else {
+        return Vec::new();
+    };
+    let symmetries = symmetry::parse_symmetries(list)
+        .unwrap_or_else(|e| exit_with_error(&format!("--check-symmetry: {}", e)));
+    symmetries
+        .into_iter()
+        .map(|symmetry| {
+            SymmetryCheck::new(&solver.mesh, symmetry)
+                .unwrap_or_else(|e| exit_with_error(&format!("--check-symmetry: {}", e)))
+        })
+        .collect()
+}
+
+/// Shoreline tracker of the --runup-line transects and --runup, or None
+/// without either
+fn create_runup_tracker(solver: &ShallowWaterSolver, args: &Args) -> Option<RunupTracker> {
+    if !args.runup && args.runup_lines.is_empty() {
+        return None;
+    }
+    let spacing = profile::default_spacing(&solver.mesh);
+    let mut transects = Vec::new();
+    for (k, spec) in args.runup_lines.iter().enumerate() {
+        let (name, vertices) = profile::parse_polyline(spec, k)
+            .unwrap_or_else(|e| exit_with_error(&format!("--runup-line: {}", e)));
+        let transect = Profile::new(name, &vertices, spacing, solver);
+        if transect.stations.is_empty() {
+            eprintln!(
+                "Warning: runup transect '{}' lies outside the mesh and is skipped",
+                transect.name
+            );
+            continue;
+        }
+        transects.push(transect);
+    }
+    let tracker = RunupTracker::new(solver, transects, args.runup, args.runup_depth);
+    for transect in &tracker.transects {
+        match transect.initial {
+            Some(shoreline) => println!(
+                "Runup transect '{}': initial shoreline at {:.3} m, elevation {:.3} m",
+                transect.profile.name, shoreline.distance, shoreline.elevation
+            ),
+            None => eprintln!(
+                "Warning: runup transect '{}' starts on dry land; draw it from the sea",
+                transect.profile.name
+            ),
+        }
+    }
+    Some(tracker)
+}
+
+/// Open the results database and register the run, exiting on failure
+#[cfg(feature = "sqlite")]
+fn start_results(
+    path: &str,
+    args: &Args,
+    solver: &ShallowWaterSolver,
+    constants: PhysicalConstants,
+) -> ResultsDatabase {
+    let command: Vec<String> = std::env::args().collect();
+    let mut database = ResultsDatabase::open(path)
+        .unwrap_or_else(|e| exit_with_error(&format!("--results-db {}: {}", path, e)));
+    let run = database
+        .begin_run(
+            &args.output_prefix,
+            &command.join(" "),
+            solver.mesh.triangles.len(),
+            &run_parameters(args, constants),
+        )
+        .unwrap_or_else(|e| exit_with_error(&format!("--results-db {}: {}", path, e)));
+    println!("  Results database: {} (run {})", path, run);
+    database
+}
+
+/// Parameters stored with each run in the results database
+#[cfg(feature = "sqlite")]
+fn run_parameters(args: &Args, constants: PhysicalConstants) -> Vec<(&'static str, String)> {
+    fn name(value: &impl ValueEnum) -> String {
+        value.to_possible_value().unwrap().get_name().to_string()
+    }
+    let mut parameters = vec![
+        ("nx", args.nx.to_string()),
+        ("ny", args.ny.to_string()),
+        ("width", args.width.to_string()),
+        ("height", args.height.to_string()),
+        ("coordinates", name(&args.coordinates)),
+        ("final_time", args.final_time.to_string()),
+        ("cfl", args.cfl.to_string()),
+        ("flux_scheme", name(&args.flux_scheme)),
+        ("gradient_method", name(&args.gradient_method)),
+        ("time_integrator", name(&args.time_integrator)),
+        ("precision", name(&args.precision)),
+        ("steady_state", args.steady_state.to_string()),
+        ("output_interval", args.output_interval.to_string()),
+        ("initial_condition", name(&args.initial_condition)),
+        ("topography", name(&args.topography)),
+        ("friction", name(&args.friction)),
+        ("gravity", constants.gravity.to_string()),
+        ("density", constants.density.to_string()),
+        ("dry_tolerance", constants.dry_tolerance.to_string()),
+        ("desingularization", constants.desingularization.to_string()),
+        ("friction_depth", constants.friction_depth.to_string()),
+    ];
+    match args.friction {
+        Friction::None => {}
+        Friction::Manning => parameters.push(("manning_n", args.manning_n.to_string())),
+        Friction::Chezy => parameters.push(("chezy_c", args.chezy_c.to_string())),
+        Friction::Voellmy => parameters.extend([
+            ("voellmy_mu", args.voellmy_mu.to_string()),
+            ("voellmy_xi", args.voellmy_xi.to_string()),
+        ]),
+        Friction::Bingham => parameters.extend([
+            ("yield_stress", args.yield_stress.to_string()),
+            ("bingham_viscosity", args.bingham_viscosity.to_string()),
+        ]),
+    }
+    for (key, path) in [
+        ("selafin_mesh", &args.selafin_mesh),
+        ("hecras_geometry", &args.hecras_geometry),
+        ("config", &args.config),
+    ] {
+        if let Some(path) = path {
+            parameters.push((key, path.clone()));
+        }
+    }
+    parameters
+}
+
+/// Initialize the inner run from the outer solution in `path` and drive its
+/// boundary with it
+/// Continue from the state and time saved in `path` (--hot-start)
+fn hot_start(solver: &mut ShallowWaterSolver, path: &str, args: &Args) {
+    let saved = SavedState::read(path, args.hot_start_time, solver.mesh.coordinate_system)
+        .unwrap_or_else(|e| exit_with_error(&format!("--hot-start: {}", e)));
+    let matching = match args.hot_start_match {
+        HotStartMatch::Index => CellMatching::Index,
+        HotStartMatch::Centroid => CellMatching::Centroid,
+        HotStartMatch::Auto if saved.same_cells(&solver.mesh) => CellMatching::Index,
+        HotStartMatch::Auto => CellMatching::Centroid,
+    };
+    let cells = saved
+        .initialize(solver, matching)
+        .unwrap_or_else(|e| exit_with_error(&format!("--hot-start: {}", e)));
+    solver.time = saved.time;
+    println!(
+        "  Hot start: {} of {} cells from t = {:.3}s of {} (matched by {})",
+        cells,
+        solver.mesh.triangles.len(),
+        saved.time,
+        path,
+        if matching == CellMatching::Index {
+            "index"
+        } else {
+            "centroid"
+        }
+    );
+    if cells < solver.mesh.triangles.len() {
+        eprintln!(
+            "Warning: {} cells lie outside the --hot-start mesh and keep the initial condition",
+            solver.mesh.triangles.len() - cells
+        );
+    }
+    if saved.time >= args.final_time {
+        eprintln!(
+            "Warning: --hot-start time {:.3}s is not before --final-time",
+            saved.time
+        );
+    }
+}
+
+/// Reference system of the mesh coordinates, None when --epsg has no
+/// built-in projection
+fn mesh_crs(args: &Args) -> Option<Crs> {
+    match args.coordinates {
+        Coordinates::Cartesian => args.epsg.and_then(Crs::from_epsg),
+        Coordinates::Spherical => Crs::from_epsg(args.epsg.unwrap_or(4326)),
+    }
+}
+
+/// Source and target systems of an input in EPSG `code` (or --input-epsg
+/// when None); None when it is already in mesh coordinates
+fn input_transform(args: &Args, code: Option<u16>) -> Result<Option<(Crs, Crs)>, String> {
+    let Some(code) = code.or(args.input_epsg) else {
+        return Ok(None);
+    };
+    let source =
+        Crs::from_epsg(code).ok_or_else(|| format!("EPSG:{} has no built-in projection", code))?;
+    let target = mesh_crs(args).ok_or_else(|| {
+        format!(
+            "EPSG:{} input needs the mesh system from --epsg (4326, 3857 or UTM)",
+            code
+        )
+    })?;
+    Ok((source != target).then_some((source, target)))
+}
+
+/// ASCII grid from `path`, resampled to mesh coordinates under --input-epsg
+fn read_raster(args: &Args, path: &str, flag: &str) -> (RasterGrid, Vec<f64>) {
+    let (grid, values) = raster::read_ascii_grid(path)
+        .unwrap_or_else(|e| exit_with_error(&format!("{}: {}", flag, e)));
+    match input_transform(args, None) {
+        Ok(Some((source, target))) => crs::reproject_raster(&grid, &values, source, target),
+        Ok(None) => (grid, values),
+        Err(e) => exit_with_error(&format!("{}: {}", flag, e)),
+    }
+}
+
+/// Imported mesh moved to mesh coordinates under --input-epsg
+fn reproject_input_mesh(args: &Args, mesh: TriangularMesh) -> Result<TriangularMesh, String> {
+    match input_transform(args, None)? {
+        Some((source, target)) => crs::reproject_mesh(&mesh, source, target),
+        None => Ok(mesh),
+    }
+}
+
+/// Gauge point in mesh coordinates under --input-epsg
+fn input_point(args: &Args, p: (f64, f64), flag: &str) -> (f64, f64) {
+    match input_transform(args, None) {
+        Ok(Some((source, target))) => source.transform(target, p),
+        Ok(None) => p,
+        Err(e) => exit_with_error(&format!("{}: {}", flag, e)),
+    }
+}
+
+/// Boundary conditions of the features in the GeoJSON file `path`
+/// Features of --boundary-geojson in mesh coordinates
+fn boundary_features(args: &Args, path: &str) -> Vec<boundary::BoundaryFeature> {
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| exit_with_error(&format!("--boundary-geojson: {}: {}", path, e)));
+    let mut features = boundary::parse_boundary_features(&text)
+        .unwrap_or_else(|e| exit_with_error(&format!("--boundary-geojson: {}: {}", path, e)));
+    // A declared system only matters once the mesh system is known
+    let declared = serde_json::from_str(&text)
+        .ok()
+        .and_then(|json| crs::geojson_epsg(&json))
+        .filter(|_| mesh_crs(args).is_some());
+    match input_transform(args, declared) {
+        Ok(Some((source, target))) => {
+            for feature in &mut features {
+                feature.transform(|p| source.transform(target, p));
+            }
+        }
+        Ok(None) => {}
+        Err(e) => exit_with_error(&format!("--boundary-geojson: {}", e)),
+    }
+    features
+}
+
+fn tag_boundary(solver: &mut ShallowWaterSolver, args: &Args, path: &str, setup: &mut SetupReport) {
+    let tolerance = args.boundary_tolerance;
+    if matches!(solver.time_integrator, TimeIntegrator::SemiImplicit { .. }) {
+        exit_with_error("--boundary-geojson is not supported with the semi-implicit integrator");
+    }
+    if solver.precision == solver::Precision::Single {
+        exit_with_error("--precision single does not support custom fluxes and boundary closures");
+    }
+    if tolerance.is_some_and(|t| t.is_nan() || t < 0.0) {
+        exit_with_error("--boundary-tolerance must not be negative");
+    }
+    let features = boundary_features(args, path);
+    let tagged = boundary::TaggedBoundary::new(&solver.mesh, &features, tolerance);
+    let boundary_edges = solver
+        .mesh
+        .edges
+        .iter()
+        .filter(|e| e.right_triangle.is_none())
+        .count();
+    setup.lines.push(format!(
+        "  Boundary features: {}, tagging {} of {} boundary edges (others are walls)",
+        features.len(),
+        tagged.edges.iter().sum::<usize>(),
+        boundary_edges
+    ));
+    for (k, _) in tagged.edges.iter().enumerate().filter(|(_, &n)| n == 0) {
+        setup.warnings.push(format!(
+            "--boundary-geojson feature {} claims no boundary edges",
+            k + 1
+        ));
+    }
+    solver.boundary_closure = Box::new(tagged);
+}
+
+/// Compile the control scripts of the run configuration
+#[cfg(feature = "scripting")]
+fn start_scripts(solver: &ShallowWaterSolver, args: &Args, config: &RunConfig) -> ScriptedControl {
+    let scripts = &config.scripts;
+    let features = match &args.boundary_geojson {
+        Some(path) => boundary_features(args, path),
+        None if scripts.boundaries.is_empty() && scripts.gates.is_empty() => Vec::new(),
+        None => exit_with_error("boundary and gate scripts need --boundary-geojson"),
+    };
+    let control = ScriptedControl::new(scripts, solver, &features, args.boundary_tolerance)
+        .unwrap_or_else(|e| exit_with_error(&format!("--config scripts: {}", e)));
+    println!(
+        "  Control scripts: {} boundaries, {} gates, {} sources",
+        scripts.boundaries.len(),
+        scripts.gates.len(),
+        scripts.sources.len()
+    );
+    control
+}
+
+fn nest_in_outer_run(solver: &mut ShallowWaterSolver, path: &str) {
+    let outer = OuterSolution::load(path)
+        .unwrap_or_else(|e| exit_with_error(&format!("--nest-from: {}", e)));
+    if outer.mesh().coordinate_system != solver.mesh.coordinate_system {
+        exit_with_error("--nest-from: the outer run uses a different coordinate system");
+    }
+    if matches!(solver.time_integrator, TimeIntegrator::SemiImplicit { .. }) {
+        exit_with_error("--nest-from is not supported with the semi-implicit integrator");
+    }
+    let (t_start, t_end) = outer.time_range();
+    let cells = outer.initialize(solver);
+    println!(
+        "  Nested in {} ({} frames, t = {:.3}-{:.3}s): {} of {} cells from the outer run",
+        path,
+        outer.frame_count(),
+        t_start,
+        t_end,
+        cells,
+        solver.mesh.triangles.len()
+    );
+    let boundary = NestedBoundary { outer };
+    let uncovered = boundary.uncovered_edges(&solver.mesh);
+    if uncovered > 0 {
+        eprintln!(
+            "Warning: {} boundary edges lie outside the outer mesh and stay walls",
+            uncovered
+        );
+    }
+    solver.boundary_closure = Box::new(boundary);
+    if solver.precision == solver::Precision::Single {
+        exit_with_error("--precision single does not support custom fluxes and boundary closures");
+    }
+}
+
+/// Parse an output region: a polygon "x1,y1;x2,y2;..." or a box
+/// "xmin,ymin,xmax,ymax"
+fn parse_region(spec: &str) -> Result<Polygon, String> {
+    if spec.contains(';') {
+        return mesh::parse_polygon(spec);
+    }
+    let values: Vec<f64> = spec
+        .split(',')
+        .map(|v| v.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid region '{}'", spec))?;
+    match values[..] {
+        [x0, y0, x1, y1] if x1 > x0 && y1 > y0 => Ok(vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)]),
+        _ => Err(format!(
+            "expected a box xmin,ymin,xmax,ymax or a polygon x1,y1;x2,y2;..., got '{}'",
+            spec
+        )),
+    }
+}
+
+/// Frame writer restricted to the --output-region cells
+fn region_writer(solver: &ShallowWaterSolver, args: &Args) -> FrameWriter {
+    let cells = output::region_cells(
+        &solver.mesh,
+        &args.output_regions,
+        args.output_outside_spacing,
+    );
+    if cells.is_empty() {
+        exit_with_error("--output-region contains no cell centroids");
+    }
+    println!(
+        "Output regions: {} of {} cells in the VTK frames",
+        cells.len(),
+        solver.mesh.triangles.len()
+    );
+    FrameWriter::restricted(&solver.mesh, cells, args.output_queue)
+        .unwrap_or_else(|e| exit_with_error(&format!("--output-region: {}", e)))
+}
+
+/// Parse a vector "X,Y"
+fn parse_vector(spec: &str) -> Result<(f64, f64), String> {
+    spec.split_once(',')
+        .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+        .ok_or_else(|| format!("expected X,Y, got '{}'", spec))
+}
+
+/// Backend asked for on the command line (--use-gpu and --gpus imply the GPU)
+fn requested_backend(args: &Args) -> Backend {
+    match args.backend {
+        Backend::Cpu if args.use_gpu || args.gpus.is_some() => Backend::Gpu,
+        backend => backend,
+    }
+}
+
+/// GPU backend on the --gpus adapters (or the preferred one); None with the
+/// reason if the run cannot use the GPU
+#[cfg(feature = "gpu")]
+fn gpu_backend(args: &Args, solver: &ShallowWaterSolver) -> Result<gpu_solver::GpuBackend, String> {
+    if let Some(feature) = solver.single_precision_unsupported() {
+        return Err(format!("the GPU backend does not support {}", feature));
+    }
+    if args.steady_state {
+        return Err("the GPU backend does not support --steady-state".to_string());
+    }
+    let devices = match &args.gpus {
+        Some(list) => gpu_solver::parse_device_list(list).map_err(|e| format!("--gpus: {}", e))?,
+        None => Vec::new(),
+    };
+    let backend = pollster::block_on(gpu_solver::GpuBackend::new(solver, &devices))
+        .map_err(|e| e.to_string())?;
+    for (k, line) in backend.describe().iter().enumerate() {
+        println!("  GPU part {}: {}", k, line);
+    }
+    Ok(backend)
+}
+
+/// Attach the GPU backend for --backend gpu; for --backend auto time a few
+/// steps on both devices and keep the faster
+#[cfg(feature = "gpu")]
+fn select_backend(args: &Args, solver: &mut ShallowWaterSolver) {
+    const BENCHMARK_STEPS: usize = 10;
+    let requested = requested_backend(args);
+    if requested == Backend::Cpu {
+        return;
+    }
+    let backend = match gpu_backend(args, solver) {
+        Ok(backend) => backend,
+        Err(e) if requested == Backend::Auto => {
+            println!("Backend: CPU ({})", e);
+            println!();
+            return;
+        }
+        Err(e) => exit_with_error(&e),
+    };
+    if requested == Backend::Gpu {
+        solver.gpu = Some(backend);
+        println!();
+        return;
+    }
+
+    let cpu_time = gpu_solver::time_steps(&mut solver.replica(), BENCHMARK_STEPS);
+    let mut on_gpu = solver.replica();
+    on_gpu.gpu = Some(backend);
+    let gpu_time = gpu_solver::time_steps(&mut on_gpu, BENCHMARK_STEPS);
+    let use_gpu = gpu_time < cpu_time;
+    println!(
+        "Backend: {} ({} steps: CPU {:.3} s, GPU {:.3} s)",
+        if use_gpu { "GPU" } else { "CPU" },
+        BENCHMARK_STEPS,
+        cpu_time.as_secs_f64(),
+        gpu_time.as_secs_f64()
+    );
+    println!();
+    if use_gpu {
+        solver.gpu = on_gpu.gpu.take();
+    }
+}
+
+/// March the run on CPU and GPU side by side to --final-time and report
+/// the largest per-cell differences
+#[cfg(feature = "gpu")]
+fn validate_gpu(args: &Args, solver: &ShallowWaterSolver) {
+    let backend = gpu_backend(args, solver).unwrap_or_else(|e| exit_with_error(&e));
+    let mut cpu = solver.replica();
+    let mut gpu = solver.replica();
+    gpu.gpu = Some(backend);
+
+    println!("Validating GPU against CPU...");
+    let mut worst = gpu_solver::Discrepancy::default();
+    let mut simulation = Simulation::until(&mut cpu, args.final_time);
+    simulation.add_coupling(Box::new(GpuShadow {
+        gpu: &mut gpu,
+        worst: &mut worst,
+    }));
+    let steps = simulation
+        .run()
+        .unwrap_or_else(|e| exit_with_error(&e))
+        .steps;
+    drop(simulation);
+    let final_discrepancy = gpu_solver::Discrepancy::between(&cpu.state, &gpu.state);
+    let max_depth = cpu.state.h.iter().copied().fold(0.0, f64::max);
+    println!(
+        "  Steps: {} (CPU t = {:.4} s, GPU t = {:.4} s)",
+        steps, cpu.time, gpu.time
+    );
+    for (name, (value, cell), (end, _)) in [
+        ("h", worst.h, final_discrepancy.h),
+        ("hu", worst.hu, final_discrepancy.hu),
+        ("hv", worst.hv, final_discrepancy.hv),
+    ] {
+        let (x, y) = cpu.mesh.triangles[cell].centroid;
+        println!(
+            "  max |d{}|: {:.3e} in cell {} at ({:.2}, {:.2}), {:.3e} at the end",
+            name, value, cell, x, y, end
+        );
+    }
+    if max_depth > 0.0 {
+        println!(
+            "  Relative depth discrepancy: {:.3e} (max depth {:.3} m)",
+            worst.h.0 / max_depth,
+            max_depth
+        );
+    }
+}
+
+/// GPU replica stepped alongside the CPU run of --validate-gpu
+#[cfg(feature = "gpu")]
+struct GpuShadow<'a> {
+    gpu: &'a mut ShallowWaterSolver,
+    worst: &'a mut gpu_solver::Discrepancy, // Largest differences after any step
+}
+
+#[cfg(feature = "gpu")]
+impl Coupling for GpuShadow<'_> {
+    fn name(&self) -> String {
+        "GPU replica".to_string()
+    }
+
+    fn after_step(&mut self, cpu: &mut ShallowWaterSolver) -> Result<(), String> {
+        self.gpu.step();
+        *self.worst = self.worst.max(gpu_solver::Discrepancy::between(
+            &cpu.state,
+            &self.gpu.state,
+        ));
+        Ok(())
+    }
+}
+
+/// Print the memory estimate of a run and stop before allocating if it
+/// exceeds --memory-limit; without a limit, warn if it exceeds the memory
+/// currently available
+fn check_memory(args: &Args, estimate: &memory::MemoryEstimate) {
+    println!("  Estimated memory:");
+    for line in estimate.report().lines() {
+        println!("  {}", line);
+    }
+    let total = estimate.total();
+    match &args.memory_limit {
+        Some(limit) => {
+            let limit = memory::parse_size(limit)
+                .unwrap_or_else(|e| exit_with_error(&format!("--memory-limit: {}", e)));
+            if total > limit {
+                exit_with_error(&format!(
+                    "estimated memory {} exceeds --memory-limit {}; reduce the mesh size, \
+                     the number of members or the output queue",
+                    memory::format_bytes(total),
+                    memory::format_bytes(limit)
+                ));
+            }
+        }
+        None => {
+            if let Some(available) = memory::available_memory().filter(|&a| total > a) {
+                eprintln!(
+                    "Warning: estimated memory {} exceeds the {} currently available",
+                    memory::format_bytes(total),
+                    memory::format_bytes(available)
+                );
+            }
+        }
+    }
+}
+
+/// Print what the run would do (memory of the built mesh, the initial time
+/// step, the boundary conditions and the files it would write) for --dry-run
+fn report_dry_run(
+    args: &Args,
+    solver: &mut ShallowWaterSolver,
+    output_fields: &[OutputField],
+    png_fields: &[RenderField],
+    raster_fields: &[RasterField],
+) {
+    let mesh = &solver.mesh;
+    let size = memory::MeshSize {
+        nodes: mesh.nodes.len(),
+        cells: mesh.triangles.len(),
+        edges: mesh.edges.len(),
+    };
+    let estimate = memory::MemoryEstimate::new(
+        size,
+        1,
+        Some((output_fields, args.output_queue)).filter(|_| !args.steady_state),
+    );
+    println!("Dry run:");
+    println!(
+        "  Estimated memory for the built mesh: {}",
+        memory::format_bytes(estimate.total())
+    );
+
+    solver.compute_timestep();
+    if solver.dt.is_finite() {
+        let (cell, courant) = solver.limiting_cell();
+        let (x, y) = solver.mesh.triangles[cell].centroid;
+        println!(
+            "  Initial time step: {:.6}s, limited by cell {} at ({:.3}, {:.3}), Courant number {:.3}",
+            solver.dt, cell, x, y, courant
+        );
+        if !args.steady_state {
+            println!(
+                "  Steps to t = {:.2}s at this time step: {}",
+                args.final_time,
+                (args.final_time / solver.dt).ceil()
+            );
+        }
+    } else {
+        println!("  Initial time step: unbounded (no water is moving)");
+    }
+
+    println!(
+        "  Boundary conditions ({}):",
+        solver.boundary_closure.name()
+    );
+    for (side, edges) in boundary_sides(&solver.mesh) {
+        println!("    {:<6} {} edges", side, edges);
+    }
+
+    println!("  Output files:");
+    for (file, description) in output_plan(args, output_fields, png_fields, raster_fields) {
+        println!("    {:<32} {}", file, description);
+    }
+    println!();
+    println!("Dry run complete; no time steps taken.");
+    println!("═══════════════════════════════════════════════════════════");
+}
+
+/// Boundary edges on each side of the mesh bounding box; edges elsewhere
+/// (holes, irregular outlines) are counted as "other"
+fn boundary_sides(mesh: &TriangularMesh) -> Vec<(&'static str, usize)> {
+    let (mut x_min, mut x_max, mut y_min, mut y_max) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+    for node in &mesh.nodes {
+        x_min = x_min.min(node.x);
+        x_max = x_max.max(node.x);
+        y_min = y_min.min(node.y);
+        y_max = y_max.max(node.y);
+    }
+    let tolerance = 1e-9 * (x_max - x_min).max(y_max - y_min);
+    let on =
+        |a: f64, b: f64, line: f64| (a - line).abs() <= tolerance && (b - line).abs() <= tolerance;
+    let mut counts = [
+        ("west", 0),
+        ("east", 0),
+        ("south", 0),
+        ("north", 0),
+        ("other", 0),
+    ];
+    for edge in mesh.edges.iter().filter(|e| e.right_triangle.is_none()) {
+        let (a, b) = (&mesh.nodes[edge.nodes[0]], &mesh.nodes[edge.nodes[1]]);
+        let side = if on(a.x, b.x, x_min) {
+            0
+        } else if on(a.x, b.x, x_max) {
+            1
+        } else if on(a.y, b.y, y_min) {
+            2
+        } else if on(a.y, b.y, y_max) {
+            3
+        } else {
+            4
+        };
+        counts[side].1 += 1;
+    }
+    counts.into_iter().filter(|&(_, n)| n > 0).collect()
+}
+
+/// Files the run will write, as (file name, description)
+fn output_plan(
+    args: &Args,
+    output_fields: &[OutputField],
+    png_fields: &[RenderField],
+    raster_fields: &[RasterField],
+) -> Vec<(String, String)> {
+    let prefix = &args.output_prefix;
+    let frames = if args.steady_state {
+        "2".to_string()
+    } else {
+        let timed = (args.final_time / args.output_interval + 1e-9).floor() as usize + 1;
+        if args.output_every.is_some() || args.output_on.is_some() {
+            format!("at least {}", timed)
+        } else {
+            timed.to_string()
+        }
+    };
+    let fields: Vec<&str> = output_fields.iter().map(|f| f.name()).collect();
+    let scheduled =
+        !args.steady_state && OutputSchedule::new(0.0, &args.output_schedule).schedules_frames();
+    let mut plan = vec![(
+        format!("{}_0000.vtk ..", prefix),
+        match scheduled {
+            true => format!("VTK frames ({}) per --output-schedule", fields.join(", ")),
+            false => format!("{} VTK frames ({})", frames, fields.join(", ")),
+        },
+    )];
+    for field in png_fields {
+        plan.push((
+            format!("{}_{}_0000.png ..", prefix, field.name()),
+            format!("{} PNG snapshots", frames),
+        ));
+    }
+    if args.steady_state {
+        plan.push((
+            format!("{}_convergence.csv", prefix),
+            "residual history".to_string(),
+        ));
+    } else {
+        let mut tables = vec![("statistics", "statistics at every output time")];
+        if args.gauges.is_some() {
+            tables.push(("gauges", "gauge series"));
+        }
+        if !args.profiles.is_empty() {
+            tables.push(("profiles", "profile samples"));
+        }
+        if args.runup || !args.runup_lines.is_empty() {
+            tables.push(("runup", "shoreline and runup series"));
+        }
+        for (table, description) in tables {
+            if args.table_format != TableFormat::Parquet {
+                plan.push((format!("{}_{}.csv", prefix, table), description.to_string()));
+            }
+            if args.table_format != TableFormat::Csv {
+                plan.push((
+                    format!("{}_{}.parquet", prefix, table),
+                    description.to_string(),
+                ));
+            }
+        }
+        if args.dt_diagnostics {
+            plan.push((
+                format!("{}_timestep.csv", prefix),
+                "time step log".to_string(),
+            ));
+        }
+        let writers = [
+            (&args.nest_save, "nesting frames for --nest-from"),
+            (&args.selafin_output, "Selafin results"),
+            (&args.sww_output, "ANUGA .sww results"),
+            (&args.zarr_output, "Zarr store"),
+        ];
+        for (path, description) in writers {
+            if let Some(path) = path {
+                plan.push((path.clone(), description.to_string()));
+            }
+        }
+    }
+    for field in raster_fields {
+        plan.push((
+            format!("{}_{}.tif", prefix, field.name()),
+            "GeoTIFF at the end of the run".to_string(),
+        ));
+    }
+    if args.flood_extent.is_some() {
+        if args.flood_extent_at != ExtentOutput::Max {
+            plan.push((
+                format!("{}_extent_0000.geojson ..", prefix),
+                format!("{} flood-extent polygons", frames),
+            ));
+        }
+        if args.flood_extent_at != ExtentOutput::Outputs {
+            plan.push((
+                format!("{}_max_extent.geojson", prefix),
+                "maximum flood extent".to_string(),
+            ));
+        }
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = &args.results_db {
+        plan.push((path.clone(), "results database (appended)".to_string()));
+    }
+    plan.push((
+        format!("{}_manifest.json", prefix),
+        "run manifest (options, versions, mesh checksum, output files)".to_string(),
+    ));
+    plan
+}
+
+fn friction_law(args: &Args) -> FrictionLaw {
+    match args.friction {
+        Friction::None => FrictionLaw::None,
+        Friction::Manning => FrictionLaw::Manning {
+            coefficient: args.manning_n,
+        },
+        Friction::Chezy => FrictionLaw::Chezy {
+            coefficient: args.chezy_c,
+        },
+        Friction::Voellmy => FrictionLaw::Voellmy {
+            mu: args.voellmy_mu,
+            xi: args.voellmy_xi,
+        },
+        Friction::Bingham => FrictionLaw::Bingham {
+            yield_stress: args.yield_stress,
+            viscosity: args.bingham_viscosity,
+        },
+    }
+}
+
+/// Per-cell friction laws from --friction-zones and --friction-zone over
+/// the global --friction
+fn assign_friction_zones(solver: &mut ShallowWaterSolver, args: &Args, setup: &mut SetupReport) {
+    let mut zones = FrictionZones::uniform(&solver.mesh, solver.friction);
+    let mut unmatched = BTreeMap::new();
+    if let (Some(path), Some(table)) = (&args.friction_zones, &args.friction_zone_table) {
+        let (grid, classes) = read_raster(args, path, "--friction-zones");
+        let table = std::fs::read_to_string(table)
+            .map_err(|e| format!("{}: {}", table, e))
+            .and_then(|text| solver::parse_friction_table(&text))
+            .unwrap_or_else(|e| exit_with_error(&format!("--friction-zone-table: {}", e)));
+        unmatched = zones.assign_raster(&solver.mesh, &grid, &classes, &table);
+    }
+    let mut empty = Vec::new();
+    for spec in &args.friction_zone {
+        let (law, polygon) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("expected LAW=POLYGON, got '{}'", spec))
+            .and_then(|(law, polygon)| {
+                Ok((
+                    solver::parse_friction_law(law)?,
+                    mesh::parse_polygon(polygon)?,
+                ))
+            })
+            .unwrap_or_else(|e| exit_with_error(&format!("--friction-zone: {}", e)));
+        if zones.assign_polygon(&solver.mesh, &polygon, law) == 0 {
+            empty.push(spec.clone());
+        }
+    }
+    let total: f64 = solver.mesh.triangles.iter().map(|t| t.area).sum();
+    let areas: Vec<String> = zones
+        .areas(&solver.mesh)
+        .iter()
+        .map(|(name, area)| format!("{} {:.1}%", name, 100.0 * area / total))
+        .collect();
+    setup
+        .lines
+        .push(format!("  Friction zones: {}", areas.join(", ")));
+    if !unmatched.is_empty() {
+        let classes: Vec<String> = unmatched.keys().map(|c| c.to_string()).collect();
+        setup.warnings.push(format!(
+            "friction zone classes {} are not in --friction-zone-table, using --friction",
+            classes.join(", ")
+        ));
+    }
+    for spec in &empty {
+        setup.warnings.push(format!(
+            "--friction-zone '{}' contains no cell centroid",
+            spec
+        ));
+    }
+    solver
+        .set_friction_zones(zones)
+        .unwrap_or_else(|e| exit_with_error(&format!("--friction-zone: {}", e)));
+}
+
+/// Summary lines and warnings of setting up a solver from the command line
+/// Only the single run prints them; sweeps and ensembles build a solver per
+/// member from the same options and drop them.
+#[derive(Default)]
+struct SetupReport {
+    lines: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl SetupReport {
+    fn print(&self) {
+        for line in &self.lines {
+            println!("{}", line);
+        }
+        for warning in &self.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+}
+
+/// Solver with the numerical options and the porosity and sediment models
+/// from the command line, with the report of setting them up
+fn create_solver(
+    args: &Args,
+    mesh: TriangularMesh,
+    constants: PhysicalConstants,
+    friction: FrictionLaw,
+) -> (ShallowWaterSolver, SetupReport) {
+    let mut setup = SetupReport::default();
+    let mut solver = numerical_solver(args, mesh, constants, friction);
+    if let Some(path) = &args.building_coverage {
+        let (grid, coverage) = read_raster(args, path, "--building-coverage");
+        let porosity = Porosity::from_coverage(&solver.mesh, &grid, &coverage);
+        solver
+            .set_porosity(porosity)
+            .unwrap_or_else(|e| exit_with_error(&format!("--building-coverage: {}", e)));
+        if solver.precision == solver::Precision::Single {
+            exit_with_error("--precision single does not support porosity");
+        }
+    }
+    if let Some(path) = &args.subgrid_dem {
+        let (grid, elevation) = read_raster(args, path, "--subgrid-dem");
+        let subgrid = Subgrid::from_dem(&solver.mesh, &grid, &elevation);
+        solver
+            .set_subgrid(subgrid)
+            .unwrap_or_else(|e| exit_with_error(&format!("--subgrid-dem: {}", e)));
+    }
+    if let Some(path) = &args.boundary_geojson {
+        tag_boundary(&mut solver, args, path, &mut setup);
+    }
+    if args.friction_zones.is_some() || !args.friction_zone.is_empty() {
+        assign_friction_zones(&mut solver, args, &mut setup);
+    }
+    if let (Some(path), Some(table)) = (&args.land_use, &args.land_use_table) {
+        let (grid, classes) = read_raster(args, path, "--land-use");
+        let table = RoughnessTable::read(table)
+            .unwrap_or_else(|e| exit_with_error(&format!("--land-use-table: {}", e)));
+        let LandUseRoughness { manning, unmatched } =
+            LandUseRoughness::from_land_use(&solver.mesh, &grid, &classes, &table, args.manning_n);
+        solver.set_manning_field(manning).unwrap_or_else(|e| {
+            exit_with_error(&format!("--land-use: {}; use --friction manning", e))
+        });
+        if !unmatched.is_empty() {
+            let classes: Vec<String> = unmatched.keys().map(|c| c.to_string()).collect();
+            setup.warnings.push(format!(
+                "land-use classes {} are not in --land-use-table, using n = {}",
+                classes.join(", "),
+                args.manning_n
+            ));
+        }
+    }
+    if !args.ice_cover.is_empty() {
+        let regions: Vec<Polygon> = args
+            .ice_cover
+            .iter()
+            .map(|spec| mesh::parse_polygon(spec))
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| exit_with_error(&format!("--ice-cover: {}", e)));
+        let ice = IceCover::from_regions(
+            &solver.mesh,
+            &regions,
+            args.ice_manning,
+            args.ice_blocks_wind,
+        );
+        solver.set_ice_cover(ice).unwrap_or_else(|e| {
+            exit_with_error(&format!("--ice-cover: {}; use --friction manning", e))
+        });
+    }
+    if let Some(velocity) = args.wind {
+        solver
+            .source_terms
+            .push(Box::new(solver::WindStress::new(velocity)));
+        if solver.precision == solver::Precision::Single {
+            exit_with_error("--precision single does not support wind");
+        }
+    }
+    if let Some(path) = &args.rainfall {
+        let rainfall = rainfall::read_rainfall(path, args.rainfall_variable.as_deref())
+            .unwrap_or_else(|e| exit_with_error(&format!("--rainfall: {}", e)));
+        let term = rainfall.source_term(&solver.mesh);
+        let areas: Vec<f64> = solver.mesh.triangles.iter().map(|t| t.area).collect();
+        setup.lines.push(format!(
+            "  Rainfall: {} frames over {:.0} s on a {} x {} grid, {:.3} m^3 on the mesh",
+            rainfall.times.len(),
+            rainfall.times[rainfall.times.len() - 1] - rainfall.times[0],
+            rainfall.grid.ncols,
+            rainfall.grid.nrows,
+            term.total_volume(&areas)
+        ));
+        solver.source_terms.push(Box::new(term));
+        if solver.precision == solver::Precision::Single {
+            exit_with_error("--precision single does not support gridded rainfall");
+        }
+    }
+    if let Some(path) = &args.snowmelt {
+        let snowmelt = Snowmelt::read(path, &solver.mesh)
+            .unwrap_or_else(|e| exit_with_error(&format!("--snowmelt: {}", e)));
+        let areas = snowmelt.zone_areas(&solver.mesh);
+        for (zone, area) in snowmelt.zones.iter().zip(areas) {
+            setup.lines.push(format!(
+                "  Snow zone {}: {:.1} mm SWE on {:.0} m^2, {:.1} mm melted by t = {:.0} s",
+                zone.name,
+                zone.swe * 1e3,
+                area,
+                zone.melted(zone.end_time()) * 1e3,
+                zone.end_time()
+            ));
+        }
+        solver.source_terms.push(Box::new(snowmelt));
+        if solver.precision == solver::Precision::Single {
+            exit_with_error("--precision single does not support snowmelt");
+        }
+    }
+    if let Some(diameter) = args.sediment_diameter {
+        if diameter.is_nan() || diameter <= 0.0 || args.sediment_density <= solver.constants.density
+        {
+            exit_with_error(
+                "--sediment-diameter must be positive and --sediment-density exceed the water density",
+            );
+        }
+        solver
+            .enable_sediment(SedimentProperties {
+                grain_diameter: diameter,
+                density: args.sediment_density,
+                density_coupling: args.sediment_density_coupling,
+                ..SedimentProperties::default()
+            })
+            .unwrap_or_else(|e| exit_with_error(&format!("--sediment-diameter: {}", e)));
+        if solver.precision == solver::Precision::Single {
+            exit_with_error("--precision single does not support suspended sediment");
+        }
+    }
+    if let (Some(scalar), Some(ambient)) = (args.scalar, args.scalar_ambient) {
+        let kind = match scalar {
+            Scalar::Temperature => ScalarKind::Temperature,
+            Scalar::Salinity => ScalarKind::Salinity,
+        };
+        let outfalls = args
+            .outfalls
+            .iter()
+            .map(|spec| parse_outfall(&solver, spec))
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| exit_with_error(&format!("--outfall: {}", e)));
+        solver
+            .enable_buoyant_scalar(kind, ambient, outfalls)
+            .unwrap_or_else(|e| exit_with_error(&format!("--scalar: {}", e)));
+        if solver.precision == solver::Precision::Single {
+            exit_with_error("--precision single does not support buoyant scalars");
+        }
+    }
+    (solver, setup)
+}
+
+/// Parse an outfall "x,y,Q,value" at the cell containing (x, y)
+fn parse_outfall(solver: &ShallowWaterSolver, spec: &str) -> Result<Outfall, String> {
+    let values: Vec<f64> = spec
+        .split(',')
+        .map(|v| v.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("expected X,Y,Q,VALUE, got '{}'", spec))?;
+    let [x, y, discharge, value] = values[..] else {
+        return Err(format!("expected X,Y,Q,VALUE, got '{}'", spec));
+    };
+    if !(discharge >= 0.0 && discharge.is_finite() && value.is_finite()) {
+        return Err(format!("'{}' needs a non-negative discharge", spec));
+    }
+    let cell = solver
+        .locate(x, y)
+        .ok_or_else(|| format!("({}, {}) is outside the mesh", x, y))?;
+    Ok(Outfall {
+        cell,
+        discharge,
+        value,
+    })
+}
+
+/// The model the command line describes on a domain centred on `center`;
+/// the mesh is built by `build_mesh`
+fn model_config(args: &Args, center: (f64, f64)) -> ModelConfig {
+    ModelConfig {
+        grid: GridConfig {
+            nx: args.nx,
+            ny: args.ny,
+            width: args.width,
+            height: args.height,
+            ..GridConfig::default()
+        },
+        cfl: args.cfl,
+        friction: friction_law(args),
+        numerics: NumericsConfig {
+            flux_scheme: match args.flux_scheme {
+                Flux::Rusanov => FluxScheme::Rusanov,
+                Flux::CentralUpwind => FluxScheme::CentralUpwind,
+            },
+            gradient_method: match args.gradient_method {
+                Gradient::GreenGauss => GradientMethod::GreenGauss,
+                Gradient::LeastSquares => GradientMethod::LeastSquares,
+            },
+            time_integrator: match args.time_integrator {
+                Integrator::Rk2 => TimeIntegrator::RungeKutta2,
+                Integrator::SemiImplicit => TimeIntegrator::SemiImplicit {
+                    theta: args.theta,
+                    max_courant: args.max_courant,
+                },
+                Integrator::Imex => TimeIntegrator::Imex {
+                    viscosity: args.eddy_viscosity,
+                },
+            },
+            precision: match args.precision {
+                Precision::Double => solver::Precision::Double,
+                Precision::Single => solver::Precision::Single,
+            },
+            discretization: match args.discretization {
+                Spatial::Fv => Discretization::FiniteVolume,
+                Spatial::Dg1 => Discretization::Dg1,
+            },
+            bed_slope_limit: args.max_bed_slope.map(|max_slope| BedSlopeLimit {
+                max_slope,
+                shallow_depth: args.slope_limit_depth,
+            }),
+            track_wet_region: args.track_wet_region,
+            source_splitting: args.source_splitting.as_ref().map(|method| {
+                let max_substeps = args.max_substeps;
+                match method {
+                    Splitting::Subcycle => SourceSplitting::Subcycle { max_substeps },
+                    Splitting::Exponential => SourceSplitting::Exponential { max_substeps },
+                }
+            }),
+        },
+        physics: PhysicsConfig::default(),
+        timestep: TimestepConfig {
+            min_dt: args.min_dt,
+            max_dt: args.max_dt,
+            max_growth: args.dt_growth,
+        },
+        initial: InitialConfig {
+            condition: Some(
+                args.initial_condition
+                    .to_possible_value()
+                    .unwrap()
+                    .get_name()
+                    .to_string(),
+            ),
+            amplitude: args.wave_amplitude,
+            width: args.wave_width,
+            center: Some((
+                args.wave_x.unwrap_or(center.0),
+                args.wave_y.unwrap_or(center.1),
+            )),
+            direction: args.wave_direction,
+            velocity: args.initial_velocity,
+            perturbation: Some(perturbation(args)),
+        },
+        sea_level: args.sea_level,
+        fault: Some(FaultParameters {
+            x: args.fault_x.unwrap_or(center.0),
+            y: args.fault_y.unwrap_or(center.1),
+            depth: args.fault_depth,
+            strike: args.fault_strike,
+            dip: args.fault_dip,
+            rake: args.fault_rake,
+            slip: args.fault_slip,
+            length: args.fault_length,
+            width: args.fault_width,
+        }),
+    }
+}
+
+/// Solver with the numerical options (scheme, integrator, precision) from the
+/// command line
+fn numerical_solver(
+    args: &Args,
+    mesh: TriangularMesh,
+    constants: PhysicalConstants,
+    friction: FrictionLaw,
+) -> ShallowWaterSolver {
+    let mut model = model_config(args, (0.0, 0.0));
+    model.friction = friction;
+    let mut solver = model.solver(mesh).unwrap_or_else(|e| exit_with_error(&e));
+    solver.constants = constants;
+    solver
+}
+
+/// Apply the selected initial condition; `amplitude` scales its departure
+/// from still water (1 = as configured, used by ensemble runs) and `member`
+/// varies the seed of the random perturbation
+fn set_initial_condition(
+    solver: &mut ShallowWaterSolver,
+    args: &Args,
+    center: (f64, f64),
+    amplitude: f64,
+    member: usize,
+) -> SetupReport {
+    let mut setup = SetupReport::default();
+    let perturbed = model_config(args, center)
+        .set_initial_condition(solver, center, amplitude, member)
+        .unwrap_or_else(|e| exit_with_error(&e));
+    if let Some((perturbation, added)) = perturbed {
+        setup.lines.push(format!(
+            "  Perturbation: depth {} m, velocity {} m/s, correlation length {} m, seed {} ({:+.3} m^3)",
+            perturbation.depth,
+            perturbation.velocity,
+            perturbation.length,
+            perturbation.seed,
+            added
+        ));
+    }
+    setup
+}
+
+/// Initial noise of the run; ensemble and assimilation members add their
+/// index to the seed
+fn perturbation(args: &Args) -> Perturbation {
+    let defaults = Perturbation::default();
+    Perturbation {
+        depth: args.perturb_depth.unwrap_or(defaults.depth),
+        velocity: args.perturb_velocity.unwrap_or(defaults.velocity),
+        length: args.perturb_length.unwrap_or(defaults.length),
+        seed: args.perturb_seed.unwrap_or(defaults.seed),
+    }
+}
+
+/// Walls of the partial dam break: a dam across the middle of the domain
+/// with one breach (Fennema and Chaudhry's geometry by default)
+fn partial_dam(args: &Args, origin: (f64, f64), center: (f64, f64)) -> Vec<Polygon> {
+    let thickness = args.dam_thickness.unwrap_or(args.width / 20.0);
+    let breach_width = args.breach_width.unwrap_or(0.375 * args.height);
+    let breach_center = args
+        .breach_center
+        .unwrap_or(origin.1 + 0.6625 * args.height);
+    if !(thickness > 0.0 && breach_width > 0.0) {
+        exit_with_error("--dam-thickness and --breach-width must be positive");
+    }
+    let breach = (
+        breach_center - 0.5 * breach_width,
+        breach_center + 0.5 * breach_width,
+    );
+    println!(
+        "  Partial dam: x = {:.3}, thickness {:.3}, breach y = {:.3} to {:.3}",
+        center.0, thickness, breach.0, breach.1
+    );
+    mesh::dam_with_breach(
+        center.0,
+        thickness,
+        (origin.1, origin.1 + args.height),
+        breach,
+    )
+}
+
+/// Run the `ensemble` subcommand and write the probabilistic flood maps
+fn run_ensemble(
+    args: &Args,
+    ensemble: &EnsembleArgs,
+    mesh: &TriangularMesh,
+    constants: PhysicalConstants,
+    center: (f64, f64),
+    crs: RasterCrs,
+) {
+    if ensemble.members == 0 {
+        exit_with_error("ensemble needs at least one member");
+    }
+    if args.steady_state {
+        exit_with_error("ensemble runs march to --final-time; --steady-state is not supported");
+    }
+    let parse_interval = |option: &str, spec: &str| {
+        ensemble::parse_interval(spec)
+            .unwrap_or_else(|e| exit_with_error(&format!("{}: {}", option, e)))
+    };
+    let manning_range = ensemble
+        .manning_range
+        .as_deref()
+        .map(|spec| parse_interval("--manning-range", spec));
+    if manning_range.is_some() && !matches!(args.friction, Friction::Manning) {
+        exit_with_error("--manning-range needs --friction manning");
+    }
+    let amplitude_range = ensemble
+        .amplitude_range
+        .as_deref()
+        .map_or((1.0, 1.0), |spec| parse_interval("--amplitude-range", spec));
+    let thresholds = ensemble::parse_thresholds(&ensemble.thresholds)
+        .unwrap_or_else(|e| exit_with_error(&format!("--thresholds: {}", e)));
+
+    let members = ensemble::sample(
+        ensemble.members,
+        manning_range,
+        amplitude_range,
+        ensemble.seed,
+    );
+    write_ensemble_members(&members, &args.output_prefix);
+    println!(
+        "Running ensemble of {} members to t = {:.2}s (seed {})...",
+        members.len(),
+        args.final_time,
+        ensemble.seed
+    );
+
+    let stats = ensemble::run(&members, args.final_time, &thresholds, |parameters| {
+        let friction = match parameters.manning_n {
+            Some(coefficient) => FrictionLaw::Manning { coefficient },
+            None => friction_law(args),
+        };
+        let (mut solver, _) = create_solver(args, mesh.clone(), constants, friction);
+        set_initial_condition(
+            &mut solver,
+            args,
+            center,
+            parameters.amplitude,
+            parameters.member,
+        );
+        solver
+    });
+
+    let grid = RasterGrid::covering(mesh, args.raster_cell_size);
+    let mut maps = vec![
+        ("mean".to_string(), stats.mean()),
+        ("std".to_string(), stats.std_dev()),
+    ];
+    for (k, threshold) in thresholds.iter().enumerate() {
+        maps.push((format!("p{}", threshold), stats.exceedance_probability(k)));
+    }
+    for (name, values) in &maps {
+        let filename = format!("{}_ensemble_{}.tif", args.output_prefix, name);
+        if let Err(e) = raster::save_geotiff(&filename, mesh, values, &grid, crs) {
+            eprintln!("Warning: Could not write output file {}: {}", filename, e);
+        }
+    }
+
+    println!();
+    println!("Ensemble completed!");
+    println!("  Members: {}", stats.members);
+    for (k, threshold) in thresholds.iter().enumerate() {
+        let flooded = stats
+            .exceedance_probability(k)
+            .iter()
+            .filter(|&&p| p >= 0.5)
+            .count();
+        println!(
+            "  Cells with P(max depth > {} m) >= 0.5: {}",
+            threshold, flooded
+        );
+    }
+    println!(
+        "Maximum-depth statistics saved to {}_ensemble_*.tif",
+        args.output_prefix
+    );
+    println!("═══════════════════════════════════════════════════════════");
+}
+
+/// Run the `calibrate` subcommand: Nelder-Mead search over log(n) for the
+/// global coefficient and one per zone
+fn run_calibration(
+    args: &Args,
+    calibrate: &CalibrateArgs,
+    mesh: &TriangularMesh,
+    constants: PhysicalConstants,
+    center: (f64, f64),
+) {
+    if !matches!(args.friction, Friction::Manning) {
+        exit_with_error("calibration fits Manning's n; use --friction manning");
+    }
+    if args.land_use.is_some() || !args.ice_cover.is_empty() {
+        exit_with_error("calibration fits Manning's n, which --land-use and --ice-cover set");
+    }
+    if args.friction_zones.is_some() || !args.friction_zone.is_empty() {
+        exit_with_error("calibration fits Manning's n everywhere; drop the friction zones");
+    }
+    let mut gauges = calibration::read_observations(&calibrate.observations)
+        .unwrap_or_else(|e| exit_with_error(&e));
+    for gauge in &mut gauges {
+        (gauge.x, gauge.y) = input_point(args, (gauge.x, gauge.y), "--observations");
+    }
+    let zones: Vec<Polygon> = calibrate
+        .zones
+        .iter()
+        .map(|spec| mesh::parse_polygon(spec))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| exit_with_error(&format!("--zone: {}", e)));
+    let (n_min, n_max) = ensemble::parse_interval(&calibrate.manning_bounds)
+        .ok()
+        .filter(|&(lo, _)| lo > 0.0)
+        .unwrap_or_else(|| exit_with_error("--manning-bounds needs 0 < min <= max"));
+
+    let points: Vec<(f64, f64)> = gauges.iter().map(|g| (g.x, g.y)).collect();
+    let cells = calibration::nearest_cells(mesh, &points);
+    // Zone of each cell (first matching polygon), shifted by one: 0 is global
+    let zone_of: Vec<usize> = mesh
+        .triangles
+        .iter()
+        .map(|t| {
+            zones
+                .iter()
+                .position(|zone| mesh::point_in_polygon(t.centroid, zone))
+                .map_or(0, |k| k + 1)
+        })
+        .collect();
+
+    println!(
+        "Calibrating Manning's n ({} parameter{}) against {} gauges, {} readings...",
+        zones.len() + 1,
+        if zones.is_empty() { "" } else { "s" },
+        gauges.len(),
+        gauges.iter().map(|g| g.times.len()).sum::<usize>()
+    );
+    for (gauge, &cell) in gauges.iter().zip(&cells) {
+        let (cx, cy) = mesh.triangles[cell].centroid;
+        println!(
+            "  Gauge {}: cell {} at ({:.3}, {:.3})",
+            gauge.name, cell, cx, cy
+        );
+    }
+    for (k, zone) in zones.iter().enumerate() {
+        let count = zone_of.iter().filter(|&&z| z == k + 1).count();
+        println!("  Zone {}: {} vertices, {} cells", k + 1, zone.len(), count);
+    }
+
+    let manning =
+        |theta: &[f64]| -> Vec<f64> { theta.iter().map(|t| t.exp().clamp(n_min, n_max)).collect() };
+    let simulate = |n: &[f64]| {
+        let friction = FrictionLaw::Manning { coefficient: n[0] };
+        let (mut solver, _) = create_solver(args, mesh.clone(), constants, friction);
+        if !zones.is_empty() {
+            solver.manning_field = Some(zone_of.iter().map(|&z| n[z]).collect());
+        }
+        set_initial_condition(&mut solver, args, center, 1.0, 0);
+        calibration::simulate_gauges(&mut solver, &gauges, &cells)
+    };
+
+    let mut history: Vec<(Vec<f64>, calibration::ErrorMetrics)> = Vec::new();
+    let x0 = vec![args.manning_n.clamp(n_min, n_max).ln(); zones.len() + 1];
+    let result = calibration::nelder_mead(
+        |theta| {
+            let n = manning(theta);
+            let metrics = calibration::error_metrics(&gauges, &simulate(&n));
+            println!(
+                "  run {:>3}: n = [{}], RMSE = {:.6} m",
+                history.len() + 1,
+                n.iter()
+                    .map(|v| format!("{:.5}", v))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                metrics.rmse
+            );
+            history.push((n, metrics));
+            metrics.rmse
+        },
+        &x0,
+        0.3,
+        calibrate.tolerance,
+        calibrate.max_evaluations,
+    );
+
+    let best = manning(&result.x);
+    let simulated = simulate(&best);
+    let metrics = calibration::error_metrics(&gauges, &simulated);
+    write_calibration_results(&history, &gauges, &simulated, &args.output_prefix);
+
+    println!();
+    if result.converged {
+        println!("Calibration converged after {} runs", result.evaluations);
+    } else {
+        println!(
+            "WARNING: calibration stopped after {} runs without converging",
+            result.evaluations
+        );
+    }
+    println!("  Global Manning's n: {:.5}", best[0]);
+    for (k, n) in best.iter().enumerate().skip(1) {
+        println!("  Zone {} Manning's n: {:.5}", k, n);
+    }
+    for (k, n) in best.iter().enumerate() {
+        if *n <= n_min * (1.0 + 1e-9) || *n >= n_max * (1.0 - 1e-9) {
+            println!(
+                "  WARNING: parameter {} is at the bound of --manning-bounds",
+                k
+            );
+        }
+    }
+    println!("  RMSE: {:.6} m", result.value);
+    println!("  Bias: {:.6} m", metrics.bias);
+    println!("  Max abs error: {:.6} m", metrics.max_abs_error);
+    println!("  Nash-Sutcliffe efficiency: {:.4}", metrics.nse);
+    println!(
+        "Calibration history and fitted gauges saved with prefix: {}",
+        args.output_prefix
+    );
+    println!("═══════════════════════════════════════════════════════════");
+}
+
+fn write_calibration_results(
+    history: &[(Vec<f64>, calibration::ErrorMetrics)],
+    gauges: &[calibration::Gauge],
+    simulated: &[Vec<f64>],
+    prefix: &str,
+) {
+    let filename = format!("{}_calibration.csv", prefix);
+    match File::create(&filename) {
+        Ok(mut file) => {
+            let zones = history.first().map_or(0, |(n, _)| n.len() - 1);
+            let columns: Vec<String> = (1..=zones).map(|k| format!(",n_zone{}", k)).collect();
+            writeln!(
+                file,
+                "run,n{},rmse,bias,max_abs_error,nse",
+                columns.concat()
+            )
+            .unwrap();
+            for (run, (n, m)) in history.iter().enumerate() {
+                let n: Vec<String> = n.iter().map(|v| v.to_string()).collect();
+                writeln!(
+                    file,
+                    "{},{},{},{},{},{}",
+                    run + 1,
+                    n.join(","),
+                    m.rmse,
+                    m.bias,
+                    m.max_abs_error,
+                    m.nse
+                )
+                .unwrap();
+            }
+        }
+        Err(e) => {
+            eprintln!("Warning: Could not write output file {}: {}", filename, e);
+        }
+    }
+
+    let filename = format!("{}_calibration_gauges.csv", prefix);
+    match File::create(&filename) {
+        Ok(mut file) => {
+            writeln!(file, "gauge,time,observed,simulated").unwrap();
+            for (gauge, series) in gauges.iter().zip(simulated) {
+                for ((t, observed), simulated) in gauge.times.iter().zip(&gauge.levels).zip(series)
+                {
+                    writeln!(file, "{},{},{},{}", gauge.name, t, observed, simulated).unwrap();
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("Warning: Could not write output file {}: {}", filename, e);
+        }
+    }
+}
+
+/// Run the `bench` subcommand and write {prefix}_bench.json
+fn run_bench(args: &Args, bench: &BenchArgs, constants: PhysicalConstants) {
+    let problems = bench::parse_problems(&bench.problems)
+        .unwrap_or_else(|e| exit_with_error(&format!("--problems: {}", e)));
+    let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let thread_counts = bench::parse_thread_counts(bench.thread_counts.as_deref(), available)
+        .unwrap_or_else(|e| exit_with_error(&format!("--thread-counts: {}", e)));
+    if bench.steps == 0 {
+        exit_with_error("--steps must be positive");
+    }
+    let setup = |problem: &bench::Problem| {
+        let mut solver = numerical_solver(args, problem.mesh(), constants, FrictionLaw::None);
+        problem.initialize(&mut solver);
+        solver
+    };
+
+    println!(
+        "Benchmarking {} steps per run, threads {:?}...",
+        bench.steps, thread_counts
+    );
+    let mut measurements = Vec::new();
+    for problem in &problems {
+        for &threads in &thread_counts {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap_or_else(|e| exit_with_error(&format!("thread pool: {}", e)));
+            let mut solver = pool.install(|| setup(problem));
+            let (seconds, phases) = pool.install(|| bench::measure(&mut solver, bench.steps, true));
+            measurements.push(bench::Measurement {
+                problem: problem.name,
+                backend: "cpu",
+                threads,
+                cells: solver.mesh.triangles.len(),
+                steps: bench.steps,
+                seconds,
+                phases,
+            });
+        }
+        #[cfg(feature = "gpu")]
+        {
+            let mut solver = setup(problem);
+            match gpu_backend(args, &solver) {
+                Ok(backend) => {
+                    solver.gpu = Some(backend);
+                    let (seconds, _) = bench::measure(&mut solver, bench.steps, false);
+                    measurements.push(bench::Measurement {
+                        problem: problem.name,
+                        backend: "gpu",
+                        threads: 0,
+                        cells: solver.mesh.triangles.len(),
+                        steps: bench.steps,
+                        seconds,
+                        phases: None,
+                    });
+                }
+                Err(e) => println!("  {}: GPU skipped ({})", problem.name, e),
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "  {:<8} {:<4} {:>7} {:>9} {:>10} {:>12}  phases",
+        "problem", "dev", "threads", "cells", "time (s)", "cells/s"
+    );
+    for m in &measurements {
+        println!(
+            "  {:<8} {:<4} {:>7} {:>9} {:>10.3} {:>12.3e}  {}",
+            m.problem,
+            m.backend,
+            m.threads,
+            m.cells,
+            m.seconds,
+            m.cell_updates_per_second(),
+            m.phases
+                .map(|phases| phases.summary(m.seconds))
+                .unwrap_or_default()
+        );
+    }
+    let scaling = bench::scaling(&measurements);
+    if thread_counts.len() > 1 {
+        println!();
+        println!("Parallel scaling (against 1 thread):");
+        for (problem, threads, speedup, efficiency) in &scaling {
+            println!(
+                "  {:<8} {:>3} threads: speed-up {:>5.2}, efficiency {:>5.1}%",
+                problem,
+                threads,
+                speedup,
+                efficiency * 100.0
+            );
+        }
+    }
+
+    let report = serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "available_threads": available,
+        "flux_scheme": format!("{:?}", args.flux_scheme),
+        "time_integrator": format!("{:?}", args.time_integrator),
+        "precision": format!("{:?}", args.precision),
+        "steps": bench.steps,
+        "measurements": measurements.iter().map(|m| m.to_json()).collect::<Vec<_>>(),
+        "scaling": scaling.iter().map(|(problem, threads, speedup, efficiency)| {
+            serde_json::json!({
+                "problem": problem,
+                "threads": threads,
+                "speedup": speedup,
+                "efficiency": efficiency,
+            })
+        }).collect::<Vec<_>>(),
+    });
+    let filename = format!("{}_bench.json", args.output_prefix);
+    match std::fs::write(&filename, serde_json::to_string_pretty(&report).unwrap()) {
+        Ok(()) => println!("Benchmark results saved to {}", filename),
+        Err(e) => eprintln!("Warning: Could not write output file {}: {}", filename, e),
+    }
+}
+
+/// Run the `validate` subcommand with the numerical options from the
+/// command line; exits with status 1 if a case fails
+fn run_validation(args: &Args, validate: &ValidateArgs, constants: PhysicalConstants) {
+    let cases = validation::parse_cases(&validate.cases)
+        .unwrap_or_else(|e| exit_with_error(&format!("--cases: {}", e)));
+    let factory = |mesh| numerical_solver(args, mesh, constants, FrictionLaw::None);
+    println!("Running {} verification benchmarks...", cases.len());
+    let mut outcomes = Vec::new();
+    for case in cases {
+        println!("  {}: {}", case.name, case.description);
+        let outcome = case.run(&factory);
+        println!(
+            "    {} = {:.3e} (tolerance {:.1e}): {} [{:.2}s]",
+            outcome.metric,
+            outcome.value,
+            outcome.tolerance,
+            if outcome.passed { "PASS" } else { "FAIL" },
+            outcome.runtime
+        );
+        outcomes.push(outcome);
+    }
+
+    let filename = format!("{}_validation.csv", args.output_prefix);
+    if let Err(e) = validation::save_outcomes(&filename, &outcomes) {
+        eprintln!("Warning: Could not write output file {}: {}", filename, e);
+    }
+    let failed: Vec<&str> = outcomes
+        .iter()
+        .filter(|outcome| !outcome.passed)
+        .map(|outcome| outcome.name)
+        .collect();
+    println!();
+    println!(
+        "Validation: {} of {} cases passed",
+        outcomes.len() - failed.len(),
+        outcomes.len()
+    );
+    println!("Results table saved to {}", filename);
+    if !failed.is_empty() {
+        println!("  Failed: {}", failed.join(", "));
+        std::process::exit(1);
+    }
+}
+
+/// Run the `compare` subcommand; exits with status 1 if the runs differ
+/// beyond the tolerances
+fn run_compare(options: &CompareArgs) {
+    let locate = |path: &str| {
+        compare::Run::locate(path).unwrap_or_else(|e| exit_with_error(&format!("compare: {}", e)))
+    };
+    let (a, b) = (locate(&options.run_a), locate(&options.run_b));
+    println!("Comparing {} with {}...", b.prefix, a.prefix);
+    let comparison =
+        compare::compare(&a, &b).unwrap_or_else(|e| exit_with_error(&format!("compare: {}", e)));
+    println!(
+        "  {} frames paired, largest time offset {:.4}s",
+        comparison.frames, comparison.time_offset
+    );
+    for (run, frames) in [(&a, &comparison.only_a), (&b, &comparison.only_b)] {
+        if !frames.is_empty() {
+            let numbers: Vec<String> = frames.iter().map(|k| format!("{:04}", k)).collect();
+            println!("  Frames only in {}: {}", run.prefix, numbers.join(", "));
+        }
+    }
+    if !comparison.unpaired.is_empty() {
+        println!(
+            "  Not compared (in one run only): {}",
+            comparison.unpaired.join(", ")
+        );
+    }
+    println!();
+    println!("  {:<20} {:>12} {:>12} {:>6}", "field", "L2", "L∞", "frame");
+    for field in &comparison.fields {
+        println!(
+            "  {:<20} {:>12.3e} {:>12.3e} {:>6}  {}",
+            field.name,
+            field.l2,
+            field.linf,
+            format!("{:04}", field.worst_frame),
+            if field.linf <= options.tolerance {
+                "PASS"
+            } else {
+                "FAIL"
+            }
+        );
+    }
+    if comparison.metrics.is_empty() {
+        println!("  No statistics tables (CSV) to compare the conservation totals");
+    }
+    for metric in &comparison.metrics {
+        println!(
+            "  final {}: {:.9e} vs {:.9e} (relative difference {:.3e}): {}",
+            metric.name,
+            metric.a,
+            metric.b,
+            metric.relative(),
+            if metric.relative() <= options.conservation_tolerance {
+                "PASS"
+            } else {
+                "FAIL"
+            }
+        );
+    }
+    println!();
+    if comparison.passed(options.tolerance, options.conservation_tolerance) {
+        println!(
+            "Comparison: within tolerances (fields {:.1e}, conservation {:.1e})",
+            options.tolerance, options.conservation_tolerance
+        );
+    } else {
+        println!(
+            "Comparison: the runs differ beyond the tolerances (fields {:.1e}, conservation {:.1e})",
+            options.tolerance, options.conservation_tolerance
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Run the `sweep` subcommand; every combination builds its own mesh
+fn run_sweep(
+    args: &Args,
+    sweep: &SweepArgs,
+    origin: (f64, f64),
+    topography_type: TopographyType,
+    holes: &[Polygon],
+    constants: PhysicalConstants,
+    center: (f64, f64),
+) {
+    let values = |option: &str, spec: &Option<String>| -> Vec<f64> {
+        spec.as_deref().map_or(Vec::new(), |spec| {
+            sweep::parse_values(spec)
+                .unwrap_or_else(|e| exit_with_error(&format!("{}: {}", option, e)))
+        })
+    };
+    let nx: Vec<usize> = values("--nx-values", &sweep.nx_values)
+        .into_iter()
+        .map(|v| (v.round() as usize).max(2))
+        .collect();
+    let cfl = values("--cfl-values", &sweep.cfl_values);
+    let manning_n = values("--manning-values", &sweep.manning_values);
+    let amplitude = values("--amplitude-values", &sweep.amplitude_values);
+    if !manning_n.is_empty() && !matches!(args.friction, Friction::Manning) {
+        exit_with_error("--manning-values needs --friction manning");
+    }
+    if args.steady_state {
+        exit_with_error("sweeps march to --final-time; --steady-state is not supported");
+    }
+
+    let points = sweep::combinations(&nx, &cfl, &manning_n, &amplitude);
+    let largest_nx = nx.iter().copied().max().unwrap_or(args.nx).max(args.nx);
+    let largest_ny =
+        ((largest_nx as f64 * args.ny as f64 / args.nx as f64).round() as usize).max(2);
+    check_memory(
+        args,
+        &memory::MemoryEstimate::new(
+            memory::MeshSize::structured(largest_nx, largest_ny),
+            sweep.jobs.max(1).min(points.len().max(1)),
+            None,
+        ),
+    );
+    println!(
+        "Running parameter sweep: {} combinations, {} at a time...",
+        points.len(),
+        sweep.jobs.max(1)
+    );
+
+    let run = |point: &sweep::SweepPoint| {
+        let run_args = sweep_run_args(args, point);
+        let start = Instant::now();
+        let mesh = build_mesh(&run_args, origin, topography_type, holes);
+        let (mut solver, _) = create_solver(&run_args, mesh, constants, friction_law(&run_args));
+        set_initial_condition(
+            &mut solver,
+            &run_args,
+            center,
+            point.amplitude.unwrap_or(1.0),
+            0,
+        );
+        let initial_mass = solver.compute_total_mass();
+        let initial_energy = solver.compute_total_energy();
+
+        let mut peak = PeakDepth(solver.state.h.iter().copied().fold(0.0, f64::max));
+        let mut simulation = Simulation::until(&mut solver, args.final_time);
+        simulation.add_coupling(Box::new(DivergenceGuard));
+        simulation.add_observer(Box::new(&mut peak));
+        let diverged = simulation.run().is_err();
+        let steps = simulation.steps;
+        drop(simulation);
+        let max_depth = peak.0;
+
+        let result = sweep::SweepResult {
+            point: *point,
+            cells: solver.mesh.triangles.len(),
+            steps,
+            time: solver.time,
+            mass_error: (solver.compute_total_mass() - initial_mass) / initial_mass * 100.0,
+            energy_change: (solver.compute_total_energy() - initial_energy) / initial_energy
+                * 100.0,
+            max_depth,
+            runtime: start.elapsed().as_secs_f64(),
+            diverged,
+        };
+        println!(
+            "  nx = {:>4}, cfl = {:.3}, n = {:.4}, amplitude = {:.3}: {} steps, mass error {:.2e}%, max depth {:.4} m, {:.2}s{}",
+            run_args.nx,
+            run_args.cfl,
+            run_args.manning_n,
+            point.amplitude.unwrap_or(1.0),
+            result.steps,
+            result.mass_error,
+            result.max_depth,
+            result.runtime,
+            if diverged { " (diverged)" } else { "" }
+        );
+        result
+    };
+
+    let results: Vec<sweep::SweepResult> = if sweep.jobs > 1 {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(sweep.jobs)
+            .build()
+            .unwrap_or_else(|e| exit_with_error(&format!("thread pool: {}", e)))
+            .install(|| points.par_iter().map(run).collect())
+    } else {
+        points.iter().map(run).collect()
+    };
+
+    let filename = format!("{}_sweep.csv", args.output_prefix);
+    if let Err(e) = sweep::save_results(&filename, &results) {
+        eprintln!("Warning: Could not write output file {}: {}", filename, e);
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = &args.results_db {
+        if let Err(e) = record_sweep(path, args, constants, &results) {
+            eprintln!("Warning: Could not write results database {}: {}", path, e);
+        }
+    }
+    println!();
+    println!("Sweep completed!");
+    println!(
+        "  Runs: {}, diverged: {}",
+        results.len(),
+        results.iter().filter(|r| r.diverged).count()
+    );
+    println!("Results table saved to {}", filename);
+    println!("═══════════════════════════════════════════════════════════");
+}
+
+/// Arguments of one sweep combination
+fn sweep_run_args(args: &Args, point: &sweep::SweepPoint) -> Args {
+    let mut run_args = args.clone();
+    if let Some(nx) = point.nx {
+        run_args.nx = nx;
+        run_args.ny = ((nx as f64 * args.ny as f64 / args.nx as f64).round() as usize).max(2);
+    }
+    if let Some(cfl) = point.cfl {
+        run_args.cfl = cfl;
+    }
+    if let Some(n) = point.manning_n {
+        run_args.manning_n = n;
+    }
+    run_args
+}
+
+/// Add every sweep combination to the results database as a run of its own
+#[cfg(feature = "sqlite")]
+fn record_sweep(
+    path: &str,
+    args: &Args,
+    constants: PhysicalConstants,
+    results: &[sweep::SweepResult],
+) -> Result<(), String> {
+    let command: Vec<String> = std::env::args().collect();
+    let mut database = ResultsDatabase::open(path)?;
+    for result in results {
+        let mut parameters = run_parameters(&sweep_run_args(args, &result.point), constants);
+        parameters.push((
+            "amplitude",
+            result.point.amplitude.unwrap_or(1.0).to_string(),
+        ));
+        database.begin_run(
+            &args.output_prefix,
+            &command.join(" "),
+            result.cells,
+            &parameters,
+        )?;
+        database.finish_run(&RunSummary {
+            status: if result.diverged {
+                RunStatus::Diverged
+            } else {
+                RunStatus::Completed
+            },
+            steps: result.steps,
+            simulated_time: result.time,
+            wall_seconds: result.runtime,
+            metrics: &[
+                ("mass_error", result.mass_error),
+                ("energy_change", result.energy_change),
+                ("max_depth", result.max_depth),
+            ],
+        })?;
+    }
+    println!("Sweep runs added to {}", path);
+    Ok(())
+}
+
+/// Run the `sensitivity` subcommand: one base run and two perturbed runs per
+/// parameter, all in parallel
+fn run_sensitivity(
+    args: &Args,
+    sensitivity: &SensitivityArgs,
+    mesh: &TriangularMesh,
+    constants: PhysicalConstants,
+    center: (f64, f64),
+) {
+    use sensitivity::{Objective, Parameter};
+
+    let parameters = sensitivity::parse_parameters(&sensitivity.parameters)
+        .unwrap_or_else(|e| exit_with_error(&format!("--parameters: {}", e)));
+    if !(sensitivity.relative_step > 0.0 && sensitivity.relative_step < 1.0) {
+        exit_with_error("--relative-step must be between 0 and 1");
+    }
+    if args.steady_state {
+        exit_with_error("sensitivity runs march to --final-time; --steady-state is not supported");
+    }
+    let objective = match sensitivity.objective {
+        SensitivityObjective::PeakDepth => Objective::PeakDepth,
+        SensitivityObjective::FinalDepth => Objective::FinalDepth,
+        SensitivityObjective::FloodedArea => Objective::FloodedArea {
+            threshold: sensitivity.flood_threshold,
+        },
+    };
+    let cell = sensitivity.gauge.as_deref().map(|spec| {
+        let (x, y) = spec
+            .split_once(',')
+            .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+            .unwrap_or_else(|| {
+                exit_with_error(&format!("--gauge: expected x,y but found '{}'", spec))
+            });
+        calibration::nearest_cells(mesh, &[(x, y)])[0]
+    });
+
+    let base_value = |parameter: Parameter| match parameter {
+        Parameter::ManningN => args.manning_n,
+        Parameter::ChezyC => args.chezy_c,
+        Parameter::VoellmyMu => args.voellmy_mu,
+        Parameter::VoellmyXi => args.voellmy_xi,
+        Parameter::YieldStress => args.yield_stress,
+        Parameter::BinghamViscosity => args.bingham_viscosity,
+        Parameter::Gravity => constants.gravity,
+        Parameter::Density => constants.density,
+        Parameter::Amplitude => 1.0,
+    };
+    let has_effect = |parameter: Parameter| match parameter {
+        Parameter::ManningN => matches!(args.friction, Friction::Manning),
+        Parameter::ChezyC => matches!(args.friction, Friction::Chezy),
+        Parameter::VoellmyMu | Parameter::VoellmyXi => matches!(args.friction, Friction::Voellmy),
+        Parameter::YieldStress | Parameter::BinghamViscosity | Parameter::Density => {
+            matches!(args.friction, Friction::Bingham)
+        }
+        Parameter::Gravity | Parameter::Amplitude => true,
+    };
+    for &parameter in &parameters {
+        if !has_effect(parameter) {
+            println!(
+                "WARNING: {} has no effect with --friction {:?}",
+                parameter.name(),
+                args.friction
+            );
+        }
+    }
+
+    // Run 0 is the base; runs 2k+1 and 2k+2 perturb parameter k down and up
+    let mut runs: Vec<Option<(Parameter, f64)>> = vec![None];
+    for &parameter in &parameters {
+        let value = base_value(parameter);
+        let step = sensitivity.relative_step * value.abs();
+        runs.push(Some((parameter, value - step)));
+        runs.push(Some((parameter, value + step)));
+    }
+    println!(
+        "Computing sensitivities of {} to {} parameters ({} runs)...",
+        objective.name(),
+        parameters.len(),
+        runs.len()
+    );
+
+    let objectives: Vec<f64> = runs
+        .par_iter()
+        .map(|run| {
+            let mut run_args = args.clone();
+            let mut run_constants = constants;
+            let mut amplitude = 1.0;
+            if let Some((parameter, value)) = *run {
+                match parameter {
+                    Parameter::ManningN => run_args.manning_n = value,
+                    Parameter::ChezyC => run_args.chezy_c = value,
+                    Parameter::VoellmyMu => run_args.voellmy_mu = value,
+                    Parameter::VoellmyXi => run_args.voellmy_xi = value,
+                    Parameter::YieldStress => run_args.yield_stress = value,
+                    Parameter::BinghamViscosity => run_args.bingham_viscosity = value,
+                    Parameter::Gravity => run_constants.gravity = value,
+                    Parameter::Density => run_constants.density = value,
+                    Parameter::Amplitude => amplitude = value,
+                }
             }
+            let friction = friction_law(&run_args);
+            let (mut solver, _) = create_solver(&run_args, mesh.clone(), run_constants, friction);
+            set_initial_condition(&mut solver, &run_args, center, amplitude, 0);
+            sensitivity::evaluate(&mut solver, objective, cell, args.final_time)
+        })
+        .collect();
+
+    let base = objectives[0];
+    let mut table: Vec<sensitivity::Sensitivity> = parameters
+        .iter()
+        .enumerate()
+        .map(|(k, &parameter)| {
+            let value = base_value(parameter);
+            sensitivity::Sensitivity::new(
+                parameter,
+                value,
+                sensitivity.relative_step * value.abs(),
+                base,
+                objectives[2 * k + 1],
+                objectives[2 * k + 2],
+            )
+        })
+        .collect();
+    sensitivity::rank(&mut table);
+
+    let filename = format!("{}_sensitivity.csv", args.output_prefix);
+    if let Err(e) = sensitivity::save_table(&filename, objective, base, &table) {
+        eprintln!("Warning: Could not write output file {}: {}", filename, e);
+    }
+
+    println!();
+    println!(
+        "Sensitivity of {} (base value {:.6}):",
+        objective.name(),
+        base
+    );
+    println!(
+        "  {:<4} {:<18} {:>12} {:>14} {:>11}",
+        "rank", "parameter", "value", "dJ/dp", "elasticity"
+    );
+    for (rank, s) in table.iter().enumerate() {
+        println!(
+            "  {:<4} {:<18} {:>12.5} {:>14.6e} {:>11.4}",
+            rank + 1,
+            s.parameter.name(),
+            s.value,
+            s.derivative,
+            s.elasticity
+        );
+    }
+    println!("Sensitivity table saved to {}", filename);
+    println!("═══════════════════════════════════════════════════════════");
+}
+
+/// Run the `assimilate` subcommand: advance the ensemble from one
+/// observation time to the next and update it with the gauge readings
+fn run_assimilation(
+    args: &Args,
+    assimilate: &AssimilateArgs,
+    mesh: &TriangularMesh,
+    constants: PhysicalConstants,
+    center: (f64, f64),
+    output_fields: &[OutputField],
+) {
+    if assimilate.members < 2 {
+        exit_with_error("assimilation needs at least 2 --members");
+    }
+    if assimilate.observation_error <= 0.0 {
+        exit_with_error("--observation-error must be positive");
+    }
+    if args.steady_state {
+        exit_with_error("assimilation marches to --final-time; --steady-state is not supported");
+    }
+    let mut gauges = calibration::read_observations(&assimilate.observations)
+        .unwrap_or_else(|e| exit_with_error(&e));
+    for gauge in &mut gauges {
+        (gauge.x, gauge.y) = input_point(args, (gauge.x, gauge.y), "--observations");
+    }
+    let points: Vec<(f64, f64)> = gauges.iter().map(|g| (g.x, g.y)).collect();
+    let cells = calibration::nearest_cells(mesh, &points);
+    let mut times: Vec<f64> = gauges.iter().flat_map(|g| g.times.clone()).collect();
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+
+    println!(
+        "Assimilating {} gauges at {} observation times into {} members...",
+        gauges.len(),
+        times.len(),
+        assimilate.members
+    );
+    let friction = friction_law(args);
+    let members: Vec<ShallowWaterSolver> = (0..assimilate.members)
+        .map(|member| {
+            let (mut solver, _) = create_solver(args, mesh.clone(), constants, friction);
+            set_initial_condition(&mut solver, args, center, 1.0, member);
+            solver
+        })
+        .collect();
+    let mut filter = assimilation::EnsembleKalmanFilter::new(
+        members,
+        assimilate.observation_error,
+        assimilate.seed,
+    );
+    filter.perturb_levels(assimilate.initial_spread, assimilate.smoothing);
+
+    let filename = format!("{}_assimilation.csv", args.output_prefix);
+    let mut table = File::create(&filename)
+        .map_err(|e| eprintln!("Warning: Could not write output file {}: {}", filename, e))
+        .ok();
+    if let Some(file) = &mut table {
+        writeln!(
+            file,
+            "time,gauge,observed,forecast_mean,forecast_spread,analysis_mean,analysis_spread"
+        )
+        .unwrap();
+    }
+
+    for &t in times.iter().filter(|&&t| t <= args.final_time) {
+        // Gauges with a reading at this time
+        let (names, (operator_cells, observed)): (Vec<&str>, (Vec<usize>, Vec<f64>)) = gauges
+            .iter()
+            .zip(&cells)
+            .filter_map(|(g, &cell)| {
+                let k = g.times.iter().position(|&time| time == t)?;
+                Some((g.name.as_str(), (cell, g.levels[k])))
+            })
+            .unzip();
+        filter.advance_to(t);
+        let operator = assimilation::GaugeOperator {
+            cells: operator_cells,
+        };
+        let report = filter.analyze(&operator, &observed);
 
-            writeln!(file, "SCALARS momentum_y float 1").unwrap();
-            writeln!(file, "LOOKUP_TABLE default").unwrap();
-            for &hv in &solver.state.hv {
-                writeln!(file, "{}", hv).unwrap();
+        let rmse = |estimate: &[f64]| {
+            let sum: f64 = estimate
+                .iter()
+                .zip(&observed)
+                .map(|(e, o)| (e - o) * (e - o))
+                .sum();
+            (sum / observed.len() as f64).sqrt()
+        };
+        println!(
+            "  t = {:.3} s: {} readings, RMSE {:.4} m -> {:.4} m",
+            filter.members[0].time,
+            observed.len(),
+            rmse(&report.forecast_mean),
+            rmse(&report.analysis_mean)
+        );
+        if let Some(file) = &mut table {
+            for (k, name) in names.iter().enumerate() {
+                writeln!(
+                    file,
+                    "{},{},{},{},{},{},{}",
+                    t,
+                    name,
+                    observed[k],
+                    report.forecast_mean[k],
+                    report.forecast_spread[k],
+                    report.analysis_mean[k],
+                    report.analysis_spread[k]
+                )
+                .unwrap();
             }
+        }
+    }
+    filter.advance_to(args.final_time);
 
-            writeln!(file, "SCALARS bed_elevation float 1").unwrap();
-            writeln!(file, "LOOKUP_TABLE default").unwrap();
-            for tri in &solver.mesh.triangles {
-                writeln!(file, "{}", tri.z_bed).unwrap();
+    // Ensemble mean written as a frame of the first member
+    let state = filter.mean_state();
+    let mut mean = filter.members.swap_remove(0);
+    mean.state = state;
+    let vtk = format!("{}_analysis.vtk", args.output_prefix);
+    if let Err(e) = output::save_vtk(&vtk, &mean.mesh, &Frame::capture(&mean, output_fields)) {
+        eprintln!("Warning: Could not write output file {}: {}", vtk, e);
+    }
+
+    println!();
+    println!("Assimilation completed!");
+    println!("  Final time: {:.4} s", mean.time);
+    println!("Gauge updates saved to {}", filename);
+    println!("Ensemble mean saved to {}", vtk);
+    println!("═══════════════════════════════════════════════════════════");
+}
+
+fn write_ensemble_members(members: &[MemberParameters], prefix: &str) {
+    let filename = format!("{}_ensemble_members.csv", prefix);
+
+    match File::create(&filename) {
+        Ok(mut file) => {
+            writeln!(file, "member,manning_n,amplitude").unwrap();
+            for (m, parameters) in members.iter().enumerate() {
+                let manning_n = parameters
+                    .manning_n
+                    .map_or(String::new(), |n| n.to_string());
+                writeln!(file, "{},{},{}", m, manning_n, parameters.amplitude).unwrap();
             }
+        }
+        Err(e) => {
+            eprintln!("Warning: Could not write output file {}: {}", filename, e);
+        }
+    }
+}
+
+fn write_convergence_history(history: &[f64], prefix: &str) {
+    let filename = format!("{}_convergence.csv", prefix);
 
-            writeln!(file, "SCALARS water_surface float 1").unwrap();
-            writeln!(file, "LOOKUP_TABLE default").unwrap();
-            for (i, tri) in solver.mesh.triangles.iter().enumerate() {
-                writeln!(file, "{}", tri.z_bed + solver.state.h[i]).unwrap();
+    match File::create(&filename) {
+        Ok(mut file) => {
+            writeln!(file, "iteration,residual_l2").unwrap();
+            for (iteration, residual) in history.iter().enumerate() {
+                writeln!(file, "{},{:e}", iteration, residual).unwrap();
             }
         }
         Err(e) => {
@@ -345,3 +5087,132 @@ fn save_state(solver: &ShallowWaterSolver, index: usize, prefix: &str) {
         }
     }
 }
+
+/// Critical bed shear stress (Pa) of the --scour-diameter grains for the
+/// shear-exceedance raster; NaN when that raster is not requested
+fn critical_shear_stress(solver: &ShallowWaterSolver, args: &Args, fields: &[RasterField]) -> f64 {
+    if !fields.contains(&RasterField::ShearExceedance) {
+        return f64::NAN;
+    }
+    let Some(diameter) = args.scour_diameter.or(args.sediment_diameter) else {
+        exit_with_error("--geotiff shear-exceedance requires --scour-diameter");
+    };
+    if !(diameter > 0.0 && diameter.is_finite())
+        || args.sediment_density <= solver.constants.density
+    {
+        exit_with_error(
+            "--scour-diameter must be positive and --sediment-density exceed the water density",
+        );
+    }
+    let (g, rho) = (solver.constants.gravity, solver.constants.density);
+    let critical = match args.critical_shields {
+        Some(theta) if theta > 0.0 && theta.is_finite() => {
+            theta * (args.sediment_density - rho) * g * diameter
+        }
+        Some(_) => exit_with_error("--critical-shields must be positive"),
+        None => SedimentProperties {
+            grain_diameter: diameter,
+            density: args.sediment_density,
+            ..SedimentProperties::default()
+        }
+        .critical_shear_stress(g, rho),
+    };
+    println!(
+        "  Scour indicator: critical shear stress {:.3} Pa for d50 = {} m",
+        critical, diameter
+    );
+    critical
+}
+
+fn save_rasters(
+    solver: &ShallowWaterSolver,
+    envelope: &FloodEnvelope,
+    shear: Option<&ShearExposure>,
+    fields: &[RasterField],
+    args: &Args,
+    crs: RasterCrs,
+) {
+    let grid = RasterGrid::covering(&solver.mesh, args.raster_cell_size);
+    println!(
+        "Writing GeoTIFF rasters ({} x {}, cell size {})",
+        grid.ncols, grid.nrows, grid.cell_size
+    );
+
+    for field in fields {
+        let values = match field {
+            RasterField::Depth => solver.state.h.clone(),
+            RasterField::MaxDepth => envelope.max_depth.clone(),
+            RasterField::Hazard => solver.hazard_rating(),
+            RasterField::MaxHazard => envelope.max_hazard.clone(),
+            RasterField::ArrivalTime => envelope.arrival_time.clone(),
+            RasterField::Duration => envelope.duration.clone(),
+            RasterField::PeakTime => envelope.peak_time.clone(),
+            RasterField::ShearIntegral => shear.expect("shear is tracked").integral.clone(),
+            RasterField::ShearExceedance => shear.expect("shear is tracked").exceedance_fraction(),
+            RasterField::MaxShear => shear.expect("shear is tracked").max_shear.clone(),
+        };
+        let filename = format!("{}_{}.tif", args.output_prefix, field.name());
+        if let Err(e) = raster::save_geotiff(&filename, &solver.mesh, &values, &grid, crs) {
+            eprintln!("Warning: Could not write output file {}: {}", filename, e);
+        }
+    }
+}
+
+/// Mass conservation error (%): the volume change not explained by the
+/// tracked exchange, or the plain change of the initial volume without it
+fn report_mass_exchange(exchange: &MassExchange, final_mass: f64) {
+    // Closed walls pass round-off only
+    let negligible = 1e-9 * exchange.reference_volume();
+    for (k, segment) in exchange.segments.iter().enumerate() {
+        let (inflow, outflow) = (exchange.inflow[k], exchange.outflow[k]);
+        if inflow + outflow > negligible {
+            println!(
+                "  Boundary {}: {:.6} in, {:.6} out",
+                segment, inflow, outflow
+            );
+        }
+    }
+    if exchange.sources != 0.0 {
+        println!("  Source terms: {:+.6}", exchange.sources);
+    }
+    if exchange.coupled != 0.0 {
+        println!("  Coupled models: {:+.6}", exchange.coupled);
+    }
+    println!(
+        "  Net exchange: {:+.6}, unaccounted: {:+.3e}",
+        exchange.net_exchange(),
+        exchange.unaccounted(final_mass)
+    );
+}
+
+fn report_timestep_control(control: &TimestepControl) {
+    if control.largest_dt > 0.0 {
+        println!(
+            "  Time step: {:.3e} to {:.3e} s, {} retried attempts",
+            control.smallest_dt, control.largest_dt, control.retries
+        );
+    }
+    if control.failures > 0 {
+        eprintln!(
+            "Warning: {} steps left negative depths or NaNs even at the minimum time step \
+             (negative depths were clamped to zero)",
+            control.failures
+        );
+    }
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line_is_consistent() {
+        // Clashing option names only panic when the program parses arguments
+        <Args as clap::CommandFactory>::command().debug_assert();
+    }
+}
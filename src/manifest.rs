@@ -0,0 +1,294 @@
+/// Run manifest and provenance metadata
+/// A JSON record of how a run was made and what it wrote: the program
+/// version and commit, the command line, the effective options and
+/// configuration file, the mesh and its checksum, the wall-clock start and
+/// end, and every output file with its size and CRC-32. It is written when
+/// the run starts and completed when it ends, so an aborted run leaves a
+/// manifest with the status "running".
+use crate::compress::crc32;
+use crate::mesh::{CoordinateSystem, TriangularMesh};
+use serde::Serialize;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Commit the program was built from (set by build.rs in a git checkout)
+pub const GIT_COMMIT: Option<&str> = option_env!("SWE_GIT_COMMIT");
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManifestStatus {
+    Running,
+    Completed,
+    TimeLimit,
+    Diverged,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MeshSummary {
+    pub nodes: usize,
+    pub cells: usize,
+    pub coordinate_system: CoordinateSystem,
+    /// CRC-32 of the node coordinates, bed elevations and cell vertices
+    pub checksum: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutputFile {
+    pub path: String,
+    pub bytes: u64,
+    pub crc32: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunManifest {
+    pub version: &'static str,
+    pub git_commit: Option<&'static str>,
+    pub command: Vec<String>,
+    pub working_directory: Option<String>,
+    pub status: ManifestStatus,
+    pub started: String,
+    pub finished: Option<String>,
+    pub wall_seconds: Option<f64>,
+    pub threads: usize,
+    pub steps: usize,
+    pub simulated_time: f64,
+    pub mesh: MeshSummary,
+    /// Command-line options after defaults and the configuration file
+    pub options: serde_json::Value,
+    /// Contents of the configuration file (null without one)
+    pub config: serde_json::Value,
+    pub outputs: Vec<OutputFile>,
+    #[serde(skip)]
+    start: SystemTime,
+}
+
+impl RunManifest {
+    pub fn new(
+        command: Vec<String>,
+        options: serde_json::Value,
+        config: serde_json::Value,
+        mesh: &TriangularMesh,
+    ) -> Self {
+        let start = SystemTime::now();
+        RunManifest {
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: GIT_COMMIT,
+            command,
+            working_directory: std::env::current_dir()
+                .ok()
+                .map(|dir| dir.display().to_string()),
+            status: ManifestStatus::Running,
+            started: utc_timestamp(start),
+            finished: None,
+            wall_seconds: None,
+            threads: rayon::current_num_threads(),
+            steps: 0,
+            simulated_time: 0.0,
+            mesh: MeshSummary {
+                nodes: mesh.nodes.len(),
+                cells: mesh.triangles.len(),
+                coordinate_system: mesh.coordinate_system,
+                checksum: format!("{:08x}", mesh_checksum(mesh)),
+            },
+            options,
+            config,
+            outputs: Vec::new(),
+            start,
+        }
+    }
+
+    /// Record the end of the run and the files named after `prefix` that it
+    /// wrote, except `manifest` itself
+    pub fn finish(
+        &mut self,
+        status: ManifestStatus,
+        steps: usize,
+        simulated_time: f64,
+        prefix: &str,
+        manifest: &str,
+    ) {
+        let end = SystemTime::now();
+        self.status = status;
+        self.finished = Some(utc_timestamp(end));
+        self.wall_seconds = Some(
+            end.duration_since(self.start)
+                .unwrap_or_default()
+                .as_secs_f64(),
+        );
+        self.steps = steps;
+        self.simulated_time = simulated_time;
+        self.outputs = output_files(prefix, self.start)
+            .into_iter()
+            .filter(|file| Path::new(&file.path) != Path::new(manifest))
+            .collect();
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).expect("manifest is serializable");
+        std::fs::write(path, text + "\n").map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// CRC-32 of the node coordinates and bed elevations and the cell vertices,
+/// in native order
+pub fn mesh_checksum(mesh: &TriangularMesh) -> u32 {
+    let mut bytes = Vec::with_capacity(24 * mesh.nodes.len() + 24 * mesh.triangles.len());
+    for node in &mesh.nodes {
+        for value in [node.x, node.y, node.z] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    for triangle in &mesh.triangles {
+        for node in triangle.nodes {
+            bytes.extend_from_slice(&(node as u64).to_le_bytes());
+        }
+    }
+    crc32(bytes.iter())
+}
+
+/// Files named `{prefix}_*` or `{prefix}.*` (and the files in such
+/// directories) modified since `since`, sorted by path
+pub fn output_files(prefix: &str, since: SystemTime) -> Vec<OutputFile> {
+    let prefix = Path::new(prefix);
+    let directory = match prefix.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let stem = prefix
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // File times may be stored to the second only
+    let since = UNIX_EPOCH
+        + Duration::from_secs(
+            since
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        );
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let named = name
+            .strip_prefix(stem.as_str())
+            .is_some_and(|rest| rest.starts_with('_') || rest.starts_with('.'));
+        if named {
+            collect_files(&entry.path(), since, &mut files);
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+fn collect_files(path: &Path, since: SystemTime, files: &mut Vec<OutputFile>) {
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+            collect_files(&entry.path(), since, files);
+        }
+        return;
+    }
+    let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    if !modified.is_ok_and(|modified| modified >= since) {
+        return;
+    }
+    if let Ok(bytes) = std::fs::read(path) {
+        let path = path.strip_prefix(".").unwrap_or(path);
+        files.push(OutputFile {
+            path: path.display().to_string(),
+            bytes: bytes.len() as u64,
+            crc32: format!("{:08x}", crc32(bytes.iter())),
+        });
+    }
+}
+
+/// ISO 8601 UTC time to the second, e.g. "2024-03-01T12:00:00Z"
+pub fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, second) = (seconds / 86_400, seconds % 86_400);
+    // Civil date from days since 1970-01-01 (proleptic Gregorian)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        second / 3600,
+        second / 60 % 60,
+        second % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+
+    #[test]
+    fn test_utc_timestamp() {
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400 + 3_723);
+        assert_eq!(utc_timestamp(leap_day), "2000-02-29T01:02:03Z");
+        let new_year = UNIX_EPOCH + Duration::from_secs(1_704_067_199);
+        assert_eq!(utc_timestamp(new_year), "2023-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn test_checksum_and_output_listing() {
+        let mut mesh = TriangularMesh::new_rectangular(5, 4, 4.0, 3.0, TopographyType::Flat);
+        let flat = mesh_checksum(&mesh);
+        assert_eq!(flat, mesh_checksum(&mesh.clone()));
+        mesh.nodes[3].z += 0.001;
+        assert_ne!(flat, mesh_checksum(&mesh));
+
+        let directory = std::env::temp_dir().join(format!("swe_manifest_{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("run.zarr/h")).unwrap();
+        for (name, text) in [
+            ("run_0000.vtk", "frame"),
+            ("run.zarr/h/0", "chunk"),
+            ("run_manifest.json", "{}"),
+            ("runaway.txt", "other run"),
+        ] {
+            std::fs::write(directory.join(name), text).unwrap();
+        }
+        let prefix = directory.join("run").display().to_string();
+        let manifest = format!("{}_manifest.json", prefix);
+        let mut run = RunManifest::new(
+            Vec::new(),
+            serde_json::Value::Null,
+            serde_json::Value::Null,
+            &mesh,
+        );
+        run.finish(ManifestStatus::Completed, 10, 1.0, &prefix, &manifest);
+        let names: Vec<&str> = run
+            .outputs
+            .iter()
+            .map(|file| file.path.strip_prefix(directory.to_str().unwrap()).unwrap())
+            .collect();
+        assert_eq!(names, ["/run.zarr/h/0", "/run_0000.vtk"]);
+        assert_eq!(run.outputs[1].bytes, 5);
+        assert_eq!(
+            run.outputs[1].crc32,
+            format!("{:08x}", crc32(b"frame".iter()))
+        );
+
+        let late = SystemTime::now() + Duration::from_secs(10);
+        assert!(output_files(&prefix, late).is_empty());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
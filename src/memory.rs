@@ -0,0 +1,197 @@
+/// Memory estimate for a run
+/// Sizes follow from the mesh dimensions and the run configuration, so the
+/// estimate is available before the mesh is generated. It counts the mesh
+/// (and its construction temporaries), every solver alive at the same time
+/// with its RK2 work arrays, and the output buffers. Smaller allocations
+/// (gauges, budgets, CSV rows) are ignored; holes only reduce the result.
+use crate::mesh::{Edge, Node, Triangle};
+use crate::output::OutputField;
+use std::mem::size_of;
+
+/// Per cell: the state, four RK2 work states and the source contributions
+const SOLVER_BYTES_PER_CELL: u64 = 5 * 3 * 8 + 32;
+/// Per edge: entry of the edge set used while generating edges
+const EDGE_SET_BYTES_PER_EDGE: u64 = 32;
+
+/// Entity counts of a mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshSize {
+    pub nodes: usize,
+    pub cells: usize,
+    pub edges: usize,
+}
+
+impl MeshSize {
+    /// Structured mesh of nx × ny nodes with two triangles per quad
+    pub fn structured(nx: usize, ny: usize) -> Self {
+        let (qx, qy) = (nx.saturating_sub(1), ny.saturating_sub(1));
+        MeshSize {
+            nodes: nx * ny,
+            cells: 2 * qx * qy,
+            edges: 3 * qx * qy + qx + qy,
+        }
+    }
+
+    /// Bytes of the mesh arrays, including the least-squares weights
+    pub fn bytes(&self) -> u64 {
+        let cell = size_of::<Triangle>() + size_of::<[(f64, f64); 3]>();
+        (self.nodes * size_of::<Node>() + self.cells * cell + self.edges * size_of::<Edge>()) as u64
+    }
+}
+
+/// Estimated peak memory, itemised
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryEstimate {
+    pub items: Vec<(&'static str, u64)>, // (description, bytes)
+}
+
+impl MemoryEstimate {
+    /// Run on `mesh` with `solvers` solvers alive at once (each owns a copy
+    /// of the mesh) and, for time-marching runs, the output `fields` with
+    /// `queued_frames` frames buffered for the writer
+    pub fn new(mesh: MeshSize, solvers: usize, output: Option<(&[OutputField], usize)>) -> Self {
+        let cells = mesh.cells as u64;
+        let solvers = solvers.max(1) as u64;
+        let mut items = vec![
+            ("mesh", mesh.bytes()),
+            (
+                "mesh construction (temporary)",
+                mesh.edges as u64 * EDGE_SET_BYTES_PER_EDGE,
+            ),
+            (
+                "solver state and work arrays",
+                solvers * cells * SOLVER_BYTES_PER_CELL,
+            ),
+        ];
+        if solvers > 1 {
+            items.push((
+                "mesh copies of concurrent solvers",
+                (solvers - 1) * mesh.bytes(),
+            ));
+        }
+        if let Some((fields, queued_frames)) = output {
+            let frame: u64 = fields
+                .iter()
+                .map(|field| match field {
+                    OutputField::Velocity => 16 * cells,
+                    _ => 8 * cells,
+                })
+                .sum();
+            // Queued frames, the frame being written and the one being captured
+            items.push((
+                "output frames",
+                (queued_frames as u64 + 2) * frame + mesh.bytes(),
+            ));
+            items.push(("flood envelope", 40 * cells));
+        }
+        MemoryEstimate { items }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.items.iter().map(|(_, bytes)| bytes).sum()
+    }
+
+    /// Table of the items and the total
+    pub fn report(&self) -> String {
+        let mut table = String::new();
+        for (name, bytes) in &self.items {
+            table += &format!("  {:<34} {:>12}\n", name, format_bytes(*bytes));
+        }
+        table += &format!("  {:<34} {:>12}\n", "total", format_bytes(self.total()));
+        table
+    }
+}
+
+/// Parse a size such as "512M", "8G", "1.5GiB" or "1000000" (bytes);
+/// units are binary (K = 1024)
+pub fn parse_size(spec: &str) -> Result<u64, String> {
+    let spec = spec.trim();
+    let split = spec
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(spec.len());
+    let (number, unit) = spec.split_at(split);
+    let value: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", spec))?;
+    let scale = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1u64,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("unknown size unit in '{}'", spec)),
+    };
+    if value < 0.0 {
+        return Err(format!("negative size '{}'", spec));
+    }
+    Ok((value * scale as f64) as u64)
+}
+
+/// Human-readable size with binary units
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Memory available to new allocations (Linux only)
+pub fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Resident set size of this process (Linux only)
+pub fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+
+    #[test]
+    fn test_structured_counts_match_generated_mesh() {
+        let mesh = TriangularMesh::new_rectangular(7, 5, 6.0, 4.0, TopographyType::Flat);
+        let size = MeshSize::structured(7, 5);
+        assert_eq!(size.nodes, mesh.nodes.len());
+        assert_eq!(size.cells, mesh.triangles.len());
+        assert_eq!(size.edges, mesh.edges.len());
+
+        let fields = [OutputField::Height, OutputField::Velocity];
+        let single = MemoryEstimate::new(size, 1, None);
+        let run = MemoryEstimate::new(size, 1, Some((&fields, 2)));
+        let ensemble = MemoryEstimate::new(size, 4, None);
+        assert!(single.total() > size.bytes());
+        assert!(run.total() > single.total());
+        assert!(ensemble.total() > 4 * (single.total() - size.bytes()));
+        assert!(run.report().contains("output frames"));
+    }
+
+    #[test]
+    fn test_parse_and_format_sizes() {
+        assert_eq!(parse_size("1000"), Ok(1000));
+        assert_eq!(parse_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_size("1.5 GiB"), Ok(3 << 29));
+        assert_eq!(parse_size("8gb"), Ok(8 << 30));
+        assert!(parse_size("8X").is_err());
+        assert!(parse_size("lots").is_err());
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3 << 29), "1.5 GiB");
+    }
+}
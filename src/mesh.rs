@@ -1,14 +1,26 @@
+use serde::{Deserialize, Serialize};
 /// Triangular mesh data structures and operations
 use std::f64;
 
-#[derive(Debug, Clone)]
+mod cache;
+mod delaunay;
+mod holes;
+mod partition;
+mod reorder;
+mod spatial;
+
+pub use delaunay::delaunay;
+pub use holes::{dam_with_breach, parse_polygon, point_in_polygon, Polygon};
+pub use spatial::{barycentric, PointLocator};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
     pub x: f64,
     pub y: f64,
     pub z: f64, // Bottom elevation (bathymetry/topography)
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Triangle {
     pub id: usize,
     pub nodes: [usize; 3],             // Node indices
@@ -18,22 +30,75 @@ pub struct Triangle {
     pub z_bed: f64, // Average bed elevation
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Edge {
+    pub nodes: [usize; 2], // End node indices
     pub length: f64,
     pub normal: (f64, f64), // Unit normal vector
     pub left_triangle: usize,
     pub right_triangle: Option<usize>, // None for boundary edges
+    pub ghost: Option<usize>,          // Ghost cell of a boundary edge
+}
+
+/// Ghost cell behind a boundary edge: the mirror image of the interior cell
+/// across the edge, whose state the boundary closure fills every stage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GhostCell {
+    pub edge: usize,
+    pub cell: usize,        // Interior cell
+    pub side: usize,        // Local edge index k (nodes k, k+1) in the interior cell
+    pub offset: (f64, f64), // From the interior to the ghost centroid (m)
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TriangularMesh {
     pub nodes: Vec<Node>,
     pub triangles: Vec<Triangle>,
     pub edges: Vec<Edge>,
+    pub coordinate_system: CoordinateSystem,
+    /// Weighted least-squares gradient weights per cell and local edge:
+    /// ∇f_i ≈ Σ_k w_ik (f_k - f_i), where f_k is the value in the neighbour
+    /// across edge k or, on the boundary, at the edge midpoint
+    pub lsq_weights: Vec<[(f64, f64); 3]>,
+    /// Ghost cells of the boundary edges, ordered by interior cell and side
+    pub ghosts: Vec<GhostCell>,
+}
+
+pub const EARTH_RADIUS: f64 = 6_371_000.0; // Mean Earth radius (m)
+
+/// Interpretation of node coordinates
+/// Geometric quantities (areas, edge lengths, normals) are always in metres;
+/// vectors are expressed in the local east/north basis.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoordinateSystem {
+    /// x, y in metres
+    Cartesian,
+    /// x = longitude, y = latitude in degrees on a sphere of the given radius (m)
+    Spherical { radius: f64 },
+}
+
+impl CoordinateSystem {
+    /// Displacement from `a` to `b` in metres, with the zonal metric
+    /// evaluated at latitude `lat` (degrees)
+    pub fn delta_at(&self, a: (f64, f64), b: (f64, f64), lat: f64) -> (f64, f64) {
+        match *self {
+            CoordinateSystem::Cartesian => (b.0 - a.0, b.1 - a.1),
+            CoordinateSystem::Spherical { radius } => (
+                radius * lat.to_radians().cos() * (b.0 - a.0).to_radians(),
+                radius * (b.1 - a.1).to_radians(),
+            ),
+        }
+    }
+
+    /// Displacement from `a` to `b` in metres using the mid-point latitude
+    pub fn delta(&self, a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+        self.delta_at(a, b, 0.5 * (a.1 + b.1))
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TopographyType {
     Flat,
     Slope {
@@ -60,15 +125,52 @@ impl TriangularMesh {
         height: f64,
         topography: TopographyType,
     ) -> Self {
-        let dx = width / (nx - 1) as f64;
-        let dy = height / (ny - 1) as f64;
+        Self::new_structured(
+            nx,
+            ny,
+            (0.0, 0.0),
+            (width, height),
+            topography,
+            CoordinateSystem::Cartesian,
+        )
+    }
+
+    /// Create a longitude-latitude mesh (degrees) with spherical metric terms
+    pub fn new_geographic(
+        nx: usize,
+        ny: usize,
+        origin: (f64, f64),
+        extent: (f64, f64),
+        topography: TopographyType,
+        radius: f64,
+    ) -> Self {
+        Self::new_structured(
+            nx,
+            ny,
+            origin,
+            extent,
+            topography,
+            CoordinateSystem::Spherical { radius },
+        )
+    }
+
+    fn new_structured(
+        nx: usize,
+        ny: usize,
+        origin: (f64, f64),
+        extent: (f64, f64),
+        topography: TopographyType,
+        coordinate_system: CoordinateSystem,
+    ) -> Self {
+        let dx = extent.0 / (nx - 1) as f64;
+        let dy = extent.1 / (ny - 1) as f64;
 
         // Generate nodes
         let mut nodes = Vec::new();
         for j in 0..ny {
             for i in 0..nx {
-                let x = i as f64 * dx;
-                let y = j as f64 * dy;
+                let x = origin.0 + i as f64 * dx;
+                let y = origin.1 + j as f64 * dy;
                 let z = Self::compute_topography(x, y, topography);
 
                 nodes.push(Node { x, y, z });
@@ -87,8 +189,14 @@ impl TriangularMesh {
                 let n3 = (j + 1) * nx + i + 1;
 
                 // Lower triangle
-                let area1 = Self::compute_area(&nodes[n0], &nodes[n1], &nodes[n2]);
                 let centroid1 = Self::compute_centroid(&nodes[n0], &nodes[n1], &nodes[n2]);
+                let area1 = Self::compute_area(
+                    coordinate_system,
+                    &nodes[n0],
+                    &nodes[n1],
+                    &nodes[n2],
+                    centroid1.1,
+                );
                 let z_bed1 = (nodes[n0].z + nodes[n1].z + nodes[n2].z) / 3.0;
                 triangles.push(Triangle {
                     id: tri_id,
@@ -101,8 +209,14 @@ impl TriangularMesh {
                 tri_id += 1;
 
                 // Upper triangle
-                let area2 = Self::compute_area(&nodes[n1], &nodes[n3], &nodes[n2]);
                 let centroid2 = Self::compute_centroid(&nodes[n1], &nodes[n3], &nodes[n2]);
+                let area2 = Self::compute_area(
+                    coordinate_system,
+                    &nodes[n1],
+                    &nodes[n3],
+                    &nodes[n2],
+                    centroid2.1,
+                );
                 let z_bed2 = (nodes[n1].z + nodes[n3].z + nodes[n2].z) / 3.0;
                 triangles.push(Triangle {
                     id: tri_id,
@@ -120,17 +234,127 @@ impl TriangularMesh {
         Self::build_neighbors(&mut triangles);
 
         // Generate edges
-        let edges = Self::generate_edges(&nodes, &triangles);
+        let edges = Self::generate_edges(&nodes, &triangles, coordinate_system);
+        let lsq_weights = Self::compute_lsq_weights(&nodes, &triangles, coordinate_system);
+        let ghosts = Self::compute_ghosts(&nodes, &triangles, &edges, coordinate_system);
 
         TriangularMesh {
             nodes,
             triangles,
             edges,
+            coordinate_system,
+            lsq_weights,
+            ghosts,
         }
     }
 
-    fn compute_area(n0: &Node, n1: &Node, n2: &Node) -> f64 {
-        0.5 * ((n1.x - n0.x) * (n2.y - n0.y) - (n2.x - n0.x) * (n1.y - n0.y)).abs()
+    /// Build a mesh from nodes and triangles given as node index triples
+    /// (e.g. read from a file). Triangles are oriented counter-clockwise;
+    /// neighbours are found through shared edges in linear time.
+    pub fn from_triangles(
+        nodes: Vec<Node>,
+        connectivity: &[[usize; 3]],
+        coordinate_system: CoordinateSystem,
+    ) -> Result<Self, String> {
+        if connectivity.is_empty() {
+            return Err("mesh has no triangles".to_string());
+        }
+        let mut triangles = Vec::with_capacity(connectivity.len());
+        for (id, &tri_nodes) in connectivity.iter().enumerate() {
+            if let Some(&n) = tri_nodes.iter().find(|&&n| n >= nodes.len()) {
+                return Err(format!(
+                    "triangle {} refers to node {} of {}",
+                    id,
+                    n,
+                    nodes.len()
+                ));
+            }
+            let [a, b, c] = tri_nodes;
+            let (n0, n1, n2) = (&nodes[a], &nodes[b], &nodes[c]);
+            let centroid = Self::compute_centroid(n0, n1, n2);
+            let area = Self::compute_area(coordinate_system, n0, n1, n2, centroid.1);
+            if area <= 0.0 || area.is_nan() {
+                return Err(format!("triangle {} is degenerate", id));
+            }
+            let orientation = (n1.x - n0.x) * (n2.y - n0.y) - (n2.x - n0.x) * (n1.y - n0.y);
+            triangles.push(Triangle {
+                id,
+                nodes: if orientation < 0.0 {
+                    [a, c, b]
+                } else {
+                    [a, b, c]
+                },
+                neighbors: [None, None, None],
+                area,
+                centroid,
+                z_bed: (n0.z + n1.z + n2.z) / 3.0,
+            });
+        }
+
+        let mut faces: std::collections::HashMap<(usize, usize), (usize, usize)> =
+            std::collections::HashMap::with_capacity(3 * triangles.len());
+        for i in 0..triangles.len() {
+            for k in 0..3 {
+                let (n0, n1) = (triangles[i].nodes[k], triangles[i].nodes[(k + 1) % 3]);
+                let key = (n0.min(n1), n0.max(n1));
+                match faces.get(&key) {
+                    Some(&(j, l)) => {
+                        if triangles[j].neighbors[l].is_some() {
+                            return Err(format!(
+                                "edge {}-{} is shared by more than two triangles",
+                                key.0, key.1
+                            ));
+                        }
+                        triangles[i].neighbors[k] = Some(j);
+                        triangles[j].neighbors[l] = Some(i);
+                    }
+                    None => {
+                        faces.insert(key, (i, k));
+                    }
+                }
+            }
+        }
+
+        let edges = Self::generate_edges(&nodes, &triangles, coordinate_system);
+        let lsq_weights = Self::compute_lsq_weights(&nodes, &triangles, coordinate_system);
+        let ghosts = Self::compute_ghosts(&nodes, &triangles, &edges, coordinate_system);
+        Ok(TriangularMesh {
+            nodes,
+            triangles,
+            edges,
+            coordinate_system,
+            lsq_weights,
+            ghosts,
+        })
+    }
+
+    /// Ghost cell behind local edge `side` of `cell`, if it is a boundary
+    /// edge
+    pub fn ghost(&self, cell: usize, side: usize) -> Option<usize> {
+        if self.triangles[cell].neighbors[side].is_some() {
+            return None;
+        }
+        self.ghosts
+            .binary_search_by_key(&(cell, side), |g| (g.cell, g.side))
+            .ok()
+    }
+
+    /// Displacement between two nodes in metres
+    pub fn node_delta(&self, a: usize, b: usize) -> (f64, f64) {
+        let (na, nb) = (&self.nodes[a], &self.nodes[b]);
+        self.coordinate_system.delta((na.x, na.y), (nb.x, nb.y))
+    }
+
+    fn compute_area(
+        coordinate_system: CoordinateSystem,
+        n0: &Node,
+        n1: &Node,
+        n2: &Node,
+        lat: f64,
+    ) -> f64 {
+        let (ax, ay) = coordinate_system.delta_at((n0.x, n0.y), (n1.x, n1.y), lat);
+        let (bx, by) = coordinate_system.delta_at((n0.x, n0.y), (n2.x, n2.y), lat);
+        0.5 * (ax * by - bx * ay).abs()
     }
 
     fn compute_centroid(n0: &Node, n1: &Node, n2: &Node) -> (f64, f64) {
@@ -177,9 +401,14 @@ impl TriangularMesh {
         0
     }
 
-    fn generate_edges(nodes: &[Node], triangles: &[Triangle]) -> Vec<Edge> {
+    fn generate_edges(
+        nodes: &[Node],
+        triangles: &[Triangle],
+        coordinate_system: CoordinateSystem,
+    ) -> Vec<Edge> {
         let mut edges = Vec::new();
         let mut edge_set = std::collections::HashSet::new();
+        let mut ghosts = 0;
 
         for tri in triangles {
             for i in 0..3 {
@@ -189,20 +418,32 @@ impl TriangularMesh {
                 let edge_key = if n0 < n1 { (n0, n1) } else { (n1, n0) };
 
                 if edge_set.insert(edge_key) {
-                    let dx = nodes[n1].x - nodes[n0].x;
-                    let dy = nodes[n1].y - nodes[n0].y;
+                    let p0 = (nodes[n0].x, nodes[n0].y);
+                    let p1 = (nodes[n1].x, nodes[n1].y);
+                    let (dx, dy) = coordinate_system.delta(p0, p1);
                     let length = (dx * dx + dy * dy).sqrt();
 
-                    // Normal vector (pointing right relative to edge direction)
-                    let normal = (-dy / length, dx / length);
+                    // Unit normal pointing out of the left triangle
+                    let mut normal = (dy / length, -dx / length);
+                    let mid = (0.5 * (p0.0 + p1.0), 0.5 * (p0.1 + p1.1));
+                    let (cx, cy) = coordinate_system.delta(tri.centroid, mid);
+                    if cx * normal.0 + cy * normal.1 < 0.0 {
+                        normal = (-normal.0, -normal.1);
+                    }
 
                     let right_triangle = tri.neighbors[i];
+                    let ghost = right_triangle.is_none().then(|| {
+                        ghosts += 1;
+                        ghosts - 1
+                    });
 
                     edges.push(Edge {
+                        nodes: [n0, n1],
                         length,
                         normal,
                         left_triangle: tri.id,
                         right_triangle,
+                        ghost,
                     });
                 }
             }
@@ -211,6 +452,100 @@ impl TriangularMesh {
         edges
     }
 
+    /// Ghost cells of the boundary edges, numbered as in `generate_edges`.
+    /// Edges are generated cell by cell and side by side, and a boundary
+    /// edge belongs to one cell only, so the ghosts are ordered by interior
+    /// cell and side as well.
+    fn compute_ghosts(
+        nodes: &[Node],
+        triangles: &[Triangle],
+        edges: &[Edge],
+        coordinate_system: CoordinateSystem,
+    ) -> Vec<GhostCell> {
+        edges
+            .iter()
+            .enumerate()
+            .filter(|(_, edge)| edge.ghost.is_some())
+            .map(|(e, edge)| {
+                let tri = &triangles[edge.left_triangle];
+                let side = (0..3)
+                    .find(|&k| {
+                        let pair = [tri.nodes[k], tri.nodes[(k + 1) % 3]];
+                        pair == edge.nodes || pair == [edge.nodes[1], edge.nodes[0]]
+                    })
+                    .expect("boundary edge belongs to its cell");
+                let (a, b) = (&nodes[edge.nodes[0]], &nodes[edge.nodes[1]]);
+                let mid = (0.5 * (a.x + b.x), 0.5 * (a.y + b.y));
+                let (dx, dy) = coordinate_system.delta(tri.centroid, mid);
+                let distance = dx * edge.normal.0 + dy * edge.normal.1;
+                GhostCell {
+                    edge: e,
+                    cell: edge.left_triangle,
+                    side,
+                    offset: (
+                        2.0 * distance * edge.normal.0,
+                        2.0 * distance * edge.normal.1,
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Position of the least-squares stencil point across local edge k:
+    /// the neighbour centroid, or the edge midpoint on the boundary
+    fn stencil_point(
+        nodes: &[Node],
+        triangles: &[Triangle],
+        tri_idx: usize,
+        k: usize,
+    ) -> (f64, f64) {
+        let tri = &triangles[tri_idx];
+        match tri.neighbors[k] {
+            Some(j) => triangles[j].centroid,
+            None => {
+                let n0 = &nodes[tri.nodes[k]];
+                let n1 = &nodes[tri.nodes[(k + 1) % 3]];
+                (0.5 * (n0.x + n1.x), 0.5 * (n0.y + n1.y))
+            }
+        }
+    }
+
+    /// Inverse-distance-squared weighted least-squares gradient weights
+    /// Unlike Green-Gauss with face averages, the result is exact for linear
+    /// fields regardless of triangle shape.
+    fn compute_lsq_weights(
+        nodes: &[Node],
+        triangles: &[Triangle],
+        coordinate_system: CoordinateSystem,
+    ) -> Vec<[(f64, f64); 3]> {
+        (0..triangles.len())
+            .map(|i| {
+                let offsets: [(f64, f64); 3] = std::array::from_fn(|k| {
+                    let point = Self::stencil_point(nodes, triangles, i, k);
+                    coordinate_system.delta(triangles[i].centroid, point)
+                });
+
+                // Normal matrix M = Σ ω d dᵀ with ω = 1 / |d|²
+                let (mut a, mut b, mut c) = (0.0, 0.0, 0.0);
+                for (dx, dy) in offsets {
+                    let omega = 1.0 / (dx * dx + dy * dy);
+                    a += omega * dx * dx;
+                    b += omega * dx * dy;
+                    c += omega * dy * dy;
+                }
+                let det = a * c - b * b;
+
+                offsets.map(|(dx, dy)| {
+                    let omega = 1.0 / (dx * dx + dy * dy);
+                    (
+                        omega * (c * dx - b * dy) / det,
+                        omega * (a * dy - b * dx) / det,
+                    )
+                })
+            })
+            .collect()
+    }
+
     /// Compute topography/bathymetry at a given point
     fn compute_topography(x: f64, y: f64, topo: TopographyType) -> f64 {
         match topo {
@@ -397,6 +732,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_from_triangles_matches_structured_mesh() {
+        let mesh = TriangularMesh::new_rectangular(5, 4, 8.0, 6.0, TopographyType::Flat);
+        // Give every other triangle clockwise
+        let connectivity: Vec<[usize; 3]> = mesh
+            .triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.nodes;
+                if t.id % 2 == 0 {
+                    [a, c, b]
+                } else {
+                    [a, b, c]
+                }
+            })
+            .collect();
+        let built = TriangularMesh::from_triangles(
+            mesh.nodes.clone(),
+            &connectivity,
+            CoordinateSystem::Cartesian,
+        )
+        .unwrap();
+        assert_eq!(built.edges.len(), mesh.edges.len());
+        for (a, b) in built.triangles.iter().zip(&mesh.triangles) {
+            assert_eq!(a.nodes, b.nodes);
+            assert_eq!(a.neighbors, b.neighbors);
+            assert!((a.area - b.area).abs() < 1e-12);
+        }
+
+        // A triangle below the bottom boundary, then a third one on its edge
+        let mut nodes = mesh.nodes.clone();
+        nodes.push(Node {
+            x: 1.0,
+            y: -1.0,
+            z: 0.0,
+        });
+        let mut extended = connectivity.clone();
+        extended.push([0, 1, 20]);
+        assert!(TriangularMesh::from_triangles(
+            nodes.clone(),
+            &extended,
+            CoordinateSystem::Cartesian
+        )
+        .is_ok());
+        extended.push([1, 0, 20]);
+        assert!(
+            TriangularMesh::from_triangles(nodes, &extended, CoordinateSystem::Cartesian).is_err()
+        );
+        assert!(TriangularMesh::from_triangles(
+            mesh.nodes.clone(),
+            &[[0, 1, 1]],
+            CoordinateSystem::Cartesian
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_mesh_consistency() {
         let mesh = TriangularMesh::new_rectangular(5, 5, 10.0, 10.0, TopographyType::Flat);
@@ -410,4 +801,131 @@ mod tests {
         let expected_triangles = 2 * (nx - 1) * (ny - 1);
         assert_eq!(mesh.triangles.len(), expected_triangles);
     }
+
+    #[test]
+    fn test_edge_normals_point_out_of_left_triangle() {
+        let mesh = TriangularMesh::new_rectangular(6, 5, 10.0, 8.0, TopographyType::Flat);
+        for edge in &mesh.edges {
+            let (nx, ny) = edge.normal;
+            assert!((nx.hypot(ny) - 1.0).abs() < 1e-12);
+            if let Some(right) = edge.right_triangle {
+                let (lx, ly) = mesh.triangles[edge.left_triangle].centroid;
+                let (rx, ry) = mesh.triangles[right].centroid;
+                assert!((rx - lx) * nx + (ry - ly) * ny > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_geographic_mesh_area() {
+        let mesh = TriangularMesh::new_geographic(
+            21,
+            21,
+            (140.0, 30.0),
+            (10.0, 10.0),
+            TopographyType::Flat,
+            EARTH_RADIUS,
+        );
+        assert!(matches!(
+            mesh.coordinate_system,
+            CoordinateSystem::Spherical { .. }
+        ));
+
+        // Exact area of a longitude-latitude patch: R^2 dlon (sin lat2 - sin lat1)
+        let exact = EARTH_RADIUS.powi(2)
+            * 10f64.to_radians()
+            * (40f64.to_radians().sin() - 30f64.to_radians().sin());
+        let total: f64 = mesh.triangles.iter().map(|t| t.area).sum();
+        assert!(((total - exact) / exact).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_geographic_edge_lengths_shrink_poleward() {
+        let mesh = TriangularMesh::new_geographic(
+            3,
+            3,
+            (0.0, 0.0),
+            (2.0, 60.0),
+            TopographyType::Flat,
+            EARTH_RADIUS,
+        );
+        // Zonal grid lines at the equator and at 60N
+        let south = mesh.node_delta(0, 1).0;
+        let north = mesh.node_delta(6, 7).0;
+        assert!((north / south - 0.5).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_lsq_weights_exact_for_linear_fields() {
+        // Strongly stretched triangles (aspect ratio 50)
+        let mesh = TriangularMesh::new_rectangular(11, 3, 100.0, 0.2, TopographyType::Flat);
+        let f = |p: (f64, f64)| 3.0 * p.0 - 7.0 * p.1;
+
+        for (i, tri) in mesh.triangles.iter().enumerate() {
+            let (mut gx, mut gy) = (0.0, 0.0);
+            for k in 0..3 {
+                let point = TriangularMesh::stencil_point(&mesh.nodes, &mesh.triangles, i, k);
+                let (wx, wy) = mesh.lsq_weights[i][k];
+                gx += wx * (f(point) - f(tri.centroid));
+                gy += wy * (f(point) - f(tri.centroid));
+            }
+            assert!((gx - 3.0).abs() < 1e-9);
+            assert!((gy + 7.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_ghost_cells_mirror_boundary_cells() {
+        let mesh = TriangularMesh::new_rectangular(5, 4, 4.0, 3.0, TopographyType::Flat);
+        let boundary: Vec<usize> = (0..mesh.edges.len())
+            .filter(|&e| mesh.edges[e].right_triangle.is_none())
+            .collect();
+        assert_eq!(boundary.len(), 2 * (4 + 3));
+        assert_eq!(mesh.ghosts.len(), boundary.len());
+        for (g, ghost) in mesh.ghosts.iter().enumerate() {
+            let edge = &mesh.edges[ghost.edge];
+            assert_eq!(edge.ghost, Some(g));
+            assert_eq!(mesh.ghost(ghost.cell, ghost.side), Some(g));
+            // The ghost centroid lies outside, as far from the edge as the
+            // interior one
+            let c = mesh.triangles[ghost.cell].centroid;
+            let p = (c.0 + ghost.offset.0, c.1 + ghost.offset.1);
+            assert!(p.0 < 0.0 || p.0 > 4.0 || p.1 < 0.0 || p.1 > 3.0);
+            let a = &mesh.nodes[edge.nodes[0]];
+            let side = |q: (f64, f64)| (q.0 - a.x) * edge.normal.0 + (q.1 - a.y) * edge.normal.1;
+            assert!((side(p) + side(c)).abs() < 1e-12);
+        }
+        assert_eq!(
+            mesh.ghost(mesh.ghosts[0].cell, (mesh.ghosts[0].side + 1) % 3),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mesh_json_round_trip() {
+        let mesh = TriangularMesh::new_rectangular(
+            5,
+            4,
+            3.0,
+            2.0,
+            TopographyType::Gaussian {
+                center: (1.5, 1.0),
+                amplitude: 0.3,
+                width: 0.7,
+            },
+        );
+        let json = serde_json::to_string(&mesh).unwrap();
+        let restored: TriangularMesh = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.coordinate_system, mesh.coordinate_system);
+        assert_eq!(restored.lsq_weights, mesh.lsq_weights);
+        assert_eq!(restored.ghosts, mesh.ghosts);
+        for (a, b) in restored.triangles.iter().zip(&mesh.triangles) {
+            assert_eq!(
+                (a.nodes, a.neighbors, a.area, a.z_bed),
+                (b.nodes, b.neighbors, b.area, b.z_bed)
+            );
+        }
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+    }
 }
@@ -0,0 +1,238 @@
+/// Binary mesh cache
+/// Nodes and triangles (with their neighbour connectivity, the expensive part
+/// of mesh generation) are stored in a versioned little-endian format; edges
+/// and least-squares weights are rebuilt on load in linear time.
+///
+/// Layout: magic "SWEMESH\0", format version (u32), key (length-prefixed
+/// UTF-8 describing the generation parameters), coordinate system, then the
+/// node and triangle records.
+use super::{CoordinateSystem, Node, Triangle, TriangularMesh};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+const MAGIC: &[u8; 8] = b"SWEMESH\0";
+const FORMAT_VERSION: u32 = 1;
+const NO_NEIGHBOR: u64 = u64::MAX;
+
+impl TriangularMesh {
+    /// Save the mesh, tagged with `key` identifying its generation parameters
+    pub fn save_cache(&self, path: &str, key: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_cache(&mut out, key)?;
+        out.flush()
+    }
+
+    /// Load a cached mesh; `Ok(None)` if it was generated with a different key
+    pub fn load_cache(path: &str, key: &str) -> io::Result<Option<Self>> {
+        Self::read_cache(&mut BufReader::new(File::open(path)?), key)
+    }
+
+    fn write_cache<W: Write>(&self, out: &mut W, key: &str) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        write_u64(out, key.len() as u64)?;
+        out.write_all(key.as_bytes())?;
+
+        match self.coordinate_system {
+            CoordinateSystem::Cartesian => {
+                out.write_all(&[0])?;
+                write_f64(out, 0.0)?;
+            }
+            CoordinateSystem::Spherical { radius } => {
+                out.write_all(&[1])?;
+                write_f64(out, radius)?;
+            }
+        }
+
+        write_u64(out, self.nodes.len() as u64)?;
+        for node in &self.nodes {
+            for value in [node.x, node.y, node.z] {
+                write_f64(out, value)?;
+            }
+        }
+
+        write_u64(out, self.triangles.len() as u64)?;
+        for tri in &self.triangles {
+            for &n in &tri.nodes {
+                write_u64(out, n as u64)?;
+            }
+            for neighbor in tri.neighbors {
+                write_u64(out, neighbor.map_or(NO_NEIGHBOR, |j| j as u64))?;
+            }
+            for value in [tri.area, tri.centroid.0, tri.centroid.1, tri.z_bed] {
+                write_f64(out, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_cache<R: Read>(input: &mut R, key: &str) -> io::Result<Option<Self>> {
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a mesh cache file"));
+        }
+        let mut version = [0u8; 4];
+        input.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != FORMAT_VERSION {
+            return Err(invalid(&format!(
+                "unsupported mesh cache version {} (expected {})",
+                version, FORMAT_VERSION
+            )));
+        }
+
+        let mut stored_key = vec![0u8; read_len(input)?];
+        input.read_exact(&mut stored_key)?;
+        if stored_key != key.as_bytes() {
+            return Ok(None);
+        }
+
+        let mut tag = [0u8; 1];
+        input.read_exact(&mut tag)?;
+        let radius = read_f64(input)?;
+        let coordinate_system = match tag[0] {
+            0 => CoordinateSystem::Cartesian,
+            1 => CoordinateSystem::Spherical { radius },
+            other => return Err(invalid(&format!("unknown coordinate system {}", other))),
+        };
+
+        let n_nodes = read_len(input)?;
+        let nodes = (0..n_nodes)
+            .map(|_| {
+                Ok(Node {
+                    x: read_f64(input)?,
+                    y: read_f64(input)?,
+                    z: read_f64(input)?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let n_triangles = read_len(input)?;
+        let index = |value: u64, limit: usize| {
+            usize::try_from(value)
+                .ok()
+                .filter(|&i| i < limit)
+                .ok_or_else(|| invalid("index out of range"))
+        };
+        let mut triangles = Vec::with_capacity(n_triangles);
+        for id in 0..n_triangles {
+            let mut tri_nodes = [0; 3];
+            for n in &mut tri_nodes {
+                *n = index(read_u64(input)?, n_nodes)?;
+            }
+            let mut neighbors = [None; 3];
+            for neighbor in &mut neighbors {
+                let value = read_u64(input)?;
+                if value != NO_NEIGHBOR {
+                    *neighbor = Some(index(value, n_triangles)?);
+                }
+            }
+            triangles.push(Triangle {
+                id,
+                nodes: tri_nodes,
+                neighbors,
+                area: read_f64(input)?,
+                centroid: (read_f64(input)?, read_f64(input)?),
+                z_bed: read_f64(input)?,
+            });
+        }
+
+        let edges = Self::generate_edges(&nodes, &triangles, coordinate_system);
+        let lsq_weights = Self::compute_lsq_weights(&nodes, &triangles, coordinate_system);
+        let ghosts = Self::compute_ghosts(&nodes, &triangles, &edges, coordinate_system);
+        Ok(Some(TriangularMesh {
+            nodes,
+            triangles,
+            edges,
+            coordinate_system,
+            lsq_weights,
+            ghosts,
+        }))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn write_u64<W: Write>(out: &mut W, value: u64) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn write_f64<W: Write>(out: &mut W, value: f64) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64<R: Read>(input: &mut R) -> io::Result<f64> {
+    read_u64(input).map(f64::from_bits)
+}
+
+/// Element count, bounded so a corrupt file cannot trigger a huge allocation
+fn read_len<R: Read>(input: &mut R) -> io::Result<usize> {
+    let len = read_u64(input)?;
+    if len > u32::MAX as u64 {
+        return Err(invalid("implausible element count"));
+    }
+    Ok(len as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, EARTH_RADIUS};
+
+    #[test]
+    fn test_cache_round_trip() {
+        let topography = TopographyType::Gaussian {
+            center: (5.0, 5.0),
+            amplitude: 1.0,
+            width: 2.0,
+        };
+        let mesh = TriangularMesh::new_geographic(
+            6,
+            5,
+            (10.0, 40.0),
+            (2.0, 1.5),
+            topography,
+            EARTH_RADIUS,
+        );
+
+        let mut bytes = Vec::new();
+        mesh.write_cache(&mut bytes, "key-a").unwrap();
+        let loaded = TriangularMesh::read_cache(&mut bytes.as_slice(), "key-a")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(loaded.coordinate_system, mesh.coordinate_system);
+        assert_eq!(loaded.nodes.len(), mesh.nodes.len());
+        assert_eq!(loaded.edges.len(), mesh.edges.len());
+        for (a, b) in loaded.triangles.iter().zip(&mesh.triangles) {
+            assert_eq!(a.nodes, b.nodes);
+            assert_eq!(a.neighbors, b.neighbors);
+            assert_eq!(a.area, b.area);
+            assert_eq!(a.z_bed, b.z_bed);
+        }
+        assert_eq!(loaded.lsq_weights, mesh.lsq_weights);
+    }
+
+    #[test]
+    fn test_cache_rejects_other_key_and_corrupt_data() {
+        let mesh = TriangularMesh::new_rectangular(4, 4, 1.0, 1.0, TopographyType::Flat);
+        let mut bytes = Vec::new();
+        mesh.write_cache(&mut bytes, "nx=4").unwrap();
+
+        assert!(TriangularMesh::read_cache(&mut bytes.as_slice(), "nx=5")
+            .unwrap()
+            .is_none());
+        assert!(TriangularMesh::read_cache(&mut &bytes[..bytes.len() - 3], "nx=4").is_err());
+        bytes[0] = b'X';
+        assert!(TriangularMesh::read_cache(&mut bytes.as_slice(), "nx=4").is_err());
+    }
+}
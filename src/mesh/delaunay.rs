@@ -0,0 +1,203 @@
+/// Delaunay triangulation of a point set (Bowyer-Watson)
+/// Points are inserted one at a time, in a serpentine order over a grid of
+/// bins so that consecutive points are close. Each point is located by
+/// walking from the last new triangle; the triangles whose circumcircle
+/// contains it form a cavity that is replaced by a fan around the point.
+/// The triangulation starts from a large enclosing triangle whose vertices
+/// are removed at the end, so the result covers the convex hull.
+const NONE: usize = usize::MAX;
+
+struct Cell {
+    vertices: [usize; 3],  // Counter-clockwise
+    neighbors: [usize; 3], // Across the edge from vertex k to k + 1
+    alive: bool,
+}
+
+/// Triangles (counter-clockwise node index triples) of the Delaunay
+/// triangulation of `points`; points coinciding with an earlier point are
+/// left out
+pub fn delaunay(points: &[(f64, f64)]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    // Work relative to the bounding box centre for accuracy
+    let (mut lo, mut hi) = (
+        (f64::INFINITY, f64::INFINITY),
+        (f64::NEG_INFINITY, f64::NEG_INFINITY),
+    );
+    for &(x, y) in points {
+        lo = (lo.0.min(x), lo.1.min(y));
+        hi = (hi.0.max(x), hi.1.max(y));
+    }
+    let centre = (0.5 * (lo.0 + hi.0), 0.5 * (lo.1 + hi.1));
+    let extent = (hi.0 - lo.0).max(hi.1 - lo.1).max(f64::MIN_POSITIVE);
+    let mut p: Vec<(f64, f64)> = points
+        .iter()
+        .map(|&(x, y)| ((x - centre.0) / extent, (y - centre.1) / extent))
+        .collect();
+    p.extend([(-50.0, -50.0), (50.0, -50.0), (0.0, 50.0)]);
+
+    let mut cells = vec![Cell {
+        vertices: [n, n + 1, n + 2],
+        neighbors: [NONE; 3],
+        alive: true,
+    }];
+    let mut last = 0;
+    let mut bad = Vec::new();
+    let mut boundary: Vec<(usize, usize, usize)> = Vec::new(); // (a, b, outside cell)
+    let tolerance = 1e-12;
+
+    for v in insertion_order(&p[..n]) {
+        let point = p[v];
+
+        // Walk to the triangle containing the point
+        let mut t = last;
+        'walk: loop {
+            let c = &cells[t];
+            for k in 0..3 {
+                let (a, b) = (p[c.vertices[k]], p[c.vertices[(k + 1) % 3]]);
+                if orient(a, b, point) < 0.0 && c.neighbors[k] != NONE {
+                    t = c.neighbors[k];
+                    continue 'walk;
+                }
+            }
+            break;
+        }
+        if cells[t].vertices.iter().any(|&w| {
+            let q = p[w];
+            (q.0 - point.0).abs() < tolerance && (q.1 - point.1).abs() < tolerance
+        }) {
+            continue;
+        }
+
+        // Cavity of triangles whose circumcircle contains the point
+        bad.clear();
+        boundary.clear();
+        cells[t].alive = false;
+        bad.push(t);
+        let mut k = 0;
+        while k < bad.len() {
+            let b = bad[k];
+            k += 1;
+            for e in 0..3 {
+                let m = cells[b].neighbors[e];
+                let edge = (cells[b].vertices[e], cells[b].vertices[(e + 1) % 3]);
+                if m != NONE && cells[m].alive && in_circle(&cells[m], &p, point) {
+                    cells[m].alive = false;
+                    bad.push(m);
+                } else if m == NONE || cells[m].alive {
+                    boundary.push((edge.0, edge.1, m));
+                }
+            }
+        }
+
+        // Fan of new triangles around the point
+        let first = cells.len();
+        for &(a, b, outside) in &boundary {
+            let new = cells.len();
+            cells.push(Cell {
+                vertices: [a, b, v],
+                neighbors: [outside, NONE, NONE],
+                alive: true,
+            });
+            if outside != NONE {
+                let back = (0..3)
+                    .find(|&e| cells[outside].vertices[(e + 1) % 3] == a)
+                    .unwrap();
+                cells[outside].neighbors[back] = new;
+            }
+        }
+        for i in first..cells.len() {
+            let [a, b, _] = cells[i].vertices;
+            // Edge (b, v) is shared with the triangle starting at b, edge
+            // (v, a) with the triangle ending at a
+            let next = (first..cells.len())
+                .find(|&j| cells[j].vertices[0] == b)
+                .unwrap();
+            let previous = (first..cells.len())
+                .find(|&j| cells[j].vertices[1] == a)
+                .unwrap();
+            cells[i].neighbors[1] = next;
+            cells[i].neighbors[2] = previous;
+        }
+        last = first;
+    }
+
+    cells
+        .iter()
+        .filter(|c| c.alive && c.vertices.iter().all(|&w| w < n))
+        .map(|c| c.vertices)
+        .collect()
+}
+
+/// Twice the signed area of (a, b, c); positive when counter-clockwise
+fn orient(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/// Whether `d` lies strictly inside the circumcircle of the cell
+fn in_circle(cell: &Cell, p: &[(f64, f64)], d: (f64, f64)) -> bool {
+    let [a, b, c] = cell.vertices.map(|w| (p[w].0 - d.0, p[w].1 - d.1));
+    let lift = |q: (f64, f64)| q.0 * q.0 + q.1 * q.1;
+    lift(a) * (b.0 * c.1 - c.0 * b.1) - lift(b) * (a.0 * c.1 - c.0 * a.1)
+        + lift(c) * (a.0 * b.1 - b.0 * a.1)
+        > 0.0
+}
+
+/// Point indices in serpentine order over a grid of about one point per bin
+fn insertion_order(points: &[(f64, f64)]) -> Vec<usize> {
+    let bins = ((points.len() as f64).sqrt().ceil() as usize).max(1);
+    let bin = |v: f64| (((v + 0.5) * bins as f64) as usize).min(bins - 1);
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.sort_by_key(|&i| {
+        let (col, row) = (bin(points[i].0), bin(points[i].1));
+        let col = if row % 2 == 0 { col } else { bins - 1 - col };
+        (row, col)
+    });
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_circumcircles() {
+        // Jittered grid plus a duplicate point
+        let mut points: Vec<(f64, f64)> = (0..144)
+            .map(|k| {
+                let (i, j) = ((k % 12) as f64, (k / 12) as f64);
+                let jitter = ((k * 7919) % 101) as f64 / 101.0 - 0.5;
+                (i + 0.3 * jitter, j - 0.2 * jitter)
+            })
+            .collect();
+        points.push(points[17]);
+        let triangles = delaunay(&points);
+
+        // Euler: every point used once, 2n - 2 - hull triangles
+        let mut used = vec![false; points.len()];
+        triangles.iter().flatten().for_each(|&v| used[v] = true);
+        assert_eq!(used.iter().filter(|&&u| u).count(), 144);
+        assert!(!used[144]);
+        let total: f64 = triangles
+            .iter()
+            .map(|t| 0.5 * orient(points[t[0]], points[t[1]], points[t[2]]))
+            .sum();
+        assert!(total > 0.0 && triangles.len() > 2 * 144 - 2 - 4 * 12);
+
+        for t in &triangles {
+            let cell = Cell {
+                vertices: *t,
+                neighbors: [NONE; 3],
+                alive: true,
+            };
+            assert!(orient(points[t[0]], points[t[1]], points[t[2]]) > 0.0);
+            for (i, &q) in points.iter().enumerate().take(144) {
+                if !t.contains(&i) {
+                    assert!(!in_circle(&cell, &points, q), "{:?} contains {}", t, i);
+                }
+            }
+        }
+    }
+}
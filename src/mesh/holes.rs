@@ -0,0 +1,192 @@
+/// Interior holes (islands, buildings, piers)
+/// Cells whose centroid lies inside a hole polygon are removed together with
+/// nodes no longer used. The faces they shared with the remaining cells become
+/// boundary edges, so the hole outline is treated like the outer boundary.
+use super::{Triangle, TriangularMesh};
+
+/// Polygon vertices in mesh coordinates; the closing edge is implicit
+pub type Polygon = Vec<(f64, f64)>;
+
+/// Parse "x1,y1;x2,y2;x3,y3" into a polygon with at least three vertices
+pub fn parse_polygon(spec: &str) -> Result<Polygon, String> {
+    let vertices = spec
+        .split(';')
+        .map(|pair| {
+            let (x, y) = pair
+                .split_once(',')
+                .ok_or_else(|| format!("expected x,y but found '{}'", pair.trim()))?;
+            let parse = |v: &str| {
+                v.trim()
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid coordinate '{}'", v.trim()))
+            };
+            Ok((parse(x)?, parse(y)?))
+        })
+        .collect::<Result<Polygon, String>>()?;
+    if vertices.len() < 3 {
+        return Err(format!(
+            "a polygon needs at least 3 vertices, got {}",
+            vertices.len()
+        ));
+    }
+    Ok(vertices)
+}
+
+/// Walls of a dam across the domain at x = `x`, `thickness` thick, spanning
+/// `y_range` except for the breach (y_start, y_end). The walls reach one
+/// thickness past the domain so the cells along its edges are cut as well.
+pub fn dam_with_breach(
+    x: f64,
+    thickness: f64,
+    y_range: (f64, f64),
+    breach: (f64, f64),
+) -> Vec<Polygon> {
+    let (x0, x1) = (x - 0.5 * thickness, x + 0.5 * thickness);
+    let wall = |y0: f64, y1: f64| vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)];
+    let mut walls = Vec::new();
+    if breach.0 > y_range.0 {
+        walls.push(wall(y_range.0 - thickness, breach.0));
+    }
+    if breach.1 < y_range.1 {
+        walls.push(wall(breach.1, y_range.1 + thickness));
+    }
+    walls
+}
+
+/// Even-odd ray casting test
+pub fn point_in_polygon(point: (f64, f64), polygon: &[(f64, f64)]) -> bool {
+    let (x, y) = point;
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (xi, yi) = polygon[i];
+        let (xj, yj) = polygon[j];
+        if (yi > y) != (yj > y) && x < xj + (y - yi) * (xi - xj) / (yj - yi) {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+impl TriangularMesh {
+    /// Remove the cells inside any of the polygons; returns the number removed
+    pub fn cut_holes(&mut self, holes: &[Polygon]) -> usize {
+        let kept: Vec<usize> = (0..self.triangles.len())
+            .filter(|&i| {
+                let centroid = self.triangles[i].centroid;
+                !holes.iter().any(|hole| point_in_polygon(centroid, hole))
+            })
+            .collect();
+        let removed = self.triangles.len() - kept.len();
+        if removed == 0 {
+            return 0;
+        }
+
+        let mut new_cell = vec![None; self.triangles.len()];
+        for (new, &old) in kept.iter().enumerate() {
+            new_cell[old] = Some(new);
+        }
+
+        let mut new_node = vec![None; self.nodes.len()];
+        let mut nodes = Vec::new();
+        for &old in &kept {
+            for &n in &self.triangles[old].nodes {
+                if new_node[n].is_none() {
+                    new_node[n] = Some(nodes.len());
+                    nodes.push(self.nodes[n].clone());
+                }
+            }
+        }
+
+        self.triangles = kept
+            .iter()
+            .enumerate()
+            .map(|(id, &old)| {
+                let tri = &self.triangles[old];
+                Triangle {
+                    id,
+                    nodes: tri.nodes.map(|n| new_node[n].unwrap()),
+                    neighbors: tri.neighbors.map(|j| j.and_then(|j| new_cell[j])),
+                    ..tri.clone()
+                }
+            })
+            .collect();
+        self.nodes = nodes;
+        self.edges = Self::generate_edges(&self.nodes, &self.triangles, self.coordinate_system);
+        self.lsq_weights =
+            Self::compute_lsq_weights(&self.nodes, &self.triangles, self.coordinate_system);
+        self.ghosts = Self::compute_ghosts(
+            &self.nodes,
+            &self.triangles,
+            &self.edges,
+            self.coordinate_system,
+        );
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+
+    #[test]
+    fn test_parse_polygon() {
+        let polygon = parse_polygon("0,0; 2,0; 2,1.5").unwrap();
+        assert_eq!(polygon, vec![(0.0, 0.0), (2.0, 0.0), (2.0, 1.5)]);
+        assert!(parse_polygon("0,0;1,1").is_err());
+        assert!(parse_polygon("0,0;1;2,2").is_err());
+    }
+
+    #[test]
+    fn test_island_becomes_boundary() {
+        let mut mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 10.0, TopographyType::Flat);
+        let outer_boundary = mesh
+            .edges
+            .iter()
+            .filter(|e| e.right_triangle.is_none())
+            .count();
+        let island = parse_polygon("4,4;6,4;6,6;4,6").unwrap();
+
+        let removed = mesh.cut_holes(std::slice::from_ref(&island));
+        assert_eq!(removed, 8); // 2 x 2 squares, two triangles each
+
+        let area: f64 = mesh.triangles.iter().map(|t| t.area).sum();
+        assert!((area - 96.0).abs() < 1e-9);
+        assert_eq!(mesh.nodes.len(), 121 - 1); // only the island centre node is unused
+
+        // The island outline adds 8 wall edges facing into the hole
+        let boundary: Vec<_> = mesh
+            .edges
+            .iter()
+            .filter(|e| e.right_triangle.is_none())
+            .collect();
+        assert_eq!(boundary.len(), outer_boundary + 8);
+        for (i, tri) in mesh.triangles.iter().enumerate() {
+            assert_eq!(tri.id, i);
+            assert!(!point_in_polygon(tri.centroid, &island));
+            for &j in tri.neighbors.iter().flatten() {
+                assert!(mesh.triangles[j].neighbors.contains(&Some(i)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_dam_with_breach() {
+        let mut mesh = TriangularMesh::new_rectangular(21, 21, 20.0, 20.0, TopographyType::Flat);
+        let walls = dam_with_breach(10.0, 2.0, (0.0, 20.0), (9.5, 16.5));
+        assert_eq!(walls.len(), 2);
+        mesh.cut_holes(&walls);
+        // The dam column x = 9..11 keeps only the cells in the breach
+        let in_dam: Vec<_> = mesh
+            .triangles
+            .iter()
+            .filter(|t| (9.0..11.0).contains(&t.centroid.0))
+            .collect();
+        assert!(!in_dam.is_empty());
+        assert!(in_dam.iter().all(|t| (9.5..16.5).contains(&t.centroid.1)));
+        // A breach over the whole height leaves no wall
+        assert!(dam_with_breach(10.0, 2.0, (0.0, 20.0), (0.0, 20.0)).is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+/// Cell partitions and colorings for inspecting the parallel decomposition
+/// The cell loops run over contiguous index ranges split across the worker
+/// threads, so `block_partition` shows which cells each worker starts with
+/// (work stealing may move blocks later). Faces between blocks form the halo
+/// whose size grows with poor cell ordering (see `--reorder`). A greedy
+/// coloring gives classes of cells that share no face. Subdomains list the
+/// cells a part owns and the halo cells of other parts it reads, as needed
+/// when the parts live on separate devices.
+use super::TriangularMesh;
+
+/// Load balance and halo size of a partition
+#[derive(Debug, Clone, Copy)]
+pub struct PartitionStats {
+    pub parts: usize,
+    pub min_cells: usize,
+    pub max_cells: usize,
+    pub halo_faces: usize, // Interior faces between cells of different parts
+}
+
+/// Cells of one part plus the halo it needs from the other parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subdomain {
+    pub owned: Vec<usize>,
+    /// Rings of cells of other parts: ring 0 shares a face with an owned
+    /// cell, ring k with ring k-1
+    pub halo: Vec<Vec<usize>>,
+}
+
+impl TriangularMesh {
+    /// Assign cells to `parts` contiguous, equally sized index blocks
+    pub fn block_partition(&self, parts: usize) -> Vec<usize> {
+        let n = self.triangles.len();
+        let parts = parts.clamp(1, n.max(1));
+        (0..n).map(|i| i * parts / n).collect()
+    }
+
+    /// Greedy distance-1 coloring: neighbouring cells never share a color
+    pub fn greedy_coloring(&self) -> Vec<usize> {
+        let mut colors: Vec<usize> = vec![usize::MAX; self.triangles.len()];
+        for (i, tri) in self.triangles.iter().enumerate() {
+            let used: Vec<usize> = tri.neighbors.iter().flatten().map(|&j| colors[j]).collect();
+            colors[i] = (0..).find(|c| !used.contains(c)).unwrap();
+        }
+        colors
+    }
+
+    pub fn partition_stats(&self, partition: &[usize]) -> PartitionStats {
+        let parts = partition.iter().max().map_or(0, |&p| p + 1);
+        let mut sizes = vec![0; parts];
+        for &p in partition {
+            sizes[p] += 1;
+        }
+        let halo_faces = self
+            .edges
+            .iter()
+            .filter(|e| {
+                e.right_triangle
+                    .is_some_and(|r| partition[r] != partition[e.left_triangle])
+            })
+            .count();
+        PartitionStats {
+            parts,
+            min_cells: sizes.iter().copied().min().unwrap_or(0),
+            max_cells: sizes.iter().copied().max().unwrap_or(0),
+            halo_faces,
+        }
+    }
+
+    /// Owned cells and `rings` halo rings of every part (each list sorted
+    /// by cell index)
+    pub fn subdomains(&self, partition: &[usize], rings: usize) -> Vec<Subdomain> {
+        let parts = partition.iter().max().map_or(0, |&p| p + 1);
+        let mut subdomains = vec![
+            Subdomain {
+                owned: Vec::new(),
+                halo: Vec::new(),
+            };
+            parts
+        ];
+        for (i, &p) in partition.iter().enumerate() {
+            subdomains[p].owned.push(i);
+        }
+        let mut seen = vec![usize::MAX; self.triangles.len()]; // Part that listed a cell last
+        for (p, subdomain) in subdomains.iter_mut().enumerate() {
+            for &i in &subdomain.owned {
+                seen[i] = p;
+            }
+            let mut inner = subdomain.owned.clone();
+            for _ in 0..rings {
+                let mut ring = Vec::new();
+                for &i in &inner {
+                    for &j in self.triangles[i].neighbors.iter().flatten() {
+                        if seen[j] != p {
+                            seen[j] = p;
+                            ring.push(j);
+                        }
+                    }
+                }
+                ring.sort_unstable();
+                subdomain.halo.push(ring.clone());
+                inner = ring;
+            }
+        }
+        subdomains
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh::{TopographyType, TriangularMesh};
+
+    #[test]
+    fn test_block_partition_and_coloring() {
+        let mesh = TriangularMesh::new_rectangular(9, 9, 10.0, 10.0, TopographyType::Flat);
+        let partition = mesh.block_partition(4);
+        let stats = mesh.partition_stats(&partition);
+        assert_eq!(stats.parts, 4);
+        assert_eq!((stats.min_cells, stats.max_cells), (32, 32));
+        assert!(stats.halo_faces > 0);
+        assert!(partition.windows(2).all(|w| w[0] <= w[1]));
+
+        let colors = mesh.greedy_coloring();
+        for (i, tri) in mesh.triangles.iter().enumerate() {
+            for &j in tri.neighbors.iter().flatten() {
+                assert_ne!(colors[i], colors[j]);
+            }
+        }
+        assert!(colors.iter().max().unwrap() < &4);
+    }
+
+    #[test]
+    fn test_subdomains_cover_mesh_with_halos() {
+        let mesh = TriangularMesh::new_rectangular(9, 9, 10.0, 10.0, TopographyType::Flat);
+        let partition = mesh.block_partition(3);
+        let subdomains = mesh.subdomains(&partition, 2);
+        assert_eq!(subdomains.len(), 3);
+
+        let mut owned: Vec<usize> = subdomains.iter().flat_map(|s| s.owned.clone()).collect();
+        owned.sort();
+        assert_eq!(owned, (0..mesh.triangles.len()).collect::<Vec<_>>());
+
+        for (p, subdomain) in subdomains.iter().enumerate() {
+            let [first, second] = [&subdomain.halo[0], &subdomain.halo[1]];
+            assert!(!first.is_empty() && !second.is_empty());
+            for &h in first.iter().chain(second) {
+                assert_ne!(partition[h], p);
+            }
+            for &h in first {
+                let neighbours = mesh.triangles[h].neighbors.iter().flatten();
+                assert!(neighbours.clone().any(|&j| partition[j] == p));
+            }
+            // Every neighbour of an owned or first-ring cell is available locally
+            let local =
+                |j: &usize| subdomain.owned.contains(j) || first.contains(j) || second.contains(j);
+            for &i in subdomain.owned.iter().chain(first) {
+                assert!(mesh.triangles[i].neighbors.iter().flatten().all(local));
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+/// Cache-friendly renumbering of cells and nodes
+/// Reverse Cuthill-McKee on the cell adjacency graph places face neighbours
+/// close together in memory; nodes are then numbered in order of first use by
+/// the renumbered cells, and edges are regenerated so that edge loops also
+/// traverse cells in order.
+use super::{Triangle, TriangularMesh};
+use std::collections::VecDeque;
+
+/// Bandwidths before and after renumbering
+#[derive(Debug, Clone, Copy)]
+pub struct ReorderReport {
+    pub cell_bandwidth: (usize, usize), // max |i - j| over face neighbours
+    pub node_bandwidth: (usize, usize), // max node index spread within a cell
+    pub applied: bool,                  // false if the original order was kept
+}
+
+impl TriangularMesh {
+    /// Renumber with reverse Cuthill-McKee; the original numbering is kept if
+    /// it already has a smaller cell bandwidth
+    pub fn reorder_rcm(&mut self) -> ReorderReport {
+        let before = (self.cell_bandwidth(), self.node_bandwidth());
+        let order = reverse_cuthill_mckee(&self.triangles);
+
+        let mut reordered = self.clone();
+        reordered.permute(&order);
+        let after = (reordered.cell_bandwidth(), reordered.node_bandwidth());
+        let applied = after.0 < before.0;
+        if applied {
+            *self = reordered;
+        }
+
+        ReorderReport {
+            cell_bandwidth: (before.0, if applied { after.0 } else { before.0 }),
+            node_bandwidth: (before.1, if applied { after.1 } else { before.1 }),
+            applied,
+        }
+    }
+
+    /// Largest index distance between face-neighbouring cells
+    pub fn cell_bandwidth(&self) -> usize {
+        self.triangles
+            .iter()
+            .enumerate()
+            .flat_map(|(i, tri)| tri.neighbors.iter().flatten().map(move |&j| i.abs_diff(j)))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Largest index distance between nodes of the same cell
+    pub fn node_bandwidth(&self) -> usize {
+        self.triangles
+            .iter()
+            .map(|tri| {
+                let max = tri.nodes.iter().max().unwrap();
+                let min = tri.nodes.iter().min().unwrap();
+                max - min
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Apply a new cell order (`order[new] = old`) and renumber nodes by first use
+    fn permute(&mut self, order: &[usize]) {
+        let mut new_cell = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_cell[old] = new;
+        }
+
+        let mut new_node = vec![usize::MAX; self.nodes.len()];
+        let mut node_order = Vec::with_capacity(self.nodes.len());
+        for &old in order {
+            for &n in &self.triangles[old].nodes {
+                if new_node[n] == usize::MAX {
+                    new_node[n] = node_order.len();
+                    node_order.push(n);
+                }
+            }
+        }
+        // Nodes not referenced by any cell keep their relative order at the end
+        for (n, new) in new_node.iter_mut().enumerate() {
+            if *new == usize::MAX {
+                *new = node_order.len();
+                node_order.push(n);
+            }
+        }
+
+        self.nodes = node_order.iter().map(|&n| self.nodes[n].clone()).collect();
+        self.triangles = order
+            .iter()
+            .enumerate()
+            .map(|(new, &old)| {
+                let tri = &self.triangles[old];
+                Triangle {
+                    id: new,
+                    nodes: tri.nodes.map(|n| new_node[n]),
+                    neighbors: tri.neighbors.map(|j| j.map(|j| new_cell[j])),
+                    ..tri.clone()
+                }
+            })
+            .collect();
+        self.edges = Self::generate_edges(&self.nodes, &self.triangles, self.coordinate_system);
+        self.lsq_weights =
+            Self::compute_lsq_weights(&self.nodes, &self.triangles, self.coordinate_system);
+        self.ghosts = Self::compute_ghosts(
+            &self.nodes,
+            &self.triangles,
+            &self.edges,
+            self.coordinate_system,
+        );
+    }
+}
+
+fn degree(tri: &Triangle) -> usize {
+    tri.neighbors.iter().flatten().count()
+}
+
+/// Breadth-first traversal from `start`, visiting neighbours by increasing
+/// degree; returns the visit order and the level of every visited cell
+fn cuthill_mckee_levels(
+    triangles: &[Triangle],
+    start: usize,
+    visited: &mut [bool],
+) -> (Vec<usize>, Vec<usize>) {
+    let mut order = Vec::new();
+    let mut levels = Vec::new();
+    let mut queue = VecDeque::from([(start, 0)]);
+    visited[start] = true;
+
+    while let Some((i, level)) = queue.pop_front() {
+        order.push(i);
+        levels.push(level);
+        let mut next: Vec<usize> = triangles[i]
+            .neighbors
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&j| !visited[j])
+            .collect();
+        next.sort_by_key(|&j| (degree(&triangles[j]), j));
+        for j in next {
+            visited[j] = true;
+            queue.push_back((j, level + 1));
+        }
+    }
+    (order, levels)
+}
+
+/// Pseudo-peripheral start cell (George-Liu): repeatedly restart from a
+/// minimum-degree cell of the last BFS level while the eccentricity grows
+fn pseudo_peripheral(triangles: &[Triangle], start: usize, visited: &[bool]) -> usize {
+    let mut root = start;
+    let mut eccentricity = 0;
+    loop {
+        let mut scratch = visited.to_vec();
+        let (order, levels) = cuthill_mckee_levels(triangles, root, &mut scratch);
+        let depth = *levels.last().unwrap();
+        if depth <= eccentricity {
+            return root;
+        }
+        eccentricity = depth;
+        root = order
+            .iter()
+            .zip(&levels)
+            .filter(|(_, &level)| level == depth)
+            .map(|(&i, _)| i)
+            .min_by_key(|&i| (degree(&triangles[i]), i))
+            .unwrap();
+    }
+}
+
+/// Reverse Cuthill-McKee cell order (`order[new] = old`), component by component
+fn reverse_cuthill_mckee(triangles: &[Triangle]) -> Vec<usize> {
+    let mut visited = vec![false; triangles.len()];
+    let mut order = Vec::with_capacity(triangles.len());
+
+    while let Some(seed) = (0..triangles.len())
+        .filter(|&i| !visited[i])
+        .min_by_key(|&i| (degree(&triangles[i]), i))
+    {
+        let start = pseudo_peripheral(triangles, seed, &visited);
+        let (component, _) = cuthill_mckee_levels(triangles, start, &mut visited);
+        order.extend(component);
+    }
+
+    order.reverse();
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+
+    /// Deterministic scramble of the cell order
+    fn scrambled(mesh: &mut TriangularMesh) {
+        let n = mesh.triangles.len();
+        let order: Vec<usize> = (0..n).map(|i| (i * 7919) % n).collect();
+        mesh.permute(&order);
+    }
+
+    #[test]
+    fn test_rcm_reduces_bandwidth_of_scrambled_mesh() {
+        let mut mesh = TriangularMesh::new_rectangular(12, 9, 10.0, 10.0, TopographyType::Flat);
+        scrambled(&mut mesh);
+        let total_area: f64 = mesh.triangles.iter().map(|t| t.area).sum();
+
+        let report = mesh.reorder_rcm();
+        assert!(report.applied);
+        assert!(report.cell_bandwidth.1 < report.cell_bandwidth.0 / 4);
+        assert!(report.node_bandwidth.1 < report.node_bandwidth.0);
+        assert_eq!(report.cell_bandwidth.1, mesh.cell_bandwidth());
+
+        // Connectivity stays consistent after renumbering
+        for (i, tri) in mesh.triangles.iter().enumerate() {
+            assert_eq!(tri.id, i);
+            for &j in tri.neighbors.iter().flatten() {
+                assert!(mesh.triangles[j].neighbors.contains(&Some(i)));
+            }
+        }
+        let reordered_area: f64 = mesh.triangles.iter().map(|t| t.area).sum();
+        assert!((reordered_area - total_area).abs() < 1e-9);
+        assert_eq!(mesh.edges.len(), 11 * 8 * 3 + 11 + 8);
+    }
+
+    #[test]
+    fn test_rcm_is_a_permutation() {
+        let mesh = TriangularMesh::new_rectangular(6, 5, 10.0, 10.0, TopographyType::Flat);
+        let mut order = reverse_cuthill_mckee(&mesh.triangles);
+        order.sort_unstable();
+        assert_eq!(order, (0..mesh.triangles.len()).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,207 @@
+/// Point location on the mesh
+/// Triangles are binned by their bounding boxes on a uniform grid with
+/// about one bin per triangle, so a query tests only the few triangles
+/// registered in the bin of the point. Nearest-cell queries search rings of
+/// bins outwards until no unvisited bin can hold a closer centroid.
+/// Coordinates are node coordinates (degrees on spherical meshes).
+use super::{CoordinateSystem, TriangularMesh};
+
+/// Tolerance on barycentric coordinates for points on shared edges
+const EDGE_TOLERANCE: f64 = 1e-12;
+
+/// Uniform bin grid over the mesh bounding box
+#[derive(Debug, Clone)]
+pub struct PointLocator {
+    origin: (f64, f64),
+    bin_size: (f64, f64),
+    bins_x: usize,
+    bins_y: usize,
+    bins: Vec<Vec<usize>>, // Triangles overlapping each bin (row-major)
+    ring_width: f64,       // Smallest bin side in metres
+}
+
+impl PointLocator {
+    pub fn new(mesh: &TriangularMesh) -> Self {
+        let (mut x_min, mut x_max) = (f64::INFINITY, f64::NEG_INFINITY);
+        let (mut y_min, mut y_max) = (f64::INFINITY, f64::NEG_INFINITY);
+        for node in &mesh.nodes {
+            x_min = x_min.min(node.x);
+            x_max = x_max.max(node.x);
+            y_min = y_min.min(node.y);
+            y_max = y_max.max(node.y);
+        }
+        let width = (x_max - x_min).max(f64::MIN_POSITIVE);
+        let height = (y_max - y_min).max(f64::MIN_POSITIVE);
+
+        // About one bin per triangle with roughly square bins
+        let n = mesh.triangles.len().max(1) as f64;
+        let bins_x = ((n * width / height).sqrt().ceil() as usize).clamp(1, 4096);
+        let bins_y = ((n / bins_x as f64).ceil() as usize).clamp(1, 4096);
+
+        let bin_size = (width / bins_x as f64, height / bins_y as f64);
+        // The zonal metric is smallest at the latitude furthest from the equator
+        let lat = match mesh.coordinate_system {
+            CoordinateSystem::Cartesian => 0.0,
+            CoordinateSystem::Spherical { .. } => y_min.abs().max(y_max.abs()).min(90.0),
+        };
+        let (dx, dy) = mesh.coordinate_system.delta_at((0.0, 0.0), bin_size, lat);
+
+        let mut locator = PointLocator {
+            origin: (x_min, y_min),
+            bin_size,
+            bins_x,
+            bins_y,
+            bins: vec![Vec::new(); bins_x * bins_y],
+            ring_width: dx.abs().min(dy.abs()),
+        };
+        for (i, tri) in mesh.triangles.iter().enumerate() {
+            let xs = tri.nodes.map(|n| mesh.nodes[n].x);
+            let ys = tri.nodes.map(|n| mesh.nodes[n].y);
+            let (c0, r0) = locator.bin_of(
+                xs.iter().copied().fold(f64::INFINITY, f64::min),
+                ys.iter().copied().fold(f64::INFINITY, f64::min),
+            );
+            let (c1, r1) = locator.bin_of(
+                xs.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                ys.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            );
+            for row in r0..=r1 {
+                for col in c0..=c1 {
+                    locator.bins[row * bins_x + col].push(i);
+                }
+            }
+        }
+        locator
+    }
+
+    /// Bin (column, row) of a point, clamped to the grid
+    fn bin_of(&self, x: f64, y: f64) -> (usize, usize) {
+        let col = ((x - self.origin.0) / self.bin_size.0).floor();
+        let row = ((y - self.origin.1) / self.bin_size.1).floor();
+        (
+            (col.max(0.0) as usize).min(self.bins_x - 1),
+            (row.max(0.0) as usize).min(self.bins_y - 1),
+        )
+    }
+
+    /// Triangle containing (x, y), None outside the mesh
+    pub fn locate(&self, mesh: &TriangularMesh, x: f64, y: f64) -> Option<usize> {
+        if !(x.is_finite() && y.is_finite()) {
+            return None;
+        }
+        let (col, row) = self.bin_of(x, y);
+        self.bins[row * self.bins_x + col]
+            .iter()
+            .copied()
+            .find(|&i| barycentric(mesh, i, (x, y)).is_some())
+    }
+
+    /// Triangle whose centroid is closest to (x, y) in metres (the first one
+    /// on ties), None on an empty mesh
+    pub fn nearest(&self, mesh: &TriangularMesh, x: f64, y: f64) -> Option<usize> {
+        if !(x.is_finite() && y.is_finite()) {
+            return None;
+        }
+        let distance = |i: usize| {
+            let (dx, dy) = mesh
+                .coordinate_system
+                .delta((x, y), mesh.triangles[i].centroid);
+            dx * dx + dy * dy
+        };
+        let (col, row) = self.bin_of(x, y);
+        let mut best: Option<(f64, usize)> = None;
+        for ring in 0..=self.bins_x.max(self.bins_y) {
+            // Centroids in this ring and beyond are at least this far away
+            let reach = ring.saturating_sub(1) as f64 * self.ring_width;
+            if best.is_some_and(|(d, _)| d < reach * reach) {
+                break;
+            }
+            let rows = row.saturating_sub(ring)..=(row + ring).min(self.bins_y - 1);
+            for r in rows {
+                let cols = col.saturating_sub(ring)..=(col + ring).min(self.bins_x - 1);
+                for c in cols {
+                    if r.abs_diff(row).max(c.abs_diff(col)) != ring {
+                        continue;
+                    }
+                    for &i in &self.bins[r * self.bins_x + c] {
+                        let candidate = (distance(i), i);
+                        if best.is_none_or(|b| candidate < b) {
+                            best = Some(candidate);
+                        }
+                    }
+                }
+            }
+        }
+        best.map(|(_, i)| i)
+    }
+}
+
+/// Barycentric coordinates of `point` in triangle `tri_idx` when it lies
+/// inside (with a small tolerance on the edges)
+pub fn barycentric(mesh: &TriangularMesh, tri_idx: usize, point: (f64, f64)) -> Option<[f64; 3]> {
+    let p = mesh.triangles[tri_idx]
+        .nodes
+        .map(|n| (mesh.nodes[n].x, mesh.nodes[n].y));
+    let det = (p[1].0 - p[0].0) * (p[2].1 - p[0].1) - (p[2].0 - p[0].0) * (p[1].1 - p[0].1);
+    if det.abs() < 1e-300 {
+        return None;
+    }
+    let (x, y) = point;
+    let l1 = ((x - p[0].0) * (p[2].1 - p[0].1) - (p[2].0 - p[0].0) * (y - p[0].1)) / det;
+    let l2 = ((p[1].0 - p[0].0) * (y - p[0].1) - (x - p[0].0) * (p[1].1 - p[0].1)) / det;
+    let l0 = 1.0 - l1 - l2;
+    (l0 >= -EDGE_TOLERANCE && l1 >= -EDGE_TOLERANCE && l2 >= -EDGE_TOLERANCE)
+        .then_some([l0, l1, l2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{Polygon, TopographyType};
+
+    #[test]
+    fn test_locate_matches_linear_scan() {
+        let mut mesh = TriangularMesh::new_rectangular(13, 7, 60.0, 20.0, TopographyType::Flat);
+        let hole: Polygon = vec![(20.0, 5.0), (35.0, 5.0), (35.0, 15.0), (20.0, 15.0)];
+        mesh.cut_holes(&[hole]);
+        let locator = PointLocator::new(&mesh);
+
+        for k in 0..400 {
+            let (x, y) = (-2.0 + 0.161 * k as f64, -1.0 + (k as f64 * 0.37) % 22.0);
+            let found = locator.locate(&mesh, x, y);
+            let scan = (0..mesh.triangles.len()).find(|&i| barycentric(&mesh, i, (x, y)).is_some());
+            assert_eq!(found.is_some(), scan.is_some(), "({}, {})", x, y);
+            if let Some(i) = found {
+                assert!(barycentric(&mesh, i, (x, y)).is_some());
+            }
+        }
+        assert_eq!(locator.locate(&mesh, 27.0, 10.0), None);
+        assert_eq!(locator.locate(&mesh, f64::NAN, 10.0), None);
+    }
+
+    #[test]
+    fn test_nearest_matches_linear_scan() {
+        let mut mesh = TriangularMesh::new_rectangular(13, 7, 60.0, 20.0, TopographyType::Flat);
+        let hole: Polygon = vec![(20.0, 5.0), (35.0, 5.0), (35.0, 15.0), (20.0, 15.0)];
+        mesh.cut_holes(&[hole]);
+        let locator = PointLocator::new(&mesh);
+        let distance = |i: usize, (x, y): (f64, f64)| {
+            let (cx, cy) = mesh.triangles[i].centroid;
+            (cx - x).powi(2) + (cy - y).powi(2)
+        };
+
+        // Inside, in the hole and well outside the mesh
+        for k in 0..400 {
+            let point = (-30.0 + 0.3 * k as f64, -15.0 + (k as f64 * 0.77) % 50.0);
+            let scan = (0..mesh.triangles.len())
+                .min_by(|&a, &b| distance(a, point).total_cmp(&distance(b, point)));
+            assert_eq!(
+                locator.nearest(&mesh, point.0, point.1),
+                scan,
+                "{:?}",
+                point
+            );
+        }
+        assert_eq!(locator.nearest(&mesh, f64::NAN, 10.0), None);
+    }
+}
@@ -0,0 +1,252 @@
+/// Prometheus metrics endpoint for long runs
+/// `GET /metrics` returns the run's progress in the Prometheus text format
+/// (version 0.0.4), so forecast jobs can be scraped and alerted on like any
+/// other service. The time loop only stores a few numbers per step; the text
+/// is rendered by a background thread when a scrape arrives.
+use crate::memory;
+use crate::simulation::{Observer, StepEvent};
+use crate::solver::ShallowWaterSolver;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct State {
+    started: Instant,
+    cells: usize,
+    final_time: f64,
+    steps: usize,
+    time: f64,
+    dt: f64,
+    mass_error: f64,
+    steps_per_second: Option<f64>, // Over the last completed output interval
+    rate_start: (Instant, usize),  // Start of the current steps/s interval
+    finished: bool,
+}
+
+pub struct MetricsExporter {
+    address: SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl MetricsExporter {
+    /// Bind `address` (e.g. "0.0.0.0:9090") and start answering scrapes
+    pub fn start(
+        address: &str,
+        solver: &ShallowWaterSolver,
+        final_time: f64,
+    ) -> Result<Self, String> {
+        let listener = TcpListener::bind(address).map_err(|e| format!("{}: {}", address, e))?;
+        let address = listener.local_addr().map_err(|e| e.to_string())?;
+        let now = Instant::now();
+        let state = Arc::new(Mutex::new(State {
+            started: now,
+            cells: solver.mesh.triangles.len(),
+            final_time,
+            steps: 0,
+            time: solver.time,
+            dt: solver.dt,
+            mass_error: 0.0,
+            steps_per_second: None,
+            rate_start: (now, 0),
+            finished: false,
+        }));
+
+        let scrape_state = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = handle_scrape(stream, &scrape_state) {
+                    eprintln!("Warning: metrics scrape failed: {}", e);
+                }
+            }
+        });
+
+        Ok(MetricsExporter { address, state })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Progress after a time step
+    pub fn record_step(&self, solver: &ShallowWaterSolver, steps: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.steps = steps;
+        state.time = solver.time;
+        state.dt = solver.dt;
+    }
+
+    /// Mass error (%) at an output time; also closes the interval over which
+    /// the step rate is measured
+    pub fn record_output(&self, mass_error: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.mass_error = mass_error;
+        let (since, steps) = state.rate_start;
+        let elapsed = since.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            state.steps_per_second = Some((state.steps - steps) as f64 / elapsed);
+        }
+        state.rate_start = (Instant::now(), state.steps);
+    }
+
+    /// Mark the run as complete; scrapes keep working until the process exits
+    pub fn finish(&self) {
+        self.state.lock().unwrap().finished = true;
+    }
+}
+
+impl Observer for MetricsExporter {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        self.record_step(solver, event.step);
+        if event.common {
+            self.record_output(event.mass_error(solver));
+        }
+    }
+}
+
+fn handle_scrape(mut stream: TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    stream.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+    stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+    // The request may arrive in several segments; read up to the blank line
+    let mut head = [0u8; 4096];
+    let mut n = 0;
+    while n < head.len() && !head[..n].windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut head[n..])? {
+            0 => break,
+            read => n += read,
+        }
+    }
+    let request = String::from_utf8_lossy(&head[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let (status, content_type, body) = match path {
+        "/metrics" => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            render(&state.lock().unwrap()),
+        ),
+        _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Prometheus text exposition of the current state
+fn render(state: &State) -> String {
+    let mut text = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+        writeln!(text, "# HELP {} {}", name, help).unwrap();
+        writeln!(text, "# TYPE {} {}", name, kind).unwrap();
+        writeln!(text, "{} {}", name, value).unwrap();
+    };
+    metric(
+        "swe_steps_total",
+        "counter",
+        "Time steps taken",
+        state.steps as f64,
+    );
+    metric(
+        "swe_steps_per_second",
+        "gauge",
+        "Time steps per wall-clock second over the last output interval",
+        state.steps_per_second.unwrap_or_else(|| {
+            // Until the first output time, the rate so far
+            let (since, steps) = state.rate_start;
+            (state.steps - steps) as f64 / since.elapsed().as_secs_f64().max(1e-9)
+        }),
+    );
+    metric(
+        "swe_simulated_time_seconds",
+        "gauge",
+        "Simulated time reached",
+        state.time,
+    );
+    metric(
+        "swe_final_time_seconds",
+        "gauge",
+        "Simulated time at which the run ends",
+        state.final_time,
+    );
+    metric(
+        "swe_time_step_seconds",
+        "gauge",
+        "Size of the last time step",
+        state.dt,
+    );
+    metric(
+        "swe_mass_error_percent",
+        "gauge",
+        "Relative change of the total mass at the last output time",
+        state.mass_error,
+    );
+    metric(
+        "swe_wall_time_seconds",
+        "counter",
+        "Wall-clock time since the run started",
+        state.started.elapsed().as_secs_f64(),
+    );
+    metric(
+        "swe_cells",
+        "gauge",
+        "Number of mesh cells",
+        state.cells as f64,
+    );
+    metric(
+        "swe_run_finished",
+        "gauge",
+        "1 once the run has completed",
+        if state.finished { 1.0 } else { 0.0 },
+    );
+    if let Some(bytes) = memory::resident_memory() {
+        metric(
+            "swe_resident_memory_bytes",
+            "gauge",
+            "Resident memory of the solver process",
+            bytes as f64,
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_scrape_reports_progress() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 4.0, 4.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(1.0);
+        let exporter = MetricsExporter::start("127.0.0.1:0", &solver, 60.0).unwrap();
+        solver.step();
+        exporter.record_step(&solver, 1);
+        exporter.record_output(1e-10);
+
+        let scrape = |path: &str| {
+            let mut http = TcpStream::connect(exporter.address()).unwrap();
+            write!(http, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            http.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = scrape("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("# TYPE swe_steps_total counter\nswe_steps_total 1\n"));
+        assert!(response.contains(&format!("swe_time_step_seconds {}\n", solver.dt)));
+        assert!(response.contains("swe_mass_error_percent 0.0000000001\n"));
+        assert!(response.contains("swe_cells 32\n"));
+        assert!(response.contains("swe_run_finished 0\n"));
+        assert!(scrape("/").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,345 @@
+/// Model description a solver is built from
+/// A `ModelConfig` holds what the command line's grid, friction, scheme and
+/// initial-condition options describe, and `build` returns the solver of
+/// the model at its initial state. `Simulation::new` builds its solver this
+/// way. The command line, which also reads meshes from files, sets up the
+/// numerics on its mesh with `solver` and applies the initial condition
+/// with `set_initial_condition`; the C interface creates its solvers with
+/// `solver` as well.
+///
+/// ```json
+/// {
+///   "grid": { "nx": 101, "ny": 21, "width": 10, "height": 2 },
+///   "friction": { "manning": { "coefficient": 0.03 } },
+///   "numerics": { "flux_scheme": "central_upwind" },
+///   "initial": { "condition": "gaussian-hump", "amplitude": 0.2 }
+/// }
+/// ```
+use crate::config::{InitialConfig, PhysicsConfig, TimestepConfig};
+use crate::mesh::{Polygon, TopographyType, TriangularMesh};
+use crate::okada::FaultParameters;
+use crate::solver::{
+    BedSlopeLimit, Discretization, FluxScheme, FrictionLaw, GradientMethod, Perturbation,
+    Precision, ShallowWaterSolver, SourceSplitting, TimeIntegrator,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelConfig {
+    pub grid: GridConfig,
+    pub cfl: f64,
+    pub friction: FrictionLaw,
+    pub numerics: NumericsConfig,
+    pub physics: PhysicsConfig,
+    pub timestep: TimestepConfig,
+    pub initial: InitialConfig,
+    /// Still level of the hump, solitary wave and Okada conditions (m)
+    pub sea_level: f64,
+    /// Source of the Okada condition
+    pub fault: Option<FaultParameters>,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        ModelConfig {
+            grid: GridConfig::default(),
+            cfl: 0.45,
+            friction: FrictionLaw::None,
+            numerics: NumericsConfig::default(),
+            physics: PhysicsConfig::default(),
+            timestep: TimestepConfig::default(),
+            initial: InitialConfig::default(),
+            sea_level: 1.0,
+            fault: None,
+        }
+    }
+}
+
+/// Structured grid of triangles on [0, width] × [0, height]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GridConfig {
+    pub nx: usize,   // Nodes along x
+    pub ny: usize,   // Nodes along y
+    pub width: f64,  // m
+    pub height: f64, // m
+    pub topography: TopographyType,
+    /// Interior hole polygons cut from the grid
+    pub holes: Vec<Polygon>,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        GridConfig {
+            nx: 40,
+            ny: 40,
+            width: 10.0,
+            height: 10.0,
+            topography: TopographyType::Flat,
+            holes: Vec::new(),
+        }
+    }
+}
+
+impl GridConfig {
+    /// The 2 (nx - 1)(ny - 1) triangles of the grid, less those in holes
+    pub fn mesh(&self) -> Result<TriangularMesh, String> {
+        if self.nx < 2 || self.ny < 2 {
+            return Err(format!(
+                "the grid needs at least 2 x 2 nodes, got {} x {}",
+                self.nx, self.ny
+            ));
+        }
+        if !(self.width > 0.0 && self.height > 0.0 && (self.width * self.height).is_finite()) {
+            return Err(format!(
+                "the domain size must be positive, got {} x {}",
+                self.width, self.height
+            ));
+        }
+        let mut mesh = TriangularMesh::new_rectangular(
+            self.nx,
+            self.ny,
+            self.width,
+            self.height,
+            self.topography,
+        );
+        if !self.holes.is_empty() {
+            mesh.cut_holes(&self.holes);
+        }
+        Ok(mesh)
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (self.width / 2.0, self.height / 2.0)
+    }
+}
+
+/// Numerical scheme; the defaults are those of a new solver
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NumericsConfig {
+    pub flux_scheme: FluxScheme,
+    pub gradient_method: GradientMethod,
+    pub time_integrator: TimeIntegrator,
+    pub precision: Precision,
+    pub discretization: Discretization,
+    pub bed_slope_limit: Option<BedSlopeLimit>,
+    pub track_wet_region: bool,
+    pub source_splitting: Option<SourceSplitting>,
+}
+
+impl Default for NumericsConfig {
+    fn default() -> Self {
+        NumericsConfig {
+            flux_scheme: FluxScheme::Rusanov,
+            gradient_method: GradientMethod::GreenGauss,
+            time_integrator: TimeIntegrator::RungeKutta2,
+            precision: Precision::Double,
+            discretization: Discretization::FiniteVolume,
+            bed_slope_limit: None,
+            track_wet_region: false,
+            source_splitting: None,
+        }
+    }
+}
+
+impl NumericsConfig {
+    /// Set up `solver` with this scheme, rejecting combinations it cannot run
+    pub fn apply(&self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        solver.flux_scheme = self.flux_scheme;
+        solver.gradient_method = self.gradient_method;
+        solver.bed_slope_limit = self.bed_slope_limit;
+        match self.time_integrator {
+            TimeIntegrator::SemiImplicit { theta, .. } if !(0.5..=1.0).contains(&theta) => {
+                return Err(format!("theta must be in [0.5, 1], got {}", theta));
+            }
+            TimeIntegrator::Imex { viscosity } if viscosity < 0.0 || viscosity.is_nan() => {
+                return Err(format!(
+                    "the eddy viscosity must not be negative, got {}",
+                    viscosity
+                ));
+            }
+            _ => {}
+        }
+        solver.time_integrator = self.time_integrator;
+        if matches!(self.time_integrator, TimeIntegrator::SemiImplicit { .. }) {
+            if let Some(feature) = solver.semi_implicit_unsupported() {
+                return Err(format!(
+                    "the semi-implicit integrator does not support {}",
+                    feature
+                ));
+            }
+        }
+        if self.precision == Precision::Single {
+            solver.precision = Precision::Single;
+            if let Some(feature) = solver.single_precision_unsupported() {
+                return Err(format!("single precision does not support {}", feature));
+            }
+        }
+        if self.discretization != solver.discretization {
+            solver.set_discretization(self.discretization)?;
+        }
+        if self.track_wet_region {
+            if solver.flux_scheme != FluxScheme::Rusanov
+                || solver.discretization != Discretization::FiniteVolume
+                || !matches!(solver.time_integrator, TimeIntegrator::RungeKutta2)
+                || solver.precision != Precision::Double
+            {
+                return Err(
+                    "wet-region tracking requires the Rusanov flux, finite volumes, RK2 and double precision"
+                        .to_string(),
+                );
+            }
+            solver.track_wet_region = true;
+        }
+        if let Some(splitting) = self.source_splitting {
+            solver.set_source_splitting(splitting)?;
+        }
+        Ok(())
+    }
+}
+
+impl ModelConfig {
+    /// Solver on the grid at the initial state
+    pub fn build(&self) -> Result<ShallowWaterSolver, String> {
+        let mut solver = self.solver(self.grid.mesh()?)?;
+        self.set_initial_condition(&mut solver, self.grid.center(), 1.0, 0)?;
+        Ok(solver)
+    }
+
+    /// Solver on `mesh` with the friction, scheme, constants and time-step
+    /// bounds of the model, with all cells dry
+    pub fn solver(&self, mesh: TriangularMesh) -> Result<ShallowWaterSolver, String> {
+        if !(self.cfl > 0.0 && self.cfl.is_finite()) {
+            return Err(format!("the CFL number must be positive, got {}", self.cfl));
+        }
+        let mut solver = ShallowWaterSolver::new(mesh, self.cfl, self.friction);
+        solver.constants = self.physics.constants()?;
+        self.numerics.apply(&mut solver)?;
+        if let Some(control) = self.timestep.control()? {
+            solver.set_timestep_control(control)?;
+        }
+        Ok(solver)
+    }
+
+    /// Apply the initial condition on a domain centred on `center`; `scale`
+    /// scales its departure from still water (1 = as configured, used by
+    /// ensemble runs) and `member` varies the seed of the random
+    /// perturbation. Returns the perturbation and the volume it added, if
+    /// one was applied.
+    pub fn set_initial_condition(
+        &self,
+        solver: &mut ShallowWaterSolver,
+        center: (f64, f64),
+        scale: f64,
+        member: usize,
+    ) -> Result<Option<(Perturbation, f64)>, String> {
+        let initial = &self.initial;
+        let point = initial.center.unwrap_or(center);
+        match initial.condition.as_deref().unwrap_or("dam-break") {
+            "dam-break" | "partial-dam-break" => {
+                solver.set_dam_break(center.0);
+                // 2 m upstream over 1 m downstream
+                for h in &mut solver.state.h {
+                    *h = 1.0 + scale * (*h - 1.0);
+                }
+            }
+            "circular-wave" => solver.set_circular_wave(center, self.grid.width / 4.0, 0.5 * scale),
+            "standing-wave" => solver.set_standing_wave(0.1 * scale, self.grid.width / 2.0),
+            "okada" => {
+                let fault = self
+                    .fault
+                    .ok_or("the okada initial condition needs a fault")?;
+                let fault = FaultParameters {
+                    slip: fault.slip * scale,
+                    ..fault
+                };
+                solver.set_okada(&fault, self.sea_level);
+            }
+            "gaussian-hump" => solver.set_gaussian_hump(
+                point,
+                initial.amplitude.unwrap_or(0.2) * scale,
+                initial.width.unwrap_or(self.grid.width / 10.0),
+                self.sea_level,
+            ),
+            "solitary-wave" => {
+                let depth = self.sea_level
+                    - solver.mesh.triangles[solver.nearest_cell(point.0, point.1).unwrap_or(0)]
+                        .z_bed;
+                solver
+                    .set_solitary_wave(
+                        point,
+                        initial.direction.unwrap_or(0.0).to_radians(),
+                        initial.amplitude.unwrap_or(0.1 * depth) * scale,
+                        self.sea_level,
+                    )
+                    .map_err(|e| format!("solitary-wave: {}", e))?;
+            }
+            name => return Err(format!("unknown initial condition '{}'", name)),
+        }
+        if let Some(field) = &initial.velocity {
+            solver.set_velocity(field);
+        }
+        let Some(perturbation) = initial.perturbation.filter(Perturbation::is_active) else {
+            return Ok(None);
+        };
+        let perturbation = Perturbation {
+            seed: perturbation.seed.wrapping_add(member as u64),
+            ..perturbation
+        };
+        let added = solver.perturb(&perturbation)?;
+        Ok(Some((perturbation, added)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_from_json() {
+        let config: ModelConfig = serde_json::from_str(
+            r#"{"grid": {"nx": 11, "ny": 3, "width": 10, "height": 1},
+                "friction": {"manning": {"coefficient": 0.03}},
+                "numerics": {"flux_scheme": "central_upwind"},
+                "initial": {"condition": "gaussian-hump", "amplitude": 0.2, "center": [5, 0.5]}}"#,
+        )
+        .unwrap();
+        let solver = config.build().unwrap();
+        assert_eq!(solver.mesh.triangles.len(), 40);
+        assert_eq!(solver.flux_scheme, FluxScheme::CentralUpwind);
+        let highest = solver.state.h.iter().copied().fold(0.0, f64::max);
+        assert!(highest > 1.1 && highest <= 1.2);
+
+        let dam_break = ModelConfig::default().build().unwrap();
+        assert_eq!(dam_break.state.h.iter().copied().fold(0.0, f64::max), 2.0);
+    }
+
+    #[test]
+    fn test_rejected_models() {
+        let mut config = ModelConfig::default();
+        config.grid.nx = 1;
+        assert!(config.build().is_err());
+
+        let mut config = ModelConfig::default();
+        config.numerics.flux_scheme = FluxScheme::CentralUpwind;
+        config.numerics.time_integrator = TimeIntegrator::SemiImplicit {
+            theta: 0.5,
+            max_courant: 20.0,
+        };
+        assert_eq!(
+            config.build().err().as_deref(),
+            Some("the semi-implicit integrator does not support the central-upwind flux")
+        );
+
+        let mut config = ModelConfig::default();
+        config.initial.condition = Some("okada".to_string());
+        assert!(config.build().is_err());
+        config.initial.condition = Some("tsunami".to_string());
+        assert_eq!(
+            config.build().err().as_deref(),
+            Some("unknown initial condition 'tsunami'")
+        );
+    }
+}
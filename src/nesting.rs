@@ -0,0 +1,658 @@
+/// One-way grid nesting
+/// An outer run saves its mesh and its state at every output time to a
+/// nesting file (JSON lines: a header with the mesh and constants, then one
+/// line per frame). An inner run on a finer mesh inside the outer domain
+/// loads the file and uses the outer solution, interpolated linearly in time
+/// and with `sample_state` in space, as the ghost state of its boundary
+/// edges. The ghost depth is the outer water level minus the inner bed, so
+/// the finer bathymetry of the inner mesh is respected. Where the outer
+/// solution is dry or the edge lies outside the outer mesh the boundary
+/// stays a reflective wall.
+///
+/// `NestedSimulation` runs both domains together (two-way nesting). Each
+/// outer step is followed by inner steps up to the new outer time, with the
+/// inner boundary interpolated between the outer states before and after
+/// the step. The inner solution is then averaged back onto the outer cells
+/// it covers. The difference between the water the outer step moved into
+/// the covered region and the water that entered the inner domain through
+/// its boundary is returned to the outer cells around the covered region,
+/// so the exchange conserves mass up to depth clipping. The inner domain is
+/// a coupling of the outer `Simulation` and runs in a `Simulation` of its own.
+use crate::mesh::TriangularMesh;
+use crate::simulation::{Coupling, Simulation};
+use crate::solver::{
+    BoundaryClosure, EdgeState, FrictionLaw, PhysicalConstants, Precision, ReflectiveWall,
+    SampledState, ShallowWaterSolver, State, TimeIntegrator,
+};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::sync::{Arc, RwLock};
+
+const FORMAT: &str = "swe-nesting-1";
+
+#[derive(Serialize)]
+struct HeaderOut<'a> {
+    format: &'a str,
+    mesh: &'a TriangularMesh,
+    constants: &'a PhysicalConstants,
+}
+
+#[derive(Deserialize)]
+struct HeaderIn {
+    format: String,
+    mesh: TriangularMesh,
+    constants: PhysicalConstants,
+}
+
+#[derive(Serialize)]
+struct FrameOut<'a> {
+    time: f64,
+    state: &'a State,
+}
+
+#[derive(Deserialize)]
+struct FrameIn {
+    time: f64,
+    state: State,
+}
+
+/// Writes the nesting file of an outer run
+pub struct NestingWriter {
+    file: BufWriter<File>,
+}
+
+impl NestingWriter {
+    /// Create `path` with the mesh and constants of `solver`
+    pub fn create(path: &str, solver: &ShallowWaterSolver) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let header = HeaderOut {
+            format: FORMAT,
+            mesh: &solver.mesh,
+            constants: &solver.constants,
+        };
+        serde_json::to_writer(&mut file, &header)?;
+        writeln!(file)?;
+        Ok(NestingWriter { file })
+    }
+
+    /// Append the current state of `solver`
+    pub fn write(&mut self, solver: &ShallowWaterSolver) -> io::Result<()> {
+        let frame = FrameOut {
+            time: solver.time,
+            state: &solver.state,
+        };
+        serde_json::to_writer(&mut self.file, &frame)?;
+        writeln!(self.file)?;
+        self.file.flush()
+    }
+}
+
+/// Saved outer solution
+pub struct OuterSolution {
+    solver: ShallowWaterSolver, // Outer mesh, used for point location
+    frames: Vec<(f64, State)>,  // Increasing times
+}
+
+impl OuterSolution {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        let mut lines = BufReader::new(file).lines();
+        let invalid = |e: serde_json::Error| format!("invalid nesting file {}: {}", path, e);
+        let read_error = |e: io::Error| format!("could not read {}: {}", path, e);
+
+        let header = lines
+            .next()
+            .ok_or_else(|| format!("{} is empty", path))?
+            .map_err(read_error)?;
+        let header: HeaderIn = serde_json::from_str(&header).map_err(invalid)?;
+        if header.format != FORMAT {
+            return Err(format!("{} is not a nesting file", path));
+        }
+        let n = header.mesh.triangles.len();
+
+        let mut frames: Vec<(f64, State)> = Vec::new();
+        for line in lines {
+            let line = line.map_err(read_error)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let frame: FrameIn = serde_json::from_str(&line).map_err(invalid)?;
+            if frame.state.h.len() != n || frame.state.hu.len() != n || frame.state.hv.len() != n {
+                return Err(format!(
+                    "{}: frame at t = {} does not match the mesh",
+                    path, frame.time
+                ));
+            }
+            if frames.last().is_some_and(|(t, _)| frame.time <= *t) {
+                return Err(format!("{}: frame times must increase", path));
+            }
+            frames.push((frame.time, frame.state));
+        }
+        if frames.is_empty() {
+            return Err(format!("{} holds no frames", path));
+        }
+        Ok(Self::new(header.mesh, header.constants, frames))
+    }
+
+    fn new(mesh: TriangularMesh, constants: PhysicalConstants, frames: Vec<(f64, State)>) -> Self {
+        let mut solver = ShallowWaterSolver::new(mesh, 0.5, FrictionLaw::None);
+        solver.constants = constants;
+        OuterSolution { solver, frames }
+    }
+
+    pub fn mesh(&self) -> &TriangularMesh {
+        &self.solver.mesh
+    }
+
+    /// Times of the first and last frame
+    pub fn time_range(&self) -> (f64, f64) {
+        (self.frames[0].0, self.frames[self.frames.len() - 1].0)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Outer solution at (x, y) and `time`, linear in time between frames
+    /// and held constant outside the saved range
+    pub fn sample(&self, time: f64, x: f64, y: f64) -> Option<SampledState> {
+        let k = self.frames.partition_point(|(t, _)| *t <= time);
+        if k == 0 || k == self.frames.len() {
+            let state = &self.frames[k.saturating_sub(1)].1;
+            return self.solver.sample_state(state, x, y);
+        }
+        let (t0, state0) = &self.frames[k - 1];
+        let (t1, state1) = &self.frames[k];
+        let a = self.solver.sample_state(state0, x, y)?;
+        let b = self.solver.sample_state(state1, x, y)?;
+        let w = (time - t0) / (t1 - t0);
+        let lerp = |p: f64, q: f64| p + w * (q - p);
+        Some(SampledState {
+            cell: if w < 0.5 { a.cell } else { b.cell },
+            depth: lerp(a.depth, b.depth),
+            velocity: (
+                lerp(a.velocity.0, b.velocity.0),
+                lerp(a.velocity.1, b.velocity.1),
+            ),
+            surface: lerp(a.surface, b.surface),
+            bed: a.bed,
+        })
+    }
+
+    /// Set the cells of `solver` that lie inside the outer mesh to the outer
+    /// solution at its current time; returns the number of cells set
+    pub fn initialize(&self, solver: &mut ShallowWaterSolver) -> usize {
+        let mut count = 0;
+        for i in 0..solver.mesh.triangles.len() {
+            let tri = &solver.mesh.triangles[i];
+            let Some(outer) = self.sample(solver.time, tri.centroid.0, tri.centroid.1) else {
+                continue;
+            };
+            let h = if outer.depth > self.solver.constants.dry_tolerance {
+                (outer.surface - tri.z_bed).max(0.0)
+            } else {
+                0.0
+            };
+            solver.state.h[i] = h;
+            solver.state.hu[i] = h * outer.velocity.0;
+            solver.state.hv[i] = h * outer.velocity.1;
+            count += 1;
+        }
+        solver.reset_active_set();
+        count
+    }
+
+    /// Ghost state of boundary `edge` of the inner `solver`
+    fn ghost(
+        &self,
+        solver: &ShallowWaterSolver,
+        edge: &crate::mesh::Edge,
+        inner: &EdgeState,
+    ) -> EdgeState {
+        let ((x, y), bed) = edge_point(&solver.mesh, edge.nodes);
+        match self.sample(solver.time, x, y) {
+            Some(outer) if outer.depth > solver.constants.dry_tolerance => {
+                let h = (outer.surface - bed).max(0.0);
+                EdgeState::from_velocity(h, outer.velocity.0, outer.velocity.1)
+            }
+            _ => ReflectiveWall.ghost(solver, edge, inner),
+        }
+    }
+
+    /// Wall flux of boundary `edge` where the outer solution is dry or
+    /// missing
+    fn boundary_flux(
+        &self,
+        solver: &ShallowWaterSolver,
+        edge: &crate::mesh::Edge,
+        inner: &EdgeState,
+    ) -> Option<(f64, f64, f64)> {
+        let ((x, y), _) = edge_point(&solver.mesh, edge.nodes);
+        match self.sample(solver.time, x, y) {
+            Some(outer) if outer.depth > solver.constants.dry_tolerance => None,
+            _ => ReflectiveWall.boundary_flux(solver, edge, inner),
+        }
+    }
+}
+
+/// Midpoint and mean bed elevation of an edge
+fn edge_point(mesh: &TriangularMesh, nodes: [usize; 2]) -> ((f64, f64), f64) {
+    let (a, b) = (&mesh.nodes[nodes[0]], &mesh.nodes[nodes[1]]);
+    ((0.5 * (a.x + b.x), 0.5 * (a.y + b.y)), 0.5 * (a.z + b.z))
+}
+
+/// Number of boundary edges of `mesh` whose midpoint lies outside `outer`
+fn uncovered_edges(outer: &ShallowWaterSolver, mesh: &TriangularMesh) -> usize {
+    mesh.edges
+        .iter()
+        .filter(|e| e.right_triangle.is_none())
+        .filter(|e| {
+            let ((x, y), _) = edge_point(mesh, e.nodes);
+            outer.locate(x, y).is_none()
+        })
+        .count()
+}
+
+/// Boundary closure driven by an outer solution
+pub struct NestedBoundary {
+    pub outer: OuterSolution,
+}
+
+impl NestedBoundary {
+    /// Number of boundary edges of `mesh` whose midpoint lies outside the
+    /// outer mesh (these stay walls)
+    pub fn uncovered_edges(&self, mesh: &TriangularMesh) -> usize {
+        uncovered_edges(&self.outer.solver, mesh)
+    }
+}
+
+impl BoundaryClosure for NestedBoundary {
+    fn name(&self) -> &str {
+        "nested"
+    }
+
+    fn ghost(
+        &self,
+        solver: &ShallowWaterSolver,
+        edge: &crate::mesh::Edge,
+        inner: &EdgeState,
+    ) -> EdgeState {
+        self.outer.ghost(solver, edge, inner)
+    }
+
+    fn boundary_flux(
+        &self,
+        solver: &ShallowWaterSolver,
+        edge: &crate::mesh::Edge,
+        inner: &EdgeState,
+    ) -> Option<(f64, f64, f64)> {
+        self.outer.boundary_flux(solver, edge, inner)
+    }
+}
+
+/// Inner boundary of a `NestedSimulation`, following the running outer
+/// domain
+struct LinkedBoundary(Arc<RwLock<OuterSolution>>);
+
+impl BoundaryClosure for LinkedBoundary {
+    fn name(&self) -> &str {
+        "nested"
+    }
+
+    fn ghost(
+        &self,
+        solver: &ShallowWaterSolver,
+        edge: &crate::mesh::Edge,
+        inner: &EdgeState,
+    ) -> EdgeState {
+        self.0.read().unwrap().ghost(solver, edge, inner)
+    }
+
+    fn boundary_flux(
+        &self,
+        solver: &ShallowWaterSolver,
+        edge: &crate::mesh::Edge,
+        inner: &EdgeState,
+    ) -> Option<(f64, f64, f64)> {
+        self.0.read().unwrap().boundary_flux(solver, edge, inner)
+    }
+}
+
+/// Coarse outer and fine inner domain advanced together with two-way
+/// coupling
+pub struct NestedSimulation {
+    pub outer: ShallowWaterSolver,
+    pub inner: ShallowWaterSolver,
+    pub inner_steps: usize, // Inner steps taken so far
+    link: Arc<RwLock<OuterSolution>>,
+    children: Vec<Vec<usize>>, // Inner cells averaged into each covered outer cell
+    ring: Vec<usize>,          // Uncovered outer cells next to covered ones
+}
+
+impl NestedSimulation {
+    /// Couple `inner` to `outer` and start it from the outer state. The
+    /// outer cells whose nodes all lie in the inner mesh are covered and
+    /// take the average of the inner cells with centroids inside them.
+    pub fn new(outer: ShallowWaterSolver, mut inner: ShallowWaterSolver) -> Result<Self, String> {
+        if outer.mesh.coordinate_system != inner.mesh.coordinate_system {
+            return Err("the domains use different coordinate systems".to_string());
+        }
+        if inner.precision == Precision::Single {
+            return Err("single precision does not support nested boundaries".to_string());
+        }
+        for solver in [&outer, &inner] {
+            if matches!(solver.time_integrator, TimeIntegrator::SemiImplicit { .. }) {
+                return Err("the semi-implicit integrator is not supported".to_string());
+            }
+        }
+
+        let mut children = vec![Vec::new(); outer.mesh.triangles.len()];
+        for (i, tri) in inner.mesh.triangles.iter().enumerate() {
+            if let Some(c) = outer.locate(tri.centroid.0, tri.centroid.1) {
+                children[c].push(i);
+            }
+        }
+        for (c, tri) in outer.mesh.triangles.iter().enumerate() {
+            let inside = tri.nodes.iter().all(|&n| {
+                let node = &outer.mesh.nodes[n];
+                inner.locate(node.x, node.y).is_some()
+            });
+            if !inside {
+                children[c].clear();
+            }
+        }
+        if children.iter().all(|c| c.is_empty()) {
+            return Err("the inner mesh covers no outer cell".to_string());
+        }
+        let mut ring: Vec<usize> = (0..outer.mesh.triangles.len())
+            .filter(|&c| children[c].is_empty())
+            .filter(|&c| {
+                outer.mesh.triangles[c]
+                    .neighbors
+                    .iter()
+                    .flatten()
+                    .any(|&j| !children[j].is_empty())
+            })
+            .collect();
+        ring.sort_unstable();
+
+        let link = OuterSolution::new(
+            outer.mesh.clone(),
+            outer.constants,
+            vec![(outer.time, outer.state.clone())],
+        );
+        inner.time = outer.time;
+        link.initialize(&mut inner);
+        let link = Arc::new(RwLock::new(link));
+        inner.boundary_closure = Box::new(LinkedBoundary(link.clone()));
+
+        Ok(NestedSimulation {
+            outer,
+            inner,
+            inner_steps: 0,
+            link,
+            children,
+            ring,
+        })
+    }
+
+    /// Number of outer cells replaced by the inner solution
+    pub fn covered_cells(&self) -> usize {
+        self.children.iter().filter(|c| !c.is_empty()).count()
+    }
+
+    /// Boundary edges of the inner mesh outside the outer mesh (walls)
+    pub fn uncovered_edges(&self) -> usize {
+        uncovered_edges(&self.link.read().unwrap().solver, &self.inner.mesh)
+    }
+
+    /// Advance the outer domain by one step and the inner domain to the
+    /// same time, then feed the inner solution back
+    pub fn step(&mut self) {
+        let mut simulation = self.simulation(f64::INFINITY);
+        simulation.step().expect("the inner domain cannot fail");
+    }
+
+    /// Advance until the outer clock reaches `time`
+    pub fn advance_to(&mut self, time: f64) {
+        let mut simulation = self.simulation(time);
+        simulation.run().expect("the inner domain cannot fail");
+    }
+
+    /// Outer simulation to `final_time` with the inner domain coupled to it
+    fn simulation(&mut self, final_time: f64) -> Simulation<'_> {
+        let mut simulation = Simulation::until(&mut self.outer, final_time);
+        simulation.add_coupling(Box::new(InnerDomain {
+            inner: &mut self.inner,
+            inner_steps: &mut self.inner_steps,
+            link: &self.link,
+            children: &self.children,
+            ring: &self.ring,
+            before: None,
+        }));
+        simulation
+    }
+
+    /// Volume of the inner domain plus the outer cells it does not cover (m^3)
+    pub fn total_mass(&self) -> f64 {
+        self.outer.compute_total_mass() - covered_mass(&self.children, &self.outer)
+            + self.inner.compute_total_mass()
+    }
+}
+
+/// Inner domain as a coupling of the outer simulation
+struct InnerDomain<'a> {
+    inner: &'a mut ShallowWaterSolver,
+    inner_steps: &'a mut usize,
+    link: &'a RwLock<OuterSolution>,
+    children: &'a [Vec<usize>],
+    ring: &'a [usize],
+    before: Option<((f64, State), f64)>, // Outer state and covered mass before the step
+}
+
+impl Coupling for InnerDomain<'_> {
+    fn name(&self) -> String {
+        "inner domain".to_string()
+    }
+
+    fn before_step(&mut self, outer: &mut ShallowWaterSolver) -> Result<(), String> {
+        let covered = covered_mass(self.children, outer);
+        self.before = Some(((outer.time, outer.state.clone()), covered));
+        Ok(())
+    }
+
+    fn after_step(&mut self, outer: &mut ShallowWaterSolver) -> Result<(), String> {
+        let (before, covered_before) = self.before.take().expect("before_step ran");
+        let outer_inflow = covered_mass(self.children, outer) - covered_before;
+
+        self.link.write().unwrap().frames = vec![before, (outer.time, outer.state.clone())];
+        let inner_before = self.inner.compute_total_mass();
+        *self.inner_steps += Simulation::until(self.inner, outer.time).run()?.steps;
+        let inner_inflow = self.inner.compute_total_mass() - inner_before;
+
+        feed_back(
+            self.children,
+            self.ring,
+            outer,
+            self.inner,
+            outer_inflow - inner_inflow,
+        );
+        Ok(())
+    }
+}
+
+/// Volume of the outer cells covered by the inner domain (m^3)
+fn covered_mass(children: &[Vec<usize>], outer: &ShallowWaterSolver) -> f64 {
+    children
+        .iter()
+        .enumerate()
+        .filter(|(_, children)| !children.is_empty())
+        .map(|(c, _)| outer.state.h[c] * outer.mesh.triangles[c].area)
+        .sum()
+}
+
+/// Replace the covered outer cells by the area-weighted inner average
+/// and spread `excess` (m^3) over the wet cells of the ring
+fn feed_back(
+    children: &[Vec<usize>],
+    ring: &[usize],
+    outer: &mut ShallowWaterSolver,
+    inner: &ShallowWaterSolver,
+    excess: f64,
+) {
+    for (c, children) in children.iter().enumerate() {
+        if children.is_empty() {
+            continue;
+        }
+        let (mut area, mut h, mut hu, mut hv) = (0.0, 0.0, 0.0, 0.0);
+        for &i in children {
+            let a = inner.mesh.triangles[i].area;
+            area += a;
+            h += a * inner.state.h[i];
+            hu += a * inner.state.hu[i];
+            hv += a * inner.state.hv[i];
+        }
+        outer.state.h[c] = h / area;
+        outer.state.hu[c] = hu / area;
+        outer.state.hv[c] = hv / area;
+    }
+
+    let dry = outer.constants.dry_tolerance;
+    let wet_area: f64 = ring
+        .iter()
+        .filter(|&&c| outer.state.h[c] > dry)
+        .map(|&c| outer.mesh.triangles[c].area)
+        .sum();
+    if wet_area > 0.0 {
+        let dh = excess / wet_area;
+        for &c in ring {
+            if outer.state.h[c] > dry {
+                let h = outer.state.h[c];
+                let h_new = (h + dh).max(0.0);
+                outer.state.hu[c] *= h_new / h;
+                outer.state.hv[c] *= h_new / h;
+                outer.state.h[c] = h_new;
+            }
+        }
+    }
+    outer.reset_active_set();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+
+    fn outer_run(path: &str, final_time: f64) -> ShallowWaterSolver {
+        let mesh = TriangularMesh::new_rectangular(41, 11, 40.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_dam_break(10.0);
+        let mut writer = NestingWriter::create(path, &solver).unwrap();
+        writer.write(&solver).unwrap();
+        while solver.time < final_time {
+            solver.step();
+            writer.write(&solver).unwrap();
+        }
+        solver
+    }
+
+    #[test]
+    fn test_time_interpolation() {
+        let path = std::env::temp_dir().join(format!("swe_nesting_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mesh = TriangularMesh::new_rectangular(3, 3, 2.0, 2.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        let mut writer = NestingWriter::create(path, &solver).unwrap();
+        for (time, depth) in [(0.0, 1.0), (10.0, 3.0)] {
+            solver.time = time;
+            solver.state.h.iter_mut().for_each(|h| *h = depth);
+            writer.write(&solver).unwrap();
+        }
+        drop(writer);
+
+        let outer = OuterSolution::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!((outer.frame_count(), outer.time_range()), (2, (0.0, 10.0)));
+        assert!((outer.sample(2.5, 1.0, 1.0).unwrap().depth - 1.5).abs() < 1e-12);
+        assert_eq!(outer.sample(-1.0, 1.0, 1.0).unwrap().depth, 1.0);
+        assert_eq!(outer.sample(20.0, 1.0, 1.0).unwrap().depth, 3.0);
+        assert!(outer.sample(5.0, 3.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_nested_dam_break_follows_outer_run() {
+        let path = std::env::temp_dir().join(format!("swe_nested_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let outer_solver = outer_run(path, 2.0);
+        let outer = OuterSolution::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // Finer inner domain around the dam, open to the east
+        let mesh = TriangularMesh::new_rectangular(41, 21, 20.0, 10.0, TopographyType::Flat);
+        let mut inner = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        assert_eq!(outer.initialize(&mut inner), inner.mesh.triangles.len());
+        let boundary = NestedBoundary { outer };
+        assert_eq!(boundary.uncovered_edges(&inner.mesh), 0);
+        inner.boundary_closure = Box::new(boundary);
+
+        while inner.time < 2.0 {
+            inner.step();
+        }
+        for x in [2.0, 6.0, 10.0, 14.0, 18.0] {
+            let fine = inner.sample(x, 5.0).unwrap().depth;
+            let coarse = outer_solver.sample(x, 5.0).unwrap().depth;
+            assert!(
+                (fine - coarse).abs() < 0.1,
+                "x = {}: {} vs {}",
+                x,
+                fine,
+                coarse
+            );
+        }
+    }
+
+    #[test]
+    fn test_two_way_dam_break_conserves_mass() {
+        let outer_mesh = TriangularMesh::new_rectangular(41, 11, 40.0, 10.0, TopographyType::Flat);
+        let mut reference = ShallowWaterSolver::new(outer_mesh.clone(), 0.45, FrictionLaw::None);
+        reference.set_dam_break(20.0);
+        let mut outer = ShallowWaterSolver::new(outer_mesh, 0.45, FrictionLaw::None);
+        outer.set_dam_break(20.0);
+
+        // Refinement patch across the dam, away from the outer walls
+        let mut inner_mesh =
+            TriangularMesh::new_rectangular(25, 13, 12.0, 6.0, TopographyType::Flat);
+        for node in &mut inner_mesh.nodes {
+            node.x += 14.0;
+            node.y += 2.0;
+        }
+        for tri in &mut inner_mesh.triangles {
+            tri.centroid = (tri.centroid.0 + 14.0, tri.centroid.1 + 2.0);
+        }
+        let inner = ShallowWaterSolver::new(inner_mesh, 0.45, FrictionLaw::None);
+
+        let mut nested = NestedSimulation::new(outer, inner).unwrap();
+        assert_eq!(nested.uncovered_edges(), 0);
+        assert!(nested.covered_cells() > 0);
+        let initial = nested.total_mass();
+        nested.advance_to(2.0);
+        while reference.time < nested.outer.time {
+            reference.step();
+        }
+
+        assert!(nested.inner_steps > 0);
+        assert!((nested.total_mass() - initial).abs() < 1e-9 * initial);
+        for x in [5.0, 12.0, 28.0, 35.0] {
+            let coupled = nested.outer.sample(x, 5.0).unwrap().depth;
+            let coarse = reference.sample(x, 5.0).unwrap().depth;
+            assert!(
+                (coupled - coarse).abs() < 0.1,
+                "x = {}: {} vs {}",
+                x,
+                coupled,
+                coarse
+            );
+        }
+    }
+}
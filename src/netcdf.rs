@@ -0,0 +1,404 @@
+/// NetCDF classic reader
+/// Reads the header of a NetCDF classic (CDF-1) or 64-bit offset (CDF-2)
+/// file and the variables in it, record variables one record at a time.
+/// Values are unpacked to f64 with `scale_factor` and `add_offset`, and
+/// `_FillValue` or `missing_value` become NaN. NetCDF-4 files are HDF5 and
+/// are not read; `nccopy -k classic` converts them.
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+const NC_DIMENSION: u32 = 0x0A;
+const NC_VARIABLE: u32 = 0x0B;
+const NC_ATTRIBUTE: u32 = 0x0C;
+const NC_BYTE: u32 = 1;
+const NC_CHAR: u32 = 2;
+const NC_SHORT: u32 = 3;
+const NC_INT: u32 = 4;
+const NC_FLOAT: u32 = 5;
+const NC_DOUBLE: u32 = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Text(String),
+    Numbers(Vec<f64>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dimension {
+    pub name: String,
+    pub length: usize, // Current record count for the record dimension
+    pub is_record: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    pub dimensions: Vec<usize>, // Indices into NetcdfFile::dimensions
+    pub attributes: Vec<(String, AttributeValue)>,
+    nc_type: u32,
+    begin: u64,
+}
+
+impl Variable {
+    pub fn attribute(&self, name: &str) -> Option<&AttributeValue> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match self.attribute(name) {
+            Some(AttributeValue::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self, name: &str) -> Option<f64> {
+        match self.attribute(name) {
+            Some(AttributeValue::Numbers(values)) => values.first().copied(),
+            _ => None,
+        }
+    }
+}
+
+pub struct NetcdfFile {
+    reader: BufReader<File>,
+    pub dimensions: Vec<Dimension>,
+    pub attributes: Vec<(String, AttributeValue)>,
+    pub variables: Vec<Variable>,
+    record_size: u64, // Bytes per record over all record variables
+}
+
+/// Big-endian header cursor
+struct Header<R: Read> {
+    reader: R,
+    offset64: bool,
+}
+
+impl<R: Read> Header<R> {
+    fn bytes(&mut self, count: usize) -> Result<Vec<u8>, String> {
+        let mut buffer = vec![0; count];
+        self.reader
+            .read_exact(&mut buffer)
+            .map_err(|_| "truncated NetCDF header".to_string())?;
+        Ok(buffer)
+    }
+
+    fn int(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn offset(&mut self) -> Result<u64, String> {
+        if self.offset64 {
+            let b = self.bytes(8)?;
+            Ok(u64::from_be_bytes(b.try_into().unwrap()))
+        } else {
+            Ok(self.int()? as u64)
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let length = self.int()? as usize;
+        let bytes = self.bytes(length.next_multiple_of(4))?;
+        String::from_utf8(bytes[..length].to_vec()).map_err(|_| "invalid NetCDF name".to_string())
+    }
+
+    /// Tag and element count of a list; an absent list is (0, 0)
+    fn list(&mut self, tag: u32) -> Result<usize, String> {
+        let (found, count) = (self.int()?, self.int()? as usize);
+        if found != tag && !(found == 0 && count == 0) {
+            return Err("malformed NetCDF header".to_string());
+        }
+        Ok(count)
+    }
+
+    fn attributes(&mut self) -> Result<Vec<(String, AttributeValue)>, String> {
+        let count = self.list(NC_ATTRIBUTE)?;
+        (0..count)
+            .map(|_| {
+                let name = self.name()?;
+                let nc_type = self.int()?;
+                let length = self.int()? as usize;
+                let size = type_size(nc_type)?;
+                let bytes = self.bytes((length * size).next_multiple_of(4))?;
+                let value = if nc_type == NC_CHAR {
+                    let text = String::from_utf8_lossy(&bytes[..length]);
+                    AttributeValue::Text(text.trim_end_matches('\0').to_string())
+                } else {
+                    AttributeValue::Numbers(decode(nc_type, &bytes[..length * size]))
+                };
+                Ok((name, value))
+            })
+            .collect()
+    }
+}
+
+fn type_size(nc_type: u32) -> Result<usize, String> {
+    match nc_type {
+        NC_BYTE | NC_CHAR => Ok(1),
+        NC_SHORT => Ok(2),
+        NC_INT | NC_FLOAT => Ok(4),
+        NC_DOUBLE => Ok(8),
+        _ => Err(format!("unsupported NetCDF type {}", nc_type)),
+    }
+}
+
+/// Big-endian values of a numeric type as f64
+fn decode(nc_type: u32, bytes: &[u8]) -> Vec<f64> {
+    match nc_type {
+        NC_BYTE => bytes.iter().map(|&b| b as i8 as f64).collect(),
+        NC_SHORT => bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_be_bytes([b[0], b[1]]) as f64)
+            .collect(),
+        NC_INT => bytes
+            .chunks_exact(4)
+            .map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect(),
+        NC_FLOAT => bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect(),
+        _ => bytes
+            .chunks_exact(8)
+            .map(|b| f64::from_be_bytes(b.try_into().unwrap()))
+            .collect(),
+    }
+}
+
+impl NetcdfFile {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::read_header(BufReader::new(file)).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn read_header(mut reader: BufReader<File>) -> Result<Self, String> {
+        let mut magic = [0; 4];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|_| "not a NetCDF file".to_string())?;
+        let offset64 = match &magic {
+            b"CDF\x01" => false,
+            b"CDF\x02" => true,
+            [0x89, b'H', b'D', b'F'] => {
+                return Err(
+                    "NetCDF-4 (HDF5) files are not supported; convert with nccopy -k classic"
+                        .to_string(),
+                )
+            }
+            _ => return Err("not a NetCDF classic file".to_string()),
+        };
+        let mut header = Header {
+            reader: &mut reader,
+            offset64,
+        };
+        let records = match header.int()? {
+            u32::MAX => return Err("streaming NetCDF files are not supported".to_string()),
+            records => records as usize,
+        };
+
+        let count = header.list(NC_DIMENSION)?;
+        let mut dimensions = Vec::with_capacity(count);
+        for _ in 0..count {
+            let name = header.name()?;
+            let length = header.int()? as usize;
+            dimensions.push(Dimension {
+                name,
+                length: if length == 0 { records } else { length },
+                is_record: length == 0,
+            });
+        }
+        let attributes = header.attributes()?;
+
+        let count = header.list(NC_VARIABLE)?;
+        let mut variables = Vec::with_capacity(count);
+        let mut record_sizes = Vec::new();
+        for _ in 0..count {
+            let name = header.name()?;
+            let rank = header.int()? as usize;
+            let dims = (0..rank)
+                .map(|_| {
+                    let d = header.int()? as usize;
+                    (d < dimensions.len())
+                        .then_some(d)
+                        .ok_or_else(|| format!("variable '{}' has an invalid dimension", name))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let var_attributes = header.attributes()?;
+            let nc_type = header.int()?;
+            type_size(nc_type)?;
+            let _vsize = header.int()?;
+            let begin = header.offset()?;
+            let variable = Variable {
+                name,
+                dimensions: dims,
+                attributes: var_attributes,
+                nc_type,
+                begin,
+            };
+            if variable
+                .dimensions
+                .first()
+                .is_some_and(|&d| dimensions[d].is_record)
+            {
+                record_sizes.push(Self::slice_bytes(&dimensions, &variable));
+            }
+            variables.push(variable);
+        }
+        // Records are padded to 4 bytes, except with a single record variable
+        let record_size = match record_sizes[..] {
+            [single] => single,
+            _ => record_sizes.iter().map(|s| s.next_multiple_of(4)).sum(),
+        };
+        Ok(NetcdfFile {
+            reader,
+            dimensions,
+            attributes,
+            variables,
+            record_size,
+        })
+    }
+
+    /// Bytes of one record of a record variable, or of a whole fixed variable
+    fn slice_bytes(dimensions: &[Dimension], variable: &Variable) -> u64 {
+        let size = type_size(variable.nc_type).unwrap() as u64;
+        variable
+            .dimensions
+            .iter()
+            .filter(|&&d| !dimensions[d].is_record)
+            .map(|&d| dimensions[d].length as u64)
+            .product::<u64>()
+            * size
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().find(|v| v.name == name)
+    }
+
+    /// Dimension lengths of a variable
+    pub fn shape(&self, variable: &Variable) -> Vec<usize> {
+        variable
+            .dimensions
+            .iter()
+            .map(|&d| self.dimensions[d].length)
+            .collect()
+    }
+
+    pub fn is_record_variable(&self, variable: &Variable) -> bool {
+        variable
+            .dimensions
+            .first()
+            .is_some_and(|&d| self.dimensions[d].is_record)
+    }
+
+    /// All values of a variable, unpacked (row-major, records first)
+    pub fn read(&mut self, name: &str) -> Result<Vec<f64>, String> {
+        let variable = self.find(name)?;
+        if !self.is_record_variable(&variable) {
+            let bytes = Self::slice_bytes(&self.dimensions, &variable);
+            return self.read_at(&variable, variable.begin, bytes);
+        }
+        let records = self.dimensions[variable.dimensions[0]].length;
+        let mut values = Vec::new();
+        for record in 0..records {
+            values.extend(self.read_record(name, record)?);
+        }
+        Ok(values)
+    }
+
+    /// One record of a record variable, unpacked
+    pub fn read_record(&mut self, name: &str, record: usize) -> Result<Vec<f64>, String> {
+        let variable = self.find(name)?;
+        if !self.is_record_variable(&variable) {
+            return Err(format!("'{}' is not a record variable", name));
+        }
+        if record >= self.dimensions[variable.dimensions[0]].length {
+            return Err(format!("'{}' has no record {}", name, record));
+        }
+        let bytes = Self::slice_bytes(&self.dimensions, &variable);
+        self.read_at(
+            &variable,
+            variable.begin + record as u64 * self.record_size,
+            bytes,
+        )
+    }
+
+    fn find(&self, name: &str) -> Result<Variable, String> {
+        self.variable(name)
+            .cloned()
+            .ok_or_else(|| format!("no variable '{}'", name))
+    }
+
+    fn read_at(
+        &mut self,
+        variable: &Variable,
+        offset: u64,
+        bytes: u64,
+    ) -> Result<Vec<f64>, String> {
+        if variable.nc_type == NC_CHAR {
+            return Err(format!("'{}' is a text variable", variable.name));
+        }
+        let mut buffer = vec![0; bytes as usize];
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.reader.read_exact(&mut buffer))
+            .map_err(|_| format!("truncated data of '{}'", variable.name))?;
+        let fill = variable
+            .number("_FillValue")
+            .or(variable.number("missing_value"));
+        let scale = variable.number("scale_factor").unwrap_or(1.0);
+        let offset = variable.number("add_offset").unwrap_or(0.0);
+        Ok(decode(variable.nc_type, &buffer)
+            .into_iter()
+            .map(|v| {
+                if fill == Some(v) {
+                    f64::NAN
+                } else {
+                    v * scale + offset
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::{FrictionLaw, ShallowWaterSolver};
+    use crate::sww::SwwWriter;
+
+    #[test]
+    fn test_read_sww_file() {
+        let mesh = TriangularMesh::new_rectangular(4, 3, 3.0, 2.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_dam_break(1.5);
+        let path = std::env::temp_dir().join(format!("swe_netcdf_{}.sww", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut writer = SwwWriter::create(path, &solver, "dam break", None).unwrap();
+        writer.write(&solver).unwrap();
+        solver.time = 0.5;
+        solver.state.h[3] += 0.25;
+        writer.write(&solver).unwrap();
+        drop(writer);
+
+        let mut file = NetcdfFile::open(path).unwrap();
+        let points = 3 * solver.mesh.triangles.len();
+        let stage = file.variable("stage").unwrap().clone();
+        assert!(file.is_record_variable(&stage));
+        assert_eq!(file.shape(&stage), vec![2, points]);
+        assert_eq!(file.read("time").unwrap(), vec![0.0, 0.5]);
+        let volumes = file.read("volumes").unwrap();
+        assert_eq!(volumes.len(), points);
+        assert_eq!(volumes[..3], [0.0, 1.0, 2.0]);
+        let last = file.read_record("stage", 1).unwrap();
+        let eta = solver.state.h[3] + solver.mesh.triangles[3].z_bed;
+        assert!((last[9] - eta).abs() < 1e-6);
+        assert_eq!(file.read("stage").unwrap().len(), 2 * points);
+        assert!(file.read_record("stage", 2).is_err());
+        assert!(file.read("depth").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
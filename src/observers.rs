@@ -0,0 +1,853 @@
+/// Output writers and run checks
+/// The writers of the tables (statistics, gauges, profiles, runup), the VTK
+/// frames and snapshots, the mesh output formats and the diagnostic logs
+/// observe a `Simulation`; the checks that stop a run are couplings. The
+/// writers are created with their files and write the initial state, so
+/// that the first row is at the start time:
+///
+/// ```no_run
+/// # use shallow_water_solver::observers::{GaugeWriter, Progress, Tables};
+/// # use shallow_water_solver::simulation::{Simulation, SimulationConfig};
+/// # fn main() -> Result<(), String> {
+/// let mut simulation = Simulation::new(SimulationConfig::new(2.0, 0.5))?;
+/// let cell = simulation.solver().nearest_cell(5.0, 1.0).unwrap_or(0);
+/// let gauges = GaugeWriter::create(
+///     &Tables::csv("dam"),
+///     simulation.solver(),
+///     vec![("middle".to_string(), cell)],
+/// );
+/// simulation.add_observer(Box::new(gauges));
+/// simulation.add_observer(Box::new(Progress));
+/// simulation.run()?;
+/// # Ok(())
+/// # }
+/// ```
+use crate::cadence::OutputStream;
+#[cfg(feature = "sqlite")]
+use crate::database::ResultsDatabase;
+use crate::extent;
+use crate::output::{self, Frame, FrameWriter, OutputField};
+use crate::parquet::{ColumnType, ParquetWriter, Value};
+use crate::preview::{self, PreviewMode};
+use crate::profile::Profile;
+use crate::profiler::PhaseTimes;
+use crate::raster::RasterCrs;
+use crate::render::{self, RenderField, RenderOptions};
+use crate::runup::{RunupRecord, RunupTracker};
+use crate::simulation::{Coupling, Observer, StepEvent};
+use crate::solver::{Budget, ShallowWaterSolver};
+use crate::symmetry::{Asymmetry, SymmetryCheck};
+use std::fs::File;
+use std::io::Write;
+use std::time::Instant;
+
+/// Formats of the tables <prefix>_<name>.csv and <prefix>_<name>.parquet
+#[derive(Debug, Clone)]
+pub struct Tables {
+    pub prefix: String,
+    pub csv: bool,
+    pub parquet: bool,
+}
+
+impl Tables {
+    pub fn csv(prefix: &str) -> Self {
+        Tables {
+            prefix: prefix.to_string(),
+            csv: true,
+            parquet: false,
+        }
+    }
+
+    /// CSV table with its header line, None if not written
+    fn create_csv(&self, name: &str, header: &str) -> Option<File> {
+        if !self.csv {
+            return None;
+        }
+        let filename = format!("{}_{}.csv", self.prefix, name);
+        match File::create(&filename) {
+            Ok(mut file) => {
+                writeln!(file, "{}", header).unwrap();
+                Some(file)
+            }
+            Err(e) => {
+                eprintln!("Warning: Could not write output file {}: {}", filename, e);
+                None
+            }
+        }
+    }
+
+    /// Parquet table, None if not written
+    fn create_parquet(&self, name: &str, schema: &[(&str, ColumnType)]) -> Option<ParquetWriter> {
+        if !self.parquet {
+            return None;
+        }
+        let filename = format!("{}_{}.parquet", self.prefix, name);
+        match ParquetWriter::create(&filename, schema) {
+            Ok(table) => Some(table),
+            Err(e) => {
+                eprintln!("Warning: Could not write output file {}: {}", filename, e);
+                None
+            }
+        }
+    }
+}
+
+/// Finish a Parquet table, if one is written
+fn finish_table(table: Option<ParquetWriter>) -> Result<(), String> {
+    match table {
+        Some(table) => table
+            .finish()
+            .map_err(|e| format!("Could not write Parquet table: {}", e)),
+        None => Ok(()),
+    }
+}
+
+fn push_row(table: &mut Option<ParquetWriter>, row: &[Value]) {
+    if let Some(writer) = table {
+        if let Err(e) = writer.push(row) {
+            eprintln!("Warning: Could not write Parquet table: {}", e);
+            *table = None;
+        }
+    }
+}
+
+/// Progress line at every common output
+pub struct Progress;
+
+impl Observer for Progress {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        if event.common {
+            println!(
+                "  t = {:.3}s, dt = {:.6}s, steps = {}, mass error = {:.6}%",
+                solver.time,
+                solver.dt,
+                event.step,
+                event.mass_error(solver)
+            );
+        }
+    }
+}
+
+/// VTK frames when due; PNG snapshots, flood extents and the terminal
+/// preview at every common output
+pub struct FrameOutputs<'a> {
+    pub writer: &'a FrameWriter,
+    pub prefix: String,
+    pub fields: Vec<OutputField>,
+    pub png_fields: Vec<RenderField>,
+    pub png_options: RenderOptions,
+    pub extent: Option<(f64, RasterCrs)>, // Depth threshold of the flood extents
+    pub preview: Option<(PreviewMode, usize)>, // Mode and width in characters
+    pub frames: usize,                    // Index of the next VTK frame
+    pub outputs: usize,                   // Index of the next common output
+}
+
+impl<'a> FrameOutputs<'a> {
+    /// VTK frames of `fields` only
+    pub fn new(writer: &'a FrameWriter, prefix: &str, fields: &[OutputField]) -> Self {
+        FrameOutputs {
+            writer,
+            prefix: prefix.to_string(),
+            fields: fields.to_vec(),
+            png_fields: Vec::new(),
+            png_options: RenderOptions::default(),
+            extent: None,
+            preview: None,
+            frames: 0,
+            outputs: 0,
+        }
+    }
+
+    /// Every output of the current state, as at the start and the end of a
+    /// steady-state run
+    pub fn write_all(&mut self, solver: &ShallowWaterSolver) {
+        self.write_frame(solver, &self.fields.clone());
+        self.write_common(solver);
+    }
+
+    fn write_frame(&mut self, solver: &ShallowWaterSolver, fields: &[OutputField]) {
+        self.writer.write(
+            frame_filename(&self.prefix, self.frames),
+            Frame::capture(solver, fields),
+        );
+        self.frames += 1;
+    }
+
+    fn write_common(&mut self, solver: &ShallowWaterSolver) {
+        save_snapshots(
+            solver,
+            &self.png_fields,
+            &self.png_options,
+            &self.prefix,
+            self.outputs,
+        );
+        if let Some((depth, crs)) = self.extent {
+            let filename = format!("{}_extent_{:04}.geojson", self.prefix, self.outputs);
+            save_flood_extent(solver, &solver.state.h, &filename, depth, crs);
+        }
+        if let Some((mode, width)) = self.preview {
+            print!("{}", preview::preview(solver, mode, width));
+        }
+        self.outputs += 1;
+    }
+}
+
+impl Observer for FrameOutputs<'_> {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        let frame_fields = event.schedule.frame_fields(&self.fields);
+        if !frame_fields.is_empty() {
+            self.write_frame(solver, &frame_fields);
+        }
+        if event.common {
+            self.write_common(solver);
+        }
+    }
+}
+
+pub fn frame_filename(prefix: &str, index: usize) -> String {
+    format!("{}_{:04}.vtk", prefix, index)
+}
+
+/// PNG images of `fields` for output `index`
+pub fn save_snapshots(
+    solver: &ShallowWaterSolver,
+    fields: &[RenderField],
+    options: &RenderOptions,
+    prefix: &str,
+    index: usize,
+) {
+    for &field in fields {
+        let filename = format!("{}_{}_{:04}.png", prefix, field.name(), index);
+        let image = render::render(solver, field, options);
+        if let Err(e) = render::save_png(&filename, &image) {
+            eprintln!("Warning: Could not write output file {}: {}", filename, e);
+        }
+    }
+}
+
+/// GeoJSON polygons of the cells deeper than `threshold` in `h`
+pub fn save_flood_extent(
+    solver: &ShallowWaterSolver,
+    h: &[f64],
+    filename: &str,
+    threshold: f64,
+    crs: RasterCrs,
+) {
+    let polygons = extent::flood_extent(&solver.mesh, h, threshold);
+    let properties = [("time", solver.time), ("depth_threshold", threshold)];
+    if let Err(e) = extent::save_geojson(filename, &polygons, &properties, crs) {
+        eprintln!("Warning: Could not write output file {}: {}", filename, e);
+    }
+}
+
+/// Budget rows of the statistics table
+pub struct StatisticsWriter {
+    file: Option<File>,
+    table: Option<ParquetWriter>,
+}
+
+impl StatisticsWriter {
+    /// Tables starting with the `initial` budget
+    pub fn create(tables: &Tables, initial: &Budget) -> Self {
+        let schema: Vec<(&str, ColumnType)> = Budget::CSV_HEADER
+            .split(',')
+            .map(|name| (name, ColumnType::Double))
+            .collect();
+        let mut writer = StatisticsWriter {
+            file: tables.create_csv("statistics", Budget::CSV_HEADER),
+            table: tables.create_parquet("statistics", &schema),
+        };
+        writer.write(initial);
+        writer
+    }
+
+    fn write(&mut self, budget: &Budget) {
+        if let Some(file) = &mut self.file {
+            writeln!(file, "{}", budget.csv_row()).unwrap();
+        }
+        let row: Vec<Value> = budget.values().into_iter().map(Value::Double).collect();
+        push_row(&mut self.table, &row);
+    }
+}
+
+impl Observer for StatisticsWriter {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        if let Some(budget) = event
+            .budget
+            .filter(|_| event.schedule.due(OutputStream::Statistics))
+        {
+            self.write(&budget.budget(solver));
+        }
+    }
+
+    fn finish(self: Box<Self>, _solver: &ShallowWaterSolver) -> Result<(), String> {
+        finish_table(self.table)
+    }
+}
+
+/// Depth, level and velocity at named cells
+pub fn gauge_readings(solver: &ShallowWaterSolver, gauges: &[(String, usize)]) -> Vec<[f64; 4]> {
+    gauges
+        .iter()
+        .map(|(_, cell)| {
+            let h = solver.state.h[*cell];
+            let (u, v) = solver.state.get_velocity(*cell, &solver.constants);
+            [h, h + solver.mesh.triangles[*cell].z_bed, u, v]
+        })
+        .collect()
+}
+
+const GAUGE_SCHEMA: [(&str, ColumnType); 6] = [
+    ("time", ColumnType::Double),
+    ("gauge", ColumnType::Text),
+    ("depth", ColumnType::Double),
+    ("level", ColumnType::Double),
+    ("u", ColumnType::Double),
+    ("v", ColumnType::Double),
+];
+
+/// Readings at named cells
+pub struct GaugeWriter {
+    gauges: Vec<(String, usize)>,
+    file: Option<File>,
+    table: Option<ParquetWriter>,
+}
+
+impl GaugeWriter {
+    pub fn create(
+        tables: &Tables,
+        solver: &ShallowWaterSolver,
+        gauges: Vec<(String, usize)>,
+    ) -> Self {
+        let mut writer = GaugeWriter {
+            gauges,
+            file: tables.create_csv("gauges", "time,gauge,depth,level,u,v"),
+            table: tables.create_parquet("gauges", &GAUGE_SCHEMA),
+        };
+        writer.write(solver);
+        writer
+    }
+
+    fn write(&mut self, solver: &ShallowWaterSolver) {
+        let readings = gauge_readings(solver, &self.gauges);
+        if let Some(file) = &mut self.file {
+            let mut rows = String::new();
+            for ((name, _), [h, level, u, v]) in self.gauges.iter().zip(&readings) {
+                rows += &format!("{},{},{},{},{},{}\n", solver.time, name, h, level, u, v);
+            }
+            file.write_all(rows.as_bytes()).unwrap();
+        }
+        for ((name, _), &[h, level, u, v]) in self.gauges.iter().zip(&readings) {
+            let row = [
+                Value::Double(solver.time),
+                Value::Text(name.clone()),
+                Value::Double(h),
+                Value::Double(level),
+                Value::Double(u),
+                Value::Double(v),
+            ];
+            push_row(&mut self.table, &row);
+        }
+    }
+}
+
+impl Observer for GaugeWriter {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        if event.schedule.due(OutputStream::Gauges) {
+            self.write(solver);
+        }
+    }
+
+    fn finish(self: Box<Self>, _solver: &ShallowWaterSolver) -> Result<(), String> {
+        finish_table(self.table)
+    }
+}
+
+const PROFILE_SCHEMA: [(&str, ColumnType); 10] = [
+    ("time", ColumnType::Double),
+    ("profile", ColumnType::Text),
+    ("distance", ColumnType::Double),
+    ("x", ColumnType::Double),
+    ("y", ColumnType::Double),
+    ("bed", ColumnType::Double),
+    ("depth", ColumnType::Double),
+    ("level", ColumnType::Double),
+    ("u", ColumnType::Double),
+    ("v", ColumnType::Double),
+];
+
+/// Samples along profile lines
+pub struct ProfileWriter {
+    profiles: Vec<Profile>,
+    file: Option<File>,
+    table: Option<ParquetWriter>,
+}
+
+impl ProfileWriter {
+    pub fn create(tables: &Tables, solver: &ShallowWaterSolver, profiles: Vec<Profile>) -> Self {
+        let mut writer = ProfileWriter {
+            profiles,
+            file: tables.create_csv("profiles", "time,profile,distance,x,y,bed,depth,level,u,v"),
+            table: tables.create_parquet("profiles", &PROFILE_SCHEMA),
+        };
+        writer.write(solver);
+        writer
+    }
+
+    fn write(&mut self, solver: &ShallowWaterSolver) {
+        let mut rows = String::new();
+        for profile in &self.profiles {
+            for (s, state) in profile.sample(solver) {
+                let (u, v) = state.velocity;
+                rows += &format!(
+                    "{},{},{},{},{},{},{},{},{},{}\n",
+                    solver.time,
+                    profile.name,
+                    s.distance,
+                    s.x,
+                    s.y,
+                    state.bed,
+                    state.depth,
+                    state.surface,
+                    u,
+                    v
+                );
+                let row = [
+                    Value::Double(solver.time),
+                    Value::Text(profile.name.clone()),
+                    Value::Double(s.distance),
+                    Value::Double(s.x),
+                    Value::Double(s.y),
+                    Value::Double(state.bed),
+                    Value::Double(state.depth),
+                    Value::Double(state.surface),
+                    Value::Double(u),
+                    Value::Double(v),
+                ];
+                push_row(&mut self.table, &row);
+            }
+        }
+        if let Some(file) = &mut self.file {
+            file.write_all(rows.as_bytes()).unwrap();
+        }
+    }
+}
+
+impl Observer for ProfileWriter {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        if event.schedule.due(OutputStream::Profiles) {
+            self.write(solver);
+        }
+    }
+
+    fn finish(self: Box<Self>, _solver: &ShallowWaterSolver) -> Result<(), String> {
+        finish_table(self.table)
+    }
+}
+
+const RUNUP_SCHEMA: [(&str, ColumnType); 8] = [
+    ("time", ColumnType::Double),
+    ("transect", ColumnType::Text),
+    ("distance", ColumnType::Double),
+    ("x", ColumnType::Double),
+    ("y", ColumnType::Double),
+    ("elevation", ColumnType::Double),
+    ("runup", ColumnType::Double),
+    ("inundation", ColumnType::Double),
+];
+
+/// Shoreline tracking along the runup transects, observed every step so
+/// that the maxima are not limited to output times
+pub struct RunupWriter {
+    tracker: RunupTracker,
+    records: Vec<(String, Option<RunupRecord>)>,
+    file: Option<File>,
+    table: Option<ParquetWriter>,
+}
+
+impl RunupWriter {
+    pub fn create(tables: &Tables, solver: &ShallowWaterSolver, mut tracker: RunupTracker) -> Self {
+        let mut writer = RunupWriter {
+            records: tracker.observe(solver),
+            tracker,
+            file: tables.create_csv(
+                "runup",
+                "time,transect,distance,x,y,elevation,runup,inundation",
+            ),
+            table: tables.create_parquet("runup", &RUNUP_SCHEMA),
+        };
+        writer.write(solver.time);
+        writer
+    }
+
+    fn write(&mut self, time: f64) {
+        let mut rows = String::new();
+        for (name, record) in &self.records {
+            let Some(record) = record else {
+                continue;
+            };
+            let s = record.shoreline;
+            rows += &format!(
+                "{},{},{},{},{},{},{},{}\n",
+                time, name, s.distance, s.x, s.y, s.elevation, record.runup, record.inundation
+            );
+            let row = [
+                Value::Double(time),
+                Value::Text(name.clone()),
+                Value::Double(s.distance),
+                Value::Double(s.x),
+                Value::Double(s.y),
+                Value::Double(s.elevation),
+                Value::Double(record.runup),
+                Value::Double(record.inundation),
+            ];
+            push_row(&mut self.table, &row);
+        }
+        if let Some(file) = &mut self.file {
+            file.write_all(rows.as_bytes()).unwrap();
+        }
+    }
+}
+
+impl Observer for RunupWriter {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        self.records = self.tracker.observe(solver);
+        if event.schedule.due(OutputStream::Runup) {
+            self.write(solver.time);
+        }
+    }
+
+    fn finish(self: Box<Self>, _solver: &ShallowWaterSolver) -> Result<(), String> {
+        for (name, runup, inundation) in self.tracker.maxima() {
+            match (runup, inundation) {
+                (Some(runup), Some(inundation)) => println!(
+                    "  Runup {}: max {:.3} m at t = {:.3}s, max inundation {:.3} m at t = {:.3}s",
+                    name, runup.runup, runup.time, inundation.inundation, inundation.time
+                ),
+                _ => println!("  Runup {}: no shoreline found", name),
+            }
+        }
+        finish_table(self.table)
+    }
+}
+
+/// Record into a results database, dropping it after the first failure
+#[cfg(feature = "sqlite")]
+pub fn record_results(
+    results: &mut Option<ResultsDatabase>,
+    record: impl FnOnce(&mut ResultsDatabase) -> Result<(), String>,
+) {
+    if let Some(database) = results {
+        if let Err(e) = record(database) {
+            eprintln!("Warning: Could not write results database: {}", e);
+            *results = None;
+        }
+    }
+}
+
+/// Gauge readings by name, as the results database stores them
+#[cfg(feature = "sqlite")]
+pub fn named_readings<'a>(
+    solver: &ShallowWaterSolver,
+    gauges: &'a [(String, usize)],
+) -> Vec<(&'a str, [f64; 4])> {
+    gauges
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .zip(gauge_readings(solver, gauges))
+        .collect()
+}
+
+/// Statistics and gauge readings in a results database
+#[cfg(feature = "sqlite")]
+pub struct ResultsRecorder<'a> {
+    pub results: &'a mut Option<ResultsDatabase>,
+    pub gauges: &'a [(String, usize)],
+}
+
+#[cfg(feature = "sqlite")]
+impl Observer for ResultsRecorder<'_> {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        if let Some(budget) = event
+            .budget
+            .filter(|_| event.schedule.due(OutputStream::Statistics))
+        {
+            record_results(self.results, |database| {
+                database.push_statistics(&budget.budget(solver))
+            });
+        }
+        if event.schedule.due(OutputStream::Gauges) && !self.gauges.is_empty() {
+            record_results(self.results, |database| {
+                database.push_gauges(solver.time, &named_readings(solver, self.gauges))
+            });
+        }
+    }
+}
+
+/// Mesh output format written at the times of its stream
+pub struct StreamWriter<F> {
+    pub stream: OutputStream,
+    pub what: &'static str, // Unit written, for warnings
+    pub write: F,
+}
+
+impl<F: FnMut(&ShallowWaterSolver) -> std::io::Result<()>> Observer for StreamWriter<F> {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        if event.schedule.due(self.stream) {
+            if let Err(e) = (self.write)(solver) {
+                eprintln!("Warning: Could not write {}: {}", self.what, e);
+            }
+        }
+    }
+}
+
+/// Asymmetry of the solution under symmetry transformations, logged at
+/// the common outputs; the worst values stay in the checks for
+/// `report_symmetry`
+pub struct SymmetryLog<'a> {
+    checks: &'a mut [SymmetryCheck],
+    file: Option<File>,
+}
+
+impl<'a> SymmetryLog<'a> {
+    /// Log to the CSV file `filename`, starting with the current state
+    pub fn create(
+        filename: &str,
+        solver: &ShallowWaterSolver,
+        checks: &'a mut [SymmetryCheck],
+    ) -> Self {
+        let mut log = SymmetryLog { checks, file: None };
+        match File::create(filename) {
+            Ok(mut file) => {
+                writeln!(file, "time,symmetry,depth_max,depth_rms,momentum_max").unwrap();
+                log.file = Some(file);
+                log.write(solver);
+            }
+            Err(e) => eprintln!("Warning: Could not write output file {}: {}", filename, e),
+        }
+        log
+    }
+
+    pub fn is_logged(&self) -> bool {
+        self.file.is_some()
+    }
+
+    fn write(&mut self, solver: &ShallowWaterSolver) {
+        let asymmetries: Vec<Asymmetry> = self
+            .checks
+            .iter_mut()
+            .map(|check| check.observe(solver))
+            .collect();
+        if let Some(file) = &mut self.file {
+            for (check, asymmetry) in self.checks.iter().zip(&asymmetries) {
+                writeln!(
+                    file,
+                    "{},{},{:e},{:e},{:e}",
+                    solver.time,
+                    check.symmetry.name(),
+                    asymmetry.depth_max,
+                    asymmetry.depth_rms,
+                    asymmetry.momentum_max
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+impl Observer for SymmetryLog<'_> {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        if event.common {
+            self.write(solver);
+        } else {
+            for check in self.checks.iter_mut() {
+                check.observe(solver);
+            }
+        }
+    }
+}
+
+/// Print the largest asymmetry of every check; false if one exceeds
+/// `tolerance`
+pub fn report_symmetry(checks: &[SymmetryCheck], tolerance: f64) -> bool {
+    let mut passed = true;
+    for check in checks {
+        let worst = check.worst;
+        let ok = worst.depth_max <= tolerance && worst.momentum_max <= tolerance;
+        passed &= ok;
+        println!(
+            "  Symmetry {}: max depth asymmetry {:.3e} m (RMS {:.3e} m), max momentum \
+             asymmetry {:.3e} m^2/s  {}",
+            check.symmetry.name(),
+            worst.depth_max,
+            worst.depth_rms,
+            worst.momentum_max,
+            if ok { "PASS" } else { "FAIL" }
+        );
+    }
+    passed
+}
+
+/// Stops the run at the first broken invariant, saving the offending state
+/// to <prefix>_invariant.vtk
+pub struct InvariantCheck {
+    pub max_velocity: f64, // Largest plausible speed (m/s)
+    pub prefix: String,
+    steps: usize,
+}
+
+impl InvariantCheck {
+    pub fn new(max_velocity: f64, prefix: &str) -> Self {
+        InvariantCheck {
+            max_velocity,
+            prefix: prefix.to_string(),
+            steps: 0,
+        }
+    }
+
+    /// The violation in the state after `step`, once that state is saved
+    pub fn check(&self, solver: &ShallowWaterSolver, step: usize) -> Result<(), String> {
+        let Err(violation) = solver.check_invariants(self.max_velocity) else {
+            return Ok(());
+        };
+        let filename = format!("{}_invariant.vtk", self.prefix);
+        let frame = Frame::capture(solver, &OutputField::DEFAULT);
+        match output::save_vtk(&filename, &solver.mesh, &frame) {
+            Ok(()) => eprintln!("State at the violation saved to {}", filename),
+            Err(e) => eprintln!("Warning: Could not write output file {}: {}", filename, e),
+        }
+        Err(format!(
+            "broken after step {} (t = {:.6}s): {}",
+            step, solver.time, violation
+        ))
+    }
+}
+
+impl Coupling for InvariantCheck {
+    fn name(&self) -> String {
+        "invariant".to_string()
+    }
+
+    fn after_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        self.steps += 1;
+        self.check(solver, self.steps)
+    }
+}
+
+/// Stops a run once the time step or the depth is no longer finite
+pub struct DivergenceGuard;
+
+impl Coupling for DivergenceGuard {
+    fn name(&self) -> String {
+        "divergence check".to_string()
+    }
+
+    fn after_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        if !solver.dt.is_finite() || solver.state.h.iter().any(|h| !h.is_finite()) {
+            return Err("the solution diverged".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The time step and the cell limiting it, every step
+pub struct TimestepLog {
+    file: std::io::BufWriter<File>,
+    filename: String,
+    counts: Vec<usize>, // Steps limited by each cell
+}
+
+impl TimestepLog {
+    /// Log to <prefix>_timestep.csv, None if it cannot be created
+    pub fn create(prefix: &str, solver: &ShallowWaterSolver) -> Option<Self> {
+        let filename = format!("{}_timestep.csv", prefix);
+        match File::create(&filename) {
+            Ok(file) => {
+                let mut file = std::io::BufWriter::new(file);
+                writeln!(file, "step,time,dt,cell,x,y,courant").unwrap();
+                Some(TimestepLog {
+                    file,
+                    filename,
+                    counts: vec![0; solver.mesh.triangles.len()],
+                })
+            }
+            Err(e) => {
+                eprintln!("Warning: Could not write output file {}: {}", filename, e);
+                None
+            }
+        }
+    }
+}
+
+impl Observer for TimestepLog {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        let (cell, courant) = solver.limiting_cell();
+        let (x, y) = solver.mesh.triangles[cell].centroid;
+        self.counts[cell] += 1;
+        writeln!(
+            self.file,
+            "{},{},{},{},{},{},{}",
+            event.step, solver.time, solver.dt, cell, x, y, courant
+        )
+        .unwrap();
+    }
+
+    fn finish(self: Box<Self>, solver: &ShallowWaterSolver) -> Result<(), String> {
+        let counts = &self.counts;
+        let steps: usize = counts.iter().sum();
+        let mut cells: Vec<usize> = (0..counts.len()).filter(|&i| counts[i] > 0).collect();
+        cells.sort_by(|&a, &b| counts[b].cmp(&counts[a]).then(a.cmp(&b)));
+        println!();
+        println!("Time step limited by:");
+        for &cell in cells.iter().take(5) {
+            let (x, y) = solver.mesh.triangles[cell].centroid;
+            println!(
+                "  cell {:>7} at ({:.3}, {:.3}): {} steps ({:.1}%)",
+                cell,
+                x,
+                y,
+                counts[cell],
+                counts[cell] as f64 / steps.max(1) as f64 * 100.0
+            );
+        }
+        println!("Time step log saved to {}", self.filename);
+        Ok(())
+    }
+}
+
+/// Where the time went since the previous common output
+pub struct OutputProfile {
+    start: (Instant, PhaseTimes),
+}
+
+impl OutputProfile {
+    pub fn new(solver: &ShallowWaterSolver) -> Self {
+        OutputProfile {
+            start: (Instant::now(), solver.profiler.snapshot()),
+        }
+    }
+}
+
+impl Observer for OutputProfile {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        if event.common {
+            let times = solver.profiler.snapshot();
+            let wall = self.start.0.elapsed().as_secs_f64();
+            println!(
+                "    {:.2}s: {}",
+                wall,
+                times.since(&self.start.1).summary(wall)
+            );
+            self.start = (Instant::now(), times);
+        }
+    }
+}
+
+/// Largest depth in the mesh over a run
+pub struct PeakDepth(pub f64);
+
+impl Observer for PeakDepth {
+    fn observe(&mut self, solver: &ShallowWaterSolver, _event: &StepEvent) {
+        self.0 = solver.state.h.iter().copied().fold(self.0, f64::max);
+    }
+}
@@ -0,0 +1,703 @@
+/// Output of solution snapshots
+/// Field selection is shared by all writers so that every backend emits the
+/// same set of cell arrays. Frames are captured on the solver thread and may
+/// be written in the background. The VTK frames can be restricted to output
+/// regions, optionally with a sparse sample of the cells elsewhere.
+use crate::mesh::{point_in_polygon, Node, Polygon, TriangularMesh};
+use crate::simulation::{Observer, StepEvent};
+use crate::solver::ShallowWaterSolver;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Cell data arrays that can be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum OutputField {
+    Height,
+    Velocity,
+    MomentumX,
+    MomentumY,
+    Bed,
+    Surface,
+    FroudeNumber,
+    Vorticity,
+    UnitDischarge,
+    BedShearStress,
+    Courant,
+    SedimentConcentration,
+    BedChange,
+    Scalar,
+    Partition,
+    Color,
+}
+
+impl OutputField {
+    /// Primary fields written when no selection is given; derived fields
+    /// are computed at save time only when requested
+    pub const DEFAULT: [OutputField; 6] = [
+        OutputField::Height,
+        OutputField::Velocity,
+        OutputField::MomentumX,
+        OutputField::MomentumY,
+        OutputField::Bed,
+        OutputField::Surface,
+    ];
+
+    /// Parse a field name; short aliases match the usual symbols
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "h" | "height" | "depth" => Ok(OutputField::Height),
+            "vel" | "velocity" | "uv" => Ok(OutputField::Velocity),
+            "hu" | "momentum_x" => Ok(OutputField::MomentumX),
+            "hv" | "momentum_y" => Ok(OutputField::MomentumY),
+            "z" | "bed" | "bed_elevation" => Ok(OutputField::Bed),
+            "eta" | "surface" | "water_surface" => Ok(OutputField::Surface),
+            "fr" | "froude" | "froude_number" => Ok(OutputField::FroudeNumber),
+            "vort" | "vorticity" => Ok(OutputField::Vorticity),
+            "q" | "discharge" | "unit_discharge" => Ok(OutputField::UnitDischarge),
+            "tau" | "bed_shear" | "bed_shear_stress" => Ok(OutputField::BedShearStress),
+            "cfl" | "courant" | "courant_number" => Ok(OutputField::Courant),
+            "sed" | "sediment" | "sediment_concentration" => Ok(OutputField::SedimentConcentration),
+            "dzb" | "bed_change" => Ok(OutputField::BedChange),
+            "scalar" | "temperature" | "salinity" => Ok(OutputField::Scalar),
+            "part" | "partition" => Ok(OutputField::Partition),
+            "color" | "colour" => Ok(OutputField::Color),
+            other => Err(format!(
+                "unknown output field '{}' (expected h, vel, hu, hv, bed, eta, fr, vort, q, tau, \
+                 cfl, sed, dzb, scalar, partition, color)",
+                other
+            )),
+        }
+    }
+
+    /// Array name in the written files
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputField::Height => "height",
+            OutputField::Velocity => "velocity",
+            OutputField::MomentumX => "momentum_x",
+            OutputField::MomentumY => "momentum_y",
+            OutputField::Bed => "bed_elevation",
+            OutputField::Surface => "water_surface",
+            OutputField::FroudeNumber => "froude_number",
+            OutputField::Vorticity => "vorticity",
+            OutputField::UnitDischarge => "unit_discharge",
+            OutputField::BedShearStress => "bed_shear_stress",
+            OutputField::Courant => "courant_number",
+            OutputField::SedimentConcentration => "sediment_concentration",
+            OutputField::BedChange => "bed_change",
+            OutputField::Scalar => "scalar",
+            OutputField::Partition => "partition",
+            OutputField::Color => "color",
+        }
+    }
+}
+
+impl From<OutputField> for String {
+    fn from(field: OutputField) -> Self {
+        field.name().to_string()
+    }
+}
+
+impl TryFrom<String> for OutputField {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        OutputField::parse(&name)
+    }
+}
+
+/// Parse a comma-separated field list such as "h,vel,eta"
+/// Duplicates are dropped; the order of first appearance is kept.
+pub fn parse_field_list(list: &str) -> Result<Vec<OutputField>, String> {
+    let mut fields = Vec::new();
+    for name in list.split(',').filter(|s| !s.trim().is_empty()) {
+        let field = OutputField::parse(name)?;
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    if fields.is_empty() {
+        return Err("empty output field list".to_string());
+    }
+    Ok(fields)
+}
+
+/// Values of one output array
+#[derive(Debug, Clone)]
+pub enum FieldData {
+    Scalar(Vec<f64>),
+    Vector(Vec<(f64, f64)>),
+}
+
+/// Snapshot of the selected output arrays at one time
+/// Capturing copies everything the writers need, so a frame can be written
+/// while the solver continues to advance.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub time: f64,
+    pub arrays: Vec<(OutputField, FieldData)>,
+}
+
+impl Frame {
+    /// The frame with only `cells`, in that order
+    pub fn select(&self, cells: &[usize]) -> Self {
+        Frame {
+            time: self.time,
+            arrays: self
+                .arrays
+                .iter()
+                .map(|(field, data)| {
+                    let data = match data {
+                        FieldData::Scalar(values) => {
+                            FieldData::Scalar(cells.iter().map(|&i| values[i]).collect())
+                        }
+                        FieldData::Vector(values) => {
+                            FieldData::Vector(cells.iter().map(|&i| values[i]).collect())
+                        }
+                    };
+                    (*field, data)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn capture(solver: &ShallowWaterSolver, fields: &[OutputField]) -> Self {
+        Frame {
+            time: solver.time,
+            arrays: fields
+                .iter()
+                .map(|&field| (field, field_data(solver, field)))
+                .collect(),
+        }
+    }
+}
+
+fn field_data(solver: &ShallowWaterSolver, field: OutputField) -> FieldData {
+    let state = &solver.state;
+    let triangles = &solver.mesh.triangles;
+    FieldData::Scalar(match field {
+        OutputField::Velocity => {
+            return FieldData::Vector(
+                (0..triangles.len())
+                    .map(|i| state.get_velocity(i, &solver.constants))
+                    .collect(),
+            )
+        }
+        OutputField::Height => state.h.clone(),
+        OutputField::MomentumX => state.hu.clone(),
+        OutputField::MomentumY => state.hv.clone(),
+        OutputField::Bed => triangles.iter().map(|t| t.z_bed).collect(),
+        OutputField::Surface => triangles
+            .iter()
+            .zip(&state.h)
+            .map(|(t, h)| t.z_bed + h)
+            .collect(),
+        OutputField::FroudeNumber => solver.froude_numbers(),
+        OutputField::Vorticity => solver.vorticity(),
+        OutputField::UnitDischarge => solver.unit_discharge(),
+        OutputField::BedShearStress => solver.bed_shear_stress(),
+        OutputField::Courant => solver.courant_numbers(),
+        OutputField::SedimentConcentration => solver.sediment_concentration(),
+        OutputField::BedChange => match &solver.sediment {
+            Some(sediment) => sediment.bed_change.clone(),
+            None => vec![0.0; triangles.len()],
+        },
+        OutputField::Scalar => solver.scalar_field(),
+        OutputField::Partition => {
+            as_scalar(solver.mesh.block_partition(rayon::current_num_threads()))
+        }
+        OutputField::Color => as_scalar(solver.mesh.greedy_coloring()),
+    })
+}
+
+fn as_scalar(labels: Vec<usize>) -> Vec<f64> {
+    labels.into_iter().map(|l| l as f64).collect()
+}
+
+/// Write a legacy ASCII VTK unstructured grid with the frame's cell arrays
+pub fn write_vtk<W: Write>(out: &mut W, mesh: &TriangularMesh, frame: &Frame) -> io::Result<()> {
+    let n_cells = mesh.triangles.len();
+
+    // Write VTK file format for visualization in ParaView or similar
+    writeln!(out, "# vtk DataFile Version 3.0")?;
+    writeln!(out, "Shallow Water Solution at t={:.4}", frame.time)?;
+    writeln!(out, "ASCII")?;
+    writeln!(out, "DATASET UNSTRUCTURED_GRID")?;
+    writeln!(out, "POINTS {} float", mesh.nodes.len())?;
+
+    for node in &mesh.nodes {
+        writeln!(out, "{} {} 0.0", node.x, node.y)?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "CELLS {} {}", n_cells, n_cells * 4)?;
+    for tri in &mesh.triangles {
+        writeln!(out, "3 {} {} {}", tri.nodes[0], tri.nodes[1], tri.nodes[2])?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "CELL_TYPES {}", n_cells)?;
+    for _ in 0..n_cells {
+        writeln!(out, "5")?; // Triangle type
+    }
+
+    writeln!(out)?;
+    writeln!(out, "CELL_DATA {}", n_cells)?;
+
+    for (field, data) in &frame.arrays {
+        match data {
+            FieldData::Vector(values) => {
+                writeln!(out, "VECTORS {} float", field.name())?;
+                for (u, v) in values {
+                    writeln!(out, "{} {} 0.0", u, v)?;
+                }
+            }
+            FieldData::Scalar(values) => {
+                writeln!(out, "SCALARS {} float 1", field.name())?;
+                writeln!(out, "LOOKUP_TABLE default")?;
+                for value in values {
+                    writeln!(out, "{}", value)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write a VTK snapshot to `filename`
+pub fn save_vtk(filename: &str, mesh: &TriangularMesh, frame: &Frame) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_vtk(&mut file, mesh, frame)?;
+    file.flush()
+}
+
+/// Cells of the output regions: those whose centroid lies in one of
+/// `regions`, plus, with `outside_spacing`, the first cell of every square
+/// of that size elsewhere (in mesh coordinates)
+pub fn region_cells(
+    mesh: &TriangularMesh,
+    regions: &[Polygon],
+    outside_spacing: Option<f64>,
+) -> Vec<usize> {
+    let mut sampled = std::collections::HashSet::new();
+    (0..mesh.triangles.len())
+        .filter(|&i| {
+            let (x, y) = mesh.triangles[i].centroid;
+            if regions.iter().any(|r| point_in_polygon((x, y), r)) {
+                return true;
+            }
+            outside_spacing
+                .is_some_and(|d| sampled.insert(((x / d).floor() as i64, (y / d).floor() as i64)))
+        })
+        .collect()
+}
+
+/// Mesh of the given cells of `mesh` with only the nodes they use
+pub fn submesh(mesh: &TriangularMesh, cells: &[usize]) -> Result<TriangularMesh, String> {
+    let mut index = vec![usize::MAX; mesh.nodes.len()];
+    let mut nodes = Vec::new();
+    let connectivity: Vec<[usize; 3]> = cells
+        .iter()
+        .map(|&i| {
+            mesh.triangles[i].nodes.map(|n| {
+                if index[n] == usize::MAX {
+                    index[n] = nodes.len();
+                    let node = &mesh.nodes[n];
+                    nodes.push(Node {
+                        x: node.x,
+                        y: node.y,
+                        z: node.z,
+                    });
+                }
+                index[n]
+            })
+        })
+        .collect();
+    TriangularMesh::from_triangles(nodes, &connectivity, mesh.coordinate_system)
+}
+
+/// Default depth above which a cell counts as flooded for the timing maps (m)
+pub const ARRIVAL_DEPTH: f64 = 0.01;
+
+/// Running per-cell maxima and flood timing used for flood maps
+/// A cell is flooded while its depth exceeds `threshold`. The duration sums
+/// the steps at whose end the cell is flooded; times never reached are NaN.
+#[derive(Debug, Clone)]
+pub struct FloodEnvelope {
+    pub threshold: f64,
+    pub max_depth: Vec<f64>,
+    pub max_hazard: Vec<f64>,
+    pub arrival_time: Vec<f64>, // First time flooded (s)
+    pub duration: Vec<f64>,     // Total time flooded (s)
+    pub peak_time: Vec<f64>,    // Time of the maximum depth (s)
+    time: f64,                  // Of the last update
+}
+
+impl FloodEnvelope {
+    pub fn new(solver: &ShallowWaterSolver, threshold: f64) -> Self {
+        let time = solver.time;
+        let flooded = |h: &f64| if *h > threshold { time } else { f64::NAN };
+        FloodEnvelope {
+            threshold,
+            max_depth: solver.state.h.clone(),
+            max_hazard: solver.hazard_rating(),
+            arrival_time: solver.state.h.iter().map(flooded).collect(),
+            duration: vec![0.0; solver.state.h.len()],
+            peak_time: solver.state.h.iter().map(flooded).collect(),
+            time,
+        }
+    }
+
+    pub fn update(&mut self, solver: &ShallowWaterSolver) {
+        let (time, dt) = (solver.time, solver.time - self.time);
+        for (i, &h) in solver.state.h.iter().enumerate() {
+            if h > self.threshold {
+                if self.arrival_time[i].is_nan() {
+                    self.arrival_time[i] = time;
+                }
+                self.duration[i] += dt;
+                if h > self.max_depth[i] || self.peak_time[i].is_nan() {
+                    self.peak_time[i] = time;
+                }
+            }
+            self.max_depth[i] = self.max_depth[i].max(h);
+        }
+        for (max, hazard) in self.max_hazard.iter_mut().zip(solver.hazard_rating()) {
+            *max = max.max(hazard);
+        }
+        self.time = time;
+    }
+}
+
+impl Observer for FloodEnvelope {
+    fn observe(&mut self, solver: &ShallowWaterSolver, _event: &StepEvent) {
+        self.update(solver);
+    }
+}
+
+/// Running per-cell bed shear stress exposure used for scour indicator maps
+/// The stress at the end of every step is held over the step, as for the
+/// flooding duration.
+#[derive(Debug, Clone)]
+pub struct ShearExposure {
+    pub critical: f64,        // Critical bed shear stress τ_cr (Pa)
+    pub integral: Vec<f64>,   // Time integral of τ_b (Pa s)
+    pub exceedance: Vec<f64>, // Time with τ_b > τ_cr (s)
+    pub max_shear: Vec<f64>,  // Maximum τ_b (Pa)
+    start: f64,
+    time: f64, // Of the last update
+}
+
+impl ShearExposure {
+    pub fn new(solver: &ShallowWaterSolver, critical: f64) -> Self {
+        let cells = solver.mesh.triangles.len();
+        ShearExposure {
+            critical,
+            integral: vec![0.0; cells],
+            exceedance: vec![0.0; cells],
+            max_shear: solver.bed_shear_stress(),
+            start: solver.time,
+            time: solver.time,
+        }
+    }
+
+    pub fn update(&mut self, solver: &ShallowWaterSolver) {
+        let dt = solver.time - self.time;
+        for (i, tau) in solver.bed_shear_stress().into_iter().enumerate() {
+            self.integral[i] += tau * dt;
+            if tau > self.critical {
+                self.exceedance[i] += dt;
+            }
+            self.max_shear[i] = self.max_shear[i].max(tau);
+        }
+        self.time = solver.time;
+    }
+
+    /// Fraction of the time since the start with τ_b > τ_cr
+    pub fn exceedance_fraction(&self) -> Vec<f64> {
+        let elapsed = self.time - self.start;
+        self.exceedance
+            .iter()
+            .map(|t| if elapsed > 0.0 { t / elapsed } else { 0.0 })
+            .collect()
+    }
+}
+
+impl Observer for ShearExposure {
+    fn observe(&mut self, solver: &ShallowWaterSolver, _event: &StepEvent) {
+        self.update(solver);
+    }
+}
+
+/// Writes frames on a dedicated thread so the time loop does not wait on I/O
+/// At most `queue_capacity` frames are buffered; `write` blocks when the
+/// queue is full (backpressure). A capacity of zero writes synchronously.
+pub struct FrameWriter {
+    mesh: Arc<TriangularMesh>,
+    cells: Option<Vec<usize>>, // Cells written, the whole mesh if None
+    sender: Option<SyncSender<(String, Frame)>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl FrameWriter {
+    pub fn new(mesh: TriangularMesh, queue_capacity: usize) -> Self {
+        let mesh = Arc::new(mesh);
+        if queue_capacity == 0 {
+            return FrameWriter {
+                mesh,
+                cells: None,
+                sender: None,
+                worker: None,
+            };
+        }
+
+        let (sender, receiver) = mpsc::sync_channel::<(String, Frame)>(queue_capacity);
+        let worker_mesh = Arc::clone(&mesh);
+        let worker = thread::spawn(move || {
+            for (filename, frame) in receiver {
+                write_or_warn(&filename, &worker_mesh, &frame);
+            }
+        });
+
+        FrameWriter {
+            mesh,
+            cells: None,
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    /// Writer of only `cells` of `mesh` (output regions)
+    pub fn restricted(
+        mesh: &TriangularMesh,
+        cells: Vec<usize>,
+        queue_capacity: usize,
+    ) -> Result<Self, String> {
+        let mut writer = FrameWriter::new(submesh(mesh, &cells)?, queue_capacity);
+        writer.cells = Some(cells);
+        Ok(writer)
+    }
+
+    pub fn write(&self, filename: String, frame: Frame) {
+        let frame = match &self.cells {
+            Some(cells) => frame.select(cells),
+            None => frame,
+        };
+        match &self.sender {
+            Some(sender) => {
+                if let Err(mpsc::SendError((filename, frame))) = sender.send((filename, frame)) {
+                    // Writer thread is gone; fall back to writing here
+                    write_or_warn(&filename, &self.mesh, &frame);
+                }
+            }
+            None => write_or_warn(&filename, &self.mesh, &frame),
+        }
+    }
+
+    /// Flush all queued frames and stop the writer thread
+    pub fn finish(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                eprintln!("Warning: output writer thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for FrameWriter {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn write_or_warn(filename: &str, mesh: &TriangularMesh, frame: &Frame) {
+    if let Err(e) = save_vtk(filename, mesh, frame) {
+        eprintln!("Warning: Could not write output file {}: {}", filename, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_parse_field_list() {
+        let fields = parse_field_list("h, vel,eta,h").unwrap();
+        assert_eq!(
+            fields,
+            vec![
+                OutputField::Height,
+                OutputField::Velocity,
+                OutputField::Surface
+            ]
+        );
+        assert!(parse_field_list("h,speed").is_err());
+        assert!(parse_field_list("").is_err());
+    }
+
+    #[test]
+    fn test_vtk_writes_only_selected_fields() {
+        let mesh = TriangularMesh::new_rectangular(3, 3, 1.0, 1.0, TopographyType::Flat);
+        let solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+
+        let frame = Frame::capture(&solver, &[OutputField::Height, OutputField::Velocity]);
+        let mut buffer = Vec::new();
+        write_vtk(&mut buffer, &solver.mesh, &frame).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+
+        assert!(text.contains("SCALARS height float 1"));
+        assert!(text.contains("VECTORS velocity float"));
+        assert!(!text.contains("momentum_x"));
+        assert!(!text.contains("water_surface"));
+        assert_eq!(text.matches("LOOKUP_TABLE").count(), 1);
+    }
+
+    #[test]
+    fn test_derived_fields_are_opt_in() {
+        assert!(!OutputField::DEFAULT.contains(&OutputField::FroudeNumber));
+        assert_eq!(
+            parse_field_list("fr,vort,q,tau").unwrap(),
+            vec![
+                OutputField::FroudeNumber,
+                OutputField::Vorticity,
+                OutputField::UnitDischarge,
+                OutputField::BedShearStress
+            ]
+        );
+
+        let mesh = TriangularMesh::new_rectangular(3, 3, 1.0, 1.0, TopographyType::Flat);
+        let solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        let frame = Frame::capture(&solver, &[OutputField::FroudeNumber]);
+        let mut buffer = Vec::new();
+        write_vtk(&mut buffer, &solver.mesh, &frame).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.contains("SCALARS froude_number float 1"));
+    }
+
+    #[test]
+    fn test_background_writer_flushes_queue() {
+        let mesh = TriangularMesh::new_rectangular(4, 4, 1.0, 1.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        let dir = std::env::temp_dir().join(format!("swe_writer_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for capacity in [0, 1] {
+            let writer = FrameWriter::new(solver.mesh.clone(), capacity);
+            for index in 0..5 {
+                solver.time = index as f64;
+                let frame = Frame::capture(&solver, &[OutputField::Height]);
+                let path = dir.join(format!("frame_{}_{}.vtk", capacity, index));
+                writer.write(path.to_string_lossy().into_owned(), frame);
+            }
+            writer.finish();
+
+            for index in 0..5 {
+                let path = dir.join(format!("frame_{}_{}.vtk", capacity, index));
+                let text = std::fs::read_to_string(path).unwrap();
+                assert!(text.contains(&format!("t={:.4}", index as f64)));
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_output_regions() {
+        let mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for (i, h) in solver.state.h.iter_mut().enumerate() {
+            *h = i as f64;
+        }
+        let corner = vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        let cells = region_cells(&solver.mesh, std::slice::from_ref(&corner), None);
+        assert_eq!(cells.len(), 8);
+        // One more cell in each of the four 5 m squares
+        let sparse = region_cells(&solver.mesh, &[corner], Some(5.0));
+        assert_eq!(sparse.len(), 8 + 4);
+
+        let sub = submesh(&solver.mesh, &cells).unwrap();
+        assert_eq!((sub.triangles.len(), sub.nodes.len()), (8, 9));
+        let frame = Frame::capture(&solver, &[OutputField::Height]).select(&cells);
+        let mut buffer = Vec::new();
+        write_vtk(&mut buffer, &sub, &frame).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.contains("POINTS 9 float") && text.contains("CELL_DATA 8"));
+        let FieldData::Scalar(h) = &frame.arrays[0].1 else {
+            panic!("height is a scalar");
+        };
+        assert!(h.iter().zip(&cells).all(|(&h, &i)| h == i as f64));
+    }
+
+    #[test]
+    fn test_flood_envelope_keeps_maxima() {
+        let mesh = TriangularMesh::new_rectangular(3, 3, 1.0, 1.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.iter_mut().for_each(|h| *h = 2.0);
+        let mut envelope = FloodEnvelope::new(&solver, ARRIVAL_DEPTH);
+
+        solver.state.h[0] = 3.0;
+        solver.state.h[1] = 1.0;
+        envelope.update(&solver);
+
+        assert_eq!(envelope.max_depth[0], 3.0);
+        assert_eq!(envelope.max_depth[1], 2.0);
+        assert_eq!(envelope.max_hazard[0], 1.5);
+    }
+
+    #[test]
+    fn test_flood_envelope_timing() {
+        let mesh = TriangularMesh::new_rectangular(3, 3, 1.0, 1.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h[0] = 0.5;
+        let mut envelope = FloodEnvelope::new(&solver, 0.1);
+        assert_eq!(envelope.arrival_time[0], 0.0);
+        assert!(envelope.arrival_time[1].is_nan());
+
+        // Cell 1 floods at t = 1, peaks at t = 2 and dries at t = 3
+        for (time, depth) in [(1.0, 0.2), (2.0, 0.4), (3.0, 0.05), (4.0, 0.3)] {
+            solver.time = time;
+            solver.state.h[1] = depth;
+            envelope.update(&solver);
+        }
+        assert_eq!(envelope.arrival_time[1], 1.0);
+        assert_eq!(envelope.peak_time[1], 2.0);
+        assert_eq!(envelope.duration[1], 3.0);
+        assert_eq!(envelope.duration[0], 4.0);
+        assert_eq!(envelope.peak_time[0], 0.0);
+        assert!(envelope.arrival_time[2].is_nan() && envelope.duration[2] == 0.0);
+    }
+
+    #[test]
+    fn test_shear_exposure() {
+        let mesh = TriangularMesh::new_rectangular(3, 3, 1.0, 1.0, TopographyType::Flat);
+        let mut solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Manning { coefficient: 0.03 });
+        solver.state.h.iter_mut().for_each(|h| *h = 1.0);
+        let mut exposure = ShearExposure::new(&solver, 5.0);
+        // rho g n² u² / h^(1/3) = 1000 * 9.81 * 0.0009 * u² Pa
+        for (time, u) in [(1.0, 0.5), (2.0, 1.0), (4.0, 0.5)] {
+            solver.time = time;
+            solver.state.hu[0] = u;
+            exposure.update(&solver);
+        }
+        let tau = |u: f64| 1000.0 * 9.81 * 0.0009 * u * u;
+        let expected = tau(0.5) + tau(1.0) + 2.0 * tau(0.5);
+        assert!((exposure.integral[0] - expected).abs() < 1e-9 * expected);
+        assert!((exposure.max_shear[0] - tau(1.0)).abs() < 1e-9);
+        // u = 1 m/s (8.8 Pa) exceeds 5 Pa for 1 s of 4, u = 0.5 m/s (2.2 Pa) not
+        assert_eq!(exposure.exceedance_fraction()[0], 0.25);
+        assert_eq!(exposure.exceedance_fraction()[1], 0.0);
+    }
+}
@@ -0,0 +1,543 @@
+/// Apache Parquet tables
+/// Rows are buffered and written in row groups of up to `ROW_GROUP_ROWS`
+/// rows, each column chunk as one PLAIN-encoded data page compressed with
+/// gzip. Columns are required (no nulls) doubles, 64-bit integers or UTF-8
+/// strings. Every chunk records min/max statistics, so polars, duckdb and
+/// pyarrow can skip row groups when filtering, e.g. on time or gauge name.
+/// The file metadata (Thrift compact protocol) is written by `finish`;
+/// a writer that is dropped without it leaves an unreadable file.
+use crate::compress;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Rows per row group
+pub const ROW_GROUP_ROWS: usize = 65536;
+
+const MAGIC: &[u8; 4] = b"PAR1";
+
+/// Physical type of a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Double,
+    Int64,
+    Text,
+}
+
+/// One value of a row
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Double(f64),
+    Int64(i64),
+    Text(String),
+}
+
+/// (min, max) of a column chunk, PLAIN-encoded
+type Statistics = Option<(Vec<u8>, Vec<u8>)>;
+
+/// Values of one column in the open row group
+enum ColumnData {
+    Double(Vec<f64>),
+    Int64(Vec<i64>),
+    Text(Vec<String>),
+}
+
+impl ColumnData {
+    fn new(kind: ColumnType) -> Self {
+        match kind {
+            ColumnType::Double => ColumnData::Double(Vec::new()),
+            ColumnType::Int64 => ColumnData::Int64(Vec::new()),
+            ColumnType::Text => ColumnData::Text(Vec::new()),
+        }
+    }
+
+    /// PLAIN encoding and the (min, max) statistics, if defined
+    fn encode(&self) -> (Vec<u8>, Statistics) {
+        match self {
+            ColumnData::Double(values) => {
+                let bytes = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+                let statistics =
+                    (!values.is_empty() && values.iter().all(|v| !v.is_nan())).then(|| {
+                        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                        (min.to_le_bytes().to_vec(), max.to_le_bytes().to_vec())
+                    });
+                (bytes, statistics)
+            }
+            ColumnData::Int64(values) => {
+                let bytes = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+                let statistics = values
+                    .iter()
+                    .min()
+                    .zip(values.iter().max())
+                    .map(|(min, max)| (min.to_le_bytes().to_vec(), max.to_le_bytes().to_vec()));
+                (bytes, statistics)
+            }
+            ColumnData::Text(values) => {
+                let mut bytes = Vec::new();
+                for value in values {
+                    bytes.extend((value.len() as u32).to_le_bytes());
+                    bytes.extend(value.as_bytes());
+                }
+                let statistics = values
+                    .iter()
+                    .min()
+                    .zip(values.iter().max())
+                    .map(|(min, max)| (min.as_bytes().to_vec(), max.as_bytes().to_vec()));
+                (bytes, statistics)
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            ColumnData::Double(values) => values.clear(),
+            ColumnData::Int64(values) => values.clear(),
+            ColumnData::Text(values) => values.clear(),
+        }
+    }
+}
+
+/// Location and sizes of a written column chunk
+struct ChunkInfo {
+    offset: u64,
+    uncompressed: usize, // Page header and data
+    compressed: usize,
+    statistics: Statistics,
+}
+
+/// Writes one Parquet file row by row
+pub struct ParquetWriter {
+    file: BufWriter<File>,
+    offset: u64,
+    schema: Vec<(String, ColumnType)>,
+    columns: Vec<ColumnData>,
+    rows: usize,                              // Rows in the open row group
+    row_groups: Vec<(usize, Vec<ChunkInfo>)>, // Rows and chunks of each written group
+}
+
+impl ParquetWriter {
+    /// Create `path` with the named columns
+    pub fn create(path: &str, schema: &[(&str, ColumnType)]) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        Ok(ParquetWriter {
+            file,
+            offset: MAGIC.len() as u64,
+            schema: schema
+                .iter()
+                .map(|&(name, kind)| (name.to_string(), kind))
+                .collect(),
+            columns: schema
+                .iter()
+                .map(|&(_, kind)| ColumnData::new(kind))
+                .collect(),
+            rows: 0,
+            row_groups: Vec::new(),
+        })
+    }
+
+    /// Append a row with one value of the matching type per column
+    pub fn push(&mut self, row: &[Value]) -> io::Result<()> {
+        let matches = row.len() == self.columns.len()
+            && row.iter().zip(&self.columns).all(|(value, column)| {
+                matches!(
+                    (value, column),
+                    (Value::Double(_), ColumnData::Double(_))
+                        | (Value::Int64(_), ColumnData::Int64(_))
+                        | (Value::Text(_), ColumnData::Text(_))
+                )
+            });
+        if !matches {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "row does not match the table columns",
+            ));
+        }
+        for (value, column) in row.iter().zip(&mut self.columns) {
+            match (value, column) {
+                (Value::Double(v), ColumnData::Double(values)) => values.push(*v),
+                (Value::Int64(v), ColumnData::Int64(values)) => values.push(*v),
+                (Value::Text(v), ColumnData::Text(values)) => values.push(v.clone()),
+                _ => unreachable!(),
+            }
+        }
+        self.rows += 1;
+        if self.rows == ROW_GROUP_ROWS {
+            self.flush_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn flush_row_group(&mut self) -> io::Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let mut chunks = Vec::with_capacity(self.columns.len());
+        for column in &mut self.columns {
+            let (plain, statistics) = column.encode();
+            let data = compress::gzip(&plain);
+            let mut header = Thrift::default();
+            header.i32(1, 0); // DATA_PAGE
+            header.i32(2, plain.len() as i32);
+            header.i32(3, data.len() as i32);
+            header.begin_struct(5); // DataPageHeader
+            header.i32(1, self.rows as i32);
+            header.i32(2, 0); // PLAIN
+            header.i32(3, 3); // RLE (no levels for required columns)
+            header.i32(4, 3);
+            header.end_struct();
+            let header = header.finish();
+            self.file.write_all(&header)?;
+            self.file.write_all(&data)?;
+            chunks.push(ChunkInfo {
+                offset: self.offset,
+                uncompressed: header.len() + plain.len(),
+                compressed: header.len() + data.len(),
+                statistics,
+            });
+            self.offset += (header.len() + data.len()) as u64;
+            column.clear();
+        }
+        self.row_groups.push((self.rows, chunks));
+        self.rows = 0;
+        Ok(())
+    }
+
+    /// Write the remaining rows and the file metadata
+    pub fn finish(mut self) -> io::Result<()> {
+        self.flush_row_group()?;
+        let mut meta = Thrift::default();
+        meta.i32(1, 1); // Version
+        meta.list(2, STRUCT, self.schema.len() + 1);
+        meta.begin_element();
+        meta.binary(4, b"schema");
+        meta.i32(5, self.schema.len() as i32);
+        meta.end_struct();
+        for (name, kind) in &self.schema {
+            meta.begin_element();
+            meta.i32(1, physical_type(*kind));
+            meta.i32(3, 0); // REQUIRED
+            meta.binary(4, name.as_bytes());
+            if *kind == ColumnType::Text {
+                meta.i32(6, 0); // UTF8
+                meta.begin_struct(10); // LogicalType
+                meta.begin_struct(1); // STRING
+                meta.end_struct();
+                meta.end_struct();
+            }
+            meta.end_struct();
+        }
+        let rows: usize = self.row_groups.iter().map(|(rows, _)| rows).sum();
+        meta.i64(3, rows as i64);
+        meta.list(4, STRUCT, self.row_groups.len());
+        for (rows, chunks) in &self.row_groups {
+            meta.begin_element();
+            meta.list(1, STRUCT, chunks.len());
+            for (chunk, (name, kind)) in chunks.iter().zip(&self.schema) {
+                meta.begin_element();
+                meta.i64(2, chunk.offset as i64);
+                meta.begin_struct(3); // ColumnMetaData
+                meta.i32(1, physical_type(*kind));
+                meta.list(2, I32, 2);
+                meta.element_i32(0); // PLAIN
+                meta.element_i32(3); // RLE
+                meta.list(3, BINARY, 1);
+                meta.element_binary(name.as_bytes());
+                meta.i32(4, 2); // GZIP
+                meta.i64(5, *rows as i64);
+                meta.i64(6, chunk.uncompressed as i64);
+                meta.i64(7, chunk.compressed as i64);
+                meta.i64(9, chunk.offset as i64);
+                meta.begin_struct(12); // Statistics
+                meta.i64(3, 0); // Null count
+                if let Some((min, max)) = &chunk.statistics {
+                    meta.binary(5, max);
+                    meta.binary(6, min);
+                }
+                meta.end_struct();
+                meta.end_struct();
+                meta.end_struct();
+            }
+            let size: usize = chunks.iter().map(|c| c.uncompressed).sum();
+            meta.i64(2, size as i64);
+            meta.i64(3, *rows as i64);
+            meta.end_struct();
+        }
+        meta.binary(6, b"shallow-water-solver");
+        let meta = meta.finish();
+        self.file.write_all(&meta)?;
+        self.file.write_all(&(meta.len() as u32).to_le_bytes())?;
+        self.file.write_all(MAGIC)?;
+        self.file.flush()
+    }
+}
+
+fn physical_type(kind: ColumnType) -> i32 {
+    match kind {
+        ColumnType::Int64 => 2,
+        ColumnType::Double => 5,
+        ColumnType::Text => 6, // BYTE_ARRAY
+    }
+}
+
+// Thrift compact protocol type codes
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+/// Thrift compact protocol encoder for the Parquet metadata structs
+#[derive(Default)]
+struct Thrift {
+    bytes: Vec<u8>,
+    field: i16,      // Last field id of the current struct
+    outer: Vec<i16>, // Last field ids of the enclosing structs
+}
+
+impl Thrift {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn zigzag(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn header(&mut self, id: i16, kind: u8) {
+        let delta = id - self.field;
+        if (1..=15).contains(&delta) {
+            self.bytes.push((delta as u8) << 4 | kind);
+        } else {
+            self.bytes.push(kind);
+            self.zigzag(id as i64);
+        }
+        self.field = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.header(id, I32);
+        self.zigzag(value as i64);
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.header(id, I64);
+        self.zigzag(value);
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.header(id, BINARY);
+        self.element_binary(value);
+    }
+
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.header(id, LIST);
+        if len < 15 {
+            self.bytes.push((len as u8) << 4 | kind);
+        } else {
+            self.bytes.push(0xf0 | kind);
+            self.varint(len as u64);
+        }
+    }
+
+    fn element_i32(&mut self, value: i32) {
+        self.zigzag(value as i64);
+    }
+
+    fn element_binary(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.bytes.extend(value);
+    }
+
+    fn begin_struct(&mut self, id: i16) {
+        self.header(id, STRUCT);
+        self.begin_element();
+    }
+
+    /// Struct element of a list
+    fn begin_element(&mut self) {
+        self.outer.push(self.field);
+        self.field = 0;
+    }
+
+    fn end_struct(&mut self) {
+        self.bytes.push(0);
+        self.field = self.outer.pop().unwrap_or(0);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.bytes.push(0);
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress::inflate_gzip;
+
+    /// Minimal Thrift compact reader: (field id, value) of a struct, with
+    /// nested structs and lists flattened into `Vec`s
+    #[derive(Debug, Clone, PartialEq)]
+    enum Field {
+        Int(i64),
+        Bytes(Vec<u8>),
+        List(Vec<Field>),
+        Struct(Vec<(i16, Field)>),
+    }
+
+    fn read_varint(bytes: &[u8], at: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = bytes[*at];
+            *at += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    fn read_value(bytes: &[u8], at: &mut usize, kind: u8) -> Field {
+        match kind {
+            I32 | I64 => {
+                let v = read_varint(bytes, at);
+                Field::Int((v >> 1) as i64 ^ -((v & 1) as i64))
+            }
+            BINARY => {
+                let len = read_varint(bytes, at) as usize;
+                *at += len;
+                Field::Bytes(bytes[*at - len..*at].to_vec())
+            }
+            LIST => {
+                let header = bytes[*at];
+                *at += 1;
+                let len = match header >> 4 {
+                    15 => read_varint(bytes, at) as usize,
+                    len => len as usize,
+                };
+                Field::List(
+                    (0..len)
+                        .map(|_| read_value(bytes, at, header & 15))
+                        .collect(),
+                )
+            }
+            STRUCT => Field::Struct(read_struct(bytes, at)),
+            _ => panic!("unexpected type {}", kind),
+        }
+    }
+
+    fn read_struct(bytes: &[u8], at: &mut usize) -> Vec<(i16, Field)> {
+        let mut fields = Vec::new();
+        let mut id = 0;
+        loop {
+            let header = bytes[*at];
+            *at += 1;
+            if header == 0 {
+                return fields;
+            }
+            id += (header >> 4) as i16;
+            fields.push((id, read_value(bytes, at, header & 15)));
+        }
+    }
+
+    fn get(fields: &[(i16, Field)], id: i16) -> &Field {
+        &fields.iter().find(|(k, _)| *k == id).unwrap().1
+    }
+
+    #[test]
+    fn test_parquet_layout() {
+        let path = std::env::temp_dir().join(format!("swe_parquet_{}.parquet", std::process::id()));
+        let path = path.to_str().unwrap();
+        let schema = [
+            ("time", ColumnType::Double),
+            ("gauge", ColumnType::Text),
+            ("step", ColumnType::Int64),
+        ];
+        let mut writer = ParquetWriter::create(path, &schema).unwrap();
+        let rows = ROW_GROUP_ROWS + 3;
+        for k in 0..rows {
+            let name = ["g2", "g1"][k % 2].to_string();
+            let row = [
+                Value::Double(0.5 * k as f64),
+                Value::Text(name),
+                Value::Int64(k as i64),
+            ];
+            writer.push(&row).unwrap();
+        }
+        assert!(writer.push(&[Value::Double(1.0)]).is_err());
+        writer.finish().unwrap();
+
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(&bytes[bytes.len() - 4..], MAGIC);
+        let len = u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap());
+        let mut at = bytes.len() - 8 - len as usize;
+        let meta = read_struct(&bytes, &mut at);
+        assert_eq!(at, bytes.len() - 8);
+        assert_eq!(get(&meta, 3), &Field::Int(rows as i64));
+        let Field::List(groups) = get(&meta, 4) else {
+            panic!()
+        };
+        assert_eq!(groups.len(), 2);
+
+        // Last row group: three rows starting at time 0.5 * ROW_GROUP_ROWS
+        let Field::Struct(group) = &groups[1] else {
+            panic!()
+        };
+        assert_eq!(get(group, 3), &Field::Int(3));
+        let Field::List(chunks) = get(group, 1) else {
+            panic!()
+        };
+        let Field::Struct(chunk) = &chunks[0] else {
+            panic!()
+        };
+        let Field::Struct(column) = get(chunk, 3) else {
+            panic!()
+        };
+        let Field::Int(offset) = get(column, 9) else {
+            panic!()
+        };
+        let mut at = *offset as usize;
+        let page = read_struct(&bytes, &mut at);
+        let Field::Int(size) = get(&page, 3) else {
+            panic!()
+        };
+        let plain = inflate_gzip(&bytes[at..at + *size as usize]);
+        let first = f64::from_le_bytes(plain[..8].try_into().unwrap());
+        assert_eq!(first, 0.5 * ROW_GROUP_ROWS as f64);
+        let Field::Struct(statistics) = get(column, 12) else {
+            panic!()
+        };
+        assert_eq!(
+            get(statistics, 6),
+            &Field::Bytes(first.to_le_bytes().to_vec())
+        );
+
+        // Text statistics of the first group
+        let Field::Struct(group) = &groups[0] else {
+            panic!()
+        };
+        let Field::List(chunks) = get(group, 1) else {
+            panic!()
+        };
+        let Field::Struct(chunk) = &chunks[1] else {
+            panic!()
+        };
+        let Field::Struct(column) = get(chunk, 3) else {
+            panic!()
+        };
+        let Field::Struct(statistics) = get(column, 12) else {
+            panic!()
+        };
+        assert_eq!(get(statistics, 5), &Field::Bytes(b"g2".to_vec()));
+        assert_eq!(get(statistics, 6), &Field::Bytes(b"g1".to_vec()));
+    }
+}
@@ -0,0 +1,297 @@
+/// Step plugins loaded at run time (feature "plugins", Unix)
+/// A plugin is a shared library with a small C ABI that runs custom logic
+/// before and after every time step, e.g. operating rules of gates and pumps,
+/// without recompiling the solver. It exports any of
+///
+///   int32_t swe_plugin_init(const char *config, void **user);
+///   int32_t swe_plugin_pre_step(SwePluginState *state);
+///   int32_t swe_plugin_post_step(SwePluginState *state);
+///   void swe_plugin_finish(void *user);
+///
+/// (`include/swe_plugin.h`), at least one of the step functions. The state
+/// exposes the cell arrays in place: the plugin reads the bed and geometry
+/// and may change depth and momentum. A nonzero return stops the run with
+/// that code. The volume a plugin adds or removes is checked (no negative or
+/// non-finite depths) and booked as coupled volume in the mass ledger.
+use crate::mesh::TriangularMesh;
+use crate::simulation::Coupling;
+use crate::solver::ShallowWaterSolver;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+/// View of the solver passed to the step functions
+#[repr(C)]
+pub struct SwePluginState {
+    pub time: f64,         // Simulation time (s)
+    pub dt: f64,           // Last time step (s)
+    pub cells: usize,      // Length of every array
+    pub h: *mut f64,       // Depth (m), writable
+    pub hu: *mut f64,      // x-momentum (m^2/s), writable
+    pub hv: *mut f64,      // y-momentum (m^2/s), writable
+    pub z_bed: *const f64, // Bed elevation of each cell (m)
+    pub x: *const f64,     // Cell centroids
+    pub y: *const f64,
+    pub area: *const f64,  // Water-holding cell area (m^2)
+    pub user: *mut c_void, // Set by swe_plugin_init
+}
+
+pub type InitFn = unsafe extern "C" fn(config: *const c_char, user: *mut *mut c_void) -> i32;
+pub type StepFn = unsafe extern "C" fn(state: *mut SwePluginState) -> i32;
+pub type FinishFn = unsafe extern "C" fn(user: *mut c_void);
+
+/// Entry points of a plugin; missing ones are skipped
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PluginFunctions {
+    pub init: Option<InitFn>,
+    pub pre_step: Option<StepFn>,
+    pub post_step: Option<StepFn>,
+    pub finish: Option<FinishFn>,
+}
+
+#[cfg_attr(target_os = "linux", link(name = "dl"))]
+extern "C" {
+    fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlclose(handle: *mut c_void) -> c_int;
+    fn dlerror() -> *mut c_char;
+}
+
+const RTLD_NOW: c_int = 2;
+
+/// A loaded plugin
+pub struct Plugin {
+    pub name: String,
+    functions: PluginFunctions,
+    library: *mut c_void, // dlopen handle, null for in-process functions
+    user: *mut c_void,
+    centroids: (Vec<f64>, Vec<f64>),
+    pub added_volume: f64, // Net volume the plugin put into the domain (m^3)
+}
+
+/// Last dynamic-linker error
+fn last_dl_error() -> String {
+    // SAFETY: dlerror returns null or a NUL-terminated string
+    let message = unsafe { dlerror() };
+    if message.is_null() {
+        "unknown error".to_string()
+    } else {
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+impl Plugin {
+    /// Load the shared library `path` and initialize it with `config`
+    pub fn load(path: &str, config: &str, mesh: &TriangularMesh) -> Result<Self, String> {
+        let filename = CString::new(path).map_err(|_| format!("{}: invalid path", path))?;
+        // SAFETY: loading a library runs its initializers; the user asked for it
+        let library = unsafe { dlopen(filename.as_ptr(), RTLD_NOW) };
+        if library.is_null() {
+            return Err(last_dl_error());
+        }
+        let symbol = |name: &str| {
+            let name = CString::new(name).unwrap();
+            // SAFETY: the handle is open
+            let address = unsafe { dlsym(library, name.as_ptr()) };
+            (!address.is_null()).then_some(address)
+        };
+        // SAFETY: the symbols are declared with these signatures by the ABI
+        let functions = unsafe {
+            PluginFunctions {
+                init: symbol("swe_plugin_init").map(|p| std::mem::transmute::<_, InitFn>(p)),
+                pre_step: symbol("swe_plugin_pre_step")
+                    .map(|p| std::mem::transmute::<_, StepFn>(p)),
+                post_step: symbol("swe_plugin_post_step")
+                    .map(|p| std::mem::transmute::<_, StepFn>(p)),
+                finish: symbol("swe_plugin_finish").map(|p| std::mem::transmute::<_, FinishFn>(p)),
+            }
+        };
+        let name = std::path::Path::new(path)
+            .file_stem()
+            .map_or(path.to_string(), |s| s.to_string_lossy().into_owned());
+        let mut plugin = Self::new(&name, functions, config, mesh);
+        if let Ok(plugin) = &mut plugin {
+            plugin.library = library;
+        } else {
+            // SAFETY: nothing from the library is kept
+            unsafe { dlclose(library) };
+        }
+        plugin
+    }
+
+    /// Plugin from functions of this process, initialized with `config`
+    pub fn new(
+        name: &str,
+        functions: PluginFunctions,
+        config: &str,
+        mesh: &TriangularMesh,
+    ) -> Result<Self, String> {
+        if functions.pre_step.is_none() && functions.post_step.is_none() {
+            return Err(format!(
+                "{} exports neither swe_plugin_pre_step nor swe_plugin_post_step",
+                name
+            ));
+        }
+        let mut user = std::ptr::null_mut();
+        if let Some(init) = functions.init {
+            let config = CString::new(config).map_err(|_| "invalid plugin configuration")?;
+            // SAFETY: both pointers are valid for the call
+            let code = unsafe { init(config.as_ptr(), &mut user) };
+            if code != 0 {
+                return Err(format!("{}: swe_plugin_init returned {}", name, code));
+            }
+        }
+        Ok(Plugin {
+            name: name.to_string(),
+            functions,
+            library: std::ptr::null_mut(),
+            user,
+            centroids: mesh.triangles.iter().map(|t| t.centroid).unzip(),
+            added_volume: 0.0,
+        })
+    }
+
+    pub fn pre_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        self.call(self.functions.pre_step, "swe_plugin_pre_step", solver)
+    }
+
+    pub fn post_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        self.call(self.functions.post_step, "swe_plugin_post_step", solver)
+    }
+
+    fn call(
+        &mut self,
+        function: Option<StepFn>,
+        stage: &str,
+        solver: &mut ShallowWaterSolver,
+    ) -> Result<(), String> {
+        let Some(function) = function else {
+            return Ok(());
+        };
+        let cells = solver.mesh.triangles.len();
+        let area: Vec<f64> = (0..cells).map(|i| solver.storage_area(i)).collect();
+        let z_bed: Vec<f64> = solver.mesh.triangles.iter().map(|t| t.z_bed).collect();
+        let volume = |h: &[f64]| h.iter().zip(&area).map(|(h, a)| h * a).sum::<f64>();
+        let before = volume(&solver.state.h);
+
+        let mut state = SwePluginState {
+            time: solver.time,
+            dt: solver.dt,
+            cells,
+            h: solver.state.h.as_mut_ptr(),
+            hu: solver.state.hu.as_mut_ptr(),
+            hv: solver.state.hv.as_mut_ptr(),
+            z_bed: z_bed.as_ptr(),
+            x: self.centroids.0.as_ptr(),
+            y: self.centroids.1.as_ptr(),
+            area: area.as_ptr(),
+            user: self.user,
+        };
+        // SAFETY: every array holds `cells` values and outlives the call
+        let code = unsafe { function(&mut state) };
+        if code != 0 {
+            return Err(format!("{}: {} returned {}", self.name, stage, code));
+        }
+        let state = &mut solver.state;
+        if let Some(i) = (0..cells).find(|&i| {
+            !(state.h[i] >= 0.0
+                && state.h[i].is_finite()
+                && state.hu[i].is_finite()
+                && state.hv[i].is_finite())
+        }) {
+            return Err(format!(
+                "{}: {} left an invalid state in cell {}",
+                self.name, stage, i
+            ));
+        }
+        let added = volume(&solver.state.h) - before;
+        self.added_volume += added;
+        solver.record_coupled_volume(added);
+        Ok(())
+    }
+}
+
+impl Coupling for Plugin {
+    fn name(&self) -> String {
+        format!("plugin {}", self.name)
+    }
+
+    fn before_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        self.pre_step(solver)
+    }
+
+    fn after_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        self.post_step(solver)
+    }
+
+    fn report(&self) -> Vec<String> {
+        vec![format!(
+            "Plugin {}: {:+.3} m^3 added",
+            self.name, self.added_volume
+        )]
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        if let Some(finish) = self.functions.finish {
+            // SAFETY: `user` is the pointer set by the plugin's init
+            unsafe { finish(self.user) };
+        }
+        if !self.library.is_null() {
+            // SAFETY: no function of the library is called after this
+            unsafe { dlclose(self.library) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+    use crate::solver::FrictionLaw;
+
+    /// Keeps the first cell at a depth of 1 m (a level boundary of sorts)
+    unsafe extern "C" fn hold_first_cell(state: *mut SwePluginState) -> i32 {
+        let state = &mut *state;
+        *state.h = 1.0;
+        0
+    }
+
+    unsafe extern "C" fn fail(_: *mut SwePluginState) -> i32 {
+        7
+    }
+
+    #[test]
+    fn test_plugin_steps() {
+        let mesh = TriangularMesh::new_rectangular(6, 3, 5.0, 2.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.enable_mass_exchange().unwrap();
+        let functions = PluginFunctions {
+            post_step: Some(hold_first_cell),
+            ..Default::default()
+        };
+        let mut plugin = Plugin::new("hold", functions, "", &solver.mesh).unwrap();
+        for _ in 0..5 {
+            plugin.pre_step(&mut solver).unwrap();
+            solver.step();
+            plugin.post_step(&mut solver).unwrap();
+        }
+        assert_eq!(solver.state.h[0], 1.0);
+        let area = solver.mesh.triangles[0].area;
+        assert!(plugin.added_volume > area && plugin.added_volume < 5.0 * area);
+        let ledger = solver.mass_exchange.as_ref().unwrap();
+        assert!((ledger.coupled - plugin.added_volume).abs() < 1e-12);
+        assert!((solver.compute_total_mass() - plugin.added_volume).abs() < 1e-9);
+
+        let failing = PluginFunctions {
+            pre_step: Some(fail),
+            ..Default::default()
+        };
+        let mut plugin = Plugin::new("fail", failing, "", &solver.mesh).unwrap();
+        let error = plugin.pre_step(&mut solver).unwrap_err();
+        assert_eq!(error, "fail: swe_plugin_pre_step returned 7");
+        assert!(Plugin::new("none", PluginFunctions::default(), "", &solver.mesh).is_err());
+        assert!(Plugin::load("/nonexistent/libplugin.so", "", &solver.mesh).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+/// Terminal preview of the depth field
+/// A quick sanity check for runs on headless machines: the depth is resampled
+/// onto a character grid and printed with 24-bit ANSI colors using half-block
+/// characters (two pixels per character cell), or as plain ASCII shading for
+/// terminals and log files without color support.
+use crate::raster::{self, RasterGrid, NODATA};
+use crate::render::{self, RenderField, LAND};
+use crate::solver::ShallowWaterSolver;
+use std::fmt::Write;
+
+/// Shading ramp from shallow to deep for the ASCII mode
+const ASCII_RAMP: &[u8] = b".:-=+*#%@";
+const ASCII_DRY: char = ' ';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewMode {
+    /// Colored half blocks (needs a 24-bit color terminal)
+    Color,
+    /// Plain characters
+    Ascii,
+}
+
+impl PreviewMode {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "color" | "colour" => Ok(PreviewMode::Color),
+            "ascii" => Ok(PreviewMode::Ascii),
+            other => Err(format!(
+                "unknown preview mode '{}' (expected color, ascii)",
+                other
+            )),
+        }
+    }
+}
+
+/// Render the current depth as terminal text `width` characters wide
+pub fn preview(solver: &ShallowWaterSolver, mode: PreviewMode, width: usize) -> String {
+    let mesh = &solver.mesh;
+    let (x_min, x_max) = mesh
+        .nodes
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), n| {
+            (lo.min(n.x), hi.max(n.x))
+        });
+    // Character cells are about twice as tall as wide: one pixel per half cell
+    let grid = RasterGrid::covering(mesh, Some((x_max - x_min) / width.max(1) as f64));
+    let depth = raster::rasterize(mesh, &solver.state.h, &grid);
+
+    let dry = solver.constants.dry_tolerance as f32;
+    let max_depth = depth.iter().copied().fold(0.0f32, f32::max);
+    let scale = |h: f32| {
+        if max_depth > 0.0 {
+            (h / max_depth) as f64
+        } else {
+            0.0
+        }
+    };
+    let pixel = |col: usize, row: usize| depth.get(row * grid.ncols + col).copied();
+
+    let stops = RenderField::Depth.colormap();
+    let color = |h: Option<f32>| match h {
+        None | Some(NODATA) => None,
+        Some(h) if h <= dry => Some(LAND),
+        Some(h) => Some(render::color_at(stops, scale(h))),
+    };
+
+    let mut text = String::new();
+    for row in (0..grid.nrows).step_by(2) {
+        for col in 0..grid.ncols {
+            let (top, bottom) = (pixel(col, row), pixel(col, row + 1));
+            match mode {
+                PreviewMode::Color => match (color(top), color(bottom)) {
+                    (Some(t), Some(b)) => write!(
+                        text,
+                        "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                        t[0], t[1], t[2], b[0], b[1], b[2]
+                    ),
+                    (Some(t), None) => {
+                        write!(text, "\x1b[0m\x1b[38;2;{};{};{}m\u{2580}", t[0], t[1], t[2])
+                    }
+                    (None, Some(b)) => {
+                        write!(text, "\x1b[0m\x1b[38;2;{};{};{}m\u{2584}", b[0], b[1], b[2])
+                    }
+                    (None, None) => write!(text, "\x1b[0m "),
+                }
+                .unwrap(),
+                PreviewMode::Ascii => text.push(match top {
+                    None | Some(NODATA) => ' ',
+                    Some(h) if h <= dry => ASCII_DRY,
+                    Some(h) => {
+                        let k = (scale(h) * (ASCII_RAMP.len() - 1) as f64).round() as usize;
+                        ASCII_RAMP[k.min(ASCII_RAMP.len() - 1)] as char
+                    }
+                }),
+            }
+        }
+        if mode == PreviewMode::Color {
+            text.push_str("\x1b[0m");
+        }
+        text.push('\n');
+    }
+
+    match mode {
+        PreviewMode::Color => {
+            let _ = write!(text, "depth 0 ");
+            for k in 0..16 {
+                let c = render::color_at(stops, k as f64 / 15.0);
+                let _ = write!(text, "\x1b[48;2;{};{};{}m ", c[0], c[1], c[2]);
+            }
+            let _ = writeln!(
+                text,
+                "\x1b[0m {:.3} m   t = {:.3} s",
+                max_depth, solver.time
+            );
+        }
+        PreviewMode::Ascii => {
+            let _ = writeln!(
+                text,
+                "depth 0 {} {:.3} m   t = {:.3} s",
+                std::str::from_utf8(ASCII_RAMP).unwrap(),
+                max_depth,
+                solver.time
+            );
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_ascii_preview_shows_wet_and_dry_halves() {
+        let mesh = TriangularMesh::new_rectangular(21, 21, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+            solver.state.h[i] = if tri.centroid.0 < 5.0 { 2.0 } else { 0.0 };
+        }
+
+        let text = preview(&solver, PreviewMode::Ascii, 40);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 20 + 1); // 40 pixel rows, two per line, plus legend
+        for line in &lines[..20] {
+            assert_eq!(line.len(), 40);
+            assert!(line[..18].chars().all(|c| c == '@'));
+            assert!(line[22..].chars().all(|c| c == ASCII_DRY));
+        }
+        assert!(lines[20].contains("2.000 m"));
+    }
+
+    #[test]
+    fn test_color_preview_uses_half_blocks() {
+        let mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(1.0);
+
+        let text = preview(&solver, PreviewMode::Color, 20);
+        assert_eq!(text.matches('\u{2580}').count(), 20 * 10);
+        assert!(PreviewMode::parse("braille").is_err());
+    }
+}
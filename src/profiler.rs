@@ -0,0 +1,156 @@
+/// Wall-clock timers for the phases of a run
+/// The solver records the time spent computing the time step, fluxes,
+/// source terms, the state update and (semi-implicit) linear solves; the
+/// driver records output. Totals are atomic so that methods taking `&self`
+/// can record, and nothing is timed while the profiler is disabled.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Timestep,
+    Flux,
+    Sources,
+    Update,
+    LinearSolve,
+    Output,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::Timestep,
+        Phase::Flux,
+        Phase::Sources,
+        Phase::Update,
+        Phase::LinearSolve,
+        Phase::Output,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Timestep => "timestep",
+            Phase::Flux => "flux",
+            Phase::Sources => "sources",
+            Phase::Update => "update",
+            Phase::LinearSolve => "linear solve",
+            Phase::Output => "output",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+    pub enabled: bool,
+    nanos: [AtomicU64; 6], // Indexed like Phase::ALL
+}
+
+impl Profiler {
+    /// Start time of a timed section (None while disabled)
+    pub fn start(&self) -> Option<Instant> {
+        self.enabled.then(Instant::now)
+    }
+
+    /// Add the time elapsed since `start` to `phase`
+    pub fn record(&self, phase: Phase, start: Option<Instant>) {
+        if let Some(start) = start {
+            self.nanos[phase as usize]
+                .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Totals recorded so far
+    pub fn snapshot(&self) -> PhaseTimes {
+        PhaseTimes {
+            seconds: self
+                .nanos
+                .each_ref()
+                .map(|n| n.load(Ordering::Relaxed) as f64 * 1e-9),
+        }
+    }
+}
+
+/// Seconds spent per phase
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseTimes {
+    pub seconds: [f64; 6], // Indexed like Phase::ALL
+}
+
+impl PhaseTimes {
+    /// Time spent between an earlier snapshot and this one
+    pub fn since(&self, earlier: &PhaseTimes) -> PhaseTimes {
+        let mut seconds = self.seconds;
+        for (s, e) in seconds.iter_mut().zip(&earlier.seconds) {
+            *s -= e;
+        }
+        PhaseTimes { seconds }
+    }
+
+    /// Breakdown table against the wall-clock time `wall` (s); time not
+    /// covered by any phase is listed as "other"
+    pub fn report(&self, wall: f64) -> String {
+        let percent = |s: f64| if wall > 0.0 { s / wall * 100.0 } else { 0.0 };
+        let mut table = String::new();
+        for (phase, &s) in Phase::ALL.iter().zip(&self.seconds) {
+            table += &format!(
+                "  {:<14} {:>10.3} s {:>6.1}%\n",
+                phase.name(),
+                s,
+                percent(s)
+            );
+        }
+        let other = (wall - self.seconds.iter().sum::<f64>()).max(0.0);
+        table += &format!(
+            "  {:<14} {:>10.3} s {:>6.1}%\n",
+            "other",
+            other,
+            percent(other)
+        );
+        table += &format!("  {:<14} {:>10.3} s\n", "total", wall);
+        table
+    }
+
+    /// One-line summary of the phases above 1% of `wall`
+    pub fn summary(&self, wall: f64) -> String {
+        let parts: Vec<String> = Phase::ALL
+            .iter()
+            .zip(&self.seconds)
+            .filter(|(_, &s)| wall > 0.0 && s / wall >= 0.01)
+            .map(|(phase, &s)| format!("{} {:.0}%", phase.name(), s / wall * 100.0))
+            .collect();
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiler_records_only_when_enabled() {
+        let mut profiler = Profiler::default();
+        let start = profiler.start();
+        assert!(start.is_none());
+        profiler.record(Phase::Flux, start);
+        assert_eq!(profiler.snapshot(), PhaseTimes::default());
+
+        profiler.enabled = true;
+        let start = profiler.start();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        profiler.record(Phase::Output, start);
+        let times = profiler.snapshot();
+        assert!(times.seconds[Phase::Output as usize] >= 0.005);
+        assert_eq!(times.seconds[Phase::Flux as usize], 0.0);
+        assert_eq!(times.since(&times), PhaseTimes::default());
+
+        let report = times.report(1.0);
+        assert!(report.contains("output"));
+        assert!(report.contains("other"));
+        assert_eq!(
+            PhaseTimes {
+                seconds: [0.5, 0.0, 0.0, 0.0, 0.0, 0.005]
+            }
+            .summary(1.0),
+            "timestep 50%"
+        );
+    }
+}
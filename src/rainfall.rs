@@ -0,0 +1,323 @@
+/// Gridded rainfall input
+/// Radar or forecast rainfall is read as a series of intensity frames on a
+/// regular grid, either from a list of ESRI ASCII grids or from a NetCDF
+/// cube (time, y, x), and interpolated bilinearly between pixel centres to
+/// the cell centroids. Frame times are seconds from the start of the run;
+/// in a NetCDF file they are taken relative to the first frame. Missing
+/// pixels and cells outside the grid receive no rain.
+use crate::mesh::TriangularMesh;
+use crate::netcdf::NetcdfFile;
+use crate::raster::{self, RasterGrid};
+use crate::solver::GriddedRainfall;
+use std::path::Path;
+
+/// Rainfall frames in mm/h on a common grid (row-major, row 0 north)
+#[derive(Debug, Clone)]
+pub struct RainfallGrid {
+    pub times: Vec<f64>,
+    pub grid: RasterGrid,
+    pub frames: Vec<Vec<f64>>,
+}
+
+/// Read a NetCDF cube if the path ends in ".nc", otherwise a raster list
+pub fn read_rainfall(path: &str, variable: Option<&str>) -> Result<RainfallGrid, String> {
+    if path.ends_with(".nc") {
+        read_netcdf(path, variable)
+    } else {
+        read_raster_list(path)
+    }
+}
+
+/// Read a text file of "TIME FILE" lines (seconds, ESRI ASCII grid of
+/// intensity in mm/h); relative paths are relative to the list
+pub fn read_raster_list(path: &str) -> Result<RainfallGrid, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let base = Path::new(path).parent().unwrap_or(Path::new(""));
+    let mut times = Vec::new();
+    let mut frames = Vec::new();
+    let mut grid: Option<RasterGrid> = None;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || format!("{}:{}: expected TIME FILE", path, number + 1);
+        let (time, file) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+        let time: f64 = time.parse().map_err(|_| invalid())?;
+        let (frame_grid, values) =
+            raster::read_ascii_grid(&base.join(file.trim()).to_string_lossy())?;
+        if let Some(grid) = grid {
+            if !same_grid(&grid, &frame_grid) {
+                return Err(format!("{}: {} is on a different grid", path, file.trim()));
+            }
+        }
+        grid = Some(frame_grid);
+        times.push(time);
+        frames.push(values);
+    }
+    let grid = grid.ok_or_else(|| format!("{}: no rainfall frames", path))?;
+    let rainfall = RainfallGrid {
+        times,
+        grid,
+        frames,
+    };
+    rainfall.check().map_err(|e| format!("{}: {}", path, e))?;
+    Ok(rainfall)
+}
+
+/// Read a NetCDF variable with dimensions (time, y, x) and coordinate
+/// variables for all three; without `variable`, the first 3-D variable
+pub fn read_netcdf(path: &str, variable: Option<&str>) -> Result<RainfallGrid, String> {
+    let mut file = NetcdfFile::open(path)?;
+    let rain = match variable {
+        Some(name) => file.variable(name).cloned(),
+        None => file
+            .variables
+            .iter()
+            .find(|v| v.dimensions.len() == 3)
+            .cloned(),
+    }
+    .ok_or_else(|| format!("{}: no rainfall variable (time, y, x)", path))?;
+    if rain.dimensions.len() != 3 {
+        return Err(format!("{}: '{}' is not (time, y, x)", path, rain.name));
+    }
+    let scale = intensity_scale(rain.text("units").unwrap_or("mm/h"))
+        .map_err(|e| format!("{}: '{}': {}", path, rain.name, e))?;
+    let [time_dim, y_dim, x_dim] =
+        [0, 1, 2].map(|k| file.dimensions[rain.dimensions[k]].name.clone());
+    let coordinate = |file: &mut NetcdfFile, name: &str| {
+        file.read(name)
+            .map_err(|_| format!("{}: no coordinate variable '{}'", path, name))
+    };
+    let x = coordinate(&mut file, &x_dim)?;
+    let y = coordinate(&mut file, &y_dim)?;
+    let time_units = file
+        .variable(&time_dim)
+        .and_then(|v| v.text("units"))
+        .unwrap_or("seconds")
+        .to_string();
+    let time_scale = time_scale(&time_units).map_err(|e| format!("{}: {}", path, e))?;
+    let raw_times = coordinate(&mut file, &time_dim)?;
+
+    let spacing = |values: &[f64]| -> Result<f64, String> {
+        let step = values.get(1).map(|v| v - values[0]).unwrap_or(0.0);
+        let regular = values
+            .windows(2)
+            .all(|w| ((w[1] - w[0]) - step).abs() <= 1e-6 * step.abs());
+        if step == 0.0 || !regular {
+            return Err(format!("{}: rainfall grid is not regular", path));
+        }
+        Ok(step)
+    };
+    let (dx, dy) = (spacing(&x)?, spacing(&y)?);
+    if (dx.abs() - dy.abs()).abs() > 1e-6 * dx.abs() {
+        return Err(format!("{}: rainfall pixels are not square", path));
+    }
+    let (ncols, nrows) = (x.len(), y.len());
+    let grid = RasterGrid {
+        x_min: x[0].min(x[ncols - 1]) - 0.5 * dx.abs(),
+        y_max: y[0].max(y[nrows - 1]) + 0.5 * dx.abs(),
+        cell_size: dx.abs(),
+        ncols,
+        nrows,
+    };
+
+    let mut frames = Vec::with_capacity(raw_times.len());
+    for record in 0..raw_times.len() {
+        let values = if file.is_record_variable(&rain) {
+            file.read_record(&rain.name, record)?
+        } else if record == 0 {
+            file.read(&rain.name)?
+        } else {
+            break;
+        };
+        // A fixed time dimension comes back whole; reorder each frame to
+        // row 0 north, column 0 west
+        let frame_len = ncols * nrows;
+        for frame in values.chunks(frame_len) {
+            let pixel = |col: usize, row: usize| {
+                let c = if dx > 0.0 { col } else { ncols - 1 - col };
+                let r = if dy < 0.0 { row } else { nrows - 1 - row };
+                frame[r * ncols + c] * scale
+            };
+            frames.push(
+                (0..frame_len)
+                    .map(|k| pixel(k % ncols, k / ncols))
+                    .collect(),
+            );
+        }
+    }
+    let start = raw_times.first().copied().unwrap_or(0.0);
+    let rainfall = RainfallGrid {
+        times: raw_times.iter().map(|t| (t - start) * time_scale).collect(),
+        grid,
+        frames,
+    };
+    rainfall
+        .check()
+        .map_err(|e| format!("{}: '{}': {}", path, rain.name, e))?;
+    Ok(rainfall)
+}
+
+/// Factor converting a CF intensity unit to mm/h
+fn intensity_scale(units: &str) -> Result<f64, String> {
+    match units.trim() {
+        "mm/h" | "mm/hr" | "mm h-1" | "mm hr-1" | "mm.h-1" => Ok(1.0),
+        "mm/s" | "mm s-1" | "kg m-2 s-1" | "kg/m2/s" => Ok(3600.0),
+        "m/s" | "m s-1" => Ok(3.6e6),
+        other => Err(format!(
+            "unsupported units '{}' (expected an intensity such as mm/h, kg m-2 s-1 or m/s)",
+            other
+        )),
+    }
+}
+
+/// Factor converting CF time units ("hours since ...") to seconds
+fn time_scale(units: &str) -> Result<f64, String> {
+    let unit = units.split_whitespace().next().unwrap_or("");
+    match unit {
+        "seconds" | "second" | "s" => Ok(1.0),
+        "minutes" | "minute" | "min" => Ok(60.0),
+        "hours" | "hour" | "h" => Ok(3600.0),
+        "days" | "day" | "d" => Ok(86400.0),
+        _ => Err(format!("unsupported time units '{}'", units)),
+    }
+}
+
+fn same_grid(a: &RasterGrid, b: &RasterGrid) -> bool {
+    (a.ncols, a.nrows) == (b.ncols, b.nrows)
+        && (a.x_min - b.x_min).abs() <= 1e-9 * a.cell_size
+        && (a.y_max - b.y_max).abs() <= 1e-9 * a.cell_size
+        && (a.cell_size - b.cell_size).abs() <= 1e-9 * a.cell_size
+}
+
+impl RainfallGrid {
+    fn check(&self) -> Result<(), String> {
+        if self.times.is_empty() {
+            return Err("no rainfall frames".to_string());
+        }
+        if self.times.windows(2).any(|w| w[1] <= w[0]) {
+            return Err("frame times must increase".to_string());
+        }
+        if self.frames.iter().flatten().any(|&r| r < 0.0) {
+            return Err("negative rainfall intensity".to_string());
+        }
+        Ok(())
+    }
+
+    /// Intensity (mm/h) at (x, y), bilinear between pixel centres; zero
+    /// outside the grid and on missing pixels
+    fn sample(&self, frame: &[f64], x: f64, y: f64) -> f64 {
+        let grid = &self.grid;
+        let col = (x - grid.x_min) / grid.cell_size - 0.5;
+        let row = (grid.y_max - y) / grid.cell_size - 0.5;
+        if !(-0.5..grid.ncols as f64 - 0.5).contains(&col)
+            || !(-0.5..grid.nrows as f64 - 0.5).contains(&row)
+        {
+            return 0.0;
+        }
+        let clamp = |v: f64, n: usize| v.clamp(0.0, (n - 1) as f64);
+        let (col, row) = (clamp(col, grid.ncols), clamp(row, grid.nrows));
+        let (c0, r0) = (col.floor() as usize, row.floor() as usize);
+        let (c1, r1) = ((c0 + 1).min(grid.ncols - 1), (r0 + 1).min(grid.nrows - 1));
+        let (wc, wr) = (col - c0 as f64, row - r0 as f64);
+        let value = |c: usize, r: usize| {
+            let v = frame[r * grid.ncols + c];
+            if v.is_nan() {
+                0.0
+            } else {
+                v
+            }
+        };
+        (1.0 - wr) * ((1.0 - wc) * value(c0, r0) + wc * value(c1, r0))
+            + wr * ((1.0 - wc) * value(c0, r1) + wc * value(c1, r1))
+    }
+
+    /// Source term with the frames interpolated to the cell centroids
+    pub fn source_term(&self, mesh: &TriangularMesh) -> GriddedRainfall {
+        const MM_PER_HOUR: f64 = 1e-3 / 3600.0;
+        let rates = self
+            .frames
+            .iter()
+            .map(|frame| {
+                mesh.triangles
+                    .iter()
+                    .map(|t| self.sample(frame, t.centroid.0, t.centroid.1) * MM_PER_HOUR)
+                    .collect()
+            })
+            .collect();
+        GriddedRainfall {
+            times: self.times.clone(),
+            rates,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+    use crate::solver::{FrictionLaw, ShallowWaterSolver};
+
+    #[test]
+    fn test_raster_list_interpolation() {
+        let dir = std::env::temp_dir().join(format!("swe_rain_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let frame = |name: &str, west: f64, east: f64| {
+            let row = format!("{} {}\n", west, east);
+            let text = format!(
+                "ncols 2\nnrows 2\nxllcorner 0\nyllcorner 0\ncellsize 5\nNODATA_value -9999\n{}{}",
+                row, row
+            );
+            std::fs::write(dir.join(name), text).unwrap();
+        };
+        frame("a.asc", 0.0, 0.0);
+        frame("b.asc", 36.0, 72.0);
+        let list = dir.join("rain.txt");
+        std::fs::write(&list, "# radar\n0 a.asc\n600 b.asc\n").unwrap();
+        let rainfall = read_rainfall(list.to_str().unwrap(), None).unwrap();
+        std::fs::write(&list, "0 b.asc\n0 a.asc\n").unwrap();
+        assert!(read_raster_list(list.to_str().unwrap()).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 10.0, TopographyType::Flat);
+        let term = rainfall.source_term(&mesh);
+        for (i, tri) in mesh.triangles.iter().enumerate() {
+            // 36 mm/h west of x = 2.5, 72 mm/h east of 7.5, linear between
+            let x = tri.centroid.0.clamp(2.5, 7.5);
+            let expected = (36.0 + 36.0 * (x - 2.5) / 5.0) * 1e-3 / 3600.0;
+            assert!((term.rate(600.0, i) - expected).abs() < 1e-15);
+            assert!((term.rate(300.0, i) - 0.5 * expected).abs() < 1e-15);
+            assert_eq!(term.rate(-1.0, i), 0.0);
+            assert_eq!(term.rate(601.0, i), 0.0);
+        }
+
+        // The water added matches the volume of the event, up to the
+        // first-order sampling of the forcing in time
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(0.01);
+        let initial = solver.compute_total_mass();
+        let areas: Vec<f64> = solver.mesh.triangles.iter().map(|t| t.area).collect();
+        let volume = term.total_volume(&areas);
+        solver.source_terms.push(Box::new(term));
+        while solver.time < 600.0 {
+            solver.step();
+        }
+        let added = solver.compute_total_mass() - initial;
+        assert!(
+            (added - volume).abs() < 0.01 * volume,
+            "{} vs {}",
+            added,
+            volume
+        );
+    }
+
+    #[test]
+    fn test_units() {
+        assert_eq!(intensity_scale("kg m-2 s-1").unwrap(), 3600.0);
+        assert_eq!(intensity_scale("mm h-1").unwrap(), 1.0);
+        assert!(intensity_scale("mm").is_err());
+        assert_eq!(time_scale("minutes since 2024-05-01 00:00").unwrap(), 60.0);
+        assert!(time_scale("months since 2000-01-01").is_err());
+    }
+}
@@ -0,0 +1,522 @@
+/// Georeferenced raster export (GeoTIFF) and import (ESRI ASCII grid)
+/// Cell fields are resampled onto a regular grid in mesh coordinates (each
+/// pixel takes the value of the triangle containing its centre) and written
+/// as single-band 32-bit float GeoTIFF files readable by GDAL, QGIS and
+/// ArcGIS. Input rasters are read from the ASCII grid format all of them
+/// export.
+use crate::mesh::TriangularMesh;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+pub const NODATA: f32 = -9999.0;
+
+/// Fields available for raster export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RasterField {
+    Depth,
+    MaxDepth,
+    Hazard,
+    MaxHazard,
+    ArrivalTime,
+    Duration,
+    PeakTime,
+    ShearIntegral,
+    ShearExceedance,
+    MaxShear,
+}
+
+impl RasterField {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "depth" | "h" => Ok(RasterField::Depth),
+            "max_depth" => Ok(RasterField::MaxDepth),
+            "hazard" => Ok(RasterField::Hazard),
+            "max_hazard" => Ok(RasterField::MaxHazard),
+            "arrival_time" | "arrival" => Ok(RasterField::ArrivalTime),
+            "duration" => Ok(RasterField::Duration),
+            "peak_time" => Ok(RasterField::PeakTime),
+            "shear_integral" => Ok(RasterField::ShearIntegral),
+            "shear_exceedance" => Ok(RasterField::ShearExceedance),
+            "max_shear" => Ok(RasterField::MaxShear),
+            other => Err(format!(
+                "unknown raster field '{}' (expected depth, max-depth, hazard, max-hazard, \
+                 arrival-time, duration, peak-time, shear-integral, shear-exceedance, \
+                 max-shear)",
+                other
+            )),
+        }
+    }
+
+    /// File name suffix
+    pub fn name(&self) -> &'static str {
+        match self {
+            RasterField::Depth => "depth",
+            RasterField::MaxDepth => "max_depth",
+            RasterField::Hazard => "hazard",
+            RasterField::MaxHazard => "max_hazard",
+            RasterField::ArrivalTime => "arrival_time",
+            RasterField::Duration => "duration",
+            RasterField::PeakTime => "peak_time",
+            RasterField::ShearIntegral => "shear_integral",
+            RasterField::ShearExceedance => "shear_exceedance",
+            RasterField::MaxShear => "max_shear",
+        }
+    }
+}
+
+/// Parse a comma-separated raster field list such as "depth,max-depth"
+pub fn parse_raster_fields(list: &str) -> Result<Vec<RasterField>, String> {
+    let mut fields = Vec::new();
+    for name in list.split(',').filter(|s| !s.trim().is_empty()) {
+        let field = RasterField::parse(name)?;
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    Ok(fields)
+}
+
+/// Regular grid; row 0 is the northern (maximum y) edge
+#[derive(Debug, Clone, Copy)]
+pub struct RasterGrid {
+    pub x_min: f64,
+    pub y_max: f64,
+    pub cell_size: f64,
+    pub ncols: usize,
+    pub nrows: usize,
+}
+
+impl RasterGrid {
+    /// Grid covering the mesh bounding box; without an explicit cell size the
+    /// spacing matches the mean triangle size
+    pub fn covering(mesh: &TriangularMesh, cell_size: Option<f64>) -> Self {
+        let (mut x_min, mut x_max) = (f64::INFINITY, f64::NEG_INFINITY);
+        let (mut y_min, mut y_max) = (f64::INFINITY, f64::NEG_INFINITY);
+        for node in &mesh.nodes {
+            x_min = x_min.min(node.x);
+            x_max = x_max.max(node.x);
+            y_min = y_min.min(node.y);
+            y_max = y_max.max(node.y);
+        }
+
+        let cell_size = cell_size.unwrap_or_else(|| {
+            let bbox_area = (x_max - x_min) * (y_max - y_min);
+            (2.0 * bbox_area / mesh.triangles.len().max(1) as f64).sqrt()
+        });
+
+        RasterGrid {
+            x_min,
+            y_max,
+            cell_size,
+            ncols: (((x_max - x_min) / cell_size).ceil() as usize).max(1),
+            nrows: (((y_max - y_min) / cell_size).ceil() as usize).max(1),
+        }
+    }
+
+    /// Centre of the pixel in column `col` and row `row`
+    pub fn pixel_center(&self, col: usize, row: usize) -> (f64, f64) {
+        (
+            self.x_min + (col as f64 + 0.5) * self.cell_size,
+            self.y_max - (row as f64 + 0.5) * self.cell_size,
+        )
+    }
+
+    /// Row-major index of the pixel containing (x, y), None outside
+    pub fn pixel_at(&self, x: f64, y: f64) -> Option<usize> {
+        let col = ((x - self.x_min) / self.cell_size).floor();
+        let row = ((self.y_max - y) / self.cell_size).floor();
+        let inside =
+            (0.0..self.ncols as f64).contains(&col) && (0.0..self.nrows as f64).contains(&row);
+        inside.then(|| row as usize * self.ncols + col as usize)
+    }
+}
+
+/// Parse an ESRI ASCII grid; NODATA pixels become NaN
+pub fn parse_ascii_grid(text: &str) -> Result<(RasterGrid, Vec<f64>), String> {
+    let mut tokens = text.split_whitespace().peekable();
+    let mut header: Vec<(String, f64)> = Vec::new();
+    while let Some(key) = tokens.peek() {
+        if !key.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            break;
+        }
+        let key = tokens.next().unwrap().to_ascii_lowercase();
+        let value = tokens
+            .next()
+            .and_then(|v| v.parse::<f64>().ok())
+            .ok_or_else(|| format!("invalid value for '{}'", key))?;
+        header.push((key, value));
+    }
+    let get = |key: &str| header.iter().find(|(k, _)| k == key).map(|(_, v)| *v);
+    let required = |key: &str| get(key).ok_or_else(|| format!("missing '{}'", key));
+
+    let ncols = required("ncols")? as usize;
+    let nrows = required("nrows")? as usize;
+    let cell_size = required("cellsize")?;
+    if ncols == 0 || nrows == 0 || cell_size <= 0.0 {
+        return Err("the grid must have positive size".to_string());
+    }
+    let (x_min, y_min) = match (get("xllcorner"), get("yllcorner")) {
+        (Some(x), Some(y)) => (x, y),
+        _ => (
+            required("xllcenter")? - 0.5 * cell_size,
+            required("yllcenter")? - 0.5 * cell_size,
+        ),
+    };
+    let nodata = get("nodata_value");
+
+    let values = tokens
+        .map(|v| {
+            v.parse::<f64>()
+                .map(|v| if Some(v) == nodata { f64::NAN } else { v })
+                .map_err(|_| format!("invalid value '{}'", v))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    if values.len() != ncols * nrows {
+        return Err(format!(
+            "expected {} values but found {}",
+            ncols * nrows,
+            values.len()
+        ));
+    }
+    let grid = RasterGrid {
+        x_min,
+        y_max: y_min + nrows as f64 * cell_size,
+        cell_size,
+        ncols,
+        nrows,
+    };
+    Ok((grid, values))
+}
+
+pub fn read_ascii_grid(path: &str) -> Result<(RasterGrid, Vec<f64>), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse_ascii_grid(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Resample a cell field onto the grid (row-major, NODATA outside the mesh
+/// and where the value is NaN)
+pub fn rasterize(mesh: &TriangularMesh, values: &[f64], grid: &RasterGrid) -> Vec<f32> {
+    pixel_triangles(mesh, grid)
+        .into_iter()
+        .map(|tri| {
+            tri.map(|i| values[i])
+                .filter(|value| !value.is_nan())
+                .map_or(NODATA, |value| value as f32)
+        })
+        .collect()
+}
+
+/// Triangle containing each pixel centre (row-major, None outside the mesh)
+pub fn pixel_triangles(mesh: &TriangularMesh, grid: &RasterGrid) -> Vec<Option<usize>> {
+    let mut pixels = vec![None; grid.ncols * grid.nrows];
+
+    for (i, tri) in mesh.triangles.iter().enumerate() {
+        let p = tri.nodes.map(|n| (mesh.nodes[n].x, mesh.nodes[n].y));
+        let x_lo = p.iter().map(|q| q.0).fold(f64::INFINITY, f64::min);
+        let x_hi = p.iter().map(|q| q.0).fold(f64::NEG_INFINITY, f64::max);
+        let y_lo = p.iter().map(|q| q.1).fold(f64::INFINITY, f64::min);
+        let y_hi = p.iter().map(|q| q.1).fold(f64::NEG_INFINITY, f64::max);
+
+        // Pixels whose centres can lie inside the bounding box
+        let col_range = pixel_range(x_lo - grid.x_min, x_hi - grid.x_min, grid.cell_size);
+        let row_range = pixel_range(grid.y_max - y_hi, grid.y_max - y_lo, grid.cell_size);
+
+        let det = (p[1].0 - p[0].0) * (p[2].1 - p[0].1) - (p[2].0 - p[0].0) * (p[1].1 - p[0].1);
+        if det.abs() < 1e-300 {
+            continue;
+        }
+
+        for row in row_range.0..row_range.1.min(grid.nrows) {
+            for col in col_range.0..col_range.1.min(grid.ncols) {
+                let (x, y) = grid.pixel_center(col, row);
+
+                // Barycentric coordinates (with a small tolerance on shared edges)
+                let l1 =
+                    ((x - p[0].0) * (p[2].1 - p[0].1) - (p[2].0 - p[0].0) * (y - p[0].1)) / det;
+                let l2 =
+                    ((p[1].0 - p[0].0) * (y - p[0].1) - (x - p[0].0) * (p[1].1 - p[0].1)) / det;
+                if l1 >= -1e-12 && l2 >= -1e-12 && l1 + l2 <= 1.0 + 1e-12 {
+                    pixels[row * grid.ncols + col] = Some(i);
+                }
+            }
+        }
+    }
+
+    pixels
+}
+
+/// Indices [first, last) of pixels whose centres fall in [lo, hi] (offsets
+/// from the grid edge)
+fn pixel_range(lo: f64, hi: f64, cell_size: f64) -> (usize, usize) {
+    let first = (lo / cell_size - 0.5).ceil().max(0.0) as usize;
+    let last = ((hi / cell_size - 0.5).floor() + 1.0).max(0.0) as usize;
+    (first, last)
+}
+
+/// Coordinate reference system written to the GeoKey directory
+#[derive(Debug, Clone, Copy)]
+pub enum RasterCrs {
+    /// Projected coordinates, optional EPSG code
+    Projected(Option<u16>),
+    /// Geographic longitude/latitude, EPSG code (e.g. 4326)
+    Geographic(u16),
+}
+
+// TIFF field types
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const ASCII: u16 = 2;
+const DOUBLE: u16 = 12;
+
+enum TagValue {
+    Short(Vec<u16>),
+    Long(u32),
+    Double(Vec<f64>),
+    Ascii(String),
+}
+
+impl TagValue {
+    fn encode(&self) -> (u16, u32, Vec<u8>) {
+        match self {
+            TagValue::Short(v) => (
+                SHORT,
+                v.len() as u32,
+                v.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            ),
+            TagValue::Long(v) => (LONG, 1, v.to_le_bytes().to_vec()),
+            TagValue::Double(v) => (
+                DOUBLE,
+                v.len() as u32,
+                v.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            ),
+            TagValue::Ascii(s) => {
+                let mut bytes = s.as_bytes().to_vec();
+                bytes.push(0);
+                (ASCII, bytes.len() as u32, bytes)
+            }
+        }
+    }
+}
+
+/// Write a single-band float32 GeoTIFF (little endian, one uncompressed strip)
+pub fn write_geotiff<W: Write>(
+    out: &mut W,
+    grid: &RasterGrid,
+    data: &[f32],
+    crs: RasterCrs,
+) -> io::Result<()> {
+    let image_bytes = (grid.ncols * grid.nrows * 4) as u32;
+
+    // GeoKey directory: header, then (key, location, count, value) entries
+    let mut keys: Vec<[u16; 4]> = vec![[1025, 0, 1, 1]]; // GTRasterType: PixelIsArea
+    match crs {
+        RasterCrs::Projected(code) => {
+            keys.push([1024, 0, 1, 1]); // GTModelType: projected
+            if let Some(code) = code {
+                keys.push([3072, 0, 1, code]); // ProjectedCSType
+            }
+        }
+        RasterCrs::Geographic(code) => {
+            keys.push([1024, 0, 1, 2]); // GTModelType: geographic
+            keys.push([2048, 0, 1, code]); // GeographicType
+        }
+    }
+    keys.sort_by_key(|k| k[0]);
+    let mut geokeys = vec![1, 1, 0, keys.len() as u16];
+    geokeys.extend(keys.iter().flatten());
+
+    let mut tags: Vec<(u16, TagValue)> = vec![
+        (256, TagValue::Long(grid.ncols as u32)),
+        (257, TagValue::Long(grid.nrows as u32)),
+        (258, TagValue::Short(vec![32])), // BitsPerSample
+        (259, TagValue::Short(vec![1])),  // Compression: none
+        (262, TagValue::Short(vec![1])),  // Photometric: BlackIsZero
+        (273, TagValue::Long(0)),         // StripOffsets (patched below)
+        (277, TagValue::Short(vec![1])),  // SamplesPerPixel
+        (278, TagValue::Long(grid.nrows as u32)),
+        (279, TagValue::Long(image_bytes)),
+        (284, TagValue::Short(vec![1])), // PlanarConfiguration: chunky
+        (339, TagValue::Short(vec![3])), // SampleFormat: IEEE float
+        (
+            33550,
+            TagValue::Double(vec![grid.cell_size, grid.cell_size, 0.0]),
+        ),
+        (
+            33922,
+            TagValue::Double(vec![0.0, 0.0, 0.0, grid.x_min, grid.y_max, 0.0]),
+        ),
+        (34735, TagValue::Short(geokeys)),
+        (42113, TagValue::Ascii(format!("{}", NODATA))), // GDAL_NODATA
+    ];
+
+    // Layout: header (8) | IFD | out-of-line tag values | image strip
+    let ifd_size = 2 + 12 * tags.len() as u32 + 4;
+    let mut extra_offset = 8 + ifd_size;
+    let extra_len: u32 = tags
+        .iter()
+        .map(|(_, v)| {
+            let len = v.encode().2.len() as u32;
+            if len > 4 {
+                len + (len & 1)
+            } else {
+                0
+            }
+        })
+        .sum();
+    let strip_offset = extra_offset + extra_len;
+    for (tag, value) in tags.iter_mut() {
+        if *tag == 273 {
+            *value = TagValue::Long(strip_offset);
+        }
+    }
+
+    out.write_all(b"II")?;
+    out.write_all(&42u16.to_le_bytes())?;
+    out.write_all(&8u32.to_le_bytes())?;
+
+    out.write_all(&(tags.len() as u16).to_le_bytes())?;
+    let mut extra = Vec::new();
+    for (tag, value) in &tags {
+        let (field_type, count, mut bytes) = value.encode();
+        out.write_all(&tag.to_le_bytes())?;
+        out.write_all(&field_type.to_le_bytes())?;
+        out.write_all(&count.to_le_bytes())?;
+        if bytes.len() <= 4 {
+            bytes.resize(4, 0);
+            out.write_all(&bytes)?;
+        } else {
+            out.write_all(&extra_offset.to_le_bytes())?;
+            if bytes.len() % 2 == 1 {
+                bytes.push(0); // Keep offsets word aligned
+            }
+            extra_offset += bytes.len() as u32;
+            extra.extend(bytes);
+        }
+    }
+    out.write_all(&0u32.to_le_bytes())?; // No further IFDs
+    out.write_all(&extra)?;
+
+    for value in data {
+        out.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Resample a cell field and write it to `filename`
+pub fn save_geotiff(
+    filename: &str,
+    mesh: &TriangularMesh,
+    values: &[f64],
+    grid: &RasterGrid,
+    crs: RasterCrs,
+) -> io::Result<()> {
+    let data = rasterize(mesh, values, grid);
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_geotiff(&mut file, grid, &data, crs)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+
+    fn read_u16(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn read_u32(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_rasterize_picks_containing_triangle() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 4.0, 4.0, TopographyType::Flat);
+        let values: Vec<f64> = mesh.triangles.iter().map(|t| t.centroid.0).collect();
+        let grid = RasterGrid::covering(&mesh, Some(0.5));
+        assert_eq!((grid.ncols, grid.nrows), (8, 8));
+
+        let raster = rasterize(&mesh, &values, &grid);
+        for (k, value) in raster.iter().enumerate() {
+            let (x, _) = grid.pixel_center(k % grid.ncols, k / grid.ncols);
+            // Centroid x of the containing triangle lies in the same unit column
+            assert!(*value != NODATA);
+            assert_eq!((*value as f64).floor(), x.floor());
+        }
+    }
+
+    #[test]
+    fn test_outside_mesh_is_nodata() {
+        let mesh = TriangularMesh::new_rectangular(3, 3, 1.0, 1.0, TopographyType::Flat);
+        let grid = RasterGrid {
+            x_min: -1.0,
+            y_max: 1.0,
+            cell_size: 0.5,
+            ncols: 4,
+            nrows: 2,
+        };
+        let raster = rasterize(&mesh, &vec![1.0; mesh.triangles.len()], &grid);
+        assert_eq!(raster[0], NODATA);
+        assert_eq!(raster[3], 1.0);
+    }
+
+    #[test]
+    fn test_parse_ascii_grid() {
+        let text = "ncols 3\nnrows 2\nxllcorner 10\nyllcorner 20\ncellsize 5\n\
+                    NODATA_value -9999\n1 2 3\n4 -9999 6\n";
+        let (grid, values) = parse_ascii_grid(text).unwrap();
+        assert_eq!(
+            (grid.ncols, grid.nrows, grid.x_min, grid.y_max),
+            (3, 2, 10.0, 30.0)
+        );
+        assert!(values[4].is_nan());
+        // Row 0 is the northern row
+        assert_eq!(grid.pixel_at(12.0, 29.0).map(|k| values[k]), Some(1.0));
+        assert_eq!(grid.pixel_at(24.0, 21.0).map(|k| values[k]), Some(6.0));
+        assert_eq!(grid.pixel_at(9.0, 25.0), None);
+        assert!(parse_ascii_grid(
+            "ncols 2\nnrows 2\nxllcorner 0\nyllcorner 0\ncellsize 1\n1 2 3\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_geotiff_structure() {
+        let grid = RasterGrid {
+            x_min: 100.0,
+            y_max: 200.0,
+            cell_size: 2.0,
+            ncols: 3,
+            nrows: 2,
+        };
+        let data = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let mut bytes = Vec::new();
+        write_geotiff(&mut bytes, &grid, &data, RasterCrs::Projected(Some(32633))).unwrap();
+
+        assert_eq!(&bytes[0..4], &[b'I', b'I', 42, 0]);
+        let ifd = read_u32(&bytes, 4) as usize;
+        let count = read_u16(&bytes, ifd) as usize;
+
+        let mut strip_offset = 0;
+        let mut tags = Vec::new();
+        for k in 0..count {
+            let entry = ifd + 2 + 12 * k;
+            let tag = read_u16(&bytes, entry);
+            tags.push(tag);
+            match tag {
+                256 => assert_eq!(read_u32(&bytes, entry + 8), 3),
+                257 => assert_eq!(read_u32(&bytes, entry + 8), 2),
+                273 => strip_offset = read_u32(&bytes, entry + 8) as usize,
+                _ => {}
+            }
+        }
+
+        // Tags in ascending order, georeferencing present
+        assert!(tags.windows(2).all(|w| w[0] < w[1]));
+        assert!(tags.contains(&33550) && tags.contains(&33922) && tags.contains(&34735));
+
+        assert_eq!(bytes.len(), strip_offset + 24);
+        let last = f32::from_le_bytes(bytes[strip_offset + 20..].try_into().unwrap());
+        assert_eq!(last, 6.0);
+    }
+}
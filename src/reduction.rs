@@ -0,0 +1,89 @@
+/// Parallel floating-point reductions
+/// Rayon's work-stealing sum combines partial results in an order that
+/// depends on scheduling, so totals can differ in the last bits between runs
+/// and thread counts. In deterministic mode sums are taken over fixed-size
+/// chunks and the chunk partials are combined by a fixed pairwise tree,
+/// giving bitwise-identical results for any number of threads.
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Chunk length of the fixed-order reduction (independent of thread count)
+const CHUNK_SIZE: usize = 4096;
+
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+/// Select fixed-order reductions for the whole process
+pub fn set_deterministic(enabled: bool) {
+    DETERMINISTIC.store(enabled, Ordering::Relaxed);
+}
+
+/// Σ f(i) for i in 0..n
+pub fn sum<F>(n: usize, f: F) -> f64
+where
+    F: Fn(usize) -> f64 + Sync,
+{
+    if DETERMINISTIC.load(Ordering::Relaxed) {
+        fixed_order_sum(n, f)
+    } else {
+        (0..n).into_par_iter().map(&f).sum()
+    }
+}
+
+fn fixed_order_sum<F>(n: usize, f: F) -> f64
+where
+    F: Fn(usize) -> f64 + Sync,
+{
+    let partials: Vec<f64> = (0..n.div_ceil(CHUNK_SIZE))
+        .into_par_iter()
+        .map(|chunk| {
+            let end = ((chunk + 1) * CHUNK_SIZE).min(n);
+            (chunk * CHUNK_SIZE..end).map(&f).sum()
+        })
+        .collect();
+    pairwise_sum(&partials)
+}
+
+fn pairwise_sum(values: &[f64]) -> f64 {
+    match values.len() {
+        0 => 0.0,
+        1 => values[0],
+        len => {
+            let (left, right) = values.split_at(len / 2);
+            pairwise_sum(left) + pairwise_sum(right)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_order_sum_independent_of_thread_count() {
+        // Values spanning many magnitudes make the result order-sensitive
+        let values: Vec<f64> = (0..100_000i32)
+            .map(|i| ((i as f64) * 0.37).sin() * 10f64.powi(i % 17 - 8))
+            .collect();
+
+        let totals: Vec<u64> = [1, 2, 3, 8]
+            .iter()
+            .map(|&threads| {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap();
+                pool.install(|| fixed_order_sum(values.len(), |i| values[i]).to_bits())
+            })
+            .collect();
+
+        assert!(totals.windows(2).all(|w| w[0] == w[1]));
+        let naive: f64 = values.iter().sum();
+        assert!((f64::from_bits(totals[0]) - naive).abs() < 1e-9 * naive.abs());
+    }
+
+    #[test]
+    fn test_pairwise_sum_small() {
+        assert_eq!(pairwise_sum(&[]), 0.0);
+        assert_eq!(pairwise_sum(&[1.0, 2.0, 3.0]), 6.0);
+    }
+}
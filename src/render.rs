@@ -0,0 +1,431 @@
+/// Quick-look PNG snapshots
+/// Depth or water surface is resampled onto a pixel grid (see `raster`),
+/// colored with a fixed colormap, outlined along the shoreline and annotated
+/// with a colorbar and the simulation time. Images are written as RGB PNG
+/// with stored (uncompressed) deflate blocks, so no image library is needed.
+use crate::compress::{adler32, crc32};
+use crate::raster::{self, RasterGrid, NODATA};
+use crate::solver::ShallowWaterSolver;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Fields that can be rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderField {
+    Depth,
+    Surface,
+}
+
+impl RenderField {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "depth" | "h" => Ok(RenderField::Depth),
+            "surface" | "eta" => Ok(RenderField::Surface),
+            other => Err(format!(
+                "unknown PNG field '{}' (expected depth, surface)",
+                other
+            )),
+        }
+    }
+
+    /// File name suffix
+    pub fn name(&self) -> &'static str {
+        match self {
+            RenderField::Depth => "depth",
+            RenderField::Surface => "surface",
+        }
+    }
+
+    /// Colormap stops from low to high values
+    pub fn colormap(&self) -> &'static [[u8; 3]] {
+        match self {
+            // Sequential blues
+            RenderField::Depth => &[
+                [222, 235, 247],
+                [107, 174, 214],
+                [33, 113, 181],
+                [8, 48, 107],
+            ],
+            // Viridis
+            RenderField::Surface => &[
+                [68, 1, 84],
+                [59, 82, 139],
+                [33, 145, 140],
+                [94, 201, 98],
+                [253, 231, 37],
+            ],
+        }
+    }
+}
+
+/// Parse a comma-separated list such as "depth,surface"
+pub fn parse_render_fields(list: &str) -> Result<Vec<RenderField>, String> {
+    let mut fields = Vec::new();
+    for name in list.split(',').filter(|s| !s.trim().is_empty()) {
+        let field = RenderField::parse(name)?;
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    Ok(fields)
+}
+
+/// Parse a color range "min,max"
+pub fn parse_range(spec: &str) -> Result<(f64, f64), String> {
+    let (lo, hi) = spec
+        .split_once(',')
+        .ok_or_else(|| format!("expected min,max but found '{}'", spec))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<f64>()
+            .map_err(|_| format!("invalid number '{}'", v.trim()))
+    };
+    let (lo, hi) = (parse(lo)?, parse(hi)?);
+    if hi <= lo {
+        return Err(format!("empty color range {},{}", lo, hi));
+    }
+    Ok((lo, hi))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions {
+    pub width: usize,              // Plot width in pixels (colorbar excluded)
+    pub range: Option<(f64, f64)>, // Fixed color range; per-frame min/max if None
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            width: 800,
+            range: None,
+        }
+    }
+}
+
+/// RGB image, row-major from the top
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+pub const LAND: [u8; 3] = [196, 180, 148];
+const SHORELINE: [u8; 3] = [0, 0, 0];
+const TEXT: [u8; 3] = [0, 0, 0];
+pub const COLORBAR_MARGIN: usize = 110; // Fits the bar and a 12-character label
+const FONT_SCALE: usize = 2;
+
+impl Image {
+    fn new(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![BACKGROUND; width * height],
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: [u8; 3]) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    /// Draw text with the built-in 3x5 font, moved left where it would run
+    /// past the right edge; unsupported characters are skipped
+    fn text(&mut self, x: usize, y: usize, text: &str) {
+        let x = x.min(self.width.saturating_sub(text_width(text)));
+        for (k, c) in text.chars().enumerate() {
+            let Some(rows) = glyph(c) else { continue };
+            let x0 = x + k * 4 * FONT_SCALE;
+            for (row, bits) in rows.iter().enumerate() {
+                for col in 0..3 {
+                    if bits & (0b100 >> col) != 0 {
+                        for dy in 0..FONT_SCALE {
+                            for dx in 0..FONT_SCALE {
+                                self.set(
+                                    x0 + col * FONT_SCALE + dx,
+                                    y + row * FONT_SCALE + dy,
+                                    TEXT,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Width in pixels of `text` in the built-in font
+fn text_width(text: &str) -> usize {
+    (text.chars().count() * 4 * FONT_SCALE).saturating_sub(FONT_SCALE)
+}
+
+/// 3x5 bitmap glyphs for numbers and the time label
+fn glyph(c: char) -> Option<[u8; 5]> {
+    Some(match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        't' => [0b010, 0b111, 0b010, 0b010, 0b011],
+        's' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        _ => return None,
+    })
+}
+
+/// Linear interpolation through the colormap stops, t in [0, 1]
+pub fn color_at(stops: &[[u8; 3]], t: f64) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let k = (t.floor() as usize).min(stops.len() - 2);
+    let f = t - k as f64;
+    std::array::from_fn(|c| {
+        (stops[k][c] as f64 * (1.0 - f) + stops[k + 1][c] as f64 * f).round() as u8
+    })
+}
+
+/// Render one field of the current solver state over the whole mesh
+pub fn render(solver: &ShallowWaterSolver, field: RenderField, options: &RenderOptions) -> Image {
+    let mesh = &solver.mesh;
+    let (x_min, x_max) = mesh
+        .nodes
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), n| {
+            (lo.min(n.x), hi.max(n.x))
+        });
+    let grid = RasterGrid::covering(mesh, Some((x_max - x_min) / options.width.max(1) as f64));
+    render_grid(solver, field, &grid, options.range)
+}
+
+/// Render one field onto an arbitrary pixel grid (plot area), with the
+/// colorbar to its right
+pub fn render_grid(
+    solver: &ShallowWaterSolver,
+    field: RenderField,
+    grid: &RasterGrid,
+    range: Option<(f64, f64)>,
+) -> Image {
+    let mesh = &solver.mesh;
+    let dry = solver.constants.dry_tolerance;
+    let values: Vec<f64> = match field {
+        RenderField::Depth => solver.state.h.clone(),
+        RenderField::Surface => mesh
+            .triangles
+            .iter()
+            .zip(&solver.state.h)
+            .map(|(t, h)| t.z_bed + h)
+            .collect(),
+    };
+    let wet: Vec<f64> = solver
+        .state
+        .h
+        .iter()
+        .map(|&h| if h > dry { 1.0 } else { 0.0 })
+        .collect();
+    let data = raster::rasterize(mesh, &values, grid);
+    let wet = raster::rasterize(mesh, &wet, grid);
+
+    let (lo, hi) = range.unwrap_or_else(|| {
+        let (lo, hi) = data
+            .iter()
+            .zip(&wet)
+            .filter(|(_, &w)| w == 1.0)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (&v, _)| {
+                (lo.min(v as f64), hi.max(v as f64))
+            });
+        if lo.is_finite() && hi > lo {
+            (lo, hi)
+        } else if lo.is_finite() {
+            (lo - 0.5, lo + 0.5)
+        } else {
+            (0.0, 1.0)
+        }
+    });
+
+    let stops = field.colormap();
+    let (ncols, nrows) = (grid.ncols, grid.nrows);
+    let mut image = Image::new(ncols + COLORBAR_MARGIN, nrows.max(120));
+    let is_wet = |col: usize, row: usize| wet[row * ncols + col] == 1.0;
+    let inside = |col: usize, row: usize| wet[row * ncols + col] != NODATA;
+
+    for row in 0..nrows {
+        for col in 0..ncols {
+            if !inside(col, row) {
+                continue;
+            }
+            let color = if !is_wet(col, row) {
+                LAND
+            } else if [(0, 1), (2, 1), (1, 0), (1, 2)].iter().any(|&(dc, dr)| {
+                let (c, r) = ((col + dc).wrapping_sub(1), (row + dr).wrapping_sub(1));
+                c < ncols && r < nrows && inside(c, r) && !is_wet(c, r)
+            }) {
+                SHORELINE
+            } else {
+                let v = data[row * ncols + col] as f64;
+                color_at(stops, (v - lo) / (hi - lo))
+            };
+            image.set(col, row, color);
+        }
+    }
+
+    // Colorbar with min/max labels and the time
+    let bar_x = ncols + 10;
+    let bar_top = 30;
+    let bar_bottom = image.height - 20;
+    for y in bar_top..bar_bottom {
+        let t = (bar_bottom - 1 - y) as f64 / (bar_bottom - 1 - bar_top).max(1) as f64;
+        for x in bar_x..bar_x + 14 {
+            image.set(x, y, color_at(stops, t));
+        }
+    }
+    let label_x = bar_x + 18;
+    image.text(label_x, bar_top, &format!("{:.2}", hi));
+    image.text(label_x, bar_bottom - 5 * FONT_SCALE, &format!("{:.2}", lo));
+    image.text(bar_x, 8, &format!("t={:.2}s", solver.time));
+
+    image
+}
+
+pub fn save_png(filename: &str, image: &Image) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(filename)?);
+    write_png(&mut out, image)?;
+    out.flush()
+}
+
+/// Write an 8-bit RGB PNG
+pub fn write_png<W: Write>(out: &mut W, image: &Image) -> io::Result<()> {
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((image.width as u32).to_be_bytes());
+    header.extend((image.height as u32).to_be_bytes());
+    header.extend([8, 2, 0, 0, 0]); // 8-bit RGB, deflate, no filter, no interlace
+    write_chunk(out, b"IHDR", &header)?;
+
+    // Scanlines, each prefixed with filter type 0
+    let mut raw = Vec::with_capacity(image.height * (1 + 3 * image.width));
+    for row in image.pixels.chunks(image.width) {
+        raw.push(0);
+        raw.extend(row.iter().flatten());
+    }
+    write_chunk(out, b"IDAT", &zlib_stored(&raw))?;
+    write_chunk(out, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())
+}
+
+/// zlib stream made of stored (uncompressed) deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65535;
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 16);
+    out.extend([0x78, 0x01]);
+    let blocks: Vec<&[u8]> = if data.is_empty() {
+        vec![&[]]
+    } else {
+        data.chunks(MAX_BLOCK).collect()
+    };
+    for (k, block) in blocks.iter().enumerate() {
+        out.push(u8::from(k + 1 == blocks.len())); // BFINAL, BTYPE = 00
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_png_structure() {
+        let image = Image {
+            width: 300,
+            height: 250,
+            pixels: vec![[10, 20, 30]; 300 * 250],
+        };
+        let mut bytes = Vec::new();
+        write_png(&mut bytes, &image).unwrap();
+
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&bytes[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes(bytes[16..20].try_into().unwrap()), 300);
+        assert_eq!(&bytes[bytes.len() - 8..bytes.len() - 4], b"IEND");
+        // Raw scanlines exceed one stored block, so the stream has several
+        let idat_len = u32::from_be_bytes(bytes[33..37].try_into().unwrap()) as usize;
+        let raw_len = 250 * 901_usize;
+        assert_eq!(idat_len, 2 + raw_len + 4 + 5 * raw_len.div_ceil(65535));
+    }
+
+    #[test]
+    fn test_render_marks_land_and_shoreline() {
+        let mesh = TriangularMesh::new_rectangular(21, 21, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+            solver.state.h[i] = if tri.centroid.0 < 5.0 { 1.0 } else { 0.0 };
+        }
+        let options = RenderOptions {
+            width: 100,
+            range: None,
+        };
+        let image = render(&solver, RenderField::Depth, &options);
+
+        assert_eq!(image.width, 100 + COLORBAR_MARGIN);
+        let row = image.height / 2 * image.width;
+        assert_ne!(image.pixels[row + 10], LAND);
+        assert_eq!(image.pixels[row + 90], LAND);
+        assert!(image.pixels[row..row + 100].contains(&SHORELINE));
+    }
+
+    #[test]
+    fn test_long_time_label_is_not_cut_off() {
+        let mesh = TriangularMesh::new_rectangular(21, 21, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.iter_mut().for_each(|h| *h = 1.0);
+        let options = RenderOptions {
+            width: 100,
+            range: None,
+        };
+        for (time, label) in [(86400.0, "t=86400.00s"), (1e7, "t=10000000.00s")] {
+            solver.time = time;
+            let image = render(&solver, RenderField::Depth, &options);
+
+            // Every pixel of the label is drawn, none clipped at the edge
+            let lit: usize = label
+                .chars()
+                .filter_map(glyph)
+                .map(|rows| {
+                    rows.iter()
+                        .map(|bits| bits.count_ones() as usize)
+                        .sum::<usize>()
+                })
+                .sum();
+            let drawn = (8..8 + 5 * FONT_SCALE)
+                .flat_map(|y| &image.pixels[y * image.width..(y + 1) * image.width])
+                .filter(|&&pixel| pixel == TEXT)
+                .count();
+            assert_eq!(drawn, lit * FONT_SCALE * FONT_SCALE, "{}", label);
+            assert!(text_width(label) < image.width);
+        }
+    }
+}
@@ -0,0 +1,359 @@
+/// Shoreline and runup tracking
+/// The wet/dry front is followed along transects, polylines drawn from the
+/// sea towards the land, and over the whole domain. Along a transect the
+/// shoreline is where the sampled depth first falls to the wet threshold,
+/// interpolated between stations. Over the domain it is the highest wet
+/// cell. Runup is the elevation of the shoreline above the initial one;
+/// inundation is the horizontal distance it has moved inland: along the
+/// transect, or over the domain the straight-line distance from the nearest
+/// initially wet cell that the water can reach it from. Maxima are kept from every step, as runup peaks
+/// rarely fall on output times.
+use crate::profile::Profile;
+use crate::solver::ShallowWaterSolver;
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Name of the whole-domain record
+pub const DOMAIN: &str = "domain";
+
+/// Position of the wet/dry front
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shoreline {
+    pub distance: f64, // Along the transect (m), NaN over the domain
+    pub x: f64,
+    pub y: f64,
+    pub elevation: f64, // Ground elevation at the front (m)
+}
+
+/// Front at one time with its runup and inundation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunupRecord {
+    pub time: f64,
+    pub shoreline: Shoreline,
+    pub runup: f64,      // Elevation above the initial shoreline (m)
+    pub inundation: f64, // Horizontal distance inland of the initial shoreline (m)
+}
+
+/// Front followed along one transect
+pub struct Transect {
+    pub profile: Profile,
+    pub initial: Option<Shoreline>,
+    pub max_runup: Option<RunupRecord>,
+    pub max_inundation: Option<RunupRecord>,
+}
+
+/// Front followed over the whole domain
+pub struct DomainFront {
+    /// Distance from the nearest initially wet cell (m), infinite where no
+    /// path through the mesh leads
+    pub distance: Vec<f64>,
+    pub initial: Option<Shoreline>,
+    pub max_runup: Option<RunupRecord>,
+    pub max_inundation: Option<RunupRecord>,
+}
+
+pub struct RunupTracker {
+    pub depth: f64, // Wet threshold (m)
+    pub transects: Vec<Transect>,
+    pub domain: Option<DomainFront>,
+}
+
+impl RunupTracker {
+    /// Follow the fronts along `profiles` (from the sea towards the land) and,
+    /// with `domain`, over the whole mesh, relative to the current state
+    pub fn new(
+        solver: &ShallowWaterSolver,
+        profiles: Vec<Profile>,
+        domain: bool,
+        depth: f64,
+    ) -> Self {
+        let transects = profiles
+            .into_iter()
+            .map(|profile| Transect {
+                initial: transect_shoreline(&profile, solver, depth),
+                profile,
+                max_runup: None,
+                max_inundation: None,
+            })
+            .collect();
+        let domain = domain.then(|| DomainFront {
+            distance: wet_distance(solver, depth),
+            initial: domain_front(solver, depth, None).map(|(front, _)| front),
+            max_runup: None,
+            max_inundation: None,
+        });
+        RunupTracker {
+            depth,
+            transects,
+            domain,
+        }
+    }
+
+    /// Current front of every transect and the domain, by name (None where
+    /// no front is found), updating the maxima
+    pub fn observe(&mut self, solver: &ShallowWaterSolver) -> Vec<(String, Option<RunupRecord>)> {
+        let mut records = Vec::new();
+        for transect in &mut self.transects {
+            let current = transect_shoreline(&transect.profile, solver, self.depth).map(|front| {
+                let initial = transect.initial;
+                RunupRecord {
+                    time: solver.time,
+                    shoreline: front,
+                    runup: initial.map_or(f64::NAN, |i| front.elevation - i.elevation),
+                    inundation: initial.map_or(f64::NAN, |i| front.distance - i.distance),
+                }
+            });
+            update_maxima(
+                &mut transect.max_runup,
+                &mut transect.max_inundation,
+                current,
+            );
+            records.push((transect.profile.name.clone(), current));
+        }
+        if let Some(domain) = &mut self.domain {
+            let current = domain_front(solver, self.depth, Some(&domain.distance)).map(
+                |(front, inundation)| RunupRecord {
+                    time: solver.time,
+                    shoreline: front,
+                    runup: domain
+                        .initial
+                        .map_or(f64::NAN, |i| front.elevation - i.elevation),
+                    inundation,
+                },
+            );
+            update_maxima(&mut domain.max_runup, &mut domain.max_inundation, current);
+            records.push((DOMAIN.to_string(), current));
+        }
+        records
+    }
+
+    /// Largest runup and inundation of every transect and the domain
+    pub fn maxima(&self) -> Vec<(&str, Option<RunupRecord>, Option<RunupRecord>)> {
+        let mut maxima: Vec<_> = self
+            .transects
+            .iter()
+            .map(|t| (t.profile.name.as_str(), t.max_runup, t.max_inundation))
+            .collect();
+        if let Some(domain) = &self.domain {
+            maxima.push((DOMAIN, domain.max_runup, domain.max_inundation));
+        }
+        maxima
+    }
+}
+
+fn update_maxima(
+    runup: &mut Option<RunupRecord>,
+    inundation: &mut Option<RunupRecord>,
+    current: Option<RunupRecord>,
+) {
+    let Some(current) = current else {
+        return;
+    };
+    if runup.is_none_or(|max| current.runup > max.runup) {
+        *runup = Some(current);
+    }
+    if inundation.is_none_or(|max| current.inundation > max.inundation) {
+        *inundation = Some(current);
+    }
+}
+
+/// Front along `profile`: between the last station of the wet run from its
+/// start and the first dry one, where the depth falls to `depth`. None when
+/// the first station is dry; the last station when all are wet.
+pub fn transect_shoreline(
+    profile: &Profile,
+    solver: &ShallowWaterSolver,
+    depth: f64,
+) -> Option<Shoreline> {
+    let samples = profile.sample(solver);
+    let (first, state) = samples.first()?;
+    if state.depth <= depth {
+        return None;
+    }
+    let mut last = Shoreline {
+        distance: first.distance,
+        x: first.x,
+        y: first.y,
+        elevation: state.bed,
+    };
+    let mut last_depth = state.depth;
+    for (station, state) in &samples[1..] {
+        if state.depth <= depth {
+            let t = ((last_depth - depth) / (last_depth - state.depth)).clamp(0.0, 1.0);
+            let lerp = |a: f64, b: f64| a + t * (b - a);
+            return Some(Shoreline {
+                distance: lerp(last.distance, station.distance),
+                x: lerp(last.x, station.x),
+                y: lerp(last.y, station.y),
+                elevation: lerp(last.elevation, state.bed),
+            });
+        }
+        last = Shoreline {
+            distance: station.distance,
+            x: station.x,
+            y: station.y,
+            elevation: state.bed,
+        };
+        last_depth = state.depth;
+    }
+    Some(last)
+}
+
+/// Highest wet cell and, with the distances from the initially wet cells,
+/// the largest of them among the wet cells
+fn domain_front(
+    solver: &ShallowWaterSolver,
+    depth: f64,
+    distance: Option<&[f64]>,
+) -> Option<(Shoreline, f64)> {
+    let wet = |i: &usize| solver.state.h[*i] > depth;
+    let highest = (0..solver.mesh.triangles.len())
+        .into_par_iter()
+        .filter(wet)
+        .max_by(|&a, &b| {
+            let (za, zb) = (
+                solver.mesh.triangles[a].z_bed,
+                solver.mesh.triangles[b].z_bed,
+            );
+            za.total_cmp(&zb).then(b.cmp(&a))
+        })?;
+    let inundation = distance.map_or(0.0, |distance| {
+        (0..solver.mesh.triangles.len())
+            .into_par_iter()
+            .filter(wet)
+            .map(|i| distance[i])
+            .filter(|d| d.is_finite())
+            .reduce(|| 0.0, f64::max)
+    });
+    let tri = &solver.mesh.triangles[highest];
+    let front = Shoreline {
+        distance: f64::NAN,
+        x: tri.centroid.0,
+        y: tri.centroid.1,
+        elevation: tri.z_bed,
+    };
+    Some((front, inundation))
+}
+
+/// Straight-line distance between centroids from every cell to the nearest
+/// cell deeper than `depth` (m). The nearest wet cell is passed on from
+/// neighbour to neighbour, so it is found among those connected to the cell
+/// through the mesh, and up to the mesh resolution.
+pub fn wet_distance(solver: &ShallowWaterSolver, depth: f64) -> Vec<f64> {
+    let mesh = &solver.mesh;
+    let between = |a: usize, b: usize| {
+        let (dx, dy) = mesh
+            .coordinate_system
+            .delta(mesh.triangles[a].centroid, mesh.triangles[b].centroid);
+        dx.hypot(dy)
+    };
+    let mut distance = vec![f64::INFINITY; mesh.triangles.len()];
+    let mut nearest = vec![usize::MAX; mesh.triangles.len()];
+    // Non-negative distances order like their bit patterns
+    let mut queue = BinaryHeap::new();
+    for (i, &h) in solver.state.h.iter().enumerate() {
+        if h > depth {
+            distance[i] = 0.0;
+            nearest[i] = i;
+            queue.push(Reverse((0.0f64.to_bits(), i)));
+        }
+    }
+    while let Some(Reverse((bits, i))) = queue.pop() {
+        if f64::from_bits(bits) > distance[i] {
+            continue;
+        }
+        let source = nearest[i];
+        for &j in mesh.triangles[i].neighbors.iter().flatten() {
+            let d = between(j, source);
+            if d < distance[j] {
+                distance[j] = d;
+                nearest[j] = source;
+                queue.push(Reverse((d.to_bits(), j)));
+            }
+        }
+    }
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::profile::parse_polyline;
+    use crate::solver::FrictionLaw;
+
+    /// Beach rising at 1:10 towards x = 20 m, still water at level 0.5 m
+    fn beach() -> ShallowWaterSolver {
+        let mesh = TriangularMesh::new_rectangular(
+            41,
+            5,
+            20.0,
+            4.0,
+            TopographyType::Slope {
+                gradient_x: 0.1,
+                gradient_y: 0.0,
+            },
+        );
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = (0.5 - solver.mesh.triangles[i].z_bed).max(0.0);
+        }
+        solver
+    }
+
+    #[test]
+    fn test_transect_shoreline_follows_the_water_level() {
+        let mut solver = beach();
+        let (name, vertices) = parse_polyline("beach=0.2,2;19.8,2", 0).unwrap();
+        let profile = Profile::new(name, &vertices, 0.25, &solver);
+        let mut tracker = RunupTracker::new(&solver, vec![profile], true, 1e-3);
+        let initial = tracker.transects[0].initial.unwrap();
+        assert!((initial.x - 5.0).abs() < 0.3, "{:?}", initial);
+        assert!((initial.elevation - 0.5).abs() < 0.03);
+
+        // Raise the water by 0.3 m: 3 m further inland on a 1:10 slope
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = (0.8 - solver.mesh.triangles[i].z_bed).max(0.0);
+        }
+        solver.time = 1.0;
+        let records = tracker.observe(&solver);
+        let (name, record) = &records[0];
+        assert_eq!(name, "beach");
+        let record = record.unwrap();
+        assert!((record.runup - 0.3).abs() < 0.03, "{:?}", record);
+        assert!((record.inundation - 3.0).abs() < 0.3);
+
+        let (name, domain) = &records[1];
+        assert_eq!(name, DOMAIN);
+        let domain = domain.unwrap();
+        assert!((domain.runup - 0.3).abs() < 0.06, "{:?}", domain);
+        assert!((domain.inundation - 3.0).abs() < 0.5);
+
+        // The maxima stay when the water recedes
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = (0.2 - solver.mesh.triangles[i].z_bed).max(0.0);
+        }
+        solver.time = 2.0;
+        let receded = tracker.observe(&solver)[0].1.unwrap();
+        assert!(receded.runup < 0.0 && receded.inundation < 0.0);
+        let (_, max_runup, max_inundation) = tracker.maxima()[0];
+        assert_eq!(max_runup.unwrap().time, 1.0);
+        assert_eq!(max_inundation.unwrap().time, 1.0);
+    }
+
+    #[test]
+    fn test_wet_distance_from_the_shoreline() {
+        let solver = beach();
+        let distance = wet_distance(&solver, 1e-3);
+        for (tri, d) in solver.mesh.triangles.iter().zip(&distance) {
+            if tri.z_bed < 0.49 {
+                assert_eq!(*d, 0.0);
+            } else {
+                // From the nearest wet centroid, at most half a cell
+                // inside the shoreline at x = 5 m
+                assert!(*d >= tri.centroid.0 - 5.0 && *d <= tri.centroid.0 - 4.5);
+            }
+        }
+    }
+}
@@ -0,0 +1,363 @@
+/// Control scripts (feature "scripting")
+/// Small Rhai scripts from the `scripts` section of the run configuration
+/// drive what tabulated series cannot express, such as operating rules. They
+/// are evaluated before every step, with the time `t` (s), the last step `dt`
+/// (s) and every probe as a map of `depth`, `level`, `u` and `v` in scope:
+///
+/// - a boundary script gives the level (stage) or discharge (inflow) of the
+///   named `TaggedBoundary` feature;
+/// - a gate script gives whether the named feature is open (`open` holds the
+///   previous answer, for hysteresis); a closed feature is a wall;
+/// - a source script gives the discharge (m^3/s) of a point source, added to
+///   the cell containing it after the step. Abstraction stops when the cell
+///   is empty; the volume is booked as coupled volume in the mass ledger.
+use crate::boundary::{BoundaryFeature, BoundaryKind, TaggedBoundary};
+use crate::config::ScriptConfig;
+use crate::mesh::PointLocator;
+use crate::simulation::Coupling;
+use crate::solver::ShallowWaterSolver;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+
+/// Operations a script may take per evaluation, against endless loops
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+struct Gate {
+    name: String,
+    feature: usize,
+    script: AST,
+    open: bool,
+}
+
+/// Point source with a scripted discharge
+pub struct Source {
+    pub name: String,
+    cell: usize,
+    script: AST,
+    discharge: f64,        // Last evaluated discharge (m^3/s)
+    pub added_volume: f64, // Net volume put into the domain (m^3)
+}
+
+/// Scripts of a run, compiled
+pub struct ScriptedControl {
+    engine: Engine,
+    probes: Vec<(String, usize)>,
+    boundary: Option<TaggedBoundary>,
+    kinds: Vec<BoundaryKind>,          // Conditions of the features as read
+    values: Vec<(String, usize, AST)>, // Level or discharge scripts by feature
+    gates: Vec<Gate>,
+    pub sources: Vec<Source>,
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !["t", "dt", "open"].contains(&name)
+}
+
+/// Number from a script result; integers are accepted
+fn number(value: Dynamic) -> Result<f64, String> {
+    let type_name = value.type_name();
+    value
+        .as_float()
+        .or_else(|_| value.as_int().map(|v| v as f64))
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| format!("expected a finite number, got {}", type_name))
+}
+
+impl ScriptedControl {
+    /// Compile the scripts of `config` for `solver`, whose named boundary
+    /// `features` were tagged with `tolerance`
+    pub fn new(
+        config: &ScriptConfig,
+        solver: &ShallowWaterSolver,
+        features: &[BoundaryFeature],
+        tolerance: Option<f64>,
+    ) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let compile = |what: String, script: &str| {
+            engine
+                .compile(script)
+                .map_err(|e| format!("{}: {}", what, e))
+        };
+        let locator = PointLocator::new(&solver.mesh);
+        let locate = |what: String, (x, y): (f64, f64)| {
+            locator
+                .locate(&solver.mesh, x, y)
+                .ok_or_else(|| format!("{} ({}, {}) is outside the mesh", what, x, y))
+        };
+        let feature = |name: &str| {
+            let mut named = features.iter().enumerate();
+            let k = named
+                .position(|(_, f)| f.name.as_deref() == Some(name))
+                .ok_or_else(|| format!("no boundary feature is named '{}'", name))?;
+            if features[k + 1..]
+                .iter()
+                .any(|f| f.name.as_deref() == Some(name))
+            {
+                return Err(format!("several boundary features are named '{}'", name));
+            }
+            Ok(k)
+        };
+
+        let mut probes = Vec::new();
+        for (name, &at) in &config.probes {
+            if !is_identifier(name) {
+                return Err(format!("probe name '{}' is not an identifier", name));
+            }
+            probes.push((name.clone(), locate(format!("probe '{}'", name), at)?));
+        }
+        let mut values = Vec::new();
+        for (name, script) in &config.boundaries {
+            let k = feature(name)?;
+            if matches!(features[k].kind, BoundaryKind::Wall | BoundaryKind::Open) {
+                return Err(format!(
+                    "boundary '{}' is neither a stage nor an inflow",
+                    name
+                ));
+            }
+            let script = compile(format!("boundary '{}'", name), script)?;
+            values.push((name.clone(), k, script));
+        }
+        let mut gates = Vec::new();
+        for (name, script) in &config.gates {
+            gates.push(Gate {
+                name: name.clone(),
+                feature: feature(name)?,
+                script: compile(format!("gate '{}'", name), script)?,
+                open: true,
+            });
+        }
+        let mut sources = Vec::new();
+        for source in &config.sources {
+            let what = format!("source '{}'", source.name);
+            sources.push(Source {
+                name: source.name.clone(),
+                cell: locate(what.clone(), source.at)?,
+                script: compile(what, &source.discharge)?,
+                discharge: 0.0,
+                added_volume: 0.0,
+            });
+        }
+        let boundary = (!values.is_empty() || !gates.is_empty())
+            .then(|| TaggedBoundary::new(&solver.mesh, features, tolerance));
+        Ok(ScriptedControl {
+            engine,
+            probes,
+            boundary,
+            kinds: features.iter().map(|f| f.kind).collect(),
+            values,
+            gates,
+            sources,
+        })
+    }
+
+    /// Evaluate the scripts and set the boundary for the next step
+    pub fn before_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        let mut scope = Scope::new();
+        scope.push("t", solver.time);
+        scope.push("dt", solver.dt);
+        for (name, cell) in &self.probes {
+            let (h, z) = (solver.state.h[*cell], solver.mesh.triangles[*cell].z_bed);
+            let (u, v) = solver.state.get_velocity(*cell, &solver.constants);
+            let mut probe = Map::new();
+            probe.insert("depth".into(), h.into());
+            probe.insert("level".into(), (z + h).into());
+            probe.insert("u".into(), u.into());
+            probe.insert("v".into(), v.into());
+            scope.push(name.clone(), probe);
+        }
+        let evaluate = |script: &AST, scope: &Scope| {
+            let mut scope = scope.clone();
+            self.engine
+                .eval_ast_with_scope::<Dynamic>(&mut scope, script)
+                .map_err(|e| e.to_string())
+        };
+
+        let mut kinds = self.kinds.clone();
+        for (name, k, script) in &self.values {
+            let value = evaluate(script, &scope).and_then(number);
+            let value = value.map_err(|e| format!("boundary '{}': {}", name, e))?;
+            match &mut kinds[*k] {
+                BoundaryKind::Stage { level } => *level = value,
+                BoundaryKind::Inflow { discharge } => *discharge = value,
+                BoundaryKind::Wall | BoundaryKind::Open => {}
+            }
+        }
+        for gate in &mut self.gates {
+            scope.push("open", gate.open);
+            let open = evaluate(&gate.script, &scope)
+                .and_then(|open| {
+                    open.as_bool()
+                        .map_err(|t| format!("expected a boolean, got {}", t))
+                })
+                .map_err(|e| format!("gate '{}': {}", gate.name, e))?;
+            scope.rewind(scope.len() - 1);
+            gate.open = open;
+            if !gate.open {
+                kinds[gate.feature] = BoundaryKind::Wall;
+            }
+        }
+        for source in &mut self.sources {
+            source.discharge = evaluate(&source.script, &scope)
+                .and_then(number)
+                .map_err(|e| format!("source '{}': {}", source.name, e))?;
+        }
+
+        if let Some(boundary) = &mut self.boundary {
+            for (k, &kind) in kinds.iter().enumerate() {
+                boundary.set_kind(k, kind);
+            }
+            solver.boundary_closure = Box::new(boundary.clone());
+        }
+        Ok(())
+    }
+
+    /// Add the source volumes of the step just taken
+    pub fn after_step(&mut self, solver: &mut ShallowWaterSolver) {
+        for source in &mut self.sources {
+            let i = source.cell;
+            let storage = solver.storage_area(i);
+            let volume = (source.discharge * solver.dt).max(-solver.state.h[i] * storage);
+            solver.state.h[i] += volume / storage;
+            if solver.state.h[i] < solver.constants.dry_tolerance {
+                solver.state.hu[i] = 0.0;
+                solver.state.hv[i] = 0.0;
+            }
+            source.added_volume += volume;
+            solver.record_coupled_volume(volume);
+        }
+    }
+
+    /// Whether feature-level scripts replace the boundary closure
+    pub fn controls_boundary(&self) -> bool {
+        self.boundary.is_some()
+    }
+
+    pub fn gate_open(&self, feature: usize) -> Option<bool> {
+        self.gates
+            .iter()
+            .find(|gate| gate.feature == feature)
+            .map(|gate| gate.open)
+    }
+}
+
+impl Coupling for ScriptedControl {
+    fn name(&self) -> String {
+        "control scripts".to_string()
+    }
+
+    fn before_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        ScriptedControl::before_step(self, solver)
+    }
+
+    fn after_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        ScriptedControl::after_step(self, solver);
+        Ok(())
+    }
+
+    fn report(&self) -> Vec<String> {
+        self.sources
+            .iter()
+            .map(|source| {
+                format!(
+                    "Source {}: {:+.3} m^3 added",
+                    source.name, source.added_volume
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::parse_boundary_features;
+    use crate::config::RunConfig;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    const FEATURES: &str = r#"{"type": "FeatureCollection", "features": [
+        {"type": "Feature", "properties": {"boundary": "inflow", "discharge": 0, "name": "river"},
+         "geometry": {"type": "LineString", "coordinates": [[0, -1], [0, 11]]}}
+    ]}"#;
+
+    #[test]
+    fn test_scripted_inflow_gate_and_source() {
+        // A closed basin fed by a river whose discharge ramps up with time,
+        // shut by a gate once the far end is 2 cm higher, and drained by a pump
+        let config = RunConfig::from_json(
+            r#"{"scripts": {
+                "probes": {"basin": [15, 5]},
+                "boundaries": {"river": "if t < 1 { 2.0 * t } else { 2 }"},
+                "gates": {"river": "if open { basin.depth < 1.02 } else { basin.depth < 1.01 }"},
+                "sources": [{"name": "pump", "at": [19, 9], "discharge": "-0.5"}]
+            }}"#,
+        )
+        .unwrap();
+        let mesh = TriangularMesh::new_rectangular(21, 11, 20.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.enable_mass_exchange().unwrap();
+        solver.state.h.iter_mut().for_each(|h| *h = 1.0);
+        let features = parse_boundary_features(FEATURES).unwrap();
+        let mut control = ScriptedControl::new(&config.scripts, &solver, &features, None).unwrap();
+        assert!(control.controls_boundary());
+
+        let volume = solver.compute_total_mass();
+        let (mut inflow, mut closed_at) = (0.0, None);
+        while solver.time < 10.0 {
+            control.before_step(&mut solver).unwrap();
+            let (t, open) = (solver.time, control.gate_open(0) == Some(true));
+            if !open && closed_at.is_none() {
+                closed_at = Some(t);
+            }
+            solver.step();
+            if open {
+                inflow += (2.0 * t).min(2.0) * solver.dt;
+            }
+            control.after_step(&mut solver);
+        }
+        let pumped = control.sources[0].added_volume;
+        assert!((pumped + 0.5 * solver.time).abs() < 1e-9, "{}", pumped);
+        let added = solver.compute_total_mass() - volume;
+        assert!(
+            (added - inflow - pumped).abs() < 1e-9,
+            "{} {}",
+            added,
+            inflow
+        );
+        let ledger = solver.mass_exchange.as_ref().unwrap();
+        assert!((ledger.coupled - pumped).abs() < 1e-9);
+        // The wave needs a few seconds to cross the basin
+        assert!(
+            closed_at.is_some_and(|t| t > 2.0 && t < 10.0),
+            "{:?}",
+            closed_at
+        );
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 4.0, 4.0, TopographyType::Flat);
+        let solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        let features = parse_boundary_features(FEATURES).unwrap();
+        let control = |json: &str| {
+            let config = RunConfig::from_json(json).unwrap();
+            ScriptedControl::new(&config.scripts, &solver, &features, None).map(|_| ())
+        };
+        assert!(control(r#"{"scripts": {"boundaries": {"sea": "1.0"}}}"#).is_err());
+        assert!(control(r#"{"scripts": {"boundaries": {"river": "1.0 +"}}}"#).is_err());
+        assert!(control(r#"{"scripts": {"probes": {"dt": [1, 1]}}}"#).is_err());
+        assert!(control(r#"{"scripts": {"probes": {"p": [9, 9]}}}"#).is_err());
+
+        let config = RunConfig::from_json(r#"{"scripts": {"gates": {"river": "42"}}}"#).unwrap();
+        let mut solver = solver;
+        let mut control = ScriptedControl::new(&config.scripts, &solver, &features, None).unwrap();
+        let error = control.before_step(&mut solver).unwrap_err();
+        assert!(error.contains("boolean"), "{}", error);
+    }
+}
@@ -0,0 +1,531 @@
+/// TELEMAC Selafin (Serafin) meshes and results
+/// A Selafin file is a sequence of big-endian Fortran records: the title
+/// (80 characters, ending in "SERAFIN " for single or "SERAFIND" for double
+/// precision), the variable counts and names (32 characters: 16 for the
+/// name, 16 for the unit), ten integer parameters (the third and fourth give
+/// an origin added to the coordinates, the tenth announces a date record),
+/// the element and node counts, the 1-based connectivity, the boundary node
+/// numbering and the node coordinates, then per time step the time and one
+/// record of node values per variable. Only 2D triangle meshes are read.
+///
+/// Results are written in single precision, one or two variables per
+/// selected output field, with the usual TELEMAC names where there is one.
+/// The solver stores cell averages; node values are area-weighted averages
+/// of the cells around each node (velocities from the momenta), so a written
+/// and re-read state is smoothed once.
+use crate::mesh::{barycentric, CoordinateSystem, Node, PointLocator, TriangularMesh};
+use crate::output::{FieldData, Frame, OutputField};
+use crate::solver::ShallowWaterSolver;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+/// Largest accepted record, so a corrupt file cannot trigger a huge allocation
+const MAX_RECORD_BYTES: usize = 1 << 31;
+
+/// Variables written for an output field (name and unit, 16 characters
+/// each)
+fn output_variables(field: OutputField) -> &'static [(&'static str, &'static str)] {
+    match field {
+        OutputField::Velocity => &[("VELOCITY U", "M/S"), ("VELOCITY V", "M/S")],
+        OutputField::Height => &[("WATER DEPTH", "M")],
+        OutputField::Surface => &[("FREE SURFACE", "M")],
+        OutputField::Bed => &[("BOTTOM", "M")],
+        OutputField::MomentumX => &[("FLOWRATE ALONG X", "M2/S")],
+        OutputField::MomentumY => &[("FLOWRATE ALONG Y", "M2/S")],
+        OutputField::FroudeNumber => &[("FROUDE NUMBER", "")],
+        OutputField::Vorticity => &[("VORTICITY", "1/S")],
+        OutputField::UnitDischarge => &[("SCALAR FLOWRATE", "M2/S")],
+        OutputField::BedShearStress => &[("BED SHEAR STRESS", "N/M2")],
+        OutputField::Courant => &[("COURANT NUMBER", "")],
+        OutputField::SedimentConcentration => &[("SEDIMENT CONC", "")],
+        OutputField::BedChange => &[("EVOLUTION", "M")],
+        OutputField::Scalar => &[("TRACER", "")],
+        OutputField::Partition => &[("PARTITION", "")],
+        OutputField::Color => &[("COLOR", "")],
+    }
+}
+
+/// English and French names of the variables used for restarts
+const BOTTOM: [&str; 2] = ["BOTTOM", "FOND"];
+const DEPTH: [&str; 2] = ["WATER DEPTH", "HAUTEUR D'EAU"];
+const SURFACE: [&str; 2] = ["FREE SURFACE", "SURFACE LIBRE"];
+const VELOCITY_U: [&str; 2] = ["VELOCITY U", "VITESSE U"];
+const VELOCITY_V: [&str; 2] = ["VELOCITY V", "VITESSE V"];
+
+/// Contents of a Selafin file
+#[derive(Clone)]
+pub struct Selafin {
+    pub title: String,
+    pub variables: Vec<(String, String)>, // (name, unit), trimmed
+    pub mesh: TriangularMesh,             // Nodes in file order, bed from BOTTOM/FOND if present
+    pub frames: Vec<(f64, Vec<Vec<f64>>)>, // (time, node values per variable)
+}
+
+impl Selafin {
+    pub fn read(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&mut BufReader::new(file)).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse<R: Read>(input: &mut R) -> Result<Self, String> {
+        let title_record = read_record(input)?.ok_or("empty file")?;
+        if title_record.len() != 80 {
+            return Err("not a Selafin file (bad title record)".to_string());
+        }
+        let double = title_record.ends_with(b"SERAFIND");
+        let title = String::from_utf8_lossy(&title_record[..72])
+            .trim()
+            .to_string();
+
+        let counts = integers(&required(input, "variable counts")?)?;
+        let n_variables = counts
+            .first()
+            .and_then(|&n| usize::try_from(n).ok())
+            .ok_or("invalid variable counts")?;
+        let mut variables = Vec::new();
+        for _ in 0..n_variables {
+            let record = required(input, "variable name")?;
+            let (name, unit) = record.split_at(16.min(record.len()));
+            let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).trim().to_string();
+            variables.push((text(name), text(unit)));
+        }
+
+        let parameters = integers(&required(input, "parameters")?)?;
+        if parameters.len() != 10 {
+            return Err("expected 10 integer parameters".to_string());
+        }
+        if parameters[6] > 1 {
+            return Err(format!(
+                "3D results with {} planes are not supported",
+                parameters[6]
+            ));
+        }
+        if parameters[9] == 1 {
+            required(input, "date")?;
+        }
+        let origin = (parameters[2] as f64, parameters[3] as f64);
+
+        let sizes = integers(&required(input, "mesh sizes")?)?;
+        let [n_elements, n_nodes, per_element] = match sizes[..] {
+            [a, b, c, _] if a > 0 && b > 0 => [a as usize, b as usize, c as usize],
+            _ => return Err("invalid mesh sizes".to_string()),
+        };
+        if per_element != 3 {
+            return Err(format!(
+                "elements with {} nodes are not supported (triangles only)",
+                per_element
+            ));
+        }
+        let ikle = integers(&required(input, "connectivity")?)?;
+        if ikle.len() != 3 * n_elements {
+            return Err("connectivity does not match the element count".to_string());
+        }
+        let connectivity = ikle
+            .chunks(3)
+            .map(|c| {
+                let index = |n: i32| {
+                    usize::try_from(n - 1)
+                        .ok()
+                        .filter(|&n| n < n_nodes)
+                        .ok_or_else(|| format!("node number {} out of range", n))
+                };
+                Ok([index(c[0])?, index(c[1])?, index(c[2])?])
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        required(input, "boundary numbering")?;
+        let x = reals(&required(input, "x coordinates")?, double)?;
+        let y = reals(&required(input, "y coordinates")?, double)?;
+        if x.len() != n_nodes || y.len() != n_nodes {
+            return Err("coordinates do not match the node count".to_string());
+        }
+
+        let mut frames = Vec::new();
+        while let Some(record) = read_record(input)? {
+            let time = *reals(&record, double)?.first().ok_or("empty time record")?;
+            let mut values = Vec::with_capacity(n_variables);
+            for (name, _) in &variables {
+                let record = read_record(input)?
+                    .ok_or_else(|| format!("time {}: missing values of {}", time, name))?;
+                let node_values = reals(&record, double)?;
+                if node_values.len() != n_nodes {
+                    return Err(format!("time {}: wrong number of {} values", time, name));
+                }
+                values.push(node_values);
+            }
+            frames.push((time, values));
+        }
+
+        let bed = find(&variables, &BOTTOM).and_then(|v| frames.first().map(|f| &f.1[v]));
+        let nodes = (0..n_nodes)
+            .map(|n| Node {
+                x: origin.0 + x[n],
+                y: origin.1 + y[n],
+                z: bed.map_or(0.0, |bed| bed[n]),
+            })
+            .collect();
+        let mesh =
+            TriangularMesh::from_triangles(nodes, &connectivity, CoordinateSystem::Cartesian)?;
+        Ok(Selafin {
+            title,
+            variables,
+            mesh,
+            frames,
+        })
+    }
+
+    /// Index of the variable with one of `names` (case-insensitive)
+    pub fn variable(&self, names: &[&str]) -> Option<usize> {
+        find(&self.variables, names)
+    }
+
+    /// Set depth and momenta of `solver` from the last frame, interpolated
+    /// linearly at the cell centroids. The depth comes from the water depth
+    /// or from the free surface minus the solver bed. Cells outside the
+    /// Selafin mesh are left unchanged; returns the time of the frame and
+    /// the number of cells set.
+    pub fn initialize(&self, solver: &mut ShallowWaterSolver) -> Result<(f64, usize), String> {
+        let (time, values) = self.frames.last().ok_or("the file has no time steps")?;
+        let depth = self.variable(&DEPTH).map(|v| &values[v]);
+        let surface = self.variable(&SURFACE).map(|v| &values[v]);
+        if depth.is_none() && surface.is_none() {
+            return Err("the file has neither a water depth nor a free surface".to_string());
+        }
+        let u = self.variable(&VELOCITY_U).map(|v| &values[v]);
+        let v = self.variable(&VELOCITY_V).map(|v| &values[v]);
+
+        let locator = PointLocator::new(&self.mesh);
+        let mut count = 0;
+        for i in 0..solver.mesh.triangles.len() {
+            let (x, y) = solver.mesh.triangles[i].centroid;
+            let Some(cell) = locator.locate(&self.mesh, x, y) else {
+                continue;
+            };
+            let Some(weights) = barycentric(&self.mesh, cell, (x, y)) else {
+                continue;
+            };
+            let nodes = self.mesh.triangles[cell].nodes;
+            let at = |field: &Vec<f64>| (0..3).map(|k| weights[k] * field[nodes[k]]).sum::<f64>();
+            let h = match (depth, surface) {
+                (Some(depth), _) => at(depth),
+                (None, Some(surface)) => at(surface) - solver.mesh.triangles[i].z_bed,
+                (None, None) => unreachable!(),
+            }
+            .max(0.0);
+            solver.state.h[i] = h;
+            solver.state.hu[i] = h * u.map_or(0.0, at);
+            solver.state.hv[i] = h * v.map_or(0.0, at);
+            count += 1;
+        }
+        Ok((*time, count))
+    }
+}
+
+fn find(variables: &[(String, String)], names: &[&str]) -> Option<usize> {
+    variables
+        .iter()
+        .position(|(name, _)| names.iter().any(|n| name.eq_ignore_ascii_case(n)))
+}
+
+/// Writes the results of a run to a Selafin file
+pub struct SelafinWriter {
+    file: BufWriter<File>,
+    fields: Vec<OutputField>,
+    node_area: Vec<f64>, // Area of the cells around each node
+}
+
+impl SelafinWriter {
+    /// Create `path` with the mesh of `solver` and the variables of `fields`
+    pub fn create(
+        path: &str,
+        solver: &ShallowWaterSolver,
+        fields: &[OutputField],
+        title: &str,
+    ) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let mesh = &solver.mesh;
+
+        let mut header = format!("{:<72.72}", title).into_bytes();
+        header.extend_from_slice(b"SERAFIN ");
+        write_record(&mut file, &header)?;
+        let variables: Vec<_> = fields.iter().flat_map(|&f| output_variables(f)).collect();
+        write_record(&mut file, &be_integers(&[variables.len() as i32, 0]))?;
+        for (name, unit) in variables {
+            write_record(&mut file, format!("{:<16}{:<16}", name, unit).as_bytes())?;
+        }
+
+        // Integer origin so large projected coordinates survive single precision
+        let origin = (
+            mesh.nodes
+                .iter()
+                .map(|n| n.x)
+                .fold(f64::INFINITY, f64::min)
+                .floor(),
+            mesh.nodes
+                .iter()
+                .map(|n| n.y)
+                .fold(f64::INFINITY, f64::min)
+                .floor(),
+        );
+        let mut parameters = [0i32; 10];
+        parameters[0] = 1;
+        parameters[2] = origin.0 as i32;
+        parameters[3] = origin.1 as i32;
+        write_record(&mut file, &be_integers(&parameters))?;
+        write_record(
+            &mut file,
+            &be_integers(&[mesh.triangles.len() as i32, mesh.nodes.len() as i32, 3, 1]),
+        )?;
+        let ikle: Vec<i32> = mesh
+            .triangles
+            .iter()
+            .flat_map(|t| t.nodes.map(|n| n as i32 + 1))
+            .collect();
+        write_record(&mut file, &be_integers(&ikle))?;
+
+        // Boundary nodes numbered in order of appearance on boundary edges
+        let mut boundary = vec![0i32; mesh.nodes.len()];
+        let mut next = 0;
+        for edge in mesh.edges.iter().filter(|e| e.right_triangle.is_none()) {
+            for &n in &edge.nodes {
+                if boundary[n] == 0 {
+                    next += 1;
+                    boundary[n] = next;
+                }
+            }
+        }
+        write_record(&mut file, &be_integers(&boundary))?;
+        let x: Vec<f64> = mesh.nodes.iter().map(|n| n.x - origin.0).collect();
+        let y: Vec<f64> = mesh.nodes.iter().map(|n| n.y - origin.1).collect();
+        write_record(&mut file, &be_reals(&x))?;
+        write_record(&mut file, &be_reals(&y))?;
+
+        let mut node_area = vec![0.0; mesh.nodes.len()];
+        for tri in &mesh.triangles {
+            for &n in &tri.nodes {
+                node_area[n] += tri.area;
+            }
+        }
+        Ok(SelafinWriter {
+            file,
+            fields: fields.to_vec(),
+            node_area,
+        })
+    }
+
+    /// Append the selected fields of `solver` as a time step
+    pub fn write(&mut self, solver: &ShallowWaterSolver) -> io::Result<()> {
+        let mesh = &solver.mesh;
+        let state = &solver.state;
+        let n = mesh.nodes.len();
+        let average = |values: &[f64]| -> Vec<f64> {
+            let mut sum = vec![0.0; n];
+            for (i, tri) in mesh.triangles.iter().enumerate() {
+                for &k in &tri.nodes {
+                    sum[k] += tri.area * values[i];
+                }
+            }
+            (0..n).map(|k| sum[k] / self.node_area[k]).collect()
+        };
+        let depth = average(&state.h);
+        let dry = solver.constants.dry_tolerance;
+        let velocity = |q: &[f64]| -> Vec<f64> {
+            let q = average(q);
+            (0..n)
+                .map(|k| if depth[k] > dry { q[k] / depth[k] } else { 0.0 })
+                .collect()
+        };
+        let bed: Vec<f64> = mesh.nodes.iter().map(|node| node.z).collect();
+
+        write_record(&mut self.file, &be_reals(&[solver.time]))?;
+        for &field in &self.fields {
+            let records = match field {
+                OutputField::Velocity => vec![velocity(&state.hu), velocity(&state.hv)],
+                OutputField::Height => vec![depth.clone()],
+                OutputField::Surface => vec![(0..n).map(|k| bed[k] + depth[k]).collect()],
+                OutputField::Bed => vec![bed.clone()],
+                _ => match &Frame::capture(solver, &[field]).arrays[0].1 {
+                    FieldData::Scalar(values) => vec![average(values)],
+                    FieldData::Vector(_) => unreachable!("only the velocity is a vector"),
+                },
+            };
+            for values in records {
+                write_record(&mut self.file, &be_reals(&values))?;
+            }
+        }
+        self.file.flush()
+    }
+}
+
+/// Next Fortran record, None at the end of the file
+fn read_record<R: Read>(input: &mut R) -> Result<Option<Vec<u8>>, String> {
+    let mut marker = [0u8; 4];
+    match input.read_exact(&mut marker) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    }
+    let len = u32::from_be_bytes(marker) as usize;
+    if len > MAX_RECORD_BYTES {
+        return Err("implausible record length".to_string());
+    }
+    let mut record = vec![0u8; len];
+    input
+        .read_exact(&mut record)
+        .and_then(|()| input.read_exact(&mut marker))
+        .map_err(|_| "truncated record".to_string())?;
+    if u32::from_be_bytes(marker) as usize != len {
+        return Err("record markers do not match".to_string());
+    }
+    Ok(Some(record))
+}
+
+fn required<R: Read>(input: &mut R, what: &str) -> Result<Vec<u8>, String> {
+    read_record(input)?.ok_or_else(|| format!("missing {} record", what))
+}
+
+fn integers(record: &[u8]) -> Result<Vec<i32>, String> {
+    if !record.len().is_multiple_of(4) {
+        return Err("integer record of odd length".to_string());
+    }
+    Ok(record
+        .chunks(4)
+        .map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+fn reals(record: &[u8], double: bool) -> Result<Vec<f64>, String> {
+    let size = if double { 8 } else { 4 };
+    if !record.len().is_multiple_of(size) {
+        return Err("real record of odd length".to_string());
+    }
+    Ok(record
+        .chunks(size)
+        .map(|b| {
+            if double {
+                f64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])
+            } else {
+                f32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64
+            }
+        })
+        .collect())
+}
+
+fn write_record<W: Write>(out: &mut W, record: &[u8]) -> io::Result<()> {
+    let marker = (record.len() as u32).to_be_bytes();
+    out.write_all(&marker)?;
+    out.write_all(record)?;
+    out.write_all(&marker)
+}
+
+fn be_integers(values: &[i32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_be_bytes()).collect()
+}
+
+fn be_reals(values: &[f64]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|&v| (v as f32).to_be_bytes())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_write_read_and_restart() {
+        let topography = TopographyType::Slope {
+            gradient_x: 0.01,
+            gradient_y: 0.0,
+        };
+        // Projected coordinates far from the origin
+        let mut mesh = TriangularMesh::new_rectangular(11, 6, 10.0, 5.0, topography);
+        for node in &mut mesh.nodes {
+            node.x += 512_000.0;
+            node.y += 6_100_000.0;
+        }
+        for tri in &mut mesh.triangles {
+            tri.centroid.0 += 512_000.0;
+            tri.centroid.1 += 6_100_000.0;
+        }
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.iter_mut().for_each(|h| *h = 1.0);
+        solver.state.hu.iter_mut().for_each(|hu| *hu = 0.5);
+
+        let path = std::env::temp_dir().join(format!("swe_selafin_{}.slf", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut writer =
+            SelafinWriter::create(path, &solver, &OutputField::DEFAULT, "round trip").unwrap();
+        writer.write(&solver).unwrap();
+        solver.time = 2.5;
+        writer.write(&solver).unwrap();
+        drop(writer);
+
+        let file = Selafin::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(file.title, "round trip");
+        assert_eq!(file.variables.len(), 7);
+        assert_eq!(file.frames.len(), 2);
+        assert_eq!(file.mesh.triangles.len(), solver.mesh.triangles.len());
+        assert_eq!(file.mesh.edges.len(), solver.mesh.edges.len());
+        for (a, b) in file.mesh.nodes.iter().zip(&solver.mesh.nodes) {
+            assert!((a.x - b.x).abs() < 1e-3 && (a.y - b.y).abs() < 1e-3);
+            assert!((a.z - b.z).abs() < 1e-6);
+        }
+
+        let mut restart = ShallowWaterSolver::new(file.mesh.clone(), 0.45, FrictionLaw::None);
+        let (time, count) = file.initialize(&mut restart).unwrap();
+        assert_eq!((time, count), (2.5, restart.mesh.triangles.len()));
+        assert!(restart.state.h.iter().all(|h| (h - 1.0).abs() < 1e-6));
+        assert!(restart.state.hu.iter().all(|hu| (hu - 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_writes_the_selected_fields() {
+        let mesh = TriangularMesh::new_rectangular(6, 6, 5.0, 5.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.iter_mut().for_each(|h| *h = 2.0);
+        solver.state.hv.iter_mut().for_each(|hv| *hv = 1.0);
+
+        let path = std::env::temp_dir().join(format!("swe_fields_{}.slf", std::process::id()));
+        let path = path.to_str().unwrap();
+        let fields = [OutputField::Velocity, OutputField::FroudeNumber];
+        let mut writer = SelafinWriter::create(path, &solver, &fields, "fields").unwrap();
+        writer.write(&solver).unwrap();
+        drop(writer);
+
+        let file = Selafin::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let names: Vec<&str> = file.variables.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["VELOCITY U", "VELOCITY V", "FROUDE NUMBER"]);
+        let values = &file.frames[0].1;
+        let froude = 0.5 / (9.81_f64 * 2.0).sqrt();
+        assert!(values[0].iter().all(|u| u.abs() < 1e-6));
+        assert!(values[1].iter().all(|v| (v - 0.5).abs() < 1e-6));
+        assert!(values[2].iter().all(|fr| (fr - froude).abs() < 1e-3));
+        // Neither a depth nor a surface to restart from
+        let mut restart = ShallowWaterSolver::new(file.mesh.clone(), 0.45, FrictionLaw::None);
+        assert!(file.initialize(&mut restart).is_err());
+    }
+
+    #[test]
+    fn test_rejects_malformed_files() {
+        let mut title = format!("{:<72}", "quads").into_bytes();
+        title.extend_from_slice(b"SERAFIN ");
+        let mut bytes = Vec::new();
+        write_record(&mut bytes, &title).unwrap();
+        write_record(&mut bytes, &be_integers(&[0, 0])).unwrap();
+        write_record(&mut bytes, &be_integers(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0])).unwrap();
+        write_record(&mut bytes, &be_integers(&[1, 4, 4, 1])).unwrap();
+        let error = Selafin::parse(&mut bytes.as_slice()).err().unwrap();
+        assert!(error.contains("triangles only"), "{}", error);
+
+        let mut truncated = Vec::new();
+        write_record(&mut truncated, &title).unwrap();
+        truncated.extend_from_slice(&8u32.to_be_bytes());
+        assert!(Selafin::parse(&mut truncated.as_slice()).is_err());
+        assert!(Selafin::parse(&mut [0u8; 3].as_slice()).is_err());
+    }
+}
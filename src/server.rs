@@ -0,0 +1,753 @@
+/// Simulation server (feature "serve")
+/// `serve` accepts runs over HTTP and executes each one as a child process of
+/// this executable, in its own directory below the runs directory, so a web
+/// front end can drive the solver without wrapper scripts:
+///
+/// ```text
+/// POST /runs                    submit a run, returns {"id": ...}
+/// GET  /runs                    status of all runs
+/// GET  /runs/{id}               status, simulated time and output files
+/// GET  /runs/{id}/gauges        gauge CSV, streamed until the run ends
+/// GET  /runs/{id}/log           console output of the run
+/// GET  /runs/{id}/files/{name}  an output file
+/// ```
+///
+/// A submission is a JSON object with command-line options and an optional
+/// configuration file:
+///
+/// ```json
+/// {
+///   "options": { "nx": 80, "final-time": 10, "gauges": "harbour=8,2", "track-wet-region": true },
+///   "config": { "physics": { "gravity": 9.81 } }
+/// }
+/// ```
+///
+/// Options are checked with the command-line parser before the run is queued;
+/// `true` adds a flag, arrays repeat the option. Only options taking numbers,
+/// choices or inline geometry can be submitted: options that name files,
+/// commands, libraries or network addresses on the server are rejected, and
+/// the server sets the output prefix and configuration file itself.
+/// Runs are executed by `jobs` worker threads in submission order.
+use crate::config::RunConfig;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Options a submission may set; anything else would let a client read,
+/// write or execute files on the server
+const SUBMITTABLE_OPTIONS: &[&str] = &[
+    // Mesh and numerics
+    "nx",
+    "ny",
+    "reorder",
+    "hole",
+    "width",
+    "height",
+    "coordinates",
+    "lon-min",
+    "lat-min",
+    "final-time",
+    "cfl",
+    "flux-scheme",
+    "gradient-method",
+    "discretization",
+    "time-integrator",
+    "precision",
+    "track-wet-region",
+    "min-dt",
+    "max-dt",
+    "dt-growth",
+    "source-splitting",
+    "max-substeps",
+    "theta",
+    "eddy-viscosity",
+    "max-courant",
+    "steady-state",
+    "steady-tolerance",
+    "max-iterations",
+    "local-time-stepping",
+    "deterministic",
+    "threads",
+    "memory-limit",
+    "max-walltime",
+    "dry-run",
+    "use-gpu",
+    "backend",
+    "validate-gpu",
+    "gpus",
+    // Initial condition
+    "initial-condition",
+    "initial-velocity",
+    "perturb-depth",
+    "perturb-velocity",
+    "perturb-length",
+    "perturb-seed",
+    "wave-amplitude",
+    "wave-width",
+    "wave-x",
+    "wave-y",
+    "wave-direction",
+    "breach-width",
+    "breach-center",
+    "dam-thickness",
+    "fault-x",
+    "fault-y",
+    "fault-depth",
+    "fault-strike",
+    "fault-dip",
+    "fault-rake",
+    "fault-slip",
+    "fault-length",
+    "fault-width",
+    "sea-level",
+    // Physics
+    "topography",
+    "max-bed-slope",
+    "slope-limit-depth",
+    "friction",
+    "manning-n",
+    "ice-cover",
+    "ice-manning",
+    "ice-blocks-wind",
+    "wind",
+    "chezy-c",
+    "voellmy-mu",
+    "voellmy-xi",
+    "yield-stress",
+    "bingham-viscosity",
+    "friction-zone",
+    "density",
+    "bulk-density",
+    "gravity",
+    "dry-tolerance",
+    "desingularization",
+    "friction-depth",
+    "sediment-diameter",
+    "sediment-density",
+    "sediment-density-coupling",
+    "scalar",
+    "scalar-ambient",
+    "outfall",
+    // Outputs
+    "output-interval",
+    "output-every",
+    "output-on",
+    "output-schedule",
+    "fields",
+    "output-region",
+    "output-outside-spacing",
+    "gauges",
+    "profile-line",
+    "profile-spacing",
+    "runup-line",
+    "runup",
+    "runup-depth",
+    "table-format",
+    "dt-diagnostics",
+    "check-symmetry",
+    "check-invariants",
+    "invariant-max-velocity",
+    "symmetry-tolerance",
+    "profile",
+    "profile-outputs",
+    "output-queue",
+    "geotiff",
+    "arrival-depth",
+    "scour-diameter",
+    "critical-shields",
+    "raster-cell-size",
+    "flood-extent",
+    "flood-extent-at",
+    "png",
+    "png-width",
+    "png-range",
+    "preview",
+    "preview-width",
+    "epsg",
+    "input-epsg",
+];
+const MAX_HEADER_BYTES: usize = 64 << 10;
+const MAX_BODY_BYTES: usize = 1 << 20;
+/// Interval at which streamed gauge files are checked for new rows
+const STREAM_POLL: Duration = Duration::from_millis(200);
+const OUTPUT_PREFIX: &str = "run";
+const LOG_FILE: &str = "log.txt";
+
+/// Checks submitted command-line arguments (program name first)
+pub type Validator = fn(&[String]) -> Result<(), String>;
+
+pub struct ServerConfig {
+    pub command: Vec<String>, // Program and leading arguments that start a run
+    pub runs_dir: PathBuf,
+    pub jobs: usize,
+    pub validate: Validator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Queued => "queued",
+            Status::Running => "running",
+            Status::Finished => "finished",
+            Status::Failed => "failed",
+        }
+    }
+
+    fn is_done(self) -> bool {
+        matches!(self, Status::Finished | Status::Failed)
+    }
+}
+
+struct Run {
+    arguments: Vec<String>,
+    dir: PathBuf,
+    status: Status,
+    exit_code: Option<i32>,
+    started: Option<Instant>,
+    wall_time: Option<f64>, // Seconds from start to exit
+}
+
+#[derive(Default)]
+struct Runs {
+    runs: Vec<Run>, // Indexed by id - 1
+    queue: VecDeque<usize>,
+}
+
+struct Shared {
+    config: ServerConfig,
+    runs: Mutex<Runs>,
+    queued: Condvar,
+}
+
+pub struct Server {
+    address: SocketAddr,
+    accept: thread::JoinHandle<()>,
+}
+
+impl Server {
+    /// Bind `address`, start the workers and accept requests in the background
+    pub fn start(address: &str, config: ServerConfig) -> Result<Self, String> {
+        std::fs::create_dir_all(&config.runs_dir)
+            .map_err(|e| format!("{}: {}", config.runs_dir.display(), e))?;
+        let listener = TcpListener::bind(address).map_err(|e| format!("{}: {}", address, e))?;
+        let address = listener.local_addr().map_err(|e| e.to_string())?;
+        let shared = Arc::new(Shared {
+            runs: Mutex::new(Runs::default()),
+            queued: Condvar::new(),
+            config,
+        });
+
+        for _ in 0..shared.config.jobs.max(1) {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || worker(&shared));
+        }
+        let accept = thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream, &shared) {
+                        eprintln!("Warning: server connection failed: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(Server { address, accept })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Serve until the process is stopped
+    pub fn wait(self) {
+        let _ = self.accept.join();
+    }
+}
+
+/// Execute queued runs one at a time
+fn worker(shared: &Shared) {
+    loop {
+        let (id, arguments, dir) = {
+            let mut runs = shared.runs.lock().unwrap();
+            let id = loop {
+                match runs.queue.pop_front() {
+                    Some(id) => break id,
+                    None => runs = shared.queued.wait(runs).unwrap(),
+                }
+            };
+            let run = &mut runs.runs[id - 1];
+            run.status = Status::Running;
+            run.started = Some(Instant::now());
+            (id, run.arguments.clone(), run.dir.clone())
+        };
+
+        let exit_code = execute(&shared.config.command, &arguments, &dir);
+        let mut runs = shared.runs.lock().unwrap();
+        let run = &mut runs.runs[id - 1];
+        run.status = match exit_code {
+            Ok(0) => Status::Finished,
+            _ => Status::Failed,
+        };
+        run.exit_code = exit_code.as_ref().ok().copied();
+        run.wall_time = run.started.map(|start| start.elapsed().as_secs_f64());
+        if let Err(e) = exit_code {
+            eprintln!("Warning: run {} could not be started: {}", id, e);
+        }
+    }
+}
+
+/// Run the solver in `dir` with its console output in the log file
+fn execute(command: &[String], arguments: &[String], dir: &std::path::Path) -> io::Result<i32> {
+    let log = File::create(dir.join(LOG_FILE))?;
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .args(arguments)
+        .arg("--output-prefix")
+        .arg(dir.join(OUTPUT_PREFIX))
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .status()?;
+    Ok(status.code().unwrap_or(-1))
+}
+
+/// Command-line arguments of a submission (without the program name)
+fn submission_arguments(submission: &Value) -> Result<Vec<String>, String> {
+    let object = submission
+        .as_object()
+        .ok_or("submission must be a JSON object")?;
+    if let Some(key) = object
+        .keys()
+        .find(|k| !["options", "config"].contains(&k.as_str()))
+    {
+        return Err(format!("unknown field '{}'", key));
+    }
+    let mut arguments = Vec::new();
+    let options = match object.get("options") {
+        Some(Value::Object(options)) => options.clone(),
+        Some(_) => return Err("options must be an object".to_string()),
+        None => Default::default(),
+    };
+    for (name, value) in &options {
+        if !SUBMITTABLE_OPTIONS.contains(&name.as_str()) {
+            return Err(format!("option '{}' cannot be submitted", name));
+        }
+        let values = match value {
+            Value::Array(values) => values.clone(),
+            value => vec![value.clone()],
+        };
+        for value in values {
+            match value {
+                Value::Bool(false) | Value::Null => {}
+                Value::Bool(true) => arguments.push(format!("--{}", name)),
+                // Joined so that a value cannot be read as another option
+                Value::Number(number) => arguments.push(format!("--{}={}", name, number)),
+                Value::String(text) => arguments.push(format!("--{}={}", name, text)),
+                _ => return Err(format!("invalid value for option '{}'", name)),
+            }
+        }
+    }
+    Ok(arguments)
+}
+
+impl Shared {
+    /// Validate and queue a submission; returns the run id
+    fn submit(&self, body: &[u8]) -> Result<usize, String> {
+        let submission: Value =
+            serde_json::from_slice(body).map_err(|e| format!("invalid JSON: {}", e))?;
+        let mut arguments = submission_arguments(&submission)?;
+        let config = match submission.get("config") {
+            Some(config) => {
+                let text = config.to_string();
+                RunConfig::from_json(&text)?;
+                Some(text)
+            }
+            None => None,
+        };
+        let mut argv = vec!["shallow-water-solver".to_string()];
+        argv.extend(arguments.iter().cloned());
+        (self.config.validate)(&argv)?;
+
+        let mut runs = self.runs.lock().unwrap();
+        let id = runs.runs.len() + 1;
+        let dir = self.config.runs_dir.join(id.to_string());
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        if let Some(text) = config {
+            let path = dir.join("config.json");
+            std::fs::write(&path, text).map_err(|e| format!("{}: {}", path.display(), e))?;
+            arguments.extend(["--config".to_string(), path.display().to_string()]);
+        }
+        runs.runs.push(Run {
+            arguments,
+            dir,
+            status: Status::Queued,
+            exit_code: None,
+            started: None,
+            wall_time: None,
+        });
+        runs.queue.push_back(id);
+        self.queued.notify_one();
+        Ok(id)
+    }
+
+    fn status(&self, id: usize) -> Option<Value> {
+        let runs = self.runs.lock().unwrap();
+        let run = runs.runs.get(id.checked_sub(1)?)?;
+        let log = std::fs::read_to_string(run.dir.join(LOG_FILE)).unwrap_or_default();
+        Some(json!({
+            "id": id,
+            "status": run.status.name(),
+            "exit_code": run.exit_code,
+            "time": simulated_time(&log),
+            "wall_time": run.wall_time.or(run.started.map(|s| s.elapsed().as_secs_f64())),
+            "arguments": run.arguments,
+            "files": output_files(&run.dir),
+        }))
+    }
+
+    fn run_dir(&self, id: usize) -> Option<PathBuf> {
+        let runs = self.runs.lock().unwrap();
+        Some(runs.runs.get(id.checked_sub(1)?)?.dir.clone())
+    }
+
+    fn is_done(&self, id: usize) -> bool {
+        self.runs.lock().unwrap().runs[id - 1].status.is_done()
+    }
+}
+
+/// Latest simulated time printed by a run ("  t = 1.234s, ...")
+fn simulated_time(log: &str) -> Option<f64> {
+    log.lines().rev().find_map(|line| {
+        let time = line.trim_start().strip_prefix("t = ")?;
+        time.split('s').next()?.parse().ok()
+    })
+}
+
+/// Names of the files in a run directory, sorted
+fn output_files(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().is_file())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+fn read_request(stream: &mut TcpStream) -> io::Result<Request> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let header_end = loop {
+        if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if data.len() > MAX_HEADER_BYTES {
+            return Err(io::Error::other("request headers too large"));
+        }
+        let n = stream.read(&mut buffer)?;
+        if n == 0 {
+            return Err(io::Error::other("connection closed"));
+        }
+        data.extend_from_slice(&buffer[..n]);
+    };
+    let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+    let mut words = head.split_whitespace();
+    let method = words.next().unwrap_or("").to_string();
+    let path = words.next().unwrap_or("/").to_string();
+    let length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if length > MAX_BODY_BYTES {
+        return Err(io::Error::other("request body too large"));
+    }
+    let mut body = data[header_end..].to_vec();
+    while body.len() < length {
+        let n = stream.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&buffer[..n]);
+    }
+    body.truncate(length);
+    Ok(Request { method, path, body })
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+fn respond_json(stream: &mut TcpStream, status: &str, value: &Value) -> io::Result<()> {
+    respond(
+        stream,
+        status,
+        "application/json",
+        value.to_string().as_bytes(),
+    )
+}
+
+fn content_type(name: &str) -> &'static str {
+    match name.rsplit('.').next() {
+        Some("csv") => "text/csv",
+        Some("json") => "application/json",
+        Some("txt") | Some("vtk") => "text/plain",
+        Some("png") => "image/png",
+        Some("tif") => "image/tiff",
+        _ => "application/octet-stream",
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    let request = read_request(&mut stream)?;
+    let path = request.path.split('?').next().unwrap_or("");
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let not_found = json!({ "error": "not found" });
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["runs"]) => match shared.submit(&request.body) {
+            Ok(id) => respond_json(
+                &mut stream,
+                "201 Created",
+                &json!({ "id": id, "status": "queued" }),
+            ),
+            Err(e) => respond_json(&mut stream, "400 Bad Request", &json!({ "error": e })),
+        },
+        ("GET", ["runs"]) => {
+            let count = shared.runs.lock().unwrap().runs.len();
+            let runs: Vec<Value> = (1..=count).filter_map(|id| shared.status(id)).collect();
+            respond_json(&mut stream, "200 OK", &Value::Array(runs))
+        }
+        ("GET", ["runs", id, rest @ ..]) => {
+            let Some(id) = id.parse().ok().filter(|&id| shared.run_dir(id).is_some()) else {
+                return respond_json(&mut stream, "404 Not Found", &not_found);
+            };
+            let dir = shared.run_dir(id).unwrap();
+            match rest {
+                [] => respond_json(&mut stream, "200 OK", &shared.status(id).unwrap()),
+                ["gauges"] => stream_gauges(&mut stream, shared, id),
+                ["log"] => {
+                    let log = std::fs::read(dir.join(LOG_FILE)).unwrap_or_default();
+                    respond(&mut stream, "200 OK", "text/plain", &log)
+                }
+                ["files", name] if output_files(&dir).iter().any(|f| f == name) => {
+                    let data = std::fs::read(dir.join(name))?;
+                    respond(&mut stream, "200 OK", content_type(name), &data)
+                }
+                _ => respond_json(&mut stream, "404 Not Found", &not_found),
+            }
+        }
+        _ => respond_json(&mut stream, "404 Not Found", &not_found),
+    }
+}
+
+/// Send the gauge CSV as it grows (chunked), ending when the run has ended
+/// and every row was sent
+fn stream_gauges(stream: &mut TcpStream, shared: &Shared, id: usize) -> io::Result<()> {
+    let path = shared
+        .run_dir(id)
+        .unwrap()
+        .join(format!("{}_gauges.csv", OUTPUT_PREFIX));
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/csv\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n"
+    )?;
+    let mut sent = 0;
+    loop {
+        // Check before reading, so rows written just before the exit are sent
+        let done = shared.is_done(id);
+        let mut new = Vec::new();
+        if let Ok(mut file) = File::open(&path) {
+            file.seek(SeekFrom::Start(sent))?;
+            file.read_to_end(&mut new)?;
+        }
+        // Only complete rows; the rest follows with the next poll
+        let complete = new.iter().rposition(|&b| b == b'\n').map_or(0, |k| k + 1);
+        if complete > 0 {
+            write!(stream, "{:x}\r\n", complete)?;
+            stream.write_all(&new[..complete])?;
+            stream.write_all(b"\r\n")?;
+            stream.flush()?;
+            sent += complete as u64;
+        }
+        if done {
+            return stream.write_all(b"0\r\n\r\n");
+        }
+        thread::sleep(STREAM_POLL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn json_body(response: &str) -> Value {
+        serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_submissions_cannot_name_server_files_or_commands() {
+        for option in [
+            "output-prefix",
+            "config",
+            "mesh-cache",
+            "selafin-output",
+            "sww-output",
+            "zarr-output",
+            "nest-save",
+            "results-db",
+            "swmm-command",
+            "plugin",
+            "hot-start",
+            "selafin-mesh",
+            "nest-from",
+            "rainfall",
+            "metrics",
+            "serve",
+            "live",
+        ] {
+            let submission = json!({ "options": { option: "/tmp/outside" } });
+            let error = submission_arguments(&submission).unwrap_err();
+            assert!(
+                error.contains("cannot be submitted"),
+                "{}: {}",
+                option,
+                error
+            );
+        }
+
+        let submission = json!({ "options": {
+            "nx": 20,
+            "fields": "--selafin-output=/tmp/outside",
+            "hole": ["1,1;2,1;2,2", "3,3;4,3;4,4"],
+            "track-wet-region": true,
+            "runup": false,
+        }});
+        assert_eq!(
+            submission_arguments(&submission).unwrap(),
+            [
+                "--fields=--selafin-output=/tmp/outside",
+                "--hole=1,1;2,1;2,2",
+                "--hole=3,3;4,3;4,4",
+                "--nx=20",
+                "--track-wet-region",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_submit_poll_and_fetch_run() {
+        // A stand-in for the solver that prints progress and writes gauges
+        let dir = std::env::temp_dir().join(format!("swe_server_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("solver.sh");
+        std::fs::write(
+            &script,
+            "while [ $# -gt 0 ]; do [ \"$1\" = --output-prefix ] && prefix=$2; shift; done\n\
+             echo '  t = 0.500s, dt = 0.1s'\n\
+             printf 'time,gauge,depth\\n0,a,1\\n' > \"${prefix}_gauges.csv\"\n",
+        )
+        .unwrap();
+        let config = ServerConfig {
+            command: vec!["sh".to_string(), script.display().to_string()],
+            runs_dir: dir.join("runs"),
+            jobs: 1,
+            validate: |argv| match argv.iter().any(|a| a == "--bogus") {
+                true => Err("unknown option --bogus".to_string()),
+                false => Ok(()),
+            },
+        };
+        let server = Server::start("127.0.0.1:0", config).unwrap();
+        let address = server.address();
+
+        let rejected = request(address, "POST", "/runs", r#"{"options": {"bogus": 1}}"#);
+        assert!(rejected.starts_with("HTTP/1.1 400"));
+        let reserved = request(address, "POST", "/runs", r#"{"options": {"config": "x"}}"#);
+        assert!(reserved.starts_with("HTTP/1.1 400"));
+
+        let submitted = request(
+            address,
+            "POST",
+            "/runs",
+            r#"{"options": {"nx": 20, "track-wet-region": true}, "config": {"physics": {"gravity": 9.8}}}"#,
+        );
+        assert!(submitted.starts_with("HTTP/1.1 201"));
+        let id = json_body(&submitted)["id"].as_u64().unwrap();
+
+        // The stream ends once the run has finished
+        let gauges = request(address, "GET", &format!("/runs/{}/gauges", id), "");
+        assert!(gauges.contains("chunked"));
+        assert!(gauges.contains("0,a,1\n"));
+
+        let status = json_body(&request(address, "GET", &format!("/runs/{}", id), ""));
+        assert_eq!(status["status"], "finished");
+        assert_eq!(status["time"], 0.5);
+        let arguments = status["arguments"].as_array().unwrap();
+        assert!(arguments.contains(&json!("--track-wet-region")));
+        assert!(arguments.contains(&json!("--config")));
+        assert!(status["files"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("run_gauges.csv")));
+
+        let file = request(address, "GET", &format!("/runs/{}/files/log.txt", id), "");
+        assert!(file.contains("t = 0.500s"));
+        let outside = request(address, "GET", &format!("/runs/{}/files/..", id), "");
+        assert!(outside.starts_with("HTTP/1.1 404"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
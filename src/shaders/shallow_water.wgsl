@@ -1,140 +1,210 @@
 // Shallow Water Equations GPU Compute Shader (WGSL)
-// Computes flux and updates for finite volume method
+// Explicit first-order Rusanov scheme with RK2 time stepping, matching the
+// CPU solver. Each invocation updates one cell from its three faces, so no
+// atomics are needed; `stage1` computes the half-step state, `stage2` the
+// full step from it.
+//
+// All floating-point data is of type `real`. The host replaces the alias
+// below with f64 on adapters with SHADER_F64, so the kernels avoid built-ins
+// that Vulkan only provides in single precision (pow, exp, log).
+alias real = f32;
 
 struct State {
-    h: f32,    // Water height
-    hu: f32,   // x-momentum
-    hv: f32,   // y-momentum
-    padding: f32,
+    h: real,    // Water height
+    hu: real,   // x-momentum
+    hv: real,   // y-momentum
+    padding: real,
+}
+
+// Faces are stored per cell with outward normals (unused slots have length 0)
+struct Cell {
+    normal_x: vec4<real>,
+    normal_y: vec4<real>,
+    length: vec4<real>,
+    area: real,
+    dzdx: real,     // Bed gradient
+    dzdy: real,
+    manning: real,  // Manning's n of the cell
+}
+
+struct SimulationParams {
+    dt: real,
+    g: real,        // Gravitational acceleration
+    dry_tolerance: real,
+    chezy: real,    // Chezy coefficient (friction == FRICTION_CHEZY)
+    desingularization: real, // Depth below which velocities are desingularized
+    friction_depth: real, // Depth below which friction vanishes
+    rest_speed: real, // Speed below which a cell is at rest
+    padding_c: real,
+    friction: u32,
+    n_stage1: u32, // Cells updated by stage1 (owned cells and first halo ring)
+    n_owned: u32,  // Cells updated by stage2
+    padding: u32,
 }
 
 @group(0) @binding(0)
-var<storage, read> input_state: array<State>;
+var<storage, read_write> state: array<State>;
 
 @group(0) @binding(1)
-var<storage, read_write> output_state: array<State>;
+var<storage, read_write> stage: array<State>;
 
 @group(0) @binding(2)
+var<storage, read> cells: array<Cell>;
+
+@group(0) @binding(3)
+var<storage, read> neighbours: array<vec4<u32>>;
+
+@group(0) @binding(4)
 var<uniform> params: SimulationParams;
 
-struct SimulationParams {
-    dt: f32,
-    g: f32,        // Gravitational acceleration
-    cfl: f32,
-    n_triangles: u32,
+const NO_NEIGHBOUR: u32 = 0xffffffffu;
+const FRICTION_MANNING: u32 = 1u;
+const FRICTION_CHEZY: u32 = 2u;
+
+fn load(i: u32, from_stage: bool) -> State {
+    if (from_stage) {
+        return stage[i];
+    }
+    return state[i];
 }
 
-// Compute wave speed for CFL condition
-fn wave_speed(h: f32, u: f32, v: f32, g: f32) -> f32 {
-    let velocity = sqrt(u * u + v * v);
-    let wave_speed = sqrt(g * h);
-    return velocity + wave_speed;
+// Velocity, zero in dry cells and desingularized in thin films:
+// 2h·hu / (h² + max(h², ε²))
+fn velocity(s: State) -> vec2<real> {
+    if (s.h > params.dry_tolerance) {
+        let h2 = s.h * s.h;
+        let eps = params.desingularization;
+        return 2.0 * s.h / (h2 + max(h2, eps * eps)) * vec2<real>(s.hu, s.hv);
+    }
+    return vec2<real>(0.0, 0.0);
+}
+
+// Rusanov (local Lax-Friedrichs) flux through a face with normal n
+fn rusanov_flux(l: State, r: State, n: vec2<real>) -> vec3<real> {
+    let g = params.g;
+    let vel_l = velocity(l);
+    let vel_r = velocity(r);
+    let un_l = dot(vel_l, n);
+    let un_r = dot(vel_r, n);
+
+    let f_l = vec3<real>(
+        l.hu * n.x + l.hv * n.y,
+        (l.hu * vel_l.x + 0.5 * g * l.h * l.h) * n.x + (l.hu * vel_l.y) * n.y,
+        (l.hv * vel_l.x) * n.x + (l.hv * vel_l.y + 0.5 * g * l.h * l.h) * n.y
+    );
+    let f_r = vec3<real>(
+        r.hu * n.x + r.hv * n.y,
+        (r.hu * vel_r.x + 0.5 * g * r.h * r.h) * n.x + (r.hu * vel_r.y) * n.y,
+        (r.hv * vel_r.x) * n.x + (r.hv * vel_r.y + 0.5 * g * r.h * r.h) * n.y
+    );
+
+    let s_max = max(abs(un_l) + sqrt(g * l.h), abs(un_r) + sqrt(g * r.h));
+    return 0.5 * (f_l + f_r - s_max * vec3<real>(r.h - l.h, r.hu - l.hu, r.hv - l.hv));
 }
 
-// HLL (Harten-Lax-van Leer) Riemann solver
-fn hll_flux(
-    h_l: f32, hu_l: f32, hv_l: f32,
-    h_r: f32, hu_r: f32, hv_r: f32,
-    nx: f32, ny: f32,
-    g: f32
-) -> vec3<f32> {
-    // Left state
-    let u_l = select(0.0, hu_l / h_l, h_l > 1e-10);
-    let v_l = select(0.0, hv_l / h_l, h_l > 1e-10);
-    let un_l = u_l * nx + v_l * ny;
-    
-    // Right state
-    let u_r = select(0.0, hu_r / h_r, h_r > 1e-10);
-    let v_r = select(0.0, hv_r / h_r, h_r > 1e-10);
-    let un_r = u_r * nx + v_r * ny;
-    
-    // Wave speeds
-    let c_l = sqrt(g * h_l);
-    let c_r = sqrt(g * h_r);
-    
-    let s_l = min(un_l - c_l, un_r - c_r);
-    let s_r = max(un_l + c_l, un_r + c_r);
-    
-    // Left flux
-    let f_h_l = h_l * un_l;
-    let f_hu_l = hu_l * un_l + 0.5 * g * h_l * h_l * nx;
-    let f_hv_l = hv_l * un_l + 0.5 * g * h_l * h_l * ny;
-    
-    // Right flux
-    let f_h_r = h_r * un_r;
-    let f_hu_r = hu_r * un_r + 0.5 * g * h_r * h_r * nx;
-    let f_hv_r = hv_r * un_r + 0.5 * g * h_r * h_r * ny;
-    
-    // HLL flux
-    var flux: vec3<f32>;
-    if (s_l >= 0.0) {
-        flux = vec3<f32>(f_h_l, f_hu_l, f_hv_l);
-    } else if (s_r <= 0.0) {
-        flux = vec3<f32>(f_h_r, f_hu_r, f_hv_r);
-    } else {
-        let denom = 1.0 / (s_r - s_l);
-        flux.x = (s_r * f_h_l - s_l * f_h_r + s_l * s_r * (h_r - h_l)) * denom;
-        flux.y = (s_r * f_hu_l - s_l * f_hu_r + s_l * s_r * (hu_r - hu_l)) * denom;
-        flux.z = (s_r * f_hv_l - s_l * f_hv_r + s_l * s_r * (hv_r - hv_l)) * denom;
+// Depth at a closed wall from the exact reflection problem: a rarefaction
+// when the flow leaves the wall, a shock (Newton iteration) when it runs in
+fn wall_depth(h: real, un: real) -> real {
+    let g = params.g;
+    let c = max(sqrt(g * h) + 0.5 * un, real(0.0));
+    var depth = c * c / g;
+    if (h <= 0.0 || un <= 0.0) {
+        return depth;
     }
-    
-    return flux;
+    for (var k = 0; k < 12; k++) {
+        let s = sqrt(0.5 * g * (1.0 / h + 1.0 / depth));
+        let f = (depth - h) * s - un;
+        let slope = s - (depth - h) * g / (4.0 * s * depth * depth);
+        depth = max(depth - f / slope, h);
+    }
+    return depth;
 }
 
-// Main compute kernel
+// Cube root, refined from a single-precision estimate by Newton steps
+fn cbrt(x: real) -> real {
+    var y = real(pow(f32(x), 1.0 / 3.0));
+    for (var k = 0; k < 2; k++) {
+        y -= (y * y * y - x) / (3.0 * y * y);
+    }
+    return y;
+}
+
+// Friction slope (S_f,x, S_f,y)
+fn friction_slope(c: Cell, h: real, vel: vec2<real>) -> vec2<real> {
+    let speed = length(vel);
+    if (speed < params.rest_speed || h <= params.friction_depth) {
+        return vec2<real>(0.0, 0.0);
+    }
+    var sf: real = 0.0;
+    if (params.friction == FRICTION_MANNING) {
+        sf = c.manning * c.manning * speed * speed / (h * cbrt(h));
+    } else if (params.friction == FRICTION_CHEZY) {
+        sf = speed * speed / (params.chezy * params.chezy * h);
+    }
+    return sf * vel / speed;
+}
+
+// Finite volume residual of cell i (flux out of the cell minus sources)
+fn residual(i: u32, from_stage: bool) -> vec3<real> {
+    let c = cells[i];
+    let nb = neighbours[i];
+    let me = load(i, from_stage);
+    let vel = velocity(me);
+
+    var r = vec3<real>(0.0, 0.0, 0.0);
+    for (var k = 0u; k < 3u; k++) {
+        if (c.length[k] == 0.0) {
+            continue;
+        }
+        let n = vec2<real>(c.normal_x[k], c.normal_y[k]);
+        if (nb[k] == NO_NEIGHBOUR) {
+            // Closed wall: no mass, pressure of the wall depth
+            let depth = wall_depth(me.h, dot(vel, n));
+            let pressure = 0.5 * params.g * depth * depth;
+            r += vec3<real>(0.0, pressure * n.x, pressure * n.y) * c.length[k];
+            continue;
+        }
+        r += rusanov_flux(me, load(nb[k], from_stage), n) * c.length[k];
+    }
+
+    if (me.h >= params.dry_tolerance) {
+        let sf = friction_slope(c, me.h, vel);
+        let gh = params.g * me.h;
+        r.y += (gh * sf.x + gh * c.dzdx) * c.area;
+        r.z += (gh * sf.y + gh * c.dzdy) * c.area;
+    }
+    return r;
+}
+
+fn update(base: State, r: vec3<real>, dt: real, area: real) -> State {
+    let h = max(base.h - dt * r.x / area, real(0.0));
+    if (h < params.dry_tolerance) {
+        return State(h, 0.0, 0.0, 0.0);
+    }
+    return State(h, base.hu - dt * r.y / area, base.hv - dt * r.z / area, 0.0);
+}
+
+// First RK2 stage: half step from `state` into `stage`
 @compute @workgroup_size(64)
-fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+fn stage1(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let idx = global_id.x;
-    
-    if (idx >= params.n_triangles) {
+    if (idx >= params.n_stage1) {
         return;
     }
-    
-    // Read current state
-    let state = input_state[idx];
-    let h = state.h;
-    let hu = state.hu;
-    let hv = state.hv;
-    
-    // Compute velocity
-    var u: f32 = 0.0;
-    var v: f32 = 0.0;
-    if (h > 1e-10) {
-        u = hu / h;
-        v = hv / h;
-    }
-    
-    // Simple explicit Euler update (placeholder)
-    // In a full implementation, you would:
-    // 1. Compute fluxes across all edges
-    // 2. Apply friction source terms
-    // 3. Apply topographic source terms
-    // 4. Update state
-    
-    // For now, just preserve state (will be enhanced)
-    output_state[idx] = state;
+    stage[idx] = update(state[idx], residual(idx, false), 0.5 * params.dt, cells[idx].area);
 }
 
-// Additional kernel for time step computation
-@compute @workgroup_size(256)
-fn compute_max_speed(
-    @builtin(global_invocation_id) global_id: vec3<u32>,
-    @builtin(local_invocation_id) local_id: vec3<u32>
-) {
+// Second RK2 stage: full step of `state` with the residual of `stage`;
+// thin films are removed as in the CPU boundary conditions
+@compute @workgroup_size(64)
+fn stage2(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let idx = global_id.x;
-    
-    // Compute local maximum wave speed
-    var local_max: f32 = 0.0;
-    
-    if (idx < params.n_triangles) {
-        let state = input_state[idx];
-        if (state.h > 1e-10) {
-            let u = state.hu / state.h;
-            let v = state.hv / state.h;
-            local_max = wave_speed(state.h, u, v, params.g);
-        }
+    if (idx >= params.n_owned) {
+        return;
+    }
+    var s = update(state[idx], residual(idx, true), params.dt, cells[idx].area);
+    if (s.h < params.dry_tolerance) {
+        s = State(0.0, 0.0, 0.0, 0.0);
     }
-    
-    // Would use shared memory reduction here for efficiency
-    // This is a simplified version
+    state[idx] = s;
 }
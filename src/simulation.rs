@@ -0,0 +1,560 @@
+/// Time-loop driver
+/// A `Simulation` advances a solver to the final time: it runs the couplings
+/// around every step, integrates the budgets, works out from the output
+/// cadence and schedule which outputs are due, hands every step to the
+/// observers that write them, and stops early at a wall-clock limit, saving
+/// a checkpoint to restart from. The command-line program, the live viewer,
+/// the batch modes and the tests share this loop, so that a new per-step
+/// concern is added once. `Simulation::new` builds the solver from the
+/// model in the configuration:
+///
+/// ```no_run
+/// # use shallow_water_solver::observers::Progress;
+/// # use shallow_water_solver::simulation::{Simulation, SimulationConfig};
+/// # fn main() -> Result<(), String> {
+/// let mut config = SimulationConfig::new(2.0, 0.5);
+/// config.model.grid.nx = 51;
+/// config.model.initial.condition = Some("circular-wave".to_string());
+/// let mut simulation = Simulation::new(config)?;
+/// simulation.add_observer(Box::new(Progress));
+/// let report = simulation.run()?;
+/// println!("{} steps", report.steps);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// `Simulation::with_solver` runs a solver set up by the caller, who reads
+/// it and the borrowed observers after the run:
+///
+/// ```no_run
+/// # use shallow_water_solver::mesh::{TopographyType, TriangularMesh};
+/// # use shallow_water_solver::output::FloodEnvelope;
+/// # use shallow_water_solver::simulation::{Simulation, SimulationConfig};
+/// # use shallow_water_solver::solver::{FrictionLaw, ShallowWaterSolver};
+/// # fn main() -> Result<(), String> {
+/// let mesh = TriangularMesh::new_rectangular(51, 11, 10.0, 2.0, TopographyType::Flat);
+/// let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+/// solver.set_dam_break(5.0);
+/// let mut envelope = FloodEnvelope::new(&solver, 0.01);
+/// let config = SimulationConfig::new(2.0, 0.5);
+/// let mut simulation = Simulation::with_solver(&mut solver, config)?;
+/// simulation.add_observer(Box::new(&mut envelope));
+/// simulation.run()?;
+/// # Ok(())
+/// # }
+/// ```
+use crate::cadence::{OutputCadence, OutputSchedule, OutputTrigger, ScheduleEntry};
+use crate::model::ModelConfig;
+use crate::output::{self, Frame, OutputField};
+use crate::profiler::Phase;
+use crate::solver::{Budget, BudgetTracker, ShallowWaterSolver, TimeIntegrator};
+use crate::walltime::WallClockLimit;
+use std::cmp::Ordering;
+use std::ops::{Deref, DerefMut};
+
+/// Model exchanging water or forcing with the solver around every step
+pub trait Coupling {
+    /// Name used in error messages
+    fn name(&self) -> String;
+
+    fn before_step(&mut self, _solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn after_step(&mut self, _solver: &mut ShallowWaterSolver) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Lines summarising the exchange over the run
+    fn report(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Release external resources once the run is over
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Output writer or diagnostic that sees the solver after every step
+pub trait Observer {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent);
+
+    /// Flush and summarise once the run is over
+    fn finish(self: Box<Self>, _solver: &ShallowWaterSolver) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// A borrowed observer stays with its owner, who reads it after the run
+impl<T: Observer + ?Sized> Observer for &mut T {
+    fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+        (**self).observe(solver, event);
+    }
+}
+
+pub struct SimulationConfig {
+    pub model: ModelConfig, // Solver built by `Simulation::new`
+    pub final_time: f64,
+    pub output_interval: f64,             // Common output cadence (s)
+    pub output_every: Option<usize>,      // Also every this many steps
+    pub output_on: Vec<OutputTrigger>,    // Also on these events
+    pub gauges: Vec<(String, usize)>,     // Named cells the triggers refer to
+    pub schedule: Vec<ScheduleEntry>,     // Streams on intervals of their own
+    pub budget: bool,                     // Integrate the energy and momentum budgets
+    pub walltime: Option<WallClockLimit>, // Stop early before this limit
+    pub checkpoint: Option<String>,       // Saved when stopped by the limit
+}
+
+impl SimulationConfig {
+    /// Run to `final_time` with the common outputs every `output_interval`
+    pub fn new(final_time: f64, output_interval: f64) -> Self {
+        SimulationConfig {
+            model: ModelConfig::default(),
+            final_time,
+            output_interval,
+            output_every: None,
+            output_on: Vec::new(),
+            gauges: Vec::new(),
+            schedule: Vec::new(),
+            budget: false,
+            walltime: None,
+            checkpoint: None,
+        }
+    }
+}
+
+/// A completed step as seen by the observers
+pub struct StepEvent<'a> {
+    pub step: usize,
+    pub common: bool,   // The common output cadence is due
+    pub output: bool,   // Some output is due, see `schedule`
+    pub stopping: bool, // Last step before the wall-clock limit
+    pub schedule: &'a OutputSchedule,
+    pub budget: Option<&'a BudgetTracker>,
+    initial_mass: f64,
+}
+
+impl StepEvent<'_> {
+    /// Mass error of `solver` in percent
+    pub fn mass_error(&self, solver: &ShallowWaterSolver) -> f64 {
+        mass_error_percent(solver, self.initial_mass)
+    }
+}
+
+/// Outcome of a run
+#[derive(Debug, Clone)]
+pub struct SimulationReport {
+    pub steps: usize,
+    pub time: f64,
+    pub stopped: bool, // At the wall-clock limit, before the final time
+    pub budget: Option<Budget>,
+}
+
+/// Solver built by the simulation or lent by the caller
+enum SolverSlot<'a> {
+    Owned(Box<ShallowWaterSolver>),
+    Borrowed(&'a mut ShallowWaterSolver),
+}
+
+impl Deref for SolverSlot<'_> {
+    type Target = ShallowWaterSolver;
+
+    fn deref(&self) -> &ShallowWaterSolver {
+        match self {
+            SolverSlot::Owned(solver) => solver,
+            SolverSlot::Borrowed(solver) => solver,
+        }
+    }
+}
+
+impl DerefMut for SolverSlot<'_> {
+    fn deref_mut(&mut self) -> &mut ShallowWaterSolver {
+        match self {
+            SolverSlot::Owned(solver) => solver,
+            SolverSlot::Borrowed(solver) => solver,
+        }
+    }
+}
+
+pub struct Simulation<'a> {
+    solver: SolverSlot<'a>,
+    pub steps: usize,
+    pub initial_mass: f64,
+    final_time: f64,
+    cadence: OutputCadence,
+    schedule: OutputSchedule,
+    budget: Option<BudgetTracker>,
+    walltime: Option<WallClockLimit>,
+    checkpoint: Option<String>,
+    couplings: Vec<Box<dyn Coupling + 'a>>,
+    observers: Vec<Box<dyn Observer + 'a>>,
+    stopped: bool,
+}
+
+impl<'a> Simulation<'a> {
+    /// Build the solver of `config.model`; fails on a model the solver
+    /// cannot run and on output triggers referring to unknown gauges
+    pub fn new(config: SimulationConfig) -> Result<Self, String> {
+        let solver = config.model.build()?;
+        Simulation::start(SolverSlot::Owned(Box::new(solver)), config)
+    }
+
+    /// Run `solver` as set up by the caller instead of `config.model`;
+    /// fails on output triggers referring to unknown gauges
+    pub fn with_solver(
+        solver: &'a mut ShallowWaterSolver,
+        config: SimulationConfig,
+    ) -> Result<Self, String> {
+        Simulation::start(SolverSlot::Borrowed(solver), config)
+    }
+
+    fn start(solver: SolverSlot<'a>, config: SimulationConfig) -> Result<Self, String> {
+        let cadence = OutputCadence::new(
+            &solver,
+            config.output_interval,
+            config.output_every,
+            config.output_on,
+            &config.gauges,
+        )?;
+        Ok(Simulation {
+            steps: 0,
+            initial_mass: solver.compute_total_mass(),
+            final_time: config.final_time,
+            cadence,
+            schedule: OutputSchedule::new(solver.time, &config.schedule),
+            budget: config.budget.then(|| BudgetTracker::new(&solver)),
+            walltime: config.walltime,
+            checkpoint: config.checkpoint,
+            couplings: Vec::new(),
+            observers: Vec::new(),
+            stopped: false,
+            solver,
+        })
+    }
+
+    /// Run to `final_time` without outputs or budgets, for callers that
+    /// only need the state at the end or follow it with observers
+    pub fn until(solver: &'a mut ShallowWaterSolver, final_time: f64) -> Self {
+        Simulation::with_solver(solver, SimulationConfig::new(final_time, f64::INFINITY))
+            .expect("no output triggers to resolve")
+    }
+
+    pub fn solver(&self) -> &ShallowWaterSolver {
+        &self.solver
+    }
+
+    pub fn solver_mut(&mut self) -> &mut ShallowWaterSolver {
+        &mut self.solver
+    }
+
+    /// Couplings run in the order they were added, before and after a step
+    pub fn add_coupling(&mut self, coupling: Box<dyn Coupling + 'a>) {
+        self.couplings.push(coupling);
+    }
+
+    pub fn couplings(&self) -> &[Box<dyn Coupling + 'a>] {
+        &self.couplings
+    }
+
+    /// Observers see every step in the order they were added
+    pub fn add_observer(&mut self, observer: Box<dyn Observer + 'a>) {
+        self.observers.push(observer);
+    }
+
+    /// At the final time or the wall-clock limit, or stalled on a time step
+    /// that is no longer finite
+    pub fn finished(&self) -> bool {
+        let before_end = self.solver.time.partial_cmp(&self.final_time) == Some(Ordering::Less);
+        self.stopped || !before_end || !self.solver.dt.is_finite()
+    }
+
+    /// Run to the final time or the wall-clock limit
+    pub fn run(&mut self) -> Result<SimulationReport, String> {
+        if let Some(limit) = &mut self.walltime {
+            limit.start_stepping();
+        }
+        while !self.finished() {
+            self.step()?;
+        }
+        if self.stopped {
+            if let Some(path) = &self.checkpoint {
+                save_checkpoint(&self.solver, path)
+                    .map_err(|e| format!("could not write checkpoint {}: {}", path, e))?;
+            }
+        }
+        Ok(self.report())
+    }
+
+    /// Take one step with the couplings around it, then show it to the
+    /// observers; their time counts as output in the solver's profile.
+    /// The step is shortened to land on the next output time or the final
+    /// time. Fails if the couplings left the solver in a configuration its
+    /// integrator cannot run.
+    pub fn step(&mut self) -> Result<(), String> {
+        let solver = &mut *self.solver;
+        for coupling in &mut self.couplings {
+            coupling
+                .before_step(solver)
+                .map_err(|e| format!("{}: {}", coupling.name(), e))?;
+        }
+        if matches!(solver.time_integrator, TimeIntegrator::SemiImplicit { .. }) {
+            if let Some(feature) = solver.semi_implicit_unsupported() {
+                return Err(format!(
+                    "the semi-implicit integrator does not support {}",
+                    feature
+                ));
+            }
+        }
+        let target = [
+            self.final_time,
+            self.cadence.next_time(),
+            self.schedule.next_time(solver.time),
+        ]
+        .into_iter()
+        .filter(|&time| time > solver.time)
+        .fold(f64::INFINITY, f64::min);
+        let limit = target - solver.time;
+        solver.step_limit = limit;
+        solver.step();
+        solver.step_limit = f64::INFINITY;
+        if solver.dt == limit {
+            // Rounding must not leave the clock a hair before the target
+            solver.time = target;
+        }
+        for coupling in &mut self.couplings {
+            coupling
+                .after_step(solver)
+                .map_err(|e| format!("{}: {}", coupling.name(), e))?;
+        }
+
+        self.steps += 1;
+        let stopping = self.walltime.as_mut().is_some_and(|limit| limit.step());
+        if let Some(budget) = &mut self.budget {
+            budget.update(&self.solver);
+        }
+        let common = self.cadence.due(&self.solver, self.steps) || stopping;
+        let output = self.schedule.advance(self.solver.time, common, stopping);
+        let event = StepEvent {
+            step: self.steps,
+            common,
+            output,
+            stopping,
+            schedule: &self.schedule,
+            budget: self.budget.as_ref(),
+            initial_mass: self.initial_mass,
+        };
+        for observer in &mut self.observers {
+            let start = self.solver.profiler.start();
+            observer.observe(&self.solver, &event);
+            self.solver.profiler.record(Phase::Output, start);
+        }
+        self.stopped = stopping;
+        Ok(())
+    }
+
+    pub fn report(&self) -> SimulationReport {
+        SimulationReport {
+            steps: self.steps,
+            time: self.solver.time,
+            stopped: self.stopped,
+            budget: self
+                .budget
+                .as_ref()
+                .map(|budget| budget.budget(&self.solver)),
+        }
+    }
+
+    /// Release the couplings and finish the observers; returns the errors
+    /// of doing so
+    pub fn finish(self) -> Vec<String> {
+        let solver = &*self.solver;
+        let couplings = self.couplings.into_iter().filter_map(|coupling| {
+            let name = coupling.name();
+            coupling.finish().err().map(|e| format!("{}: {}", name, e))
+        });
+        let observers = self
+            .observers
+            .into_iter()
+            .filter_map(|observer| observer.finish(solver).err());
+        couplings
+            .collect::<Vec<_>>()
+            .into_iter()
+            .chain(observers)
+            .collect()
+    }
+}
+
+/// Mass error in percent, net of the recorded exchanges when the solver
+/// keeps them
+pub fn mass_error_percent(solver: &ShallowWaterSolver, initial_mass: f64) -> f64 {
+    let mass = solver.compute_total_mass();
+    match &solver.mass_exchange {
+        Some(exchange) => exchange.error_percent(mass),
+        None => ((mass - initial_mass) / initial_mass * 100.0).abs(),
+    }
+}
+
+/// Save the conserved variables and the bed at full precision to a VTK
+/// file a run can restart from
+pub fn save_checkpoint(solver: &ShallowWaterSolver, path: &str) -> std::io::Result<()> {
+    let frame = Frame::capture(
+        solver,
+        &[
+            OutputField::Height,
+            OutputField::MomentumX,
+            OutputField::MomentumY,
+            OutputField::Bed,
+        ],
+    );
+    output::save_vtk(path, &solver.mesh, &frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::{FluxScheme, FrictionLaw};
+
+    fn dam_break() -> ShallowWaterSolver {
+        let mesh = TriangularMesh::new_rectangular(41, 6, 10.0, 2.5, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_dam_break(5.0);
+        solver
+    }
+
+    /// Adds a fixed volume to the first cell after every step
+    struct Source {
+        added: f64,
+    }
+
+    impl Coupling for Source {
+        fn name(&self) -> String {
+            "source".to_string()
+        }
+
+        fn after_step(&mut self, solver: &mut ShallowWaterSolver) -> Result<(), String> {
+            let volume = 1e-3;
+            solver.state.h[0] += volume / solver.mesh.triangles[0].area;
+            self.added += volume;
+            Ok(())
+        }
+
+        fn report(&self) -> Vec<String> {
+            vec![format!("{:.3}", self.added)]
+        }
+    }
+
+    /// Records the times of the common outputs
+    #[derive(Default)]
+    struct Recorder {
+        outputs: Vec<f64>,
+        last_step: usize,
+    }
+
+    impl Observer for Recorder {
+        fn observe(&mut self, solver: &ShallowWaterSolver, event: &StepEvent) {
+            assert_eq!(event.step, self.last_step + 1);
+            self.last_step = event.step;
+            if event.common {
+                self.outputs.push(solver.time);
+                assert!(event.output);
+                assert!(event.mass_error(solver) < 1e-9);
+            }
+        }
+
+        fn finish(self: Box<Self>, _solver: &ShallowWaterSolver) -> Result<(), String> {
+            Err(format!("{} outputs", self.outputs.len()))
+        }
+    }
+
+    #[test]
+    fn test_run_lands_on_output_and_final_times() {
+        let mut solver = dam_break();
+        let mut recorder = Recorder::default();
+        let mut simulation =
+            Simulation::with_solver(&mut solver, SimulationConfig::new(1.0, 0.25)).unwrap();
+        simulation.add_observer(Box::new(&mut recorder));
+        simulation.add_observer(Box::new(Recorder::default()));
+        let report = simulation.run().unwrap();
+        assert!(simulation.finished());
+        assert_eq!(simulation.finish(), ["4 outputs"]);
+        assert_eq!(report.steps, recorder.last_step);
+        assert!(!report.stopped);
+        assert!(report.budget.is_none());
+        assert_eq!(solver.time, 1.0);
+        assert_eq!(recorder.outputs, [0.25, 0.5, 0.75, 1.0]);
+
+        // Steps far longer than the run are cut to it
+        let mut solver = dam_break();
+        solver.time_integrator = TimeIntegrator::SemiImplicit {
+            theta: 0.5,
+            max_courant: 1e6,
+        };
+        let report = Simulation::until(&mut solver, 0.3).run().unwrap();
+        assert_eq!((report.steps, solver.time), (1, 0.3));
+
+        // ... which has no central-upwind variant
+        solver.flux_scheme = FluxScheme::CentralUpwind;
+        let error = Simulation::until(&mut solver, 1.0).run().unwrap_err();
+        assert_eq!(
+            error,
+            "the semi-implicit integrator does not support the central-upwind flux"
+        );
+        assert_eq!(solver.time, 0.3);
+    }
+
+    #[test]
+    fn test_couplings_and_walltime() {
+        let mut config = SimulationConfig::new(1.0, 0.25);
+        config.walltime = Some(WallClockLimit::new(std::time::Duration::ZERO));
+        config.budget = true;
+        let mut solver = dam_break();
+        let mut simulation = Simulation::with_solver(&mut solver, config).unwrap();
+        let mass = simulation.initial_mass;
+        simulation.add_coupling(Box::new(Source { added: 0.0 }));
+        let report = simulation.run().unwrap();
+        assert!(report.stopped);
+        assert_eq!(report.steps, 1);
+        assert!(report.budget.is_some());
+        assert_eq!(simulation.couplings()[0].report(), ["0.001"]);
+        assert!(simulation.finish().is_empty());
+        assert!((solver.compute_total_mass() - mass - 1e-3).abs() < 1e-9);
+
+        let mut solver = dam_break();
+        let mut config = SimulationConfig::new(1.0, 0.25);
+        config.output_on = vec![OutputTrigger::Wetting];
+        config.gauges = vec![("g".to_string(), 0)];
+        assert!(Simulation::with_solver(&mut solver, config).is_ok());
+        let mut config = SimulationConfig::new(1.0, 0.25);
+        config.output_on = crate::cadence::parse_triggers("depth:missing>0.1").unwrap();
+        assert!(Simulation::with_solver(&mut solver, config).is_err());
+    }
+
+    #[test]
+    fn test_simulation_builds_its_solver() {
+        let mut config = SimulationConfig::new(0.5, 0.25);
+        config.model.grid.nx = 41;
+        config.model.grid.ny = 6;
+        config.model.grid.height = 2.5;
+        let mut recorder = Recorder::default();
+        let mut simulation = Simulation::new(config).unwrap();
+        simulation.add_observer(Box::new(&mut recorder));
+        let report = simulation.run().unwrap();
+        assert_eq!(report.time, 0.5);
+
+        // As on a solver set up by hand
+        let mut solver = dam_break();
+        Simulation::with_solver(&mut solver, SimulationConfig::new(0.5, 0.25))
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(simulation.solver().state.h, solver.state.h);
+        drop(simulation);
+        assert_eq!(recorder.outputs, [0.25, 0.5]);
+
+        let mut config = SimulationConfig::new(1.0, 0.25);
+        config.model.cfl = 0.0;
+        assert!(Simulation::new(config).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+/// Degree-day snowmelt
+/// Each snow zone is a polygon with a snowpack (snow water equivalent, SWE)
+/// and an air temperature series. The melt rate follows the degree-day
+/// method, M = DDF max(T - T_base, 0), with the temperature interpolated
+/// linearly in time and held constant beyond the series. A zone melts
+/// until the cumulative melt since the start of the run reaches its SWE;
+/// because the forcing does not depend on the flow, the cumulative melt is
+/// integrated exactly from the series. The melt enters every cell of the
+/// zone (the first zone containing the centroid) as a mass source.
+use crate::mesh::{point_in_polygon, Polygon, TriangularMesh};
+use crate::solver::{ShallowWaterSolver, SourceTerm, State};
+use serde::Deserialize;
+
+/// Degree-day factor unit: mm/(°C day) to m/(°C s)
+const MM_PER_DEGREE_DAY: f64 = 1e-3 / 86400.0;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnowmeltConfig {
+    /// Temperature (°C) above which snow melts
+    #[serde(default)]
+    pub base_temperature: f64,
+    /// Default degree-day factor, mm/(°C day)
+    #[serde(default = "default_degree_day_factor")]
+    pub degree_day_factor: f64,
+    /// Default temperature series as [time (s), °C] pairs
+    #[serde(default)]
+    pub temperature: Vec<(f64, f64)>,
+    pub zones: Vec<SnowZoneConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnowZoneConfig {
+    #[serde(default)]
+    pub name: String,
+    pub polygon: Polygon,
+    pub swe: f64, // Snow water equivalent (mm)
+    pub degree_day_factor: Option<f64>,
+    pub temperature: Option<Vec<(f64, f64)>>,
+}
+
+fn default_degree_day_factor() -> f64 {
+    3.0
+}
+
+/// Snow zone with its temperature series and cumulative degree-seconds
+#[derive(Debug, Clone)]
+pub struct SnowZone {
+    pub name: String,
+    pub swe: f64, // m
+    factor: f64,  // m/(°C s)
+    times: Vec<f64>,
+    excess: Vec<f64>,     // T - T_base at the series times
+    cumulative: Vec<f64>, // ∫ max(T - T_base, 0) dt from the first time (°C s)
+    start: f64,           // The same integral at t = 0
+}
+
+/// ∫ max(e, 0) over an interval of length `dt` where e goes linearly from
+/// `a` to `b`
+fn positive_part(a: f64, b: f64, dt: f64) -> f64 {
+    if a >= 0.0 && b >= 0.0 {
+        0.5 * (a + b) * dt
+    } else if a <= 0.0 && b <= 0.0 {
+        0.0
+    } else {
+        let peak = a.max(b);
+        0.5 * peak * peak / (a - b).abs() * dt
+    }
+}
+
+impl SnowZone {
+    fn new(
+        name: String,
+        swe_mm: f64,
+        degree_day_factor: f64,
+        series: &[(f64, f64)],
+        base: f64,
+    ) -> Result<Self, String> {
+        if series.is_empty() {
+            return Err(format!("snow zone '{}' has no temperature series", name));
+        }
+        if series.windows(2).any(|w| w[1].0 <= w[0].0) {
+            return Err(format!(
+                "temperature times of zone '{}' must increase",
+                name
+            ));
+        }
+        if !(swe_mm >= 0.0 && degree_day_factor >= 0.0) {
+            return Err(format!(
+                "snow zone '{}' needs a non-negative SWE and degree-day factor",
+                name
+            ));
+        }
+        let times: Vec<f64> = series.iter().map(|p| p.0).collect();
+        let excess: Vec<f64> = series.iter().map(|p| p.1 - base).collect();
+        let mut cumulative = vec![0.0];
+        for k in 1..times.len() {
+            let step = positive_part(excess[k - 1], excess[k], times[k] - times[k - 1]);
+            cumulative.push(cumulative[k - 1] + step);
+        }
+        let mut zone = SnowZone {
+            name,
+            swe: swe_mm * 1e-3,
+            factor: degree_day_factor * MM_PER_DEGREE_DAY,
+            times,
+            excess,
+            cumulative,
+            start: 0.0,
+        };
+        zone.start = zone.degree_seconds(0.0);
+        Ok(zone)
+    }
+
+    /// Temperature excess T - T_base at `time`
+    fn excess_at(&self, time: f64) -> f64 {
+        let k = self.times.partition_point(|&t| t <= time);
+        if k == 0 {
+            return self.excess[0];
+        }
+        if k == self.times.len() {
+            return self.excess[k - 1];
+        }
+        let w = (time - self.times[k - 1]) / (self.times[k] - self.times[k - 1]);
+        (1.0 - w) * self.excess[k - 1] + w * self.excess[k]
+    }
+
+    /// ∫ max(T - T_base, 0) dt from the first series time to `time`
+    /// (negative before it)
+    fn degree_seconds(&self, time: f64) -> f64 {
+        let k = self.times.partition_point(|&t| t <= time);
+        if k == 0 {
+            return self.excess[0].max(0.0) * (time - self.times[0]);
+        }
+        let (t0, e0) = (self.times[k - 1], self.excess[k - 1]);
+        self.cumulative[k - 1] + positive_part(e0, self.excess_at(time), time - t0)
+    }
+
+    /// Water melted (m) between the start of the run and `time`
+    pub fn melted(&self, time: f64) -> f64 {
+        (self.factor * (self.degree_seconds(time) - self.start)).min(self.swe)
+    }
+
+    /// Melt rate (m/s) at `time`, zero once the snowpack is gone
+    pub fn melt_rate(&self, time: f64) -> f64 {
+        if self.melted(time) >= self.swe {
+            0.0
+        } else {
+            self.factor * self.excess_at(time).max(0.0)
+        }
+    }
+
+    /// Continue from `melted` (m) at `time`, e.g. after an updated forecast
+    /// replaced the temperature series
+    pub fn resume(&mut self, time: f64, melted: f64) {
+        if self.factor > 0.0 {
+            self.start = self.degree_seconds(time) - melted / self.factor;
+        }
+    }
+
+    /// Last time of the temperature series
+    pub fn end_time(&self) -> f64 {
+        self.times[self.times.len() - 1]
+    }
+}
+
+/// Snowmelt source term
+#[derive(Debug, Clone)]
+pub struct Snowmelt {
+    pub zones: Vec<SnowZone>,
+    pub zone_of: Vec<Option<usize>>, // Zone of each cell
+}
+
+impl Snowmelt {
+    pub fn new(config: &SnowmeltConfig, mesh: &TriangularMesh) -> Result<Self, String> {
+        if config.zones.is_empty() {
+            return Err("no snow zones".to_string());
+        }
+        let zones = config
+            .zones
+            .iter()
+            .enumerate()
+            .map(|(k, zone)| {
+                let name = if zone.name.is_empty() {
+                    (k + 1).to_string()
+                } else {
+                    zone.name.clone()
+                };
+                if zone.polygon.len() < 3 {
+                    return Err(format!("snow zone '{}' needs at least 3 vertices", name));
+                }
+                SnowZone::new(
+                    name,
+                    zone.swe,
+                    zone.degree_day_factor.unwrap_or(config.degree_day_factor),
+                    zone.temperature.as_deref().unwrap_or(&config.temperature),
+                    config.base_temperature,
+                )
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let zone_of = mesh
+            .triangles
+            .iter()
+            .map(|t| {
+                config
+                    .zones
+                    .iter()
+                    .position(|zone| point_in_polygon(t.centroid, &zone.polygon))
+            })
+            .collect();
+        Ok(Snowmelt { zones, zone_of })
+    }
+
+    pub fn read(path: &str, mesh: &TriangularMesh) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let config: SnowmeltConfig =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        Self::new(&config, mesh).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Carry over the melt of `previous` at `time` to the zones of the same
+    /// name; new zones start with their full snowpack
+    pub fn resume_from(&mut self, previous: &Snowmelt, time: f64) {
+        for zone in &mut self.zones {
+            if let Some(old) = previous.zones.iter().find(|z| z.name == zone.name) {
+                zone.resume(time, old.melted(time));
+            }
+        }
+    }
+
+    /// Area (m^2) of the cells in each zone
+    pub fn zone_areas(&self, mesh: &TriangularMesh) -> Vec<f64> {
+        let mut areas = vec![0.0; self.zones.len()];
+        for (tri, zone) in mesh.triangles.iter().zip(&self.zone_of) {
+            if let Some(k) = zone {
+                areas[*k] += tri.area;
+            }
+        }
+        areas
+    }
+}
+
+impl SourceTerm for Snowmelt {
+    fn name(&self) -> &str {
+        "snowmelt"
+    }
+
+    fn evaluate(&self, solver: &ShallowWaterSolver, _state: &State, i: usize) -> (f64, f64, f64) {
+        let rate = self.zone_of[i].map_or(0.0, |k| self.zones[k].melt_rate(solver.time));
+        (rate, 0.0, 0.0)
+    }
+
+    fn acts_on_dry_cells(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_degree_day_melt() {
+        // From -4 °C to +4 °C over one day, then +4 °C: no melt during the
+        // first half day, 4 mm/(°C day) x 1 °C day in the second half
+        let zone = SnowZone::new(
+            "test".into(),
+            10.0,
+            4.0,
+            &[(0.0, -4.0), (86400.0, 4.0)],
+            0.0,
+        )
+        .unwrap();
+        assert_eq!(zone.melt_rate(30000.0), 0.0);
+        assert!((zone.melted(86400.0) - 4e-3).abs() < 1e-12);
+        // 16 mm/day afterwards: the remaining 6 mm are gone after 9 h
+        let rate = 16e-3 / 86400.0;
+        assert!((zone.melt_rate(86400.0 + 3600.0) - rate).abs() < 1e-15);
+        assert!((zone.melted(86400.0 + 9.0 * 3600.0) - 10e-3).abs() < 1e-12);
+        assert_eq!(zone.melt_rate(86400.0 + 9.5 * 3600.0), 0.0);
+
+        // An updated forecast (+2 °C from the start) keeps the 4 mm already
+        // melted at the end of the first day
+        let mut updated = SnowZone::new("test".into(), 10.0, 4.0, &[(0.0, 2.0)], 0.0).unwrap();
+        updated.resume(86400.0, zone.melted(86400.0));
+        assert!((updated.melted(86400.0) - 4e-3).abs() < 1e-12);
+        // Another 8 mm over the next day exceed the 10 mm of SWE
+        assert!((updated.melted(2.0 * 86400.0) - 10e-3).abs() < 1e-12);
+        assert!((updated.melted(1.25 * 86400.0) - 6e-3).abs() < 1e-12);
+
+        assert!(SnowZone::new("t".into(), 10.0, 4.0, &[], 0.0).is_err());
+        assert!(SnowZone::new("t".into(), -1.0, 4.0, &[(0.0, 1.0)], 0.0).is_err());
+    }
+
+    #[test]
+    fn test_snowmelt_zones() {
+        let json = r#"{
+            "temperature": [[0, 5.0]],
+            "degree_day_factor": 4.0,
+            "zones": [
+                {"name": "west", "polygon": [[0, 0], [5, 0], [5, 10], [0, 10]], "swe": 0.1},
+                {"polygon": [[0, 0], [10, 0], [10, 10], [0, 10]], "swe": 100.0,
+                 "temperature": [[0, -1.0]]}
+            ]
+        }"#;
+        let config: SnowmeltConfig = serde_json::from_str(json).unwrap();
+        let mesh = TriangularMesh::new_rectangular(3, 3, 10.0, 10.0, TopographyType::Flat);
+        let snow = Snowmelt::new(&config, &mesh).unwrap();
+        assert_eq!(snow.zones[1].name, "2");
+        let areas = snow.zone_areas(&mesh);
+        assert!((areas[0] - 50.0).abs() < 1e-9 && (areas[1] - 50.0).abs() < 1e-9);
+
+        // 20 mm/day melts the west zone's 0.1 mm in 7.2 minutes; the east zone
+        // stays frozen
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(0.01);
+        let initial = solver.compute_total_mass();
+        solver.source_terms.push(Box::new(snow));
+        while solver.time < 900.0 {
+            solver.step();
+        }
+        // The step that empties the snowpack melts at the full rate
+        let melted = solver.compute_total_mass() - initial;
+        assert!(
+            (melted - 50.0 * 1e-4).abs() < 1e-2 * 50.0 * 1e-4,
+            "{}",
+            melted
+        );
+    }
+}
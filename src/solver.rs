@@ -1,21 +1,144 @@
 /// Shallow Water Equations solver using Finite Volume Method
 /// Solves: ∂U/∂t + ∂F/∂x + ∂G/∂y = S
 /// where U = [h, hu, hv]^T (water height, x-momentum, y-momentum)
-/// S includes bottom friction and topographic source terms
-use crate::mesh::{Edge, TriangularMesh};
+/// S holds the registered source terms (bottom friction, topography, ...)
+use crate::linear_solver::UnconvergedSolves;
+use crate::mesh::{CoordinateSystem, Edge, TriangularMesh};
+use crate::okada::FaultParameters;
+use crate::profiler::{Phase, Profiler};
+use crate::reduction;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::f64::consts::PI;
 
-const G: f64 = 9.81; // Gravitational acceleration (m/s^2)
+mod active_set;
+mod bed_motion;
+mod budget;
+mod buoyancy;
+mod central_upwind;
+mod closures;
+mod dg;
+mod exchange;
+mod friction_zones;
+mod ice;
+mod imex;
+mod initial_velocity;
+mod invariants;
+mod perturbation;
+mod porosity;
+mod roughness;
+mod sampling;
+mod sediment;
+mod semi_implicit;
+mod single_precision;
+mod source_terms;
+mod splitting;
+mod subgrid;
+mod timestep;
+
+pub use bed_motion::BedDeformation;
+pub use budget::{Budget, BudgetTracker};
+pub use buoyancy::{BuoyantScalar, Outfall, ScalarBuoyancy, ScalarKind};
+pub use closures::{
+    wall_depth, wall_flux, BoundaryClosure, EdgeState, NumericalFlux, ReflectiveWall, RusanovFlux,
+    Transmissive,
+};
+pub use dg::Discretization;
+pub use exchange::MassExchange;
+pub use friction_zones::{parse_friction_law, parse_friction_table, FrictionZones};
+pub use ice::IceCover;
+pub use initial_velocity::VelocityField;
+pub use perturbation::{Perturbation, RandomField};
+pub use porosity::{Porosity, MIN_STORAGE_POROSITY};
+pub use roughness::{LandUseRoughness, RoughnessTable};
+pub use sampling::SampledState;
+pub use sediment::{SedimentDensity, SedimentProperties, SuspendedSediment};
+pub use single_precision::Precision;
+pub use source_terms::{
+    default_source_terms, BedFriction, BedSlope, GriddedRainfall, Rainfall, Rotation, SourceTerm,
+    WindStress,
+};
+pub use splitting::SourceSplitting;
+pub use subgrid::{Hypsometry, Subgrid};
+pub use timestep::TimestepControl;
+
+const EARTH_ROTATION_RATE: f64 = 7.2921e-5; // Ω (rad/s)
+
+/// Speed (m/s) below which a cell counts as at rest wherever a direction or
+/// a time step is obtained by dividing by the speed
+pub(crate) const REST_SPEED: f64 = 1e-10;
+
+/// Physical and numerical constants shared by all solver modules
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhysicalConstants {
+    pub gravity: f64,       // Gravitational acceleration (m/s^2)
+    pub dry_tolerance: f64, // Depth below which a cell is treated as dry (m)
+    pub density: f64,       // Bulk density of the flowing mixture (kg/m^3)
+    #[serde(default = "default_desingularization")]
+    pub desingularization: f64, // Depth ε below which velocities are desingularized (m)
+    #[serde(default = "default_friction_depth")]
+    pub friction_depth: f64, // Depth below which the depth-dependent friction terms vanish (m)
+}
+
+fn default_desingularization() -> f64 {
+    1e-4
+}
+
+/// The friction laws divide by h or h^(4/3); they need a cut-off well above
+/// the dry tolerance, where the slope would still overflow
+fn default_friction_depth() -> f64 {
+    1e-6
+}
+
+impl Default for PhysicalConstants {
+    fn default() -> Self {
+        PhysicalConstants {
+            gravity: 9.81,
+            dry_tolerance: 1e-10,
+            density: 1000.0,
+            desingularization: default_desingularization(),
+            friction_depth: default_friction_depth(),
+        }
+    }
+}
+
+impl PhysicalConstants {
+    /// Velocity (u, v) from depth and momentum, zero in dry cells. The
+    /// desingularized form 2h·hu / (h² + max(h², ε²)) equals hu/h above
+    /// ε = `desingularization` and vanishes smoothly in thinner films,
+    /// whose speeds would otherwise dictate the time step; ε = 0 gives hu/h.
+    pub fn velocity(&self, h: f64, hu: f64, hv: f64) -> (f64, f64) {
+        if h <= self.dry_tolerance {
+            return (0.0, 0.0);
+        }
+        let h2 = h * h;
+        let eps2 = self.desingularization * self.desingularization;
+        let scale = 2.0 * h / (h2 + h2.max(eps2));
+        (hu * scale, hv * scale)
+    }
+}
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FrictionLaw {
     None,
     Manning { coefficient: f64 }, // Manning's n (s/m^(1/3))
     Chezy { coefficient: f64 },   // Chezy's C (m^(1/2)/s)
+    Voellmy { mu: f64, xi: f64 }, // Coulomb coefficient (-), turbulence coefficient (m/s^2)
+    Bingham { yield_stress: f64, viscosity: f64 }, // Yield stress (Pa), Bingham viscosity (Pa s)
 }
 
-#[derive(Debug, Clone)]
+impl FrictionLaw {
+    /// Laws with a yield (Coulomb) term can bring the flow to rest
+    fn has_yield_term(&self) -> bool {
+        matches!(
+            self,
+            FrictionLaw::Voellmy { .. } | FrictionLaw::Bingham { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct State {
     pub h: Vec<f64>,  // Water height
     pub hu: Vec<f64>, // x-momentum (h * u)
@@ -31,23 +154,126 @@ impl State {
         }
     }
 
-    pub fn get_velocity(&self, i: usize) -> (f64, f64) {
-        let h = self.h[i];
-        if h > 1e-10 {
-            (self.hu[i] / h, self.hv[i] / h)
+    /// Velocity (u, v) of cell `i`, desingularized as in
+    /// `PhysicalConstants::velocity`
+    pub fn get_velocity(&self, i: usize, constants: &PhysicalConstants) -> (f64, f64) {
+        constants.velocity(self.h[i], self.hu[i], self.hv[i])
+    }
+}
+
+/// Numerical flux and reconstruction
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FluxScheme {
+    /// First-order edge flux `ShallowWaterSolver::numerical_flux`, by
+    /// default Rusanov (local Lax-Friedrichs)
+    Rusanov,
+    /// Kurganov-Petrova central-upwind scheme with limited piecewise-linear
+    /// reconstruction of the free surface; well-balanced and positivity
+    /// preserving by construction
+    CentralUpwind,
+}
+
+/// Cell gradient reconstruction
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientMethod {
+    /// Green-Gauss with face values (default)
+    GreenGauss,
+    /// Inverse-distance weighted least squares over the face neighbours;
+    /// exact for linear fields on stretched triangles
+    LeastSquares,
+}
+
+/// Regularization of the topographic source term on steep terrain
+/// The bed slope magnitude is capped at `max_slope`, and in cells shallower
+/// than `shallow_depth` the slope is further scaled by h / shallow_depth so
+/// that thin films cannot be accelerated beyond what their depth supports.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BedSlopeLimit {
+    pub max_slope: f64,     // Maximum |∇z_b| (-)
+    pub shallow_depth: f64, // Depth below which the source is ramped down (m)
+}
+
+impl BedSlopeLimit {
+    fn apply(&self, gradient: (f64, f64), h: f64) -> (f64, f64) {
+        let magnitude = (gradient.0 * gradient.0 + gradient.1 * gradient.1).sqrt();
+        let mut scale = if magnitude > self.max_slope {
+            self.max_slope / magnitude
         } else {
-            (0.0, 0.0)
+            1.0
+        };
+        if h < self.shallow_depth {
+            scale *= h / self.shallow_depth;
         }
+        (gradient.0 * scale, gradient.1 * scale)
     }
 }
 
+/// Time integration scheme
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeIntegrator {
+    /// Explicit second-order Runge-Kutta (default)
+    RungeKutta2,
+    /// Semi-implicit free surface (Casulli type): gravity and surface-gradient
+    /// terms are weighted by theta in [0.5, 1] and solved implicitly, so the
+    /// time step is limited by advection and `max_courant` (gravity-wave
+    /// Courant number) instead of the explicit CFL condition.
+    SemiImplicit { theta: f64, max_courant: f64 },
+    /// Implicit-explicit Runge-Kutta (ARS(2,2,2)): fluxes explicit, stiff
+    /// source terms (friction) and the optional eddy viscosity (m^2/s)
+    /// implicit, so the step is the explicit CFL step however stiff they are
+    Imex { viscosity: f64 },
+}
+
+/// Outcome of a steady-state (pseudo-time) solve
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SteadyStateReport {
+    pub converged: bool,
+    pub iterations: usize,
+    pub residual_history: Vec<f64>, // Residual L2 norm per iteration
+}
+
 pub struct ShallowWaterSolver {
     pub mesh: TriangularMesh,
     pub state: State,
     pub time: f64,
     pub dt: f64,
+    pub step_limit: f64, // Longest next step, set by drivers to land on output and end times
+    unlimited_dt: Option<f64>, // Step before the last one was shortened to `step_limit`
     pub cfl: f64,
     pub friction: FrictionLaw,
+    pub constants: PhysicalConstants,
+    pub time_integrator: TimeIntegrator,
+    pub flux_scheme: FluxScheme,
+    pub numerical_flux: Box<dyn NumericalFlux>, // Edge flux of the first-order scheme
+    pub boundary_closure: Box<dyn BoundaryClosure>, // Ghost state outside boundary edges
+    pub gradient_method: GradientMethod,
+    pub bed_slope_limit: Option<BedSlopeLimit>,
+    pub manning_field: Option<Vec<f64>>, // Per-cell Manning's n replacing the law's coefficient
+    friction_zones: Option<FrictionZones>, // Per-cell law replacing `friction`
+    pub source_terms: Vec<Box<dyn SourceTerm>>, // Right-hand side of the explicit residual
+    pub profiler: Profiler,
+    pub precision: Precision, // Arithmetic of the explicit RK2 kernel
+    single_precision_kernel: Option<single_precision::Kernel<f32>>,
+    pub track_wet_region: bool, // Skip dry cells and edges in the explicit step
+    active_set: Option<active_set::ActiveSet>,
+    point_locator: std::sync::OnceLock<crate::mesh::PointLocator>, // Built on the first sample
+    bed_motion: Option<bed_motion::BedMotion>, // Prescribed bed displacement applied after each step
+    porosity: Option<porosity::PorosityFields>, // Building porosity (urban flooding)
+    subgrid: Option<Subgrid>, // Hypsometric curves of a fine DEM under coarse cells
+    pub sediment: Option<SuspendedSediment>, // Suspended sediment transported with the flow
+    pub ice_cover: Option<IceCover>, // River ice (its roughness is in manning_field)
+    pub buoyant_scalar: Option<BuoyantScalar>, // Temperature or salinity driving density currents
+    pub mass_exchange: Option<MassExchange>, // Volumes exchanged through the boundary and sources
+    pub timestep_control: Option<TimestepControl>, // Growth limit and retry of the explicit step
+    pub unconverged_solves: UnconvergedSolves, // Free-surface and viscosity solves stopped early
+    pub source_splitting: Option<SourceSplitting>, // Stiff source terms integrated after the update
+    pub discretization: Discretization, // Finite volumes or P1 DG (see set_discretization)
+    dg: Option<dg::DgField>,  // P1 solution of the DG discretization
+    #[cfg(feature = "gpu")]
+    pub gpu: Option<crate::gpu_solver::GpuBackend>, // Explicit steps on the GPU when set
 }
 
 impl ShallowWaterSolver {
@@ -60,8 +286,40 @@ impl ShallowWaterSolver {
             state,
             time: 0.0,
             dt: 0.001,
+            step_limit: f64::INFINITY,
+            unlimited_dt: None,
             cfl,
             friction,
+            constants: PhysicalConstants::default(),
+            time_integrator: TimeIntegrator::RungeKutta2,
+            flux_scheme: FluxScheme::Rusanov,
+            numerical_flux: Box::new(RusanovFlux),
+            boundary_closure: Box::new(ReflectiveWall),
+            gradient_method: GradientMethod::GreenGauss,
+            bed_slope_limit: None,
+            manning_field: None,
+            friction_zones: None,
+            source_terms: default_source_terms(),
+            profiler: Profiler::default(),
+            precision: Precision::Double,
+            single_precision_kernel: None,
+            track_wet_region: false,
+            active_set: None,
+            point_locator: std::sync::OnceLock::new(),
+            bed_motion: None,
+            porosity: None,
+            subgrid: None,
+            sediment: None,
+            ice_cover: None,
+            buoyant_scalar: None,
+            mass_exchange: None,
+            timestep_control: None,
+            unconverged_solves: UnconvergedSolves::default(),
+            source_splitting: None,
+            discretization: Discretization::FiniteVolume,
+            dg: None,
+            #[cfg(feature = "gpu")]
+            gpu: None,
         }
     }
 
@@ -70,20 +328,19 @@ impl ShallowWaterSolver {
         let max_speed = (0..self.mesh.triangles.len())
             .into_par_iter()
             .map(|i| {
-                let (u, v) = self.state.get_velocity(i);
+                let (u, v) = self.velocity(&self.state, i);
                 let h = self.state.h[i];
-                let c = (G * h).sqrt(); // Wave speed
+                let c = (self.constants.gravity * h).sqrt(); // Wave speed
                 (u * u + v * v).sqrt() + c
             })
             .reduce(|| 0.0, f64::max);
 
-        if max_speed > 1e-10 {
-            // Compute minimum element size
-            let min_size = self
-                .mesh
-                .triangles
-                .par_iter()
-                .map(|t| (t.area * 2.0).sqrt())
+        if max_speed > REST_SPEED {
+            // Compute minimum element size (inscribed radius 2A/P, which keeps
+            // the first-order update positive for CFL <= 1)
+            let min_size = (0..self.mesh.triangles.len())
+                .into_par_iter()
+                .map(|i| self.inscribed_radius(i))
                 .min_by(|a, b| a.partial_cmp(b).unwrap())
                 .unwrap_or(1.0);
 
@@ -91,31 +348,316 @@ impl ShallowWaterSolver {
         }
     }
 
-    /// Second-order Runge-Kutta time stepping
+    /// Inscribed radius 2A/P of a triangle (m), reduced where the storage
+    /// porosity is smaller than the conveyance porosity
+    fn inscribed_radius(&self, tri_idx: usize) -> f64 {
+        let tri = &self.mesh.triangles[tri_idx];
+        let perimeter: f64 = (0..3)
+            .map(|k| {
+                let (dx, dy) = self.mesh.node_delta(tri.nodes[k], tri.nodes[(k + 1) % 3]);
+                (dx * dx + dy * dy).sqrt()
+            })
+            .sum();
+        2.0 * tri.area / perimeter * self.porosity_size_factor(tri_idx)
+    }
+
+    /// Velocity of cell `i` using the configured dry tolerance and
+    /// desingularization depth
+    fn velocity(&self, state: &State, i: usize) -> (f64, f64) {
+        state.get_velocity(i, &self.constants)
+    }
+
+    /// Fastest signal speed |u| + sqrt(g h) in a cell
+    fn signal_speed(&self, state: &State, i: usize) -> f64 {
+        let (u, v) = self.velocity(state, i);
+        (u * u + v * v).sqrt() + (self.constants.gravity * state.h[i]).sqrt()
+    }
+
+    /// Per-cell pseudo time steps for local time stepping
+    /// Each cell uses its own CFL limit, with the signal speed taken as the
+    /// maximum over the cell and its neighbours so dry cells next to wet
+    /// ones still advance.
+    fn local_timesteps(&self) -> Vec<f64> {
+        (0..self.mesh.triangles.len())
+            .into_par_iter()
+            .map(|i| {
+                let speed = self.neighbourhood_signal_speed(i);
+                if speed > REST_SPEED {
+                    self.cfl * self.inscribed_radius(i) / speed
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    /// Weights of the mass flux of every edge into its left and right cell
+    /// for per-cell steps `dts`
+    /// An edge moves water over the smaller step of its two cells, so what
+    /// leaves one cell enters the other and the volume is conserved.
+    fn mass_flux_weights(&self, dts: &[f64]) -> Vec<(f64, f64)> {
+        let weight = |dt_edge: f64, dt_cell: f64| {
+            if dt_cell > 0.0 {
+                dt_edge / dt_cell
+            } else {
+                0.0
+            }
+        };
+        self.mesh
+            .edges
+            .iter()
+            .map(|edge| match edge.right_triangle {
+                Some(right) => {
+                    let dt_edge = dts[edge.left_triangle].min(dts[right]);
+                    (
+                        weight(dt_edge, dts[edge.left_triangle]),
+                        weight(dt_edge, dts[right]),
+                    )
+                }
+                None => (1.0, 1.0),
+            })
+            .collect()
+    }
+
+    /// Fastest signal speed over a cell and its face neighbours
+    fn neighbourhood_signal_speed(&self, i: usize) -> f64 {
+        self.mesh.triangles[i]
+            .neighbors
+            .iter()
+            .flatten()
+            .map(|&j| self.signal_speed(&self.state, j))
+            .fold(self.signal_speed(&self.state, i), f64::max)
+    }
+
+    /// Local Courant number dt s / r per cell for the current time step,
+    /// with s the neighbourhood signal speed and r the inscribed radius
+    /// The global step uses the fastest speed and the smallest cell anywhere,
+    /// so for the state it was computed from the values are at most the CFL
+    /// number with the explicit integrator.
+    pub fn courant_numbers(&self) -> Vec<f64> {
+        (0..self.mesh.triangles.len())
+            .into_par_iter()
+            .map(|i| self.dt * self.neighbourhood_signal_speed(i) / self.inscribed_radius(i))
+            .collect()
+    }
+
+    /// Cell with the largest local Courant number, i.e. the one that limits
+    /// the time step, and that Courant number
+    pub fn limiting_cell(&self) -> (usize, f64) {
+        (0..self.mesh.triangles.len())
+            .into_par_iter()
+            .map(|i| {
+                (
+                    i,
+                    self.dt * self.neighbourhood_signal_speed(i) / self.inscribed_radius(i),
+                )
+            })
+            .reduce(
+                || (0, 0.0),
+                |a, b| {
+                    if b.1 > a.1 || (b.1 == a.1 && b.0 < a.0) {
+                        b
+                    } else {
+                        a
+                    }
+                },
+            )
+    }
+
+    /// Area-weighted L2 norm of the residual rate R/A over h, hu and hv
+    fn residual_norm(&self, residual: &State) -> f64 {
+        let triangles = &self.mesh.triangles;
+        let sum = reduction::sum(triangles.len(), |i| {
+            let area = triangles[i].area;
+            let rh = residual.h[i] / area;
+            let rhu = residual.hu[i] / area;
+            let rhv = residual.hv[i] / area;
+            (rh * rh + rhu * rhu + rhv * rhv) * area
+        });
+        let total_area = reduction::sum(triangles.len(), |i| triangles[i].area);
+        (sum / total_area).sqrt()
+    }
+
+    /// Iterate in pseudo time until the residual L2 norm drops below
+    /// `tolerance` or `max_iterations` is reached.
+    /// With `local_time_stepping` each cell advances with its own CFL-limited
+    /// step (not time accurate) and each edge moves water over the smaller
+    /// step of its two cells, which keeps the volume; otherwise the global CFL step is used and
+    /// the solver clock advances.
+    pub fn solve_steady_state(
+        &mut self,
+        tolerance: f64,
+        max_iterations: usize,
+        local_time_stepping: bool,
+    ) -> SteadyStateReport {
+        // Stiff terms stay in the residual, which is what converges
+        let splitting = self.source_splitting.take();
+        let integrator = std::mem::replace(&mut self.time_integrator, TimeIntegrator::RungeKutta2);
+        let report = self.steady_state_iterations(tolerance, max_iterations, local_time_stepping);
+        self.source_splitting = splitting;
+        self.time_integrator = integrator;
+        report
+    }
+
+    fn steady_state_iterations(
+        &mut self,
+        tolerance: f64,
+        max_iterations: usize,
+        local_time_stepping: bool,
+    ) -> SteadyStateReport {
+        let mut residual_history = Vec::new();
+        self.active_set = None;
+
+        for iteration in 0..max_iterations {
+            let dts = local_time_stepping.then(|| self.local_timesteps());
+            let residual = match &dts {
+                Some(dts) => {
+                    let weights = self.mass_flux_weights(dts);
+                    self.compute_residual_weighted(&self.state, None, Some(&weights))
+                }
+                None => self.compute_residual(&self.state),
+            };
+            let norm = self.residual_norm(&residual);
+            residual_history.push(norm);
+
+            if norm < tolerance {
+                return SteadyStateReport {
+                    converged: true,
+                    iterations: iteration,
+                    residual_history,
+                };
+            }
+
+            if let Some(dts) = dts {
+                self.state = self.update_state_with(&self.state, &residual, |i| dts[i]);
+                if self.has_yield_friction() {
+                    self.apply_yield_resistance_with(|i| dts[i]);
+                }
+            } else {
+                self.compute_timestep();
+                self.state = self.update_state(&self.state, &residual, self.dt);
+                self.time += self.dt;
+                if self.has_yield_friction() {
+                    self.apply_yield_resistance(self.dt);
+                }
+            }
+            self.apply_boundary_conditions();
+        }
+
+        SteadyStateReport {
+            converged: false,
+            iterations: max_iterations,
+            residual_history,
+        }
+    }
+
+    /// Advance one time step with the configured integrator
     pub fn step(&mut self) {
+        if let Some(dt) = self.unlimited_dt.take() {
+            self.dt = dt;
+        }
+        #[cfg(feature = "gpu")]
+        if self.gpu.is_some() {
+            return self.step_gpu();
+        }
+        if self.discretization == Discretization::Dg1 {
+            self.step_dg();
+            return self.update_bed();
+        }
+        match self.time_integrator {
+            TimeIntegrator::RungeKutta2 if self.precision == Precision::Single => {
+                self.step_single_precision()
+            }
+            TimeIntegrator::RungeKutta2 => self.step_rk2(),
+            TimeIntegrator::SemiImplicit { theta, max_courant } => {
+                self.step_semi_implicit(theta, max_courant)
+            }
+            TimeIntegrator::Imex { viscosity } => self.step_imex(viscosity),
+        }
+        self.update_bed();
+    }
+
+    /// Second-order Runge-Kutta time stepping
+    fn step_rk2(&mut self) {
+        let start = self.profiler.start();
         self.compute_timestep();
+        self.profiler.record(Phase::Timestep, start);
+        self.refresh_active_set();
 
         // RK2 first stage
         let k1 = self.compute_residual(&self.state);
-        let state_intermediate = self.update_state(&self.state, &k1, 0.5 * self.dt);
-
-        // RK2 second stage
-        let k2 = self.compute_residual(&state_intermediate);
-        self.state = self.update_state(&self.state, &k2, self.dt);
+        let (dt, may_retry) = self.controlled_timestep();
+        self.dt = dt;
+        self.limit_step();
+        let (state_intermediate, new_state, rates, valid) = loop {
+            let checked = self.timestep_control.is_some();
+            let stage_valid = !checked || self.update_is_valid(&self.state, &k1, 0.5 * self.dt);
+            let state_intermediate = self.update_state(&self.state, &k1, 0.5 * self.dt);
+
+            // RK2 second stage, which builds the update
+            let mut rates = self
+                .mass_exchange
+                .as_ref()
+                .map(|_| exchange::MassRates::default());
+            let k2 = self.compute_residual_recording(&state_intermediate, rates.as_mut());
+            let update_valid =
+                stage_valid && (!checked || self.update_is_valid(&self.state, &k2, self.dt));
+            if !update_valid && may_retry {
+                // Retry with a smaller step rather than clamp negative depths
+                if let Some(dt) = self.retry_timestep() {
+                    self.dt = dt;
+                    continue;
+                }
+            }
+            let new_state = self.update_state(&self.state, &k2, self.dt);
+            break (state_intermediate, new_state, rates, update_valid);
+        };
+        self.accept_timestep(valid);
+        if let (Some(ledger), Some(rates)) = (&mut self.mass_exchange, rates) {
+            ledger.record(&rates, self.dt);
+        }
+        self.transport_sediment(&state_intermediate, &new_state.h);
+        self.transport_scalar(&state_intermediate, &new_state.h);
+        self.state = new_state;
+
+        if self.has_yield_friction() {
+            let start = self.profiler.start();
+            self.apply_yield_resistance(self.dt);
+            self.profiler.record(Phase::Sources, start);
+        }
+        if self.source_splitting.is_some() {
+            let start = self.profiler.start();
+            let volume = self.apply_split_sources(self.dt);
+            if let Some(ledger) = &mut self.mass_exchange {
+                ledger.sources += volume;
+            }
+            self.profiler.record(Phase::Sources, start);
+        }
 
+        let start = self.profiler.start();
         self.apply_boundary_conditions();
+        self.profiler.record(Phase::Update, start);
         self.time += self.dt;
     }
 
     fn update_state(&self, state: &State, residual: &State, dt: f64) -> State {
+        self.update_state_with(state, residual, |_| dt)
+    }
+
+    /// Explicit update with a (possibly) cell-dependent time step
+    fn update_state_with<F>(&self, state: &State, residual: &State, dt_of: F) -> State
+    where
+        F: Fn(usize) -> f64 + Sync,
+    {
+        let start = self.profiler.start();
         let n = self.mesh.triangles.len();
 
         // Compute new values in parallel
         let new_h: Vec<f64> = (0..n)
             .into_par_iter()
             .map(|i| {
-                let area = self.mesh.triangles[i].area;
-                let h = state.h[i] - dt * residual.h[i] / area;
+                let area = self.storage_area(i);
+                let h = state.h[i] - dt_of(i) * residual.h[i] / area;
                 h.max(0.0) // Ensure positive depth
             })
             .collect();
@@ -123,9 +665,9 @@ impl ShallowWaterSolver {
         let new_hu: Vec<f64> = (0..n)
             .into_par_iter()
             .map(|i| {
-                let area = self.mesh.triangles[i].area;
-                let hu = state.hu[i] - dt * residual.hu[i] / area;
-                if new_h[i] < 1e-10 {
+                let area = self.storage_area(i);
+                let hu = state.hu[i] - dt_of(i) * residual.hu[i] / area;
+                if new_h[i] < self.constants.dry_tolerance {
                     0.0
                 } else {
                     hu
@@ -136,9 +678,9 @@ impl ShallowWaterSolver {
         let new_hv: Vec<f64> = (0..n)
             .into_par_iter()
             .map(|i| {
-                let area = self.mesh.triangles[i].area;
-                let hv = state.hv[i] - dt * residual.hv[i] / area;
-                if new_h[i] < 1e-10 {
+                let area = self.storage_area(i);
+                let hv = state.hv[i] - dt_of(i) * residual.hv[i] / area;
+                if new_h[i] < self.constants.dry_tolerance {
                     0.0
                 } else {
                     hv
@@ -146,91 +688,153 @@ impl ShallowWaterSolver {
             })
             .collect();
 
-        State {
+        let mut updated = State {
             h: new_h,
             hu: new_hu,
             hv: new_hv,
+        };
+        if self.flux_scheme == FluxScheme::CentralUpwind {
+            self.desingularize_momentum(&mut updated);
         }
+        self.profiler.record(Phase::Update, start);
+        updated
     }
 
     /// Compute spatial residual using finite volume method
     fn compute_residual(&self, state: &State) -> State {
+        self.compute_residual_recording(state, None)
+    }
+
+    /// Spatial residual, also recording the boundary and source mass rates
+    /// into `rates`
+    fn compute_residual_recording(
+        &self,
+        state: &State,
+        rates: Option<&mut exchange::MassRates>,
+    ) -> State {
+        self.compute_residual_weighted(state, rates, None)
+    }
+
+    /// Spatial residual with the mass flux of every edge into its left and
+    /// right cell scaled by `mass_weights`
+    fn compute_residual_weighted(
+        &self,
+        state: &State,
+        mut rates: Option<&mut exchange::MassRates>,
+        mass_weights: Option<&[(f64, f64)]>,
+    ) -> State {
+        if self.flux_scheme == FluxScheme::CentralUpwind {
+            return self.compute_central_upwind_residual(state, rates, mass_weights);
+        }
+
+        let start = self.profiler.start();
         let mut residual = State::new(self.mesh.triangles.len());
+        let ghosts = self.fill_ghosts(state);
 
-        // Loop over all edges and compute fluxes
-        for edge in &self.mesh.edges {
-            let flux = self.compute_flux(edge, state);
+        // Loop over all edges (or those touching the wet region) and
+        // compute fluxes
+        let edges: Box<dyn Iterator<Item = usize>> = match &self.active_set {
+            Some(set) => Box::new(set.edges.iter().copied()),
+            None => Box::new(0..self.mesh.edges.len()),
+        };
+        for e in edges {
+            let edge = &self.mesh.edges[e];
+            let (flux, pressure) = match self.subgrid_flux(edge, state) {
+                Some(reconstructed) => reconstructed,
+                None => (self.compute_flux(edge, state, &ghosts), (0.0, 0.0)),
+            };
+            let length = self.open_length(e);
+            let (nx, ny) = edge.normal;
+            let (weight_l, weight_r) = mass_weights.map_or((1.0, 1.0), |w| w[e]);
 
             // Add flux contribution to left triangle
             let left = edge.left_triangle;
-            residual.h[left] += flux.0 * edge.length;
-            residual.hu[left] += flux.1 * edge.length;
-            residual.hv[left] += flux.2 * edge.length;
+            residual.h[left] += flux.0 * length * weight_l;
+            residual.hu[left] += (flux.1 + pressure.0 * nx) * length;
+            residual.hv[left] += (flux.2 + pressure.0 * ny) * length;
 
             // Subtract flux contribution from right triangle (if exists)
-            if let Some(right) = edge.right_triangle {
-                residual.h[right] -= flux.0 * edge.length;
-                residual.hu[right] -= flux.1 * edge.length;
-                residual.hv[right] -= flux.2 * edge.length;
+            match edge.right_triangle {
+                Some(right) => {
+                    residual.h[right] -= flux.0 * length * weight_r;
+                    residual.hu[right] -= (flux.1 + pressure.1 * nx) * length;
+                    residual.hv[right] -= (flux.2 + pressure.1 * ny) * length;
+                }
+                None => {
+                    if let Some(rates) = rates.as_deref_mut() {
+                        rates.boundary.push((e, flux.0 * length));
+                    }
+                }
             }
         }
+        self.add_wall_reaction(&mut residual, state);
+        self.profiler.record(Phase::Flux, start);
 
-        // Add source terms (friction and topography)
-        self.add_source_terms(&mut residual, state);
+        // Add the registered source terms
+        let sources = self.add_source_terms(&mut residual, state);
+        if let Some(rates) = rates {
+            rates.sources = sources;
+        }
 
         residual
     }
 
-    /// Add source terms: bottom friction and topographic gradients
-    fn add_source_terms(&self, residual: &mut State, state: &State) {
-        // Parallel computation of source terms
-        let source_contributions: Vec<_> = (0..self.mesh.triangles.len())
+    /// Apply Voellmy/Bingham resistance as a split step.
+    /// The Coulomb/yield part can stop the flow but must never reverse it,
+    /// so the momentum magnitude is reduced and clamped at zero.
+    fn apply_yield_resistance(&mut self, dt: f64) {
+        self.apply_yield_resistance_with(|_| dt)
+    }
+
+    /// Yield resistance with a (possibly) cell-dependent time step
+    fn apply_yield_resistance_with<F>(&mut self, dt_of: F)
+    where
+        F: Fn(usize) -> f64 + Sync,
+    {
+        let updated: Vec<(f64, f64)> = (0..self.mesh.triangles.len())
             .into_par_iter()
             .map(|i| {
-                let tri = &self.mesh.triangles[i];
-                let h = state.h[i];
-                let (u, v) = state.get_velocity(i);
-
-                if h < 1e-10 {
-                    return (0.0, 0.0, 0.0);
+                let h = self.state.h[i];
+                let (hu, hv) = (self.state.hu[i], self.state.hv[i]);
+                if h < self.constants.dry_tolerance || !self.friction_law(i).has_yield_term() {
+                    return (hu, hv);
                 }
 
-                // Bottom friction source term
-                let (sf_x, sf_y) = self.compute_friction_slope(h, u, v);
-
-                // Topographic source term: -g * h * ∇z_b
-                let (dzdx, dzdy) = self.compute_bed_gradient(i);
-
-                // Combine friction and topography contributions
-                let dhu = -G * h * (sf_x + dzdx) * tri.area;
-                let dhv = -G * h * (sf_y + dzdy) * tri.area;
+                let (u, v) = self.velocity(&self.state, i);
+                let (sf_x, sf_y) = self.compute_friction_slope(i, h, u, v);
+                let momentum_mag = (hu * hu + hv * hv).sqrt();
+                if momentum_mag < 1e-12 {
+                    return (0.0, 0.0);
+                }
 
-                (0.0, dhu, dhv) // No mass source term
+                let reduction =
+                    dt_of(i) * self.constants.gravity * h * (sf_x * sf_x + sf_y * sf_y).sqrt();
+                let scale = (momentum_mag - reduction).max(0.0) / momentum_mag;
+                (hu * scale, hv * scale)
             })
             .collect();
 
-        // Apply contributions sequentially (fast, no contention)
-        for (i, (dh, dhu, dhv)) in source_contributions.iter().enumerate() {
-            residual.h[i] += dh;
-            residual.hu[i] += dhu;
-            residual.hv[i] += dhv;
+        for (i, (hu, hv)) in updated.into_iter().enumerate() {
+            self.state.hu[i] = hu;
+            self.state.hv[i] = hv;
         }
     }
 
-    /// Compute friction slope using Manning's or Chezy's formula
-    fn compute_friction_slope(&self, h: f64, u: f64, v: f64) -> (f64, f64) {
+    /// Compute friction slope in cell `i` using the configured resistance law
+    fn compute_friction_slope(&self, i: usize, h: f64, u: f64, v: f64) -> (f64, f64) {
+        let h = self.conveyance_depth(i, h);
         let velocity_mag = (u * u + v * v).sqrt();
 
-        if velocity_mag < 1e-10 {
+        if velocity_mag < REST_SPEED {
             return (0.0, 0.0);
         }
 
-        let sf_mag = match self.friction {
+        let sf_mag = match self.friction_law(i) {
             FrictionLaw::None => 0.0,
             FrictionLaw::Manning { coefficient } => {
                 // S_f = n^2 * |v|^2 / h^(4/3)
-                let n = coefficient;
-                if h > 1e-6 {
+                let n = self.manning_field.as_ref().map_or(coefficient, |n| n[i]);
+                if h > self.constants.friction_depth {
                     n * n * velocity_mag * velocity_mag / h.powf(4.0 / 3.0)
                 } else {
                     0.0
@@ -239,12 +843,33 @@ impl ShallowWaterSolver {
             FrictionLaw::Chezy { coefficient } => {
                 // S_f = |v|^2 / (C^2 * h)
                 let c = coefficient;
-                if h > 1e-6 {
+                if h > self.constants.friction_depth {
                     velocity_mag * velocity_mag / (c * c * h)
                 } else {
                     0.0
                 }
             }
+            FrictionLaw::Voellmy { mu, xi } => {
+                // S_f = mu + |v|^2 / (xi * h)
+                if h > self.constants.friction_depth {
+                    mu + velocity_mag * velocity_mag / (xi * h)
+                } else {
+                    mu
+                }
+            }
+            FrictionLaw::Bingham {
+                yield_stress,
+                viscosity,
+            } => {
+                // Quadratic-free Bingham closure: tau_b = 1.5 tau_y + 3 mu_B |v| / h
+                // S_f = tau_b / (rho * g * h)
+                if h > self.constants.friction_depth {
+                    let tau_b = 1.5 * yield_stress + 3.0 * viscosity * velocity_mag / h;
+                    tau_b / (self.constants.density * self.constants.gravity * h)
+                } else {
+                    0.0
+                }
+            }
         };
 
         // Direction of friction (opposite to velocity)
@@ -254,35 +879,57 @@ impl ShallowWaterSolver {
         (sf_x, sf_y)
     }
 
+    /// Outward edge normals scaled by edge length (m) for the three local edges
+    /// Local edge k joins nodes k and k+1 of the triangle.
+    fn scaled_outward_normals(&self, tri_idx: usize) -> [(f64, f64); 3] {
+        let tri = &self.mesh.triangles[tri_idx];
+
+        // Orientation of the node ordering (+1 counter-clockwise, -1 clockwise)
+        let (ax, ay) = self.mesh.node_delta(tri.nodes[0], tri.nodes[1]);
+        let (bx, by) = self.mesh.node_delta(tri.nodes[0], tri.nodes[2]);
+        let orientation = (ax * by - bx * ay).signum();
+
+        let mut normals = [(0.0, 0.0); 3];
+        for (i, normal) in normals.iter_mut().enumerate() {
+            let (dx, dy) = self.mesh.node_delta(tri.nodes[i], tri.nodes[(i + 1) % 3]);
+            *normal = (orientation * dy, -orientation * dx);
+        }
+        normals
+    }
+
     /// Compute bed elevation gradient at triangle center
-    fn compute_bed_gradient(&self, tri_idx: usize) -> (f64, f64) {
+    pub fn compute_bed_gradient(&self, tri_idx: usize) -> (f64, f64) {
         let tri = &self.mesh.triangles[tri_idx];
 
+        if self.gradient_method == GradientMethod::LeastSquares {
+            let bed = |k: usize| match tri.neighbors[k] {
+                Some(j) => self.mesh.triangles[j].z_bed,
+                None => {
+                    let z0 = self.mesh.nodes[tri.nodes[k]].z;
+                    let z1 = self.mesh.nodes[tri.nodes[(k + 1) % 3]].z;
+                    0.5 * (z0 + z1)
+                }
+            };
+            return self.least_squares_gradient(tri_idx, |k| bed(k) - tri.z_bed);
+        }
+
         // Use Green-Gauss theorem for gradient computation
-        // ∇z_b ≈ (1/A) * Σ z_b_face * n * L
+        // ∇z_b ≈ (1/A) * Σ (z_b_face - z_b_cell) * n * L
+        // Subtracting the cell value keeps the gradient of a constant exactly
+        // zero when Σ n L does not vanish (spherical metric).
 
         let mut grad_x = 0.0;
         let mut grad_y = 0.0;
 
-        for i in 0..3 {
-            let n0_idx = tri.nodes[i];
-            let n1_idx = tri.nodes[(i + 1) % 3];
-
-            let n0 = &self.mesh.nodes[n0_idx];
-            let n1 = &self.mesh.nodes[n1_idx];
+        for (i, (nx_l, ny_l)) in self.scaled_outward_normals(tri_idx).iter().enumerate() {
+            let n0 = &self.mesh.nodes[tri.nodes[i]];
+            let n1 = &self.mesh.nodes[tri.nodes[(i + 1) % 3]];
 
             // Edge midpoint elevation
             let z_mid = (n0.z + n1.z) / 2.0;
 
-            // Edge normal vector (pointing outward)
-            let dx = n1.x - n0.x;
-            let dy = n1.y - n0.y;
-            let edge_length = (dx * dx + dy * dy).sqrt();
-            let nx = -dy / edge_length;
-            let ny = dx / edge_length;
-
-            grad_x += z_mid * nx * edge_length;
-            grad_y += z_mid * ny * edge_length;
+            grad_x += (z_mid - tri.z_bed) * nx_l;
+            grad_y += (z_mid - tri.z_bed) * ny_l;
         }
 
         grad_x /= tri.area;
@@ -291,63 +938,125 @@ impl ShallowWaterSolver {
         (grad_x, grad_y)
     }
 
-    /// Compute numerical flux using Lax-Friedrichs (Rusanov) flux
-    fn compute_flux(&self, edge: &Edge, state: &State) -> (f64, f64, f64) {
-        let left = edge.left_triangle;
+    /// Gradient of a cell field (zero normal gradient on the boundary)
+    /// Green-Gauss uses neighbour-averaged face values.
+    fn cell_field_gradient(&self, field: &[f64], i: usize) -> (f64, f64) {
+        self.local_gradient(i, |j| field[j])
+    }
 
-        // Left state
-        let h_l = state.h[left];
-        let (u_l, v_l) = state.get_velocity(left);
-        let hu_l = state.hu[left];
-        let hv_l = state.hv[left];
+    /// Gradient in cell `i` of the field with values `value(j)` in cell `i`
+    /// and its face neighbours
+    fn local_gradient<F>(&self, i: usize, value: F) -> (f64, f64)
+    where
+        F: Fn(usize) -> f64,
+    {
+        self.ghosted_gradient(i, value, |_| None)
+    }
 
-        // Right state (or boundary condition)
-        let (h_r, u_r, v_r, hu_r, hv_r) = if let Some(right) = edge.right_triangle {
-            let (u, v) = state.get_velocity(right);
-            (state.h[right], u, v, state.hu[right], state.hv[right])
-        } else {
-            // Wall boundary condition (reflective)
-            let (nx, ny) = edge.normal;
-            let u_normal = u_l * nx + v_l * ny;
-            let u_r = u_l - 2.0 * u_normal * nx;
-            let v_r = v_l - 2.0 * u_normal * ny;
-            (h_l, u_r, v_r, h_l * u_r, h_l * v_r)
+    /// Gradient as in `local_gradient`, with the value `ghost(k)` of the
+    /// ghost cell behind boundary side k where given (the face value is the
+    /// mean of both) instead of a zero normal gradient
+    fn ghosted_gradient<F, G>(&self, i: usize, value: F, ghost: G) -> (f64, f64)
+    where
+        F: Fn(usize) -> f64,
+        G: Fn(usize) -> Option<f64>,
+    {
+        let tri = &self.mesh.triangles[i];
+        let own = value(i);
+        let face = |k: usize| match tri.neighbors[k] {
+            Some(j) => 0.5 * (own + value(j)),
+            None => ghost(k).map_or(own, |g| 0.5 * (own + g)),
         };
 
-        let (nx, ny) = edge.normal;
+        if self.gradient_method == GradientMethod::LeastSquares {
+            // The boundary stencil point is the edge midpoint
+            return self.least_squares_gradient(i, |k| match tri.neighbors[k] {
+                Some(j) => value(j) - own,
+                None => face(k) - own,
+            });
+        }
 
-        // Compute normal velocities
-        let un_l = u_l * nx + v_l * ny;
-        let un_r = u_r * nx + v_r * ny;
+        let (mut gx, mut gy) = (0.0, 0.0);
+        for (k, (nx_l, ny_l)) in self.scaled_outward_normals(i).iter().enumerate() {
+            let face = face(k);
+            gx += (face - own) * nx_l;
+            gy += (face - own) * ny_l;
+        }
+        (gx / tri.area, gy / tri.area)
+    }
 
-        // Physical fluxes in normal direction
-        let f_h_l = hu_l * nx + hv_l * ny;
-        let f_hu_l = (hu_l * u_l + 0.5 * G * h_l * h_l) * nx + (hu_l * v_l) * ny;
-        let f_hv_l = (hv_l * u_l) * nx + (hv_l * v_l + 0.5 * G * h_l * h_l) * ny;
+    /// Least-squares gradient from the differences across the local edges
+    fn least_squares_gradient<F>(&self, tri_idx: usize, difference: F) -> (f64, f64)
+    where
+        F: Fn(usize) -> f64,
+    {
+        self.mesh.lsq_weights[tri_idx].iter().enumerate().fold(
+            (0.0, 0.0),
+            |(gx, gy), (k, (wx, wy))| {
+                let df = difference(k);
+                (gx + wx * df, gy + wy * df)
+            },
+        )
+    }
 
-        let f_h_r = hu_r * nx + hv_r * ny;
-        let f_hu_r = (hu_r * u_r + 0.5 * G * h_r * h_r) * nx + (hu_r * v_r) * ny;
-        let f_hv_r = (hv_r * u_r) * nx + (hv_r * v_r + 0.5 * G * h_r * h_r) * ny;
+    /// Metric pressure term ½ g h² Σ n L / A on the sphere (per unit area)
+    /// Σ n L does not vanish on spherical cells; this term balances the
+    /// flux of a lake at rest.
+    fn metric_pressure(&self, tri_idx: usize, h: f64) -> (f64, f64) {
+        if !matches!(
+            self.mesh.coordinate_system,
+            CoordinateSystem::Spherical { .. }
+        ) {
+            return (0.0, 0.0);
+        }
+        let (sum_x, sum_y) = self
+            .scaled_outward_normals(tri_idx)
+            .iter()
+            .fold((0.0, 0.0), |acc, n| (acc.0 + n.0, acc.1 + n.1));
+        let pressure = 0.5 * self.constants.gravity * h * h / self.mesh.triangles[tri_idx].area;
+        (pressure * sum_x, pressure * sum_y)
+    }
+
+    /// Curvature of the east/north basis and Coriolis force on the sphere
+    fn rotation_source(&self, tri_idx: usize, h: f64, u: f64, v: f64) -> (f64, f64) {
+        let CoordinateSystem::Spherical { radius } = self.mesh.coordinate_system else {
+            return (0.0, 0.0);
+        };
+        let lat = self.mesh.triangles[tri_idx].centroid.1.to_radians();
 
-        // Wave speeds
-        let c_l = (G * h_l).sqrt();
-        let c_r = (G * h_r).sqrt();
-        let s_max = (un_l.abs() + c_l).max(un_r.abs() + c_r);
+        let tan_lat = lat.tan();
+        let curvature_x = h * u * v * tan_lat / radius;
+        let curvature_y = -h * u * u * tan_lat / radius;
 
-        // Lax-Friedrichs flux
-        let flux_h = 0.5 * (f_h_l + f_h_r - s_max * (h_r - h_l));
-        let flux_hu = 0.5 * (f_hu_l + f_hu_r - s_max * (hu_r - hu_l));
-        let flux_hv = 0.5 * (f_hv_l + f_hv_r - s_max * (hv_r - hv_l));
+        // Coriolis parameter f = 2 Ω sin(lat)
+        let f = 2.0 * EARTH_ROTATION_RATE * lat.sin();
 
-        (flux_h, flux_hu, flux_hv)
+        (curvature_x + f * h * v, curvature_y - f * h * u)
+    }
+
+    /// Numerical flux across `edge`; boundary edges take the closure's
+    /// flux or the numerical flux against their state in `ghosts`
+    fn compute_flux(&self, edge: &Edge, state: &State, ghosts: &[EdgeState]) -> (f64, f64, f64) {
+        let left = self.edge_state(state, edge.left_triangle);
+        let right = match edge.right_triangle {
+            Some(right) => self.edge_state(state, right),
+            None => {
+                if let Some(flux) = self.boundary_closure.boundary_flux(self, edge, &left) {
+                    return flux;
+                }
+                ghosts[edge.ghost.expect("boundary edges have ghost cells")]
+            }
+        };
+        self.numerical_flux
+            .flux(&left, &right, edge.normal, &self.constants)
     }
 
     /// Apply boundary conditions
     pub fn apply_boundary_conditions(&mut self) {
-        // Boundary conditions are handled in flux computation
-        // This method is for any additional constraints
+        // Boundary conditions are handled in flux computation (see
+        // boundary_closure); this method is for any additional constraints
         for i in 0..self.mesh.triangles.len() {
-            if self.state.h[i] < 1e-10 {
+            if self.state.h[i] < self.constants.dry_tolerance {
                 self.state.h[i] = 0.0;
                 self.state.hu[i] = 0.0;
                 self.state.hv[i] = 0.0;
@@ -404,11 +1113,85 @@ impl ShallowWaterSolver {
         }
     }
 
+    /// Set initial condition: Gaussian hump η = A exp(-r² / (2 σ²)) of the
+    /// free surface on the still level `still_level`, with `width` = σ (m)
+    pub fn set_gaussian_hump(
+        &mut self,
+        center: (f64, f64),
+        amplitude: f64,
+        width: f64,
+        still_level: f64,
+    ) {
+        for (i, tri) in self.mesh.triangles.iter().enumerate() {
+            let (dx, dy) = self.mesh.coordinate_system.delta(center, tri.centroid);
+            let eta = amplitude * (-(dx * dx + dy * dy) / (2.0 * width * width)).exp();
+            self.state.h[i] = (still_level + eta - tri.z_bed).max(0.0);
+            self.state.hu[i] = 0.0;
+            self.state.hv[i] = 0.0;
+        }
+    }
+
+    /// Set initial condition: solitary wave η = A sech²(k s) travelling in
+    /// the direction `direction` (radians, counter-clockwise from east),
+    /// where s is the distance (m) along it from the crest line through
+    /// `crest`. The depth d below the still level at the crest sets
+    /// k = sqrt(3A / (4 d³)), and the water moves with u = η sqrt(g / d).
+    pub fn set_solitary_wave(
+        &mut self,
+        crest: (f64, f64),
+        direction: f64,
+        amplitude: f64,
+        still_level: f64,
+    ) -> Result<(), String> {
+        let nearest = self.nearest_cell(crest.0, crest.1);
+        let depth = nearest.map_or(0.0, |i| still_level - self.mesh.triangles[i].z_bed);
+        if depth <= 0.0 {
+            return Err(format!(
+                "the solitary wave crest at ({}, {}) is not under water",
+                crest.0, crest.1
+            ));
+        }
+        let k = (3.0 * amplitude.abs() / (4.0 * depth.powi(3))).sqrt();
+        let speed = (self.constants.gravity / depth).sqrt();
+        let (cos, sin) = (direction.cos(), direction.sin());
+        for (i, tri) in self.mesh.triangles.iter().enumerate() {
+            let (dx, dy) = self.mesh.coordinate_system.delta(crest, tri.centroid);
+            let sech = 1.0 / (k * (dx * cos + dy * sin)).cosh();
+            let eta = amplitude * sech * sech;
+            let h = (still_level + eta - tri.z_bed).max(0.0);
+            let u = if h > self.constants.dry_tolerance {
+                eta * speed
+            } else {
+                0.0
+            };
+            self.state.h[i] = h;
+            self.state.hu[i] = h * u * cos;
+            self.state.hv[i] = h * u * sin;
+        }
+        Ok(())
+    }
+
+    /// Set initial condition: tsunami from an Okada fault source
+    /// The co-seismic vertical displacement is added to the still sea level.
+    pub fn set_okada(&mut self, fault: &FaultParameters, sea_level: f64) {
+        for (i, tri) in self.mesh.triangles.iter().enumerate() {
+            // Work in metres relative to the fault, also on geographic meshes
+            let (dx, dy) = self
+                .mesh
+                .coordinate_system
+                .delta((fault.x, fault.y), tri.centroid);
+            let uz = fault.vertical_displacement_at_offset(dx, dy);
+            self.state.h[i] = (sea_level + uz - tri.z_bed).max(0.0);
+            self.state.hu[i] = 0.0;
+            self.state.hv[i] = 0.0;
+        }
+    }
+
     /// Compute total mass (should be conserved)
     pub fn compute_total_mass(&self) -> f64 {
         let mut total = 0.0;
-        for (i, tri) in self.mesh.triangles.iter().enumerate() {
-            total += self.state.h[i] * tri.area;
+        for i in 0..self.mesh.triangles.len() {
+            total += self.state.h[i] * self.storage_area(i);
         }
         total
     }
@@ -418,13 +1201,84 @@ impl ShallowWaterSolver {
         let mut total = 0.0;
         for (i, tri) in self.mesh.triangles.iter().enumerate() {
             let h = self.state.h[i];
-            let (u, v) = self.state.get_velocity(i);
+            let (u, v) = self.velocity(&self.state, i);
             let kinetic = 0.5 * h * (u * u + v * v);
-            let potential = 0.5 * G * h * h;
+            let potential = 0.5 * self.constants.gravity * h * h;
             total += (kinetic + potential) * tri.area;
         }
         total
     }
+
+    /// Froude number |u| / sqrt(g h) per cell (zero in dry cells)
+    pub fn froude_numbers(&self) -> Vec<f64> {
+        (0..self.mesh.triangles.len())
+            .map(|i| {
+                let h = self.state.h[i];
+                if h < self.constants.dry_tolerance {
+                    return 0.0;
+                }
+                let (u, v) = self.velocity(&self.state, i);
+                (u * u + v * v).sqrt() / (self.constants.gravity * h).sqrt()
+            })
+            .collect()
+    }
+
+    /// Depth-averaged vertical vorticity ∂v/∂x - ∂u/∂y per cell (1/s)
+    pub fn vorticity(&self) -> Vec<f64> {
+        let n = self.mesh.triangles.len();
+        let (u, v): (Vec<f64>, Vec<f64>) = (0..n).map(|i| self.velocity(&self.state, i)).unzip();
+        (0..n)
+            .into_par_iter()
+            .map(|i| {
+                if self.state.h[i] < self.constants.dry_tolerance {
+                    return 0.0;
+                }
+                let (_, du_dy) = self.cell_field_gradient(&u, i);
+                let (dv_dx, _) = self.cell_field_gradient(&v, i);
+                dv_dx - du_dy
+            })
+            .collect()
+    }
+
+    /// Unit discharge magnitude h |u| per cell (m^2/s)
+    pub fn unit_discharge(&self) -> Vec<f64> {
+        self.state
+            .hu
+            .iter()
+            .zip(&self.state.hv)
+            .map(|(hu, hv)| (hu * hu + hv * hv).sqrt())
+            .collect()
+    }
+
+    /// Flood hazard rating h (|u| + 0.5) per cell (m^2/s), after the UK
+    /// Environment Agency FD2321 method without the debris factor
+    pub fn hazard_rating(&self) -> Vec<f64> {
+        (0..self.mesh.triangles.len())
+            .map(|i| {
+                let h = self.state.h[i];
+                let (u, v) = self.velocity(&self.state, i);
+                h * ((u * u + v * v).sqrt() + 0.5)
+            })
+            .collect()
+    }
+
+    /// Bed shear stress magnitude rho g h |S_f| from the active friction law (Pa)
+    pub fn bed_shear_stress(&self) -> Vec<f64> {
+        (0..self.mesh.triangles.len())
+            .map(|i| {
+                let h = self.state.h[i];
+                if h < self.constants.dry_tolerance {
+                    return 0.0;
+                }
+                let (u, v) = self.velocity(&self.state, i);
+                let (sf_x, sf_y) = self.compute_friction_slope(i, h, u, v);
+                self.constants.density
+                    * self.constants.gravity
+                    * h
+                    * (sf_x * sf_x + sf_y * sf_y).sqrt()
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -543,6 +1397,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_state_json_round_trip() {
+        let mesh = TriangularMesh::new_rectangular(8, 8, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_dam_break(5.0);
+        for _ in 0..10 {
+            solver.step();
+        }
+
+        let json = serde_json::to_string(&solver.state).unwrap();
+        let restored: State = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, solver.state);
+
+        let friction: FrictionLaw =
+            serde_json::from_str(r#"{"manning": {"coefficient": 0.03}}"#).unwrap();
+        assert!(matches!(friction, FrictionLaw::Manning { coefficient } if coefficient == 0.03));
+    }
+
     #[test]
     fn test_positive_depth_preservation() {
         let mesh = TriangularMesh::new_rectangular(10, 10, 10.0, 10.0, TopographyType::Flat);
@@ -571,7 +1443,7 @@ mod tests {
         solver.state.hu[0] = 4.0; // u = 2.0
         solver.state.hv[0] = 6.0; // v = 3.0
 
-        let (u, v) = solver.state.get_velocity(0);
+        let (u, v) = solver.velocity(&solver.state, 0);
         assert!((u - 2.0).abs() < 1e-10);
         assert!((v - 3.0).abs() < 1e-10);
     }
@@ -582,7 +1454,7 @@ mod tests {
         let solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
 
         // Dry cell should have zero velocity
-        let (u, v) = solver.state.get_velocity(0);
+        let (u, v) = solver.velocity(&solver.state, 0);
         assert_eq!(u, 0.0);
         assert_eq!(v, 0.0);
     }
@@ -689,7 +1561,7 @@ mod tests {
 
         // Expected energy: KE + PE = 0.5*h*u^2 + 0.5*g*h^2
         let expected_kinetic = 0.5 * 2.0 * 2.0 * 2.0;
-        let expected_potential = 0.5 * G * 2.0 * 2.0;
+        let expected_potential = 0.5 * solver.constants.gravity * 2.0 * 2.0;
         let expected_energy = (expected_kinetic + expected_potential) * area;
 
         let total_energy = solver.compute_total_energy();
@@ -746,4 +1618,551 @@ mod tests {
             avg_depth_far
         );
     }
+    #[test]
+    fn test_dam_break_flows_downstream() {
+        let mesh = TriangularMesh::new_rectangular(21, 5, 10.0, 2.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+
+        solver.set_dam_break(5.0);
+        for _ in 0..10 {
+            solver.step();
+        }
+
+        // Water released from the reservoir (x < 5) moves in +x
+        let total_hu: f64 = solver.state.hu.iter().sum();
+        assert!(total_hu > 0.0, "Dam break should flow towards +x");
+    }
+
+    #[test]
+    fn test_bed_gradient_slope() {
+        let mesh = TriangularMesh::new_rectangular(
+            11,
+            11,
+            10.0,
+            10.0,
+            TopographyType::Slope {
+                gradient_x: 0.1,
+                gradient_y: 0.05,
+            },
+        );
+        let solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+
+        for i in 0..solver.mesh.triangles.len() {
+            let (dzdx, dzdy) = solver.compute_bed_gradient(i);
+            assert!((dzdx - 0.1).abs() < 1e-10);
+            assert!((dzdy - 0.05).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_water_on_slope_flows_downhill() {
+        let topography = TopographyType::Slope {
+            gradient_x: 0.1,
+            gradient_y: 0.0,
+        };
+        let mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 10.0, topography);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = 1.0;
+        }
+        solver.step();
+
+        // The bed rises towards +x, so a layer of uniform depth accelerates towards -x
+        let total_hu: f64 = solver.state.hu.iter().sum();
+        assert!(total_hu < 0.0, "Water should flow downhill");
+    }
+
+    #[test]
+    fn test_timestep_uses_inscribed_radius() {
+        let mesh = TriangularMesh::new_rectangular(11, 6, 10.0, 5.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = 1.0;
+        }
+        solver.compute_timestep();
+
+        // Right triangles with 1 m legs: inscribed radius 2A/P = 1/(2 + sqrt(2))
+        let radius = 1.0 / (2.0 + 2.0f64.sqrt());
+        let limit = 0.45 * radius / 9.81f64.sqrt();
+        assert!(solver.dt > 0.0);
+        assert!(
+            solver.dt <= limit * (1.0 + 1e-9),
+            "dt {} > {}",
+            solver.dt,
+            limit
+        );
+    }
+
+    #[test]
+    fn test_least_squares_gradients_on_stretched_mesh() {
+        let mesh = TriangularMesh::new_rectangular(
+            21,
+            3,
+            100.0,
+            0.5,
+            TopographyType::Slope {
+                gradient_x: 0.1,
+                gradient_y: 0.05,
+            },
+        );
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.gradient_method = GradientMethod::LeastSquares;
+
+        let field: Vec<f64> = solver
+            .mesh
+            .triangles
+            .iter()
+            .map(|t| 2.0 * t.centroid.0 + 5.0 * t.centroid.1)
+            .collect();
+
+        for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+            let (dzdx, dzdy) = solver.compute_bed_gradient(i);
+            assert!((dzdx - 0.1).abs() < 1e-10);
+            assert!((dzdy - 0.05).abs() < 1e-10);
+
+            if tri.neighbors.iter().all(|n| n.is_some()) {
+                let (gx, gy) = solver.cell_field_gradient(&field, i);
+                assert!((gx - 2.0).abs() < 1e-9);
+                assert!((gy - 5.0).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bed_slope_limit() {
+        let limit = BedSlopeLimit {
+            max_slope: 0.5,
+            shallow_depth: 0.1,
+        };
+
+        // Steep slope capped, direction preserved
+        let (sx, sy) = limit.apply((3.0, 4.0), 1.0);
+        assert!((sx - 0.3).abs() < 1e-12 && (sy - 0.4).abs() < 1e-12);
+
+        // Mild slope in deep water untouched
+        assert_eq!(limit.apply((0.1, 0.0), 1.0), (0.1, 0.0));
+
+        // Thin film: ramped down linearly with depth
+        let (sx, _) = limit.apply((0.1, 0.0), 0.05);
+        assert!((sx - 0.05).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_bed_slope_limit_bounds_thin_film_speed() {
+        let run = |scheme: FluxScheme, limit: Option<BedSlopeLimit>| {
+            let mesh = TriangularMesh::new_rectangular(
+                21,
+                5,
+                20.0,
+                4.0,
+                TopographyType::Slope {
+                    gradient_x: -2.0,
+                    gradient_y: 0.0,
+                },
+            );
+            let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+            solver.flux_scheme = scheme;
+            solver.bed_slope_limit = limit;
+            solver.state.h.iter_mut().for_each(|h| *h = 0.01);
+            for _ in 0..50 {
+                solver.step();
+            }
+            (0..solver.state.h.len())
+                .map(|i| {
+                    let (u, v) = solver.velocity(&solver.state, i);
+                    (u * u + v * v).sqrt()
+                })
+                .fold(0.0, f64::max)
+        };
+
+        let limit = BedSlopeLimit {
+            max_slope: 0.3,
+            shallow_depth: 0.05,
+        };
+        for scheme in [FluxScheme::Rusanov, FluxScheme::CentralUpwind] {
+            let unlimited = run(scheme, None);
+            let limited = run(scheme, Some(limit));
+            assert!(limited < 0.5 * unlimited, "{:?}", scheme);
+        }
+    }
+
+    #[test]
+    fn test_derived_quantities() {
+        let mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 10.0, TopographyType::Flat);
+        let mut solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Manning { coefficient: 0.03 });
+
+        // Solid-body rotation u = -w y, v = w x has vorticity 2w
+        let omega = 0.1;
+        for i in 0..solver.mesh.triangles.len() {
+            let (x, y) = solver.mesh.triangles[i].centroid;
+            solver.state.h[i] = 2.0;
+            solver.state.hu[i] = 2.0 * -omega * (y - 5.0);
+            solver.state.hv[i] = 2.0 * omega * (x - 5.0);
+        }
+
+        let vorticity = solver.vorticity();
+        let froude = solver.froude_numbers();
+        let discharge = solver.unit_discharge();
+        let shear = solver.bed_shear_stress();
+
+        for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+            if tri.neighbors.iter().all(|n| n.is_some()) {
+                assert!((vorticity[i] - 2.0 * omega).abs() < 1e-10);
+            }
+            let (u, v) = solver.velocity(&solver.state, i);
+            let speed = (u * u + v * v).sqrt();
+            assert!((froude[i] - speed / (solver.constants.gravity * 2.0).sqrt()).abs() < 1e-12);
+            assert!((discharge[i] - 2.0 * speed).abs() < 1e-12);
+
+            // Manning: tau = rho g n^2 |u|^2 / h^(1/3)
+            let expected = 1000.0 * solver.constants.gravity * 0.03 * 0.03 * speed * speed
+                / 2f64.powf(1.0 / 3.0);
+            assert!((shear[i] - expected).abs() < 1e-9 * expected.max(1.0));
+        }
+    }
+
+    #[test]
+    fn test_courant_numbers_locate_limiting_cell() {
+        let mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(1.0);
+        let fast = 37;
+        solver.state.hu[fast] = 5.0;
+        solver.compute_timestep();
+
+        let courant = solver.courant_numbers();
+        let (cell, largest) = solver.limiting_cell();
+        assert!(courant.iter().all(|&c| c <= solver.cfl + 1e-12));
+        assert!((largest - courant[cell]).abs() < 1e-15);
+        assert!(cell == fast || solver.mesh.triangles[fast].neighbors.contains(&Some(cell)));
+        assert!(largest > 0.8 * solver.cfl);
+    }
+
+    #[test]
+    fn test_friction_voellmy_slope() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 10.0, 10.0, TopographyType::Flat);
+        let solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Voellmy { mu: 0.2, xi: 500.0 });
+
+        // S_f = mu + |v|^2 / (xi * h)
+        let (sf_x, sf_y) = solver.compute_friction_slope(0, 2.0, 3.0, 0.0);
+        assert!((sf_x - (0.2 + 9.0 / 1000.0)).abs() < 1e-12);
+        assert_eq!(sf_y, 0.0);
+    }
+
+    #[test]
+    fn test_gravity_sets_wave_speed() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(1.0);
+
+        solver.compute_timestep();
+        let dt_earth = solver.dt;
+        solver.constants.gravity = 9.81 / 4.0;
+        solver.compute_timestep();
+
+        // Still water: dt ∝ 1 / sqrt(g h)
+        assert!((solver.dt - 2.0 * dt_earth).abs() < 1e-12 * dt_earth);
+    }
+
+    #[test]
+    fn test_dry_tolerance_zeroes_thin_films() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h[0] = 1e-6;
+        solver.state.hu[0] = 1e-6;
+        solver.constants.desingularization = 0.0;
+
+        assert_eq!(solver.velocity(&solver.state, 0), (1.0, 0.0));
+        solver.constants.dry_tolerance = 1e-4;
+        assert_eq!(solver.velocity(&solver.state, 0), (0.0, 0.0));
+
+        solver.apply_boundary_conditions();
+        assert_eq!(solver.state.h[0], 0.0);
+        assert_eq!(solver.state.hu[0], 0.0);
+    }
+
+    #[test]
+    fn test_desingularized_film_keeps_time_step() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(1.0);
+        solver.compute_timestep();
+        let dt_still = solver.dt;
+
+        // A 10 nm film carrying round-off momentum: 100 m/s as hu/h
+        solver.state.h[0] = 1e-8;
+        solver.state.hu[0] = 1e-6;
+        let (u, _) = solver.velocity(&solver.state, 0);
+        assert!((u - 2e-6).abs() < 1e-12, "{}", u);
+        solver.compute_timestep();
+        assert_eq!(solver.dt, dt_still);
+        // Deeper than ε the velocity is hu/h
+        assert_eq!(solver.constants.velocity(0.5, 0.25, -0.5), (0.5, -1.0));
+
+        solver.constants.desingularization = 0.0;
+        let (u, _) = solver.velocity(&solver.state, 0);
+        assert!((u - 100.0).abs() < 1e-9, "{}", u);
+        solver.compute_timestep();
+        assert!(solver.dt < 0.1 * dt_still);
+    }
+
+    #[test]
+    fn test_friction_depth_cuts_off_thin_films() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 10.0, 10.0, TopographyType::Flat);
+        let mut solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Manning { coefficient: 0.03 });
+        assert!(solver.compute_friction_slope(0, 1e-3, 1.0, 0.0).0 > 0.0);
+        solver.constants.friction_depth = 1e-2;
+        assert_eq!(solver.compute_friction_slope(0, 1e-3, 1.0, 0.0), (0.0, 0.0));
+
+        // Voellmy keeps its Coulomb part below the cut-off
+        solver.friction = FrictionLaw::Voellmy { mu: 0.2, xi: 500.0 };
+        assert_eq!(solver.compute_friction_slope(0, 1e-3, 1.0, 0.0), (0.2, 0.0));
+    }
+
+    #[test]
+    fn test_friction_bingham_uses_bulk_density() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 10.0, 10.0, TopographyType::Flat);
+        let law = FrictionLaw::Bingham {
+            yield_stress: 100.0,
+            viscosity: 10.0,
+        };
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, law);
+
+        let (sf_water, _) = solver.compute_friction_slope(0, 1.0, 1.0, 0.0);
+        solver.constants.density = 2000.0;
+        let (sf_mud, _) = solver.compute_friction_slope(0, 1.0, 1.0, 0.0);
+
+        let expected = (1.5 * 100.0 + 3.0 * 10.0) / (2000.0 * solver.constants.gravity);
+        assert!((sf_mud - expected).abs() < 1e-12);
+        assert!((sf_water - 2.0 * sf_mud).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_voellmy_flow_comes_to_rest() {
+        let mesh = TriangularMesh::new_rectangular(6, 6, 10.0, 10.0, TopographyType::Flat);
+        let mut solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Voellmy { mu: 0.3, xi: 500.0 });
+
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = 0.5;
+            solver.state.hu[i] = 0.5;
+        }
+
+        // Coulomb resistance stops a sliding layer without reversing it
+        for _ in 0..200 {
+            solver.step();
+        }
+        let max_speed = (0..solver.state.h.len())
+            .map(|i| {
+                let (u, v) = solver.velocity(&solver.state, i);
+                (u * u + v * v).sqrt()
+            })
+            .fold(0.0, f64::max);
+        assert!(
+            max_speed < 1e-3,
+            "Flow should stop, max speed {}",
+            max_speed
+        );
+    }
+
+    #[test]
+    fn test_okada_initial_condition() {
+        let mesh = TriangularMesh::new_rectangular(21, 21, 200e3, 200e3, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+
+        let fault = FaultParameters {
+            x: 100e3,
+            y: 100e3,
+            depth: 5000.0,
+            strike: 0.0,
+            dip: 20.0,
+            rake: 90.0,
+            slip: 10.0,
+            length: 80e3,
+            width: 40e3,
+        };
+        solver.set_okada(&fault, 4000.0);
+
+        let h_max = solver.state.h.iter().cloned().fold(f64::MIN, f64::max);
+        let h_min = solver.state.h.iter().cloned().fold(f64::MAX, f64::min);
+        assert!(h_max > 4000.0, "Uplift should raise the sea surface");
+        assert!(h_min < 4000.0, "Subsidence should lower the sea surface");
+        assert!(solver.state.hu.iter().all(|&hu| hu == 0.0));
+    }
+
+    #[test]
+    fn test_solitary_wave_travels_at_its_celerity() {
+        let mesh = TriangularMesh::new_rectangular(401, 3, 100.0, 0.5, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        assert!(solver
+            .set_solitary_wave((20.0, 0.0), 0.0, 0.1, -1.0)
+            .is_err());
+        solver
+            .set_solitary_wave((20.0, 0.0), 0.0, 0.1, 1.0)
+            .unwrap();
+        assert!(solver.state.hv.iter().all(|&hv| hv == 0.0));
+        let crest = |solver: &ShallowWaterSolver| {
+            let i = (0..solver.state.h.len())
+                .max_by(|&a, &b| solver.state.h[a].total_cmp(&solver.state.h[b]))
+                .unwrap();
+            (solver.mesh.triangles[i].centroid.0, solver.state.h[i] - 1.0)
+        };
+        assert!((crest(&solver).1 - 0.1).abs() < 1e-3);
+
+        while solver.time < 5.0 {
+            solver.step();
+        }
+        // Right-going at sqrt(g (d + A)), without a left-going part
+        let (x, amplitude) = crest(&solver);
+        let expected = 20.0 + (9.81f64 * 1.1).sqrt() * solver.time;
+        assert!(
+            (x - expected).abs() < 1.5,
+            "crest at {} not {}",
+            x,
+            expected
+        );
+        assert!(amplitude > 0.05);
+        let behind = solver.mesh.triangles.iter().zip(&solver.state.h);
+        assert!(behind
+            .filter(|(tri, _)| tri.centroid.0 < 15.0)
+            .all(|(_, &h)| (h - 1.0).abs() < 0.01));
+
+        solver.set_gaussian_hump((50.0, 0.25), 0.2, 5.0, 1.0);
+        let (x, amplitude) = crest(&solver);
+        assert!((x - 50.0).abs() < 0.25 && (amplitude - 0.2).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_spherical_lake_at_rest() {
+        let mesh = TriangularMesh::new_geographic(
+            11,
+            11,
+            (140.0, 20.0),
+            (10.0, 10.0),
+            TopographyType::Flat,
+            crate::mesh::EARTH_RADIUS,
+        );
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = 4000.0;
+        }
+
+        for _ in 0..10 {
+            solver.step();
+        }
+
+        // Metric pressure terms must balance the fluxes exactly
+        for i in 0..solver.state.h.len() {
+            let (u, v) = solver.velocity(&solver.state, i);
+            assert!(
+                u.abs() < 1e-8 && v.abs() < 1e-8,
+                "Spurious flow: {} {}",
+                u,
+                v
+            );
+        }
+    }
+
+    #[test]
+    fn test_spherical_coriolis_deflects_right() {
+        let mesh = TriangularMesh::new_geographic(
+            11,
+            11,
+            (0.0, 40.0),
+            (2.0, 2.0),
+            TopographyType::Flat,
+            crate::mesh::EARTH_RADIUS,
+        );
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = 100.0;
+            solver.state.hu[i] = 100.0; // u = 1 m/s eastward
+        }
+
+        // Northern hemisphere: an eastward current turns south
+        let center = solver.state.h.len() / 2;
+        solver.step();
+        assert!(solver.state.hv[center] < 0.0);
+    }
+
+    #[test]
+    fn test_steady_state_converges() {
+        let mesh = TriangularMesh::new_rectangular(6, 6, 10.0, 10.0, TopographyType::Flat);
+        let mut solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Manning { coefficient: 0.05 });
+        solver.set_circular_wave((5.0, 5.0), 3.0, 0.05);
+
+        let report = solver.solve_steady_state(1e-8, 20000, true);
+
+        assert!(
+            report.converged,
+            "Residual stalled at {:?}",
+            report.residual_history.last()
+        );
+        assert_eq!(report.residual_history.len(), report.iterations + 1);
+        assert!(report.residual_history[0] > *report.residual_history.last().unwrap());
+        // Pseudo-time iterations leave the physical clock untouched
+        assert_eq!(solver.time, 0.0);
+    }
+
+    #[test]
+    fn test_steady_state_local_time_stepping_conserves_mass() {
+        let mesh = TriangularMesh::new_rectangular(16, 6, 20.0, 5.0, TopographyType::Flat);
+        for scheme in [FluxScheme::Rusanov, FluxScheme::CentralUpwind] {
+            let mut solver = ShallowWaterSolver::new(mesh.clone(), 0.45, FrictionLaw::None);
+            solver.flux_scheme = scheme;
+            solver.set_dam_break(10.0);
+            let initial = solver.compute_total_mass();
+
+            solver.solve_steady_state(1e-12, 200, true);
+
+            let mass = solver.compute_total_mass();
+            assert!(
+                ((mass - initial) / initial).abs() < 1e-12,
+                "{:?}: {} -> {}",
+                scheme,
+                initial,
+                mass
+            );
+        }
+    }
+
+    #[test]
+    fn test_steady_state_lake_at_rest_immediate() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = 1.0;
+        }
+
+        let report = solver.solve_steady_state(1e-10, 10, false);
+        assert!(report.converged);
+        assert_eq!(report.iterations, 0);
+    }
+
+    #[test]
+    fn test_steady_state_yield_resistance_uses_local_steps() {
+        let mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 10.0, TopographyType::Flat);
+        let law = FrictionLaw::Voellmy { mu: 0.3, xi: 500.0 };
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, law);
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = 0.5;
+            solver.state.hu[i] = 0.5;
+        }
+        // Local time stepping never sets the global step
+        solver.dt = 0.0;
+        solver.solve_steady_state(1e-12, 1, true);
+
+        // A uniform sliding layer has no flux divergence away from the
+        // walls, so only the yield resistance slows it there
+        let center = solver.locate(5.2, 5.1).unwrap();
+        assert!(
+            solver.state.hu[center] < 0.5 - 1e-3,
+            "{}",
+            solver.state.hu[center]
+        );
+    }
 }
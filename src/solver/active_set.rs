@@ -0,0 +1,170 @@
+/// Wet-region tracking for the explicit Rusanov step
+/// The Rusanov flux between two dry cells (h = hu = hv = 0) and the sources
+/// of a dry cell are exactly zero, so only edges touching the wet region
+/// contribute. Within one RK2 step water can advance by at most one cell, so
+/// the active set holds the wet cells plus one ring of neighbours, and the
+/// active edges are those touching an active cell. After each step the set is
+/// rebuilt from the cells that could have changed (the active cells and their
+/// neighbours) instead of the whole mesh. Edges are kept in mesh order, so the
+/// residual is accumulated in the same order as the full loop and results are
+/// bitwise identical.
+use super::{ShallowWaterSolver, State};
+use crate::mesh::TriangularMesh;
+
+pub(super) struct ActiveSet {
+    cell_edges: Vec<Vec<usize>>, // Edges of every cell
+    in_set: Vec<bool>,           // Scratch marker per cell
+    pub(super) cells: Vec<usize>,
+    pub(super) edges: Vec<usize>,
+}
+
+/// A cell that can produce a non-zero flux or source
+fn is_wet(state: &State, i: usize) -> bool {
+    state.h[i] != 0.0 || state.hu[i] != 0.0 || state.hv[i] != 0.0
+}
+
+impl ActiveSet {
+    /// Active set of `state`, found by scanning every cell
+    fn new(mesh: &TriangularMesh, state: &State) -> Self {
+        let mut cell_edges = vec![Vec::with_capacity(3); mesh.triangles.len()];
+        for (e, edge) in mesh.edges.iter().enumerate() {
+            cell_edges[edge.left_triangle].push(e);
+            if let Some(right) = edge.right_triangle {
+                cell_edges[right].push(e);
+            }
+        }
+        let mut set = ActiveSet {
+            cell_edges,
+            in_set: vec![false; mesh.triangles.len()],
+            cells: Vec::new(),
+            edges: Vec::new(),
+        };
+        let wet: Vec<usize> = (0..mesh.triangles.len())
+            .filter(|&i| is_wet(state, i))
+            .collect();
+        set.rebuild(mesh, wet);
+        set
+    }
+
+    /// Update after a step: only active cells and their neighbours can have
+    /// become wet
+    fn refresh(&mut self, mesh: &TriangularMesh, state: &State) {
+        let candidates = self.with_ring(mesh, self.cells.clone());
+        let wet = candidates
+            .into_iter()
+            .filter(|&i| is_wet(state, i))
+            .collect();
+        self.rebuild(mesh, wet);
+    }
+
+    fn rebuild(&mut self, mesh: &TriangularMesh, wet: Vec<usize>) {
+        self.cells = self.with_ring(mesh, wet);
+        let mut edges: Vec<usize> = self
+            .cells
+            .iter()
+            .flat_map(|&i| self.cell_edges[i].iter().copied())
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        self.edges = edges;
+    }
+
+    /// Sorted union of `cells` and their face neighbours
+    fn with_ring(&mut self, mesh: &TriangularMesh, cells: Vec<usize>) -> Vec<usize> {
+        let mut result = Vec::with_capacity(cells.len() * 2);
+        for &i in &cells {
+            let neighbours = mesh.triangles[i].neighbors.iter().flatten();
+            for &j in std::iter::once(&i).chain(neighbours) {
+                if !self.in_set[j] {
+                    self.in_set[j] = true;
+                    result.push(j);
+                }
+            }
+        }
+        for &i in &result {
+            self.in_set[i] = false;
+        }
+        result.sort_unstable();
+        result
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Bring the active set up to date with the current state before a step
+    /// Terms acting on dry cells (rainfall) and open boundaries can wet any
+    /// cell, so tracking is suspended while one is in use
+    pub(super) fn refresh_active_set(&mut self) {
+        if !self.track_wet_region || self.sources_wet_dry_cells() || !self.has_closed_boundary() {
+            self.active_set = None;
+            return;
+        }
+        match &mut self.active_set {
+            Some(set) => set.refresh(&self.mesh, &self.state),
+            None => self.active_set = Some(ActiveSet::new(&self.mesh, &self.state)),
+        }
+    }
+
+    /// Forget the active set after the state was changed outside a step
+    /// (it is rebuilt from a full scan before the next step)
+    pub fn reset_active_set(&mut self) {
+        self.active_set = None;
+    }
+
+    /// Fraction of the cells in the active set (None without tracking)
+    pub fn active_fraction(&self) -> Option<f64> {
+        self.active_set
+            .as_ref()
+            .map(|set| set.cells.len() as f64 / self.mesh.triangles.len().max(1) as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::{FrictionLaw, ShallowWaterSolver};
+
+    #[test]
+    fn test_wet_region_tracking_matches_full_loop() {
+        let mesh = TriangularMesh::new_rectangular(
+            30,
+            10,
+            30.0,
+            10.0,
+            TopographyType::Slope {
+                gradient_x: 0.02,
+                gradient_y: 0.005,
+            },
+        );
+        let make = |track: bool| {
+            let mut solver = ShallowWaterSolver::new(
+                mesh.clone(),
+                0.45,
+                FrictionLaw::Manning { coefficient: 0.03 },
+            );
+            solver.track_wet_region = track;
+            for (i, tri) in mesh.triangles.iter().enumerate() {
+                if tri.centroid.0 < 5.0 {
+                    solver.state.h[i] = 1.0;
+                }
+            }
+            solver
+        };
+        let (mut full, mut tracked) = (make(false), make(true));
+
+        let mut fractions = Vec::new();
+        for _ in 0..60 {
+            full.step();
+            tracked.step();
+            fractions.push(tracked.active_fraction().unwrap());
+        }
+        assert_eq!(full.state.h, tracked.state.h);
+        assert_eq!(full.state.hu, tracked.state.hu);
+        assert_eq!(full.state.hv, tracked.state.hv);
+        assert_eq!(full.time, tracked.time);
+
+        // The set starts small and grows with the flood
+        assert!(fractions[0] < 0.3);
+        assert!(fractions.last().unwrap() > &fractions[0]);
+        assert!(full.active_fraction().is_none());
+    }
+}
@@ -0,0 +1,258 @@
+/// Prescribed bed motion
+/// The bed displacement dz(x, y, t) is given on a regular grid at a series
+/// of times (e.g. a landslide or a co-seismic rupture history). It is
+/// interpolated bilinearly in space (zero outside the grid) and linearly in
+/// time (held outside the time range), added to the initial node
+/// elevations after every step, and the cell beds are recomputed from the
+/// nodes. The continuity equation for the water level gains ∂z_b/∂t; with
+/// the depth as unknown this amounts to moving the bed under the water
+/// column, which lifts or lowers the surface by the bed change of the step.
+use super::ShallowWaterSolver;
+
+/// Gridded bed displacement time series
+#[derive(Debug, Clone, PartialEq)]
+pub struct BedDeformation {
+    pub xs: Vec<f64>,                 // Grid x coordinates, ascending
+    pub ys: Vec<f64>,                 // Grid y coordinates, ascending
+    pub frames: Vec<(f64, Vec<f64>)>, // (time, dz per grid point with x fastest), ascending times
+}
+
+/// Deformation attached to a solver
+pub(super) struct BedMotion {
+    deformation: BedDeformation,
+    base: Vec<f64>,                           // Node elevations before the motion
+    stencils: Vec<Option<(usize, [f64; 4])>>, // Lower-left grid point and bilinear weights per node
+}
+
+impl BedDeformation {
+    /// Parse rows "time,x,y,dz" (header line and lines starting with '#'
+    /// are skipped); every time must give the same grid of points
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut rows: Vec<(f64, f64, f64, f64)> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || (number == 0 && line.starts_with("time"))
+            {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() != 4 {
+                return Err(format!(
+                    "line {}: expected time,x,y,dz but found '{}'",
+                    number + 1,
+                    line
+                ));
+            }
+            let value = |k: usize| {
+                fields[k]
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(|| format!("line {}: invalid number '{}'", number + 1, fields[k]))
+            };
+            rows.push((value(0)?, value(1)?, value(2)?, value(3)?));
+        }
+        if rows.is_empty() {
+            return Err("no bed displacement rows found".to_string());
+        }
+
+        let axis = |coordinate: fn(&(f64, f64, f64, f64)) -> f64| {
+            let mut values: Vec<f64> = rows.iter().map(coordinate).collect();
+            values.sort_by(f64::total_cmp);
+            values.dedup();
+            values
+        };
+        let xs = axis(|r| r.1);
+        let ys = axis(|r| r.2);
+        if xs.len() < 2 || ys.len() < 2 {
+            return Err("the displacement grid needs at least 2 x 2 points".to_string());
+        }
+
+        rows.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut frames: Vec<(f64, Vec<f64>)> = Vec::new();
+        let mut filled: Vec<bool> = Vec::new();
+        for (time, x, y, dz) in rows {
+            if frames.last().is_none_or(|(t, _)| *t != time) {
+                if filled.iter().any(|f| !f) {
+                    return Err(format!(
+                        "time {}: incomplete displacement grid",
+                        frames[frames.len() - 1].0
+                    ));
+                }
+                frames.push((time, vec![0.0; xs.len() * ys.len()]));
+                filled = vec![false; xs.len() * ys.len()];
+            }
+            let col = xs.binary_search_by(|v| v.total_cmp(&x)).unwrap();
+            let row = ys.binary_search_by(|v| v.total_cmp(&y)).unwrap();
+            let k = row * xs.len() + col;
+            if filled[k] {
+                return Err(format!("time {}: point ({}, {}) given twice", time, x, y));
+            }
+            filled[k] = true;
+            frames.last_mut().unwrap().1[k] = dz;
+        }
+        if filled.iter().any(|f| !f) {
+            return Err(format!(
+                "time {}: incomplete displacement grid",
+                frames[frames.len() - 1].0
+            ));
+        }
+        Ok(BedDeformation { xs, ys, frames })
+    }
+
+    pub fn read(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Times of the first and last frame
+    pub fn time_range(&self) -> (f64, f64) {
+        (self.frames[0].0, self.frames[self.frames.len() - 1].0)
+    }
+
+    /// Lower-left grid point and bilinear weights at (x, y), None outside
+    fn stencil(&self, x: f64, y: f64) -> Option<(usize, [f64; 4])> {
+        let cell = |axis: &[f64], v: f64| {
+            if !(axis[0]..=axis[axis.len() - 1]).contains(&v) {
+                return None;
+            }
+            let i = axis.partition_point(|&a| a <= v).clamp(1, axis.len() - 1) - 1;
+            Some((i, (v - axis[i]) / (axis[i + 1] - axis[i])))
+        };
+        let (col, fx) = cell(&self.xs, x)?;
+        let (row, fy) = cell(&self.ys, y)?;
+        Some((
+            row * self.xs.len() + col,
+            [
+                (1.0 - fx) * (1.0 - fy),
+                fx * (1.0 - fy),
+                (1.0 - fx) * fy,
+                fx * fy,
+            ],
+        ))
+    }
+
+    /// Frame indices and weight of the later frame at `time`
+    fn bracket(&self, time: f64) -> (usize, usize, f64) {
+        let k = self.frames.partition_point(|(t, _)| *t <= time);
+        if k == 0 || k == self.frames.len() {
+            let k = k.saturating_sub(1);
+            return (k, k, 0.0);
+        }
+        let (t0, t1) = (self.frames[k - 1].0, self.frames[k].0);
+        (k - 1, k, (time - t0) / (t1 - t0))
+    }
+
+    fn evaluate(&self, stencil: (usize, [f64; 4]), frames: (usize, usize, f64)) -> f64 {
+        let (k, w) = stencil;
+        let nx = self.xs.len();
+        let at = |values: &[f64]| {
+            w[0] * values[k]
+                + w[1] * values[k + 1]
+                + w[2] * values[k + nx]
+                + w[3] * values[k + nx + 1]
+        };
+        let (a, b, s) = frames;
+        let (za, zb) = (at(&self.frames[a].1), at(&self.frames[b].1));
+        za + s * (zb - za)
+    }
+
+    /// Displacement at (x, y) and `time` (zero outside the grid)
+    pub fn displacement(&self, time: f64, x: f64, y: f64) -> f64 {
+        self.stencil(x, y)
+            .map_or(0.0, |stencil| self.evaluate(stencil, self.bracket(time)))
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Move the bed with `deformation` relative to the current node
+    /// elevations, starting with the displacement at the current time
+    pub fn set_bed_deformation(&mut self, deformation: BedDeformation) {
+        let stencils = self
+            .mesh
+            .nodes
+            .iter()
+            .map(|node| deformation.stencil(node.x, node.y))
+            .collect();
+        self.bed_motion = Some(BedMotion {
+            base: self.mesh.nodes.iter().map(|node| node.z).collect(),
+            deformation,
+            stencils,
+        });
+        self.update_bed();
+    }
+
+    /// Whether a prescribed bed motion is attached
+    pub fn has_moving_bed(&self) -> bool {
+        self.bed_motion.is_some()
+    }
+
+    /// Set the node and cell bed elevations for the current time
+    pub(super) fn update_bed(&mut self) {
+        let Some(motion) = &self.bed_motion else {
+            return;
+        };
+        let frames = motion.deformation.bracket(self.time);
+        for (n, node) in self.mesh.nodes.iter_mut().enumerate() {
+            node.z = motion.base[n]
+                + motion.stencils[n].map_or(0.0, |s| motion.deformation.evaluate(s, frames));
+        }
+        for tri in &mut self.mesh.triangles {
+            tri.z_bed = tri.nodes.iter().map(|&n| self.mesh.nodes[n].z).sum::<f64>() / 3.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_parse_and_interpolate() {
+        let text = "time,x,y,dz\n\
+                    0,0,0,0\n0,10,0,0\n0,0,10,0\n0,10,10,0\n\
+                    # uplift of the north-east corner\n\
+                    2,0,0,0\n2,10,0,0\n2,0,10,0\n2,10,10,4\n";
+        let deformation = BedDeformation::parse(text).unwrap();
+        assert_eq!(deformation.time_range(), (0.0, 2.0));
+        assert!((deformation.displacement(2.0, 5.0, 5.0) - 1.0).abs() < 1e-12);
+        assert!((deformation.displacement(1.0, 10.0, 10.0) - 2.0).abs() < 1e-12);
+        assert_eq!(deformation.displacement(5.0, 10.0, 10.0), 4.0);
+        assert_eq!(deformation.displacement(1.0, 10.5, 5.0), 0.0);
+
+        assert!(BedDeformation::parse("0,0,0,0\n0,1,0,0\n0,0,1,0\n").is_err());
+        assert!(BedDeformation::parse("0,0,0,x\n").is_err());
+    }
+
+    #[test]
+    fn test_uplift_lifts_the_surface() {
+        let mesh = TriangularMesh::new_rectangular(21, 21, 20.0, 20.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.iter_mut().for_each(|h| *h = 2.0);
+        // 0.3 m uplift of the centre over 0.5 s
+        let mut text = String::new();
+        for (t, amplitude) in [(0.0, 0.0), (0.5, 0.3)] {
+            for y in [0.0, 8.0, 12.0, 20.0] {
+                for x in [0.0, 8.0, 12.0, 20.0] {
+                    let inside = (8.0..=12.0).contains(&x) && (8.0..=12.0).contains(&y);
+                    let dz = if inside { amplitude } else { 0.0 };
+                    text += &format!("{},{},{},{}\n", t, x, y, dz);
+                }
+            }
+        }
+        solver.set_bed_deformation(BedDeformation::parse(&text).unwrap());
+        assert!(solver.has_moving_bed() && solver.single_precision_unsupported().is_some());
+        let mass = solver.compute_total_mass();
+
+        while solver.time < 0.5 {
+            solver.step();
+        }
+        let centre = solver.sample(10.0, 10.0).unwrap();
+        assert!((centre.bed - 0.3).abs() < 1e-12);
+        assert!(centre.surface > 2.1, "surface {}", centre.surface);
+        assert!(solver.state.hu.iter().any(|hu| hu.abs() > 1e-3));
+        assert!((solver.compute_total_mass() - mass).abs() < 1e-9 * mass);
+    }
+}
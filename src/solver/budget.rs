@@ -0,0 +1,276 @@
+/// Momentum and energy budgets
+/// The energy per unit density, E = ∫ (½ h |u|² + g h (z_b + h/2)) dA,
+/// changes only by the work done against bed friction and by the energy flux
+/// through the boundary. What remains after subtracting both from the change
+/// of E is dissipated by the scheme itself, so the budget residual measures
+/// the numerical dissipation. Likewise the change of ∫ (hu, hv) dA is
+/// balanced by the boundary flux (pressure on walls), the bed slope force and
+/// friction. Rates are evaluated from the state after each step and
+/// integrated with the trapezoidal rule, so the residuals also contain an
+/// O(dt) quadrature error that vanishes with the time step.
+use super::ShallowWaterSolver;
+use crate::reduction;
+
+/// Instantaneous rates of the budget terms
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BudgetRates {
+    pub boundary_energy: f64,          // Energy outflow through the boundary
+    pub boundary_momentum: (f64, f64), // Momentum outflow, including wall pressure
+    pub bed_force: (f64, f64),         // -∫ g h ∇z_b dA
+    pub friction_force: (f64, f64),    // -∫ g h S_f dA
+    pub friction_power: f64,           // ∫ g h S_f · u dA (work done against friction)
+}
+
+impl ShallowWaterSolver {
+    /// Total momentum (∫ hu dA, ∫ hv dA)
+    pub fn compute_total_momentum(&self) -> (f64, f64) {
+        let triangles = &self.mesh.triangles;
+        (
+            reduction::sum(triangles.len(), |i| self.state.hu[i] * triangles[i].area),
+            reduction::sum(triangles.len(), |i| self.state.hv[i] * triangles[i].area),
+        )
+    }
+
+    /// Kinetic energy ∫ ½ h |u|² dA
+    pub fn compute_kinetic_energy(&self) -> f64 {
+        let triangles = &self.mesh.triangles;
+        reduction::sum(triangles.len(), |i| {
+            let (u, v) = self.velocity(&self.state, i);
+            0.5 * self.state.h[i] * (u * u + v * v) * triangles[i].area
+        })
+    }
+
+    /// Potential energy ∫ g h (z_b + h/2) dA relative to z = 0
+    pub fn compute_potential_energy(&self) -> f64 {
+        let triangles = &self.mesh.triangles;
+        reduction::sum(triangles.len(), |i| {
+            let h = self.state.h[i];
+            self.constants.gravity * h * (triangles[i].z_bed + 0.5 * h) * triangles[i].area
+        })
+    }
+
+    /// Rates of the budget terms in the current state
+    /// Boundary fluxes are the first-order ones for either scheme.
+    pub fn budget_rates(&self) -> BudgetRates {
+        let g = self.constants.gravity;
+        let mut rates = BudgetRates::default();
+        let ghosts = self.fill_ghosts(&self.state);
+
+        for edge in self
+            .mesh
+            .edges
+            .iter()
+            .filter(|e| e.right_triangle.is_none())
+        {
+            let i = edge.left_triangle;
+            let (flux_h, flux_hu, flux_hv) = self.compute_flux(edge, &self.state, &ghosts);
+            let (u, v) = self.velocity(&self.state, i);
+            let head = 0.5 * (u * u + v * v) + g * (self.state.h[i] + self.mesh.triangles[i].z_bed);
+            rates.boundary_energy += head * flux_h * edge.length;
+            rates.boundary_momentum.0 += flux_hu * edge.length;
+            rates.boundary_momentum.1 += flux_hv * edge.length;
+        }
+
+        for (i, tri) in self.mesh.triangles.iter().enumerate() {
+            let h = self.state.h[i];
+            if h < self.constants.dry_tolerance {
+                continue;
+            }
+            let (u, v) = self.velocity(&self.state, i);
+            let (sf_x, sf_y) = self.compute_friction_slope(i, h, u, v);
+            rates.friction_force.0 -= g * h * sf_x * tri.area;
+            rates.friction_force.1 -= g * h * sf_y * tri.area;
+            rates.friction_power += g * h * (sf_x * u + sf_y * v) * tri.area;
+
+            // The central-upwind scheme balances the bed in its edge flux;
+            // the equivalent force still acts on the momentum
+            let mut slope = self.compute_bed_gradient(i);
+            if let Some(limit) = &self.bed_slope_limit {
+                slope = limit.apply(slope, h);
+            }
+            rates.bed_force.0 -= g * h * slope.0 * tri.area;
+            rates.bed_force.1 -= g * h * slope.1 * tri.area;
+        }
+        rates
+    }
+}
+
+/// Budget terms accumulated since the start of tracking
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+    pub time: f64,
+    pub mass: f64,
+    pub momentum: (f64, f64),
+    pub kinetic_energy: f64,
+    pub potential_energy: f64,
+    pub friction_work: f64,           // Energy lost to bed friction
+    pub boundary_energy_outflow: f64, // Energy carried out through the boundary
+    pub numerical_dissipation: f64,   // E0 - E - friction - outflow (budget residual)
+    pub boundary_momentum_outflow: (f64, f64),
+    pub bed_impulse: (f64, f64),
+    pub friction_impulse: (f64, f64),
+    pub momentum_residual: (f64, f64), // Change of momentum not explained by the forces
+}
+
+impl Budget {
+    pub const CSV_HEADER: &'static str = "time,mass,momentum_x,momentum_y,kinetic_energy,potential_energy,total_energy,friction_work,boundary_energy_outflow,numerical_dissipation,boundary_momentum_outflow_x,boundary_momentum_outflow_y,bed_impulse_x,bed_impulse_y,friction_impulse_x,friction_impulse_y,momentum_residual_x,momentum_residual_y";
+
+    pub fn total_energy(&self) -> f64 {
+        self.kinetic_energy + self.potential_energy
+    }
+
+    /// One row matching `CSV_HEADER`
+    pub fn csv_row(&self) -> String {
+        self.values().map(|v| v.to_string()).join(",")
+    }
+
+    /// Values in the order of `CSV_HEADER`
+    pub fn values(&self) -> [f64; 18] {
+        [
+            self.time,
+            self.mass,
+            self.momentum.0,
+            self.momentum.1,
+            self.kinetic_energy,
+            self.potential_energy,
+            self.total_energy(),
+            self.friction_work,
+            self.boundary_energy_outflow,
+            self.numerical_dissipation,
+            self.boundary_momentum_outflow.0,
+            self.boundary_momentum_outflow.1,
+            self.bed_impulse.0,
+            self.bed_impulse.1,
+            self.friction_impulse.0,
+            self.friction_impulse.1,
+            self.momentum_residual.0,
+            self.momentum_residual.1,
+        ]
+    }
+}
+
+/// Integrates the budget rates over the steps of a run
+pub struct BudgetTracker {
+    initial_energy: f64,
+    initial_momentum: (f64, f64),
+    rates: BudgetRates, // At the last recorded state
+    time: f64,
+    friction_work: f64,
+    boundary_energy: f64,
+    boundary_momentum: (f64, f64),
+    bed_impulse: (f64, f64),
+    friction_impulse: (f64, f64),
+}
+
+impl BudgetTracker {
+    pub fn new(solver: &ShallowWaterSolver) -> Self {
+        BudgetTracker {
+            initial_energy: solver.compute_kinetic_energy() + solver.compute_potential_energy(),
+            initial_momentum: solver.compute_total_momentum(),
+            rates: solver.budget_rates(),
+            time: solver.time,
+            friction_work: 0.0,
+            boundary_energy: 0.0,
+            boundary_momentum: (0.0, 0.0),
+            bed_impulse: (0.0, 0.0),
+            friction_impulse: (0.0, 0.0),
+        }
+    }
+
+    /// Integrate the rates from the previous update to the solver's time
+    pub fn update(&mut self, solver: &ShallowWaterSolver) {
+        let rates = solver.budget_rates();
+        let half_dt = 0.5 * (solver.time - self.time);
+        let (a, b) = (&self.rates, &rates);
+        self.friction_work += half_dt * (a.friction_power + b.friction_power);
+        self.boundary_energy += half_dt * (a.boundary_energy + b.boundary_energy);
+        let integrate = |total: &mut (f64, f64), a: (f64, f64), b: (f64, f64)| {
+            total.0 += half_dt * (a.0 + b.0);
+            total.1 += half_dt * (a.1 + b.1);
+        };
+        integrate(
+            &mut self.boundary_momentum,
+            a.boundary_momentum,
+            b.boundary_momentum,
+        );
+        integrate(&mut self.bed_impulse, a.bed_force, b.bed_force);
+        integrate(
+            &mut self.friction_impulse,
+            a.friction_force,
+            b.friction_force,
+        );
+        self.rates = rates;
+        self.time = solver.time;
+    }
+
+    /// Budget of the solver's current state
+    pub fn budget(&self, solver: &ShallowWaterSolver) -> Budget {
+        let kinetic_energy = solver.compute_kinetic_energy();
+        let potential_energy = solver.compute_potential_energy();
+        let momentum = solver.compute_total_momentum();
+        let explained = |k: usize| {
+            let pick = |v: (f64, f64)| if k == 0 { v.0 } else { v.1 };
+            pick(self.bed_impulse) + pick(self.friction_impulse) - pick(self.boundary_momentum)
+        };
+        Budget {
+            time: solver.time,
+            mass: solver.compute_total_mass(),
+            momentum,
+            kinetic_energy,
+            potential_energy,
+            friction_work: self.friction_work,
+            boundary_energy_outflow: self.boundary_energy,
+            numerical_dissipation: self.initial_energy
+                - kinetic_energy
+                - potential_energy
+                - self.friction_work
+                - self.boundary_energy,
+            boundary_momentum_outflow: self.boundary_momentum,
+            bed_impulse: self.bed_impulse,
+            friction_impulse: self.friction_impulse,
+            momentum_residual: (
+                momentum.0 - self.initial_momentum.0 - explained(0),
+                momentum.1 - self.initial_momentum.1 - explained(1),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    fn run_dam_break(friction: FrictionLaw) -> Budget {
+        let mesh = TriangularMesh::new_rectangular(20, 4, 10.0, 2.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.4, friction);
+        solver.set_dam_break(5.0);
+        let mut tracker = BudgetTracker::new(&solver);
+        while solver.time < 0.5 {
+            solver.step();
+            tracker.update(&solver);
+        }
+        tracker.budget(&solver)
+    }
+
+    #[test]
+    fn test_budgets_close_for_dam_break() {
+        let budget = run_dam_break(FrictionLaw::None);
+        assert_eq!(budget.friction_work, 0.0);
+        // Walls carry no energy; the Rusanov flux only dissipates
+        assert!(budget.boundary_energy_outflow.abs() < 1e-9);
+        assert!(budget.numerical_dissipation > 0.0);
+        assert!(budget.numerical_dissipation < 0.05 * budget.total_energy());
+
+        // Wall pressure is the only force on a flat bed
+        let wall = budget.boundary_momentum_outflow.0;
+        assert!(wall.abs() > 1.0);
+        assert!(budget.momentum_residual.0.abs() < 0.05 * wall.abs());
+
+        let rough = run_dam_break(FrictionLaw::Manning { coefficient: 0.05 });
+        assert!(rough.friction_work > 0.0);
+        assert!(rough.friction_impulse.0 < 0.0);
+        assert!(rough.momentum_residual.0.abs() < 0.05 * rough.boundary_momentum_outflow.0.abs());
+    }
+}
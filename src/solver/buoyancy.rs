@@ -0,0 +1,269 @@
+/// Buoyant scalar (temperature or salinity)
+/// The scalar φ is carried as the depth-integrated anomaly h (φ - φ_a) from
+/// the ambient value φ_a, advected like suspended sediment (first-order
+/// upwind with the mass fluxes of the final Runge-Kutta stage), so ambient
+/// water stays ambient. Outfalls discharge water of a given scalar value at
+/// a point, adding Q/A to the mass and Q (φ_d - φ_a)/A to the anomaly of
+/// their cell. Dry cells return to ambient.
+///
+/// The density follows the equation of state of the scalar, relative to the
+/// ambient water, r = ρ(φ)/ρ(φ_a) - 1, and the `ScalarBuoyancy` source term
+/// adds the reduced-gravity pressure force -½ g h² ∇r (Boussinesq
+/// approximation) on top of the outfall discharge. Warm or fresh water
+/// spreads over the ambient water and brine slumps under it, but within the
+/// single depth-averaged layer there is no stratification: a plume is mixed
+/// over the full depth.
+use super::{FluxScheme, ShallowWaterSolver, SourceTerm, State, TimeIntegrator};
+
+/// Haline contraction coefficient of the linear salinity law (per g/kg)
+const HALINE_CONTRACTION: f64 = 7.6e-4;
+
+/// Quantity the scalar stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarKind {
+    Temperature, // °C, fresh water
+    Salinity,    // g/kg, at constant temperature
+}
+
+impl ScalarKind {
+    /// Water density (kg/m^3) at scalar value `value`: the fresh water
+    /// equation of Tanaka et al. (2001) for temperature, a linear law for
+    /// salinity
+    pub fn density(&self, value: f64) -> f64 {
+        match self {
+            ScalarKind::Temperature => {
+                let t = value;
+                999.97495
+                    * (1.0 - (t - 3.983035).powi(2) * (t + 301.797) / (522528.9 * (t + 69.34881)))
+            }
+            ScalarKind::Salinity => 999.97495 * (1.0 + HALINE_CONTRACTION * value),
+        }
+    }
+}
+
+/// Point discharge of water with a scalar value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outfall {
+    pub cell: usize,
+    pub discharge: f64, // Q (m^3/s)
+    pub value: f64,     // Scalar value of the discharged water
+}
+
+/// Buoyant scalar field
+#[derive(Debug, Clone)]
+pub struct BuoyantScalar {
+    pub kind: ScalarKind,
+    pub ambient: f64, // φ_a
+    pub hs: Vec<f64>, // Depth-integrated anomaly h (φ - φ_a)
+    pub outfalls: Vec<Outfall>,
+}
+
+impl BuoyantScalar {
+    /// Relative density excess of water with scalar value `value` over the
+    /// ambient water
+    pub fn relative_density(&self, value: f64) -> f64 {
+        self.kind.density(value) / self.kind.density(self.ambient) - 1.0
+    }
+}
+
+/// Outfall discharge and reduced-gravity pressure force of the scalar
+pub struct ScalarBuoyancy;
+
+impl SourceTerm for ScalarBuoyancy {
+    fn name(&self) -> &str {
+        "scalar buoyancy"
+    }
+
+    fn evaluate(&self, solver: &ShallowWaterSolver, state: &State, i: usize) -> (f64, f64, f64) {
+        let Some(scalar) = &solver.buoyant_scalar else {
+            return (0.0, 0.0, 0.0);
+        };
+        let inflow: f64 = scalar
+            .outfalls
+            .iter()
+            .filter(|o| o.cell == i)
+            .map(|o| o.discharge / solver.mesh.triangles[i].area)
+            .sum();
+        let h = state.h[i];
+        if h < solver.constants.dry_tolerance {
+            return (inflow, 0.0, 0.0);
+        }
+        let (rx, ry) = solver.local_gradient(i, |j| {
+            scalar.relative_density(solver.scalar_value_at(scalar, j))
+        });
+        let force = -0.5 * solver.constants.gravity * h * h;
+        (inflow, force * rx, force * ry)
+    }
+
+    fn acts_on_dry_cells(&self) -> bool {
+        true
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Carry a buoyant scalar at its ambient value everywhere, fed by
+    /// `outfalls` (Rusanov flux and RK2 only)
+    pub fn enable_buoyant_scalar(
+        &mut self,
+        kind: ScalarKind,
+        ambient: f64,
+        outfalls: Vec<Outfall>,
+    ) -> Result<(), String> {
+        if self.flux_scheme != FluxScheme::Rusanov {
+            return Err("a buoyant scalar requires the Rusanov flux".to_string());
+        }
+        if !matches!(self.time_integrator, TimeIntegrator::RungeKutta2) {
+            return Err("a buoyant scalar requires the RK2 integrator".to_string());
+        }
+        self.require_finite_volume("buoyant scalars")?;
+        let n = self.mesh.triangles.len();
+        if let Some(outfall) = outfalls.iter().find(|o| o.cell >= n) {
+            return Err(format!("outfall cell {} is outside the mesh", outfall.cell));
+        }
+        if !kind.density(ambient).is_finite() {
+            return Err(format!("ambient value {} out of range", ambient));
+        }
+        self.buoyant_scalar = Some(BuoyantScalar {
+            kind,
+            ambient,
+            hs: vec![0.0; n],
+            outfalls,
+        });
+        self.source_terms
+            .retain(|t| t.name() != ScalarBuoyancy.name());
+        self.source_terms.push(Box::new(ScalarBuoyancy));
+        Ok(())
+    }
+
+    /// Scalar value of cell `i` (ambient in dry cells)
+    fn scalar_value_at(&self, scalar: &BuoyantScalar, i: usize) -> f64 {
+        if self.state.h[i] >= self.constants.dry_tolerance {
+            scalar.ambient + scalar.hs[i] / self.state.h[i]
+        } else {
+            scalar.ambient
+        }
+    }
+
+    /// Scalar value per cell (zeros without a buoyant scalar)
+    pub fn scalar_field(&self) -> Vec<f64> {
+        let Some(scalar) = &self.buoyant_scalar else {
+            return vec![0.0; self.mesh.triangles.len()];
+        };
+        (0..self.mesh.triangles.len())
+            .map(|i| self.scalar_value_at(scalar, i))
+            .collect()
+    }
+
+    /// Integral of the anomaly h (φ - φ_a) over the domain (e.g. °C m^3),
+    /// which changes only through the outfalls, the boundary and drying
+    pub fn total_scalar_anomaly(&self) -> f64 {
+        let Some(scalar) = &self.buoyant_scalar else {
+            return 0.0;
+        };
+        (0..self.mesh.triangles.len())
+            .map(|i| scalar.hs[i] * self.storage_area(i))
+            .sum()
+    }
+
+    /// Advance the scalar over the current step: upwind advection with the
+    /// mass fluxes of `stage`, the outfall loads, and ambient water in the
+    /// cells that are dry at the new depths `h_new`
+    pub(super) fn transport_scalar(&mut self, stage: &State, h_new: &[f64]) {
+        let Some(mut scalar) = self.buoyant_scalar.take() else {
+            return;
+        };
+        let mut hs = self.advect_depth_integrated(stage, &scalar.hs);
+        for outfall in &scalar.outfalls {
+            let area = self.mesh.triangles[outfall.cell].area;
+            hs[outfall.cell] +=
+                self.dt * outfall.discharge * (outfall.value - scalar.ambient) / area;
+        }
+        for (value, &h) in hs.iter_mut().zip(h_new) {
+            if h < self.constants.dry_tolerance {
+                *value = 0.0;
+            }
+        }
+        scalar.hs = hs;
+        self.buoyant_scalar = Some(scalar);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_equations_of_state() {
+        // Fresh water is densest near 4 °C
+        let t = ScalarKind::Temperature;
+        assert!((t.density(4.0) - 999.975).abs() < 1e-3);
+        assert!(
+            (t.density(20.0) - 998.206).abs() < 2e-3,
+            "{}",
+            t.density(20.0)
+        );
+        assert!(t.density(4.0) > t.density(0.0) && t.density(4.0) > t.density(8.0));
+        let s = ScalarKind::Salinity;
+        assert!((s.density(35.0) - 1026.6).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_brine_outfall() {
+        // Brine discharged into a basin at rest spreads from the outfall and
+        // the salt load matches the discharge
+        let mesh = TriangularMesh::new_rectangular(11, 11, 20.0, 20.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(1.0);
+        let cell = solver.locate(10.3, 10.6).unwrap();
+        let outfall = Outfall {
+            cell,
+            discharge: 0.05,
+            value: 70.0,
+        };
+        solver
+            .enable_buoyant_scalar(ScalarKind::Salinity, 35.0, vec![outfall])
+            .unwrap();
+        assert!(solver.single_precision_unsupported().is_some());
+        let initial = solver.compute_total_mass();
+        while solver.time < 20.0 {
+            solver.step();
+        }
+        let discharged = 0.05 * solver.time;
+        assert!((solver.compute_total_mass() - initial - discharged).abs() < 1e-9);
+        let load = solver.total_scalar_anomaly();
+        assert!((load - 35.0 * discharged).abs() < 1e-9 * load, "{}", load);
+
+        let field = solver.scalar_field();
+        assert!(field.iter().all(|&s| (35.0..=70.0).contains(&s)));
+        let spread = field.iter().filter(|&&s| s > 35.0 + 1e-6).count();
+        assert!(spread > 1);
+        assert!(field[cell] > 35.0);
+    }
+
+    #[test]
+    fn test_lock_exchange() {
+        // Warm water behind the lock: the reduced-gravity force pushes the
+        // column from the denser cold side towards the warm side
+        let mesh = TriangularMesh::new_rectangular(21, 3, 20.0, 2.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(1.0);
+        solver
+            .enable_buoyant_scalar(ScalarKind::Temperature, 10.0, Vec::new())
+            .unwrap();
+        for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+            if tri.centroid.0 > 10.0 {
+                solver.buoyant_scalar.as_mut().unwrap().hs[i] = 15.0;
+            }
+        }
+        while solver.time < 1.0 {
+            solver.step();
+        }
+        let at_lock = solver.locate(10.2, 1.0).unwrap();
+        assert!(solver.state.hu[at_lock] > 0.0);
+        // 15 °C x 1 m over the 20 m^2 behind the lock
+        let anomaly = 15.0 * 20.0;
+        assert!((solver.total_scalar_anomaly() - anomaly).abs() < 1e-9 * anomaly);
+    }
+}
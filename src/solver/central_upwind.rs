@@ -0,0 +1,506 @@
+/// Kurganov-Petrova central-upwind scheme on triangles
+/// Following Bryson, Epshteyn, Kurganov & Petrova (2011): the free surface
+/// w = h + B is reconstructed piecewise linearly at edge midpoints, corrected
+/// so that the depth never becomes negative, and the bed source term is
+/// discretised as
+///
+///   -g h ∇B = ½ g ∇(h²) - g h ∇w
+///
+/// with ½ g ∇(h²) evaluated from the same edge depths as the flux. A lake at
+/// rest is therefore preserved exactly and the first-order update keeps the
+/// depth non-negative under the CFL condition.
+use super::exchange::MassRates;
+use super::{EdgeState, ShallowWaterSolver, State};
+use crate::mesh::Triangle;
+use crate::profiler::Phase;
+use rayon::prelude::*;
+
+/// Reconstructed point values at the three edge midpoints of a cell
+#[derive(Debug, Clone, Copy, Default)]
+struct EdgeValues {
+    h: [f64; 3],
+    u: [f64; 3],
+    v: [f64; 3],
+    surface_gradient: (f64, f64), // ∇w of the corrected reconstruction
+}
+
+/// Cell-average quantities that are reconstructed, in the cells and in the
+/// ghost cells of the mesh
+struct CellFields {
+    surface: Vec<f64>, // w = h + B
+    u: Vec<f64>,
+    v: Vec<f64>,
+    ghost_surface: Vec<f64>,
+    ghost_u: Vec<f64>,
+    ghost_v: Vec<f64>,
+}
+
+/// Local edge index k (nodes k, k+1) of a triangle
+fn local_edge(tri: &Triangle, nodes: [usize; 2]) -> usize {
+    (0..3)
+        .find(|&k| {
+            let (a, b) = (tri.nodes[k], tri.nodes[(k + 1) % 3]);
+            (a == nodes[0] && b == nodes[1]) || (a == nodes[1] && b == nodes[0])
+        })
+        .unwrap_or(0)
+}
+
+impl ShallowWaterSolver {
+    /// Bed elevation at the midpoint of local edge k (bed is linear per cell)
+    fn edge_bed(&self, tri: &Triangle, k: usize) -> f64 {
+        let z0 = self.mesh.nodes[tri.nodes[k]].z;
+        let z1 = self.mesh.nodes[tri.nodes[(k + 1) % 3]].z;
+        0.5 * (z0 + z1)
+    }
+
+    /// Barth-Jespersen limited linear reconstruction at the edge midpoints,
+    /// with the values `ghost_field` of the ghost cells `ghosts` of the
+    /// cell's boundary sides in the stencil
+    fn limited_edge_values(
+        &self,
+        field: &[f64],
+        ghost_field: &[f64],
+        ghosts: [Option<usize>; 3],
+        i: usize,
+    ) -> [f64; 3] {
+        let tri = &self.mesh.triangles[i];
+        let (gx, gy) =
+            self.ghosted_gradient(i, |j| field[j], |k| ghosts[k].map(|g| ghost_field[g]));
+
+        let (mut lo, mut hi) = (field[i], field[i]);
+        let ghost_values = ghosts.iter().flatten().map(|&g| ghost_field[g]);
+        for value in tri
+            .neighbors
+            .iter()
+            .flatten()
+            .map(|&j| field[j])
+            .chain(ghost_values)
+        {
+            lo = lo.min(value);
+            hi = hi.max(value);
+        }
+
+        let mut increments = [0.0; 3];
+        let mut phi: f64 = 1.0;
+        for (k, increment) in increments.iter_mut().enumerate() {
+            let n0 = &self.mesh.nodes[tri.nodes[k]];
+            let n1 = &self.mesh.nodes[tri.nodes[(k + 1) % 3]];
+            let mid = (0.5 * (n0.x + n1.x), 0.5 * (n0.y + n1.y));
+            let (dx, dy) = self.mesh.coordinate_system.delta(tri.centroid, mid);
+            *increment = gx * dx + gy * dy;
+
+            if *increment > 1e-14 {
+                phi = phi.min((hi - field[i]) / *increment);
+            } else if *increment < -1e-14 {
+                phi = phi.min((lo - field[i]) / *increment);
+            }
+        }
+
+        increments.map(|d| field[i] + phi * d)
+    }
+
+    /// Reconstruct depth and velocity at the edge midpoints of cell i
+    /// Velocities rather than momenta are reconstructed so that edges with
+    /// a vanishing corrected depth cannot carry a spurious momentum.
+    fn reconstruct_cell(&self, state: &State, fields: &CellFields, i: usize) -> EdgeValues {
+        let tri = &self.mesh.triangles[i];
+        let h_mean = state.h[i];
+        if h_mean < self.constants.dry_tolerance {
+            return EdgeValues::default();
+        }
+
+        let ghosts = std::array::from_fn(|k| self.mesh.ghost(i, k));
+        let w = self.limited_edge_values(&fields.surface, &fields.ghost_surface, ghosts, i);
+        let u = self.limited_edge_values(&fields.u, &fields.ghost_u, ghosts, i);
+        let v = self.limited_edge_values(&fields.v, &fields.ghost_v, ghosts, i);
+
+        // Positivity correction: clip negative edge depths and rescale the
+        // rest so that the cell average is unchanged (the mean of the edge
+        // midpoint beds equals the cell bed for a linear bed)
+        let mut h = [0.0; 3];
+        for k in 0..3 {
+            h[k] = (w[k] - self.edge_bed(tri, k)).max(0.0);
+        }
+        let h_edges = (h[0] + h[1] + h[2]) / 3.0;
+        if h_edges > 0.0 {
+            h = h.map(|d| d * h_mean / h_edges);
+        } else {
+            h = [h_mean; 3];
+        }
+
+        // Gradient of the corrected surface, consistent with the edge depths
+        let (mut wx, mut wy) = (0.0, 0.0);
+        for (k, (nx_l, ny_l)) in self.scaled_outward_normals(i).iter().enumerate() {
+            let w_k = h[k] - h_mean + self.edge_bed(tri, k) - tri.z_bed;
+            wx += w_k * nx_l;
+            wy += w_k * ny_l;
+        }
+
+        EdgeValues {
+            h,
+            u,
+            v,
+            surface_gradient: (wx / tri.area, wy / tri.area),
+        }
+    }
+
+    /// Recompute cell momentum from desingularized velocities so that thin
+    /// films near the dry state cannot produce spurious large speeds
+    pub(super) fn desingularize_momentum(&self, state: &mut State) {
+        state
+            .h
+            .par_iter()
+            .zip(state.hu.par_iter_mut().zip(state.hv.par_iter_mut()))
+            .for_each(|(&h, (hu, hv))| {
+                let (u, v) = self.constants.velocity(h, *hu, *hv);
+                (*hu, *hv) = (h * u, h * v);
+            });
+    }
+
+    /// Central-upwind flux across a unit normal from edge point values
+    pub(super) fn central_upwind_flux(
+        &self,
+        normal: (f64, f64),
+        left: (f64, f64, f64),
+        right: (f64, f64, f64),
+    ) -> (f64, f64, f64) {
+        let (nx, ny) = normal;
+        let (h_l, u_l, v_l) = left;
+        let (h_r, u_r, v_r) = right;
+
+        let un_l = u_l * nx + v_l * ny;
+        let un_r = u_r * nx + v_r * ny;
+        let (c_l, c_r) = (
+            (self.constants.gravity * h_l).sqrt(),
+            (self.constants.gravity * h_r).sqrt(),
+        );
+
+        // One-sided local speeds of propagation
+        let a_out = (un_l + c_l).max(un_r + c_r).max(0.0);
+        let a_in = (un_l - c_l).min(un_r - c_r).min(0.0);
+        if a_out - a_in < 1e-12 {
+            return (0.0, 0.0, 0.0);
+        }
+
+        let physical = |h: f64, u: f64, v: f64, un: f64| {
+            let pressure = 0.5 * self.constants.gravity * h * h;
+            (
+                h * un,
+                h * u * un + pressure * nx,
+                h * v * un + pressure * ny,
+            )
+        };
+        let f_l = physical(h_l, u_l, v_l, un_l);
+        let f_r = physical(h_r, u_r, v_r, un_r);
+
+        // The jump in w equals the jump in h because both sides share B_e
+        let denom = a_out - a_in;
+        let diffusion = a_out * a_in / denom;
+        (
+            (a_out * f_l.0 - a_in * f_r.0) / denom + diffusion * (h_r - h_l),
+            (a_out * f_l.1 - a_in * f_r.1) / denom + diffusion * (h_r * u_r - h_l * u_l),
+            (a_out * f_l.2 - a_in * f_r.2) / denom + diffusion * (h_r * v_r - h_l * v_l),
+        )
+    }
+
+    /// Cell fields and their reconstruction at the edge midpoints of every
+    /// cell
+    fn central_upwind_reconstruction(&self, state: &State) -> (CellFields, Vec<EdgeValues>) {
+        let n = self.mesh.triangles.len();
+        let (u, v): (Vec<f64>, Vec<f64>) = (0..n).map(|i| self.velocity(state, i)).unzip();
+        let surface: Vec<f64> = (0..n)
+            .map(|i| state.h[i] + self.mesh.triangles[i].z_bed)
+            .collect();
+        // Ghost cells are filled from the interior surface carried flat to
+        // the edge; the ghost surface differs from the interior one by the
+        // depth the closure adds, so a lake at rest stays at rest next to
+        // any closure that keeps the depth or the level
+        let inner_depth: Vec<f64> = self
+            .mesh
+            .ghosts
+            .iter()
+            .map(|ghost| {
+                let bed = self.edge_bed(&self.mesh.triangles[ghost.cell], ghost.side);
+                (surface[ghost.cell] - bed).max(0.0)
+            })
+            .collect();
+        let ghosts = self.fill_ghosts_with(|g| {
+            let cell = self.mesh.ghosts[g].cell;
+            EdgeState::from_velocity(inner_depth[g], u[cell], v[cell])
+        });
+        let fields = CellFields {
+            ghost_surface: (0..ghosts.len())
+                .map(|g| surface[self.mesh.ghosts[g].cell] + ghosts[g].h - inner_depth[g])
+                .collect(),
+            surface,
+            u,
+            v,
+            ghost_u: ghosts.iter().map(|s| s.u).collect(),
+            ghost_v: ghosts.iter().map(|s| s.v).collect(),
+        };
+        let reconstruction = (0..n)
+            .into_par_iter()
+            .map(|i| self.reconstruct_cell(state, &fields, i))
+            .collect();
+        (fields, reconstruction)
+    }
+
+    /// Reconstructed (h, u, v) on the left and right of every interior
+    /// edge, as the central-upwind flux sees them (None on the boundary)
+    pub(super) fn central_upwind_edge_points(
+        &self,
+        state: &State,
+    ) -> Vec<Option<[(f64, f64, f64); 2]>> {
+        let (_, reconstruction) = self.central_upwind_reconstruction(state);
+        let point = |i: usize, nodes: [usize; 2]| {
+            let k = local_edge(&self.mesh.triangles[i], nodes);
+            let rec = &reconstruction[i];
+            (rec.h[k], rec.u[k], rec.v[k])
+        };
+        self.mesh
+            .edges
+            .iter()
+            .map(|edge| {
+                let right = edge.right_triangle?;
+                Some([
+                    point(edge.left_triangle, edge.nodes),
+                    point(right, edge.nodes),
+                ])
+            })
+            .collect()
+    }
+
+    /// Spatial residual of the central-upwind scheme including all sources,
+    /// with the mass flux of every edge scaled by `mass_weights`
+    pub(super) fn compute_central_upwind_residual(
+        &self,
+        state: &State,
+        mut rates: Option<&mut MassRates>,
+        mass_weights: Option<&[(f64, f64)]>,
+    ) -> State {
+        let start = self.profiler.start();
+        let n = self.mesh.triangles.len();
+        let (fields, reconstruction) = self.central_upwind_reconstruction(state);
+
+        let mut residual = State::new(n);
+
+        for (e, edge) in self.mesh.edges.iter().enumerate() {
+            let (nx, ny) = edge.normal;
+            let left = edge.left_triangle;
+            let k_l = local_edge(&self.mesh.triangles[left], edge.nodes);
+            let rec_l = &reconstruction[left];
+            let point_l = (rec_l.h[k_l], rec_l.u[k_l], rec_l.v[k_l]);
+
+            let (flux, point_r) = match edge.right_triangle {
+                Some(right) => {
+                    let k_r = local_edge(&self.mesh.triangles[right], edge.nodes);
+                    let rec_r = &reconstruction[right];
+                    let point_r = (rec_r.h[k_r], rec_r.u[k_r], rec_r.v[k_r]);
+                    (
+                        self.central_upwind_flux(edge.normal, point_l, point_r),
+                        point_r,
+                    )
+                }
+                None => {
+                    let (h, mut u, mut v) = point_l;
+                    // In a corner cell the limited velocity gradient rests on
+                    // a single neighbour besides two ghosts of the cell
+                    // itself; its walls see the cell velocity
+                    let open = self.mesh.triangles[left].neighbors.iter().flatten().count();
+                    if open < 2 {
+                        (u, v) = (fields.u[left], fields.v[left]);
+                    }
+                    let inner = EdgeState::from_velocity(h, u, v);
+                    match self.boundary_closure.boundary_flux(self, edge, &inner) {
+                        Some(flux) => (flux, point_l),
+                        None => {
+                            let ghost = self.boundary_closure.ghost(self, edge, &inner);
+                            let point_r = (ghost.h, ghost.u, ghost.v);
+                            let point_l = (h, u, v);
+                            (
+                                self.central_upwind_flux(edge.normal, point_l, point_r),
+                                point_r,
+                            )
+                        }
+                    }
+                }
+            };
+
+            // Well-balanced part of the bed source, ½ g h_k² n L per side
+            let pressure_l = 0.5 * self.constants.gravity * point_l.0 * point_l.0;
+            let (weight_l, weight_r) = mass_weights.map_or((1.0, 1.0), |w| w[e]);
+            residual.h[left] += flux.0 * edge.length * weight_l;
+            residual.hu[left] += (flux.1 - pressure_l * nx) * edge.length;
+            residual.hv[left] += (flux.2 - pressure_l * ny) * edge.length;
+
+            match edge.right_triangle {
+                Some(right) => {
+                    let pressure_r = 0.5 * self.constants.gravity * point_r.0 * point_r.0;
+                    residual.h[right] -= flux.0 * edge.length * weight_r;
+                    residual.hu[right] -= (flux.1 - pressure_r * nx) * edge.length;
+                    residual.hv[right] -= (flux.2 - pressure_r * ny) * edge.length;
+                }
+                None => {
+                    if let Some(rates) = rates.as_deref_mut() {
+                        rates.boundary.push((e, flux.0 * edge.length));
+                    }
+                }
+            }
+        }
+
+        // Remaining part of the bed source, -g h ∇w
+        for (i, rec) in reconstruction.iter().enumerate() {
+            let area = self.mesh.triangles[i].area;
+            let (wx, wy) = rec.surface_gradient;
+            residual.hu[i] += self.constants.gravity * state.h[i] * wx * area;
+            residual.hv[i] += self.constants.gravity * state.h[i] * wy * area;
+        }
+
+        // The slope limit swaps -g h ∇B for -g h times the limited slope
+        if let Some(limit) = &self.bed_slope_limit {
+            for i in 0..n {
+                let h = state.h[i];
+                if h < self.constants.dry_tolerance {
+                    continue;
+                }
+                let slope = self.compute_bed_gradient(i);
+                let limited = limit.apply(slope, h);
+                let area = self.mesh.triangles[i].area;
+                residual.hu[i] += self.constants.gravity * h * (limited.0 - slope.0) * area;
+                residual.hv[i] += self.constants.gravity * h * (limited.1 - slope.1) * area;
+            }
+        }
+        self.profiler.record(Phase::Flux, start);
+
+        // Friction and rotation
+        let sources = self.add_source_terms(&mut residual, state);
+        if let Some(rates) = rates {
+            rates.sources = sources;
+        }
+
+        residual
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{FluxScheme, FrictionLaw};
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+
+    fn central_upwind_solver(nx: usize, topography: TopographyType) -> ShallowWaterSolver {
+        let mesh = TriangularMesh::new_rectangular(nx, nx, 10.0, 10.0, topography);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.3, FrictionLaw::None);
+        solver.flux_scheme = FluxScheme::CentralUpwind;
+        solver
+    }
+
+    #[test]
+    fn test_lake_at_rest_over_bump() {
+        let mut solver = central_upwind_solver(
+            15,
+            TopographyType::Gaussian {
+                center: (5.0, 5.0),
+                amplitude: 0.8,
+                width: 2.0,
+            },
+        );
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = 1.0 - solver.mesh.triangles[i].z_bed;
+        }
+
+        for _ in 0..20 {
+            solver.step();
+        }
+        for i in 0..solver.state.h.len() {
+            let eta = solver.state.h[i] + solver.mesh.triangles[i].z_bed;
+            assert!((eta - 1.0).abs() < 1e-10, "Surface moved in cell {}", i);
+            assert!(solver.state.hu[i].abs() < 1e-10);
+            assert!(solver.state.hv[i].abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_dam_break_onto_dry_bed() {
+        let mut solver = central_upwind_solver(21, TopographyType::Flat);
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = if solver.mesh.triangles[i].centroid.0 < 5.0 {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        let initial_mass = solver.compute_total_mass();
+
+        while solver.time < 0.5 {
+            solver.step();
+            assert!(solver.state.h.iter().all(|h| *h >= 0.0 && h.is_finite()));
+        }
+
+        let mass_error = (solver.compute_total_mass() - initial_mass).abs() / initial_mass;
+        assert!(mass_error < 1e-10, "Mass error {}", mass_error);
+
+        // The front has advanced into the dry region
+        let wet_downstream = solver
+            .mesh
+            .triangles
+            .iter()
+            .zip(&solver.state.h)
+            .any(|(tri, h)| tri.centroid.0 > 6.0 && *h > 1e-3);
+        assert!(wet_downstream);
+    }
+
+    #[test]
+    fn test_limiter_bounds_reconstruction() {
+        let solver = central_upwind_solver(11, TopographyType::Flat);
+        let field: Vec<f64> = solver
+            .mesh
+            .triangles
+            .iter()
+            .map(|t| if t.centroid.0 < 5.0 { 1.0 } else { 0.0 })
+            .collect();
+
+        let ghost_field: Vec<f64> = solver.mesh.ghosts.iter().map(|g| field[g.cell]).collect();
+        for i in 0..field.len() {
+            let ghosts = std::array::from_fn(|k| solver.mesh.ghost(i, k));
+            let values = solver.limited_edge_values(&field, &ghost_field, ghosts, i);
+            for v in values {
+                assert!((-1e-12..=1.0 + 1e-12).contains(&v));
+            }
+        }
+    }
+
+    #[test]
+    fn test_wall_ghosts_shape_boundary_reconstruction() {
+        // Flow slowing down towards the eastern wall: the mirrored ghost
+        // velocity bends the reconstruction further towards zero normal
+        // velocity at the wall than a zero-gradient boundary
+        let mut solver = central_upwind_solver(11, TopographyType::Flat);
+        let u: Vec<f64> = solver
+            .mesh
+            .triangles
+            .iter()
+            .map(|tri| 0.1 + 0.05 * (10.0 - tri.centroid.0))
+            .collect();
+        solver.state.h.fill(1.0);
+        solver.state.hu = u.clone();
+        let ghosts = solver.fill_ghosts(&solver.state);
+        let ghost_u: Vec<f64> = ghosts.iter().map(|s| s.u).collect();
+        let mirror_free: Vec<f64> = solver.mesh.ghosts.iter().map(|g| u[g.cell]).collect();
+        let mut walls = 0;
+        for (g, ghost) in solver.mesh.ghosts.iter().enumerate() {
+            if solver.mesh.edges[ghost.edge].normal.0 < 0.99 {
+                continue;
+            }
+            assert!((ghosts[g].u + u[ghost.cell]).abs() < 1e-12);
+            let sides = std::array::from_fn(|k| solver.mesh.ghost(ghost.cell, k));
+            let wall = solver.limited_edge_values(&u, &ghost_u, sides, ghost.cell)[ghost.side];
+            let zero_gradient =
+                solver.limited_edge_values(&u, &mirror_free, sides, ghost.cell)[ghost.side];
+            assert!(wall < zero_gradient - 1e-3, "{} {}", wall, zero_gradient);
+            assert!(wall >= -u[ghost.cell]);
+            walls += 1;
+        }
+        assert_eq!(walls, 10);
+    }
+}
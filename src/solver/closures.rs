@@ -0,0 +1,416 @@
+/// Pluggable edge fluxes and boundary closures
+/// With `FluxScheme::Rusanov` the residual evaluates
+/// `ShallowWaterSolver::numerical_flux` on every edge. The state outside a
+/// boundary edge is the ghost state returned by
+/// `ShallowWaterSolver::boundary_closure`, which the central-upwind scheme
+/// uses as well, unless the closure prescribes the boundary flux itself:
+/// closed walls pass no mass and only the pressure of the wall depth from
+/// the exact reflection problem. Downstream crates can implement either
+/// trait for experimental Riemann solvers or boundary conditions.
+use super::{PhysicalConstants, ShallowWaterSolver, State};
+use crate::mesh::Edge;
+use rayon::prelude::*;
+
+/// Names of the closures set by `ShallowWaterSolver::new`
+const DEFAULT_FLUX: &str = "rusanov";
+const DEFAULT_BOUNDARY: &str = "wall";
+
+/// State on one side of an edge
+/// `u` and `v` are zero in cells shallower than the dry tolerance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeState {
+    pub h: f64,
+    pub hu: f64,
+    pub hv: f64,
+    pub u: f64,
+    pub v: f64,
+}
+
+impl EdgeState {
+    /// State with depth `h` and velocity (u, v)
+    pub fn from_velocity(h: f64, u: f64, v: f64) -> Self {
+        EdgeState {
+            h,
+            hu: h * u,
+            hv: h * v,
+            u,
+            v,
+        }
+    }
+}
+
+/// Numerical flux across an edge
+pub trait NumericalFlux: Send + Sync {
+    /// Short name used in messages
+    fn name(&self) -> &str;
+
+    /// Flux (h, hu, hv) per unit length from `left` to `right` along the
+    /// unit normal `normal`
+    fn flux(
+        &self,
+        left: &EdgeState,
+        right: &EdgeState,
+        normal: (f64, f64),
+        constants: &PhysicalConstants,
+    ) -> (f64, f64, f64);
+}
+
+/// State outside a boundary edge
+pub trait BoundaryClosure: Send + Sync {
+    /// Short name used in messages
+    fn name(&self) -> &str;
+
+    /// Ghost state across boundary `edge` next to the interior state `inner`
+    fn ghost(&self, solver: &ShallowWaterSolver, edge: &Edge, inner: &EdgeState) -> EdgeState;
+
+    /// Flux (h, hu, hv) per unit length out through boundary `edge`, if the
+    /// closure prescribes it instead of a ghost state (`wall_flux` for
+    /// closed walls)
+    fn boundary_flux(
+        &self,
+        _solver: &ShallowWaterSolver,
+        _edge: &Edge,
+        _inner: &EdgeState,
+    ) -> Option<(f64, f64, f64)> {
+        None
+    }
+}
+
+/// Depth at a closed wall from the exact solution of the reflection
+/// problem: a rarefaction when the flow with normal velocity `un` leaves the
+/// wall, a shock when it runs into it
+pub fn wall_depth(h: f64, un: f64, gravity: f64) -> f64 {
+    if h <= 0.0 {
+        return 0.0;
+    }
+    let c = (gravity * h).sqrt() + 0.5 * un;
+    let mut depth = c.max(0.0).powi(2) / gravity;
+    if un <= 0.0 {
+        return depth;
+    }
+    // Reflected bore: (h* - h) sqrt(g/2 (1/h + 1/h*)) = un, which is
+    // increasing and concave in h*, so Newton's method converges from the
+    // two-rarefaction estimate
+    for _ in 0..20 {
+        let s = (0.5 * gravity * (1.0 / h + 1.0 / depth)).sqrt();
+        let residual = (depth - h) * s - un;
+        let slope = s - (depth - h) * gravity / (4.0 * s * depth * depth);
+        let next = (depth - residual / slope).max(h);
+        if (next - depth).abs() <= 1e-14 * depth {
+            return next;
+        }
+        depth = next;
+    }
+    depth
+}
+
+/// Flux through a closed wall: no mass, and the pressure ½ g h*² of the
+/// wall depth `wall_depth` along the normal
+pub fn wall_flux(
+    inner: &EdgeState,
+    normal: (f64, f64),
+    constants: &PhysicalConstants,
+) -> (f64, f64, f64) {
+    let un = inner.u * normal.0 + inner.v * normal.1;
+    let h = wall_depth(inner.h, un, constants.gravity);
+    let pressure = 0.5 * constants.gravity * h * h;
+    (0.0, pressure * normal.0, pressure * normal.1)
+}
+
+/// First-order Rusanov (local Lax-Friedrichs) flux
+pub struct RusanovFlux;
+
+impl NumericalFlux for RusanovFlux {
+    fn name(&self) -> &str {
+        DEFAULT_FLUX
+    }
+
+    fn flux(
+        &self,
+        left: &EdgeState,
+        right: &EdgeState,
+        normal: (f64, f64),
+        constants: &PhysicalConstants,
+    ) -> (f64, f64, f64) {
+        let (nx, ny) = normal;
+        let g = constants.gravity;
+        let EdgeState {
+            h: h_l,
+            hu: hu_l,
+            hv: hv_l,
+            u: u_l,
+            v: v_l,
+        } = *left;
+        let EdgeState {
+            h: h_r,
+            hu: hu_r,
+            hv: hv_r,
+            u: u_r,
+            v: v_r,
+        } = *right;
+
+        // Compute normal velocities
+        let un_l = u_l * nx + v_l * ny;
+        let un_r = u_r * nx + v_r * ny;
+
+        // Physical fluxes in normal direction
+        let f_h_l = hu_l * nx + hv_l * ny;
+        let f_hu_l = (hu_l * u_l + 0.5 * g * h_l * h_l) * nx + (hu_l * v_l) * ny;
+        let f_hv_l = (hv_l * u_l) * nx + (hv_l * v_l + 0.5 * g * h_l * h_l) * ny;
+
+        let f_h_r = hu_r * nx + hv_r * ny;
+        let f_hu_r = (hu_r * u_r + 0.5 * g * h_r * h_r) * nx + (hu_r * v_r) * ny;
+        let f_hv_r = (hv_r * u_r) * nx + (hv_r * v_r + 0.5 * g * h_r * h_r) * ny;
+
+        // Wave speeds
+        let c_l = (g * h_l).sqrt();
+        let c_r = (g * h_r).sqrt();
+        let s_max = (un_l.abs() + c_l).max(un_r.abs() + c_r);
+
+        // Lax-Friedrichs flux
+        let flux_h = 0.5 * (f_h_l + f_h_r - s_max * (h_r - h_l));
+        let flux_hu = 0.5 * (f_hu_l + f_hu_r - s_max * (hu_r - hu_l));
+        let flux_hv = 0.5 * (f_hv_l + f_hv_r - s_max * (hv_r - hv_l));
+
+        (flux_h, flux_hu, flux_hv)
+    }
+}
+
+/// Reflective wall: the flux is `wall_flux`; the ghost state has the same
+/// depth and the normal velocity mirrored
+pub struct ReflectiveWall;
+
+impl BoundaryClosure for ReflectiveWall {
+    fn name(&self) -> &str {
+        DEFAULT_BOUNDARY
+    }
+
+    fn ghost(&self, _solver: &ShallowWaterSolver, edge: &Edge, inner: &EdgeState) -> EdgeState {
+        let (nx, ny) = edge.normal;
+        let u_normal = inner.u * nx + inner.v * ny;
+        EdgeState::from_velocity(
+            inner.h,
+            inner.u - 2.0 * u_normal * nx,
+            inner.v - 2.0 * u_normal * ny,
+        )
+    }
+
+    fn boundary_flux(
+        &self,
+        solver: &ShallowWaterSolver,
+        edge: &Edge,
+        inner: &EdgeState,
+    ) -> Option<(f64, f64, f64)> {
+        Some(wall_flux(inner, edge.normal, &solver.constants))
+    }
+}
+
+/// Transmissive (zero-gradient) boundary: waves leave the domain
+pub struct Transmissive;
+
+impl BoundaryClosure for Transmissive {
+    fn name(&self) -> &str {
+        "transmissive"
+    }
+
+    fn ghost(&self, _solver: &ShallowWaterSolver, _edge: &Edge, inner: &EdgeState) -> EdgeState {
+        *inner
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Whether the Rusanov flux and wall boundaries are in use, which the
+    /// semi-implicit, single-precision and GPU paths hard-code
+    pub fn has_default_closures(&self) -> bool {
+        self.numerical_flux.name() == DEFAULT_FLUX
+            && self.boundary_closure.name() == DEFAULT_BOUNDARY
+    }
+
+    /// Whether the boundary closure is a closed wall, so dry boundary cells
+    /// stay dry
+    pub(super) fn has_closed_boundary(&self) -> bool {
+        self.boundary_closure.name() == DEFAULT_BOUNDARY
+    }
+
+    /// States of the mesh's ghost cells, filled by the boundary closure from
+    /// the interior cell states of `state`
+    pub fn fill_ghosts(&self, state: &State) -> Vec<EdgeState> {
+        self.fill_ghosts_with(|g| self.edge_state(state, self.mesh.ghosts[g].cell))
+    }
+
+    /// States of the mesh's ghost cells, filled by the boundary closure from
+    /// the interior state `inner(g)` of each ghost cell g
+    pub(super) fn fill_ghosts_with<F>(&self, inner: F) -> Vec<EdgeState>
+    where
+        F: Fn(usize) -> EdgeState + Sync,
+    {
+        self.mesh
+            .ghosts
+            .par_iter()
+            .enumerate()
+            .map(|(g, ghost)| {
+                self.boundary_closure
+                    .ghost(self, &self.mesh.edges[ghost.edge], &inner(g))
+            })
+            .collect()
+    }
+
+    /// Flux out through boundary `edge` from the interior state `inner`:
+    /// the closure's own flux, or the numerical flux against its ghost state
+    pub(super) fn boundary_edge_flux(&self, edge: &Edge, inner: &EdgeState) -> (f64, f64, f64) {
+        self.boundary_closure
+            .boundary_flux(self, edge, inner)
+            .unwrap_or_else(|| {
+                let ghost = self.boundary_closure.ghost(self, edge, inner);
+                self.numerical_flux
+                    .flux(inner, &ghost, edge.normal, &self.constants)
+            })
+    }
+
+    /// Edge state of cell `i`
+    pub(super) fn edge_state(&self, state: &State, i: usize) -> EdgeState {
+        let (u, v) = self.velocity(state, i);
+        EdgeState {
+            h: state.h[i],
+            hu: state.hu[i],
+            hv: state.hv[i],
+            u,
+            v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn dam_break() -> ShallowWaterSolver {
+        let mesh = TriangularMesh::new_rectangular(12, 4, 12.0, 4.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_dam_break(6.0);
+        solver
+    }
+
+    fn volume(solver: &ShallowWaterSolver) -> f64 {
+        (0..solver.mesh.triangles.len())
+            .map(|i| solver.state.h[i] * solver.mesh.triangles[i].area)
+            .sum()
+    }
+
+    /// Rusanov flux that counts its evaluations
+    struct Counting(Arc<AtomicUsize>);
+
+    impl NumericalFlux for Counting {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn flux(
+            &self,
+            left: &EdgeState,
+            right: &EdgeState,
+            normal: (f64, f64),
+            constants: &PhysicalConstants,
+        ) -> (f64, f64, f64) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            RusanovFlux.flux(left, right, normal, constants)
+        }
+    }
+
+    #[test]
+    fn test_injected_flux_is_used() {
+        let mut reference = dam_break();
+        let mut solver = dam_break();
+        let count = Arc::new(AtomicUsize::new(0));
+        solver.numerical_flux = Box::new(Counting(count.clone()));
+        assert!(!solver.has_default_closures());
+        assert!(solver.single_precision_unsupported().is_some());
+        for _ in 0..5 {
+            reference.step();
+            solver.step();
+        }
+        // Every interior edge per stage; walls take the wall flux
+        let interior = solver
+            .mesh
+            .edges
+            .iter()
+            .filter(|edge| edge.right_triangle.is_some())
+            .count();
+        assert!(count.load(Ordering::Relaxed) >= 10 * interior);
+        assert_eq!(solver.state.h, reference.state.h);
+        assert_eq!(solver.state.hu, reference.state.hu);
+    }
+
+    #[test]
+    fn test_wall_depth() {
+        let g = 9.81;
+        assert_eq!(wall_depth(2.0, 0.0, g), 2.0);
+        assert_eq!(wall_depth(0.0, 1.0, g), 0.0);
+        // Rarefaction: the Riemann invariant u + 2c carries over to rest
+        let h = wall_depth(2.0, -1.0, g);
+        assert!(((g * h).sqrt() - ((g * 2.0).sqrt() - 0.5)).abs() < 1e-12);
+        assert_eq!(wall_depth(0.1, -5.0, g), 0.0);
+        // Shock: the reflected bore brings the inflow to rest
+        for un in [0.1, 3.0, 30.0] {
+            let h = wall_depth(1.0, un, g);
+            let jump = (h - 1.0) * (0.5 * g * (1.0 + 1.0 / h)).sqrt();
+            assert!((jump - un).abs() < 1e-10 * un, "{} {}", un, h);
+        }
+    }
+
+    #[test]
+    fn test_supercritical_flow_into_corner() {
+        // Froude number 4 flow along the diagonal of a closed basin whose
+        // cells mirror across it: no mass is lost and the corner keeps the
+        // symmetry
+        let mesh = TriangularMesh::new_rectangular(9, 9, 8.0, 8.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.3, FrictionLaw::None);
+        solver.flux_scheme = crate::solver::FluxScheme::CentralUpwind;
+        let speed = 4.0 * (solver.constants.gravity * 0.5f64).sqrt() / 2f64.sqrt();
+        for i in 0..solver.mesh.triangles.len() {
+            solver.state.h[i] = 0.5;
+            solver.state.hu[i] = 0.5 * speed;
+            solver.state.hv[i] = 0.5 * speed;
+        }
+        let mirror: Vec<usize> = solver
+            .mesh
+            .triangles
+            .iter()
+            .map(|tri| {
+                let (x, y) = tri.centroid;
+                solver.mesh.triangles.iter().position(|other| {
+                    (other.centroid.0 - y).abs() < 1e-9 && (other.centroid.1 - x).abs() < 1e-9
+                })
+            })
+            .collect::<Option<_>>()
+            .expect("mesh mirrors across the diagonal");
+        let before = volume(&solver);
+        for _ in 0..40 {
+            solver.step();
+        }
+        assert!((volume(&solver) - before).abs() < 1e-9 * before);
+        for (i, &j) in mirror.iter().enumerate() {
+            assert!(solver.state.h[i].is_finite() && solver.state.h[i] >= 0.0);
+            assert!((solver.state.h[i] - solver.state.h[j]).abs() < 1e-9);
+            assert!((solver.state.hu[i] - solver.state.hv[j]).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_transmissive_boundary_lets_water_out() {
+        let mut walled = dam_break();
+        let mut open = dam_break();
+        open.boundary_closure = Box::new(Transmissive);
+        let before = volume(&walled);
+        for _ in 0..200 {
+            walled.step();
+            open.step();
+        }
+        assert!((volume(&walled) - before).abs() < 1e-9 * before);
+        assert!(volume(&open) < before - 1.0);
+    }
+}
@@ -0,0 +1,270 @@
+/// Friction laws by zone
+/// Each cell carries its own resistance law, so a channel can follow Chezy
+/// while the floodplain follows Manning and a landslide source area Voellmy.
+/// Zones are painted in order over the global law: first the classes of a
+/// raster through a lookup table (the class at the cell centroid, as laws
+/// cannot be averaged), then polygons, later ones over earlier ones. Cells
+/// with a yield law (Voellmy, Bingham) are stopped by the split yield step,
+/// the others by the friction source term, as with a single global law. A
+/// Manning's n field (land use, ice) replaces n in the Manning cells.
+use super::{FrictionLaw, ShallowWaterSolver};
+use crate::mesh::{point_in_polygon, TriangularMesh};
+use crate::raster::RasterGrid;
+use std::collections::BTreeMap;
+
+/// Resistance law of every cell
+#[derive(Debug, Clone)]
+pub struct FrictionZones {
+    pub laws: Vec<FrictionLaw>,
+}
+
+/// Parse a law "none", "manning:N", "chezy:C", "voellmy:MU:XI" or
+/// "bingham:TAU_Y:MU_B"
+pub fn parse_friction_law(spec: &str) -> Result<FrictionLaw, String> {
+    let mut parts = spec.trim().split(':');
+    let name = parts.next().unwrap_or("").trim().to_lowercase();
+    let values: Vec<f64> = parts
+        .map(|p| p.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid friction law '{}'", spec))?;
+    if values.iter().any(|v| v.is_nan() || *v < 0.0) {
+        return Err(format!(
+            "friction parameters must not be negative in '{}'",
+            spec
+        ));
+    }
+    let positive = |v: f64| match v > 0.0 && v.is_finite() {
+        true => Ok(v),
+        false => Err(format!(
+            "friction coefficient must be positive in '{}'",
+            spec
+        )),
+    };
+    match (name.as_str(), values.as_slice()) {
+        ("none", []) => Ok(FrictionLaw::None),
+        ("manning", &[n]) => Ok(FrictionLaw::Manning {
+            coefficient: positive(n)?,
+        }),
+        ("chezy", &[c]) => Ok(FrictionLaw::Chezy {
+            coefficient: positive(c)?,
+        }),
+        ("voellmy", &[mu, xi]) => Ok(FrictionLaw::Voellmy {
+            mu,
+            xi: positive(xi)?,
+        }),
+        ("bingham", &[yield_stress, viscosity]) => Ok(FrictionLaw::Bingham {
+            yield_stress,
+            viscosity: positive(viscosity)?,
+        }),
+        _ => Err(format!(
+            "invalid friction law '{}' (none, manning:N, chezy:C, voellmy:MU:XI or bingham:TAU_Y:MU_B)",
+            spec
+        )),
+    }
+}
+
+/// Parse a JSON object mapping raster classes to laws, e.g.
+/// `{"1": "manning:0.035", "2": "chezy:40"}`
+pub fn parse_friction_table(json: &str) -> Result<BTreeMap<i64, FrictionLaw>, String> {
+    let entries: BTreeMap<String, String> =
+        serde_json::from_str(json).map_err(|e| format!("invalid friction table: {}", e))?;
+    let mut classes = BTreeMap::new();
+    for (class, law) in entries {
+        let code: i64 = class
+            .trim()
+            .parse()
+            .map_err(|_| format!("friction class '{}' is not an integer", class))?;
+        classes.insert(code, parse_friction_law(&law)?);
+    }
+    if classes.is_empty() {
+        return Err("friction table is empty".to_string());
+    }
+    Ok(classes)
+}
+
+impl FrictionZones {
+    /// Every cell of `mesh` under `law`
+    pub fn uniform(mesh: &TriangularMesh, law: FrictionLaw) -> Self {
+        FrictionZones {
+            laws: vec![law; mesh.triangles.len()],
+        }
+    }
+
+    /// Give each cell the law of the raster class (row-major codes on
+    /// `grid`) at its centroid; cells outside the raster, on NODATA or on
+    /// classes missing from `table` keep their law. Returns the number of
+    /// cells of each missing class.
+    pub fn assign_raster(
+        &mut self,
+        mesh: &TriangularMesh,
+        grid: &RasterGrid,
+        classes: &[f64],
+        table: &BTreeMap<i64, FrictionLaw>,
+    ) -> BTreeMap<i64, usize> {
+        let mut unmatched = BTreeMap::new();
+        for (law, tri) in self.laws.iter_mut().zip(&mesh.triangles) {
+            let class = grid
+                .pixel_at(tri.centroid.0, tri.centroid.1)
+                .map(|k| classes[k])
+                .filter(|c| !c.is_nan())
+                .map(|c| c.round() as i64);
+            if let Some(class) = class {
+                match table.get(&class) {
+                    Some(&zone_law) => *law = zone_law,
+                    None => *unmatched.entry(class).or_insert(0) += 1,
+                }
+            }
+        }
+        unmatched
+    }
+
+    /// Give the cells whose centroid lies in `polygon` the law `law`;
+    /// returns their number
+    pub fn assign_polygon(
+        &mut self,
+        mesh: &TriangularMesh,
+        polygon: &[(f64, f64)],
+        law: FrictionLaw,
+    ) -> usize {
+        let mut count = 0;
+        for (cell_law, tri) in self.laws.iter_mut().zip(&mesh.triangles) {
+            if point_in_polygon(tri.centroid, polygon) {
+                *cell_law = law;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Area under each kind of law ("none", "manning", ...), in first-seen order
+    pub fn areas(&self, mesh: &TriangularMesh) -> Vec<(&'static str, f64)> {
+        let mut areas: Vec<(&'static str, f64)> = Vec::new();
+        for (law, tri) in self.laws.iter().zip(&mesh.triangles) {
+            let name = law.name();
+            match areas.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, area)) => *area += tri.area,
+                None => areas.push((name, tri.area)),
+            }
+        }
+        areas
+    }
+}
+
+impl FrictionLaw {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrictionLaw::None => "none",
+            FrictionLaw::Manning { .. } => "manning",
+            FrictionLaw::Chezy { .. } => "chezy",
+            FrictionLaw::Voellmy { .. } => "voellmy",
+            FrictionLaw::Bingham { .. } => "bingham",
+        }
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Use a friction law per cell instead of the global one (not in single
+    /// precision or on the GPU)
+    pub fn set_friction_zones(&mut self, zones: FrictionZones) -> Result<(), String> {
+        if zones.laws.len() != self.mesh.triangles.len() {
+            return Err(format!(
+                "friction zones have {} laws for {} cells",
+                zones.laws.len(),
+                self.mesh.triangles.len()
+            ));
+        }
+        if self.precision != super::Precision::Double {
+            return Err("friction zones require double precision".to_string());
+        }
+        #[cfg(feature = "gpu")]
+        if self.gpu.is_some() {
+            return Err("friction zones are not available on the GPU".to_string());
+        }
+        self.friction_zones = Some(zones);
+        Ok(())
+    }
+
+    pub fn friction_zones(&self) -> Option<&FrictionZones> {
+        self.friction_zones.as_ref()
+    }
+
+    /// Resistance law of cell `i`
+    pub fn friction_law(&self, i: usize) -> FrictionLaw {
+        match &self.friction_zones {
+            Some(zones) => zones.laws[i],
+            None => self.friction,
+        }
+    }
+
+    /// Whether any cell has a yield law, which needs the split yield step
+    pub(super) fn has_yield_friction(&self) -> bool {
+        match &self.friction_zones {
+            Some(zones) => zones.laws.iter().any(FrictionLaw::has_yield_term),
+            None => self.friction.has_yield_term(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+
+    #[test]
+    fn test_parse_friction_laws() {
+        assert!(matches!(
+            parse_friction_law("Chezy:40"),
+            Ok(FrictionLaw::Chezy { coefficient }) if coefficient == 40.0
+        ));
+        assert!(matches!(
+            parse_friction_law("voellmy:0.2:500"),
+            Ok(FrictionLaw::Voellmy { mu, xi }) if mu == 0.2 && xi == 500.0
+        ));
+        assert!(matches!(parse_friction_law("none"), Ok(FrictionLaw::None)));
+        assert!(parse_friction_law("manning").is_err());
+        assert!(parse_friction_law("manning:0").is_err());
+        assert!(parse_friction_law("chezy:40:1").is_err());
+        let table = parse_friction_table(r#"{"1": "manning:0.035", "7": "bingham:50:5"}"#);
+        assert_eq!(table.unwrap().len(), 2);
+        assert!(parse_friction_table(r#"{"a": "none"}"#).is_err());
+    }
+
+    #[test]
+    fn test_zones_stop_only_the_yield_cells() {
+        // Uniform flow over a flat bed: Manning on the left half, Voellmy
+        // with a Coulomb term too strong to move on the right half
+        let mesh = TriangularMesh::new_rectangular(20, 4, 20.0, 4.0, TopographyType::Flat);
+        let mut solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Manning { coefficient: 0.03 });
+        let mut zones = FrictionZones::uniform(&solver.mesh, solver.friction);
+        let right = [(10.0, -1.0), (21.0, -1.0), (21.0, 5.0), (10.0, 5.0)];
+        let claimed = zones.assign_polygon(
+            &solver.mesh,
+            &right,
+            FrictionLaw::Voellmy { mu: 2.0, xi: 500.0 },
+        );
+        assert_eq!(claimed, solver.mesh.triangles.len() / 2);
+        solver.set_friction_zones(zones).unwrap();
+        let areas = solver.friction_zones().unwrap().areas(&solver.mesh);
+        assert_eq!(areas.len(), 2);
+        assert!((areas[0].1 - 40.0).abs() < 1e-9 && (areas[1].1 - 40.0).abs() < 1e-9);
+
+        for i in 0..solver.mesh.triangles.len() {
+            solver.state.h[i] = 1.0;
+            solver.state.hu[i] = 0.5;
+        }
+        solver.step();
+        for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+            let interior = (tri.centroid.0 - 10.0).abs() > 3.0 && tri.centroid.0 > 1.0;
+            if !interior || tri.centroid.0 > 19.0 {
+                continue;
+            }
+            if tri.centroid.0 > 10.0 {
+                // mu g h dt exceeds the momentum within the first step
+                assert_eq!(solver.state.hu[i], 0.0);
+            } else {
+                assert!(solver.state.hu[i] > 0.49 && solver.state.hu[i] < 0.5);
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+/// Initial velocity fields
+/// The built-in initial conditions start from rest; a velocity field sets
+/// hu, hv = h (u, v) on top of the depth they define. Distances are in metres
+/// also on geographic meshes, where (u, v) are the east and north components.
+use super::ShallowWaterSolver;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum VelocityField {
+    /// Uniform current (m/s)
+    Uniform { u: f64, v: f64 },
+    /// Counter-clockwise Rankine vortex: solid-body rotation inside `radius`
+    /// (m) with `speed` (m/s) at its edge, irrotational decay as 1/r outside
+    Vortex {
+        center: (f64, f64),
+        radius: f64,
+        speed: f64,
+    },
+    /// Bickley jet along x centred on y = `axis`: u = speed sech²(d / width),
+    /// with d the distance (m) from the axis
+    Jet { axis: f64, width: f64, speed: f64 },
+}
+
+impl VelocityField {
+    /// Parse "uniform:U,V", "vortex:X,Y,RADIUS,SPEED" or "jet:Y,WIDTH,SPEED"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (kind, values) = spec.trim().split_once(':').unwrap_or((spec.trim(), ""));
+        let values: Vec<f64> = values
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("invalid velocity field '{}'", spec))?;
+        let field = match (kind, &values[..]) {
+            ("uniform", &[u, v]) => VelocityField::Uniform { u, v },
+            ("vortex", &[x, y, radius, speed]) => VelocityField::Vortex {
+                center: (x, y),
+                radius,
+                speed,
+            },
+            ("jet", &[axis, width, speed]) => VelocityField::Jet { axis, width, speed },
+            _ => {
+                return Err(format!(
+                    "invalid velocity field '{}' (expected uniform:U,V, vortex:X,Y,RADIUS,SPEED \
+                     or jet:Y,WIDTH,SPEED)",
+                    spec
+                ))
+            }
+        };
+        field.validate()?;
+        Ok(field)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let length = match *self {
+            VelocityField::Uniform { .. } => return Ok(()),
+            VelocityField::Vortex { radius, .. } => radius,
+            VelocityField::Jet { width, .. } => width,
+        };
+        if length > 0.0 && length.is_finite() {
+            Ok(())
+        } else {
+            Err(format!(
+                "velocity field length scale must be positive, got {}",
+                length
+            ))
+        }
+    }
+
+    /// Velocity (u, v) at offset `(dx, dy)` (m) from the field's reference
+    /// point: the vortex centre, or a point on the jet axis
+    fn velocity(&self, (dx, dy): (f64, f64)) -> (f64, f64) {
+        match *self {
+            VelocityField::Uniform { u, v } => (u, v),
+            VelocityField::Vortex { radius, speed, .. } => {
+                let r = dx.hypot(dy);
+                if r == 0.0 {
+                    return (0.0, 0.0);
+                }
+                let swirl = if r < radius {
+                    speed * r / radius
+                } else {
+                    speed * radius / r
+                };
+                (-swirl * dy / r, swirl * dx / r)
+            }
+            VelocityField::Jet { width, speed, .. } => {
+                let sech = 1.0 / (dy / width).cosh();
+                (speed * sech * sech, 0.0)
+            }
+        }
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Give the water the velocity `field`; dry cells stay at rest
+    pub fn set_velocity(&mut self, field: &VelocityField) {
+        let dry = self.constants.dry_tolerance;
+        for (i, tri) in self.mesh.triangles.iter().enumerate() {
+            let reference = match *field {
+                VelocityField::Uniform { .. } => tri.centroid,
+                VelocityField::Vortex { center, .. } => center,
+                VelocityField::Jet { axis, .. } => (tri.centroid.0, axis),
+            };
+            let offset = self.mesh.coordinate_system.delta(reference, tri.centroid);
+            let (u, v) = field.velocity(offset);
+            let h = self.state.h[i];
+            let (hu, hv) = if h > dry { (h * u, h * v) } else { (0.0, 0.0) };
+            self.state.hu[i] = hu;
+            self.state.hv[i] = hv;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_parse_velocity_fields() {
+        assert_eq!(
+            VelocityField::parse("uniform:0.5,-0.1").unwrap(),
+            VelocityField::Uniform { u: 0.5, v: -0.1 }
+        );
+        assert_eq!(
+            VelocityField::parse("vortex: 5, 5, 1, 0.3").unwrap(),
+            VelocityField::Vortex {
+                center: (5.0, 5.0),
+                radius: 1.0,
+                speed: 0.3
+            }
+        );
+        for bad in ["uniform:1", "jet:5,0,1", "spiral:1,2", "vortex:a,b,c,d"] {
+            assert!(VelocityField::parse(bad).is_err(), "{}", bad);
+        }
+        let json = r#"{"type": "jet", "axis": 5.0, "width": 1.0, "speed": 2.0}"#;
+        let field: VelocityField = serde_json::from_str(json).unwrap();
+        assert_eq!(field, VelocityField::parse("jet:5,1,2").unwrap());
+    }
+
+    #[test]
+    fn test_rankine_vortex() {
+        let mesh = TriangularMesh::new_rectangular(41, 41, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.fill(2.0);
+        solver.state.h[0] = 0.0;
+        let (radius, speed) = (2.0, 0.4);
+        solver.set_velocity(&VelocityField::Vortex {
+            center: (5.0, 5.0),
+            radius,
+            speed,
+        });
+        assert_eq!((solver.state.hu[0], solver.state.hv[0]), (0.0, 0.0));
+        for (i, tri) in solver.mesh.triangles.iter().enumerate().skip(1) {
+            let (dx, dy) = (tri.centroid.0 - 5.0, tri.centroid.1 - 5.0);
+            let (u, v) = (solver.state.hu[i] / 2.0, solver.state.hv[i] / 2.0);
+            let r = dx.hypot(dy);
+            let expected = if r < radius {
+                speed * r / radius
+            } else {
+                speed * radius / r
+            };
+            // Purely azimuthal and counter-clockwise
+            assert!((u.hypot(v) - expected).abs() < 1e-12);
+            assert!((u * dx + v * dy).abs() < 1e-12);
+            assert!(dx * v - dy * u >= 0.0);
+        }
+    }
+}
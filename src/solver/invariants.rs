@@ -0,0 +1,237 @@
+/// Invariant checks for debugging
+/// Properties every valid state and scheme keep, checked in order so that
+/// the first broken one names the cell or edge where an instability starts:
+/// finite, non-negative depths and finite momenta; velocities below a bound;
+/// numerical fluxes that are antisymmetric across interior edges (the flux
+/// from one side equals minus the flux from the other, which conservation
+/// rests on); and a finite spatial residual. The fluxes are those of the
+/// active scheme on the states it passes them: cell values, reconstructed
+/// central-upwind edge values or DG traces. Costs about one extra residual
+/// evaluation per check.
+use super::{Discretization, EdgeState, FluxScheme, ShallowWaterSolver, State};
+use rayon::prelude::*;
+
+/// Relative tolerance on the sum of the two one-sided fluxes of an edge
+const FLUX_TOLERANCE: f64 = 1e-9;
+
+impl ShallowWaterSolver {
+    /// First broken invariant of the current state, if any
+    pub fn check_invariants(&self, max_velocity: f64) -> Result<(), String> {
+        let state = &self.state;
+        let cell = |i: usize| {
+            let (x, y) = self.mesh.triangles[i].centroid;
+            format!("cell {} at ({:.6}, {:.6})", i, x, y)
+        };
+
+        let broken = (0..state.h.len()).into_par_iter().find_first(|&i| {
+            !(state.h[i] >= 0.0 && state.hu[i].is_finite() && state.hv[i].is_finite())
+                || state.h[i].is_infinite()
+        });
+        if let Some(i) = broken {
+            return Err(format!(
+                "{}: invalid state h = {}, hu = {}, hv = {}",
+                cell(i),
+                state.h[i],
+                state.hu[i],
+                state.hv[i]
+            ));
+        }
+
+        let speed = |i: usize| {
+            let (u, v) = self.velocity(state, i);
+            u.hypot(v)
+        };
+        if let Some(i) = (0..state.h.len())
+            .into_par_iter()
+            .find_first(|&i| speed(i) > max_velocity)
+        {
+            return Err(format!(
+                "{}: velocity {:.6e} m/s exceeds {} m/s (h = {:.6e} m)",
+                cell(i),
+                speed(i),
+                max_velocity,
+                state.h[i]
+            ));
+        }
+
+        let traces = self.edge_traces(state);
+        let one_sided = |e: usize| {
+            let (nx, ny) = self.mesh.edges[e].normal;
+            traces[e].iter().find_map(|(left, right)| {
+                let forward = self.edge_flux(left, right, (nx, ny));
+                let backward = self.edge_flux(right, left, (-nx, -ny));
+                let scale = 1.0 + forward.0.abs() + forward.1.abs() + forward.2.abs();
+                let sum = (forward.0 + backward.0)
+                    .abs()
+                    .max((forward.1 + backward.1).abs())
+                    .max((forward.2 + backward.2).abs());
+                (sum.is_nan() || sum > FLUX_TOLERANCE * scale).then_some((forward, backward))
+            })
+        };
+        if let Some(e) = (0..self.mesh.edges.len())
+            .into_par_iter()
+            .find_first(|&e| one_sided(e).is_some())
+        {
+            let edge = &self.mesh.edges[e];
+            let (forward, backward) = one_sided(e).expect("edge is broken");
+            let (a, b) = (
+                &self.mesh.nodes[edge.nodes[0]],
+                &self.mesh.nodes[edge.nodes[1]],
+            );
+            return Err(format!(
+                "edge {} between cells {} and {} at ({:.6}, {:.6}): flux {:?} is not the \
+                 negative of the reverse flux {:?}",
+                e,
+                edge.left_triangle,
+                edge.right_triangle.expect("interior edge"),
+                0.5 * (a.x + b.x),
+                0.5 * (a.y + b.y),
+                forward,
+                backward
+            ));
+        }
+
+        let residual = self.compute_residual(state);
+        if let Some(i) = (0..state.h.len()).into_par_iter().find_first(|&i| {
+            !(residual.h[i].is_finite() && residual.hu[i].is_finite() && residual.hv[i].is_finite())
+        }) {
+            return Err(format!(
+                "{}: non-finite residual ({}, {}, {})",
+                cell(i),
+                residual.h[i],
+                residual.hu[i],
+                residual.hv[i]
+            ));
+        }
+        Ok(())
+    }
+
+    /// States on the left and right of every interior edge that the active
+    /// scheme passes to its edge flux (none on boundary edges)
+    fn edge_traces(&self, state: &State) -> Vec<Vec<(EdgeState, EdgeState)>> {
+        match (self.discretization, self.flux_scheme) {
+            (Discretization::Dg1, _) => self.dg_edge_traces(state),
+            (_, FluxScheme::CentralUpwind) => self
+                .central_upwind_edge_points(state)
+                .into_iter()
+                .map(|points| {
+                    let state = |(h, u, v)| EdgeState::from_velocity(h, u, v);
+                    points
+                        .map(|[left, right]| vec![(state(left), state(right))])
+                        .unwrap_or_default()
+                })
+                .collect(),
+            (_, FluxScheme::Rusanov) => self
+                .mesh
+                .edges
+                .iter()
+                .map(|edge| match edge.right_triangle {
+                    Some(right) => vec![(
+                        self.edge_state(state, edge.left_triangle),
+                        self.edge_state(state, right),
+                    )],
+                    None => Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Edge flux of the active scheme
+    fn edge_flux(
+        &self,
+        left: &EdgeState,
+        right: &EdgeState,
+        normal: (f64, f64),
+    ) -> (f64, f64, f64) {
+        match self.flux_scheme {
+            FluxScheme::Rusanov => self
+                .numerical_flux
+                .flux(left, right, normal, &self.constants),
+            FluxScheme::CentralUpwind => self.central_upwind_flux(
+                normal,
+                (left.h, left.u, left.v),
+                (right.h, right.u, right.v),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::{EdgeState, FrictionLaw, NumericalFlux, PhysicalConstants};
+
+    /// Flux that leaks mass: the same from both sides
+    struct Leaky;
+
+    impl NumericalFlux for Leaky {
+        fn name(&self) -> &str {
+            "leaky"
+        }
+
+        fn flux(
+            &self,
+            left: &EdgeState,
+            right: &EdgeState,
+            _normal: (f64, f64),
+            _constants: &PhysicalConstants,
+        ) -> (f64, f64, f64) {
+            (0.1 * (left.h + right.h), 0.0, 0.0)
+        }
+    }
+
+    #[test]
+    fn test_invariants_pinpoint_the_first_violation() {
+        let mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_dam_break(5.0);
+        for _ in 0..10 {
+            solver.step();
+            solver.check_invariants(100.0).unwrap();
+        }
+
+        let mut broken = ShallowWaterSolver::new(solver.mesh.clone(), 0.45, FrictionLaw::None);
+        broken.state = solver.state.clone();
+        broken.state.hu[17] = 1e3 * broken.state.h[17];
+        let error = broken.check_invariants(100.0).unwrap_err();
+        assert!(error.starts_with("cell 17 "), "{}", error);
+        broken.state.h[12] = -1e-3;
+        let error = broken.check_invariants(100.0).unwrap_err();
+        assert!(error.starts_with("cell 12 "), "{}", error);
+
+        solver.numerical_flux = Box::new(Leaky);
+        let error = solver.check_invariants(100.0).unwrap_err();
+        let first_interior = solver
+            .mesh
+            .edges
+            .iter()
+            .position(|edge| edge.right_triangle.is_some())
+            .unwrap();
+        let expected = format!("edge {} between", first_interior);
+        assert!(error.starts_with(&expected), "{}", error);
+    }
+
+    #[test]
+    fn test_invariants_check_the_flux_of_the_active_scheme() {
+        let mesh = TriangularMesh::new_rectangular(11, 11, 10.0, 10.0, TopographyType::Flat);
+        let dam_break = |scheme: FluxScheme, discretization: Discretization| {
+            let mut solver = ShallowWaterSolver::new(mesh.clone(), 0.45, FrictionLaw::None);
+            solver.flux_scheme = scheme;
+            solver.set_discretization(discretization).unwrap();
+            solver.set_dam_break(5.0);
+            for _ in 0..10 {
+                solver.step();
+                solver.check_invariants(100.0).unwrap();
+            }
+            solver.numerical_flux = Box::new(Leaky);
+            solver.check_invariants(100.0)
+        };
+
+        // The central-upwind scheme has a flux of its own; DG passes its
+        // traces to the numerical flux
+        assert!(dam_break(FluxScheme::CentralUpwind, Discretization::FiniteVolume).is_ok());
+        let error = dam_break(FluxScheme::Rusanov, Discretization::Dg1).unwrap_err();
+        assert!(error.starts_with("edge "), "{}", error);
+    }
+}
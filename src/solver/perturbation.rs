@@ -0,0 +1,207 @@
+/// Random perturbations of the initial state
+/// Seeded noise superimposed on the initial depth and velocity spreads
+/// ensemble members and breaks the symmetry of idealized instability runs.
+/// The noise is a Gaussian random field of unit variance with a Gaussian
+/// correlation exp(-r²/2L²), drawn as a sum of random Fourier modes. It is a
+/// function of position (metres, from a sinusoidal projection on geographic
+/// meshes) and the seed only, so a seed gives the same perturbation on every
+/// run, thread count and mesh. A correlation length of zero gives
+/// independent values per cell instead. Only wet cells are perturbed; depths
+/// are kept non-negative.
+use super::ShallowWaterSolver;
+use crate::ensemble::SplitMix64;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Fourier modes per random field
+const MODES: usize = 256;
+
+/// Perturbation of the initial state
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Perturbation {
+    pub depth: f64,    // Standard deviation of the depth (m)
+    pub velocity: f64, // Standard deviation of each velocity component (m/s)
+    pub length: f64,   // Correlation length (m), 0 for independent cells
+    pub seed: u64,
+}
+
+impl Default for Perturbation {
+    fn default() -> Self {
+        Perturbation {
+            depth: 0.0,
+            velocity: 0.0,
+            length: 0.0,
+            seed: 1,
+        }
+    }
+}
+
+/// Gaussian random field of unit variance
+pub struct RandomField {
+    modes: Vec<((f64, f64), f64)>, // Wavenumber vector (rad/m) and phase
+    seed: u64,                     // For independent cells
+}
+
+impl RandomField {
+    pub fn new(seed: u64, length: f64) -> Self {
+        let mut rng = SplitMix64::new(seed);
+        let modes = if length > 0.0 {
+            (0..MODES)
+                .map(|_| {
+                    let k = (rng.next_normal() / length, rng.next_normal() / length);
+                    (k, 2.0 * std::f64::consts::PI * rng.next_f64())
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        RandomField { modes, seed }
+    }
+
+    /// Value at `p` (m), or of cell `cell` for independent cells
+    pub fn value(&self, cell: usize, p: (f64, f64)) -> f64 {
+        if self.modes.is_empty() {
+            let mut rng =
+                SplitMix64::new(self.seed ^ (cell as u64).wrapping_mul(0xD6E8_FEB8_6659_FD93));
+            return rng.next_normal();
+        }
+        let sum: f64 = self
+            .modes
+            .iter()
+            .map(|&((kx, ky), phase)| (kx * p.0 + ky * p.1 + phase).cos())
+            .sum();
+        sum * (2.0 / self.modes.len() as f64).sqrt()
+    }
+}
+
+impl Perturbation {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("depth amplitude", self.depth),
+            ("velocity amplitude", self.velocity),
+            ("correlation length", self.length),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(format!("perturbation {} must not be negative", name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.depth > 0.0 || self.velocity > 0.0
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Superimpose `perturbation` on the state; returns the volume it adds
+    pub fn perturb(&mut self, perturbation: &Perturbation) -> Result<f64, String> {
+        perturbation.validate()?;
+        let mut seeds = SplitMix64::new(perturbation.seed);
+        let [depth, u, v] =
+            [(); 3].map(|_| RandomField::new(seeds.next_u64(), perturbation.length));
+        let system = self.mesh.coordinate_system;
+        let dry = self.constants.dry_tolerance;
+        let before = self.compute_total_mass();
+        let state = &mut self.state;
+        (&mut state.h, &mut state.hu, &mut state.hv)
+            .into_par_iter()
+            .zip(&self.mesh.triangles)
+            .enumerate()
+            .filter(|(_, ((h, _, _), _))| **h > dry)
+            .for_each(|(i, ((h, hu, hv), tri))| {
+                let c = tri.centroid;
+                let p = system.delta_at((0.0, 0.0), c, c.1);
+                let (u0, v0) = (*hu / *h, *hv / *h);
+                *h = (*h + perturbation.depth * depth.value(i, p)).max(0.0);
+                if *h > dry {
+                    *hu = *h * (u0 + perturbation.velocity * u.value(i, p));
+                    *hv = *h * (v0 + perturbation.velocity * v.value(i, p));
+                } else {
+                    (*hu, *hv) = (0.0, 0.0);
+                }
+            });
+        Ok(self.compute_total_mass() - before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_field_statistics() {
+        // Over many points and seeds: unit variance, correlation exp(-1/2) at
+        // one correlation length
+        let length = 10.0;
+        let (mut variance, mut covariance, mut n) = (0.0, 0.0, 0.0);
+        for seed in 0..20 {
+            let field = RandomField::new(seed, length);
+            for k in 0..200 {
+                let p = (37.0 * k as f64, 11.0 * (k % 7) as f64);
+                let a = field.value(0, p);
+                variance += a * a;
+                covariance += a * field.value(0, (p.0 + length, p.1));
+                n += 1.0;
+            }
+        }
+        assert!((variance / n - 1.0).abs() < 0.1, "{}", variance / n);
+        assert!(
+            (covariance / n - (-0.5f64).exp()).abs() < 0.1,
+            "{}",
+            covariance / n
+        );
+
+        let field = RandomField::new(3, length);
+        assert_eq!(
+            field.value(0, (1.0, 2.0)),
+            RandomField::new(3, length).value(5, (1.0, 2.0))
+        );
+        let cells = RandomField::new(3, 0.0);
+        assert_ne!(cells.value(0, (0.0, 0.0)), cells.value(1, (0.0, 0.0)));
+        assert_eq!(
+            cells.value(1, (0.0, 0.0)),
+            RandomField::new(3, 0.0).value(1, (9.0, 9.0))
+        );
+    }
+
+    #[test]
+    fn test_perturb_wet_cells() {
+        let mesh = TriangularMesh::new_rectangular(21, 11, 20.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+            solver.state.h[i] = if tri.centroid.0 < 10.0 { 1.0 } else { 0.0 };
+        }
+        let mut twin = ShallowWaterSolver::new(solver.mesh.clone(), 0.45, FrictionLaw::None);
+        twin.state = solver.state.clone();
+        let perturbation = Perturbation {
+            depth: 0.01,
+            velocity: 0.05,
+            length: 2.0,
+            seed: 42,
+        };
+        let before = solver.compute_total_mass();
+        let added = solver.perturb(&perturbation).unwrap();
+        assert!((solver.compute_total_mass() - before - added).abs() < 1e-9);
+        for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+            if tri.centroid.0 > 10.0 {
+                assert_eq!(solver.state.h[i], 0.0);
+            } else {
+                assert!((solver.state.h[i] - 1.0).abs() < 0.06);
+                assert!(solver.state.hu[i] != 0.0 && solver.state.hv[i] != 0.0);
+            }
+        }
+        twin.perturb(&perturbation).unwrap();
+        assert_eq!(twin.state.h, solver.state.h);
+        assert_eq!(twin.state.hv, solver.state.hv);
+
+        let negative = Perturbation {
+            depth: -1.0,
+            ..perturbation
+        };
+        assert!(twin.perturb(&negative).is_err());
+    }
+}
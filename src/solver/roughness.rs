@@ -0,0 +1,170 @@
+/// Manning roughness from land use
+/// A land-use classification raster gives every pixel an integer class, and
+/// a lookup table maps the classes to Manning's n. The n of a cell is the
+/// mean over points spread inside it, so cells straddling two land uses get
+/// an intermediate value. Points outside the raster, on NODATA pixels or on
+/// classes missing from the table take the default n.
+use super::porosity::cell_samples;
+use super::{FrictionLaw, ShallowWaterSolver};
+use crate::mesh::TriangularMesh;
+use crate::raster::RasterGrid;
+use std::collections::BTreeMap;
+
+/// Manning's n per land-use class
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoughnessTable {
+    pub classes: BTreeMap<i64, f64>,
+}
+
+impl RoughnessTable {
+    /// Parse a JSON object mapping class codes to n, e.g.
+    /// `{"11": 0.025, "21": 0.04, "41": 0.1}`
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let entries: BTreeMap<String, f64> =
+            serde_json::from_str(json).map_err(|e| format!("invalid roughness table: {}", e))?;
+        let mut classes = BTreeMap::new();
+        for (class, n) in entries {
+            let code: i64 = class
+                .trim()
+                .parse()
+                .map_err(|_| format!("land-use class '{}' is not an integer", class))?;
+            if !(n > 0.0 && n.is_finite()) {
+                return Err(format!("Manning's n for class {} must be positive", code));
+            }
+            classes.insert(code, n);
+        }
+        if classes.is_empty() {
+            return Err("roughness table is empty".to_string());
+        }
+        Ok(RoughnessTable { classes })
+    }
+
+    pub fn read(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// Per-cell Manning's n
+#[derive(Debug, Clone, PartialEq)]
+pub struct LandUseRoughness {
+    pub manning: Vec<f64>,
+    pub unmatched: BTreeMap<i64, usize>, // Samples of classes missing from the table
+}
+
+impl LandUseRoughness {
+    /// Roughness from a land-use raster (row-major class codes on `grid`)
+    pub fn from_land_use(
+        mesh: &TriangularMesh,
+        grid: &RasterGrid,
+        classes: &[f64],
+        table: &RoughnessTable,
+        default_n: f64,
+    ) -> Self {
+        let mut unmatched = BTreeMap::new();
+        let node = |n: usize| (mesh.nodes[n].x, mesh.nodes[n].y);
+        let manning = mesh
+            .triangles
+            .iter()
+            .map(|tri| {
+                let samples = cell_samples(tri.nodes.map(node));
+                let sum: f64 = samples
+                    .iter()
+                    .map(|&(x, y)| {
+                        let class = grid
+                            .pixel_at(x, y)
+                            .map(|k| classes[k])
+                            .filter(|c| !c.is_nan())
+                            .map(|c| c.round() as i64);
+                        let Some(class) = class else {
+                            return default_n;
+                        };
+                        table.classes.get(&class).copied().unwrap_or_else(|| {
+                            *unmatched.entry(class).or_insert(0) += 1;
+                            default_n
+                        })
+                    })
+                    .sum();
+                sum / samples.len() as f64
+            })
+            .collect();
+        LandUseRoughness { manning, unmatched }
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Use a per-cell Manning's n (Manning friction only)
+    pub fn set_manning_field(&mut self, manning: Vec<f64>) -> Result<(), String> {
+        if !matches!(self.friction, FrictionLaw::Manning { .. }) {
+            return Err("a roughness field requires Manning friction".to_string());
+        }
+        if manning.len() != self.mesh.triangles.len() {
+            return Err(format!(
+                "roughness field has {} values for {} cells",
+                manning.len(),
+                self.mesh.triangles.len()
+            ));
+        }
+        self.manning_field = Some(manning);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+
+    #[test]
+    fn test_parse_roughness_table() {
+        let table = RoughnessTable::parse(r#"{"11": 0.025, " 41 ": 0.1}"#).unwrap();
+        assert_eq!(table.classes[&11], 0.025);
+        assert_eq!(table.classes[&41], 0.1);
+        for bad in [r#"{"forest": 0.1}"#, r#"{"11": 0}"#, "{}", "[0.03]"] {
+            assert!(RoughnessTable::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_roughness_from_land_use() {
+        // Channel (class 1) in x < 10 m, forest (class 2) above, an unknown
+        // class 9 in the last column and NODATA in the top row
+        let mesh = TriangularMesh::new_rectangular(21, 11, 20.0, 10.0, TopographyType::Flat);
+        let grid = RasterGrid {
+            x_min: 0.0,
+            y_max: 10.0,
+            cell_size: 1.0,
+            ncols: 20,
+            nrows: 10,
+        };
+        let classes: Vec<f64> = (0..200)
+            .map(|k| match (k % 20, k / 20) {
+                (_, 0) => f64::NAN,
+                (19, _) => 9.0,
+                (col, _) if col < 10 => 1.0,
+                _ => 2.0,
+            })
+            .collect();
+        let table = RoughnessTable::parse(r#"{"1": 0.03, "2": 0.12}"#).unwrap();
+        let roughness = LandUseRoughness::from_land_use(&mesh, &grid, &classes, &table, 0.05);
+        assert!(roughness.unmatched.contains_key(&9));
+        assert_eq!(roughness.unmatched.len(), 1);
+        for (tri, n) in mesh.triangles.iter().zip(&roughness.manning) {
+            let (x, y) = tri.centroid;
+            let expected = if y > 9.0 || x > 19.0 {
+                0.05
+            } else if x < 10.0 {
+                0.03
+            } else {
+                0.12
+            };
+            assert!((n - expected).abs() < 1e-12, "({}, {}): {}", x, y, n);
+        }
+
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        assert!(solver.set_manning_field(roughness.manning.clone()).is_err());
+        solver.friction = FrictionLaw::Manning { coefficient: 0.05 };
+        assert!(solver.set_manning_field(vec![0.03]).is_err());
+        solver.set_manning_field(roughness.manning).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+/// Sampling the solution at arbitrary points
+/// The containing (or nearest) triangle is found with the mesh's
+/// `PointLocator`, built on the first query. Within the cell the free surface and the velocity
+/// are reconstructed linearly from the cell gradients (dry neighbours do not
+/// contribute) and clamped to the range of the cell and its wet neighbours,
+/// so sampling creates no new extrema. The bed is interpolated from the
+/// nodes and the depth is the surface minus the bed.
+use super::{ShallowWaterSolver, State};
+use crate::mesh::{barycentric, PointLocator};
+
+/// Solution at a point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledState {
+    pub cell: usize,          // Containing triangle
+    pub depth: f64,           // h (m)
+    pub velocity: (f64, f64), // (u, v) (m/s)
+    pub surface: f64,         // Water surface elevation h + z_b (m)
+    pub bed: f64,             // Bed elevation z_b (m)
+}
+
+impl ShallowWaterSolver {
+    /// Triangle containing (x, y), None outside the mesh
+    pub fn locate(&self, x: f64, y: f64) -> Option<usize> {
+        self.point_locator
+            .get_or_init(|| PointLocator::new(&self.mesh))
+            .locate(&self.mesh, x, y)
+    }
+
+    /// Triangle whose centroid is closest to (x, y), None on an empty mesh
+    pub fn nearest_cell(&self, x: f64, y: f64) -> Option<usize> {
+        self.point_locator
+            .get_or_init(|| PointLocator::new(&self.mesh))
+            .nearest(&self.mesh, x, y)
+    }
+
+    /// Interpolated solution at (x, y), None outside the mesh
+    pub fn sample(&self, x: f64, y: f64) -> Option<SampledState> {
+        self.sample_state(&self.state, x, y)
+    }
+
+    /// Interpolated value of `state` (on this solver's mesh) at (x, y)
+    pub fn sample_state(&self, state: &State, x: f64, y: f64) -> Option<SampledState> {
+        let i = self.locate(x, y)?;
+        let weights = barycentric(&self.mesh, i, (x, y))?;
+        let tri = &self.mesh.triangles[i];
+        let bed = (0..3)
+            .map(|k| weights[k] * self.mesh.nodes[tri.nodes[k]].z)
+            .sum::<f64>();
+
+        if state.h[i] < self.constants.dry_tolerance {
+            return Some(SampledState {
+                cell: i,
+                depth: 0.0,
+                velocity: (0.0, 0.0),
+                surface: bed,
+                bed,
+            });
+        }
+
+        let offset = self.mesh.coordinate_system.delta(tri.centroid, (x, y));
+        let surface = self.reconstruct(state, i, offset, |j| {
+            state.h[j] + self.mesh.triangles[j].z_bed
+        });
+        let u = self.reconstruct(state, i, offset, |j| self.velocity(state, j).0);
+        let v = self.reconstruct(state, i, offset, |j| self.velocity(state, j).1);
+        let depth = (surface - bed).max(0.0);
+
+        Some(SampledState {
+            cell: i,
+            depth,
+            velocity: (u, v),
+            surface: bed + depth,
+            bed,
+        })
+    }
+
+    /// Linear reconstruction of a cell field in wet cell `i` at `offset` (m)
+    /// from the centroid, clamped to the values of `i` and its wet neighbours
+    fn reconstruct<F>(&self, state: &State, i: usize, offset: (f64, f64), value: F) -> f64
+    where
+        F: Fn(usize) -> f64,
+    {
+        let wet = |j: usize| state.h[j] >= self.constants.dry_tolerance;
+        let own = value(i);
+        let neighbour_value = |j: usize| if wet(j) { value(j) } else { own };
+
+        let (gx, gy) = self.local_gradient(i, neighbour_value);
+        let (lo, hi) = self.mesh.triangles[i]
+            .neighbors
+            .iter()
+            .flatten()
+            .map(|&j| neighbour_value(j))
+            .fold((own, own), |(lo, hi), f| (lo.min(f), hi.max(f)));
+        (own + gx * offset.0 + gy * offset.1).clamp(lo, hi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::{FrictionLaw, ShallowWaterSolver};
+
+    #[test]
+    fn test_sample_linear_surface_on_slope() {
+        let mesh = TriangularMesh::new_rectangular(
+            11,
+            11,
+            10.0,
+            10.0,
+            TopographyType::Slope {
+                gradient_x: 0.1,
+                gradient_y: 0.0,
+            },
+        );
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+            // Surface 2 + 0.05 x and flow 0.5 m/s in x
+            let h = 2.0 + 0.05 * tri.centroid.0 - tri.z_bed;
+            solver.state.h[i] = h;
+            solver.state.hu[i] = 0.5 * h;
+        }
+
+        let sample = solver.sample(4.3, 6.1).unwrap();
+        assert!((sample.surface - (2.0 + 0.05 * 4.3)).abs() < 1e-9);
+        assert!((sample.depth - (sample.surface - sample.bed)).abs() < 1e-12);
+        assert!((sample.velocity.0 - 0.5).abs() < 1e-12);
+        assert_eq!(sample.velocity.1, 0.0);
+        assert_eq!(solver.locate(4.3, 6.1), Some(sample.cell));
+
+        assert!(solver.sample(-0.5, 3.0).is_none());
+        assert!(solver.sample(3.0, 10.5).is_none());
+    }
+
+    #[test]
+    fn test_sample_dry_cell() {
+        let mesh = TriangularMesh::new_rectangular(5, 5, 4.0, 4.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_dam_break(2.0);
+        for i in 0..solver.mesh.triangles.len() {
+            if solver.mesh.triangles[i].centroid.0 > 2.0 {
+                solver.state.h[i] = 0.0;
+            }
+        }
+        let dry = solver.sample(3.5, 1.0).unwrap();
+        assert_eq!((dry.depth, dry.velocity), (0.0, (0.0, 0.0)));
+        let wet = solver.sample(0.5, 1.0).unwrap();
+        assert!((wet.depth - 2.0).abs() < 1e-12);
+    }
+}
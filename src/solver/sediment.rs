@@ -0,0 +1,291 @@
+/// Suspended sediment transport
+/// The depth-integrated volumetric concentration hc is advected with the
+/// mass fluxes of the final Runge-Kutta stage (first-order upwind), so a
+/// uniform concentration stays uniform, and exchanged with the bed after
+/// van Rijn (1984): particles settle with the velocity of the grain size and
+/// are picked up at w_s c_a, with the reference concentration
+///   c_a = 0.015 d T^1.5 / (a D*^0.3),   T = (τ_b - τ_cr) / τ_cr
+/// at a = 0.01 h above the bed. τ_cr follows from the Shields curve of
+/// Soulsby and Whitehouse; τ_b comes from the active friction law.
+/// Deposition w_s c is treated implicitly so it can never remove more than
+/// is in suspension. The net exchange is accumulated as a bed level change
+/// of the open part of each cell (including the pores of the bed) but not
+/// applied to the bed; there is no bedload or Exner update to feed yet.
+///
+/// With density coupling the mixture is denser where it carries sediment,
+/// ρ = ρ_w (1 + (s - 1) c), and the `SedimentDensity` source term adds the
+/// baroclinic pressure force -½ g h² ∇((s - 1) c) (Boussinesq approximation),
+/// which drives turbid water under clear water.
+use super::{FluxScheme, ShallowWaterSolver, SourceTerm, State, TimeIntegrator};
+
+/// Upper limit of the reference concentration (volumetric)
+const MAX_REFERENCE_CONCENTRATION: f64 = 0.05;
+
+/// Sediment and bed properties
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SedimentProperties {
+    pub grain_diameter: f64,      // d50 (m)
+    pub density: f64,             // Grain density ρ_s (kg/m^3)
+    pub bed_porosity: f64,        // Pore fraction of the bed (-)
+    pub kinematic_viscosity: f64, // Of the water (m^2/s)
+    pub density_coupling: bool,   // Add the baroclinic pressure of the mixture
+}
+
+impl Default for SedimentProperties {
+    fn default() -> Self {
+        SedimentProperties {
+            grain_diameter: 200e-6,
+            density: 2650.0,
+            bed_porosity: 0.4,
+            kinematic_viscosity: 1e-6,
+            density_coupling: false,
+        }
+    }
+}
+
+impl SedimentProperties {
+    /// Relative density s - 1 for water of density `water_density`
+    fn submerged_density(&self, water_density: f64) -> f64 {
+        self.density / water_density - 1.0
+    }
+
+    /// Dimensionless grain size D* = d ((s - 1) g / ν²)^(1/3)
+    pub fn dimensionless_diameter(&self, gravity: f64, water_density: f64) -> f64 {
+        let nu = self.kinematic_viscosity;
+        self.grain_diameter * (self.submerged_density(water_density) * gravity / (nu * nu)).cbrt()
+    }
+
+    /// Settling velocity w_s (m/s) after van Rijn (1984)
+    pub fn settling_velocity(&self, gravity: f64, water_density: f64) -> f64 {
+        let (d, nu) = (self.grain_diameter, self.kinematic_viscosity);
+        let rg = self.submerged_density(water_density) * gravity;
+        if d < 100e-6 {
+            rg * d * d / (18.0 * nu)
+        } else if d < 1e-3 {
+            10.0 * nu / d * ((1.0 + 0.01 * rg * d.powi(3) / (nu * nu)).sqrt() - 1.0)
+        } else {
+            1.1 * (rg * d).sqrt()
+        }
+    }
+
+    /// Critical bed shear stress τ_cr (Pa) from the Shields curve of Soulsby
+    /// and Whitehouse (1997)
+    pub fn critical_shear_stress(&self, gravity: f64, water_density: f64) -> f64 {
+        let d_star = self.dimensionless_diameter(gravity, water_density);
+        let shields = 0.30 / (1.0 + 1.2 * d_star) + 0.055 * (1.0 - (-0.020 * d_star).exp());
+        shields * (self.density - water_density) * gravity * self.grain_diameter
+    }
+}
+
+/// Suspended sediment field and accumulated bed change
+#[derive(Debug, Clone)]
+pub struct SuspendedSediment {
+    pub properties: SedimentProperties,
+    pub hc: Vec<f64>,         // Depth-integrated volumetric concentration (m)
+    pub bed_change: Vec<f64>, // Net deposition since the start (m, positive = accretion)
+}
+
+/// Baroclinic pressure force of the sediment-laden mixture
+pub struct SedimentDensity;
+
+impl SourceTerm for SedimentDensity {
+    fn name(&self) -> &str {
+        "sediment density"
+    }
+
+    fn evaluate(&self, solver: &ShallowWaterSolver, state: &State, i: usize) -> (f64, f64, f64) {
+        let (Some(sediment), h) = (&solver.sediment, state.h[i]) else {
+            return (0.0, 0.0, 0.0);
+        };
+        if h < solver.constants.dry_tolerance {
+            return (0.0, 0.0, 0.0);
+        }
+        let s1 = sediment
+            .properties
+            .submerged_density(solver.constants.density);
+        let (cx, cy) = solver.local_gradient(i, |j| solver.sediment_concentration_at(j));
+        let force = -0.5 * solver.constants.gravity * h * h * s1;
+        (0.0, force * cx, force * cy)
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Transport suspended sediment with the flow, starting from clear water
+    /// (Rusanov flux and RK2 only)
+    pub fn enable_sediment(&mut self, properties: SedimentProperties) -> Result<(), String> {
+        if self.flux_scheme != FluxScheme::Rusanov {
+            return Err("suspended sediment requires the Rusanov flux".to_string());
+        }
+        if !matches!(self.time_integrator, TimeIntegrator::RungeKutta2) {
+            return Err("suspended sediment requires the RK2 integrator".to_string());
+        }
+        self.require_finite_volume("suspended sediment")?;
+        let n = self.mesh.triangles.len();
+        self.sediment = Some(SuspendedSediment {
+            properties,
+            hc: vec![0.0; n],
+            bed_change: vec![0.0; n],
+        });
+        self.source_terms
+            .retain(|t| t.name() != SedimentDensity.name());
+        if properties.density_coupling {
+            self.source_terms.push(Box::new(SedimentDensity));
+        }
+        Ok(())
+    }
+
+    /// Depth-averaged concentration of cell `i` (zero in dry cells)
+    fn sediment_concentration_at(&self, i: usize) -> f64 {
+        match &self.sediment {
+            Some(sediment) if self.state.h[i] >= self.constants.dry_tolerance => {
+                sediment.hc[i] / self.state.h[i]
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Volumetric concentration per cell (zeros without sediment)
+    pub fn sediment_concentration(&self) -> Vec<f64> {
+        (0..self.mesh.triangles.len())
+            .map(|i| self.sediment_concentration_at(i))
+            .collect()
+    }
+
+    /// Sediment volume in suspension and in the bed change (m^3), which
+    /// changes only through the boundary
+    pub fn total_sediment(&self) -> f64 {
+        let Some(sediment) = &self.sediment else {
+            return 0.0;
+        };
+        let solid = 1.0 - sediment.properties.bed_porosity;
+        (0..self.mesh.triangles.len())
+            .map(|i| (sediment.hc[i] + solid * sediment.bed_change[i]) * self.storage_area(i))
+            .sum()
+    }
+
+    /// Depth-integrated tracer `field` after first-order upwind advection
+    /// over the current step with the mass fluxes of `stage`
+    pub(super) fn advect_depth_integrated(&self, stage: &State, field: &[f64]) -> Vec<f64> {
+        let dt = self.dt;
+        let dry = self.constants.dry_tolerance;
+        let concentration = |i: usize| {
+            if self.state.h[i] >= dry {
+                field[i] / self.state.h[i]
+            } else {
+                0.0
+            }
+        };
+        let mut advected = field.to_vec();
+        let ghosts = self.fill_ghosts(stage);
+        for (e, edge) in self.mesh.edges.iter().enumerate() {
+            let q = self.compute_flux(edge, stage, &ghosts).0 * self.open_length(e);
+            let donor = match edge.right_triangle {
+                Some(right) if q < 0.0 => right,
+                _ => edge.left_triangle,
+            };
+            let flux = dt * q * concentration(donor);
+            advected[edge.left_triangle] -= flux / self.storage_area(edge.left_triangle);
+            if let Some(right) = edge.right_triangle {
+                advected[right] += flux / self.storage_area(right);
+            }
+        }
+        advected
+    }
+
+    /// Advance the sediment over the current step: upwind advection with
+    /// the mass fluxes of `stage`, then exchange with the bed for the new
+    /// depths `h_new`
+    pub(super) fn transport_sediment(&mut self, stage: &State, h_new: &[f64]) {
+        let Some(mut sediment) = self.sediment.take() else {
+            return;
+        };
+        let dt = self.dt;
+        let g = self.constants.gravity;
+        let rho = self.constants.density;
+        let dry = self.constants.dry_tolerance;
+        let properties = sediment.properties;
+        let hc = self.advect_depth_integrated(stage, &sediment.hc);
+
+        // Exchange with the bed
+        let w_s = properties.settling_velocity(g, rho);
+        let tau_cr = properties.critical_shear_stress(g, rho);
+        let d_star = properties.dimensionless_diameter(g, rho);
+        let shear = self.bed_shear_stress();
+        let solid = 1.0 - properties.bed_porosity;
+        for i in 0..hc.len() {
+            let advected = hc[i].max(0.0);
+            let h = h_new[i];
+            if h < dry {
+                sediment.hc[i] = 0.0;
+                sediment.bed_change[i] += advected / solid;
+                continue;
+            }
+            let transport_stage = (shear[i] - tau_cr).max(0.0) / tau_cr;
+            let reference_height = 0.01 * h;
+            let c_a = (0.015 * properties.grain_diameter * transport_stage.powf(1.5)
+                / (reference_height * d_star.powf(0.3)))
+            .min(MAX_REFERENCE_CONCENTRATION);
+            let pickup = dt * w_s * c_a;
+            let updated = (advected + pickup) / (1.0 + dt * w_s / h);
+            sediment.bed_change[i] += (advected - updated) / solid;
+            sediment.hc[i] = updated;
+        }
+        self.sediment = Some(sediment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_van_rijn_parameters() {
+        let sand = SedimentProperties::default();
+        // 200 μm quartz sand: w_s ≈ 2.4 cm/s, τ_cr ≈ 0.15 Pa
+        let w_s = sand.settling_velocity(9.81, 1000.0);
+        assert!((w_s - 0.024).abs() < 0.002, "{}", w_s);
+        let tau_cr = sand.critical_shear_stress(9.81, 1000.0);
+        assert!((tau_cr - 0.154).abs() < 0.005, "{}", tau_cr);
+        let silt = SedimentProperties {
+            grain_diameter: 20e-6,
+            ..sand
+        };
+        assert!(silt.settling_velocity(9.81, 1000.0) < 1e-3);
+    }
+
+    #[test]
+    fn test_erosion_deposition_and_conservation() {
+        // The fast flow behind a dam break scours the sand bed, and every
+        // grain picked up is still in suspension or back on the bed
+        let mesh = TriangularMesh::new_rectangular(21, 5, 20.0, 4.0, TopographyType::Flat);
+        let mut solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Manning { coefficient: 0.03 });
+        solver.set_dam_break(10.0);
+        solver
+            .enable_sediment(SedimentProperties {
+                density_coupling: true,
+                ..SedimentProperties::default()
+            })
+            .unwrap();
+        assert!(solver.single_precision_unsupported().is_some());
+
+        while solver.time < 2.0 {
+            solver.step();
+        }
+        let suspended: f64 = solver.sediment.as_ref().unwrap().hc.iter().sum();
+        assert!(suspended > 0.0);
+        let scour = solver
+            .sediment
+            .as_ref()
+            .unwrap()
+            .bed_change
+            .iter()
+            .cloned()
+            .fold(0.0, f64::min);
+        assert!(scour < 0.0);
+        assert!(solver.total_sediment().abs() < 1e-9 * suspended);
+        assert!(solver.sediment_concentration().iter().all(|&c| c >= 0.0));
+    }
+}
@@ -0,0 +1,414 @@
+/// Semi-implicit θ-scheme for the free surface (Casulli type)
+/// The continuity equation and the surface-gradient term of the momentum
+/// equations are discretised implicitly with weight θ, the advection and
+/// friction terms explicitly. Eliminating the face discharges yields a
+/// symmetric positive definite system for the new surface elevation,
+///
+///   A_i η_i + Σ_e g θ² Δt² L_e H_e / d_e (η_i - η_j) = rhs_i
+///
+/// which is solved with preconditioned conjugate gradients. Mass is
+/// updated from the resulting face discharges and is therefore conserved
+/// independently of the linear solver tolerance. The advection uses a
+/// Rusanov flux and closed walls of its own, so other edge fluxes and
+/// boundary closures are rejected rather than ignored.
+use super::{FluxScheme, ShallowWaterSolver, State, REST_SPEED};
+use crate::linear_solver::{conjugate_gradient, SparseMatrix};
+use crate::profiler::Phase;
+use rayon::prelude::*;
+
+const CG_TOLERANCE: f64 = 1e-10;
+const CG_MAX_ITERATIONS: usize = 2000;
+
+/// Geometry and depth of an interior face for one step
+struct Face {
+    edge: usize,
+    left: usize,
+    right: usize,
+    coupling: f64, // L_e H_e / d_e
+    depth: f64,    // Face depth H_e
+}
+
+impl ShallowWaterSolver {
+    /// Why the current configuration cannot run with the semi-implicit
+    /// integrator, if it cannot
+    pub fn semi_implicit_unsupported(&self) -> Option<&'static str> {
+        if self.flux_scheme != FluxScheme::Rusanov {
+            Some("the central-upwind flux")
+        } else if !self.has_default_closures() {
+            Some("custom fluxes and boundary closures")
+        } else {
+            None
+        }
+    }
+
+    /// Time step limited by advection and the gravity-wave Courant number
+    fn compute_semi_implicit_timestep(&mut self, max_courant: f64) {
+        let dt = (0..self.mesh.triangles.len())
+            .into_par_iter()
+            .map(|i| {
+                let (u, v) = self.velocity(&self.state, i);
+                let speed = (u * u + v * v).sqrt();
+                let radius = self.inscribed_radius(i);
+                let advective = if speed > REST_SPEED {
+                    self.cfl * radius / speed
+                } else {
+                    f64::INFINITY
+                };
+                let gravity_speed = speed + (self.constants.gravity * self.state.h[i]).sqrt();
+                let gravity = if gravity_speed > REST_SPEED {
+                    max_courant * radius / gravity_speed
+                } else {
+                    f64::INFINITY
+                };
+                advective.min(gravity)
+            })
+            .reduce(|| f64::INFINITY, f64::min);
+
+        if dt.is_finite() {
+            self.dt = dt;
+        }
+    }
+
+    /// Explicit momentum advection (Rusanov without the pressure part)
+    /// Returns the integrated outward flux of (hu, hv) per cell.
+    fn momentum_advection(&self, state: &State) -> Vec<(f64, f64)> {
+        let mut residual = vec![(0.0, 0.0); self.mesh.triangles.len()];
+
+        for edge in &self.mesh.edges {
+            let (nx, ny) = edge.normal;
+            let left = edge.left_triangle;
+            let (u_l, v_l) = self.velocity(state, left);
+            let (hu_l, hv_l) = (state.hu[left], state.hv[left]);
+
+            let (u_r, v_r, hu_r, hv_r) = match edge.right_triangle {
+                Some(right) => {
+                    let (u, v) = self.velocity(state, right);
+                    (u, v, state.hu[right], state.hv[right])
+                }
+                None => {
+                    // Reflective wall: mirror the normal velocity
+                    let un = u_l * nx + v_l * ny;
+                    let (u, v) = (u_l - 2.0 * un * nx, v_l - 2.0 * un * ny);
+                    let h = state.h[left];
+                    (u, v, h * u, h * v)
+                }
+            };
+
+            let un_l = u_l * nx + v_l * ny;
+            let un_r = u_r * nx + v_r * ny;
+            let a = un_l.abs().max(un_r.abs());
+
+            let flux_hu = 0.5 * (hu_l * un_l + hu_r * un_r - a * (hu_r - hu_l));
+            let flux_hv = 0.5 * (hv_l * un_l + hv_r * un_r - a * (hv_r - hv_l));
+
+            residual[left].0 += flux_hu * edge.length;
+            residual[left].1 += flux_hv * edge.length;
+            if let Some(right) = edge.right_triangle {
+                residual[right].0 -= flux_hu * edge.length;
+                residual[right].1 -= flux_hv * edge.length;
+            }
+        }
+
+        residual
+    }
+
+    /// Advance one step with the semi-implicit θ-scheme
+    pub(super) fn step_semi_implicit(&mut self, theta: f64, max_courant: f64) {
+        let start = self.profiler.start();
+        self.compute_semi_implicit_timestep(max_courant);
+        self.limit_step();
+        self.profiler.record(Phase::Timestep, start);
+        let start = self.profiler.start();
+        let dt = self.dt;
+        let n = self.mesh.triangles.len();
+        let state = &self.state;
+
+        let z: Vec<f64> = self.mesh.triangles.iter().map(|t| t.z_bed).collect();
+        let eta: Vec<f64> = (0..n).map(|i| state.h[i] + z[i]).collect();
+
+        // Explicit momentum predictor: advection, (1 - θ) surface gradient,
+        // rotation terms and point-implicit friction
+        let advection = self.momentum_advection(state);
+        let predictor: Vec<(f64, f64)> = (0..n)
+            .into_par_iter()
+            .map(|i| {
+                let h = state.h[i];
+                if h < self.constants.dry_tolerance {
+                    return (0.0, 0.0);
+                }
+                let area = self.mesh.triangles[i].area;
+                let (u, v) = self.velocity(state, i);
+                let (gx, gy) = self.cell_field_gradient(&eta, i);
+                let (rx, ry) = self.rotation_source(i, h, u, v);
+
+                let hu = state.hu[i] - dt * advection[i].0 / area
+                    + dt * ((1.0 - theta) * -self.constants.gravity * h * gx + rx);
+                let hv = state.hv[i] - dt * advection[i].1 / area
+                    + dt * ((1.0 - theta) * -self.constants.gravity * h * gy + ry);
+
+                // Registered terms beyond the built-in ones enter explicitly
+                let (_, shu, shv) = self.sum_source_terms(self.extra_source_terms(), state, i);
+                let (hu, hv) = (hu + dt * shu, hv + dt * shv);
+
+                let speed = (u * u + v * v).sqrt();
+                if self.friction_law(i).has_yield_term() || speed < REST_SPEED {
+                    return (hu, hv);
+                }
+                let (sf_x, sf_y) = self.compute_friction_slope(i, h, u, v);
+                let sf = (sf_x * sf_x + sf_y * sf_y).sqrt();
+                let factor = 1.0 + dt * self.constants.gravity * sf / speed;
+                (hu / factor, hv / factor)
+            })
+            .collect();
+
+        // Interior faces and their explicit normal discharges
+        let velocity = |h: f64, m: (f64, f64)| self.constants.velocity(h, m.0, m.1);
+        let faces: Vec<Face> = self
+            .mesh
+            .edges
+            .iter()
+            .enumerate()
+            .filter_map(|(e, edge)| {
+                let right = edge.right_triangle?;
+                let left = edge.left_triangle;
+                let depth = (eta[left].max(eta[right]) - z[left].max(z[right])).max(0.0);
+                let (dx, dy) = self.mesh.coordinate_system.delta(
+                    self.mesh.triangles[left].centroid,
+                    self.mesh.triangles[right].centroid,
+                );
+                let distance = (dx * dx + dy * dy).sqrt();
+                Some(Face {
+                    edge: e,
+                    left,
+                    right,
+                    coupling: edge.length * depth / distance,
+                    depth,
+                })
+            })
+            .collect();
+
+        let normal_discharge = |face: &Face, ul: (f64, f64), ur: (f64, f64)| {
+            let (nx, ny) = self.mesh.edges[face.edge].normal;
+            face.depth * 0.5 * ((ul.0 + ur.0) * nx + (ul.1 + ur.1) * ny)
+        };
+        let q_old: Vec<f64> = faces
+            .iter()
+            .map(|f| {
+                normal_discharge(
+                    f,
+                    self.velocity(state, f.left),
+                    self.velocity(state, f.right),
+                )
+            })
+            .collect();
+        let q_star: Vec<f64> = faces
+            .iter()
+            .map(|f| {
+                normal_discharge(
+                    f,
+                    velocity(state.h[f.left], predictor[f.left]),
+                    velocity(state.h[f.right], predictor[f.right]),
+                )
+            })
+            .collect();
+
+        // Assemble the free-surface system
+        let gamma = self.constants.gravity * theta * theta * dt * dt;
+        let mut triplets = Vec::with_capacity(n + 4 * faces.len());
+        let mut rhs: Vec<f64> = (0..n)
+            .map(|i| {
+                let area = self.mesh.triangles[i].area;
+                triplets.push((i, i, area));
+                area * eta[i]
+            })
+            .collect();
+
+        for (k, face) in faces.iter().enumerate() {
+            let c = gamma * face.coupling;
+            triplets.push((face.left, face.left, c));
+            triplets.push((face.right, face.right, c));
+            triplets.push((face.left, face.right, -c));
+            triplets.push((face.right, face.left, -c));
+
+            let length = self.mesh.edges[face.edge].length;
+            let flux = dt * length * (theta * q_star[k] + (1.0 - theta) * q_old[k]);
+            rhs[face.left] -= flux;
+            rhs[face.right] += flux;
+        }
+
+        let matrix = SparseMatrix::from_triplets(n, &triplets);
+        self.profiler.record(Phase::Flux, start);
+        let start = self.profiler.start();
+        let mut eta_new = eta.clone();
+        let stats =
+            conjugate_gradient(&matrix, &rhs, &mut eta_new, CG_TOLERANCE, CG_MAX_ITERATIONS);
+        self.profiler.record(Phase::LinearSolve, start);
+        let start = self.profiler.start();
+
+        // Conservative depth update from the new face discharges
+        let mut h_new = state.h.clone();
+        for (k, face) in faces.iter().enumerate() {
+            let edge = &self.mesh.edges[face.edge];
+            let (dx, dy) = self.mesh.coordinate_system.delta(
+                self.mesh.triangles[face.left].centroid,
+                self.mesh.triangles[face.right].centroid,
+            );
+            let distance = (dx * dx + dy * dy).sqrt();
+            let q_new = q_star[k]
+                - self.constants.gravity
+                    * theta
+                    * dt
+                    * face.depth
+                    * (eta_new[face.right] - eta_new[face.left])
+                    / distance;
+            let volume = dt * edge.length * (theta * q_new + (1.0 - theta) * q_old[k]);
+            h_new[face.left] -= volume / self.mesh.triangles[face.left].area;
+            h_new[face.right] += volume / self.mesh.triangles[face.right].area;
+        }
+        for (i, h) in h_new.iter_mut().enumerate() {
+            let (sh, _, _) = self.sum_source_terms(self.extra_source_terms(), state, i);
+            *h = (*h + dt * sh).max(0.0);
+        }
+
+        // Implicit part of the surface gradient
+        let eta_final: Vec<f64> = (0..n).map(|i| h_new[i] + z[i]).collect();
+        let momentum: Vec<(f64, f64)> = (0..n)
+            .into_par_iter()
+            .map(|i| {
+                if h_new[i] < self.constants.dry_tolerance {
+                    return (0.0, 0.0);
+                }
+                let (gx, gy) = self.cell_field_gradient(&eta_final, i);
+                (
+                    predictor[i].0 - dt * theta * self.constants.gravity * h_new[i] * gx,
+                    predictor[i].1 - dt * theta * self.constants.gravity * h_new[i] * gy,
+                )
+            })
+            .collect();
+
+        self.state.h = h_new;
+        for (i, (hu, hv)) in momentum.into_iter().enumerate() {
+            self.state.hu[i] = hu;
+            self.state.hv[i] = hv;
+        }
+
+        self.profiler.record(Phase::Update, start);
+
+        if self.has_yield_friction() {
+            let start = self.profiler.start();
+            self.apply_yield_resistance(dt);
+            self.profiler.record(Phase::Sources, start);
+        }
+        let start = self.profiler.start();
+        self.apply_boundary_conditions();
+        self.profiler.record(Phase::Update, start);
+        self.unconverged_solves.record(&stats);
+        self.time += dt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{FrictionLaw, TimeIntegrator};
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+
+    fn semi_implicit_solver(topography: TopographyType) -> ShallowWaterSolver {
+        let mesh = TriangularMesh::new_rectangular(11, 11, 1000.0, 1000.0, topography);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.time_integrator = TimeIntegrator::SemiImplicit {
+            theta: 0.6,
+            max_courant: 20.0,
+        };
+        solver
+    }
+
+    #[test]
+    fn test_lake_at_rest_over_bump() {
+        let mut solver = semi_implicit_solver(TopographyType::Gaussian {
+            center: (500.0, 500.0),
+            amplitude: 2.0,
+            width: 200.0,
+        });
+        for i in 0..solver.state.h.len() {
+            solver.state.h[i] = 10.0 - solver.mesh.triangles[i].z_bed;
+        }
+
+        for _ in 0..5 {
+            solver.step();
+        }
+        for i in 0..solver.state.h.len() {
+            let eta = solver.state.h[i] + solver.mesh.triangles[i].z_bed;
+            assert!((eta - 10.0).abs() < 1e-8);
+            assert!(solver.state.hu[i].abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_large_steps_conserve_mass() {
+        let mut solver = semi_implicit_solver(TopographyType::Flat);
+        solver.set_circular_wave((500.0, 500.0), 200.0, 0.1);
+        let initial_mass = solver.compute_total_mass();
+
+        let mut explicit = semi_implicit_solver(TopographyType::Flat);
+        explicit.state = solver.state.clone();
+        explicit.compute_timestep();
+
+        for _ in 0..10 {
+            solver.step();
+        }
+
+        // Time step far beyond the explicit CFL limit
+        assert!(solver.dt > 10.0 * explicit.dt);
+        let mass_error = (solver.compute_total_mass() - initial_mass).abs() / initial_mass;
+        assert!(mass_error < 1e-12, "Mass error {}", mass_error);
+        assert!(solver.state.h.iter().all(|h| h.is_finite() && *h > 0.0));
+    }
+
+    #[test]
+    fn test_unsupported_fluxes_and_closures() {
+        let mut solver = semi_implicit_solver(TopographyType::Flat);
+        assert_eq!(solver.semi_implicit_unsupported(), None);
+        solver.boundary_closure = Box::new(super::super::Transmissive);
+        assert_eq!(
+            solver.semi_implicit_unsupported(),
+            Some("custom fluxes and boundary closures")
+        );
+        solver.flux_scheme = FluxScheme::CentralUpwind;
+        assert_eq!(
+            solver.semi_implicit_unsupported(),
+            Some("the central-upwind flux")
+        );
+    }
+
+    #[test]
+    fn test_step_limit_shortens_one_step() {
+        let mut solver = semi_implicit_solver(TopographyType::Flat);
+        solver.set_circular_wave((500.0, 500.0), 200.0, 0.1);
+        solver.step();
+        let dt = solver.dt;
+
+        solver.step_limit = 1.0;
+        solver.step();
+        assert_eq!(solver.dt, 1.0);
+        assert_eq!(solver.time, dt + 1.0);
+
+        // The next step starts again from the unlimited one
+        solver.step_limit = f64::INFINITY;
+        solver.step();
+        assert!(solver.dt > 10.0);
+    }
+
+    #[test]
+    fn test_wave_spreads_outward() {
+        let mut solver = semi_implicit_solver(TopographyType::Flat);
+        solver.set_circular_wave((500.0, 500.0), 200.0, 0.1);
+        let peak = solver.state.h.iter().cloned().fold(f64::MIN, f64::max);
+
+        for _ in 0..5 {
+            solver.step();
+        }
+        let new_peak = solver.state.h.iter().cloned().fold(f64::MIN, f64::max);
+        assert!(new_peak < peak, "Hump should collapse");
+    }
+}
@@ -0,0 +1,416 @@
+/// Explicit stepping in a generic floating-point type
+/// The default configuration (Rusanov flux, RK2, Cartesian mesh, no yield
+/// law) is implemented once over `num_traits::Float` and run in f32 for
+/// `Precision::Single`. Geometry is converted once; the state is converted
+/// on every step so that everything reading `solver.state` keeps working.
+/// Single precision halves the memory traffic of the kernel and matches the
+/// precision of the GPU path; differences to f64 are at the round-off level
+/// of f32 (about 1e-7 relative).
+use super::{
+    wall_depth, Discretization, FluxScheme, FrictionLaw, ShallowWaterSolver, State, TimeIntegrator,
+    REST_SPEED,
+};
+use crate::mesh::CoordinateSystem;
+use crate::profiler::Phase;
+use num_traits::Float;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Arithmetic precision of the explicit kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precision {
+    Double,
+    Single,
+}
+
+fn real<T: Float>(x: f64) -> T {
+    T::from(x).unwrap()
+}
+
+#[derive(Debug, Clone, Copy)]
+struct KernelEdge<T> {
+    left: usize,
+    right: Option<usize>,
+    normal: (T, T),
+    length: T,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum KernelFriction<T> {
+    None,
+    Manning, // Per-cell n in `Kernel::manning`
+    Chezy(T),
+}
+
+/// Geometry, parameters and state of the explicit scheme in type `T`
+pub(super) struct Kernel<T> {
+    edges: Vec<KernelEdge<T>>,
+    area: Vec<T>,
+    bed_gradient: Vec<(T, T)>,
+    manning: Vec<T>,
+    friction: KernelFriction<T>,
+    min_size: f64, // Smallest inscribed radius (m)
+    gravity: T,
+    dry_tolerance: T,
+    desingularization: T,
+    friction_depth: T,
+    h: Vec<T>,
+    hu: Vec<T>,
+    hv: Vec<T>,
+}
+
+impl<T: Float + Send + Sync> Kernel<T> {
+    pub(super) fn new(solver: &ShallowWaterSolver) -> Self {
+        let n = solver.mesh.triangles.len();
+        let (friction, manning) = match solver.friction {
+            FrictionLaw::Manning { coefficient } => (
+                KernelFriction::Manning,
+                (0..n)
+                    .map(|i| real(solver.manning_field.as_ref().map_or(coefficient, |f| f[i])))
+                    .collect(),
+            ),
+            FrictionLaw::Chezy { coefficient } => {
+                (KernelFriction::Chezy(real(coefficient)), vec![])
+            }
+            _ => (KernelFriction::None, vec![]),
+        };
+        Kernel {
+            edges: solver
+                .mesh
+                .edges
+                .iter()
+                .map(|e| KernelEdge {
+                    left: e.left_triangle,
+                    right: e.right_triangle,
+                    normal: (real(e.normal.0), real(e.normal.1)),
+                    length: real(e.length),
+                })
+                .collect(),
+            area: solver.mesh.triangles.iter().map(|t| real(t.area)).collect(),
+            bed_gradient: (0..n)
+                .map(|i| {
+                    let (gx, gy) = solver.compute_bed_gradient(i);
+                    (real(gx), real(gy))
+                })
+                .collect(),
+            manning,
+            friction,
+            min_size: (0..n)
+                .map(|i| solver.inscribed_radius(i))
+                .fold(f64::INFINITY, f64::min),
+            gravity: real(solver.constants.gravity),
+            dry_tolerance: real(solver.constants.dry_tolerance),
+            desingularization: real(solver.constants.desingularization),
+            friction_depth: real(solver.constants.friction_depth),
+            h: vec![T::zero(); n],
+            hu: vec![T::zero(); n],
+            hv: vec![T::zero(); n],
+        }
+    }
+
+    pub(super) fn load(&mut self, state: &State) {
+        let convert = |src: &[f64], dst: &mut Vec<T>| {
+            dst.par_iter_mut().zip(src).for_each(|(d, &s)| *d = real(s));
+        };
+        convert(&state.h, &mut self.h);
+        convert(&state.hu, &mut self.hu);
+        convert(&state.hv, &mut self.hv);
+    }
+
+    pub(super) fn store(&self, state: &mut State) {
+        let convert = |src: &[T], dst: &mut Vec<f64>| {
+            dst.par_iter_mut()
+                .zip(src)
+                .for_each(|(d, s)| *d = s.to_f64().unwrap());
+        };
+        convert(&self.h, &mut state.h);
+        convert(&self.hu, &mut state.hu);
+        convert(&self.hv, &mut state.hv);
+    }
+
+    /// Desingularized velocity as in `PhysicalConstants::velocity`
+    fn velocity(&self, h: T, hu: T, hv: T) -> (T, T) {
+        if h <= self.dry_tolerance {
+            return (T::zero(), T::zero());
+        }
+        let h2 = h * h;
+        let eps2 = self.desingularization * self.desingularization;
+        let scale = real::<T>(2.0) * h / (h2 + h2.max(eps2));
+        (hu * scale, hv * scale)
+    }
+
+    /// CFL time step from the fastest signal speed and the smallest cell;
+    /// `dt` is kept when the water is at rest
+    pub(super) fn timestep(&self, cfl: f64, dt: f64) -> f64 {
+        let max_speed = (0..self.h.len())
+            .into_par_iter()
+            .map(|i| {
+                let (u, v) = self.velocity(self.h[i], self.hu[i], self.hv[i]);
+                ((u * u + v * v).sqrt() + (self.gravity * self.h[i]).sqrt())
+                    .to_f64()
+                    .unwrap()
+            })
+            .reduce(|| 0.0, f64::max);
+        if max_speed > REST_SPEED {
+            cfl * self.min_size / max_speed
+        } else {
+            dt
+        }
+    }
+
+    /// Rusanov flux across one edge (wall flux without a neighbour)
+    fn flux(&self, edge: &KernelEdge<T>, h: &[T], hu: &[T], hv: &[T]) -> (T, T, T) {
+        let half = real::<T>(0.5);
+        let g = self.gravity;
+        let (nx, ny) = edge.normal;
+        let l = edge.left;
+        let (h_l, hu_l, hv_l) = (h[l], hu[l], hv[l]);
+        let (u_l, v_l) = self.velocity(h_l, hu_l, hv_l);
+
+        let Some(r) = edge.right else {
+            let un = (u_l * nx + v_l * ny).to_f64().unwrap();
+            let depth = wall_depth(h_l.to_f64().unwrap(), un, g.to_f64().unwrap());
+            let pressure = half * g * real::<T>(depth * depth);
+            return (T::zero(), pressure * nx, pressure * ny);
+        };
+        let (u_r, v_r) = self.velocity(h[r], hu[r], hv[r]);
+        let (h_r, hu_r, hv_r) = (h[r], hu[r], hv[r]);
+
+        let un_l = u_l * nx + v_l * ny;
+        let un_r = u_r * nx + v_r * ny;
+        let f_h_l = hu_l * nx + hv_l * ny;
+        let f_hu_l = (hu_l * u_l + half * g * h_l * h_l) * nx + (hu_l * v_l) * ny;
+        let f_hv_l = (hv_l * u_l) * nx + (hv_l * v_l + half * g * h_l * h_l) * ny;
+        let f_h_r = hu_r * nx + hv_r * ny;
+        let f_hu_r = (hu_r * u_r + half * g * h_r * h_r) * nx + (hu_r * v_r) * ny;
+        let f_hv_r = (hv_r * u_r) * nx + (hv_r * v_r + half * g * h_r * h_r) * ny;
+
+        let s_max = (un_l.abs() + (g * h_l).sqrt()).max(un_r.abs() + (g * h_r).sqrt());
+        (
+            half * (f_h_l + f_h_r - s_max * (h_r - h_l)),
+            half * (f_hu_l + f_hu_r - s_max * (hu_r - hu_l)),
+            half * (f_hv_l + f_hv_r - s_max * (hv_r - hv_l)),
+        )
+    }
+
+    /// Friction slope (S_f,x, S_f,y) in cell `i`
+    fn friction_slope(&self, i: usize, h: T, u: T, v: T) -> (T, T) {
+        let speed = (u * u + v * v).sqrt();
+        if speed < real(REST_SPEED) || h <= self.friction_depth {
+            return (T::zero(), T::zero());
+        }
+        let sf = match self.friction {
+            KernelFriction::None => return (T::zero(), T::zero()),
+            KernelFriction::Manning => {
+                let n = self.manning[i];
+                n * n * speed * speed / h.powf(real(4.0 / 3.0))
+            }
+            KernelFriction::Chezy(c) => speed * speed / (c * c * h),
+        };
+        (sf * u / speed, sf * v / speed)
+    }
+
+    fn residual(
+        &self,
+        h: &[T],
+        hu: &[T],
+        hv: &[T],
+        profiler: &crate::profiler::Profiler,
+    ) -> [Vec<T>; 3] {
+        let start = profiler.start();
+        let n = h.len();
+        let [mut rh, mut rhu, mut rhv] =
+            [vec![T::zero(); n], vec![T::zero(); n], vec![T::zero(); n]];
+        for edge in &self.edges {
+            let (fh, fhu, fhv) = self.flux(edge, h, hu, hv);
+            let l = edge.left;
+            rh[l] = rh[l] + fh * edge.length;
+            rhu[l] = rhu[l] + fhu * edge.length;
+            rhv[l] = rhv[l] + fhv * edge.length;
+            if let Some(r) = edge.right {
+                rh[r] = rh[r] - fh * edge.length;
+                rhu[r] = rhu[r] - fhu * edge.length;
+                rhv[r] = rhv[r] - fhv * edge.length;
+            }
+        }
+        profiler.record(Phase::Flux, start);
+
+        // Bed slope and friction: -g h (∇z_b + S_f) enters with a minus
+        let start = profiler.start();
+        rhu.par_iter_mut()
+            .zip(rhv.par_iter_mut())
+            .enumerate()
+            .for_each(|(i, (rx, ry))| {
+                if h[i] < self.dry_tolerance {
+                    return;
+                }
+                let (u, v) = self.velocity(h[i], hu[i], hv[i]);
+                let (sf_x, sf_y) = self.friction_slope(i, h[i], u, v);
+                let (dzdx, dzdy) = self.bed_gradient[i];
+                let gh = self.gravity * h[i];
+                *rx = *rx + (gh * sf_x + gh * dzdx) * self.area[i];
+                *ry = *ry + (gh * sf_y + gh * dzdy) * self.area[i];
+            });
+        profiler.record(Phase::Sources, start);
+        [rh, rhu, rhv]
+    }
+
+    fn update(&self, residual: &[Vec<T>; 3], dt: T) -> [Vec<T>; 3] {
+        let n = self.h.len();
+        let h: Vec<T> = (0..n)
+            .into_par_iter()
+            .map(|i| (self.h[i] - dt * residual[0][i] / self.area[i]).max(T::zero()))
+            .collect();
+        let momentum = |m: &[T], r: &[T]| -> Vec<T> {
+            (0..n)
+                .into_par_iter()
+                .map(|i| {
+                    if h[i] < self.dry_tolerance {
+                        T::zero()
+                    } else {
+                        m[i] - dt * r[i] / self.area[i]
+                    }
+                })
+                .collect()
+        };
+        let hu = momentum(&self.hu, &residual[1]);
+        let hv = momentum(&self.hv, &residual[2]);
+        [h, hu, hv]
+    }
+
+    /// One RK2 step of `dt`
+    pub(super) fn step(&mut self, dt: f64, profiler: &crate::profiler::Profiler) {
+        let k1 = self.residual(&self.h, &self.hu, &self.hv, profiler);
+        let start = profiler.start();
+        let [h, hu, hv] = self.update(&k1, real(0.5 * dt));
+        profiler.record(Phase::Update, start);
+
+        let k2 = self.residual(&h, &hu, &hv, profiler);
+        let start = profiler.start();
+        let [h, hu, hv] = self.update(&k2, real(dt));
+        (self.h, self.hu, self.hv) = (h, hu, hv);
+        for i in 0..self.h.len() {
+            if self.h[i] < self.dry_tolerance {
+                self.h[i] = T::zero();
+                self.hu[i] = T::zero();
+                self.hv[i] = T::zero();
+            }
+        }
+        profiler.record(Phase::Update, start);
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Why the current configuration cannot run in single precision, if it
+    /// cannot
+    pub fn single_precision_unsupported(&self) -> Option<&'static str> {
+        if self.flux_scheme != FluxScheme::Rusanov {
+            Some("the central-upwind flux")
+        } else if matches!(self.time_integrator, TimeIntegrator::SemiImplicit { .. }) {
+            Some("the semi-implicit integrator")
+        } else if matches!(self.time_integrator, TimeIntegrator::Imex { .. }) {
+            Some("the IMEX integrator")
+        } else if self.friction.has_yield_term() {
+            Some("Voellmy and Bingham friction")
+        } else if !self.has_default_closures() {
+            Some("custom fluxes and boundary closures")
+        } else if !self.has_default_source_terms() {
+            Some("custom source terms")
+        } else if self.bed_slope_limit.is_some() {
+            Some("bed slope limiting")
+        } else if self.has_moving_bed() {
+            Some("moving beds")
+        } else if self.porosity.is_some() {
+            Some("porosity")
+        } else if self.subgrid.is_some() {
+            Some("subgrid bathymetry")
+        } else if self.friction_zones.is_some() {
+            Some("friction zones")
+        } else if self.sediment.is_some() {
+            Some("suspended sediment")
+        } else if self.buoyant_scalar.is_some() {
+            Some("buoyant scalars")
+        } else if self.timestep_control.is_some() {
+            Some("time step control")
+        } else if self.source_splitting.is_some() {
+            Some("source splitting")
+        } else if self.discretization == Discretization::Dg1 {
+            Some("the DG discretization")
+        } else if matches!(
+            self.mesh.coordinate_system,
+            CoordinateSystem::Spherical { .. }
+        ) {
+            Some("spherical coordinates")
+        } else {
+            None
+        }
+    }
+
+    /// RK2 step with the f32 kernel, built on first use
+    pub(super) fn step_single_precision(&mut self) {
+        let mut kernel = match self.single_precision_kernel.take() {
+            Some(kernel) => kernel,
+            None => Kernel::<f32>::new(self),
+        };
+        kernel.load(&self.state);
+        let start = self.profiler.start();
+        self.dt = kernel.timestep(self.cfl, self.dt);
+        self.limit_step();
+        self.profiler.record(Phase::Timestep, start);
+        kernel.step(self.dt, &self.profiler);
+        kernel.store(&mut self.state);
+        self.single_precision_kernel = Some(kernel);
+        self.time += self.dt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+
+    fn solver() -> ShallowWaterSolver {
+        let slope = TopographyType::Slope {
+            gradient_x: 0.02,
+            gradient_y: 0.01,
+        };
+        let mesh = TriangularMesh::new_rectangular(12, 12, 10.0, 10.0, slope);
+        let mut solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Manning { coefficient: 0.03 });
+        solver.set_dam_break(5.0);
+        solver
+    }
+
+    #[test]
+    fn test_generic_kernel_matches_reference_solver() {
+        let mut reference = solver();
+        let mut kernel = Kernel::<f64>::new(&reference);
+        let mut single = solver();
+        single.precision = Precision::Single;
+        assert_eq!(single.single_precision_unsupported(), None);
+
+        let mut state = reference.state.clone();
+        let mut dt = reference.dt;
+        for _ in 0..20 {
+            reference.step();
+            single.step();
+            kernel.load(&state);
+            dt = kernel.timestep(reference.cfl, dt);
+            kernel.step(dt, &reference.profiler);
+            kernel.store(&mut state);
+        }
+
+        let initial_mass = solver().compute_total_mass();
+        assert!((single.compute_total_mass() - initial_mass).abs() < 1e-5 * initial_mass);
+        for i in 0..state.h.len() {
+            // f64 instantiation reproduces the reference scheme
+            assert!((state.h[i] - reference.state.h[i]).abs() < 1e-12);
+            assert!((state.hu[i] - reference.state.hu[i]).abs() < 1e-12);
+            // f32 stays within single-precision round-off
+            assert!((single.state.h[i] - reference.state.h[i]).abs() < 1e-4);
+            assert!((single.state.hu[i] - reference.state.hu[i]).abs() < 1e-4);
+        }
+        assert!((single.time - reference.time).abs() < 1e-6);
+    }
+}
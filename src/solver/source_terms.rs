@@ -0,0 +1,414 @@
+/// Pluggable source terms
+/// The explicit residual sums the terms registered in
+/// `ShallowWaterSolver::source_terms`. Each term returns its rate of change
+/// S = (∂h/∂t, ∂(hu)/∂t, ∂(hv)/∂t) per unit area for one cell; the solver
+/// scales by the cell area and subtracts it from the flux residual. By
+/// default friction, bed slope and rotation are registered, and library
+/// users can push their own terms (wind, rainfall, point inflows, ...).
+use super::{FluxScheme, ShallowWaterSolver, State, REST_SPEED};
+use rayon::prelude::*;
+
+/// Names of the terms registered by `ShallowWaterSolver::new`, in order
+const BUILT_IN: [&str; 3] = ["friction", "bed slope", "rotation"];
+
+/// One physical process on the right-hand side of the equations
+pub trait SourceTerm: Send + Sync {
+    /// Short name used in messages
+    fn name(&self) -> &str;
+
+    /// Rate of change (∂h/∂t, ∂(hu)/∂t, ∂(hv)/∂t) of cell `i` in `state`
+    fn evaluate(&self, solver: &ShallowWaterSolver, state: &State, i: usize) -> (f64, f64, f64);
+
+    /// Whether the term also acts on dry cells (e.g. rainfall); otherwise it
+    /// is only evaluated where h >= dry_tolerance
+    fn acts_on_dry_cells(&self) -> bool {
+        false
+    }
+
+    /// Whether the term can be stiff relative to the advective time step
+    /// (friction in thin films, infiltration); with source splitting it is
+    /// integrated after the update instead of in the residual, so it must
+    /// depend on the values of cell `i` only
+    fn stiff(&self) -> bool {
+        false
+    }
+
+    /// Rate λ (1/s) of a term that damps the momentum as ∂(hu)/∂t = -λ hu,
+    /// which the exponential split step integrates as exp(-λτ)
+    fn damping_rate(&self, _solver: &ShallowWaterSolver, _state: &State, _i: usize) -> Option<f64> {
+        None
+    }
+}
+
+/// Bottom friction with the solver's resistance law
+/// Yield-type laws (Voellmy, Bingham) are applied as a split step after the
+/// update instead, see `apply_yield_resistance`.
+pub struct BedFriction;
+
+impl SourceTerm for BedFriction {
+    fn name(&self) -> &str {
+        "friction"
+    }
+
+    fn evaluate(&self, solver: &ShallowWaterSolver, state: &State, i: usize) -> (f64, f64, f64) {
+        if solver.friction_law(i).has_yield_term() {
+            return (0.0, 0.0, 0.0);
+        }
+        let h = state.h[i];
+        let (u, v) = solver.velocity(state, i);
+        let (sf_x, sf_y) = solver.compute_friction_slope(i, h, u, v);
+        let g = solver.constants.gravity;
+        (0.0, -(g * h * sf_x), -(g * h * sf_y))
+    }
+
+    fn stiff(&self) -> bool {
+        true
+    }
+
+    fn damping_rate(&self, solver: &ShallowWaterSolver, state: &State, i: usize) -> Option<f64> {
+        if solver.friction_law(i).has_yield_term() {
+            return Some(0.0);
+        }
+        let (u, v) = solver.velocity(state, i);
+        let speed = u.hypot(v);
+        if speed < REST_SPEED {
+            return Some(0.0);
+        }
+        let (sf_x, sf_y) = solver.compute_friction_slope(i, state.h[i], u, v);
+        Some(solver.constants.gravity * sf_x.hypot(sf_y) / speed)
+    }
+}
+
+/// Topographic source -g h ∇z_b (with the optional slope limit) and the
+/// metric pressure term on the sphere
+/// The central-upwind scheme and the hydrostatic reconstruction of subgrid
+/// bathymetry balance the bed slope in their edge fluxes, so it is zero
+/// there.
+pub struct BedSlope;
+
+impl SourceTerm for BedSlope {
+    fn name(&self) -> &str {
+        "bed slope"
+    }
+
+    fn evaluate(&self, solver: &ShallowWaterSolver, state: &State, i: usize) -> (f64, f64, f64) {
+        if solver.flux_scheme == FluxScheme::CentralUpwind {
+            return (0.0, 0.0, 0.0);
+        }
+        let h = state.h[i];
+        let mut slope = match solver.subgrid() {
+            Some(_) => (0.0, 0.0),
+            None => solver.compute_bed_gradient(i),
+        };
+        if let Some(limit) = &solver.bed_slope_limit {
+            slope = limit.apply(slope, h);
+        }
+        let (px, py) = solver.metric_pressure(i, h);
+        let g = solver.constants.gravity;
+        (0.0, -(g * h * slope.0 - px), -(g * h * slope.1 - py))
+    }
+}
+
+/// Curvature of the east/north basis and Coriolis force on the sphere
+/// (zero on Cartesian meshes)
+pub struct Rotation;
+
+impl SourceTerm for Rotation {
+    fn name(&self) -> &str {
+        "rotation"
+    }
+
+    fn evaluate(&self, solver: &ShallowWaterSolver, state: &State, i: usize) -> (f64, f64, f64) {
+        let (u, v) = solver.velocity(state, i);
+        let (rx, ry) = solver.rotation_source(i, state.h[i], u, v);
+        (0.0, rx, ry)
+    }
+}
+
+/// Uniform surface wind stress τ = ρ_air C_d |W| W, except under an ice
+/// cover that blocks the wind
+#[derive(Debug, Clone, Copy)]
+pub struct WindStress {
+    pub velocity: (f64, f64),  // Wind velocity 10 m above the surface (m/s)
+    pub drag_coefficient: f64, // C_d (-)
+    pub air_density: f64,      // ρ_air (kg/m^3)
+}
+
+impl WindStress {
+    /// Wind with a typical drag coefficient of 1.3e-3 and air at 1.225 kg/m^3
+    pub fn new(velocity: (f64, f64)) -> Self {
+        WindStress {
+            velocity,
+            drag_coefficient: 1.3e-3,
+            air_density: 1.225,
+        }
+    }
+}
+
+impl SourceTerm for WindStress {
+    fn name(&self) -> &str {
+        "wind"
+    }
+
+    fn evaluate(&self, solver: &ShallowWaterSolver, _state: &State, i: usize) -> (f64, f64, f64) {
+        if !solver.exposed_to_wind(i) {
+            return (0.0, 0.0, 0.0);
+        }
+        let (wx, wy) = self.velocity;
+        let speed = (wx * wx + wy * wy).sqrt();
+        let scale = self.air_density * self.drag_coefficient * speed / solver.constants.density;
+        (0.0, scale * wx, scale * wy)
+    }
+}
+
+/// Uniform rainfall adding water at `rate` (m/s) to every cell, dry or wet
+#[derive(Debug, Clone, Copy)]
+pub struct Rainfall {
+    pub rate: f64,
+}
+
+impl SourceTerm for Rainfall {
+    fn name(&self) -> &str {
+        "rainfall"
+    }
+
+    fn evaluate(&self, _solver: &ShallowWaterSolver, _state: &State, _i: usize) -> (f64, f64, f64) {
+        (self.rate, 0.0, 0.0)
+    }
+
+    fn acts_on_dry_cells(&self) -> bool {
+        true
+    }
+}
+
+/// Spatially distributed, time-varying rainfall: `rates[k][i]` (m/s) is the
+/// intensity over cell i at `times[k]` (s), linear in time between frames
+/// and zero before the first and after the last
+#[derive(Debug, Clone)]
+pub struct GriddedRainfall {
+    pub times: Vec<f64>,
+    pub rates: Vec<Vec<f64>>,
+}
+
+impl GriddedRainfall {
+    /// Intensity (m/s) over cell `i` at `time`
+    pub fn rate(&self, time: f64, i: usize) -> f64 {
+        let k = self.times.partition_point(|&t| t <= time);
+        if k == 0 || (k == self.times.len() && time > self.times[k - 1]) {
+            return 0.0;
+        }
+        if k == self.times.len() {
+            return self.rates[k - 1][i];
+        }
+        let w = (time - self.times[k - 1]) / (self.times[k] - self.times[k - 1]);
+        (1.0 - w) * self.rates[k - 1][i] + w * self.rates[k][i]
+    }
+
+    /// Volume (m^3) falling on cells with `areas` over the whole event
+    pub fn total_volume(&self, areas: &[f64]) -> f64 {
+        let frame = |k: usize| -> f64 { self.rates[k].iter().zip(areas).map(|(r, a)| r * a).sum() };
+        self.times
+            .windows(2)
+            .enumerate()
+            .map(|(k, t)| 0.5 * (t[1] - t[0]) * (frame(k) + frame(k + 1)))
+            .sum()
+    }
+}
+
+impl SourceTerm for GriddedRainfall {
+    fn name(&self) -> &str {
+        "gridded rainfall"
+    }
+
+    fn evaluate(&self, solver: &ShallowWaterSolver, _state: &State, i: usize) -> (f64, f64, f64) {
+        (self.rate(solver.time, i), 0.0, 0.0)
+    }
+
+    fn acts_on_dry_cells(&self) -> bool {
+        true
+    }
+}
+
+/// Friction, bed slope and rotation, as registered by `ShallowWaterSolver::new`
+pub fn default_source_terms() -> Vec<Box<dyn SourceTerm>> {
+    vec![
+        Box::new(BedFriction),
+        Box::new(BedSlope),
+        Box::new(Rotation),
+    ]
+}
+
+impl ShallowWaterSolver {
+    /// Whether the registered terms are exactly the built-in ones, which the
+    /// semi-implicit, single-precision and GPU paths hard-code
+    pub fn has_default_source_terms(&self) -> bool {
+        self.source_terms.iter().map(|t| t.name()).eq(BUILT_IN)
+    }
+
+    /// Put `term` in the place of the registered term of the same name (e.g.
+    /// updated forcing), or register it if there is none
+    pub fn replace_source_term(&mut self, term: Box<dyn SourceTerm>) {
+        match self
+            .source_terms
+            .iter()
+            .position(|t| t.name() == term.name())
+        {
+            Some(k) => self.source_terms[k] = term,
+            None => self.source_terms.push(term),
+        }
+    }
+
+    /// Registered terms beyond the built-in ones
+    pub(super) fn extra_source_terms(&self) -> impl Iterator<Item = &dyn SourceTerm> {
+        self.source_terms
+            .iter()
+            .filter(|t| !BUILT_IN.contains(&t.name()))
+            .map(|t| t.as_ref())
+    }
+
+    /// Whether some registered term acts on dry cells
+    pub(super) fn sources_wet_dry_cells(&self) -> bool {
+        self.source_terms.iter().any(|t| t.acts_on_dry_cells())
+    }
+
+    /// Sum of `terms` in cell `i`; dry cells only see terms that act on them
+    pub(super) fn sum_source_terms<'a>(
+        &self,
+        terms: impl Iterator<Item = &'a dyn SourceTerm>,
+        state: &State,
+        i: usize,
+    ) -> (f64, f64, f64) {
+        let wet = state.h[i] >= self.constants.dry_tolerance;
+        terms
+            .filter(|t| wet || t.acts_on_dry_cells())
+            .fold((0.0, 0.0, 0.0), |acc, t| {
+                let (sh, shu, shv) = t.evaluate(self, state, i);
+                (acc.0 + sh, acc.1 + shu, acc.2 + shv)
+            })
+    }
+
+    /// Subtract the area-integrated registered source terms from `residual`
+    /// (the residual is subtracted in the update, so S enters with a minus)
+    /// and return the volume they add per second
+    pub(super) fn add_source_terms(&self, residual: &mut State, state: &State) -> f64 {
+        let start = self.profiler.start();
+        let source = |i: usize| {
+            let area = self.storage_area(i);
+            let terms = self
+                .source_terms
+                .iter()
+                .map(|t| t.as_ref())
+                .filter(|t| !self.is_split(*t));
+            let (sh, shu, shv) = self.sum_source_terms(terms, state, i);
+            (-sh * area, -shu * area, -shv * area)
+        };
+
+        // Parallel computation of source terms (dry cells outside the active
+        // set contribute nothing)
+        let source_contributions: Vec<_> = match &self.active_set {
+            Some(set) => set.cells.par_iter().map(|&i| (i, source(i))).collect(),
+            None => (0..self.mesh.triangles.len())
+                .into_par_iter()
+                .map(|i| (i, source(i)))
+                .collect(),
+        };
+
+        // Apply contributions sequentially (fast, no contention)
+        let mut volume = 0.0;
+        for &(i, (dh, dhu, dhv)) in &source_contributions {
+            residual.h[i] += dh;
+            residual.hu[i] += dhu;
+            residual.hv[i] += dhv;
+            volume -= dh;
+        }
+        self.profiler.record(super::Phase::Sources, start);
+        volume
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    fn lake() -> ShallowWaterSolver {
+        let mesh = TriangularMesh::new_rectangular(6, 6, 10.0, 10.0, TopographyType::Flat);
+        let mut solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Manning { coefficient: 0.03 });
+        solver.state.h.iter_mut().for_each(|h| *h = 1.0);
+        solver
+    }
+
+    /// A point inflow as a library user would write it
+    struct Inflow {
+        cell: usize,
+        discharge: f64, // m^3/s
+    }
+
+    impl SourceTerm for Inflow {
+        fn name(&self) -> &str {
+            "inflow"
+        }
+
+        fn evaluate(
+            &self,
+            solver: &ShallowWaterSolver,
+            _state: &State,
+            i: usize,
+        ) -> (f64, f64, f64) {
+            if i == self.cell {
+                (self.discharge / solver.mesh.triangles[i].area, 0.0, 0.0)
+            } else {
+                (0.0, 0.0, 0.0)
+            }
+        }
+
+        fn acts_on_dry_cells(&self) -> bool {
+            true
+        }
+    }
+
+    fn volume(solver: &ShallowWaterSolver) -> f64 {
+        (0..solver.mesh.triangles.len())
+            .map(|i| solver.state.h[i] * solver.mesh.triangles[i].area)
+            .sum()
+    }
+
+    #[test]
+    fn test_custom_terms_add_mass() {
+        let mut solver = lake();
+        assert!(solver.has_default_source_terms());
+        solver.source_terms.push(Box::new(Inflow {
+            cell: 7,
+            discharge: 2.0,
+        }));
+        solver.source_terms.push(Box::new(Rainfall { rate: 1e-3 }));
+        assert!(!solver.has_default_source_terms());
+        assert!(solver.single_precision_unsupported().is_some());
+
+        let before = volume(&solver);
+        let mut elapsed = 0.0;
+        for _ in 0..20 {
+            solver.step();
+            elapsed += solver.dt;
+        }
+        let expected = before + (2.0 + 1e-3 * 100.0) * elapsed;
+        assert!((volume(&solver) - expected).abs() < 1e-9 * expected);
+    }
+
+    #[test]
+    fn test_wind_drives_lake_downwind() {
+        let mut solver = lake();
+        solver
+            .source_terms
+            .push(Box::new(WindStress::new((20.0, 0.0))));
+        for _ in 0..20 {
+            solver.step();
+        }
+        let momentum: f64 = solver.state.hu.iter().sum();
+        assert!(momentum > 0.0);
+        let cross: f64 = solver.state.hv.iter().sum();
+        assert!(cross.abs() < 0.1 * momentum);
+    }
+}
@@ -0,0 +1,303 @@
+/// Subgrid bathymetry for coarse meshes
+/// Each cell keeps the hypsometric curve of the high-resolution DEM pixels
+/// inside it: the wet fraction a(η) and the mean depth d(η) = V(η)/A at
+/// levels η from its lowest to its highest pixel. The depth of the solver
+/// state is the mean depth (volume per cell area), so mass is conserved as
+/// before, and the free surface η(d) follows from the curve: a coarse cell
+/// crossed by a narrow channel holds water at the channel level long before
+/// its mean bed is flooded. Interior edge fluxes are hydrostatically
+/// reconstructed (Audusse et al.) on the effective bed η(d) - d, which
+/// replaces the bed slope term and keeps water at rest at rest across wet
+/// and dry cells. Friction acts over the wet part with the conveyance depth
+/// d / a, so flow concentrated in the channel is not slowed as if it were
+/// spread over the whole cell. Above its highest pixel a cell is fully wet.
+use super::{EdgeState, FluxScheme, Precision, ShallowWaterSolver, State, TimeIntegrator};
+use crate::mesh::{Edge, TriangularMesh};
+use crate::raster::{pixel_triangles, RasterGrid};
+
+/// Levels of each hypsometric curve
+const LEVELS: usize = 32;
+
+/// Edge flux and the pressure corrections of the left and right cells
+type ReconstructedFlux = ((f64, f64, f64), (f64, f64));
+
+/// Hypsometric curve of one cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hypsometry {
+    pub levels: Vec<f64>,       // Surface elevations, increasing (m)
+    pub wet_fraction: Vec<f64>, // Fraction of the cell area at or below each level
+    pub depth: Vec<f64>,        // Mean depth V/A at each level (m)
+}
+
+/// Subgrid curves of all cells
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subgrid {
+    pub cells: Vec<Hypsometry>,
+}
+
+impl Hypsometry {
+    /// Curve of the bed elevations sampled evenly over a cell
+    pub fn from_samples(samples: &[f64]) -> Self {
+        let lo = samples.iter().copied().fold(f64::INFINITY, f64::min);
+        let hi = samples.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let count = if hi > lo { LEVELS } else { 1 };
+        let n = samples.len() as f64;
+
+        let levels: Vec<f64> = (0..count)
+            .map(|k| lo + (hi - lo) * k as f64 / (count - 1).max(1) as f64)
+            .collect();
+        let wet_fraction = levels
+            .iter()
+            .map(|&level| samples.iter().filter(|&&z| z <= level).count() as f64 / n)
+            .collect();
+        let depth = levels
+            .iter()
+            .map(|&level| samples.iter().map(|&z| (level - z).max(0.0)).sum::<f64>() / n)
+            .collect();
+        Hypsometry {
+            levels,
+            wet_fraction,
+            depth,
+        }
+    }
+
+    /// Free-surface elevation at mean depth `depth`
+    pub fn level(&self, depth: f64) -> f64 {
+        let top = self.levels.len() - 1;
+        if depth >= self.depth[top] {
+            return self.levels[top] + depth - self.depth[top];
+        }
+        interpolate(&self.depth, &self.levels, depth.max(0.0))
+    }
+
+    /// Mean depth at surface elevation `level`
+    pub fn depth_at(&self, level: f64) -> f64 {
+        let top = self.levels.len() - 1;
+        if level >= self.levels[top] {
+            return self.depth[top] + level - self.levels[top];
+        }
+        if level <= self.levels[0] {
+            return 0.0;
+        }
+        interpolate(&self.levels, &self.depth, level)
+    }
+
+    /// Wet fraction at mean depth `depth`
+    pub fn wet_fraction_at(&self, depth: f64) -> f64 {
+        let top = self.levels.len() - 1;
+        if depth >= self.depth[top] {
+            return 1.0;
+        }
+        interpolate(&self.depth, &self.wet_fraction, depth.max(0.0))
+    }
+
+    /// Elevation range of the pixels in the cell (m)
+    pub fn relief(&self) -> f64 {
+        self.levels[self.levels.len() - 1] - self.levels[0]
+    }
+}
+
+/// Piecewise-linear interpolation of `ys` at `x` within the increasing `xs`
+fn interpolate(xs: &[f64], ys: &[f64], x: f64) -> f64 {
+    let k = xs.partition_point(|&xk| xk <= x).clamp(1, xs.len() - 1);
+    let t = (x - xs[k - 1]) / (xs[k] - xs[k - 1]);
+    ys[k - 1] + t * (ys[k] - ys[k - 1])
+}
+
+impl Subgrid {
+    /// Curves from a DEM (row-major elevations on `grid`) of the pixels
+    /// whose centres fall in each cell; cells without a valid pixel keep a
+    /// flat bed at the mesh elevation
+    pub fn from_dem(mesh: &TriangularMesh, grid: &RasterGrid, elevation: &[f64]) -> Self {
+        let mut samples = vec![Vec::new(); mesh.triangles.len()];
+        for (k, tri) in pixel_triangles(mesh, grid).into_iter().enumerate() {
+            if let Some(i) = tri.filter(|_| !elevation[k].is_nan()) {
+                samples[i].push(elevation[k]);
+            }
+        }
+        let cells = samples
+            .iter()
+            .zip(&mesh.triangles)
+            .map(|(samples, tri)| match samples.is_empty() {
+                true => Hypsometry::from_samples(&[tri.z_bed]),
+                false => Hypsometry::from_samples(samples),
+            })
+            .collect();
+        Subgrid { cells }
+    }
+
+    /// Number of cells whose pixels are not all at one elevation
+    pub fn varying_cells(&self) -> usize {
+        self.cells.iter().filter(|c| c.relief() > 0.0).count()
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Use subgrid bathymetry (Rusanov flux, RK2 and finite volumes only)
+    pub fn set_subgrid(&mut self, subgrid: Subgrid) -> Result<(), String> {
+        if self.flux_scheme != FluxScheme::Rusanov {
+            return Err("subgrid bathymetry requires the Rusanov flux".to_string());
+        }
+        if !matches!(self.time_integrator, TimeIntegrator::RungeKutta2) {
+            return Err("subgrid bathymetry requires the RK2 integrator".to_string());
+        }
+        if self.precision != Precision::Double {
+            return Err("subgrid bathymetry requires double precision".to_string());
+        }
+        self.require_finite_volume("subgrid bathymetry")?;
+        if self.porosity.is_some() || self.has_moving_bed() {
+            return Err("subgrid bathymetry does not combine with porosity or moving beds".into());
+        }
+        #[cfg(feature = "gpu")]
+        if self.gpu.is_some() {
+            return Err("subgrid bathymetry is not available on the GPU".to_string());
+        }
+        if subgrid.cells.len() != self.mesh.triangles.len() {
+            return Err("subgrid bathymetry does not match the mesh".to_string());
+        }
+        self.subgrid = Some(subgrid);
+        Ok(())
+    }
+
+    pub fn subgrid(&self) -> Option<&Subgrid> {
+        self.subgrid.as_ref()
+    }
+
+    /// Fill every cell to the surface elevation `level` at rest
+    pub fn set_water_level(&mut self, level: f64) {
+        for i in 0..self.mesh.triangles.len() {
+            self.state.h[i] = match &self.subgrid {
+                Some(subgrid) => subgrid.cells[i].depth_at(level),
+                None => (level - self.mesh.triangles[i].z_bed).max(0.0),
+            };
+            self.state.hu[i] = 0.0;
+            self.state.hv[i] = 0.0;
+        }
+    }
+
+    /// Flux across the interior `edge` from the states hydrostatically
+    /// reconstructed on the higher effective bed, with the pressure
+    /// corrections ½ g (h² - h*²) of the left and right cells; None without
+    /// subgrid bathymetry or on the boundary
+    pub(super) fn subgrid_flux(&self, edge: &Edge, state: &State) -> Option<ReconstructedFlux> {
+        let subgrid = self.subgrid.as_ref()?;
+        let right = edge.right_triangle?;
+        let left = edge.left_triangle;
+        let g = self.constants.gravity;
+        let level = |i: usize| subgrid.cells[i].level(state.h[i]);
+        let bed = (level(left) - state.h[left]).max(level(right) - state.h[right]);
+
+        let side = |i: usize| {
+            let h = state.h[i];
+            let depth = (level(i) - bed).clamp(0.0, h);
+            let (u, v) = self.velocity(state, i);
+            let reconstructed = EdgeState {
+                h: depth,
+                hu: depth * u,
+                hv: depth * v,
+                u,
+                v,
+            };
+            (reconstructed, 0.5 * g * (h * h - depth * depth))
+        };
+        let (l, pressure_l) = side(left);
+        let (r, pressure_r) = side(right);
+        let flux = self
+            .numerical_flux
+            .flux(&l, &r, edge.normal, &self.constants);
+        Some((flux, (pressure_l, pressure_r)))
+    }
+
+    /// Depth the friction of cell `i` acts over: the mean depth `h` spread
+    /// over the wet part of the cell
+    pub(super) fn conveyance_depth(&self, i: usize, h: f64) -> f64 {
+        match &self.subgrid {
+            Some(subgrid) => {
+                let wet = subgrid.cells[i].wet_fraction_at(h);
+                if wet > 0.0 {
+                    h / wet
+                } else {
+                    h
+                }
+            }
+            None => h,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_two_level_hypsometry() {
+        // Half the cell at 0 m and half at 1 m
+        let curve = Hypsometry::from_samples(&[0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(curve.levels.len(), LEVELS);
+        assert!((curve.depth_at(0.5) - 0.25).abs() < 1e-12);
+        assert!((curve.level(0.25) - 0.5).abs() < 1e-12);
+        assert!((curve.wet_fraction_at(0.25) - 0.5).abs() < 1e-12);
+        // Above the highest pixel the cell is fully wet
+        assert!((curve.depth_at(2.0) - 1.5).abs() < 1e-12);
+        assert!((curve.level(1.5) - 2.0).abs() < 1e-12);
+        assert_eq!(curve.wet_fraction_at(1.5), 1.0);
+        assert_eq!(curve.level(0.0), 0.0);
+
+        let flat = Hypsometry::from_samples(&[2.0]);
+        assert_eq!(flat.level(0.3), 2.3);
+        assert_eq!(flat.relief(), 0.0);
+    }
+
+    #[test]
+    fn test_channel_in_coarse_cells() {
+        // 1 m wide, 1 m deep channel along y = 5 m on a 0.25 m DEM, under
+        // a 2 m mesh that cannot resolve it
+        let grid = RasterGrid {
+            x_min: 0.0,
+            y_max: 10.0,
+            cell_size: 0.25,
+            ncols: 80,
+            nrows: 40,
+        };
+        let dem: Vec<f64> = (0..grid.ncols * grid.nrows)
+            .map(|k| {
+                let (_, y) = grid.pixel_center(k % grid.ncols, k / grid.ncols);
+                if (y - 5.0).abs() < 0.5 {
+                    -1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let mesh = TriangularMesh::new_rectangular(11, 6, 20.0, 10.0, TopographyType::Flat);
+        let subgrid = Subgrid::from_dem(&mesh, &grid, &dem);
+        assert!(subgrid.varying_cells() > 0);
+
+        let mut solver =
+            ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::Manning { coefficient: 0.03 });
+        solver.set_subgrid(subgrid).unwrap();
+        // Water only in the channel: the volume is the channel's, and the
+        // channel cells are wet while the mesh bed is dry
+        solver.set_water_level(-0.5);
+        let volume = solver.compute_total_mass();
+        assert!((volume - 20.0 * 0.5).abs() < 0.5, "{}", volume);
+        let channel = solver.locate(10.0, 5.0).unwrap();
+        assert!(solver.state.h[channel] > 0.0);
+        assert!(
+            solver.conveyance_depth(channel, solver.state.h[channel]) > solver.state.h[channel]
+        );
+
+        // The channel water stays at rest and does not spill onto the banks
+        let depths = solver.state.h.clone();
+        for _ in 0..20 {
+            solver.step();
+        }
+        assert!((solver.compute_total_mass() - volume).abs() < 1e-9 * volume);
+        for (i, depth) in depths.iter().enumerate() {
+            assert!((solver.state.h[i] - depth).abs() < 1e-12);
+            assert!(solver.state.hu[i].abs() < 1e-12 && solver.state.hv[i].abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+/// Time step control
+/// The CFL condition bounds the explicit step, but a step that satisfies it
+/// can still drive a depth negative (strong sinks, wetting fronts over steep
+/// beds), which the update would clamp to zero and so lose mass silently.
+/// The controller keeps the step within [min_dt, max_dt], limits its growth
+/// from one step to the next, and retries a step with half the time step
+/// when either RK2 stage leaves a negative depth or a non-finite value. A
+/// step that still fails at min_dt is taken as before, clamped, and counted
+/// as a failure; a CFL step below min_dt is taken without retries.
+use super::{Precision, ShallowWaterSolver, State, TimeIntegrator};
+use rayon::prelude::*;
+
+#[derive(Debug, Clone)]
+pub struct TimestepControl {
+    pub min_dt: f64,       // Smallest step a retry may use (s)
+    pub max_dt: f64,       // Largest step (s)
+    pub max_growth: f64,   // Largest ratio of a step to the previous one
+    pub retries: usize,    // Rejected step attempts so far
+    pub failures: usize,   // Steps taken with an invalid update
+    pub smallest_dt: f64,  // Smallest step taken
+    pub largest_dt: f64,   // Largest step taken
+    previous: Option<f64>, // Last step taken
+}
+
+impl TimestepControl {
+    pub fn new(min_dt: f64, max_dt: f64, max_growth: f64) -> Result<Self, String> {
+        if !(min_dt >= 0.0 && min_dt.is_finite()) {
+            return Err(format!(
+                "minimum time step must be non-negative, got {}",
+                min_dt
+            ));
+        }
+        if max_dt.is_nan() || max_dt <= min_dt {
+            return Err(format!(
+                "maximum time step {} must exceed the minimum {}",
+                max_dt, min_dt
+            ));
+        }
+        if max_growth.is_nan() || max_growth <= 1.0 {
+            return Err(format!(
+                "time step growth must exceed 1, got {}",
+                max_growth
+            ));
+        }
+        Ok(TimestepControl {
+            min_dt,
+            max_dt,
+            max_growth,
+            retries: 0,
+            failures: 0,
+            smallest_dt: f64::INFINITY,
+            largest_dt: 0.0,
+            previous: None,
+        })
+    }
+
+    /// Step to try given the CFL step, and whether it may be retried
+    fn limit(&self, cfl_dt: f64) -> (f64, bool) {
+        let mut dt = cfl_dt.min(self.max_dt);
+        if let Some(previous) = self.previous {
+            dt = dt.min(previous * self.max_growth);
+        }
+        (dt, cfl_dt >= self.min_dt)
+    }
+
+    /// Halved step for a retry, None once min_dt has been tried
+    fn retry(&mut self, dt: f64) -> Option<f64> {
+        if dt <= self.min_dt {
+            return None;
+        }
+        self.retries += 1;
+        Some((0.5 * dt).max(self.min_dt))
+    }
+
+    /// A step `shortened` to land on an output or end time neither limits
+    /// the growth of the next one nor counts as the smallest step
+    fn accept(&mut self, dt: f64, valid: bool, shortened: bool) {
+        if !valid {
+            self.failures += 1;
+        }
+        self.largest_dt = self.largest_dt.max(dt);
+        if !shortened {
+            self.smallest_dt = self.smallest_dt.min(dt);
+            self.previous = Some(dt);
+        }
+    }
+}
+
+impl ShallowWaterSolver {
+    /// Control the time step of the explicit double-precision RK2 step
+    pub fn set_timestep_control(&mut self, control: TimestepControl) -> Result<(), String> {
+        if !matches!(self.time_integrator, TimeIntegrator::RungeKutta2) {
+            return Err("time step control requires the RK2 integrator".to_string());
+        }
+        if self.precision != Precision::Double {
+            return Err("time step control requires double precision".to_string());
+        }
+        self.require_finite_volume("time step control")?;
+        #[cfg(feature = "gpu")]
+        if self.gpu.is_some() {
+            return Err("time step control is not available on the GPU".to_string());
+        }
+        self.timestep_control = Some(control);
+        Ok(())
+    }
+
+    /// Whether the explicit update of `state` by `residual` over `dt` keeps
+    /// every depth non-negative and every value finite
+    pub(super) fn update_is_valid(&self, state: &State, residual: &State, dt: f64) -> bool {
+        let tolerance = self.constants.dry_tolerance;
+        (0..self.mesh.triangles.len()).into_par_iter().all(|i| {
+            let scale = dt / self.storage_area(i);
+            let h = state.h[i] - scale * residual.h[i];
+            let hu = state.hu[i] - scale * residual.hu[i];
+            let hv = state.hv[i] - scale * residual.hv[i];
+            h >= -tolerance && h.is_finite() && hu.is_finite() && hv.is_finite()
+        })
+    }
+
+    /// First step to try and whether it may be retried, from the CFL step
+    pub(super) fn controlled_timestep(&self) -> (f64, bool) {
+        match &self.timestep_control {
+            Some(control) => control.limit(self.dt),
+            None => (self.dt, true),
+        }
+    }
+
+    /// Smaller step after a failed attempt, None if the step must be taken
+    pub(super) fn retry_timestep(&mut self) -> Option<f64> {
+        let dt = self.dt;
+        self.timestep_control.as_mut()?.retry(dt)
+    }
+
+    /// Shorten the step about to be taken to `step_limit`; the next step
+    /// starts again from the unlimited one
+    pub(crate) fn limit_step(&mut self) {
+        if self.dt > self.step_limit {
+            self.unlimited_dt = Some(self.dt);
+            self.dt = self.step_limit;
+        }
+    }
+
+    pub(super) fn accept_timestep(&mut self, valid: bool) {
+        let dt = self.dt;
+        let shortened = self.unlimited_dt.is_some();
+        if let Some(control) = &mut self.timestep_control {
+            control.accept(dt, valid, shortened);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::{FrictionLaw, Rainfall};
+
+    #[test]
+    fn test_retry_instead_of_clamping() {
+        let mesh = TriangularMesh::new_rectangular(10, 10, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.state.h.iter_mut().for_each(|h| *h = 0.05);
+        // Evaporation of 1 cm/s empties the lake at t = 5 s
+        solver.source_terms.push(Box::new(Rainfall { rate: -0.01 }));
+        let control = TimestepControl::new(1e-4, 0.5, 1.5).unwrap();
+        solver.set_timestep_control(control).unwrap();
+
+        let mut previous: Option<f64> = None;
+        while solver.time < 4.9 {
+            solver.step();
+            assert!(solver.dt <= 0.5);
+            if let Some(previous) = previous {
+                assert!(solver.dt <= 1.5 * previous * (1.0 + 1e-12));
+            }
+            previous = Some(solver.dt);
+            // No depth was clamped: the lake drains exactly at the set rate
+            let expected = 0.05 - 0.01 * solver.time;
+            assert!(solver.state.h.iter().all(|h| (h - expected).abs() < 1e-12));
+        }
+        let control = solver.timestep_control.as_ref().unwrap();
+        assert!(control.retries > 0);
+        assert_eq!(control.failures, 0);
+
+        // Once the lake is empty no step down to min_dt is valid
+        for _ in 0..100 {
+            solver.step();
+        }
+        let control = solver.timestep_control.as_ref().unwrap();
+        assert!(control.failures > 0);
+        assert_eq!(control.smallest_dt, 1e-4);
+        assert!(solver.state.h.iter().all(|&h| h == 0.0));
+
+        assert!(TimestepControl::new(0.1, 0.01, 1.5).is_err());
+        assert!(TimestepControl::new(0.0, 1.0, 1.0).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+/// Parameter sweeps
+/// Each swept parameter takes a list of values ("0.3,0.45,0.6") or an evenly
+/// spaced range ("start:stop:count"); the sweep runs every combination and
+/// records conservation errors, the maximum depth and the wall-clock time.
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// One combination of swept parameters (None: value of the base run)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepPoint {
+    pub nx: Option<usize>,
+    pub cfl: Option<f64>,
+    pub manning_n: Option<f64>,
+    pub amplitude: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SweepResult {
+    pub point: SweepPoint,
+    pub cells: usize,
+    pub steps: usize,
+    pub time: f64,          // Simulated time reached (s)
+    pub mass_error: f64,    // Relative mass change (%)
+    pub energy_change: f64, // Relative energy change (%)
+    pub max_depth: f64,     // Largest depth in any cell at any step (m)
+    pub runtime: f64,       // Wall-clock time of the run (s)
+    pub diverged: bool,     // Stopped on a non-finite state
+}
+
+/// Parse "a,b,c" or "start:stop:count" into a list of values
+pub fn parse_values(spec: &str) -> Result<Vec<f64>, String> {
+    let number = |v: &str| {
+        v.trim()
+            .parse::<f64>()
+            .map_err(|_| format!("invalid number '{}'", v.trim()))
+    };
+    if let Some((start, rest)) = spec.split_once(':') {
+        let (stop, count) = rest
+            .split_once(':')
+            .ok_or_else(|| format!("expected start:stop:count but found '{}'", spec))?;
+        let (start, stop) = (number(start)?, number(stop)?);
+        let count: usize = count
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&c| c >= 1)
+            .ok_or_else(|| format!("invalid count '{}'", count.trim()))?;
+        if count == 1 {
+            return Ok(vec![start]);
+        }
+        let step = (stop - start) / (count - 1) as f64;
+        return Ok((0..count).map(|k| start + k as f64 * step).collect());
+    }
+    spec.split(',').map(number).collect()
+}
+
+/// Cartesian product of the swept values; an empty list leaves the
+/// parameter at its base value
+pub fn combinations(
+    nx: &[usize],
+    cfl: &[f64],
+    manning_n: &[f64],
+    amplitude: &[f64],
+) -> Vec<SweepPoint> {
+    fn or_base<T: Copy>(values: &[T]) -> Vec<Option<T>> {
+        if values.is_empty() {
+            vec![None]
+        } else {
+            values.iter().copied().map(Some).collect()
+        }
+    }
+    let mut points = Vec::new();
+    for &nx in &or_base(nx) {
+        for &cfl in &or_base(cfl) {
+            for &manning_n in &or_base(manning_n) {
+                for &amplitude in &or_base(amplitude) {
+                    points.push(SweepPoint {
+                        nx,
+                        cfl,
+                        manning_n,
+                        amplitude,
+                    });
+                }
+            }
+        }
+    }
+    points
+}
+
+/// Results table, one row per combination; base values are left empty
+pub fn write_results<W: Write>(out: &mut W, results: &[SweepResult]) -> io::Result<()> {
+    fn cell<T: ToString>(value: Option<T>) -> String {
+        value.map_or(String::new(), |v| v.to_string())
+    }
+    writeln!(
+        out,
+        "run,nx,cfl,manning_n,amplitude,cells,steps,time,mass_error_pct,energy_change_pct,max_depth,runtime_s,diverged"
+    )?;
+    for (run, r) in results.iter().enumerate() {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{:e},{:e},{},{:.3},{}",
+            run + 1,
+            cell(r.point.nx),
+            cell(r.point.cfl),
+            cell(r.point.manning_n),
+            cell(r.point.amplitude),
+            r.cells,
+            r.steps,
+            r.time,
+            r.mass_error,
+            r.energy_change,
+            r.max_depth,
+            r.runtime,
+            r.diverged
+        )?;
+    }
+    Ok(())
+}
+
+pub fn save_results(filename: &str, results: &[SweepResult]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_results(&mut file, results)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_values_lists_and_ranges() {
+        assert_eq!(parse_values("0.3, 0.45").unwrap(), vec![0.3, 0.45]);
+        assert_eq!(
+            parse_values("10:40:4").unwrap(),
+            vec![10.0, 20.0, 30.0, 40.0]
+        );
+        assert_eq!(parse_values("2:5:1").unwrap(), vec![2.0]);
+        assert!(parse_values("1:2").is_err());
+        assert!(parse_values("1:2:0").is_err());
+    }
+
+    #[test]
+    fn test_combinations_span_cartesian_product() {
+        let points = combinations(&[10, 20], &[0.3, 0.4, 0.5], &[], &[1.0]);
+        assert_eq!(points.len(), 6);
+        assert!(points.iter().all(|p| p.manning_n.is_none()));
+        assert_eq!(points[5].nx, Some(20));
+        assert_eq!(points[5].cfl, Some(0.5));
+
+        let mut table = Vec::new();
+        let result = SweepResult {
+            point: points[0],
+            cells: 162,
+            steps: 10,
+            time: 1.0,
+            mass_error: 0.0,
+            energy_change: -1.5,
+            max_depth: 2.0,
+            runtime: 0.25,
+            diverged: false,
+        };
+        write_results(&mut table, &[result]).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert_eq!(
+            table.lines().nth(1).unwrap(),
+            "1,10,0.3,,1,162,10,1,0e0,-1.5e0,2,0.250,false"
+        );
+    }
+}
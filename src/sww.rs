@@ -0,0 +1,345 @@
+/// ANUGA .sww output
+/// An .sww file is a NetCDF file with the triangles (`volumes`), their
+/// vertex coordinates (`x`, `y`), the static `elevation` and per time step
+/// the `stage`, `xmomentum` and `ymomentum` at the vertices. Vertices are
+/// stored uniquely per triangle (ANUGA's `smoothing = No`), so the
+/// finite-volume cell averages are written unchanged to the three vertices
+/// of their cell. Coordinates are relative to `xllcorner`/`yllcorner`, as
+/// ANUGA stores them, and the UTM zone and false northing come from the
+/// mesh reference system when it is UTM (ANUGA's unknown zone otherwise).
+///
+/// The file is written in the NetCDF classic format with 64-bit offsets
+/// (CDF-2) by a small encoder: fixed variables first, then one record per
+/// time step. The record count and the `*_range` variables in the header
+/// are updated after every time step, so the file is valid while the run
+/// continues.
+use crate::crs::Crs;
+use crate::solver::ShallowWaterSolver;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+const NC_DIMENSION: u32 = 0x0A;
+const NC_VARIABLE: u32 = 0x0B;
+const NC_ATTRIBUTE: u32 = 0x0C;
+const NC_CHAR: u32 = 2;
+const NC_INT: u32 = 4;
+const NC_FLOAT: u32 = 5;
+const NC_DOUBLE: u32 = 6;
+
+/// ANUGA's default for an unknown UTM zone
+const DEFAULT_ZONE: i32 = -1;
+
+/// Dimension indices
+const VOLUMES: usize = 0;
+const VERTICES: usize = 1;
+const RANGE: usize = 2;
+const POINTS: usize = 3;
+const TIMESTEPS: usize = 4;
+
+enum Attribute {
+    Text(&'static str, String),
+    Int(&'static str, i32),
+    Double(&'static str, f64),
+}
+
+/// Variable of the file: name, type, dimension indices
+struct Variable(&'static str, u32, &'static [usize]);
+
+/// Fixed variables, then record variables (those using TIMESTEPS)
+const VARIABLES: [Variable; 12] = [
+    Variable("x", NC_FLOAT, &[POINTS]),
+    Variable("y", NC_FLOAT, &[POINTS]),
+    Variable("volumes", NC_INT, &[VOLUMES, VERTICES]),
+    Variable("elevation", NC_FLOAT, &[POINTS]),
+    Variable("elevation_range", NC_FLOAT, &[RANGE]),
+    Variable("stage_range", NC_FLOAT, &[RANGE]),
+    Variable("xmomentum_range", NC_FLOAT, &[RANGE]),
+    Variable("ymomentum_range", NC_FLOAT, &[RANGE]),
+    Variable("time", NC_DOUBLE, &[TIMESTEPS]),
+    Variable("stage", NC_FLOAT, &[TIMESTEPS, POINTS]),
+    Variable("xmomentum", NC_FLOAT, &[TIMESTEPS, POINTS]),
+    Variable("ymomentum", NC_FLOAT, &[TIMESTEPS, POINTS]),
+];
+const FIRST_RECORD_VARIABLE: usize = 8;
+const STAGE_RANGE: usize = 5;
+
+/// Writes the results of a run to an ANUGA .sww file
+pub struct SwwWriter {
+    file: BufWriter<File>,
+    begin: Vec<u64>, // Offset of every variable
+    record_size: u64,
+    records: u32,
+    ranges: [(f32, f32); 3], // stage, xmomentum, ymomentum
+}
+
+impl SwwWriter {
+    /// Create `path` with the mesh and bed of `solver`, georeferenced by
+    /// the mesh reference system `crs`
+    pub fn create(
+        path: &str,
+        solver: &ShallowWaterSolver,
+        description: &str,
+        crs: Option<Crs>,
+    ) -> io::Result<Self> {
+        let mesh = &solver.mesh;
+        let n_volumes = mesh.triangles.len();
+        let n_points = 3 * n_volumes;
+        let lower_left = (
+            mesh.nodes.iter().map(|n| n.x).fold(f64::INFINITY, f64::min),
+            mesh.nodes.iter().map(|n| n.y).fold(f64::INFINITY, f64::min),
+        );
+        let (zone, false_northing) = match crs {
+            Some(Crs::Utm { zone, north: true }) => (zone as i32, 0),
+            Some(Crs::Utm { zone, north: false }) => (zone as i32, 10_000_000),
+            _ => (DEFAULT_ZONE, 10_000_000),
+        };
+        let attributes = [
+            Attribute::Text("institution", "shallow-water-solver".to_string()),
+            Attribute::Text("description", description.to_string()),
+            Attribute::Text("smoothing", "No".to_string()),
+            Attribute::Text("vertices_are_stored_uniquely", "True".to_string()),
+            Attribute::Int("order", 1),
+            Attribute::Double("starttime", 0.0),
+            Attribute::Double("xllcorner", lower_left.0),
+            Attribute::Double("yllcorner", lower_left.1),
+            Attribute::Int("zone", zone),
+            Attribute::Int("false_easting", 500_000),
+            Attribute::Int("false_northing", false_northing),
+            Attribute::Text("datum", "wgs84".to_string()),
+            Attribute::Text("projection", "UTM".to_string()),
+            Attribute::Text("units", "m".to_string()),
+        ];
+        let dimensions = [
+            ("number_of_volumes", n_volumes),
+            ("number_of_vertices", 3),
+            ("numbers_in_range", 2),
+            ("number_of_points", n_points),
+            ("number_of_timesteps", 0), // Unlimited
+        ];
+
+        // Variable sizes (per record for record variables), padded to 4 bytes
+        let sizes: Vec<u64> = VARIABLES
+            .iter()
+            .map(|Variable(_, nc_type, dims)| {
+                let count: usize = dims
+                    .iter()
+                    .filter(|&&d| d != TIMESTEPS)
+                    .map(|&d| dimensions[d].1)
+                    .product();
+                (count as u64 * type_size(*nc_type)).next_multiple_of(4)
+            })
+            .collect();
+        let header_len =
+            header(&dimensions, &attributes, &sizes, &vec![0; sizes.len()]).len() as u64;
+        let mut begin = Vec::with_capacity(sizes.len());
+        let mut offset = header_len;
+        for size in &sizes {
+            begin.push(offset);
+            offset += size;
+        }
+        let record_size = sizes[FIRST_RECORD_VARIABLE..].iter().sum();
+
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&header(&dimensions, &attributes, &sizes, &begin))?;
+        let vertices = || mesh.triangles.iter().flat_map(|t| t.nodes);
+        let x: Vec<f64> = vertices().map(|n| mesh.nodes[n].x - lower_left.0).collect();
+        let y: Vec<f64> = vertices().map(|n| mesh.nodes[n].y - lower_left.1).collect();
+        let volumes: Vec<u8> = (0..n_points as i32).flat_map(i32::to_be_bytes).collect();
+        let elevation = per_vertex(mesh.triangles.iter().map(|t| t.z_bed));
+        file.write_all(&be_floats(&x))?;
+        file.write_all(&be_floats(&y))?;
+        file.write_all(&volumes)?;
+        file.write_all(&be_floats(&elevation))?;
+        for range in [range(&elevation), EMPTY_RANGE, EMPTY_RANGE, EMPTY_RANGE] {
+            file.write_all(&be_floats(&[range.0 as f64, range.1 as f64]))?;
+        }
+        file.flush()?;
+        Ok(SwwWriter {
+            file,
+            begin,
+            record_size,
+            records: 0,
+            ranges: [EMPTY_RANGE; 3],
+        })
+    }
+
+    /// Append the current state of `solver` as a time step
+    pub fn write(&mut self, solver: &ShallowWaterSolver) -> io::Result<()> {
+        let triangles = &solver.mesh.triangles;
+        let state = &solver.state;
+        let stage = per_vertex(triangles.iter().zip(&state.h).map(|(t, h)| t.z_bed + h));
+        let xmomentum = per_vertex(state.hu.iter().copied());
+        let ymomentum = per_vertex(state.hv.iter().copied());
+
+        let start = self.begin[FIRST_RECORD_VARIABLE] + self.records as u64 * self.record_size;
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.write_all(&solver.time.to_be_bytes())?;
+        for (values, range) in [stage, xmomentum, ymomentum].iter().zip(&mut self.ranges) {
+            self.file.write_all(&be_floats(values))?;
+            let (low, high) = self::range(values);
+            *range = (range.0.min(low), range.1.max(high));
+        }
+        self.records += 1;
+
+        // Header: record count and value ranges
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&self.records.to_be_bytes())?;
+        for (k, range) in self.ranges.iter().enumerate() {
+            self.file
+                .seek(SeekFrom::Start(self.begin[STAGE_RANGE + k]))?;
+            self.file
+                .write_all(&be_floats(&[range.0 as f64, range.1 as f64]))?;
+        }
+        self.file.flush()
+    }
+}
+
+const EMPTY_RANGE: (f32, f32) = (f32::INFINITY, f32::NEG_INFINITY);
+
+/// Cell values repeated for the three vertices of each cell
+fn per_vertex(values: impl Iterator<Item = f64>) -> Vec<f64> {
+    values.flat_map(|v| [v; 3]).collect()
+}
+
+fn range(values: &[f64]) -> (f32, f32) {
+    values.iter().fold(EMPTY_RANGE, |(low, high), &v| {
+        (low.min(v as f32), high.max(v as f32))
+    })
+}
+
+fn type_size(nc_type: u32) -> u64 {
+    match nc_type {
+        NC_CHAR => 1,
+        NC_INT | NC_FLOAT => 4,
+        _ => 8,
+    }
+}
+
+/// NetCDF classic header (CDF-2) with the record count set to zero
+fn header(
+    dimensions: &[(&str, usize)],
+    attributes: &[Attribute],
+    sizes: &[u64],
+    begin: &[u64],
+) -> Vec<u8> {
+    let mut out = b"CDF\x02".to_vec();
+    let int = |out: &mut Vec<u8>, value: u32| out.extend_from_slice(&value.to_be_bytes());
+    let name = |out: &mut Vec<u8>, text: &str| {
+        int(out, text.len() as u32);
+        out.extend_from_slice(text.as_bytes());
+        out.resize(out.len().next_multiple_of(4), 0);
+    };
+    int(&mut out, 0);
+
+    int(&mut out, NC_DIMENSION);
+    int(&mut out, dimensions.len() as u32);
+    for (dim_name, length) in dimensions {
+        name(&mut out, dim_name);
+        int(&mut out, *length as u32);
+    }
+
+    int(&mut out, NC_ATTRIBUTE);
+    int(&mut out, attributes.len() as u32);
+    for attribute in attributes {
+        match attribute {
+            Attribute::Text(key, value) => {
+                name(&mut out, key);
+                int(&mut out, NC_CHAR);
+                name(&mut out, value);
+            }
+            Attribute::Int(key, value) => {
+                name(&mut out, key);
+                int(&mut out, NC_INT);
+                int(&mut out, 1);
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+            Attribute::Double(key, value) => {
+                name(&mut out, key);
+                int(&mut out, NC_DOUBLE);
+                int(&mut out, 1);
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+
+    int(&mut out, NC_VARIABLE);
+    int(&mut out, VARIABLES.len() as u32);
+    for (k, Variable(var_name, nc_type, dims)) in VARIABLES.iter().enumerate() {
+        name(&mut out, var_name);
+        int(&mut out, dims.len() as u32);
+        for &d in *dims {
+            int(&mut out, d as u32);
+        }
+        int(&mut out, 0); // No variable attributes
+        int(&mut out, 0);
+        int(&mut out, *nc_type);
+        int(&mut out, sizes[k].min(u32::MAX as u64) as u32);
+        out.extend_from_slice(&begin[k].to_be_bytes());
+    }
+    out
+}
+
+fn be_floats(values: &[f64]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|&v| (v as f32).to_be_bytes())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_sww_layout() {
+        let mesh = TriangularMesh::new_rectangular(4, 3, 3.0, 2.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_dam_break(1.5);
+        let path = std::env::temp_dir().join(format!("swe_sww_{}.sww", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut writer = SwwWriter::create(path, &solver, "dam break", None).unwrap();
+        writer.write(&solver).unwrap();
+        solver.time = 0.5;
+        solver.state.hu[11] = -0.25;
+        writer.write(&solver).unwrap();
+        let (begin, record_size) = (writer.begin.clone(), writer.record_size);
+        drop(writer);
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let n_points = 3 * solver.mesh.triangles.len();
+        assert_eq!(&bytes[..8], b"CDF\x02\x00\x00\x00\x02");
+        assert_eq!(record_size, 8 + 3 * 4 * n_points as u64);
+        assert_eq!(
+            bytes.len() as u64,
+            begin[FIRST_RECORD_VARIABLE] + 2 * record_size
+        );
+        let float = |offset: u64| {
+            let o = offset as usize;
+            f32::from_be_bytes(bytes[o..o + 4].try_into().unwrap())
+        };
+        let double = |offset: u64| {
+            let o = offset as usize;
+            f64::from_be_bytes(bytes[o..o + 8].try_into().unwrap())
+        };
+        // Second record: time, then the stage of the first vertex (upstream)
+        assert_eq!(double(begin[8] + record_size), 0.5);
+        assert_eq!(float(begin[9] + record_size), 2.0);
+        let xmomentum = begin[10] + record_size;
+        assert_eq!(float(xmomentum + 4 * 3 * 11 + 8), -0.25);
+        assert_eq!((float(begin[6]), float(begin[6] + 4)), (-0.25, 0.0));
+        // The vertices of the last triangle
+        let last = solver.mesh.triangles.last().unwrap();
+        let n = n_points as u64 - 1;
+        assert_eq!(
+            float(begin[0] + 4 * n),
+            solver.mesh.nodes[last.nodes[2]].x as f32
+        );
+        assert_eq!(
+            float(begin[1] + 4 * n),
+            solver.mesh.nodes[last.nodes[2]].y as f32
+        );
+    }
+}
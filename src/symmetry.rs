@@ -0,0 +1,224 @@
+/// Symmetry verification
+/// A setup that is symmetric under a mirror or a rotation of the domain
+/// (mesh, bed, initial state and boundaries) must stay symmetric, and the
+/// scheme keeps it so up to rounding. Any larger asymmetry points at a bug in
+/// edge normals, neighbour mapping or reductions that no conservation check
+/// sees. Every cell is paired with the cell its centroid maps to under the
+/// symmetry about the centre of the mesh bounding box, and the depths and
+/// the correspondingly transformed momenta of the pairs are compared.
+/// Symmetries act on the mesh coordinates (degrees on spherical meshes).
+use crate::mesh::{PointLocator, TriangularMesh};
+use crate::solver::ShallowWaterSolver;
+use rayon::prelude::*;
+
+/// Relative tolerance on the position and area of the mirror cell
+const MATCH_TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    MirrorX,      // x -> -x
+    MirrorY,      // y -> -y
+    Point,        // Rotation by 180°
+    Diagonal,     // Mirror in the line x = y through the centre
+    AntiDiagonal, // Mirror in the line x = -y through the centre
+}
+
+impl Symmetry {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "x" | "mirror-x" => Ok(Symmetry::MirrorX),
+            "y" | "mirror-y" => Ok(Symmetry::MirrorY),
+            "point" | "rotation" => Ok(Symmetry::Point),
+            "diagonal" => Ok(Symmetry::Diagonal),
+            "anti-diagonal" => Ok(Symmetry::AntiDiagonal),
+            other => Err(format!(
+                "unknown symmetry '{}' (expected x, y, point, diagonal, anti-diagonal)",
+                other
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Symmetry::MirrorX => "x",
+            Symmetry::MirrorY => "y",
+            Symmetry::Point => "point",
+            Symmetry::Diagonal => "diagonal",
+            Symmetry::AntiDiagonal => "anti-diagonal",
+        }
+    }
+
+    /// Image of the offset (dx, dy) from the centre, or of a vector
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        match self {
+            Symmetry::MirrorX => (-x, y),
+            Symmetry::MirrorY => (x, -y),
+            Symmetry::Point => (-x, -y),
+            Symmetry::Diagonal => (y, x),
+            Symmetry::AntiDiagonal => (-y, -x),
+        }
+    }
+}
+
+/// Parse a comma-separated symmetry list such as "x,y"
+pub fn parse_symmetries(list: &str) -> Result<Vec<Symmetry>, String> {
+    let mut symmetries = Vec::new();
+    for name in list.split(',').filter(|s| !s.trim().is_empty()) {
+        let symmetry = Symmetry::parse(name)?;
+        if !symmetries.contains(&symmetry) {
+            symmetries.push(symmetry);
+        }
+    }
+    if symmetries.is_empty() {
+        return Err("no symmetry given".to_string());
+    }
+    Ok(symmetries)
+}
+
+/// Differences between the state and its image
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Asymmetry {
+    pub depth_max: f64,    // L∞ of the depth difference (m)
+    pub depth_rms: f64,    // Area-weighted RMS of the depth difference (m)
+    pub momentum_max: f64, // L∞ of the momentum difference (m^2/s)
+}
+
+impl Asymmetry {
+    /// Largest of each norm
+    pub fn max(self, other: Asymmetry) -> Asymmetry {
+        Asymmetry {
+            depth_max: self.depth_max.max(other.depth_max),
+            depth_rms: self.depth_rms.max(other.depth_rms),
+            momentum_max: self.momentum_max.max(other.momentum_max),
+        }
+    }
+}
+
+/// Cells paired by a symmetry
+pub struct SymmetryCheck {
+    pub symmetry: Symmetry,
+    pub worst: Asymmetry, // Largest of each norm observed
+    mirror: Vec<usize>,   // Image of every cell
+}
+
+impl SymmetryCheck {
+    /// Pair the cells of `mesh`; fails if the mesh is not symmetric
+    pub fn new(mesh: &TriangularMesh, symmetry: Symmetry) -> Result<Self, String> {
+        let (mut x_min, mut x_max) = (f64::INFINITY, f64::NEG_INFINITY);
+        let (mut y_min, mut y_max) = (f64::INFINITY, f64::NEG_INFINITY);
+        for node in &mesh.nodes {
+            x_min = x_min.min(node.x);
+            x_max = x_max.max(node.x);
+            y_min = y_min.min(node.y);
+            y_max = y_max.max(node.y);
+        }
+        let center = (0.5 * (x_min + x_max), 0.5 * (y_min + y_max));
+        let scale = (x_max - x_min).max(y_max - y_min);
+        let locator = PointLocator::new(mesh);
+        let mirror = mesh
+            .triangles
+            .par_iter()
+            .enumerate()
+            .map(|(i, tri)| {
+                let (dx, dy) =
+                    symmetry.apply((tri.centroid.0 - center.0, tri.centroid.1 - center.1));
+                let image = (center.0 + dx, center.1 + dy);
+                locator
+                    .nearest(mesh, image.0, image.1)
+                    .filter(|&j| {
+                        let other = &mesh.triangles[j];
+                        let (dx, dy) = (other.centroid.0 - image.0, other.centroid.1 - image.1);
+                        dx.hypot(dy) <= MATCH_TOLERANCE * scale
+                            && (other.area - tri.area).abs() <= MATCH_TOLERANCE * tri.area
+                    })
+                    .ok_or_else(|| {
+                        format!(
+                            "the mesh is not symmetric under '{}': no cell mirrors cell {} at \
+                             ({:.6}, {:.6})",
+                            symmetry.name(),
+                            i,
+                            tri.centroid.0,
+                            tri.centroid.1
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(SymmetryCheck {
+            symmetry,
+            worst: Asymmetry::default(),
+            mirror,
+        })
+    }
+
+    /// Asymmetry of the current state, also kept in `worst`
+    pub fn observe(&mut self, solver: &ShallowWaterSolver) -> Asymmetry {
+        let asymmetry = self.measure(solver);
+        self.worst = self.worst.max(asymmetry);
+        asymmetry
+    }
+
+    /// Asymmetry of the current state of `solver`
+    pub fn measure(&self, solver: &ShallowWaterSolver) -> Asymmetry {
+        let state = &solver.state;
+        let triangles = &solver.mesh.triangles;
+        let (depth_max, momentum_max, square, area) = (0..triangles.len())
+            .into_par_iter()
+            .map(|i| {
+                let j = self.mirror[i];
+                let dh = (state.h[i] - state.h[j]).abs();
+                let (hu, hv) = self.symmetry.apply((state.hu[i], state.hv[i]));
+                let dq = (hu - state.hu[j]).hypot(hv - state.hv[j]);
+                let area = triangles[i].area;
+                (dh, dq, area * dh * dh, area)
+            })
+            .reduce(
+                || (0.0, 0.0, 0.0, 0.0),
+                |a, b| (a.0.max(b.0), a.1.max(b.1), a.2 + b.2, a.3 + b.3),
+            );
+        Asymmetry {
+            depth_max,
+            depth_rms: if area > 0.0 {
+                (square / area).sqrt()
+            } else {
+                0.0
+            },
+            momentum_max,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::TopographyType;
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_circular_wave_stays_symmetric() {
+        let mesh = TriangularMesh::new_rectangular(21, 21, 10.0, 10.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_circular_wave((5.0, 5.0), 2.0, 0.2);
+        // The diagonals of the structured mesh all run the same way
+        assert!(SymmetryCheck::new(&solver.mesh, Symmetry::MirrorX).is_err());
+        let checks: Vec<SymmetryCheck> = [Symmetry::Point, Symmetry::Diagonal]
+            .into_iter()
+            .map(|symmetry| SymmetryCheck::new(&solver.mesh, symmetry).unwrap())
+            .collect();
+        for _ in 0..50 {
+            solver.step();
+        }
+        let wave = solver.state.hu.iter().fold(0.0f64, |m, q| m.max(q.abs()));
+        assert!(wave > 0.01);
+        for check in &checks {
+            let asymmetry = check.measure(&solver);
+            assert!(asymmetry.depth_max < 1e-12, "{:?}", asymmetry);
+            assert!(asymmetry.momentum_max < 1e-12, "{:?}", asymmetry);
+        }
+
+        // A perturbed cell is seen at once
+        solver.state.h[7] += 1e-3;
+        let asymmetry = checks[0].measure(&solver);
+        assert!((asymmetry.depth_max - 1e-3).abs() < 1e-9);
+        assert!(asymmetry.depth_rms > 0.0 && asymmetry.depth_rms < 1e-3);
+    }
+}
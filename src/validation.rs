@@ -0,0 +1,458 @@
+/// Verification benchmarks with analytical solutions
+/// Each case builds its own mesh, runs the solver configured by the caller
+/// (flux, gradients, integrator, precision) and measures one error metric
+/// against the exact solution at the time the run reached. A case passes when
+/// the metric is finite and within its tolerance. The error tolerances leave
+/// about a factor of two over the default Rusanov flux, so a failure points
+/// at a regression in the numerics rather than at discretisation error. The
+/// lake at rest over a bump is only preserved by the well-balanced
+/// central-upwind flux.
+use crate::mesh::{Edge, TopographyType, TriangularMesh};
+use crate::simulation::Simulation;
+use crate::solver::{wall_flux, BoundaryClosure, EdgeState, ReflectiveWall, ShallowWaterSolver};
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Builds a solver for a benchmark mesh
+pub type SolverFactory<'a> = dyn Fn(TriangularMesh) -> ShallowWaterSolver + 'a;
+
+pub struct Case {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub metric: &'static str,
+    pub tolerance: f64,
+    run: fn(&SolverFactory) -> f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub name: &'static str,
+    pub metric: &'static str,
+    pub value: f64,
+    pub tolerance: f64,
+    pub passed: bool,
+    pub runtime: f64, // Wall-clock time (s)
+}
+
+/// The benchmark battery, in the order it is run
+pub const CASES: [Case; 6] = [
+    Case {
+        name: "lake-at-rest",
+        description: "Still water over a submerged Gaussian bump stays at rest",
+        metric: "max speed (m/s)",
+        tolerance: 1e-10,
+        run: lake_at_rest,
+    },
+    Case {
+        name: "mass-conservation",
+        description: "Total mass of a circular wave in a closed basin",
+        metric: "relative mass change",
+        tolerance: 1e-12,
+        run: mass_conservation,
+    },
+    Case {
+        name: "stoker",
+        description: "Dam break onto a wet bed (Stoker): rarefaction, plateau and bore",
+        metric: "relative L1 depth error",
+        tolerance: 0.01,
+        run: stoker,
+    },
+    Case {
+        name: "ritter",
+        description: "Dam break onto a dry bed (Ritter): rarefaction with a wet/dry front",
+        metric: "relative L1 depth error",
+        tolerance: 0.02,
+        run: ritter,
+    },
+    Case {
+        name: "seiche",
+        description: "Linear standing wave in a closed basin after one period",
+        metric: "relative L2 surface error",
+        tolerance: 0.05,
+        run: seiche,
+    },
+    Case {
+        name: "oblique-jump",
+        description: "Supercritical channel flow (Fr 2.74) deflected by an 8.95 degree wall",
+        metric: "max relative error of jump angle and depth ratio",
+        tolerance: 0.03,
+        run: oblique_jump,
+    },
+];
+
+/// Parse a comma-separated list of case names ("all" selects every case)
+pub fn parse_cases(spec: &str) -> Result<Vec<&'static Case>, String> {
+    if spec.trim() == "all" {
+        return Ok(CASES.iter().collect());
+    }
+    spec.split(',')
+        .map(|name| {
+            let name = name.trim();
+            CASES.iter().find(|case| case.name == name).ok_or_else(|| {
+                let names: Vec<&str> = CASES.iter().map(|case| case.name).collect();
+                format!("unknown case '{}' (expected {})", name, names.join(", "))
+            })
+        })
+        .collect()
+}
+
+impl Case {
+    pub fn run(&self, factory: &SolverFactory) -> Outcome {
+        let start = std::time::Instant::now();
+        let value = (self.run)(factory);
+        Outcome {
+            name: self.name,
+            metric: self.metric,
+            value,
+            tolerance: self.tolerance,
+            passed: value.is_finite() && value <= self.tolerance,
+            runtime: start.elapsed().as_secs_f64(),
+        }
+    }
+}
+
+/// Table of outcomes: case, metric, value, tolerance, passed, runtime
+pub fn save_outcomes(filename: &str, outcomes: &[Outcome]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    writeln!(file, "case,metric,value,tolerance,passed,runtime_s")?;
+    for outcome in outcomes {
+        writeln!(
+            file,
+            "{},{},{:e},{:e},{},{:.3}",
+            outcome.name,
+            outcome.metric,
+            outcome.value,
+            outcome.tolerance,
+            outcome.passed,
+            outcome.runtime
+        )?;
+    }
+    file.flush()
+}
+
+fn run_until(solver: &mut ShallowWaterSolver, final_time: f64) {
+    Simulation::until(solver, final_time)
+        .run()
+        .expect("validation cases have no couplings to fail");
+}
+
+fn max_speed(solver: &ShallowWaterSolver) -> f64 {
+    let dry = solver.constants.dry_tolerance;
+    (0..solver.state.h.len())
+        .filter(|&i| solver.state.h[i] > dry)
+        .map(|i| solver.state.hu[i].hypot(solver.state.hv[i]) / solver.state.h[i])
+        .fold(0.0, f64::max)
+}
+
+/// Area-weighted L1 error of the depth relative to the L1 norm of the exact depth
+fn relative_l1(solver: &ShallowWaterSolver, exact: impl Fn(f64) -> f64) -> f64 {
+    let (mut error, mut norm) = (0.0, 0.0);
+    for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+        let h = exact(tri.centroid.0);
+        error += (solver.state.h[i] - h).abs() * tri.area;
+        norm += h * tri.area;
+    }
+    error / norm
+}
+
+fn still_water(solver: &mut ShallowWaterSolver, level: f64) {
+    for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+        solver.state.h[i] = (level - tri.z_bed).max(0.0);
+    }
+    solver.state.hu.fill(0.0);
+    solver.state.hv.fill(0.0);
+}
+
+fn lake_at_rest(factory: &SolverFactory) -> f64 {
+    let topography = TopographyType::Gaussian {
+        center: (5.0, 5.0),
+        amplitude: 0.5,
+        width: 1.5,
+    };
+    let mut solver = factory(TriangularMesh::new_rectangular(
+        31, 31, 10.0, 10.0, topography,
+    ));
+    still_water(&mut solver, 1.0);
+    run_until(&mut solver, 2.0);
+    max_speed(&solver)
+}
+
+fn mass_conservation(factory: &SolverFactory) -> f64 {
+    let mesh = TriangularMesh::new_rectangular(41, 41, 10.0, 10.0, TopographyType::Flat);
+    let mut solver = factory(mesh);
+    solver.set_circular_wave((5.0, 5.0), 2.0, 0.5);
+    let initial = solver.compute_total_mass();
+    run_until(&mut solver, 5.0);
+    ((solver.compute_total_mass() - initial) / initial).abs()
+}
+
+/// Depth of a dam break from `h_left` onto `h_right` (0 for a dry bed) at
+/// distance `x` from the dam, time `t`
+pub fn dam_break_depth(h_left: f64, h_right: f64, g: f64, x: f64, t: f64) -> f64 {
+    let c_left = (g * h_left).sqrt();
+    if x <= -c_left * t {
+        return h_left;
+    }
+    let rarefaction = |x: f64| (2.0 * c_left - x / t).powi(2) / (9.0 * g);
+    if h_right <= 0.0 {
+        return if x < 2.0 * c_left * t {
+            rarefaction(x)
+        } else {
+            0.0
+        };
+    }
+
+    // Middle state: the rarefaction and the bore give the same velocity
+    let velocity_gap = |h: f64| {
+        2.0 * (c_left - (g * h).sqrt())
+            - (h - h_right) * (g * (h + h_right) / (2.0 * h * h_right)).sqrt()
+    };
+    let (mut low, mut high) = (h_right, h_left);
+    for _ in 0..100 {
+        let mid = 0.5 * (low + high);
+        if velocity_gap(mid) > 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let h_middle = 0.5 * (low + high);
+    let u_middle = 2.0 * (c_left - (g * h_middle).sqrt());
+    let bore_speed = u_middle * h_middle / (h_middle - h_right);
+    if x < (u_middle - (g * h_middle).sqrt()) * t {
+        rarefaction(x)
+    } else if x < bore_speed * t {
+        h_middle
+    } else {
+        h_right
+    }
+}
+
+/// Dam break at x = 50 m in a 100 m channel, compared before any wave
+/// reaches a wall
+fn dam_break(factory: &SolverFactory, h_right: f64, final_time: f64) -> f64 {
+    let mesh = TriangularMesh::new_rectangular(401, 3, 100.0, 0.5, TopographyType::Flat);
+    let mut solver = factory(mesh);
+    for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+        solver.state.h[i] = if tri.centroid.0 < 50.0 { 1.0 } else { h_right };
+    }
+    run_until(&mut solver, final_time);
+    let (g, t) = (solver.constants.gravity, solver.time);
+    relative_l1(&solver, |x| dam_break_depth(1.0, h_right, g, x - 50.0, t))
+}
+
+fn stoker(factory: &SolverFactory) -> f64 {
+    dam_break(factory, 0.5, 6.0)
+}
+
+fn ritter(factory: &SolverFactory) -> f64 {
+    dam_break(factory, 0.0, 4.0)
+}
+
+/// Surface a cos(pi x / L) over depth 1 returns after the period 2L / sqrt(gh)
+fn seiche(factory: &SolverFactory) -> f64 {
+    let (length, amplitude) = (10.0, 0.01);
+    let mesh = TriangularMesh::new_rectangular(201, 3, length, 0.5, TopographyType::Flat);
+    let mut solver = factory(mesh);
+    let g = solver.constants.gravity;
+    for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+        solver.state.h[i] = 1.0 + amplitude * (PI * tri.centroid.0 / length).cos();
+    }
+    run_until(&mut solver, 2.0 * length / g.sqrt());
+    let omega = PI / length * g.sqrt();
+    let (mut error, mut norm) = (0.0, 0.0);
+    for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+        let eta = amplitude * (PI * tri.centroid.0 / length).cos() * (omega * solver.time).cos();
+        error += (solver.state.h[i] - 1.0 - eta).powi(2) * tri.area;
+        norm += eta.powi(2) * tri.area;
+    }
+    (error / norm).sqrt()
+}
+
+/// Angle β (radians) and depth ratio h2/h1 of the oblique jump raised by a
+/// wall deflected by `theta` (radians) into flow of Froude number `froude`
+pub fn oblique_jump_solution(froude: f64, theta: f64) -> (f64, f64) {
+    let root = |beta: f64| (1.0 + 8.0 * (froude * beta.sin()).powi(2)).sqrt();
+    let deflection = |beta: f64| {
+        let tan = beta.tan();
+        (tan * (root(beta) - 3.0) / (2.0 * tan * tan + root(beta) - 1.0)).atan()
+    };
+    // The weak jump lies between the Mach angle and the maximum deflection
+    let (mut low, mut high) = ((1.0 / froude).asin(), (1.0 / froude).asin());
+    let mut best = deflection(high);
+    for k in 1..=1000 {
+        let beta = (1.0 / froude).asin() + (0.5 * PI - (1.0 / froude).asin()) * k as f64 / 1000.0;
+        if deflection(beta) > best {
+            best = deflection(beta);
+            high = beta;
+        }
+    }
+    for _ in 0..100 {
+        let mid = 0.5 * (low + high);
+        if deflection(mid) < theta {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let beta = 0.5 * (low + high);
+    (beta, 0.5 * (root(beta) - 1.0))
+}
+
+/// Supercritical inflow on the west, free outflow on the east, walls elsewhere
+struct ChannelBoundary {
+    inflow: EdgeState,
+    x_in: f64,
+    x_out: f64,
+}
+
+impl BoundaryClosure for ChannelBoundary {
+    fn name(&self) -> &str {
+        "channel"
+    }
+
+    fn ghost(&self, solver: &ShallowWaterSolver, edge: &Edge, inner: &EdgeState) -> EdgeState {
+        let x = edge.nodes.map(|n| solver.mesh.nodes[n].x);
+        if x.iter().all(|&x| (x - self.x_in).abs() < 1e-9) {
+            self.inflow
+        } else if x.iter().all(|&x| (x - self.x_out).abs() < 1e-9) {
+            *inner
+        } else {
+            ReflectiveWall.ghost(solver, edge, inner)
+        }
+    }
+
+    fn boundary_flux(
+        &self,
+        solver: &ShallowWaterSolver,
+        edge: &Edge,
+        inner: &EdgeState,
+    ) -> Option<(f64, f64, f64)> {
+        let x = edge.nodes.map(|n| solver.mesh.nodes[n].x);
+        let open = [self.x_in, self.x_out]
+            .iter()
+            .any(|end| x.iter().all(|&x| (x - end).abs() < 1e-9));
+        (!open).then(|| wall_flux(inner, edge.normal, &solver.constants))
+    }
+}
+
+/// Channel 40 m x 30 m whose south wall turns by 8.95° at x = 10 m, fed with
+/// 1 m of water at 8.57 m/s (the classic test of Hager et al.); the jump
+/// angle and the depth behind it are measured once the flow is steady
+fn oblique_jump(factory: &SolverFactory) -> f64 {
+    let (length, width, corner) = (40.0, 30.0, 10.0);
+    let (h_in, u_in, theta) = (1.0, 8.57, 8.95f64.to_radians());
+    let base = TriangularMesh::new_rectangular(81, 61, length, width, TopographyType::Flat);
+    let nodes = base
+        .nodes
+        .iter()
+        .map(|node| {
+            let floor = (node.x - corner).max(0.0) * theta.tan();
+            crate::mesh::Node {
+                y: floor + node.y * (width - floor) / width,
+                ..node.clone()
+            }
+        })
+        .collect();
+    let triangles: Vec<[usize; 3]> = base.triangles.iter().map(|t| t.nodes).collect();
+    let mesh = TriangularMesh::from_triangles(nodes, &triangles, base.coordinate_system)
+        .expect("mapped channel mesh is valid");
+
+    let mut solver = factory(mesh);
+    let g = solver.constants.gravity;
+    solver.boundary_closure = Box::new(ChannelBoundary {
+        inflow: EdgeState::from_velocity(h_in, u_in, 0.0),
+        x_in: 0.0,
+        x_out: length,
+    });
+    solver.state.h.fill(h_in);
+    solver.state.hu.fill(h_in * u_in);
+    solver.state.hv.fill(0.0);
+    run_until(&mut solver, 8.0);
+
+    let (beta, ratio) = oblique_jump_solution(u_in / (g * h_in).sqrt(), theta);
+    // Depth between the wall and the jump, away from both
+    let (mut depth, mut area) = (0.0, 0.0);
+    for (i, tri) in solver.mesh.triangles.iter().enumerate() {
+        let (dx, dy) = (tri.centroid.0 - corner, tri.centroid.1);
+        let angle = dy.atan2(dx);
+        if dx > 10.0 && angle > theta + 0.05 && angle < beta - 0.05 {
+            depth += solver.state.h[i] * tri.area;
+            area += tri.area;
+        }
+    }
+    let ratio_error = (depth / area / h_in / ratio - 1.0).abs();
+    // Jump position: highest cell deeper than the mean of both sides, in
+    // slices across the channel
+    let threshold = 0.5 * h_in * (1.0 + ratio);
+    let slices = [12.0, 16.0, 20.0, 24.0, 28.0];
+    let mean_angle = slices
+        .iter()
+        .map(|&x| {
+            let front = solver
+                .mesh
+                .triangles
+                .iter()
+                .enumerate()
+                .filter(|(i, tri)| {
+                    (tri.centroid.0 - corner - x).abs() < 0.5 && solver.state.h[*i] > threshold
+                })
+                .map(|(_, tri)| tri.centroid.1)
+                .fold(0.0, f64::max);
+            front.atan2(x)
+        })
+        .sum::<f64>()
+        / slices.len() as f64;
+    ratio_error.max((mean_angle / beta - 1.0).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{FluxScheme, FrictionLaw};
+
+    #[test]
+    fn test_dam_break_solutions() {
+        let g = 9.81;
+        // Ritter: still water behind the rarefaction, dry beyond the front
+        assert_eq!(dam_break_depth(1.0, 0.0, g, -20.0, 1.0), 1.0);
+        assert_eq!(dam_break_depth(1.0, 0.0, g, 7.0, 1.0), 0.0);
+        assert!((dam_break_depth(1.0, 0.0, g, 0.0, 1.0) - 4.0 / 9.0).abs() < 1e-12);
+        // Stoker: the middle state conserves mass and momentum across the bore
+        let h_middle = dam_break_depth(1.0, 0.5, g, 1.0, 1.0);
+        let u_middle = 2.0 * ((g * 1.0).sqrt() - (g * h_middle).sqrt());
+        let s = u_middle * h_middle / (h_middle - 0.5);
+        let momentum_flux = |h: f64, u: f64| h * u * u + 0.5 * g * h * h;
+        let jump = momentum_flux(h_middle, u_middle) - momentum_flux(0.5, 0.0);
+        assert!((jump - s * h_middle * u_middle).abs() < 1e-9);
+        assert!(h_middle > 0.5 && h_middle < 1.0);
+    }
+
+    #[test]
+    fn test_oblique_jump_solution() {
+        // Hager et al.: Fr 2.74 and an 8.95° wall give β = 30° and h2/h1 = 1.5
+        let (beta, ratio) = oblique_jump_solution(2.74, 8.95f64.to_radians());
+        assert!((beta.to_degrees() - 30.0).abs() < 0.05);
+        assert!((ratio - 1.5).abs() < 0.005);
+    }
+
+    #[test]
+    fn test_cases_pass_with_central_upwind() {
+        let factory = |mesh| {
+            let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+            solver.flux_scheme = FluxScheme::CentralUpwind;
+            solver
+        };
+        assert_eq!(parse_cases("all").unwrap().len(), CASES.len());
+        for case in parse_cases("lake-at-rest, ritter").unwrap() {
+            let outcome = case.run(&factory);
+            assert!(
+                outcome.passed,
+                "{}: {} = {}",
+                case.name, case.metric, outcome.value
+            );
+        }
+        assert!(parse_cases("thacker").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+/// Wall-clock time limit for batch jobs
+/// The limit counts from the start of the process, so mesh generation and
+/// setup are charged against it like in a queue allocation. A run stops
+/// before the step that would cross the limit, judged by the longest step so
+/// far; writing the final outputs and the checkpoint comes on top.
+use std::time::{Duration, Instant};
+
+/// Parse a duration such as "3h45m", "90m", "1h30m15s" or "5400" (seconds)
+pub fn parse_duration(spec: &str) -> Result<Duration, String> {
+    let spec = spec.trim();
+    let invalid = || format!("invalid duration '{}' (e.g. 3h45m, 90m, 5400s)", spec);
+    if let Ok(seconds) = spec.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|_| invalid());
+    }
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for c in spec.chars() {
+        let scale = match c {
+            'h' => 3600.0,
+            'm' => 60.0,
+            's' => 1.0,
+            _ if c.is_ascii_digit() || c == '.' => {
+                number.push(c);
+                continue;
+            }
+            _ => return Err(invalid()),
+        };
+        let value: f64 = number.parse().map_err(|_| invalid())?;
+        seconds += value * scale;
+        number.clear();
+    }
+    if !number.is_empty() || spec.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+pub struct WallClockLimit {
+    start: Instant,
+    limit: Duration,
+    last_step: Instant,
+    longest_step: Duration,
+}
+
+impl WallClockLimit {
+    pub fn new(limit: Duration) -> Self {
+        let now = Instant::now();
+        WallClockLimit {
+            start: now,
+            limit,
+            last_step: now,
+            longest_step: Duration::ZERO,
+        }
+    }
+
+    pub fn limit(&self) -> Duration {
+        self.limit
+    }
+
+    /// Start timing steps (call just before the time loop)
+    pub fn start_stepping(&mut self) {
+        self.last_step = Instant::now();
+    }
+
+    /// Record a completed step; true when another step of the same length
+    /// as the longest so far would cross the limit
+    pub fn step(&mut self) -> bool {
+        let now = Instant::now();
+        self.longest_step = self.longest_step.max(now - self.last_step);
+        self.last_step = now;
+        now - self.start + self.longest_step >= self.limit
+    }
+}
+
+/// Format a duration as "1h02m03s"
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{}h{:02}m{:02}s",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        let secs = |spec| parse_duration(spec).unwrap().as_secs_f64();
+        assert_eq!(secs("3h45m"), 13500.0);
+        assert_eq!(secs("90m"), 5400.0);
+        assert_eq!(secs("1h30m15s"), 5415.0);
+        assert_eq!(secs("5400"), 5400.0);
+        assert_eq!(secs("0.5h"), 1800.0);
+        for bad in ["", "3d", "45m3", "h", "-5"] {
+            assert!(parse_duration(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h02m03s");
+    }
+
+    #[test]
+    fn test_limit_anticipates_next_step() {
+        let mut limit = WallClockLimit::new(Duration::from_millis(200));
+        limit.start_stepping();
+        assert!(!limit.step());
+        std::thread::sleep(Duration::from_millis(120));
+        // 120 ms elapsed and a 120 ms step would end past 200 ms
+        assert!(limit.step());
+    }
+}
@@ -0,0 +1,374 @@
+/// Zarr (v2) output for lazy analysis with xarray/dask
+/// The store is a directory holding a group with one array per output field
+/// (dimensions time × cell), the output times and the mesh (cell centroids
+/// and areas, node coordinates and triangles), with the EPSG code of the
+/// mesh reference system in the `crs` attribute when known. Field arrays are chunked by
+/// `time_chunk` frames and up to `CELL_CHUNK` cells; each chunk is
+/// byte-shuffled and zlib-compressed (numcodecs `shuffle` and `zlib`), so
+/// smooth fields compress well. Every array carries `_ARRAY_DIMENSIONS` and
+/// units, and `xr.open_zarr(path)` yields a dataset with named dimensions.
+/// The chunks of the current time block are rewritten at every output time
+/// and the array shapes updated, so the store is complete after each frame
+/// and can be read while the run continues, or copied to object storage.
+use crate::compress;
+use crate::crs::Crs;
+use crate::output::{FieldData, Frame, OutputField};
+use crate::solver::ShallowWaterSolver;
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Cells per chunk along the cell dimension
+pub const CELL_CHUNK: usize = 16384;
+
+/// Writes the store of one run
+pub struct ZarrWriter {
+    root: PathBuf,
+    cells: usize,
+    time_chunk: usize,
+    fields: Vec<OutputField>,
+    arrays: Vec<(String, &'static str)>, // Time-dependent arrays (name, units)
+    frames: usize,                       // Frames written so far
+    times: Vec<f64>,                     // Times of the open time chunk
+    buffers: Vec<Vec<f64>>,              // Values of the open time chunk, frame-major
+}
+
+impl ZarrWriter {
+    /// Create the store at `path` with the mesh of `solver` in the
+    /// reference system `crs`; `time_chunk` frames go into one chunk along
+    /// the time dimension
+    pub fn create(
+        path: &str,
+        solver: &ShallowWaterSolver,
+        fields: &[OutputField],
+        time_chunk: usize,
+        title: &str,
+        crs: Option<Crs>,
+    ) -> io::Result<Self> {
+        let root = PathBuf::from(path);
+        fs::create_dir_all(&root)?;
+        let mesh = &solver.mesh;
+        let cells = mesh.triangles.len();
+        fs::write(
+            root.join(".zgroup"),
+            json!({ "zarr_format": 2 }).to_string(),
+        )?;
+        let coordinates = match mesh.coordinate_system {
+            crate::mesh::CoordinateSystem::Cartesian => "cartesian (m)",
+            crate::mesh::CoordinateSystem::Spherical { .. } => "spherical (degrees)",
+        };
+        let mut attributes = json!({
+            "title": title,
+            "source": "shallow-water-solver",
+            "coordinate_system": coordinates,
+            "gravity": solver.constants.gravity,
+            "dry_tolerance": solver.constants.dry_tolerance,
+        });
+        if let Some(crs) = crs {
+            attributes["crs"] = json!(format!("EPSG:{}", crs.epsg()));
+        }
+        fs::write(root.join(".zattrs"), attributes.to_string())?;
+
+        // Mesh
+        let centroids: Vec<(f64, f64)> = mesh.triangles.iter().map(|t| t.centroid).collect();
+        let statics: [(&str, &str, &str, Vec<f64>); 5] = [
+            ("x", "cell", "m", centroids.iter().map(|c| c.0).collect()),
+            ("y", "cell", "m", centroids.iter().map(|c| c.1).collect()),
+            (
+                "area",
+                "cell",
+                "m^2",
+                mesh.triangles.iter().map(|t| t.area).collect(),
+            ),
+            (
+                "node_x",
+                "node",
+                "m",
+                mesh.nodes.iter().map(|n| n.x).collect(),
+            ),
+            (
+                "node_y",
+                "node",
+                "m",
+                mesh.nodes.iter().map(|n| n.y).collect(),
+            ),
+        ];
+        for (name, dimension, units, values) in statics {
+            let units = if name != "area" && coordinates.starts_with("spherical") {
+                "degrees"
+            } else {
+                units
+            };
+            let chunk = values.len().clamp(1, CELL_CHUNK);
+            write_metadata(
+                &root,
+                name,
+                &[values.len()],
+                &[chunk],
+                "<f8",
+                json!("NaN"),
+                json!({ "_ARRAY_DIMENSIONS": [dimension], "units": units }),
+            )?;
+            for (k, block) in values.chunks(chunk).enumerate() {
+                let mut block = block.to_vec();
+                block.resize(chunk, f64::NAN);
+                write_chunk(&root, name, &k.to_string(), &f64_bytes(&block), 8)?;
+            }
+        }
+        let chunk = cells.clamp(1, CELL_CHUNK);
+        write_metadata(
+            &root,
+            "triangles",
+            &[cells, 3],
+            &[chunk, 3],
+            "<i4",
+            json!(-1),
+            json!({
+                "_ARRAY_DIMENSIONS": ["cell", "vertex"],
+                "long_name": "node indices of each cell, counter-clockwise",
+            }),
+        )?;
+        for (k, block) in mesh.triangles.chunks(chunk).enumerate() {
+            let mut bytes: Vec<u8> = block
+                .iter()
+                .flat_map(|t| t.nodes.iter().flat_map(|&n| (n as i32).to_le_bytes()))
+                .collect();
+            bytes.resize(chunk * 3 * 4, 0xff);
+            write_chunk(&root, "triangles", &format!("{}.0", k), &bytes, 4)?;
+        }
+
+        let arrays: Vec<(String, &'static str)> = fields
+            .iter()
+            .flat_map(|&field| {
+                let units = units(field);
+                match field {
+                    OutputField::Velocity => vec![
+                        (format!("{}_x", field.name()), units),
+                        (format!("{}_y", field.name()), units),
+                    ],
+                    _ => vec![(field.name().to_string(), units)],
+                }
+            })
+            .collect();
+        let writer = ZarrWriter {
+            root,
+            cells,
+            time_chunk: time_chunk.max(1),
+            fields: fields.to_vec(),
+            buffers: vec![Vec::new(); arrays.len()],
+            arrays,
+            frames: 0,
+            times: Vec::new(),
+        };
+        writer.write_shapes()?;
+        Ok(writer)
+    }
+
+    /// Append the selected fields of `solver` at its current time
+    pub fn write(&mut self, solver: &ShallowWaterSolver) -> io::Result<()> {
+        let frame = Frame::capture(solver, &self.fields);
+        let mut k = 0;
+        for (_, data) in &frame.arrays {
+            match data {
+                FieldData::Scalar(values) => {
+                    self.buffers[k].extend(values);
+                    k += 1;
+                }
+                FieldData::Vector(values) => {
+                    self.buffers[k].extend(values.iter().map(|v| v.0));
+                    self.buffers[k + 1].extend(values.iter().map(|v| v.1));
+                    k += 2;
+                }
+            }
+        }
+        self.times.push(frame.time);
+        self.frames += 1;
+
+        // Rewrite the chunks of the open time block, padded with NaN
+        let (block, cell_chunk) = ((self.frames - 1) / self.time_chunk, self.cell_chunk());
+        let mut times = self.times.clone();
+        times.resize(self.time_chunk, f64::NAN);
+        write_chunk(
+            &self.root,
+            "time",
+            &block.to_string(),
+            &f64_bytes(&times),
+            8,
+        )?;
+        for (k, (name, _)) in self.arrays.iter().enumerate() {
+            let buffer = &self.buffers[k];
+            for c in 0..self.cells.div_ceil(cell_chunk) {
+                let mut values = vec![f64::NAN; self.time_chunk * cell_chunk];
+                for (t, frame) in buffer.chunks(self.cells).enumerate() {
+                    let part = &frame[c * cell_chunk..((c + 1) * cell_chunk).min(self.cells)];
+                    values[t * cell_chunk..t * cell_chunk + part.len()].copy_from_slice(part);
+                }
+                let key = format!("{}.{}", block, c);
+                write_chunk(&self.root, name, &key, &f64_bytes(&values), 8)?;
+            }
+        }
+        if self.times.len() == self.time_chunk {
+            self.times.clear();
+            self.buffers.iter_mut().for_each(Vec::clear);
+        }
+        self.write_shapes()
+    }
+
+    fn cell_chunk(&self) -> usize {
+        self.cells.clamp(1, CELL_CHUNK)
+    }
+
+    /// Metadata of the time-dependent arrays for the frames written so far
+    fn write_shapes(&self) -> io::Result<()> {
+        write_metadata(
+            &self.root,
+            "time",
+            &[self.frames],
+            &[self.time_chunk],
+            "<f8",
+            json!("NaN"),
+            json!({ "_ARRAY_DIMENSIONS": ["time"], "units": "seconds since start" }),
+        )?;
+        for (name, units) in &self.arrays {
+            write_metadata(
+                &self.root,
+                name,
+                &[self.frames, self.cells],
+                &[self.time_chunk, self.cell_chunk()],
+                "<f8",
+                json!("NaN"),
+                json!({
+                    "_ARRAY_DIMENSIONS": ["time", "cell"],
+                    "units": units,
+                    "coordinates": "x y",
+                }),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Units of a field's values
+fn units(field: OutputField) -> &'static str {
+    match field {
+        OutputField::Height | OutputField::Bed | OutputField::Surface => "m",
+        OutputField::BedChange => "m",
+        OutputField::Velocity => "m/s",
+        OutputField::MomentumX | OutputField::MomentumY | OutputField::UnitDischarge => "m^2/s",
+        OutputField::Vorticity => "1/s",
+        OutputField::BedShearStress => "Pa",
+        OutputField::Scalar => "", // °C or g/kg, set by the scalar's kind
+        OutputField::FroudeNumber
+        | OutputField::Courant
+        | OutputField::SedimentConcentration
+        | OutputField::Partition
+        | OutputField::Color => "1",
+    }
+}
+
+fn write_metadata(
+    root: &Path,
+    name: &str,
+    shape: &[usize],
+    chunks: &[usize],
+    dtype: &str,
+    fill_value: Value,
+    attributes: Value,
+) -> io::Result<()> {
+    let element_size: usize = dtype[2..].parse().unwrap();
+    let metadata = json!({
+        "zarr_format": 2,
+        "shape": shape,
+        "chunks": chunks,
+        "dtype": dtype,
+        "compressor": { "id": "zlib", "level": 6 },
+        "fill_value": fill_value,
+        "order": "C",
+        "filters": [{ "id": "shuffle", "elementsize": element_size }],
+    });
+    let dir = root.join(name);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(".zarray"), metadata.to_string())?;
+    fs::write(dir.join(".zattrs"), attributes.to_string())
+}
+
+/// Shuffle and compress one chunk into `root/name/key`
+fn write_chunk(
+    root: &Path,
+    name: &str,
+    key: &str,
+    bytes: &[u8],
+    element_size: usize,
+) -> io::Result<()> {
+    fs::write(
+        root.join(name).join(key),
+        compress::zlib(&shuffle(bytes, element_size)),
+    )
+}
+
+fn f64_bytes(values: &[f64]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Group the k-th bytes of all elements together (numcodecs `shuffle`)
+fn shuffle(bytes: &[u8], element_size: usize) -> Vec<u8> {
+    let count = bytes.len() / element_size;
+    let mut out = vec![0; bytes.len()];
+    for (i, element) in bytes.chunks_exact(element_size).enumerate() {
+        for (k, &byte) in element.iter().enumerate() {
+            out[k * count + i] = byte;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress::inflate;
+    use crate::mesh::{TopographyType, TriangularMesh};
+    use crate::solver::FrictionLaw;
+
+    #[test]
+    fn test_zarr_store() {
+        let mesh = TriangularMesh::new_rectangular(4, 3, 4.0, 3.0, TopographyType::Flat);
+        let mut solver = ShallowWaterSolver::new(mesh, 0.45, FrictionLaw::None);
+        solver.set_dam_break(2.0);
+        let path = std::env::temp_dir().join(format!("swe_zarr_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let fields = [OutputField::Height, OutputField::Velocity];
+        let mut writer = ZarrWriter::create(path, &solver, &fields, 2, "dam break", None).unwrap();
+        for t in 0..3 {
+            solver.time = t as f64;
+            solver.state.h[5] = 10.0 + t as f64;
+            writer.write(&solver).unwrap();
+        }
+
+        let read = |name: &str, key: &str| {
+            let stream = fs::read(Path::new(path).join(name).join(key)).unwrap();
+            let shuffled = inflate(&stream);
+            let count = shuffled.len() / 8;
+            (0..count)
+                .map(|i| f64::from_le_bytes(std::array::from_fn(|k| shuffled[k * count + i])))
+                .collect::<Vec<f64>>()
+        };
+        let metadata: Value =
+            serde_json::from_str(&fs::read_to_string(format!("{}/height/.zarray", path)).unwrap())
+                .unwrap();
+        let cells = solver.mesh.triangles.len();
+        assert_eq!(metadata["shape"], json!([3, cells]));
+        assert_eq!(metadata["chunks"], json!([2, cells]));
+        let times = read("time", "1");
+        assert_eq!(times[0], 2.0);
+        assert!(times[1].is_nan());
+        let height = read("height", "1.0");
+        assert_eq!(height[5], 12.0);
+        assert_eq!(height[6], solver.state.h[6]);
+        assert!(height[cells..].iter().all(|h| h.is_nan()));
+        assert_eq!(read("height", "0.0")[cells + 5], 11.0);
+        assert_eq!(read("x", "0")[7], solver.mesh.triangles[7].centroid.0);
+        assert!(Path::new(path).join("velocity_y/1.0").exists());
+        fs::remove_dir_all(path).unwrap();
+    }
+}